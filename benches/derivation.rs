@@ -0,0 +1,109 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Costs of the operations performed during sub-account derivation, which
+//! are used for the selection of the key to derive from, and derivation of
+//! accounts in a deep hierarchy with and without the derivation cache.
+
+use std::str::FromStr;
+
+use bitcoin::secp256k1;
+use bitcoin::util::bip32::{ChildNumber, DerivationPath, ExtendedPrivKey};
+use criterion::{criterion_group, criterion_main, Criterion};
+use keyring::vault::{DerivationCache, Keyring};
+
+const MASTER: &str = "xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb";
+
+/// Number of accounts derived in a single batch
+const BATCH: u32 = 16;
+
+fn seckey() -> secp256k1::SecretKey {
+    secp256k1::SecretKey::from_slice(&[0xA5u8; 32]).unwrap()
+}
+
+fn keyring() -> Keyring {
+    Keyring::from_xpriv(
+        "Benchmark",
+        "",
+        None,
+        ExtendedPrivKey::from_str(MASTER).unwrap(),
+        None,
+        secp256k1::PublicKey::from_secret_key(&keyring::SECP256K1, &seckey()),
+    )
+    .unwrap()
+}
+
+fn path(index: u32) -> DerivationPath {
+    DerivationPath::from_str(&format!("m/84'/0'/0'/0/{}", index)).unwrap()
+}
+
+fn operations(c: &mut Criterion) {
+    let xpriv = ExtendedPrivKey::from_str(MASTER).unwrap();
+    c.bench_function("hardened step", |b| {
+        b.iter(|| {
+            xpriv.ckd_priv(
+                &keyring::SECP256K1,
+                ChildNumber::Hardened { index: 0 },
+            )
+        })
+    });
+    c.bench_function("normal step", |b| {
+        b.iter(|| {
+            xpriv
+                .ckd_priv(&keyring::SECP256K1, ChildNumber::Normal { index: 0 })
+        })
+    });
+    let keyring = keyring();
+    let account = keyring.account_by_id(keyring.identifier()).unwrap();
+    c.bench_function("decryption", |b| {
+        b.iter(|| account.verify_decryption_key(&mut seckey()))
+    });
+}
+
+fn batch(c: &mut Criterion) {
+    let keyring = keyring();
+    c.bench_function("batch derivation", |b| {
+        b.iter(|| {
+            for index in 0..BATCH {
+                keyring
+                    .derive_account(
+                        path(index),
+                        "",
+                        None::<String>,
+                        Default::default(),
+                        &mut seckey(),
+                    )
+                    .unwrap();
+            }
+        })
+    });
+    c.bench_function("cached batch derivation", |b| {
+        b.iter(|| {
+            let mut cache = DerivationCache::with(&mut seckey());
+            for index in 0..BATCH {
+                keyring
+                    .derive_account_cached(
+                        path(index),
+                        "",
+                        None::<String>,
+                        Default::default(),
+                        &mut cache,
+                    )
+                    .unwrap();
+            }
+        })
+    });
+}
+
+criterion_group!(benches, operations, batch);
+criterion_main!(benches);
@@ -0,0 +1,143 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Latency of signing PSBTs spending outputs of a deep account hierarchy,
+//! with a per-request signing cache and with a warm cache kept by an
+//! unlocked session.
+
+use std::str::FromStr;
+
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1;
+use bitcoin::util::bip32::{DerivationPath, ExtendedPrivKey, ExtendedPubKey};
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::{OutPoint, Script, Transaction, TxIn, TxOut, Txid};
+use criterion::{criterion_group, criterion_main, Criterion};
+use keyring::rpc::types::CollisionPolicy;
+use keyring::vault::{driver, file_driver, SigningCache, Vault};
+use microservices::FileFormat;
+use slip132::KeyApplication;
+
+const MASTER: &str = "xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb";
+
+/// Number of inputs in the signed PSBT
+const BATCH: u32 = 16;
+
+fn seckey() -> secp256k1::SecretKey {
+    secp256k1::SecretKey::from_slice(&[0xA5u8; 32]).unwrap()
+}
+
+fn vault() -> Vault {
+    let path = std::env::temp_dir()
+        .join(format!("keyring-{}-signing.vault", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut vault = Vault::with(&driver::Config::File(file_driver::Config {
+        location: path.display().to_string(),
+        format: FileFormat::StrictEncode,
+        backups: 0,
+        signed: false,
+        node_key: None,
+        read_only: false,
+    }))
+    .unwrap();
+    vault
+        .import_xpriv(
+            ExtendedPrivKey::from_str(MASTER).unwrap(),
+            None,
+            Some(KeyApplication::SegWit),
+            "Benchmark",
+            None::<String>,
+            CollisionPolicy::Reject,
+            secp256k1::PublicKey::from_secret_key(
+                &keyring::SECP256K1,
+                &seckey(),
+            ),
+        )
+        .unwrap();
+    vault
+}
+
+/// PSBT spending P2WPKH outputs of the keys `m/84'/0'/0'/0/<input index>`
+fn psbt() -> PartiallySignedTransaction {
+    let master = ExtendedPrivKey::from_str(MASTER).unwrap();
+    let fingerprint = master.fingerprint(&keyring::SECP256K1);
+    let derivations = (0..BATCH)
+        .map(|index| {
+            DerivationPath::from_str(&format!("m/84'/0'/0'/0/{}", index))
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    let tx = Transaction {
+        version: 2,
+        lock_time: 0,
+        input: (0..BATCH)
+            .map(|index| TxIn {
+                previous_output: OutPoint::new(
+                    Txid::from_inner([1u8; 32]),
+                    index,
+                ),
+                script_sig: Script::new(),
+                sequence: 0xFFFF_FFFD,
+                witness: vec![],
+            })
+            .collect(),
+        output: vec![],
+    };
+    let mut psbt = PartiallySignedTransaction::from_unsigned_tx(tx).unwrap();
+    for (inp, derivation) in psbt.inputs.iter_mut().zip(derivations) {
+        let xpriv = master
+            .derive_priv(&keyring::SECP256K1, &derivation)
+            .unwrap();
+        let pubkey = ExtendedPubKey::from_private(&keyring::SECP256K1, &xpriv)
+            .public_key;
+        inp.witness_utxo = Some(TxOut {
+            value: 10_000,
+            script_pubkey: Script::new_v0_wpkh(
+                &pubkey.wpubkey_hash().expect("derived keys are compressed"),
+            ),
+        });
+        inp.bip32_derivation
+            .insert(pubkey, (fingerprint, derivation));
+    }
+    psbt
+}
+
+fn batch(c: &mut Criterion) {
+    let mut vault = vault();
+    let psbt = psbt();
+    c.bench_function("batch signing", |b| {
+        b.iter(|| {
+            vault
+                .sign_psbt(psbt.clone(), false, &mut seckey(), &mut || ())
+                .unwrap()
+        })
+    });
+    let mut cache = SigningCache::with_capacity(BATCH as usize + 1);
+    c.bench_function("session cached batch signing", |b| {
+        b.iter(|| {
+            vault
+                .sign_psbt_cached(
+                    psbt.clone(),
+                    false,
+                    &mut seckey(),
+                    &mut cache,
+                    &mut || (),
+                )
+                .unwrap()
+        })
+    });
+    assert_eq!(cache.decryptions(), 1);
+}
+
+criterion_group!(benches, batch);
+criterion_main!(benches);
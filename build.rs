@@ -24,6 +24,43 @@ pub mod cli {
     include!("src/cli/opts.rs");
 }
 
+// Command-line options refer to the library types, which are parsed from
+// strings; for shell completions the types can be replaced with strings
+pub mod lifecycle {
+    pub type Lifecycle = String;
+}
+
+pub mod derivation {
+    use bitcoin::util::bip32::{self, DerivationPath};
+
+    pub fn parse(s: &str) -> Result<DerivationPath, bip32::Error> {
+        s.parse()
+    }
+}
+
+pub mod rpc {
+    pub mod types {
+        pub type Bip85Application = String;
+        pub type CollisionPolicy = String;
+        pub type CosignerKey = String;
+        pub type DerivationTemplate = String;
+        pub type KeyPrefix = String;
+        pub type LabelQuery = String;
+        pub type MuSigNonce = String;
+        pub type MuSigSessionId = bitcoin::hashes::sha256::Hash;
+        pub type MultisigId = bitcoin::hashes::sha256::Hash;
+        pub type PaymentCode = String;
+        pub type PsbtInput = String;
+        pub type PsbtOutput = String;
+        pub type RateLimit = String;
+        pub type SessionToken = bitcoin::hashes::sha256::Hash;
+        pub type UpdateMode = String;
+        pub type VaultId = String;
+        pub type LnChannelId = bitcoin::hashes::sha256::Hash;
+        pub type CommitmentSecret = bitcoin::hashes::sha256::Hash;
+    }
+}
+
 pub mod keyringd {
     include!("src/daemon/opts.rs");
 }
@@ -38,5 +75,9 @@ fn main() -> Result<(), configure_me_codegen::Error> {
         generate_to::<Zsh, _, _>(app, &name, &outdir);
     }
 
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("api/keyring.proto")
+        .expect("gRPC service definition must be valid");
+
     configure_me_codegen::build_script_auto()
 }
@@ -0,0 +1,106 @@
+/*
+ * Keyring: private/public key managing service
+ * Written in 2020 by
+ *     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+ *
+ * To the extent possible under law, the author(s) have dedicated all
+ * copyright and related and neighboring rights to this software to
+ * the public domain worldwide. This software is distributed without
+ * any warranty.
+ *
+ * You should have received a copy of the AGPL License
+ * along with this software.
+ * If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+ */
+
+/*
+ * C interface of the embedded vault, provided by the keyring library built
+ * with `ffi` feature. See `src/ffi.rs` for the documentation.
+ */
+
+#ifndef KEYRING_H
+#define KEYRING_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef enum {
+    KEYRING_OK = 0,
+    KEYRING_INVALID_ARGUMENT = 1,
+    KEYRING_BOOTSTRAP = 2,
+    KEYRING_VAULT_LOCKED = 3,
+    KEYRING_UNKNOWN_SESSION = 4,
+    KEYRING_PASSPHRASE = 5,
+    KEYRING_READ_ONLY = 6,
+    KEYRING_POLICY_VIOLATION = 7,
+    KEYRING_WATCH_ONLY = 8,
+    KEYRING_FAILURE = 9,
+    KEYRING_PANIC = 10,
+} KeyringError;
+
+/* Opaque handle of the opened vault */
+typedef struct Keyring Keyring;
+
+/* Storage callbacks of the delegated vault driver */
+typedef int (*KeyringLoadCallback)(const unsigned char *xpubkey,
+                                   unsigned char *xprivkey);
+typedef int (*KeyringSaveCallback)(const unsigned char *xpubkey,
+                                   unsigned char *xprivkey);
+
+typedef struct {
+    const char *data_dir;
+    uint8_t node_key[32];
+    bool passphrase;
+    uint64_t unlock_timeout;
+    bool read_only;
+    KeyringLoadCallback load_cb;
+    KeyringSaveCallback save_cb;
+} KeyringConfig;
+
+typedef struct {
+    uint8_t token[32];
+    uint64_t expires_in;
+} KeyringSession;
+
+typedef struct {
+    uint8_t id[20];
+    uint8_t key_id[20];
+    uint8_t fingerprint[4];
+    char *name;
+    char *details;
+    bool watch_only;
+} KeyringAccount;
+
+typedef struct {
+    KeyringAccount *accounts;
+    size_t len;
+} KeyringAccountList;
+
+KeyringError keyring_open(const KeyringConfig *config, Keyring **handle);
+void keyring_close(Keyring *handle);
+
+KeyringError keyring_unlock(const Keyring *handle, const char *passphrase,
+                            KeyringSession *session);
+KeyringError keyring_lock(const Keyring *handle,
+                          const KeyringSession *session);
+
+KeyringError keyring_list(const Keyring *handle, KeyringAccountList *list);
+KeyringError keyring_sign_psbt(const Keyring *handle, const char *psbt,
+                               const KeyringSession *session,
+                               char **signed_psbt);
+
+const char *keyring_last_error(void);
+
+void keyring_free_accounts(KeyringAccountList *list);
+void keyring_free_string(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* KEYRING_H */
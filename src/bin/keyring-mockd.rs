@@ -0,0 +1,55 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![recursion_limit = "256"]
+// Coding conventions
+#![deny(
+    non_upper_case_globals,
+    non_camel_case_types,
+    non_snake_case,
+    unused_mut,
+    unused_imports,
+    dead_code,
+    missing_docs
+)]
+
+//! Mock keyring daemon with deterministic keys for client testing
+
+#[macro_use]
+extern crate log;
+
+use clap::Clap;
+use internet2::zmqsocket::ZmqSocketAddr;
+use std::convert::TryInto;
+
+use keyring::{mock, Opts};
+
+fn main() {
+    println!("keyring-mockd: mock key management daemon for testing");
+
+    let mut opts = Opts::parse();
+    trace!("Command-line arguments: {:?}", &opts);
+    opts.process();
+    trace!("Processed arguments: {:?}", &opts);
+
+    let endpoint: ZmqSocketAddr = opts
+        .rpc_socket
+        .try_into()
+        .expect("Only ZMQ RPC is supported");
+    debug!("RPC socket {}", &endpoint);
+
+    debug!("Starting mock runtime ...");
+    mock::run(endpoint).expect("Error running keyring-mockd runtime");
+
+    unreachable!()
+}
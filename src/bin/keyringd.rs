@@ -29,22 +29,40 @@
 extern crate log;
 
 use clap::Clap;
+use microservices::shell::LogLevel;
 use std::convert::TryInto;
+use std::process::exit;
 
-use keyring::daemon::{self, Config, Opts};
+use keyring::daemon::{self, logging, Config, Opts};
 
 fn main() {
     println!("keyringd: key management daemon");
 
     let mut opts = Opts::parse();
+    logging::init(LogLevel::from_verbosity_flag_count(opts.shared.verbose));
     trace!("Command-line arguments: {:?}", &opts);
     opts.process();
     trace!("Processed arguments: {:?}", &opts);
 
     let config: Config = opts.clone().try_into().expect("Wrong configuration");
+    logging::set_level(config.log_level);
+    logging::set_format(config.log_format);
     trace!("Daemon configuration: {:?}", &config);
     debug!("RPC socket {}", &config.endpoint);
 
+    if opts.check {
+        match daemon::check(&config) {
+            Ok(_) => {
+                println!("Configuration, vault and RPC socket are valid");
+                exit(0);
+            }
+            Err(err) => {
+                eprintln!("Check failed: {}", err);
+                exit(1);
+            }
+        }
+    }
+
     /*
     use self::internal::ResultExt;
     let (config_from_file, _) =
@@ -56,6 +74,4 @@ fn main() {
 
     debug!("Starting runtime ...");
     daemon::run(config).expect("Error running keyringd runtime");
-
-    unreachable!()
 }
@@ -0,0 +1,65 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Electrum server blockchain data source
+
+use bitcoin::Script;
+use electrum_client::{Client, ElectrumApi};
+
+use super::{ChainSource, Error, ScriptStats};
+
+impl From<electrum_client::Error> for Error {
+    fn from(err: electrum_client::Error) -> Self {
+        Error::Source(format!("{:?}", err))
+    }
+}
+
+/// Blockchain data source backed by an Electrum server
+pub struct ElectrumSource {
+    client: Client,
+}
+
+impl ElectrumSource {
+    /// Connects to the Electrum server with a given URL
+    pub fn with(server: &str) -> Result<Self, Error> {
+        info!("Connecting to Electrum server at {}", server);
+        Ok(Self {
+            client: Client::new(server)?,
+        })
+    }
+}
+
+impl ChainSource for ElectrumSource {
+    fn script_stats(&self, script: &Script) -> Result<ScriptStats, Error> {
+        let history = self.client.script_get_history(script)?;
+        if history.is_empty() {
+            return Ok(ScriptStats::default());
+        }
+        let mut received = 0u64;
+        for item in &history {
+            let tx = self.client.transaction_get(&item.tx_hash)?;
+            received += tx
+                .output
+                .iter()
+                .filter(|out| &out.script_pubkey == script)
+                .map(|out| out.value)
+                .sum::<u64>();
+        }
+        let balance = self.client.script_get_balance(script)?;
+        Ok(ScriptStats {
+            tx_count: history.len() as u32,
+            received,
+            balance: (balance.confirmed as i64 + balance.unconfirmed) as u64,
+        })
+    }
+}
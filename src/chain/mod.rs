@@ -0,0 +1,178 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! External blockchain data sources (indexers) used by the daemon to enrich
+//! vault data with on-chain information, like account balances
+
+#[cfg(feature = "electrum")]
+mod electrum;
+
+use bitcoin::util::bip32::{ChildNumber, ExtendedPubKey};
+use bitcoin::Script;
+use slip132::KeyApplication;
+
+use crate::error::BootstrapError;
+use crate::rpc::types::Branches;
+
+#[cfg(feature = "electrum")]
+pub use electrum::ElectrumSource;
+
+/// Default number of consecutive unused addresses after which address scan
+/// for a given derivation branch stops
+pub const DEFAULT_GAP_LIMIT: u32 = 20;
+
+/// Derivation purposes (BIP-43) of single-key applications scanned during
+/// the account discovery, in the order of the scan
+pub const DISCOVERY_PURPOSES: [(u32, KeyApplication); 3] = [
+    (44, KeyApplication::Hashed),
+    (49, KeyApplication::Nested),
+    (84, KeyApplication::SegWit),
+];
+
+/// Configuration of the blockchain data source
+#[derive(Clone, PartialEq, Eq, Debug, Display, Serialize, Deserialize)]
+#[serde(crate = "serde_crate", tag = "source")]
+#[display(Debug)]
+#[non_exhaustive]
+pub enum Config {
+    /// Electrum server accessible via `tcp://` or `ssl://` URL
+    Electrum { server: String },
+}
+
+/// Error cases related to the blockchain data source operations
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum Error {
+    /// Blockchain data source returned error: {0}
+    Source(String),
+
+    /// Blockchain data source of this type is not supported by the current
+    /// build; please re-compile with `{0}` feature
+    NotCompiled(&'static str),
+
+    /// Key application {0:?} can't be used for address scanning since it
+    /// requires multiple keys to construct an address
+    MultisigApplication(KeyApplication),
+
+    /// Extended public key contains uncompressed public key, which can't be
+    /// used with SegWit applications
+    UncompressedKey,
+
+    /// Address derivation failed for a non-hardened index {0}
+    Derivation(u32),
+}
+
+/// Information on the use of a single script pubkey
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct ScriptStats {
+    /// Number of transactions in which the script was used
+    pub tx_count: u32,
+
+    /// Total amount received to the script, in satoshis
+    pub received: u64,
+
+    /// Current balance (sum of unspent outputs) of the script, in satoshis
+    pub balance: u64,
+}
+
+/// Source of blockchain information, usually some form of indexer
+pub trait ChainSource: Send {
+    /// Returns usage statistics for a given script pubkey
+    fn script_stats(&self, script: &Script) -> Result<ScriptStats, Error>;
+}
+
+/// Constructs blockchain data source from its configuration
+pub fn source_with(
+    config: &Config,
+) -> Result<Box<dyn ChainSource>, BootstrapError> {
+    match config {
+        #[cfg(feature = "electrum")]
+        Config::Electrum { server } => {
+            Ok(Box::new(ElectrumSource::with(server)?) as Box<dyn ChainSource>)
+        }
+        #[cfg(not(feature = "electrum"))]
+        Config::Electrum { .. } => {
+            Err(BootstrapError::ChainSource(Error::NotCompiled("electrum")))
+        }
+    }
+}
+
+/// Constructs script pubkey for a single-key application from the provided
+/// public key
+pub fn script_pubkey(
+    pubkey: &bitcoin::PublicKey,
+    application: KeyApplication,
+) -> Result<Script, Error> {
+    Ok(match application {
+        KeyApplication::Hashed => Script::new_p2pkh(&pubkey.pubkey_hash()),
+        KeyApplication::SegWit => Script::new_v0_wpkh(
+            &pubkey.wpubkey_hash().ok_or(Error::UncompressedKey)?,
+        ),
+        KeyApplication::Nested => Script::new_p2sh(
+            &Script::new_v0_wpkh(
+                &pubkey.wpubkey_hash().ok_or(Error::UncompressedKey)?,
+            )
+            .script_hash(),
+        ),
+        other => Err(Error::MultisigApplication(other))?,
+    })
+}
+
+/// Scans external and internal derivation `branches` of an account extended
+/// public key, until gap limit of consecutive unused addresses is reached for
+/// each of the branches, and returns aggregated statistics. The gap limit
+/// configured for the branches takes precedence over `gap_limit` argument.
+pub fn scan_account(
+    source: &dyn ChainSource,
+    xpubkey: &ExtendedPubKey,
+    application: KeyApplication,
+    branches: &Branches,
+    gap_limit: u32,
+) -> Result<ScriptStats, Error> {
+    let gap_limit = branches.gap_limit.unwrap_or(gap_limit);
+    let mut total = ScriptStats::default();
+    for branch in &[branches.external, branches.internal] {
+        let mut gap = 0u32;
+        let mut index = 0u32;
+        while gap < gap_limit {
+            let path = [
+                ChildNumber::from_normal_idx(*branch)
+                    .map_err(|_| Error::Derivation(*branch))?,
+                ChildNumber::from_normal_idx(index)
+                    .map_err(|_| Error::Derivation(index))?,
+            ];
+            let pubkey = xpubkey
+                .derive_pub(&crate::SECP256K1, &path)
+                .map_err(|_| Error::Derivation(index))?
+                .public_key;
+            let script = script_pubkey(&pubkey, application)?;
+            let stats = source.script_stats(&script)?;
+            if stats.tx_count == 0 {
+                gap += 1;
+            } else {
+                gap = 0;
+                total.tx_count += stats.tx_count;
+                total.received += stats.received;
+                total.balance += stats.balance;
+            }
+            index += 1;
+        }
+        trace!(
+            "Scanned {} addresses on branch {} of {}",
+            index,
+            branch,
+            xpubkey
+        );
+    }
+    Ok(total)
+}
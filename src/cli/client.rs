@@ -11,26 +11,54 @@
 // along with this software.
 // If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
 
-use internet2::zmqsocket::{self, ZmqType};
+use std::thread;
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::rand::{thread_rng, RngCore};
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use internet2::session::noise::{HandshakeState, IHandshakeState};
+use internet2::zmqsocket::{self, ZmqSocketAddr, ZmqType};
 use internet2::{
-    session, CreateUnmarshaller, PlainTranscoder, Session, TypedEnum,
-    Unmarshall, Unmarshaller,
+    session, CreateUnmarshaller, Decrypt, Encrypt, NoiseTranscoder,
+    PlainTranscoder, Session, TypedEnum, Unmarshall, Unmarshaller,
 };
+use zeroize::Zeroizing;
 
-use super::Config;
+use super::progress::{ProgressBar, POLL_INTERVAL};
+use super::{Config, OutputFormat, SecretSource};
 use crate::error::BootstrapError;
-use crate::rpc::{self, Reply, Request};
+use crate::rpc::auth::NonceGenerator;
+use crate::rpc::transport::{self, ChannelId};
+use crate::rpc::{self, routed, sealed, tagged, types, Reply, Request};
 
 #[repr(C)]
 pub struct Client {
     config: Config,
     session_rpc: session::Raw<PlainTranscoder, zmqsocket::Connection>,
+    channel: Option<(ChannelId, NoiseTranscoder)>,
     unmarshaller: Unmarshaller<Reply>,
+    nonces: NonceGenerator,
+    daemon: Option<types::Hello>,
+    /// Decryption key read from the `vault_key` source on its first use
+    decryption_key: Option<Zeroizing<[u8; 32]>>,
 }
 
 impl Client {
+    /// Decryption key of the requests using private keys built before the
+    /// key is known; [`Client::request`] replaces it with
+    /// [`Client::decryption_key`] or with a vault session
+    pub const DECRYPTION_KEY_PLACEHOLDER: SecretKey =
+        bitcoin::secp256k1::key::ONE_KEY;
+
+    /// Connects to the daemon over TCP or IPC endpoint. In-process endpoints
+    /// are reachable only with [`Client::inproc`].
     pub fn with(config: Config) -> Result<Self, BootstrapError> {
         debug!("Initializing runtime");
+        if let ZmqSocketAddr::Inproc(_) = config.endpoint {
+            return Err(BootstrapError::InprocEndpoint(
+                config.endpoint.to_string(),
+            ));
+        }
         trace!("Connecting to keyring daemon at {}", config.endpoint);
         let session_rpc = session::Raw::with_zmq_unencrypted(
             ZmqType::Req,
@@ -38,38 +66,515 @@ impl Client {
             None,
             None,
         )?;
-        Ok(Self {
+        Self::with_session(config, session_rpc)
+    }
+
+    /// Connects to the daemon runtime embedded into the same application
+    /// over in-process endpoint, using the ZMQ `context` of the runtime
+    /// (see `Runtime::context`). No network ports or socket files are used.
+    #[cfg(feature = "embedded")]
+    pub fn inproc(
+        config: Config,
+        context: &zmq::Context,
+    ) -> Result<Self, BootstrapError> {
+        debug!("Initializing runtime");
+        trace!(
+            "Connecting to embedded keyring daemon at {}",
+            config.endpoint
+        );
+        let socket = context.socket(zmq::REQ)?;
+        socket.connect(&config.endpoint.zmq_socket_string())?;
+        let session_rpc =
+            session::Raw::from_zmq_socket_unencrypted(ZmqType::Req, socket);
+        Self::with_session(config, session_rpc)
+    }
+
+    fn with_session(
+        config: Config,
+        session_rpc: session::Raw<PlainTranscoder, zmqsocket::Connection>,
+    ) -> Result<Self, BootstrapError> {
+        let mut client = Self {
             config,
             session_rpc,
+            channel: None,
             unmarshaller: Reply::create_unmarshaller(),
-        })
+            nonces: NonceGenerator::new(),
+            daemon: None,
+            decryption_key: None,
+        };
+        if client.config.is_encrypted() {
+            client.handshake()?;
+        }
+        client.hello().map_err(BootstrapError::Negotiation)?;
+        Ok(client)
+    }
+
+    /// Protocol versions and features announced by the daemon, unless the
+    /// daemon predates protocol negotiation
+    pub fn daemon(&self) -> Option<&types::Hello> {
+        self.daemon.as_ref()
+    }
+
+    /// Token of the unlocked vault session used by the requests requiring
+    /// private keys
+    pub fn session(&self) -> Option<types::SessionToken> {
+        self.config.session
+    }
+
+    /// Sets the vault session used by the requests requiring private keys;
+    /// without a session the requests carry the decryption key
+    pub fn set_session(&mut self, session: Option<types::SessionToken>) {
+        self.config.session = session;
+    }
+
+    /// Detects whether the daemon vault is encrypted with a passphrase, so
+    /// the requests using private keys require a vault session
+    pub fn is_passphrase_encrypted(&self) -> bool {
+        self.daemon
+            .as_ref()
+            .map(|hello| hello.features.contains(types::Feature::Passphrase))
+            .unwrap_or_default()
+    }
+
+    /// Key decrypting private keys of the vault encrypted with the daemon
+    /// node key, used by the requests when no vault session is given. The
+    /// key is read from the `vault_key` source on its first use; without
+    /// the source the node key is used.
+    pub fn decryption_key(&mut self) -> Result<SecretKey, rpc::Error> {
+        let source = match self.config.vault_key {
+            Some(ref source) => source,
+            None => return Ok(self.config.node_key),
+        };
+        if self.decryption_key.is_none() {
+            let key = source.read_key("Vault decryption key")?;
+            let mut secret = Zeroizing::new([0u8; 32]);
+            secret.copy_from_slice(&key[..]);
+            self.decryption_key = Some(secret);
+        }
+        let secret = self.decryption_key.as_ref().expect("key is read");
+        Ok(SecretKey::from_slice(&secret[..])
+            .expect("key is validated when read"))
+    }
+
+    /// Unlocks the vault encrypted with a passphrase read from the
+    /// `passphrase` source, returning the new vault session. If the source
+    /// is absent, the passphrase is taken from the environment or prompted
+    /// for (see [`SecretSource::resolve`]). The passphrase of the vault
+    /// holding no private keys is confirmed by asking for it again, unless
+    /// it is read from a non-interactive source.
+    pub fn unlock(
+        &mut self,
+        passphrase: &Option<SecretSource>,
+    ) -> Result<types::Session, rpc::Error> {
+        let source = passphrase
+            .clone()
+            .or_else(|| SecretSource::from_env("KEYRING_PASSPHRASE"))
+            .unwrap_or(SecretSource::Prompt);
+        let passphrase = Zeroizing::new(source.read("Vault passphrase")?);
+        debug!("Unlocking the vault");
+        let reply = match self.request_unlock(&passphrase)? {
+            Reply::Failure(failure)
+                if failure.code
+                    == rpc::FailureCode::PassphraseConfirmation as u16 =>
+            {
+                debug!(
+                    "Confirming passphrase of the vault without private keys"
+                );
+                match source {
+                    SecretSource::Prompt => {
+                        let confirmation = Zeroizing::new(
+                            source.read("Confirm vault passphrase")?,
+                        );
+                        self.request_unlock(&confirmation)?
+                    }
+                    _ => self.request_unlock(&passphrase)?,
+                }
+            }
+            reply => reply,
+        };
+        match reply {
+            Reply::Session(session) => {
+                info!("Vault is unlocked");
+                Ok(session)
+            }
+            Reply::Failure(failure) => Err(rpc::Error::ServerFailure(failure)),
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+
+    fn request_unlock(
+        &mut self,
+        passphrase: &str,
+    ) -> Result<Reply, rpc::Error> {
+        self.request(Request::Unlock(rpc::message::Unlock {
+            passphrase: passphrase.to_owned(),
+            decryption_key: Self::DECRYPTION_KEY_PLACEHOLDER,
+            auth_code: types::AuthCode::default(),
+        }))
+    }
+
+    /// Format of the command results selected with `--output` option
+    pub fn output(&self) -> OutputFormat {
+        self.config.output
+    }
+
+    /// Negotiates RPC protocol version and features with the daemon, so
+    /// incompatible message layouts are detected before any other request
+    /// is sent. Daemons predating the negotiation reply with a failure to
+    /// an unsupported request and are assumed to be compatible.
+    fn hello(&mut self) -> Result<(), rpc::Error> {
+        let mut features = types::Features::default();
+        if self.config.payload_encryption {
+            features.insert(types::Feature::Sealing);
+        }
+        let request = Request::Hello(rpc::message::Hello {
+            client_version: rpc::PROTOCOL_VERSION,
+            features,
+        });
+        match self.send(request)? {
+            Reply::Hello(hello) => {
+                debug!("Daemon implements protocol {}", hello);
+                if hello.server_version < rpc::MIN_PROTOCOL_VERSION {
+                    return Err(rpc::Error::IncompatibleProtocol(
+                        hello.server_version,
+                        rpc::MIN_PROTOCOL_VERSION,
+                        rpc::PROTOCOL_VERSION,
+                    ));
+                }
+                if let Some(feature) =
+                    features.iter().find(|f| !hello.features.contains(*f))
+                {
+                    return Err(rpc::Error::FeatureUnsupported(feature));
+                }
+                self.daemon = Some(hello);
+                Ok(())
+            }
+            Reply::Failure(failure)
+                if failure.code == rpc::UNSUPPORTED_REQUEST_FAILURE_CODE =>
+            {
+                warn!("Daemon does not support protocol negotiation");
+                Ok(())
+            }
+            Reply::Failure(failure) => Err(rpc::Error::ServerFailure(failure)),
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+
+    /// Daemon node id used for the encrypted channel
+    fn daemon_id(&self) -> Result<PublicKey, rpc::Error> {
+        self.config.daemon_id.ok_or(rpc::Error::DaemonIdRequired)
     }
 
-    pub fn request(
+    /// Establishes encrypted channel with the daemon using Noise_XK
+    /// handshake, where the client node key is used as the initiator static
+    /// key
+    fn handshake(&mut self) -> Result<(), rpc::Error> {
+        let mut random = [0u8; 32];
+        thread_rng().fill_bytes(&mut random);
+        let channel = sha256::Hash::from_inner(random);
+        let daemon_id = self.daemon_id()?;
+        debug!("Establishing encrypted channel with daemon {}", daemon_id);
+
+        let ephemeral_key = SecretKey::new(&mut thread_rng());
+        let mut handshake = HandshakeState::new_initiator(
+            &self.config.node_key,
+            &daemon_id,
+            &ephemeral_key,
+        );
+        let mut input = vec![];
+        loop {
+            let (act, next) = handshake
+                .next(&input)
+                .map_err(|err| rpc::Error::Handshake(err.to_string()))?;
+            handshake = next;
+            let act = match act {
+                Some(act) => act,
+                None => break,
+            };
+            trace!("Sending handshake act ({} bytes)", act.len());
+            self.session_rpc
+                .send_raw_message(&transport::encode_frame(channel, &act))?;
+            let raw = self.session_rpc.recv_raw_message()?;
+            input = self.decode_frame(channel, &raw)?;
+            if let HandshakeState::Complete(_) = handshake {
+                break;
+            }
+        }
+        match handshake {
+            HandshakeState::Complete(Some((transcoder, _))) => {
+                debug!("Encrypted channel {} is established", channel);
+                self.channel = Some((channel, transcoder));
+                Ok(())
+            }
+            _ => Err(rpc::Error::Handshake(s!("handshake is not complete"))),
+        }
+    }
+
+    /// Extracts payload of the encrypted channel frame. Failures are
+    /// returned by the daemon without encryption, so they are parsed as
+    /// plaintext replies.
+    fn decode_frame(
+        &self,
+        channel: ChannelId,
+        raw: &[u8],
+    ) -> Result<Vec<u8>, rpc::Error> {
+        match transport::decode_frame(raw) {
+            Some((id, payload)) if id == channel => Ok(payload.to_vec()),
+            Some(_) => Err(rpc::Error::UnexpectedServerResponse),
+            None => match &*self.unmarshaller.unmarshall(raw)? {
+                Reply::Failure(failure) => {
+                    Err(rpc::Error::ServerFailure(failure.clone()))
+                }
+                _ => Err(rpc::Error::UnexpectedServerResponse),
+            },
+        }
+    }
+
+    pub fn request(&mut self, request: Request) -> Result<Reply, rpc::Error> {
+        self.request_with(request, None)
+    }
+
+    /// Sends the `request` tagged with the `request_id`, checking that the
+    /// daemon reply carries the same id; see [`rpc::tagged`]
+    pub fn request_tagged(
+        &mut self,
+        request: Request,
+        request_id: types::RequestId,
+    ) -> Result<Reply, rpc::Error> {
+        match self.request_with(request, Some(request_id))? {
+            Reply::Tagged(tagged) if tagged.request_id == request_id => {
+                match tagged::open_reply(&tagged)? {
+                    Reply::Sealed(data) => {
+                        Ok(sealed::open_reply(&data, &self.config.node_key)?)
+                    }
+                    reply => Ok(reply),
+                }
+            }
+            // Requests failing authorization are not opened by the daemon
+            Reply::Failure(failure) => Err(rpc::Error::ServerFailure(failure)),
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+
+    fn request_with(
         &mut self,
         mut request: Request,
+        request_id: Option<types::RequestId>,
     ) -> Result<Reply, rpc::Error> {
-        // Inserting decryption key if needed
-        if let Some(decryption_key) = match request {
-            Request::ExportXpriv(ref mut req) => Some(&mut req.decryption_key),
-            Request::Derive(ref mut req) => Some(&mut req.decryption_key),
-            Request::SignPsbt(ref mut req) => Some(&mut req.decryption_key),
-            Request::SignKey(ref mut req) => Some(&mut req.decryption_key),
-            Request::SignData(ref mut req) => Some(&mut req.decryption_key),
+        // Inserting unlocked session token or decryption key if needed
+        if let Some((decryption_key, session)) = match request {
+            Request::ExportXpriv(ref mut req) => {
+                Some((&mut req.decryption_key, &mut req.session))
+            }
+            Request::IdentityKey(ref mut req) => {
+                Some((&mut req.decryption_key, &mut req.session))
+            }
+            Request::DeriveEntropy(ref mut req) => {
+                Some((&mut req.decryption_key, &mut req.session))
+            }
+            Request::Derive(ref mut req) => {
+                Some((&mut req.decryption_key, &mut req.session))
+            }
+            Request::Discover(ref mut req) => {
+                Some((&mut req.decryption_key, &mut req.session))
+            }
+            Request::DeleteKeyring(ref mut req) => {
+                Some((&mut req.decryption_key, &mut req.session))
+            }
+            Request::DeleteAccount(ref mut req) => {
+                Some((&mut req.decryption_key, &mut req.session))
+            }
+            Request::SignPsbt(ref mut req) => {
+                Some((&mut req.decryption_key, &mut req.session))
+            }
+            Request::SignKey(ref mut req) => {
+                Some((&mut req.decryption_key, &mut req.session))
+            }
+            Request::SignData(ref mut req) => {
+                Some((&mut req.decryption_key, &mut req.session))
+            }
+            Request::SignIdentity(ref mut req) => {
+                Some((&mut req.decryption_key, &mut req.session))
+            }
+            Request::SignMessage(ref mut req) => {
+                Some((&mut req.decryption_key, &mut req.session))
+            }
+            Request::ExportDescriptor(ref mut req) => {
+                Some((&mut req.decryption_key, &mut req.session))
+            }
+            Request::DerivePaymentCode(ref mut req) => {
+                Some((&mut req.decryption_key, &mut req.session))
+            }
+            Request::MuSigPartialSign(ref mut req) => {
+                Some((&mut req.decryption_key, &mut req.session))
+            }
+            Request::Ecdh(ref mut req) => {
+                Some((&mut req.decryption_key, &mut req.session))
+            }
+            Request::SignInvoice(ref mut req) => {
+                Some((&mut req.decryption_key, &mut req.session))
+            }
+            Request::SignChannelAnnouncement(ref mut req) => {
+                Some((&mut req.decryption_key, &mut req.session))
+            }
+            Request::SignGossip(ref mut req) => {
+                Some((&mut req.decryption_key, &mut req.session))
+            }
             _ => None,
         } {
-            *decryption_key = self.config.node_key;
+            // The daemon accepts only sessions for the vault encrypted with a
+            // passphrase, so the vault is unlocked for the first request
+            // using private keys and the session is used by the next ones
+            if session.is_none() && self.config.session.is_none() {
+                if self.is_passphrase_encrypted() {
+                    let passphrase = self.config.vault_passphrase.clone();
+                    self.config.session = Some(self.unlock(&passphrase)?.token);
+                } else {
+                    *decryption_key = self.decryption_key()?;
+                }
+            }
+            if session.is_none() {
+                *session = self.config.session;
+            }
+        } else if let Request::Unlock(ref mut req) = request {
+            if !self.is_passphrase_encrypted() {
+                req.decryption_key = self.decryption_key()?;
+            }
+        } else if let Request::ImportVault(ref mut req) = request {
+            req.session = req.session.or(self.config.session);
+        }
+
+        if self.config.payload_encryption && request.has_secrets() {
+            trace!("Sealing {} request to the daemon", request.name());
+            request = sealed::seal(
+                &request,
+                self.daemon_id()?,
+                self.config.node_id(),
+            )?;
+        }
+        if let Some(ref vault_id) = self.config.vault {
+            request = routed::route(&request, vault_id.clone())?;
         }
+        if let Some(request_id) = request_id {
+            request = tagged::tag(&request, request_id)?;
+        }
+
+        let auth = self
+            .config
+            .auth_client
+            .clone()
+            .zip(self.config.auth_secret.clone());
+        if let (Some((client, secret)), true) =
+            (auth, request.auth_code_mut().is_some())
+        {
+            if self.config.timestamp_auth {
+                let timestamp =
+                    self.nonces.authorize(&mut request, &client, &secret);
+                trace!("Request authorized with timestamp {}", timestamp);
+                return self.send(request);
+            }
+            trace!("Requesting authorization challenge");
+            let challenge =
+                match self.send(Request::Challenge(client.clone()))? {
+                    Reply::Challenge(challenge) => challenge,
+                    Reply::Failure(failure) => {
+                        return Err(rpc::Error::ServerFailure(failure))
+                    }
+                    _ => return Err(rpc::Error::UnexpectedServerResponse),
+                };
+            request.authorize(
+                &secret,
+                types::AuthCode::with_challenge(&client, challenge),
+            );
+        }
+
+        self.send(request)
+    }
+
+    /// Sends the `request` tagging it with a new job id, if the request is
+    /// a long-running one, and polls status of the job drawing its progress
+    /// until the reply to the request is received; see [`super::progress`]
+    pub fn request_tracked(
+        &mut self,
+        mut request: Request,
+    ) -> Result<Reply, rpc::Error> {
+        let job = match request.job_mut() {
+            Some(job) => {
+                let mut random = [0u8; 32];
+                thread_rng().fill_bytes(&mut random);
+                *job.get_or_insert(sha256::Hash::from_inner(random))
+            }
+            None => return self.request(request),
+        };
+        let mut progress = ProgressBar::start();
+        let mut reply = self.request(request)?;
+        while let Reply::JobProgress(ref state) = reply {
+            if state.job != job {
+                return Err(rpc::Error::UnexpectedServerResponse);
+            }
+            progress.update(state);
+            thread::sleep(POLL_INTERVAL);
+            reply = self
+                .request(Request::JobStatus(rpc::message::JobStatus { job }))?;
+        }
+        progress.finish();
+        Ok(reply)
+    }
+
+    /// Requests platform quote from the daemon running inside a trusted
+    /// execution environment, checking that the quote report data commit to
+    /// the daemon node id and a fresh nonce. The quote must be verified
+    /// with the platform vendor before the daemon is trusted.
+    pub fn attest(&mut self) -> Result<types::Attestation, rpc::Error> {
+        let mut random = [0u8; 32];
+        thread_rng().fill_bytes(&mut random);
+        let nonce = sha256::Hash::from_inner(random);
+        let request = Request::Attest(rpc::message::Attest { nonce });
+        match self.request(request)? {
+            Reply::Attestation(attestation) => {
+                if !attestation.is_bound(&self.daemon_id()?, nonce) {
+                    return Err(rpc::Error::AttestationBinding);
+                }
+                Ok(attestation)
+            }
+            Reply::Failure(failure) => Err(rpc::Error::ServerFailure(failure)),
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
 
+    fn send(&mut self, request: Request) -> Result<Reply, rpc::Error> {
         trace!("Sending request to the server: {:?}", request);
         let data = request.serialize();
         trace!("Raw request data ({} bytes): {:?}", data.len(), data);
-        self.session_rpc.send_raw_message(&data)?;
-        trace!("Awaiting reply");
-        let raw = self.session_rpc.recv_raw_message()?;
+        let raw = match self.channel.take() {
+            Some((channel, mut transcoder)) => {
+                let frame =
+                    transport::encode_frame(channel, &transcoder.encrypt(data));
+                let result = self.exchange(&frame).and_then(|raw| {
+                    let payload = self.decode_frame(channel, &raw)?;
+                    transcoder
+                        .decrypt(payload)
+                        .map_err(|_| rpc::Error::Decryption)
+                });
+                self.channel = Some((channel, transcoder));
+                result?
+            }
+            None => self.exchange(&data)?,
+        };
         trace!("Got reply ({} bytes), parsing", raw.len());
-        let reply = self.unmarshaller.unmarshall(&raw)?;
+        let reply = match &*self.unmarshaller.unmarshall(&raw)? {
+            Reply::Sealed(data) => {
+                sealed::open_reply(data, &self.config.node_key)?
+            }
+            reply => reply.clone(),
+        };
         trace!("Reply: {:?}", reply);
-        Ok((&*reply).clone())
+        Ok(reply)
+    }
+
+    fn exchange(&mut self, data: &[u8]) -> Result<Vec<u8>, rpc::Error> {
+        self.session_rpc.send_raw_message(data)?;
+        trace!("Awaiting reply");
+        Ok(self.session_rpc.recv_raw_message()?)
     }
 }
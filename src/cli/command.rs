@@ -11,25 +11,55 @@
 // along with this software.
 // If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
 
-use std::{fs, io};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::{fs, io, process};
 
-use bitcoin::consensus::encode::{Decodable, Encodable};
-use bitcoin::hashes::hex::ToHex;
+use bitcoin::consensus::{deserialize, serialize};
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::hashes::{sha256, sha256d, Hash};
 use bitcoin::secp256k1;
-use bitcoin::util::bip32::DerivationPath;
+use bitcoin::util::bip32::{
+    DerivationPath, ExtendedPrivKey, ExtendedPubKey, KeySource,
+};
 use bitcoin::util::psbt::PartiallySignedTransaction as Psbt;
-use bitcoin::XpubIdentifier;
-use lnpbp::strict_encoding::strict_serialize;
+use bitcoin::{Address, XpubIdentifier};
+use chrono::{TimeZone, Utc};
+use lnpbp::chain::AssetId;
+use lnpbp::strict_encoding::{strict_serialize, StrictEncode};
 use lnpbp::Chain;
 use microservices::shell::Exec;
+#[cfg(feature = "node")]
+use microservices::FileFormat;
 use microservices::StructuredFormat;
+use serde::Serialize;
 use slip132::KeyApplication;
 
+use super::output::{self, Nonce, Secret, Signature, Verification};
+use super::qr::Exported;
+use super::shell::Shell;
 use super::Client;
+#[cfg(feature = "node")]
+use super::VaultCommand;
 use super::{
-    Command, SeedCommand, SignCommand, XPrivkeyCommand, XPubkeyCommand,
+    Command, IdentityCommand, MuSigCommand, MultisigCommand, OutputFormat,
+    PsbtCommand, QrOpts, RevocationCommand, SandboxCommand, SecretSource,
+    SeedCommand, SignCommand, TxCommand, UtilCommand, VerifyCommand,
+    XPrivkeyCommand, XPubkeyCommand, STRUCTURED_FORMATS,
 };
+use crate::crypto;
+use crate::lifecycle::Lifecycle;
+use crate::psbt;
 use crate::rpc;
+use crate::rpc::types::{
+    AccountQuery, AuthCode, Bip85Application, Branches, CollisionPolicy,
+    DerivationTemplate, KeyPrefix, LabelQuery, LedgerEntry, PaymentCode,
+    SessionToken, SigningPolicy, UpdateMode,
+};
+use crate::signed_message;
+#[cfg(feature = "node")]
+use crate::vault::{diff, example, file_driver, journal, FileDriver};
 
 impl Exec for Command {
     type Client = Client;
@@ -38,10 +68,231 @@ impl Exec for Command {
     #[inline]
     fn exec(self, runtime: &mut Client) -> Result<(), Self::Error> {
         match self {
+            Command::Status { format } => self.exec_status(runtime, &format),
+            Command::Attest {
+                ref quote,
+                ref verifier,
+                format,
+            } => self.exec_attest(runtime, quote, verifier, &format),
             Command::Seed { subcommand } => subcommand.exec(runtime),
             Command::Xpub { subcommand } => subcommand.exec(runtime),
             Command::Xpriv { subcommand } => subcommand.exec(runtime),
             Command::Sign { subcommand } => subcommand.exec(runtime),
+            Command::Verify { subcommand } => subcommand.exec(runtime),
+            Command::Psbt { subcommand } => subcommand.exec(runtime),
+            Command::Tx { subcommand } => subcommand.exec(runtime),
+            Command::Unlock { ref passphrase } => {
+                self.exec_unlock(runtime, passphrase)
+            }
+            Command::Lock { session } => self.exec_lock(runtime, session),
+            Command::Shell {
+                ref history,
+                unlock,
+                ref passphrase,
+            } => self.exec_shell(runtime, history, unlock, passphrase),
+            Command::Reconfigure => self.exec_reconfigure(runtime),
+            Command::Sandbox { subcommand } => subcommand.exec(runtime),
+            Command::Identity { subcommand } => subcommand.exec(runtime),
+            Command::Multisig { subcommand } => subcommand.exec(runtime),
+            Command::Musig { subcommand } => subcommand.exec(runtime),
+            Command::Revocation { subcommand } => subcommand.exec(runtime),
+            Command::Util { subcommand } => subcommand.exec(runtime),
+            #[cfg(feature = "node")]
+            Command::Vault { subcommand } => subcommand.exec(runtime),
+        }
+    }
+}
+
+impl Command {
+    pub fn exec_status(
+        &self,
+        runtime: &mut Client,
+        format: &StructuredFormat,
+    ) -> Result<(), rpc::Error> {
+        debug!("Requesting daemon status");
+        match runtime.request(rpc::Request::Status)? {
+            rpc::Reply::Status(status) => {
+                print_data(runtime.output(), &status, format)
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+
+    pub fn exec_attest(
+        &self,
+        runtime: &mut Client,
+        quote: &Option<PathBuf>,
+        verifier: &Option<String>,
+        format: &StructuredFormat,
+    ) -> Result<(), rpc::Error> {
+        debug!("Requesting daemon attestation");
+        let attestation = runtime.attest()?;
+        if let Some(path) = quote {
+            fs::write(path, &attestation.quote)?;
+        }
+        if let Some(program) = verifier {
+            debug!("Verifying {} quote with {}", attestation.platform, program);
+            let mut child = process::Command::new(program)
+                .arg(&attestation.platform)
+                .arg(attestation.report_data.to_hex())
+                .stdin(process::Stdio::piped())
+                .stderr(process::Stdio::piped())
+                .spawn()?;
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(&attestation.quote)?;
+            }
+            let output = child.wait_with_output()?;
+            if !output.status.success() {
+                return Err(rpc::Error::AttestationRejected(
+                    String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+                ));
+            }
+        }
+        print_data(runtime.output(), &attestation, format)
+    }
+
+    pub fn exec_unlock(
+        &self,
+        runtime: &mut Client,
+        passphrase: &Option<SecretSource>,
+    ) -> Result<(), rpc::Error> {
+        let session = runtime.unlock(passphrase)?;
+        output::print_with(runtime.output(), &session, || {
+            eprintln!(
+                "Session expires in {} seconds; use it with `--session` \
+                 argument or `KEYRING_SESSION` environment variable:",
+                session.expires_in
+            );
+            println!("{}", session.token);
+        })
+    }
+
+    pub fn exec_shell(
+        &self,
+        runtime: &mut Client,
+        history: &str,
+        unlock: bool,
+        passphrase: &Option<SecretSource>,
+    ) -> Result<(), rpc::Error> {
+        debug!("Starting interactive shell");
+        let mut shell = Shell::with(runtime, history, passphrase.clone())?;
+        if unlock {
+            shell.unlock(runtime)?;
+        }
+        shell.run(runtime)
+    }
+
+    pub fn exec_lock(
+        &self,
+        runtime: &mut Client,
+        session: SessionToken,
+    ) -> Result<(), rpc::Error> {
+        debug!("Locking vault session {}", session);
+        let reply =
+            runtime.request(rpc::Request::Lock(rpc::message::Lock {
+                session,
+                auth_code: AuthCode::default(),
+            }))?;
+        match reply {
+            rpc::Reply::Success => {
+                output::done(runtime.output(), "Vault session is locked")
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+
+    pub fn exec_reconfigure(
+        &self,
+        runtime: &mut Client,
+    ) -> Result<(), rpc::Error> {
+        debug!("Requesting daemon reconfiguration");
+        let reply = runtime.request(rpc::Request::Reconfigure(
+            rpc::message::Reconfigure {
+                auth_code: AuthCode::default(),
+            },
+        ))?;
+        match reply {
+            rpc::Reply::Success => output::done(
+                runtime.output(),
+                "Daemon configuration is reloaded",
+            ),
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+}
+
+impl Exec for SandboxCommand {
+    type Client = Client;
+    type Error = rpc::Error;
+
+    #[inline]
+    fn exec(self, runtime: &mut Client) -> Result<(), Self::Error> {
+        match self {
+            SandboxCommand::Commit { session } => {
+                self.exec_commit(runtime, session)
+            }
+            SandboxCommand::Discard { session } => {
+                self.exec_discard(runtime, session)
+            }
+        }
+    }
+}
+
+impl SandboxCommand {
+    pub fn exec_commit(
+        &self,
+        runtime: &mut Client,
+        session: SessionToken,
+    ) -> Result<(), rpc::Error> {
+        debug!("Committing sandboxed accounts of session {}", session);
+        let reply = runtime.request(rpc::Request::CommitSandbox(
+            rpc::message::Sandbox {
+                session,
+                auth_code: AuthCode::default(),
+            },
+        ))?;
+        match reply {
+            rpc::Reply::Keylist(accounts) => {
+                info!("{} accounts are committed to the vault", accounts.len());
+                output::print_list(runtime.output(), &accounts)
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+
+    pub fn exec_discard(
+        &self,
+        runtime: &mut Client,
+        session: SessionToken,
+    ) -> Result<(), rpc::Error> {
+        debug!("Discarding sandboxed accounts of session {}", session);
+        let reply = runtime.request(rpc::Request::DiscardSandbox(
+            rpc::message::Sandbox {
+                session,
+                auth_code: AuthCode::default(),
+            },
+        ))?;
+        match reply {
+            rpc::Reply::Success => output::done(
+                runtime.output(),
+                "Sandboxed accounts are discarded",
+            ),
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
         }
     }
 }
@@ -69,6 +320,9 @@ impl Exec for SeedCommand {
             SeedCommand::Export { id, ref file } => {
                 self.exec_export(runtime, &id, file)
             }
+            SeedCommand::Delete { id, purge } => {
+                self.exec_delete(runtime, id, purge)
+            }
         }
     }
 }
@@ -80,15 +334,175 @@ impl Exec for XPubkeyCommand {
     #[inline]
     fn exec(self, runtime: &mut Client) -> Result<(), Self::Error> {
         match self {
-            XPubkeyCommand::List { format } => self.exec_list(runtime, &format),
+            XPubkeyCommand::List {
+                format,
+                balances: false,
+                ref chain,
+                ..
+            } => self.exec_list(runtime, &format, chain),
+            XPubkeyCommand::List {
+                format,
+                balances: true,
+                gap_limit,
+                ref chain,
+            } => self.exec_list_balances(runtime, &format, gap_limit, chain),
+            XPubkeyCommand::Find {
+                format,
+                ref labels,
+                asset,
+                application,
+                fingerprint,
+            } => self.exec_find(
+                runtime,
+                &format,
+                AccountQuery {
+                    labels: labels.clone(),
+                    asset,
+                    application,
+                    fingerprint,
+                },
+            ),
             XPubkeyCommand::Derive {
                 id,
                 ref path,
                 ref name,
                 ref details,
-            } => self.exec_derive(runtime, &id, path, name, details),
-            XPubkeyCommand::Export { id, ref file } => {
-                self.exec_export(runtime, &id, file)
+                sandbox,
+                descriptor,
+                ref render,
+            } => self.exec_derive(
+                runtime, &id, path, name, details, sandbox, descriptor, render,
+            ),
+            XPubkeyCommand::Discover {
+                format,
+                id,
+                gap_limit,
+            } => self.exec_discover(runtime, &format, id, gap_limit),
+            XPubkeyCommand::Range {
+                format,
+                id,
+                ref template,
+                internal,
+                start,
+                count,
+            } => self.exec_range(
+                runtime, &format, id, template, internal, start, count,
+            ),
+            XPubkeyCommand::Watch {
+                xpubkey,
+                ref name,
+                ref details,
+                ref origin,
+                application,
+                on_collision,
+            } => self.exec_watch(
+                runtime,
+                xpubkey,
+                name,
+                details,
+                origin,
+                application,
+                on_collision,
+            ),
+            XPubkeyCommand::Import {
+                format,
+                ref file,
+                on_collision,
+                ref descriptors,
+            } => self.exec_import(
+                runtime,
+                &format,
+                file,
+                descriptors,
+                on_collision,
+            ),
+            XPubkeyCommand::Export {
+                id,
+                ref file,
+                descriptor,
+                prefix,
+                ref render,
+            } => {
+                self.exec_export(runtime, id, file, descriptor, prefix, render)
+            }
+            XPubkeyCommand::Descriptor { id } => {
+                self.exec_descriptor(runtime, id)
+            }
+            XPubkeyCommand::LnKeys {
+                format,
+                id,
+                channel,
+            } => self.exec_ln_keys(runtime, &format, id, channel),
+            XPubkeyCommand::Paycode {
+                format,
+                id,
+                account,
+                counterparty,
+                ref contact,
+                start,
+                count,
+            } => self.exec_paycode(
+                runtime,
+                &format,
+                id,
+                account,
+                counterparty,
+                contact,
+                start,
+                count,
+            ),
+            XPubkeyCommand::Delete { id, purge } => {
+                self.exec_delete(runtime, id, purge)
+            }
+            XPubkeyCommand::Branches {
+                id,
+                external,
+                internal,
+                gap_limit,
+            } => self.exec_branches(
+                runtime,
+                id,
+                Branches {
+                    external,
+                    internal,
+                    gap_limit,
+                },
+            ),
+            XPubkeyCommand::Policy {
+                id,
+                max_amount,
+                ref destinations,
+                ref sighash_types,
+                rate_limit,
+                ecdh,
+            } => self.exec_policy(
+                runtime,
+                id,
+                SigningPolicy {
+                    max_amount,
+                    destinations: destinations
+                        .iter()
+                        .map(Address::script_pubkey)
+                        .collect(),
+                    sighash_types: sighash_types.clone(),
+                    rate_limit,
+                    allow_ecdh: ecdh,
+                },
+            ),
+            XPubkeyCommand::Label {
+                id,
+                ref label,
+                remove,
+            } => self.exec_label(runtime, id, label, remove),
+            XPubkeyCommand::Update {
+                id,
+                ref name,
+                ref details,
+                ref assets,
+                mode,
+            } => self.exec_update(runtime, id, name, details, assets, mode),
+            XPubkeyCommand::Lifecycle { id, state } => {
+                self.exec_lifecycle(runtime, id, state)
             }
         }
     }
@@ -101,9 +515,30 @@ impl Exec for XPrivkeyCommand {
     #[inline]
     fn exec(self, runtime: &mut Client) -> Result<(), Self::Error> {
         match self {
+            XPrivkeyCommand::Import {
+                ref name,
+                ref details,
+                ref in_file,
+                ref origin,
+                application,
+                on_collision,
+            } => self.exec_import(
+                runtime,
+                name,
+                details,
+                in_file,
+                origin,
+                application,
+                on_collision,
+            ),
             XPrivkeyCommand::Export { id, ref file } => {
                 self.exec_export(runtime, &id, file)
             }
+            XPrivkeyCommand::Bip85 {
+                id,
+                application,
+                index,
+            } => self.exec_bip85(runtime, id, application, index),
         }
     }
 }
@@ -120,28 +555,28 @@ impl Exec for SignCommand {
                 in_file,
                 data,
                 out_file,
+                finalize,
+                extract,
+                force,
             } => {
-                let reader = match (data, in_file) {
-                    (Some(data), _) => {
-                        Box::new(io::BufReader::new(io::Cursor::new(data)))
-                            as Box<dyn io::BufRead>
-                    }
-                    (None, None) => Box::new(io::BufReader::new(io::stdin()))
-                        as Box<dyn io::BufRead>,
-                    (_, Some(filename)) => {
-                        Box::new(io::BufReader::new(fs::File::open(filename)?))
-                            as Box<dyn io::BufRead>
+                let data = match (data, in_file) {
+                    (Some(data), _) => data.into_bytes(),
+                    (None, Some(filename)) => fs::read(filename)?,
+                    (None, None) => {
+                        let mut data = vec![];
+                        io::stdin().read_to_end(&mut data)?;
+                        data
                     }
                 };
-                let psbt = match format {
-                    StructuredFormat::Bin => Psbt::consensus_decode(reader)?,
-                    _ => unimplemented!(),
-                };
-                let reply = runtime.request(rpc::Request::SignPsbt(
+                let psbt = decode_psbt(&data)?;
+                let reply = runtime.request_tracked(rpc::Request::SignPsbt(
                     rpc::message::SignPsbt {
                         psbt,
-                        decryption_key: secp256k1::key::ONE_KEY,
-                        auth_code: 0,
+                        force,
+                        decryption_key: Client::DECRYPTION_KEY_PLACEHOLDER,
+                        session: None,
+                        job: None,
+                        auth_code: AuthCode::default(),
                     },
                 ))?;
                 let psbt = match reply {
@@ -151,52 +586,990 @@ impl Exec for SignCommand {
                     }
                     _ => Err(rpc::Error::UnexpectedServerResponse)?,
                 };
-                let writer = match out_file {
-                    Some(filename) => Box::new(io::BufWriter::new(
-                        fs::File::create(filename)?,
-                    ))
-                        as Box<dyn io::Write>,
-                    None => Box::new(io::BufWriter::new(io::stdout()))
-                        as Box<dyn io::Write>,
+                let reply = if finalize {
+                    runtime.request(rpc::Request::FinalizePsbt(
+                        rpc::message::FinalizePsbt { psbt, extract },
+                    ))?
+                } else {
+                    rpc::Reply::Psbt(psbt)
                 };
-                match format {
-                    StructuredFormat::Bin => {
-                        psbt.consensus_encode(writer)?;
+                let data = match reply {
+                    rpc::Reply::Psbt(psbt) => serialize(&psbt),
+                    rpc::Reply::Transaction(tx) => serialize(&tx),
+                    rpc::Reply::Failure(failure) => {
+                        Err(rpc::Error::ServerFailure(failure))?
                     }
-                    _ => unimplemented!(),
-                }
+                    _ => Err(rpc::Error::UnexpectedServerResponse)?,
+                };
+                write_encoded(&data, &format, &out_file)?;
                 Ok(())
             }
-            SignCommand::File { .. } => unimplemented!(),
-            SignCommand::Text { .. } => unimplemented!(),
+            SignCommand::Inspect { in_file, data } => {
+                let data = match (data, in_file) {
+                    (Some(data), _) => data.into_bytes(),
+                    (None, Some(filename)) => fs::read(filename)?,
+                    (None, None) => {
+                        let mut data = vec![];
+                        io::stdin().read_to_end(&mut data)?;
+                        data
+                    }
+                };
+                let psbt = decode_psbt(&data)?;
+                let reply = runtime.request(rpc::Request::AnalyzePsbt(
+                    rpc::message::AnalyzePsbt {
+                        psbt,
+                        auth_code: AuthCode::default(),
+                    },
+                ))?;
+                let analysis = match reply {
+                    rpc::Reply::PsbtAnalysis(analysis) => analysis,
+                    rpc::Reply::Failure(failure) => {
+                        Err(rpc::Error::ServerFailure(failure))?
+                    }
+                    _ => Err(rpc::Error::UnexpectedServerResponse)?,
+                };
+                output::print_with(runtime.output(), &analysis, || {
+                    println!("{}", analysis);
+                    for input in &analysis.inputs {
+                        println!("  {}", input);
+                    }
+                    match analysis.fee {
+                        Some(fee) => println!("Fee: {} sat", fee),
+                        None => println!("Fee: unknown"),
+                    }
+                    for issue in &analysis.issues {
+                        println!("Warning: {}", issue);
+                    }
+                })
+            }
+            SignCommand::File {
+                ref format,
+                id,
+                ref in_file,
+                ref out_file,
+            } => {
+                let digest = file_digest(in_file)?;
+                self.exec_sign_file(runtime, format, id, digest, out_file)
+            }
+            SignCommand::Text {
+                id,
+                ref message,
+                ref in_file,
+            } => {
+                let message = read_message(message, in_file)?;
+                self.exec_sign_text(runtime, id, message)
+            }
+            SignCommand::Verify {
+                ref signature,
+                ref message,
+                ref in_file,
+                ref address,
+                ref pubkey,
+            } => {
+                let message = read_message(message, in_file)?;
+                let signature = base64::decode(signature)
+                    .map_err(|_| signed_message::Error::InvalidSignature)?;
+                let valid = match (address, pubkey) {
+                    (Some(address), _) => signed_message::verify_address(
+                        &signature, &message, address,
+                    )?,
+                    (None, Some(pubkey)) => signed_message::verify_pubkey(
+                        &signature, &message, pubkey,
+                    )?,
+                    (None, None) => unreachable!(
+                        "clap requires either address or public key"
+                    ),
+                };
+                output::print(runtime.output(), &Verification { valid })
+            }
             SignCommand::Key { id } => self.exec_sign_key(runtime, id),
         }
     }
 }
 
-impl SeedCommand {
-    pub fn exec_create(
-        &self,
-        runtime: &mut Client,
-        name: String,
-        description: Option<String>,
-        chain: Chain,
-        application: KeyApplication,
-    ) -> Result<(), rpc::Error> {
-        debug!("Creating new seed");
-        let reply =
-            runtime.request(rpc::Request::Seed(rpc::message::Seed {
-                auth_code: 0,
-                name,
-                chain,
-                application,
-                description,
-            }))?;
-        match reply {
-            rpc::Reply::Success => {
-                info!("New seed created");
+impl Exec for VerifyCommand {
+    type Client = Client;
+    type Error = rpc::Error;
+
+    #[inline]
+    fn exec(self, runtime: &mut Client) -> Result<(), Self::Error> {
+        match self {
+            VerifyCommand::File {
+                ref format,
+                ref signature,
+                pubkey,
+                ref in_file,
+            } => {
+                let data = fs::read(signature)?;
+                let signature = match format {
+                    StructuredFormat::Bin => Ok(data),
+                    StructuredFormat::Hex => std::str::from_utf8(&data)
+                        .map_err(|err| err.to_string())
+                        .and_then(|s| {
+                            Vec::<u8>::from_hex(s.trim())
+                                .map_err(|err| err.to_string())
+                        }),
+                    StructuredFormat::Base64 => std::str::from_utf8(&data)
+                        .map_err(|err| err.to_string())
+                        .and_then(|s| {
+                            base64::decode(s.trim())
+                                .map_err(|err| err.to_string())
+                        }),
+                    _ => Err(unsupported_format(format))?,
+                }
+                .and_then(|sig| {
+                    secp256k1::Signature::from_der(&sig)
+                        .map_err(|err| err.to_string())
+                })
+                .map_err(|err| {
+                    io::Error::new(io::ErrorKind::InvalidData, err)
+                })?;
+                let digest = sha256d::Hash::hash(&file_digest(in_file)?);
+                let message = secp256k1::Message::from_slice(&digest[..])
+                    .expect("SHA256d hash is always a valid message");
+                let valid = crate::SECP256K1
+                    .verify(&message, &signature, &pubkey.key)
+                    .is_ok();
+                output::print(runtime.output(), &Verification { valid })
+            }
+        }
+    }
+}
+
+impl Exec for PsbtCommand {
+    type Client = Client;
+    type Error = rpc::Error;
+
+    #[inline]
+    fn exec(self, runtime: &mut Client) -> Result<(), Self::Error> {
+        match self {
+            PsbtCommand::Combine {
+                format,
+                files,
+                out_file,
+                local,
+            } => {
+                let psbts = files
+                    .iter()
+                    .map(|filename| {
+                        debug!("Reading PSBT from {}", filename.display());
+                        decode_psbt(&fs::read(filename)?)
+                    })
+                    .collect::<Result<Vec<_>, io::Error>>()?;
+                let psbt = if local {
+                    psbt::combine(psbts)?
+                } else {
+                    let reply = runtime.request(rpc::Request::CombinePsbt(
+                        rpc::message::CombinePsbt { psbts },
+                    ))?;
+                    match reply {
+                        rpc::Reply::Psbt(psbt) => psbt,
+                        rpc::Reply::Failure(failure) => {
+                            Err(rpc::Error::ServerFailure(failure))?
+                        }
+                        _ => Err(rpc::Error::UnexpectedServerResponse)?,
+                    }
+                };
+                info!(
+                    "{} PSBTs combined for transaction {}",
+                    files.len(),
+                    psbt.global.unsigned_tx.txid()
+                );
+                write_encoded(&serialize(&psbt), &format, &out_file)?;
                 Ok(())
             }
+        }
+    }
+}
+
+impl Exec for RevocationCommand {
+    type Client = Client;
+    type Error = rpc::Error;
+
+    #[inline]
+    fn exec(self, runtime: &mut Client) -> Result<(), Self::Error> {
+        let request = match self {
+            RevocationCommand::Append {
+                id,
+                channel,
+                index,
+                secret,
+            } => {
+                rpc::Request::AppendRevocation(rpc::message::AppendRevocation {
+                    key_id: id,
+                    channel,
+                    index,
+                    secret,
+                    auth_code: AuthCode::default(),
+                })
+            }
+            RevocationCommand::Query { id, channel, index } => {
+                rpc::Request::QueryRevocation(rpc::message::QueryRevocation {
+                    key_id: id,
+                    channel,
+                    index,
+                    auth_code: AuthCode::default(),
+                })
+            }
+            RevocationCommand::Compact { id } => {
+                rpc::Request::CompactRevocations(
+                    rpc::message::CompactRevocations {
+                        key_id: id,
+                        auth_code: AuthCode::default(),
+                    },
+                )
+            }
+        };
+        match runtime.request(request)? {
+            rpc::Reply::Success => {
+                output::done(runtime.output(), "Revocation storage is updated")
+            }
+            rpc::Reply::CommitmentSecret(secret) => output::print(
+                runtime.output(),
+                &Secret {
+                    secret: secret.to_string(),
+                },
+            ),
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+}
+
+impl Exec for TxCommand {
+    type Client = Client;
+    type Error = rpc::Error;
+
+    #[inline]
+    fn exec(self, runtime: &mut Client) -> Result<(), Self::Error> {
+        match self {
+            TxCommand::Create {
+                format,
+                inputs,
+                outputs,
+                lock_time,
+                out_file,
+            } => {
+                let reply = runtime.request(rpc::Request::ComposePsbt(
+                    rpc::message::ComposePsbt {
+                        inputs,
+                        outputs,
+                        lock_time,
+                        auth_code: AuthCode::default(),
+                    },
+                ))?;
+                match reply {
+                    rpc::Reply::Psbt(psbt) => {
+                        write_encoded(&serialize(&psbt), &format, &out_file)?;
+                        Ok(())
+                    }
+                    rpc::Reply::Failure(failure) => {
+                        Err(rpc::Error::ServerFailure(failure))
+                    }
+                    _ => Err(rpc::Error::UnexpectedServerResponse),
+                }
+            }
+            TxCommand::Ledger {
+                format,
+                csv,
+                since,
+                out_file,
+            } => {
+                let reply = runtime.request(rpc::Request::ExportLedger(
+                    rpc::message::ExportLedger {
+                        since,
+                        auth_code: AuthCode::default(),
+                    },
+                ))?;
+                let entries = match reply {
+                    rpc::Reply::Ledger(entries) => entries,
+                    rpc::Reply::Failure(failure) => {
+                        Err(rpc::Error::ServerFailure(failure))?
+                    }
+                    _ => Err(rpc::Error::UnexpectedServerResponse)?,
+                };
+                info!("{} ledger records exported", entries.len());
+                if !csv && out_file.is_none() {
+                    return print_data(runtime.output(), &entries, &format);
+                }
+                let output = if csv {
+                    ledger_csv(&entries)
+                } else {
+                    format_data(&entries, &format)?
+                };
+                match out_file {
+                    Some(filename) => fs::write(filename, output)?,
+                    None => println!("{}", output),
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Formats ledger records as CSV table with a header row and time given in
+/// RFC 3339 format
+fn ledger_csv(entries: &[LedgerEntry]) -> String {
+    let mut csv = s!("txid,time,client,spent,received,fee\n");
+    for entry in entries {
+        let time = Utc.timestamp(entry.timestamp as i64, 0).to_rfc3339();
+        let client = entry.client.as_deref().unwrap_or_default();
+        let fee = entry.fee.map(|fee| fee.to_string()).unwrap_or_default();
+        csv += &format!(
+            "{},{},\"{}\",{},{},{}\n",
+            entry.txid,
+            time,
+            client.replace('"', "\"\""),
+            entry.spent,
+            entry.received,
+            fee
+        );
+    }
+    csv
+}
+
+impl Exec for IdentityCommand {
+    type Client = Client;
+    type Error = rpc::Error;
+
+    #[inline]
+    fn exec(self, runtime: &mut Client) -> Result<(), Self::Error> {
+        let request = match self {
+            IdentityCommand::Key { id, index } => {
+                rpc::Request::IdentityKey(rpc::message::IdentityKey {
+                    key_id: id,
+                    index,
+                    decryption_key: Client::DECRYPTION_KEY_PLACEHOLDER,
+                    session: None,
+                    auth_code: AuthCode::default(),
+                })
+            }
+            IdentityCommand::Sign { id, digest, index } => {
+                rpc::Request::SignIdentity(rpc::message::SignIdentity {
+                    key_id: id,
+                    index,
+                    digest,
+                    decryption_key: Client::DECRYPTION_KEY_PLACEHOLDER,
+                    session: None,
+                    auth_code: AuthCode::default(),
+                })
+            }
+        };
+        match runtime.request(request)? {
+            rpc::Reply::IdentityKey(identity) => {
+                output::print_with(runtime.output(), &identity, || {
+                    println!("{}", identity.pubkey.to_hex());
+                    info!("Identity key: {}", identity);
+                })
+            }
+            rpc::Reply::IdentitySignature(signature) => {
+                output::print_with(runtime.output(), &signature, || {
+                    println!("{}", signature.signature.to_hex());
+                    info!("Identity key: {}", signature.key);
+                })
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+}
+
+impl Exec for MultisigCommand {
+    type Client = Client;
+    type Error = rpc::Error;
+
+    #[inline]
+    fn exec(self, runtime: &mut Client) -> Result<(), Self::Error> {
+        let request = match self {
+            MultisigCommand::Create {
+                name,
+                threshold,
+                accounts,
+                cosigners,
+            } => rpc::Request::CreateMultisig(rpc::message::CreateMultisig {
+                name,
+                threshold,
+                accounts,
+                cosigners,
+                auth_code: AuthCode::default(),
+            }),
+            MultisigCommand::List => rpc::Request::ListMultisig,
+            MultisigCommand::Descriptor { id } => {
+                rpc::Request::ExportMultisig(rpc::message::ExportMultisig {
+                    group: id,
+                    auth_code: AuthCode::default(),
+                })
+            }
+        };
+        match runtime.request(request)? {
+            rpc::Reply::Multisig(group) => {
+                info!("Multisig group {} is registered", group.id());
+                output::print_with(runtime.output(), &group, || {
+                    println!("{}", group);
+                    group.cosigners.iter().for_each(|c| println!("  {}", c));
+                })
+            }
+            rpc::Reply::MultisigGroups(groups) => {
+                output::print_with(runtime.output(), &groups, || {
+                    for group in &groups {
+                        println!("{}", group);
+                        group
+                            .cosigners
+                            .iter()
+                            .for_each(|c| println!("  {}", c));
+                    }
+                })
+            }
+            rpc::Reply::Descriptors(descriptors) => {
+                output::print_list(runtime.output(), &descriptors)
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+}
+
+impl Exec for MuSigCommand {
+    type Client = Client;
+    type Error = rpc::Error;
+
+    #[inline]
+    fn exec(self, runtime: &mut Client) -> Result<(), Self::Error> {
+        let request = match self {
+            MuSigCommand::Start {
+                id,
+                message,
+                participants,
+                taproot,
+            } => rpc::Request::MuSigStartSession(
+                rpc::message::MuSigStartSession {
+                    key_id: id,
+                    participants,
+                    message,
+                    taproot,
+                    auth_code: AuthCode::default(),
+                },
+            ),
+            MuSigCommand::Nonces { session, nonces } => {
+                rpc::Request::MuSigNonceExchange(
+                    rpc::message::MuSigNonceExchange {
+                        id: session,
+                        nonces,
+                        auth_code: AuthCode::default(),
+                    },
+                )
+            }
+            MuSigCommand::Sign {
+                session,
+                partial_sigs,
+            } => {
+                rpc::Request::MuSigPartialSign(rpc::message::MuSigPartialSign {
+                    id: session,
+                    partial_sigs,
+                    decryption_key: Client::DECRYPTION_KEY_PLACEHOLDER,
+                    session: None,
+                    auth_code: AuthCode::default(),
+                })
+            }
+        };
+        match runtime.request(request)? {
+            rpc::Reply::MuSigSession(session) => {
+                info!("MuSig2 session {} is started", session);
+                output::print_with(runtime.output(), &session, || {
+                    println!("session: {}", session.id);
+                    println!("participant: {}", session.participant);
+                    println!(
+                        "aggregated key: {}",
+                        session.aggregated_key.to_hex()
+                    );
+                    println!("nonce: {}", session.nonce.to_hex());
+                })
+            }
+            rpc::Reply::MuSigNonce(aggnonce) => output::print(
+                runtime.output(),
+                &Nonce {
+                    nonce: aggnonce.to_hex(),
+                },
+            ),
+            rpc::Reply::MuSigSignature(signature) => {
+                output::print_with(runtime.output(), &signature, || {
+                    println!("partial: {}", signature.partial.to_hex());
+                    if let Some(ref signature) = signature.signature {
+                        println!("signature: {}", signature.to_hex());
+                    }
+                })
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+}
+
+#[cfg(feature = "node")]
+impl Exec for VaultCommand {
+    type Client = Client;
+    type Error = rpc::Error;
+
+    #[inline]
+    fn exec(self, runtime: &mut Client) -> Result<(), Self::Error> {
+        match self {
+            VaultCommand::List => {
+                match runtime.request(rpc::Request::ListVaults)? {
+                    rpc::Reply::VaultList(vaults) => {
+                        output::print_list(runtime.output(), &vaults)
+                    }
+                    rpc::Reply::Failure(failure) => {
+                        Err(rpc::Error::ServerFailure(failure))
+                    }
+                    _ => Err(rpc::Error::UnexpectedServerResponse),
+                }
+            }
+            VaultCommand::Backup => {
+                debug!("Requesting vault backup");
+                match runtime.request_tracked(rpc::Request::Backup(
+                    rpc::message::Backup {
+                        job: None,
+                        auth_code: AuthCode::default(),
+                    },
+                ))? {
+                    rpc::Reply::Backup(path) => output::written(
+                        runtime.output(),
+                        path,
+                        "Vault is backed up to",
+                    ),
+                    rpc::Reply::Failure(failure) => {
+                        Err(rpc::Error::ServerFailure(failure))
+                    }
+                    _ => Err(rpc::Error::UnexpectedServerResponse),
+                }
+            }
+            VaultCommand::Restore { file } => {
+                debug!("Restoring vault from {}", file.display());
+                let snapshot = fs::read(&file)?;
+                match runtime.request_tracked(rpc::Request::Restore(
+                    rpc::message::Restore {
+                        snapshot,
+                        job: None,
+                        auth_code: AuthCode::default(),
+                    },
+                ))? {
+                    rpc::Reply::Keylist(accounts) => {
+                        output::print_with(runtime.output(), &accounts, || {
+                            println!(
+                                "Vault is restored with {} accounts",
+                                accounts.len()
+                            )
+                        })
+                    }
+                    rpc::Reply::Failure(failure) => {
+                        Err(rpc::Error::ServerFailure(failure))
+                    }
+                    _ => Err(rpc::Error::UnexpectedServerResponse),
+                }
+            }
+            VaultCommand::Export { file, encrypt } => {
+                let passphrase = encrypt
+                    .map(|source| source.read("Dump passphrase"))
+                    .transpose()?;
+                debug!("Exporting vault into {}", file.display());
+                match runtime.request(rpc::Request::ExportVault(
+                    rpc::message::ExportVault {
+                        passphrase,
+                        auth_code: AuthCode::default(),
+                    },
+                ))? {
+                    rpc::Reply::VaultDump(dump) => {
+                        fs::write(&file, dump)?;
+                        output::written(
+                            runtime.output(),
+                            file.display(),
+                            "Vault is exported to",
+                        )
+                    }
+                    rpc::Reply::Failure(failure) => {
+                        Err(rpc::Error::ServerFailure(failure))
+                    }
+                    _ => Err(rpc::Error::UnexpectedServerResponse),
+                }
+            }
+            VaultCommand::Import {
+                file,
+                replace,
+                passphrase,
+            } => {
+                let passphrase = passphrase
+                    .map(|source| source.read("Dump passphrase"))
+                    .transpose()?;
+                debug!("Importing vault from {}", file.display());
+                let dump = fs::read(&file)?;
+                match runtime.request(rpc::Request::ImportVault(
+                    rpc::message::ImportVault {
+                        dump,
+                        passphrase,
+                        replace,
+                        session: None,
+                        auth_code: AuthCode::default(),
+                    },
+                ))? {
+                    rpc::Reply::Keylist(accounts) => {
+                        output::print_with(runtime.output(), &accounts, || {
+                            println!(
+                                "Vault is imported and contains {} accounts",
+                                accounts.len()
+                            )
+                        })
+                    }
+                    rpc::Reply::Failure(failure) => {
+                        Err(rpc::Error::ServerFailure(failure))
+                    }
+                    _ => Err(rpc::Error::UnexpectedServerResponse),
+                }
+            }
+            VaultCommand::Diff {
+                snapshot_a,
+                snapshot_b,
+            } => {
+                let read = |path: &PathBuf| {
+                    FileDriver::read_snapshot(path).map_err(|err| {
+                        rpc::Error::VaultSnapshot(
+                            path.display().to_string(),
+                            err.to_string(),
+                        )
+                    })
+                };
+                let changes =
+                    diff::diff(&read(&snapshot_a)?, &read(&snapshot_b)?)
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>();
+                if changes.is_empty() && runtime.output() == OutputFormat::Plain
+                {
+                    println!("Vault snapshots contain the same accounts");
+                }
+                output::print_list(runtime.output(), &changes)
+            }
+            VaultCommand::Journal { journal, vault } => {
+                let keyrings =
+                    FileDriver::read_snapshot(&vault).map_err(|err| {
+                        rpc::Error::VaultSnapshot(
+                            vault.display().to_string(),
+                            err.to_string(),
+                        )
+                    })?;
+                let records =
+                    journal::verify(&journal, &keyrings).map_err(|err| {
+                        rpc::Error::Journal(
+                            journal.display().to_string(),
+                            err.to_string(),
+                        )
+                    })?;
+                println!(
+                    "Journal of {} records is consistent with the vault",
+                    records
+                );
+                Ok(())
+            }
+            VaultCommand::Example { dir } => {
+                debug!("Writing example vault into {}", dir.display());
+                let keyrings = example::keyrings().map_err(|err| {
+                    rpc::Error::ExampleVault(
+                        dir.display().to_string(),
+                        err.to_string(),
+                    )
+                })?;
+                for format in file_driver::supported_formats() {
+                    let extension = match format {
+                        FileFormat::StrictEncode => "dat",
+                        FileFormat::Yaml => "yaml",
+                        FileFormat::Json => "json",
+                        FileFormat::Toml => "toml",
+                        _ => "vault",
+                    };
+                    let path = dir.join(format!("example.{}", extension));
+                    // Formats which can't represent the vault are reported, but
+                    // do not prevent writing the others
+                    match FileDriver::write_snapshot(&path, &keyrings, &format)
+                    {
+                        Ok(()) => {
+                            println!(
+                                "{} example vault: {}",
+                                format,
+                                path.display()
+                            )
+                        }
+                        Err(err) => eprintln!(
+                            "Unable to write {} example vault {}: {}",
+                            format,
+                            path.display(),
+                            err
+                        ),
+                    }
+                }
+                println!("Decryption key: {}", example::decryption_key());
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Exec for UtilCommand {
+    type Client = Client;
+    type Error = rpc::Error;
+
+    #[inline]
+    fn exec(self, _runtime: &mut Client) -> Result<(), Self::Error> {
+        match self {
+            UtilCommand::Encrypt {
+                key,
+                in_file,
+                out_file,
+            } => {
+                let mut secret = vec![];
+                match in_file {
+                    Some(filename) => {
+                        fs::File::open(filename)?.read_to_end(&mut secret)?
+                    }
+                    None => io::stdin().read_to_end(&mut secret)?,
+                };
+                let wrapped = crypto::wrap(&secret, key);
+                secret.iter_mut().for_each(|byte| *byte = 0);
+                let wrapped = wrapped?.to_hex();
+                match out_file {
+                    Some(filename) => fs::write(filename, wrapped)?,
+                    None => println!("{}", wrapped),
+                }
+                Ok(())
+            }
+            UtilCommand::Decrypt {
+                key,
+                data,
+                in_file,
+                out_file,
+            } => {
+                // The key is read first, since both the key and the data may
+                // come from STDIN
+                let key = SecretSource::resolve(
+                    &key,
+                    "KEYRING_DECRYPTION_KEY",
+                    "Decryption key",
+                )?;
+                let key = secp256k1::SecretKey::from_str(key.trim())
+                    .map_err(|_| crypto::Error::InvalidKey)?;
+                let data = match (data, in_file) {
+                    (Some(data), _) => data,
+                    (None, Some(filename)) => fs::read_to_string(filename)?,
+                    (None, None) => {
+                        let mut data = String::new();
+                        io::stdin().read_to_string(&mut data)?;
+                        data
+                    }
+                };
+                let wrapped = Vec::<u8>::from_hex(data.trim())
+                    .map_err(|_| crypto::Error::Corrupted)?;
+                let mut secret = crypto::unwrap(&wrapped, &key)?;
+                match out_file {
+                    Some(filename) => fs::write(filename, &secret)?,
+                    None => io::stdout().write_all(&secret)?,
+                }
+                secret.iter_mut().for_each(|byte| *byte = 0);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl SeedCommand {
+    pub fn exec_create(
+        &self,
+        runtime: &mut Client,
+        name: String,
+        description: Option<String>,
+        chain: Chain,
+        application: KeyApplication,
+    ) -> Result<(), rpc::Error> {
+        debug!("Creating new seed");
+        let reply =
+            runtime.request(rpc::Request::Seed(rpc::message::Seed {
+                auth_code: AuthCode::default(),
+                name,
+                chain,
+                application,
+                description,
+                idempotency_key: None,
+            }))?;
+        match reply {
+            rpc::Reply::AccountInfo(info) => {
+                info!("New seed created");
+                output::print(runtime.output(), &info)
+            }
+            // Daemons of protocol versions before 27 do not return the
+            // account of the new seed
+            rpc::Reply::Success => {
+                output::done(runtime.output(), "New seed created")
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+
+    pub fn exec_import(
+        &self,
+        _runtime: &mut Client,
+        _id: &XpubIdentifier,
+    ) -> Result<(), rpc::Error> {
+        unimplemented!()
+    }
+
+    pub fn exec_discover(
+        &self,
+        runtime: &mut Client,
+        format: &StructuredFormat,
+        id: XpubIdentifier,
+        gap_limit: u32,
+    ) -> Result<(), rpc::Error> {
+        debug!("Discovering used accounts of keyring {}", id);
+        let reply = runtime.request_tracked(rpc::Request::Discover(
+            rpc::message::Discover {
+                key_id: id,
+                gap_limit,
+                decryption_key: Client::DECRYPTION_KEY_PLACEHOLDER,
+                session: None,
+                job: None,
+                auth_code: AuthCode::default(),
+            },
+        ))?;
+        match reply {
+            rpc::Reply::Keylist(accounts) => {
+                print_data(runtime.output(), &accounts, format)
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+
+    pub fn exec_export(
+        &self,
+        _runtime: &mut Client,
+        _id: &XpubIdentifier,
+        _file: &str,
+    ) -> Result<(), rpc::Error> {
+        unimplemented!()
+    }
+
+    pub fn exec_delete(
+        &self,
+        runtime: &mut Client,
+        id: XpubIdentifier,
+        purge: bool,
+    ) -> Result<(), rpc::Error> {
+        debug!("Deleting keyring {}", id);
+        let reply = runtime.request(rpc::Request::DeleteKeyring(
+            rpc::message::Delete {
+                key_id: id,
+                purge,
+                decryption_key: Client::DECRYPTION_KEY_PLACEHOLDER,
+                session: None,
+                auth_code: AuthCode::default(),
+            },
+        ))?;
+        match reply {
+            rpc::Reply::Success => {
+                output::done(runtime.output(), "Keyring deleted")
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+}
+
+impl XPubkeyCommand {
+    pub fn exec_list(
+        &self,
+        runtime: &mut Client,
+        format: &StructuredFormat,
+        chain: &Option<Chain>,
+    ) -> Result<(), rpc::Error> {
+        debug!("Listing known accounts/extended public keys");
+        let request = match chain {
+            Some(chain) => rpc::Request::FindAccounts(AccountQuery {
+                chain: Some(chain.clone()),
+                ..Default::default()
+            }),
+            None => rpc::Request::List,
+        };
+        let reply = runtime.request(request)?;
+        match reply {
+            rpc::Reply::Keylist(accounts) => {
+                print_data(runtime.output(), &accounts, format)
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure.clone()))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+
+    pub fn exec_find(
+        &self,
+        runtime: &mut Client,
+        format: &StructuredFormat,
+        query: AccountQuery,
+    ) -> Result<(), rpc::Error> {
+        debug!("Searching for accounts matching {}", query);
+        let reply = runtime.request(rpc::Request::FindAccounts(query))?;
+        match reply {
+            rpc::Reply::Keylist(accounts) => {
+                print_data(runtime.output(), &accounts, format)
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+
+    pub fn exec_watch(
+        &self,
+        runtime: &mut Client,
+        xpubkey: ExtendedPubKey,
+        name: &str,
+        details: &Option<String>,
+        key_source: &Option<KeySource>,
+        application: Option<KeyApplication>,
+        collision: CollisionPolicy,
+    ) -> Result<(), rpc::Error> {
+        debug!("Importing watch-only extended public key {}", xpubkey);
+        let reply = runtime.request(rpc::Request::ImportXpub(
+            rpc::message::ImportXpub {
+                xpubkey,
+                key_source: key_source.clone(),
+                application,
+                name: name.to_owned(),
+                details: details.clone(),
+                collision,
+                auth_code: AuthCode::default(),
+            },
+        ))?;
+        match reply {
+            rpc::Reply::AccountInfo(info) => {
+                output::print_with(runtime.output(), &info, || {
+                    info!("Watch-only account imported: {}", info)
+                })
+            }
             rpc::Reply::Failure(failure) => {
                 Err(rpc::Error::ServerFailure(failure))
             }
@@ -206,118 +1579,735 @@ impl SeedCommand {
 
     pub fn exec_import(
         &self,
-        _runtime: &mut Client,
-        _id: &XpubIdentifier,
+        runtime: &mut Client,
+        format: &StructuredFormat,
+        file: &Option<PathBuf>,
+        descriptors: &Vec<String>,
+        collision: CollisionPolicy,
+    ) -> Result<(), rpc::Error> {
+        let mut descriptors = descriptors.clone();
+        if let Some(file) = file {
+            debug!("Reading output descriptors from {}", file.display());
+            descriptors.extend(
+                fs::read_to_string(file)?
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(str::to_owned),
+            );
+        }
+        debug!("Importing {} output descriptors", descriptors.len());
+        let reply = runtime.request(rpc::Request::ImportDescriptors(
+            rpc::message::ImportDescriptors {
+                descriptors,
+                collision,
+                auth_code: AuthCode::default(),
+            },
+        ))?;
+        match reply {
+            rpc::Reply::Keylist(accounts) => {
+                print_data(runtime.output(), &accounts, format)
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+
+    pub fn exec_list_balances(
+        &self,
+        runtime: &mut Client,
+        format: &StructuredFormat,
+        gap_limit: u32,
+        chain: &Option<Chain>,
+    ) -> Result<(), rpc::Error> {
+        debug!("Listing known accounts with their balances");
+        let reply = runtime.request(rpc::Request::ListWithBalances(
+            rpc::message::Scan { gap_limit },
+        ))?;
+        match reply {
+            rpc::Reply::BalanceList(mut balances) => {
+                if chain.is_some() {
+                    balances.retain(|balance| balance.info.chain == *chain);
+                }
+                print_data(runtime.output(), &balances, format)
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure.clone()))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+
+    pub fn exec_derive(
+        &self,
+        runtime: &mut Client,
+        id: &XpubIdentifier,
+        path: &DerivationPath,
+        name: &String,
+        details: &Option<String>,
+        sandbox: bool,
+        descriptor: bool,
+        render: &QrOpts,
+    ) -> Result<(), rpc::Error> {
+        debug!("Deriving new subaccount");
+        let reply =
+            runtime.request(rpc::Request::Derive(rpc::message::Derive {
+                from: *id,
+                path: path.clone(),
+                name: name.clone(),
+                details: details.as_ref().cloned().unwrap_or_default(),
+                assets: Default::default(),
+                sandbox,
+                idempotency_key: None,
+                decryption_key: Client::DECRYPTION_KEY_PLACEHOLDER,
+                session: None,
+                auth_code: AuthCode::default(),
+            }))?;
+        match reply {
+            rpc::Reply::AccountInfo(info) if render.is_set() => {
+                eprintln!("{}", info);
+                self.exported(
+                    runtime,
+                    info.key_id,
+                    info.key_source,
+                    descriptor,
+                    None,
+                )?
+                .render(render)
+            }
+            rpc::Reply::AccountInfo(info) => {
+                output::print(runtime.output(), &info)
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure.clone()))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+
+    pub fn exec_export(
+        &self,
+        runtime: &mut Client,
+        id: XpubIdentifier,
+        file: &Option<PathBuf>,
+        descriptor: bool,
+        prefix: Option<KeyPrefix>,
+        render: &QrOpts,
+    ) -> Result<(), rpc::Error> {
+        debug!("Exporting keys account {}", id);
+        let origin = match runtime.request(rpc::Request::List)? {
+            rpc::Reply::Keylist(accounts) => accounts
+                .into_iter()
+                .find(|info| info.key_id == id)
+                .and_then(|info| info.key_source),
+            rpc::Reply::Failure(failure) => {
+                return Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => return Err(rpc::Error::UnexpectedServerResponse),
+        };
+        let exported =
+            self.exported(runtime, id, origin, descriptor, prefix)?;
+        match file {
+            Some(file) => {
+                fs::write(file, format!("{}\n", exported))?;
+                info!("Exported data are written to {}", file.display());
+                if render.is_set() {
+                    exported.render(render)?;
+                }
+                Ok(())
+            }
+            None if render.is_set() => exported.render(render),
+            None => output::print(runtime.output(), &exported),
+        }
+    }
+
+    /// Requests extended public key or output descriptors of the account
+    fn exported(
+        &self,
+        runtime: &mut Client,
+        id: XpubIdentifier,
+        origin: Option<KeySource>,
+        descriptor: bool,
+        prefix: Option<KeyPrefix>,
+    ) -> Result<Exported, rpc::Error> {
+        let export = rpc::message::Export {
+            key_id: id,
+            slip132: prefix,
+            decryption_key: Client::DECRYPTION_KEY_PLACEHOLDER,
+            session: None,
+            auth_code: AuthCode::default(),
+        };
+        let request = if descriptor {
+            rpc::Request::ExportDescriptor(export)
+        } else {
+            rpc::Request::ExportXpub(export)
+        };
+        match runtime.request(request)? {
+            rpc::Reply::XPub(xpub) => Ok(Exported::Xpub(xpub, origin)),
+            rpc::Reply::EncodedXPub(key) => Ok(Exported::Slip132(key)),
+            rpc::Reply::Descriptors(descriptors) => {
+                Ok(Exported::Descriptors(descriptors))
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+
+    pub fn exec_delete(
+        &self,
+        runtime: &mut Client,
+        id: XpubIdentifier,
+        purge: bool,
     ) -> Result<(), rpc::Error> {
-        unimplemented!()
+        debug!("Deleting keys account {}", id);
+        let reply = runtime.request(rpc::Request::DeleteAccount(
+            rpc::message::Delete {
+                key_id: id,
+                purge,
+                decryption_key: Client::DECRYPTION_KEY_PLACEHOLDER,
+                session: None,
+                auth_code: AuthCode::default(),
+            },
+        ))?;
+        match reply {
+            rpc::Reply::Success => {
+                output::done(runtime.output(), "Keys account deleted")
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
     }
 
-    pub fn exec_export(
+    pub fn exec_range(
         &self,
-        _runtime: &mut Client,
-        _id: &XpubIdentifier,
-        _file: &str,
+        runtime: &mut Client,
+        format: &StructuredFormat,
+        id: XpubIdentifier,
+        template: &Option<DerivationTemplate>,
+        internal: bool,
+        start: u32,
+        count: u32,
     ) -> Result<(), rpc::Error> {
-        unimplemented!()
+        debug!(
+            "Deriving {} keys from account {} starting at {}",
+            count, id, start
+        );
+        let reply = runtime.request_tracked(rpc::Request::DeriveRange(
+            rpc::message::DeriveRange {
+                key_id: id,
+                template: template.clone(),
+                internal,
+                start,
+                count,
+                job: None,
+                auth_code: AuthCode::default(),
+            },
+        ))?;
+        match reply {
+            rpc::Reply::DerivedKeys(keys) => {
+                print_data(runtime.output(), &keys, format)
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
     }
-}
 
-impl XPubkeyCommand {
-    pub fn exec_list(
+    pub fn exec_descriptor(
+        &self,
+        runtime: &mut Client,
+        id: XpubIdentifier,
+    ) -> Result<(), rpc::Error> {
+        debug!("Exporting output descriptors for keys account {}", id);
+        let reply = runtime.request(rpc::Request::ExportDescriptor(
+            rpc::message::Export {
+                key_id: id,
+                slip132: None,
+                decryption_key: Client::DECRYPTION_KEY_PLACEHOLDER,
+                session: None,
+                auth_code: AuthCode::default(),
+            },
+        ))?;
+        match reply {
+            rpc::Reply::Descriptors(descriptors) => {
+                output::print_list(runtime.output(), &descriptors)
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+
+    pub fn exec_ln_keys(
         &self,
         runtime: &mut Client,
         format: &StructuredFormat,
+        id: XpubIdentifier,
+        channel: u32,
     ) -> Result<(), rpc::Error> {
-        const ERR: &'static str = "Error formatting data";
+        debug!(
+            "Deriving Lightning keys of channel #{} from {}",
+            channel, id
+        );
+        let reply = runtime.request(rpc::Request::DeriveLnKeySet(
+            rpc::message::DeriveLnKeySet {
+                key_id: id,
+                channel,
+                auth_code: AuthCode::default(),
+            },
+        ))?;
+        match reply {
+            rpc::Reply::LnKeySet(key_set) => {
+                print_data(runtime.output(), &key_set, format)
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
 
-        debug!("Listing known accounts/extended public keys");
-        let reply = runtime.request(rpc::Request::List)?;
+    pub fn exec_paycode(
+        &self,
+        runtime: &mut Client,
+        format: &StructuredFormat,
+        id: XpubIdentifier,
+        account: u32,
+        counterparty: Option<PaymentCode>,
+        contact: &Option<String>,
+        start: u32,
+        count: u32,
+    ) -> Result<(), rpc::Error> {
+        debug!("Deriving BIP-47 payment code #{} of {}", account, id);
+        let reply = runtime.request(rpc::Request::DerivePaymentCode(
+            rpc::message::DerivePaymentCode {
+                key_id: id,
+                account,
+                counterparty,
+                contact: contact.clone(),
+                start,
+                count,
+                decryption_key: Client::DECRYPTION_KEY_PLACEHOLDER,
+                session: None,
+                auth_code: AuthCode::default(),
+            },
+        ))?;
         match reply {
-            rpc::Reply::Keylist(accounts) => {
-                let result = match format {
-                    #[cfg(feature = "serde_json")]
-                    StructuredFormat::Json => {
-                        serde_json::to_string(&accounts).expect(ERR)
-                    }
-                    #[cfg(feature = "serde_yaml")]
-                    StructuredFormat::Yaml => {
-                        serde_yaml::to_string(&accounts).expect(ERR)
-                    }
-                    #[cfg(feature = "toml")]
-                    StructuredFormat::Toml => {
-                        toml::to_string(&accounts).expect(ERR)
-                    }
-                    StructuredFormat::Hex => {
-                        strict_serialize(&accounts).expect(ERR).to_hex()
-                    }
-                    StructuredFormat::Base64 => {
-                        base64::encode(strict_serialize(&accounts).expect(ERR))
-                    }
-                    _ => unimplemented!(),
-                };
-                println!("{}", result);
-                Ok(())
+            rpc::Reply::PaymentCode(info) => {
+                print_data(runtime.output(), &info, format)
             }
             rpc::Reply::Failure(failure) => {
-                Err(rpc::Error::ServerFailure(failure.clone()))
+                Err(rpc::Error::ServerFailure(failure))
             }
             _ => Err(rpc::Error::UnexpectedServerResponse),
         }
     }
 
-    pub fn exec_derive(
+    pub fn exec_branches(
         &self,
         runtime: &mut Client,
-        id: &XpubIdentifier,
-        path: &DerivationPath,
-        name: &String,
+        id: XpubIdentifier,
+        branches: Branches,
+    ) -> Result<(), rpc::Error> {
+        debug!(
+            "Changing branch layout of keys account {} to {}",
+            id, branches
+        );
+        let reply = runtime.request(rpc::Request::SetBranches(
+            rpc::message::SetBranches {
+                key_id: id,
+                branches,
+                auth_code: AuthCode::default(),
+            },
+        ))?;
+        match reply {
+            rpc::Reply::AccountInfo(info) => {
+                output::print(runtime.output(), &info)
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+
+    pub fn exec_policy(
+        &self,
+        runtime: &mut Client,
+        id: XpubIdentifier,
+        policy: SigningPolicy,
+    ) -> Result<(), rpc::Error> {
+        debug!(
+            "Changing signing policy of keys account {} to {}",
+            id, policy
+        );
+        let reply = runtime.request(rpc::Request::SetPolicy(
+            rpc::message::SetPolicy {
+                key_id: id,
+                policy,
+                auth_code: AuthCode::default(),
+            },
+        ))?;
+        match reply {
+            rpc::Reply::AccountInfo(info) => {
+                output::print_with(runtime.output(), &info, || {
+                    println!("{}", info);
+                    println!("Signing policy: {}", info.policy);
+                })
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+
+    pub fn exec_label(
+        &self,
+        runtime: &mut Client,
+        id: XpubIdentifier,
+        label: &LabelQuery,
+        remove: bool,
+    ) -> Result<(), rpc::Error> {
+        let value = if remove {
+            debug!("Removing label {} of keys account {}", label.key, id);
+            None
+        } else {
+            debug!("Setting label {} of keys account {}", label, id);
+            Some(label.value.clone().unwrap_or_default())
+        };
+        let reply = runtime.request(rpc::Request::SetLabel(
+            rpc::message::SetLabel {
+                key_id: id,
+                label: label.key.clone(),
+                value,
+                auth_code: AuthCode::default(),
+            },
+        ))?;
+        match reply {
+            rpc::Reply::AccountInfo(info) => {
+                output::print(runtime.output(), &info)
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+
+    pub fn exec_update(
+        &self,
+        runtime: &mut Client,
+        id: XpubIdentifier,
+        name: &Option<String>,
         details: &Option<String>,
+        assets: &[AssetId],
+        mode: UpdateMode,
     ) -> Result<(), rpc::Error> {
-        debug!("Deriving new subaccount");
-        let reply =
-            runtime.request(rpc::Request::Derive(rpc::message::Derive {
-                from: *id,
-                path: path.clone(),
+        debug!("Updating keys account {}", id);
+        let assets = if assets.is_empty() && mode != UpdateMode::Replace {
+            None
+        } else {
+            Some(assets.iter().copied().collect())
+        };
+        let reply = runtime.request(rpc::Request::UpdateAccount(
+            rpc::message::UpdateAccount {
+                key_id: id,
                 name: name.clone(),
-                details: details.as_ref().cloned().unwrap_or_default(),
-                assets: Default::default(),
-                decryption_key: secp256k1::key::ONE_KEY,
-                auth_code: 0,
-            }))?;
+                details: details.clone(),
+                assets,
+                mode,
+                auth_code: AuthCode::default(),
+            },
+        ))?;
         match reply {
             rpc::Reply::AccountInfo(info) => {
-                println!("{}", info);
-                Ok(())
+                output::print(runtime.output(), &info)
             }
             rpc::Reply::Failure(failure) => {
-                Err(rpc::Error::ServerFailure(failure.clone()))
+                Err(rpc::Error::ServerFailure(failure))
             }
             _ => Err(rpc::Error::UnexpectedServerResponse),
         }
     }
 
-    pub fn exec_export(
+    pub fn exec_lifecycle(
         &self,
-        _runtime: &mut Client,
-        _id: &XpubIdentifier,
-        _file: &str,
+        runtime: &mut Client,
+        id: XpubIdentifier,
+        state: Lifecycle,
     ) -> Result<(), rpc::Error> {
-        unimplemented!()
+        debug!(
+            "Changing lifecycle state of keys account {} to {}",
+            id, state
+        );
+        let reply = runtime.request(rpc::Request::SetLifecycle(
+            rpc::message::SetLifecycle {
+                key_id: id,
+                state,
+                auth_code: AuthCode::default(),
+            },
+        ))?;
+        match reply {
+            rpc::Reply::AccountInfo(info) => {
+                output::print(runtime.output(), &info)
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+}
+
+/// Prints data in the format given by the global `--output` option; the
+/// plain text output uses command-specific `--format` instead
+fn print_data<T>(
+    output: OutputFormat,
+    data: &T,
+    format: &StructuredFormat,
+) -> Result<(), rpc::Error>
+where
+    T: Serialize + StrictEncode,
+{
+    if output == OutputFormat::Plain {
+        println!("{}", format_data(data, format)?);
+        return Ok(());
     }
+    output::print_with(output, data, || {})
+}
+
+fn format_data<T>(
+    data: &T,
+    format: &StructuredFormat,
+) -> Result<String, io::Error>
+where
+    T: Serialize + StrictEncode,
+{
+    const ERR: &'static str = "Error formatting data";
+
+    Ok(match format {
+        #[cfg(feature = "serde_json")]
+        StructuredFormat::Json => serde_json::to_string(data).expect(ERR),
+        #[cfg(feature = "serde_yaml")]
+        StructuredFormat::Yaml => serde_yaml::to_string(data).expect(ERR),
+        #[cfg(feature = "toml")]
+        StructuredFormat::Toml => toml::to_string(data).expect(ERR),
+        StructuredFormat::Hex => strict_serialize(data).expect(ERR).to_hex(),
+        StructuredFormat::Base64 => {
+            base64::encode(strict_serialize(data).expect(ERR))
+        }
+        _ => return Err(unavailable_format(format)),
+    })
 }
 
 impl XPrivkeyCommand {
+    pub fn exec_import(
+        &self,
+        runtime: &mut Client,
+        name: &str,
+        details: &Option<String>,
+        in_file: &Option<PathBuf>,
+        key_source: &Option<KeySource>,
+        application: Option<KeyApplication>,
+        collision: CollisionPolicy,
+    ) -> Result<(), rpc::Error> {
+        let mut data = vec![];
+        match in_file {
+            Some(filename) => {
+                fs::File::open(filename)?.read_to_end(&mut data)?
+            }
+            None => io::stdin().read_to_end(&mut data)?,
+        };
+        let xprivkey = std::str::from_utf8(&data)
+            .map_err(|err| err.to_string())
+            .and_then(|s| {
+                ExtendedPrivKey::from_str(s.trim())
+                    .map_err(|err| err.to_string())
+            });
+        // Wiping out key data right after parsing
+        data.iter_mut().for_each(|byte| *byte = 0);
+        let xprivkey = xprivkey
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        debug!("Importing extended private key as a new keyring");
+        let reply = runtime.request(rpc::Request::ImportXpriv(
+            rpc::message::ImportXpriv {
+                xprivkey,
+                key_source: key_source.clone(),
+                application,
+                name: name.to_owned(),
+                details: details.clone(),
+                collision,
+                auth_code: AuthCode::default(),
+            },
+        ))?;
+        match reply {
+            rpc::Reply::AccountInfo(info) => {
+                output::print_with(runtime.output(), &info, || {
+                    info!("Keyring imported: {}", info)
+                })
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+
+    /// Requests export approval token and then exports the extended private
+    /// key into the `file`
     pub fn exec_export(
         &self,
-        _runtime: &mut Client,
-        _id: &XpubIdentifier,
-        _file: &str,
+        runtime: &mut Client,
+        id: &XpubIdentifier,
+        file: &str,
     ) -> Result<(), rpc::Error> {
-        unimplemented!()
+        debug!("Requesting approval to export private key {}", id);
+        let reply = runtime.request(rpc::Request::ApproveExport(
+            rpc::message::ApproveExport {
+                key_id: *id,
+                auth_code: AuthCode::default(),
+            },
+        ))?;
+        let approval = match reply {
+            rpc::Reply::Approval(approval) => approval,
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))?
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse)?,
+        };
+        debug!("Private key export is approved: {}", approval);
+        let reply = runtime.request(rpc::Request::ExportXpriv(
+            rpc::message::ExportXpriv {
+                key_id: *id,
+                approval: approval.token,
+                decryption_key: Client::DECRYPTION_KEY_PLACEHOLDER,
+                session: None,
+                auth_code: AuthCode::default(),
+            },
+        ))?;
+        match reply {
+            #[cfg(feature = "export-secrets")]
+            rpc::Reply::XPriv(xpriv) => {
+                fs::write(file, xpriv.to_string())?;
+                output::written(
+                    runtime.output(),
+                    file,
+                    "Extended private key is exported to",
+                )
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+
+    pub fn exec_bip85(
+        &self,
+        runtime: &mut Client,
+        id: XpubIdentifier,
+        application: Bip85Application,
+        index: u32,
+    ) -> Result<(), rpc::Error> {
+        debug!(
+            "Deriving BIP-85 {} secret #{} from {}",
+            application, index, id
+        );
+        let reply = runtime.request(rpc::Request::DeriveEntropy(
+            rpc::message::DeriveEntropy {
+                key_id: id,
+                application,
+                index,
+                decryption_key: Client::DECRYPTION_KEY_PLACEHOLDER,
+                session: None,
+                auth_code: AuthCode::default(),
+            },
+        ))?;
+        match reply {
+            #[cfg(feature = "export-secrets")]
+            rpc::Reply::DerivedSecret(secret) => {
+                output::print(runtime.output(), &Secret { secret })
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
     }
 }
 
 impl SignCommand {
+    pub fn exec_sign_text(
+        &self,
+        runtime: &mut Client,
+        id: XpubIdentifier,
+        message: String,
+    ) -> Result<(), rpc::Error> {
+        debug!("Signing text message with private key");
+        let reply = runtime.request(rpc::Request::SignMessage(
+            rpc::message::SignMessage {
+                key_id: id,
+                message,
+                decryption_key: Client::DECRYPTION_KEY_PLACEHOLDER,
+                session: None,
+                auth_code: AuthCode::default(),
+            },
+        ))?;
+        match reply {
+            rpc::Reply::MessageSignature(signature) => output::print(
+                runtime.output(),
+                &Signature {
+                    signature: base64::encode(&signature),
+                },
+            ),
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+
+    pub fn exec_sign_file(
+        &self,
+        runtime: &mut Client,
+        format: &StructuredFormat,
+        id: XpubIdentifier,
+        digest: sha256::Hash,
+        out_file: &Option<PathBuf>,
+    ) -> Result<(), rpc::Error> {
+        debug!("Signing file with private key");
+        // The daemon hashes signed data once more, so the signature commits
+        // to SHA256d hash of the file contents
+        let reply = runtime.request(rpc::Request::SignData(
+            rpc::message::SignData {
+                key_id: id,
+                data: digest.to_vec(),
+                decryption_key: Client::DECRYPTION_KEY_PLACEHOLDER,
+                session: None,
+                auth_code: AuthCode::default(),
+            },
+        ))?;
+        let signature = match reply {
+            rpc::Reply::Signature(signature) => signature.serialize_der(),
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))?
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse)?,
+        };
+        write_encoded(&signature, format, out_file)?;
+        Ok(())
+    }
+
     pub fn exec_sign_key(
         &self,
         runtime: &mut Client,
@@ -327,13 +2317,18 @@ impl SignCommand {
         let reply =
             runtime.request(rpc::Request::SignKey(rpc::message::SignKey {
                 key_id: id,
-                decryption_key: secp256k1::key::ONE_KEY,
-                auth_code: 0,
+                decryption_key: Client::DECRYPTION_KEY_PLACEHOLDER,
+                session: None,
+                auth_code: AuthCode::default(),
             }))?;
         match reply {
             rpc::Reply::Signature(signature) => {
-                info!("New signature created: {}", signature);
-                Ok(())
+                let result = Signature {
+                    signature: signature.to_string(),
+                };
+                output::print_with(runtime.output(), &result, || {
+                    info!("New signature created: {}", signature)
+                })
             }
             rpc::Reply::Failure(failure) => {
                 Err(rpc::Error::ServerFailure(failure))
@@ -342,3 +2337,101 @@ impl SignCommand {
         }
     }
 }
+
+fn read_message(
+    message: &Option<String>,
+    in_file: &Option<PathBuf>,
+) -> Result<String, io::Error> {
+    Ok(match (message, in_file) {
+        (Some(message), _) => message.clone(),
+        (None, Some(filename)) => fs::read_to_string(filename)?,
+        (None, None) => {
+            let mut message = String::new();
+            io::stdin().read_to_string(&mut message)?;
+            message
+        }
+    })
+}
+
+/// Computes SHA256 hash of the file contents, reading data from STDIN if no
+/// file is given
+fn file_digest(in_file: &Option<PathBuf>) -> Result<sha256::Hash, io::Error> {
+    let mut reader = match in_file {
+        Some(filename) => Box::new(fs::File::open(filename)?) as Box<dyn Read>,
+        None => Box::new(io::stdin()) as Box<dyn Read>,
+    };
+    let mut engine = sha256::Hash::engine();
+    io::copy(&mut reader, &mut engine)?;
+    Ok(sha256::Hash::from_engine(engine))
+}
+
+/// Decodes PSBT from binary data or from a string in hex or base64 encoding,
+/// detecting the format automatically
+fn decode_psbt(data: &[u8]) -> Result<Psbt, io::Error> {
+    const PSBT_MAGIC: &[u8] = b"psbt\xff";
+    let invalid = |err: String| io::Error::new(io::ErrorKind::InvalidData, err);
+
+    let data = if data.starts_with(PSBT_MAGIC) {
+        data.to_vec()
+    } else {
+        let text = std::str::from_utf8(data)
+            .map_err(|_| invalid("PSBT is not in a known format".to_string()))?
+            .trim();
+        Vec::<u8>::from_hex(text)
+            .ok()
+            .or_else(|| base64::decode(text).ok())
+            .ok_or_else(|| {
+                invalid(
+                    "PSBT is neither binary nor hex or base64 string"
+                        .to_string(),
+                )
+            })?
+    };
+    deserialize(&data).map_err(|err| invalid(err.to_string()))
+}
+
+/// Writes binary `data` to the output file or STDOUT in a given format; only
+/// binary, hex and base64 formats are supported
+fn write_encoded(
+    data: &[u8],
+    format: &StructuredFormat,
+    out_file: &Option<PathBuf>,
+) -> Result<(), io::Error> {
+    let data = match format {
+        StructuredFormat::Bin => data.to_vec(),
+        StructuredFormat::Hex => data.to_hex().into_bytes(),
+        StructuredFormat::Base64 => base64::encode(data).into_bytes(),
+        _ => return Err(unsupported_format(format)),
+    };
+    match out_file {
+        Some(filename) => fs::write(filename, data),
+        None => {
+            io::stdout().write_all(&data)?;
+            if !matches!(format, StructuredFormat::Bin) {
+                println!();
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Error for the formats known to [`StructuredFormat`], which serialization
+/// is not compiled into this build
+fn unavailable_format(format: &StructuredFormat) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+            "{:?} format is not supported by this build; supported formats \
+             are {}",
+            format,
+            STRUCTURED_FORMATS.join(", ")
+        ),
+    )
+}
+
+fn unsupported_format(format: &StructuredFormat) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("binary data can't be represented in {:?} format", format),
+    )
+}
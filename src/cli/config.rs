@@ -14,6 +14,7 @@
 use ::core::convert::{TryFrom, TryInto};
 use ::core::fmt::Display;
 use ::core::str::FromStr;
+use ::serde_with::hex::Hex;
 use ::serde_with::DisplayFromStr;
 use ::settings::{self, Config as Settings, ConfigError};
 use ::std::fs::File;
@@ -24,9 +25,10 @@ use bitcoin::secp256k1;
 use internet2::zmqsocket::ZmqSocketAddr;
 use microservices::shell::LogLevel;
 
-use super::Opts;
+use super::{Opts, OutputFormat, SecretSource};
 use crate::error::ConfigInitError;
 use crate::opts::{KEYRING_DATA_DIR, KEYRING_RPC_SOCKET_NAME};
+use crate::rpc::types::{SessionToken, VaultId};
 
 // We need config structure since not all of the parameters can be specified
 // via environment and command-line arguments. Thus we need a config file and
@@ -46,6 +48,49 @@ pub struct Config {
     pub log_level: LogLevel,
     #[serde_as(as = "DisplayFromStr")]
     pub endpoint: ZmqSocketAddr,
+    #[serde(skip)]
+    pub session: Option<SessionToken>,
+    /// Source of the key decrypting private keys of the vault encrypted
+    /// with the daemon node key; if absent, the node key is used
+    #[serde(skip)]
+    pub vault_key: Option<SecretSource>,
+    /// Source of the passphrase unlocking the vault encrypted with a
+    /// passphrase for the requests sent without a session
+    #[serde(skip)]
+    pub vault_passphrase: Option<SecretSource>,
+    /// Vault requests are routed to; see [`crate::rpc::routed`]
+    #[serde(skip)]
+    pub vault: Option<VaultId>,
+    /// Format of the command results printed to STDOUT
+    #[serde(skip)]
+    pub output: OutputFormat,
+    /// Name of the client under which its auth secret is configured in the
+    /// daemon
+    #[serde(default)]
+    pub auth_client: Option<String>,
+    /// Secret shared with the daemon used to authorize requests
+    #[serde_as(as = "Option<Hex>")]
+    #[serde(default)]
+    pub auth_secret: Option<Vec<u8>>,
+    /// Whether requests are authorized with time-stamped auth codes instead
+    /// of requesting a challenge from the daemon for each of them
+    #[serde(default)]
+    pub timestamp_auth: bool,
+    /// Daemon node id used to establish encrypted channel and seal requests;
+    /// required if either of them is used
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub daemon_id: Option<secp256k1::PublicKey>,
+    /// Whether requests must be sent over encrypted channel. If not given,
+    /// encryption is used for all endpoints except local ones
+    #[serde(default)]
+    pub transport_encryption: Option<bool>,
+    /// Whether requests carrying secrets are sealed to the daemon node id,
+    /// with the replies sealed to the client node id, so the secrets are not
+    /// exposed to the proxies and transports between the client and the
+    /// daemon
+    #[serde(default)]
+    pub payload_encryption: bool,
 }
 
 impl TryFrom<Opts> for Config {
@@ -91,6 +136,13 @@ impl TryFrom<Opts> for Config {
             .rpc_socket
             .try_into()
             .expect("Only ZMQ RPC is supported");
+        me.session = opts.session;
+        me.vault = opts.vault;
+        me.output = opts.output;
+        me.vault_key = opts
+            .vault_key
+            .or_else(|| SecretSource::from_env("KEYRING_VAULT_KEY"));
+        me.vault_passphrase = opts.vault_passphrase;
 
         if opts.shared.init {
             if let Err(err) = init_config(&conf_file, me) {
@@ -126,6 +178,18 @@ impl Config {
     pub fn node_id(&self) -> secp256k1::PublicKey {
         secp256k1::PublicKey::from_secret_key(&crate::SECP256K1, &self.node_key)
     }
+
+    /// Detects whether the RPC must go over encrypted channel: unless
+    /// configured explicitly, only IPC, in-process and loopback TCP
+    /// endpoints are used without encryption
+    pub fn is_encrypted(&self) -> bool {
+        self.transport_encryption
+            .unwrap_or_else(|| match self.endpoint {
+                ZmqSocketAddr::Inproc(_) | ZmqSocketAddr::Ipc(_) => false,
+                ZmqSocketAddr::Tcp(addr) => !addr.ip().is_loopback(),
+                _ => true,
+            })
+    }
 }
 
 impl Default for Config {
@@ -142,6 +206,17 @@ impl Default for Config {
             endpoint: KEYRING_RPC_SOCKET_NAME
                 .parse()
                 .expect("Broken KEYRING_RPC_SOCKET_NAME value"),
+            session: None,
+            vault_key: None,
+            vault_passphrase: None,
+            vault: None,
+            output: OutputFormat::Plain,
+            auth_client: None,
+            auth_secret: None,
+            timestamp_auth: false,
+            daemon_id: None,
+            transport_encryption: None,
+            payload_encryption: false,
         }
     }
 }
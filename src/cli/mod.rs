@@ -16,9 +16,20 @@ mod command;
 mod config;
 pub mod format;
 mod opts;
+mod output;
+pub mod progress;
+mod qr;
+mod secret;
+mod shell;
 
 pub use client::Client;
 pub use config::Config;
+#[cfg(feature = "node")]
+pub use opts::VaultCommand;
 pub use opts::{
-    Command, Opts, SeedCommand, SignCommand, XPrivkeyCommand, XPubkeyCommand,
+    Command, IdentityCommand, MuSigCommand, MultisigCommand, Opts,
+    OutputFormat, PsbtCommand, QrOpts, RevocationCommand, SandboxCommand,
+    SecretSource, SeedCommand, ShellLine, SignCommand, TxCommand, UtilCommand,
+    VerifyCommand, XPrivkeyCommand, XPubkeyCommand, BINARY_FORMATS,
+    STRUCTURED_FORMATS,
 };
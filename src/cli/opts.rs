@@ -11,17 +11,163 @@
 // along with this software.
 // If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
 
-use clap::{AppSettings, Clap, ValueHint};
+use clap::{AppSettings, ArgEnum, Clap, ValueHint};
 use std::path::PathBuf;
+use std::str::FromStr;
 
 use bitcoin::hashes::hex::FromHex;
-use bitcoin::util::bip32::DerivationPath;
-use bitcoin::XpubIdentifier;
+use bitcoin::hashes::sha256;
+use bitcoin::secp256k1;
+use bitcoin::util::bip32::{
+    DerivationPath, ExtendedPubKey, Fingerprint, KeySource,
+};
+use bitcoin::{Address, XpubIdentifier};
+use lnpbp::chain::AssetId;
 use lnpbp::Chain;
 use microservices::StructuredFormat;
 use slip132::KeyApplication;
 
+use crate::derivation;
+use crate::lifecycle::Lifecycle;
+use crate::rpc::types::{
+    Bip85Application, CollisionPolicy, CommitmentSecret, CosignerKey,
+    DerivationTemplate, KeyPrefix, LabelQuery, LnChannelId, MuSigNonce,
+    MuSigSessionId, MultisigId, PaymentCode, PsbtInput, PsbtOutput, RateLimit,
+    SessionToken, UpdateMode, VaultId,
+};
+
 pub const KEYRING_CLI_CONFIG: &'static str = "{data_dir}/keyring-cli.toml";
+pub const KEYRING_CLI_HISTORY: &'static str = "{data_dir}/keyring-cli.history";
+
+/// Structured data formats supported by this build
+pub const STRUCTURED_FORMATS: &[&str] = &[
+    #[cfg(feature = "serde_yaml")]
+    "yaml",
+    #[cfg(feature = "serde_json")]
+    "json",
+    #[cfg(feature = "toml")]
+    "toml",
+    "bin",
+    "hex",
+    "base64",
+];
+
+/// Formats in which binary data like signatures and PSBTs are represented
+pub const BINARY_FORMATS: &[&str] = &["bin", "hex", "base64"];
+
+/// Parses `--format` argument value. Formats which serialization is not
+/// compiled into this build are rejected, even if they are known to
+/// [`StructuredFormat`].
+pub fn parse_format(format: &str) -> Result<StructuredFormat, String> {
+    if !STRUCTURED_FORMATS.contains(&format) {
+        return Err(format!(
+            "`{}` format is not supported by this build; supported formats \
+             are {}",
+            format,
+            STRUCTURED_FORMATS.join(", ")
+        ));
+    }
+    StructuredFormat::from_str(format, false)
+}
+
+/// Format of the command results printed to STDOUT, selected with global
+/// `--output` option
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum OutputFormat {
+    /// Human-readable text
+    Plain,
+
+    /// Aligned table with a row per item and a column per field
+    Table,
+
+    /// JSON document
+    Json,
+
+    /// YAML document
+    Yaml,
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        OutputFormat::Plain
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "plain" => OutputFormat::Plain,
+            "table" => OutputFormat::Table,
+            "json" => OutputFormat::Json,
+            "yaml" => OutputFormat::Yaml,
+            _ => {
+                return Err(format!(
+                    "unknown output format `{}`; possible values are plain, \
+                     table, json and yaml",
+                    s
+                ))
+            }
+        })
+    }
+}
+
+/// Source of a secret given to a command-line option: passphrase,
+/// decryption key or mnemonic. Secrets themselves are never accepted as
+/// argument values, since these are kept in the shell history and are
+/// visible to other users in the process listings.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum SecretSource {
+    /// `@<path>`: the secret is read from a file
+    File(PathBuf),
+
+    /// `env:<NAME>`: the secret is taken from an environment variable
+    Env(String),
+
+    /// `fd:<N>`: the secret is read from a file descriptor inherited from
+    /// the parent process, which is closed afterwards
+    Fd(i32),
+
+    /// `keychain:<ENTRY>`: the secret is taken from the operating system
+    /// keychain entry of `keyring-cli` service
+    #[cfg(feature = "os-keychain")]
+    Keychain(String),
+
+    /// `-`: the secret is read from STDIN, with a prompt; the secret typed
+    /// in the terminal is not echoed
+    Prompt,
+}
+
+impl FromStr for SecretSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "-" {
+            Ok(SecretSource::Prompt)
+        } else if let Some(path) = s.strip_prefix('@') {
+            Ok(SecretSource::File(PathBuf::from(path)))
+        } else if let Some(name) = s.strip_prefix("env:") {
+            Ok(SecretSource::Env(name.to_owned()))
+        } else if let Some(fd) = s.strip_prefix("fd:") {
+            fd.parse().map(SecretSource::Fd).map_err(|_| {
+                format!("`{}` is not a valid file descriptor number", fd)
+            })
+        } else if let Some(_entry) = s.strip_prefix("keychain:") {
+            #[cfg(feature = "os-keychain")]
+            return Ok(SecretSource::Keychain(_entry.to_owned()));
+            #[cfg(not(feature = "os-keychain"))]
+            Err("keychain support is not compiled into this build".to_owned())
+        } else {
+            Err(
+                "secrets can't be given in the command line; use `@<file>`, \
+                 `env:<VARIABLE>`, `fd:<N>`, `keychain:<ENTRY>` or `-` to \
+                 read the secret from STDIN"
+                    .to_owned(),
+            )
+        }
+    }
+}
 
 #[derive(Clap, Clone, Debug)]
 #[clap(
@@ -49,6 +195,50 @@ pub struct Opts {
     )]
     pub config: String,
 
+    /// Token of the unlocked vault session returned by `unlock` command. If
+    /// provided, operations requiring private keys use the session instead
+    /// of the decryption key
+    #[clap(long, global = true, env = "KEYRING_SESSION")]
+    pub session: Option<SessionToken>,
+
+    /// Source of the vault decryption key used by the operations with
+    /// private keys when no session is given and the vault is encrypted
+    /// with the daemon node key: `@<file>`, `env:<VARIABLE>`, `fd:<N>`,
+    /// `keychain:<ENTRY>` or `-` for the prompt. If not given, the key is
+    /// taken from `KEYRING_VAULT_KEY` environment variable or from the file
+    /// descriptor in `KEYRING_VAULT_KEY_FD`; otherwise the node key of the
+    /// configuration file is used.
+    #[clap(long, global = true, value_name = "SOURCE")]
+    pub vault_key: Option<SecretSource>,
+
+    /// Source of the passphrase of the vault encrypted with a passphrase,
+    /// which is unlocked for the operations with private keys when no
+    /// session is given; the sources are the same as for `--vault-key`. If
+    /// not given, the passphrase is taken from `KEYRING_PASSPHRASE`
+    /// environment variable or from the file descriptor in
+    /// `KEYRING_PASSPHRASE_FD`; otherwise it is prompted for.
+    #[clap(long, global = true, value_name = "SOURCE")]
+    pub vault_passphrase: Option<SecretSource>,
+
+    /// Format of the command results: `plain` text, `table`, `json` or
+    /// `yaml`. Structured formats use the same field names in all releases,
+    /// so scripts can rely on them, and take precedence over `--format` of
+    /// the commands printing structured data.
+    #[clap(
+        long,
+        global = true,
+        env = "KEYRING_OUTPUT",
+        default_value = "plain",
+        possible_values = &["plain", "table", "json", "yaml"]
+    )]
+    pub output: OutputFormat,
+
+    /// Name of the daemon vault serving the requests, as listed by `vault
+    /// list` command. If not provided, the daemon routes requests by the
+    /// chain of a new keyring or by the vault containing the requested key.
+    #[clap(long, global = true, env = "KEYRING_VAULT")]
+    pub vault: Option<VaultId>,
+
     /// Command to execute
     #[clap(subcommand)]
     pub command: Command,
@@ -58,11 +248,69 @@ impl Opts {
     pub fn process(&mut self) {
         self.shared.process();
         self.shared.process_dir(&mut self.config);
+        if let Command::Shell {
+            ref mut history, ..
+        } = self.command
+        {
+            self.shared.process_dir(history);
+        }
     }
 }
 
+/// Command line entered into the interactive shell started with `shell`
+/// command
+#[derive(Clap, Clone, Debug)]
+#[clap(
+    name = "keyring",
+    setting = AppSettings::NoBinaryName,
+    setting = AppSettings::ColoredHelp
+)]
+pub struct ShellLine {
+    /// Command to execute
+    #[clap(subcommand)]
+    pub command: Command,
+}
+
 #[derive(Clap, Clone, Debug)]
 pub enum Command {
+    /// Reports daemon status: uptime, vault driver, number of keyrings and
+    /// accounts, lock state and RPC protocol version
+    Status {
+        #[clap(
+            short,
+            long,
+            possible_values = STRUCTURED_FORMATS,
+            parse(try_from_str = parse_format),
+            default_value = "yaml"
+        )]
+        format: StructuredFormat,
+    },
+
+    /// Requests attestation quote from the daemon running inside a trusted
+    /// execution environment and checks that it is bound to the daemon node
+    /// id and a fresh nonce
+    Attest {
+        /// File to save the raw quote to
+        #[clap(long, value_name = "FILE")]
+        quote: Option<PathBuf>,
+
+        /// Program verifying the quote with the platform vendor. It is run
+        /// with the platform name and hex-encoded report data as arguments
+        /// and receives the quote on STDIN; non-zero exit status rejects
+        /// the attestation
+        #[clap(long, value_name = "PROGRAM")]
+        verifier: Option<String>,
+
+        #[clap(
+            short,
+            long,
+            possible_values = STRUCTURED_FORMATS,
+            parse(try_from_str = parse_format),
+            default_value = "yaml"
+        )]
+        format: StructuredFormat,
+    },
+
     /// Seed operations: generation, import, export
     Seed {
         /// Subcommand specifying particular operation
@@ -90,6 +338,231 @@ pub enum Command {
         #[clap(subcommand)]
         subcommand: SignCommand,
     },
+
+    /// Unlocks vault encrypted with a passphrase for the time specified in
+    /// the daemon configuration
+    Unlock {
+        /// Source of the vault passphrase: `@<file>`, `env:<VARIABLE>` or
+        /// `-` for STDIN. If not given, the passphrase is taken from
+        /// `KEYRING_PASSPHRASE` environment variable or read from STDIN
+        #[clap(long, value_name = "SOURCE")]
+        passphrase: Option<SecretSource>,
+    },
+
+    /// Starts interactive shell executing commands over a single connection
+    /// to the daemon, with command history and completion of account ids.
+    /// The vault session unlocked in the shell is used by all its commands
+    /// and is locked on exit.
+    Shell {
+        /// File keeping the command history
+        #[clap(
+            long,
+            default_value = KEYRING_CLI_HISTORY,
+            value_hint = ValueHint::FilePath
+        )]
+        history: String,
+
+        /// Unlocks the vault when the shell starts, instead of asking for
+        /// the passphrase on the first command requiring private keys
+        #[clap(long)]
+        unlock: bool,
+
+        /// Source of the vault passphrase: `@<file>`, `env:<VARIABLE>` or
+        /// `-` for STDIN. If not given, the passphrase is taken from
+        /// `KEYRING_PASSPHRASE` environment variable or read from STDIN
+        #[clap(long, value_name = "SOURCE")]
+        passphrase: Option<SecretSource>,
+    },
+
+    /// Locks unlocked vault session, wiping decryption key from the daemon
+    /// memory
+    Lock {
+        /// Session token returned by `unlock` command
+        #[clap(env = "KEYRING_SESSION")]
+        session: SessionToken,
+    },
+
+    /// Makes the daemon re-read its configuration file and apply the changed
+    /// log level, unlock timeout, passphrase, client and export settings.
+    ///
+    /// Requires the client secret of a daemon administrator.
+    Reconfigure,
+
+    /// Manages accounts derived into the sandbox of unlocked vault session
+    /// with `xpub derive --sandbox`
+    Sandbox {
+        /// Subcommand specifying particular operation
+        #[clap(subcommand)]
+        subcommand: SandboxCommand,
+    },
+
+    /// Verifies signatures produced by `sign` command
+    Verify {
+        /// Subcommand specifying particular type of signature
+        #[clap(subcommand)]
+        subcommand: VerifyCommand,
+    },
+
+    /// Local operations with PSBTs exchanged between cosigners
+    Psbt {
+        /// Subcommand specifying particular operation
+        #[clap(subcommand)]
+        subcommand: PsbtCommand,
+    },
+
+    /// Composes transactions spending funds of the vault accounts
+    Tx {
+        /// Subcommand specifying particular operation
+        #[clap(subcommand)]
+        subcommand: TxCommand,
+    },
+
+    /// Identity keys for Nostr/DID-style protocols
+    Identity {
+        /// Subcommand specifying particular operation
+        #[clap(subcommand)]
+        subcommand: IdentityCommand,
+    },
+
+    /// Multisig groups of the vault accounts and external cosigners
+    Multisig {
+        /// Subcommand specifying particular operation
+        #[clap(subcommand)]
+        subcommand: MultisigCommand,
+    },
+
+    /// MuSig2 signing sessions producing aggregated Schnorr signatures
+    /// together with other participants
+    Musig {
+        /// Subcommand specifying particular operation
+        #[clap(subcommand)]
+        subcommand: MuSigCommand,
+    },
+
+    /// Storage of Lightning channel revocation secrets
+    Revocation {
+        /// Subcommand specifying particular operation
+        #[clap(subcommand)]
+        subcommand: RevocationCommand,
+    },
+
+    /// Local utilities which do not require connection to the daemon
+    Util {
+        #[clap(subcommand)]
+        subcommand: UtilCommand,
+    },
+
+    /// Vault backups and local operations with vault files
+    #[cfg(feature = "node")]
+    Vault {
+        #[clap(subcommand)]
+        subcommand: VaultCommand,
+    },
+}
+
+#[cfg(feature = "node")]
+#[derive(Clap, Clone, Debug)]
+pub enum VaultCommand {
+    /// Lists vaults served by the daemon with their storage drivers and
+    /// chains routed to them
+    List,
+
+    /// Compares two vault snapshots (like backup copies of the vault file)
+    /// and reports added, removed and modified keyrings and accounts. Only
+    /// metadata are compared; the snapshots are not decrypted.
+    Diff {
+        /// The older vault snapshot
+        snapshot_a: PathBuf,
+
+        /// The newer vault snapshot
+        snapshot_b: PathBuf,
+    },
+
+    /// Replays hash-chained journal of the vault updates, checking the chain
+    /// of its records and that the vault file contents are consistent with
+    /// the recorded history. Reports the first record from which the journal
+    /// or the vault diverges.
+    Journal {
+        /// Journal file written by the daemon
+        #[clap(value_hint = ValueHint::FilePath)]
+        journal: PathBuf,
+
+        /// Vault file (or its snapshot) which updates are journaled
+        #[clap(value_hint = ValueHint::FilePath)]
+        vault: PathBuf,
+    },
+
+    /// Requests daemon to write encrypted backup of the current vault state
+    /// into its backup directory
+    Backup,
+
+    /// Replaces all vault data with the content of an encrypted backup
+    /// written by the daemon. The backup must be encrypted for the node key
+    /// of the daemon performing the restore.
+    Restore {
+        /// Backup file
+        file: PathBuf,
+    },
+
+    /// Writes portable dump of all vault keyrings and their metadata, which
+    /// can be imported by a daemon using any storage driver. Private keys
+    /// stay encrypted with the vault key, so the importing daemon must use
+    /// the same node key or vault passphrase.
+    Export {
+        /// File to write the dump into
+        #[clap(value_hint = ValueHint::FilePath)]
+        file: PathBuf,
+
+        /// Encrypts the dump with a passphrase, read from the given source:
+        /// `@<file>`, `env:<VARIABLE>` or `-` for STDIN
+        #[clap(long, value_name = "SOURCE")]
+        encrypt: Option<SecretSource>,
+    },
+
+    /// Imports keyrings from the vault dump written by `vault export`.
+    /// Keyrings already present in the vault are skipped.
+    Import {
+        /// Vault dump file
+        #[clap(value_hint = ValueHint::FilePath)]
+        file: PathBuf,
+
+        /// Replaces all vault keyrings with the dump content
+        #[clap(long)]
+        replace: bool,
+
+        /// Source of the passphrase of the encrypted dump: `@<file>`,
+        /// `env:<VARIABLE>` or `-` for STDIN
+        #[clap(long, value_name = "SOURCE")]
+        passphrase: Option<SecretSource>,
+    },
+
+    /// Writes example vault with fake keys into a given directory, one file
+    /// per vault format supported by this build. The example uses all
+    /// fields of the current vault schema and is a reference for the tools
+    /// reading vault files. Private keys of the example accounts are
+    /// decryptable with the key printed by the command.
+    Example {
+        /// Directory to write the example vault files into
+        #[clap(default_value = ".", value_hint = ValueHint::DirPath)]
+        dir: PathBuf,
+    },
+}
+
+#[derive(Clap, Clone, Debug)]
+pub enum SandboxCommand {
+    /// Stores all sandboxed accounts of the session in the vault
+    Commit {
+        /// Session token returned by `unlock` command
+        #[clap(env = "KEYRING_SESSION")]
+        session: SessionToken,
+    },
+
+    /// Discards all sandboxed accounts of the session
+    Discard {
+        /// Session token returned by `unlock` command
+        #[clap(env = "KEYRING_SESSION")]
+        session: SessionToken,
+    },
 }
 
 #[derive(Clap, Clone, Debug)]
@@ -115,19 +588,135 @@ pub enum SeedCommand {
         id: XpubIdentifier,
     },
 
+    /// Exports extended public key of the account, printing it or
+    /// rendering it as QR code and BC-UR
     Export {
+        /// Extended public key identifier of the account
         #[clap(parse(try_from_str = FromHex::from_hex))]
         id: XpubIdentifier,
 
-        file: String,
+        /// File to write the exported data into
+        #[clap(value_hint = ValueHint::FilePath)]
+        file: Option<PathBuf>,
+
+        /// Exports output descriptors of the account instead of its extended
+        /// public key
+        #[clap(long)]
+        descriptor: bool,
+
+        /// Serializes extended public key with the given SLIP-132 prefix:
+        /// xpub, ypub, zpub, Ypub, Zpub for mainnet keys or tpub, upub,
+        /// vpub, Upub, Vpub for testnet keys
+        #[clap(
+            long = "as",
+            value_name = "PREFIX",
+            conflicts_with = "descriptor"
+        )]
+        prefix: Option<KeyPrefix>,
+
+        #[clap(flatten)]
+        render: QrOpts,
+    },
+
+    /// Deletes keyring with all its subaccounts. By default, the keyring is
+    /// archived and its data are kept in the vault
+    Delete {
+        /// Master extended public key identifier of the keyring
+        #[clap(parse(try_from_str = FromHex::from_hex))]
+        id: XpubIdentifier,
+
+        /// Remove keyring data from the vault instead of archiving it
+        #[clap(long)]
+        purge: bool,
     },
 }
 
+/// Options rendering exported extended public key or descriptor for the
+/// import into airgapped wallets
+#[derive(Clap, Clone, PartialEq, Eq, Debug)]
+pub struct QrOpts {
+    /// Shows QR code in the terminal instead of printing the text
+    #[clap(long)]
+    pub qr: bool,
+
+    /// Writes QR code as PNG image into the file
+    #[clap(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub png: Option<PathBuf>,
+
+    /// Encodes the data as BC-UR. Data longer than the fragment length are
+    /// split into multi-part UR, printed one fragment per line or, together
+    /// with `--qr`, shown as animated QR code
+    #[clap(long)]
+    pub ur: bool,
+
+    /// Maximal length of BC-UR fragment, in bytes
+    #[clap(long, default_value = "100")]
+    pub fragment_len: usize,
+}
+
+impl QrOpts {
+    /// Detects whether any of the rendering options is given
+    pub fn is_set(&self) -> bool {
+        self.qr || self.ur || self.png.is_some()
+    }
+}
+
 #[derive(Clap, Clone, Debug)]
 pub enum XPubkeyCommand {
     List {
-        #[clap(short, long, arg_enum, default_value = "yaml")]
+        #[clap(
+            short,
+            long,
+            possible_values = STRUCTURED_FORMATS,
+            parse(try_from_str = parse_format),
+            default_value = "yaml"
+        )]
         format: StructuredFormat,
+
+        /// Requests daemon to add balance information to each of the accounts
+        /// using the blockchain data source configured for the daemon
+        #[clap(short, long)]
+        balances: bool,
+
+        /// Number of consecutive unused addresses after which the balance
+        /// scan of a derivation branch stops
+        #[clap(short, long, default_value = "20", requires = "balances")]
+        gap_limit: u32,
+
+        /// Lists only accounts of the keyrings created for the given chain
+        #[clap(long)]
+        chain: Option<Chain>,
+    },
+
+    /// Finds accounts matching all of the given criteria
+    Find {
+        #[clap(
+            short,
+            long,
+            possible_values = STRUCTURED_FORMATS,
+            parse(try_from_str = parse_format),
+            default_value = "yaml"
+        )]
+        format: StructuredFormat,
+
+        /// Label the account must have, given as `<key>` to match any value
+        /// or as `<key>=<value>`; may be given multiple times
+        #[clap(short, long = "label")]
+        labels: Vec<LabelQuery>,
+
+        /// Asset id the account must be known to hold
+        #[clap(long, parse(try_from_str = FromHex::from_hex))]
+        asset: Option<AssetId>,
+
+        /// Application scope of the account key. Possible values are:
+        /// pkh, sh, wpkh, wsh, wpkh-sh, wsh-sh
+        #[clap(long)]
+        application: Option<KeyApplication>,
+
+        /// Fingerprint of the account key or of the master key it was
+        /// derived from
+        #[clap(long, parse(try_from_str = FromHex::from_hex))]
+        fingerprint: Option<Fingerprint>,
     },
 
     /// Derives new keys account from a given master extended public key
@@ -137,7 +726,10 @@ pub enum XPubkeyCommand {
         #[clap(parse(try_from_str = FromHex::from_hex))]
         id: XpubIdentifier,
 
-        /// Subaccount derivation path starting with `m` prefix
+        /// Subaccount derivation path, like `m/84'/0'/0'`. Paths of the
+        /// standard purposes (BIP-44, 48, 49, 84 and 86) are checked against
+        /// the purpose layout
+        #[clap(parse(try_from_str = derivation::parse))]
         path: DerivationPath,
 
         /// Name for newly generated account with a seed phrase
@@ -145,6 +737,137 @@ pub enum XPubkeyCommand {
 
         /// More details information about the new account
         details: Option<String>,
+
+        /// Keep the account in the sandbox of the unlocked vault session
+        /// instead of storing it in the vault. Sandboxed accounts are
+        /// discarded when the session ends unless committed with `sandbox
+        /// commit`
+        #[clap(long)]
+        sandbox: bool,
+
+        /// Renders output descriptors of the new account instead of its
+        /// extended public key; used with the rendering options only
+        #[clap(long)]
+        descriptor: bool,
+
+        #[clap(flatten)]
+        render: QrOpts,
+    },
+
+    /// Discovers accounts of the keyring used on-chain, scanning BIP-44,
+    /// BIP-49 and BIP-84 accounts with the blockchain data source configured
+    /// for the daemon, and adds them to the keyring
+    Discover {
+        #[clap(
+            short,
+            long,
+            possible_values = STRUCTURED_FORMATS,
+            parse(try_from_str = parse_format),
+            default_value = "yaml"
+        )]
+        format: StructuredFormat,
+
+        /// Master extended public key identifier of the keyring
+        #[clap(parse(try_from_str = FromHex::from_hex))]
+        id: XpubIdentifier,
+
+        /// Number of consecutive unused addresses after which the scan of a
+        /// derivation branch stops
+        #[clap(short, long, default_value = "20")]
+        gap_limit: u32,
+    },
+
+    /// Derives range of public keys and addresses from the account using
+    /// derivation template with a wildcard, like `0/*`
+    Range {
+        #[clap(
+            short,
+            long,
+            possible_values = STRUCTURED_FORMATS,
+            parse(try_from_str = parse_format),
+            default_value = "yaml"
+        )]
+        format: StructuredFormat,
+
+        /// Extended public key identifier of the account
+        #[clap(parse(try_from_str = FromHex::from_hex))]
+        id: XpubIdentifier,
+
+        /// Derivation template with a single `*` wildcard. If not given,
+        /// the account external branch is used
+        template: Option<DerivationTemplate>,
+
+        /// Use the account internal (change) branch instead of the external
+        /// one; ignored if the template is given
+        #[clap(long, conflicts_with = "template")]
+        internal: bool,
+
+        /// First index of the range
+        #[clap(long, default_value = "0")]
+        start: u32,
+
+        /// Number of keys to derive
+        #[clap(long, default_value = "20")]
+        count: u32,
+    },
+
+    /// Imports extended public key as a watch-only account, which can be
+    /// used for key derivation but not for signing
+    Watch {
+        /// Extended public key to import
+        xpubkey: ExtendedPubKey,
+
+        /// Name for the imported account
+        name: String,
+
+        /// More details information about the imported account
+        details: Option<String>,
+
+        /// Origin of the extended public key in the form of master key
+        /// fingerprint followed by the derivation path, like
+        /// `d34db33f/84'/0'/0'`
+        #[clap(long, parse(try_from_str = parse_key_source))]
+        origin: Option<KeySource>,
+
+        /// Application scope of the key. Possible values are:
+        /// pkh, sh, wpkh, wsh, wpkh-sh, wsh-sh
+        #[clap(long)]
+        application: Option<KeyApplication>,
+
+        /// Handling of the key already present in the vault. Possible values
+        /// are: reject, skip, merge (replace account name and details),
+        /// alias (add the name to the account aliases)
+        #[clap(long, default_value = "reject")]
+        on_collision: CollisionPolicy,
+    },
+
+    /// Imports watch-only accounts from a list of output descriptors.
+    /// Extended public keys originating from the same master key fingerprint
+    /// are grouped into a single keyring
+    Import {
+        #[clap(
+            short,
+            long,
+            possible_values = STRUCTURED_FORMATS,
+            parse(try_from_str = parse_format),
+            default_value = "yaml"
+        )]
+        format: StructuredFormat,
+
+        /// File with output descriptors, one per line
+        #[clap(short, long, value_hint = ValueHint::FilePath)]
+        file: Option<PathBuf>,
+
+        /// Handling of the keys already present in the vault. Possible
+        /// values are: reject (nothing is imported), skip, merge (replace
+        /// account name and details), alias (add the name to the account
+        /// aliases)
+        #[clap(long, default_value = "skip")]
+        on_collision: CollisionPolicy,
+
+        /// Output descriptors to import
+        #[clap(required_unless_present = "file")]
+        descriptors: Vec<String>,
     },
 
     Export {
@@ -153,38 +876,283 @@ pub enum XPubkeyCommand {
 
         file: String,
     },
+
+    /// Exports output descriptors for the keys account, including key origin
+    /// information, for the import into Bitcoin Core or BDK wallets
+    Descriptor {
+        /// Extended public key identifier of the account
+        #[clap(parse(try_from_str = FromHex::from_hex))]
+        id: XpubIdentifier,
+    },
+
+    /// Derives BIP-47 reusable payment code of the keys account together
+    /// with its notification address. If a counterparty is given, keys of
+    /// the payments sent to and received from it are derived as well
+    Paycode {
+        #[clap(
+            short,
+            long,
+            possible_values = STRUCTURED_FORMATS,
+            parse(try_from_str = parse_format),
+            default_value = "yaml"
+        )]
+        format: StructuredFormat,
+
+        /// Extended public key identifier of the account
+        #[clap(parse(try_from_str = FromHex::from_hex))]
+        id: XpubIdentifier,
+
+        /// BIP-47 account index of the payment code
+        #[clap(short, long, default_value = "0")]
+        account: u32,
+
+        /// Payment code of the counterparty
+        #[clap(long)]
+        counterparty: Option<PaymentCode>,
+
+        /// Name of the counterparty. When given together with its payment
+        /// code, the code is saved with the account under this name;
+        /// otherwise the payment code saved earlier is used
+        #[clap(long)]
+        contact: Option<String>,
+
+        /// First index of the payment keys
+        #[clap(long, default_value = "0")]
+        start: u32,
+
+        /// Number of payment keys to derive in each direction
+        #[clap(long, default_value = "5")]
+        count: u32,
+    },
+
+    /// Derives Lightning node identity key and BOLT-3 basepoints of a
+    /// channel from the keyring used by the Lightning node
+    LnKeys {
+        #[clap(
+            short,
+            long,
+            possible_values = STRUCTURED_FORMATS,
+            parse(try_from_str = parse_format),
+            default_value = "yaml"
+        )]
+        format: StructuredFormat,
+
+        /// Extended public key identifier of the Lightning node keyring
+        #[clap(parse(try_from_str = FromHex::from_hex))]
+        id: XpubIdentifier,
+
+        /// Channel index assigned by the Lightning node
+        channel: u32,
+    },
+
+    /// Deletes keys subaccount. By default, the account is archived and its
+    /// data are kept in the vault
+    Delete {
+        /// Extended public key identifier of the subaccount
+        #[clap(parse(try_from_str = FromHex::from_hex))]
+        id: XpubIdentifier,
+
+        /// Remove account data from the vault instead of archiving it
+        #[clap(long)]
+        purge: bool,
+    },
+
+    /// Changes derivation branch layout of the keys account, used for
+    /// address derivation and balance discovery
+    Branches {
+        /// Extended public key identifier of the account
+        #[clap(parse(try_from_str = FromHex::from_hex))]
+        id: XpubIdentifier,
+
+        /// Derivation index of the external (receiving) addresses branch
+        #[clap(long, default_value = "0")]
+        external: u32,
+
+        /// Derivation index of the internal (change) addresses branch
+        #[clap(long, default_value = "1")]
+        internal: u32,
+
+        /// Gap limit for the account address discovery
+        #[clap(long)]
+        gap_limit: Option<u32>,
+    },
+
+    /// Sets signing policy of the keys account, evaluated by the daemon
+    /// before signing PSBTs with the account keys. Running the command
+    /// without options removes all restrictions.
+    Policy {
+        /// Extended public key identifier of the account
+        #[clap(parse(try_from_str = FromHex::from_hex))]
+        id: XpubIdentifier,
+
+        /// Maximal amount in satoshis which a transaction may send outside
+        /// of the account keyring
+        #[clap(long)]
+        max_amount: Option<u64>,
+
+        /// Address which the transactions may send funds to; may be given
+        /// multiple times. If absent, any destination is allowed
+        #[clap(short, long = "destination")]
+        destinations: Vec<Address>,
+
+        /// Allowed sighash type, given as a number (like 1 for
+        /// `SIGHASH_ALL`); may be given multiple times. If absent, only
+        /// `SIGHASH_ALL` and taproot `SIGHASH_DEFAULT` are allowed
+        #[clap(long = "sighash")]
+        sighash_types: Vec<u8>,
+
+        /// Maximal number of PSBTs signed within a period, given as
+        /// `<count>/<seconds>`
+        #[clap(long)]
+        rate_limit: Option<RateLimit>,
+
+        /// Allow ECDH with the account Lightning node key, required by the
+        /// Lightning nodes keeping their node key in the vault
+        #[clap(long)]
+        ecdh: bool,
+    },
+
+    /// Sets label of the keys account, given as `<key>=<value>`, or tag,
+    /// given as `<key>`, which is a label with an empty value
+    Label {
+        /// Extended public key identifier of the account
+        #[clap(parse(try_from_str = FromHex::from_hex))]
+        id: XpubIdentifier,
+
+        /// Label or tag to set
+        label: LabelQuery,
+
+        /// Remove the label with the given key instead of setting it
+        #[clap(long)]
+        remove: bool,
+    },
+
+    /// Changes name, details or the list of assets of the keys account.
+    /// Asset list of the keyring master account can't be changed
+    Update {
+        /// Extended public key identifier of the account
+        #[clap(parse(try_from_str = FromHex::from_hex))]
+        id: XpubIdentifier,
+
+        /// New name of the account
+        #[clap(short, long)]
+        name: Option<String>,
+
+        /// New details of the account
+        #[clap(short, long)]
+        details: Option<String>,
+
+        /// Asset id to add or remove according to the `--mode`; may be
+        /// given multiple times
+        #[clap(short, long = "asset", parse(try_from_str = FromHex::from_hex))]
+        assets: Vec<AssetId>,
+
+        /// How the asset list is updated. Possible values are: add,
+        /// replace, remove-ignore (unknown assets are skipped) and
+        /// remove-or-fail. With `replace` and no assets given, all assets
+        /// are removed from the account
+        #[clap(short, long, default_value = "add")]
+        mode: UpdateMode,
+    },
+
+    /// Changes lifecycle state of the keys account. Possible states are
+    /// `pending`, `active`, `retiring` and `revoked`
+    Lifecycle {
+        /// Extended public key identifier of the account
+        #[clap(parse(try_from_str = FromHex::from_hex))]
+        id: XpubIdentifier,
+
+        /// New lifecycle state
+        state: Lifecycle,
+    },
 }
 
 #[derive(Clap, Clone, Debug)]
 pub enum XPrivkeyCommand {
+    /// Imports existing extended private key from other wallet as a new
+    /// keyring, preserving key depth and parent fingerprint
+    Import {
+        /// Name for the imported keyring
+        name: String,
+
+        /// More details information about the imported keyring
+        details: Option<String>,
+
+        /// File to read extended private key from. If absent, the key is read
+        /// from STDIN
+        #[clap(short, long = "in", value_hint = ValueHint::FilePath)]
+        in_file: Option<PathBuf>,
+
+        /// Origin of the extended private key in the form of master key
+        /// fingerprint followed by the derivation path, like
+        /// `d34db33f/84'/0'/0'`
+        #[clap(long, parse(try_from_str = parse_key_source))]
+        origin: Option<KeySource>,
+
+        /// Application scope of the key. Possible values are:
+        /// pkh, sh, wpkh, wsh, wpkh-sh, wsh-sh
+        #[clap(long)]
+        application: Option<KeyApplication>,
+
+        /// Handling of the key already present in the vault. Possible values
+        /// are: reject, skip, merge (replace account name and details),
+        /// alias (add the name to the account aliases)
+        #[clap(long, default_value = "reject")]
+        on_collision: CollisionPolicy,
+    },
+
+    /// Exports extended private key of the account into a file. Export must
+    /// be enabled for the account keyring in the daemon configuration; the
+    /// command obtains one-time approval token from the daemon before the
+    /// export.
     Export {
         #[clap(parse(try_from_str = FromHex::from_hex))]
         id: XpubIdentifier,
 
         file: String,
     },
+
+    /// Derives deterministic child secret for other wallet or application
+    /// from the account private key (BIP-85)
+    Bip85 {
+        /// Extended public key identifier of the account
+        #[clap(parse(try_from_str = FromHex::from_hex))]
+        id: XpubIdentifier,
+
+        /// Application of the child secret. Possible values are:
+        /// `mnemonic[:<words>[:<language>]]` (24 English words by default),
+        /// `wif`, `xprv` and `hex[:<bytes>]` (64 bytes by default)
+        application: Bip85Application,
+
+        /// Index of the child secret
+        #[clap(long, default_value = "0")]
+        index: u32,
+    },
 }
 
 #[derive(Clap, Clone, Debug)]
 pub enum SignCommand {
     /// Signs given PSBT
     Psbt {
+        /// Output format; only `bin`, `hex` and `base64` are supported. The
+        /// input format is detected automatically
         #[clap(
             short = 'f',
             long = "format",
-            arg_enum,
+            possible_values = BINARY_FORMATS,
+            parse(try_from_str = parse_format),
             default_value = "base64"
         )]
         format: StructuredFormat,
 
         /// Input file to read PSBT from. If absent, and no `data` parameter
-        /// is provided, data are read from STDIN. The file and data must be in
-        /// a `format` format.
+        /// is provided, data are read from STDIN. The file may contain
+        /// either binary PSBT or its hex or base64 encoding.
         #[clap(short, long = "in")]
         in_file: Option<PathBuf>,
 
-        /// Data string containing PSBT encoded in hexadecimal format (must
-        /// contain even number of 0-9, A-f characters)
+        /// Data string containing PSBT encoded in hexadecimal or base64
+        /// format
         #[clap()]
         data: Option<String>,
 
@@ -192,11 +1160,103 @@ pub enum SignCommand {
         /// STDOUT
         #[clap(short, long = "out")]
         out_file: Option<PathBuf>,
+
+        /// Finalize inputs which have enough signatures after signing
+        #[clap(long)]
+        finalize: bool,
+
+        /// Output network-serialized transaction extracted from the
+        /// finalized PSBT instead of the PSBT itself. Fails if some of the
+        /// inputs can't be finalized
+        #[clap(long, requires = "finalize")]
+        extract: bool,
+
+        /// Sign inputs transferring RGB assets even if the signing accounts
+        /// are not bound to these assets
+        #[clap(long)]
+        force: bool,
+    },
+
+    /// Reports which inputs of the PSBT can be signed by the vault, amounts
+    /// spent and received and the transaction fee, without signing it
+    Inspect {
+        /// Input file to read PSBT from. If absent, and no `data` parameter
+        /// is provided, data are read from STDIN. The file may contain
+        /// either binary PSBT or its hex or base64 encoding.
+        #[clap(short, long = "in")]
+        in_file: Option<PathBuf>,
+
+        /// Data string containing PSBT encoded in hexadecimal or base64
+        /// format
+        #[clap()]
+        data: Option<String>,
+    },
+
+    /// Signs SHA256d hash of the file contents, writing detached signature
+    /// in DER encoding
+    File {
+        /// Signature format; only `bin`, `hex` and `base64` are supported
+        #[clap(
+            short,
+            long,
+            possible_values = BINARY_FORMATS,
+            parse(try_from_str = parse_format),
+            default_value = "hex"
+        )]
+        format: StructuredFormat,
+
+        /// Key identifier for the signature
+        #[clap(parse(try_from_str = FromHex::from_hex))]
+        id: XpubIdentifier,
+
+        /// File to sign. If absent, data are read from STDIN
+        #[clap(short, long = "in")]
+        in_file: Option<PathBuf>,
+
+        /// Output file to save the signature. If absent, the signature is
+        /// written to STDOUT
+        #[clap(short, long = "out")]
+        out_file: Option<PathBuf>,
     },
 
-    File {},
+    /// Signs text message in the Bitcoin Signed Message format (BIP-137),
+    /// writing base64-encoded signature to STDOUT
+    Text {
+        /// Key identifier for the signature
+        #[clap(parse(try_from_str = FromHex::from_hex))]
+        id: XpubIdentifier,
+
+        /// Message to sign. If absent, and no input file is given, the
+        /// message is read from STDIN
+        message: Option<String>,
+
+        /// Input file to read the message from
+        #[clap(short, long = "in")]
+        in_file: Option<PathBuf>,
+    },
+
+    /// Verifies base64-encoded signature of a text message in the Bitcoin
+    /// Signed Message format against an address or a public key
+    Verify {
+        /// Base64-encoded message signature
+        signature: String,
+
+        /// Signed message. If absent, and no input file is given, the
+        /// message is read from STDIN
+        message: Option<String>,
+
+        /// Input file to read the message from
+        #[clap(short, long = "in")]
+        in_file: Option<PathBuf>,
 
-    Text {},
+        /// Address which must control the signing key
+        #[clap(long, required_unless_present = "pubkey")]
+        address: Option<bitcoin::Address>,
+
+        /// Public key which must have produced the signature
+        #[clap(long, conflicts_with = "address")]
+        pubkey: Option<bitcoin::PublicKey>,
+    },
 
     Key {
         /// Key identifier for the signature
@@ -204,3 +1264,358 @@ pub enum SignCommand {
         id: XpubIdentifier,
     },
 }
+
+#[derive(Clap, Clone, Debug)]
+pub enum VerifyCommand {
+    /// Verifies detached signature produced by `sign file` command
+    File {
+        /// Signature format; only `bin`, `hex` and `base64` are supported
+        #[clap(
+            short,
+            long,
+            possible_values = BINARY_FORMATS,
+            parse(try_from_str = parse_format),
+            default_value = "hex"
+        )]
+        format: StructuredFormat,
+
+        /// File containing detached signature
+        signature: PathBuf,
+
+        /// Public key of the account which must have produced the signature
+        pubkey: bitcoin::PublicKey,
+
+        /// Signed file. If absent, data are read from STDIN
+        #[clap(short, long = "in")]
+        in_file: Option<PathBuf>,
+    },
+}
+
+#[derive(Clap, Clone, Debug)]
+pub enum TxCommand {
+    /// Creates unsigned PSBT spending outputs controlled by the vault
+    /// accounts, which can be signed with `sign psbt` command. Only accounts
+    /// with SegWit and nested SegWit applications can be spent.
+    Create {
+        /// Output format; only `bin`, `hex` and `base64` are supported
+        #[clap(
+            short = 'f',
+            long = "format",
+            possible_values = BINARY_FORMATS,
+            parse(try_from_str = parse_format),
+            default_value = "base64"
+        )]
+        format: StructuredFormat,
+
+        /// Spent output in `<txid>:<vout>:<amount>:<key_id>:<path>` format,
+        /// where amount is given in satoshis, `key_id` identifies the
+        /// account controlling the output and path is the derivation path of
+        /// the output key relative to the account, like `0/5`
+        #[clap(long = "input", required = true)]
+        inputs: Vec<PsbtInput>,
+
+        /// Transaction output in `<address>:<amount>` format, where amount
+        /// is given in satoshis
+        #[clap(long = "output", required = true)]
+        outputs: Vec<PsbtOutput>,
+
+        /// Transaction lock time
+        #[clap(long, default_value = "0")]
+        lock_time: u32,
+
+        /// Output file to save the PSBT. If absent, data are written to
+        /// STDOUT
+        #[clap(short, long = "out")]
+        out_file: Option<PathBuf>,
+    },
+
+    /// Exports records of the transactions signed by the vault from the
+    /// daemon ledger for reconciliation with accounting systems
+    Ledger {
+        /// Output format for the records
+        #[clap(
+            short = 'f',
+            long = "format",
+            possible_values = STRUCTURED_FORMATS,
+            parse(try_from_str = parse_format),
+            default_value = "json"
+        )]
+        format: StructuredFormat,
+
+        /// Output records as CSV table instead of the structured format
+        #[clap(long, conflicts_with = "format")]
+        csv: bool,
+
+        /// Export only transactions signed at or after the given time, in
+        /// seconds since UNIX epoch
+        #[clap(long, default_value = "0")]
+        since: u64,
+
+        /// Output file to save the records. If absent, records are written to
+        /// STDOUT
+        #[clap(short, long = "out")]
+        out_file: Option<PathBuf>,
+    },
+}
+
+#[derive(Clap, Clone, Debug)]
+pub enum PsbtCommand {
+    /// Combines partially signed copies of the same transaction from
+    /// multiple cosigners into a single PSBT. Fails if the copies refer to
+    /// different transactions or disagree on the spent outputs or scripts.
+    /// The PSBTs are combined by the daemon unless `--local` is given.
+    #[clap(alias = "merge")]
+    Combine {
+        /// Output format; only `bin`, `hex` and `base64` are supported. The
+        /// input format is detected automatically for each file
+        #[clap(
+            short = 'f',
+            long = "format",
+            possible_values = BINARY_FORMATS,
+            parse(try_from_str = parse_format),
+            default_value = "base64"
+        )]
+        format: StructuredFormat,
+
+        /// Files with PSBTs to combine, each containing either binary PSBT
+        /// or its hex or base64 encoding
+        #[clap(
+            required = true,
+            min_values = 2,
+            value_hint = ValueHint::FilePath
+        )]
+        files: Vec<PathBuf>,
+
+        /// Output file to save the combined PSBT. If absent, data are written
+        /// to STDOUT
+        #[clap(short, long = "out")]
+        out_file: Option<PathBuf>,
+
+        /// Combine PSBTs locally instead of sending them to the daemon
+        #[clap(long)]
+        local: bool,
+    },
+}
+
+#[derive(Clap, Clone, Debug)]
+pub enum RevocationCommand {
+    /// Stores per-commitment secret revealed by the counterparty of a
+    /// Lightning channel. Secrets of a channel must be stored in the order
+    /// of decreasing indexes.
+    Append {
+        /// Extended public key identifier of the account which namespace
+        /// keeps the channel secrets
+        #[clap(parse(try_from_str = FromHex::from_hex))]
+        id: XpubIdentifier,
+
+        /// Channel identifier in hexadecimal format
+        channel: LnChannelId,
+
+        /// Index of the per-commitment secret
+        index: u64,
+
+        /// Per-commitment secret in hexadecimal format
+        secret: CommitmentSecret,
+    },
+
+    /// Returns per-commitment secret of a Lightning channel, which was
+    /// either stored or can be derived from the stored secrets
+    Query {
+        /// Extended public key identifier of the account which namespace
+        /// keeps the channel secrets
+        #[clap(parse(try_from_str = FromHex::from_hex))]
+        id: XpubIdentifier,
+
+        /// Channel identifier in hexadecimal format
+        channel: LnChannelId,
+
+        /// Index of the per-commitment secret
+        index: u64,
+    },
+
+    /// Compacts revocation secret storage of the account, leaving only the
+    /// secrets from which all other secrets can be derived
+    Compact {
+        /// Extended public key identifier of the account
+        #[clap(parse(try_from_str = FromHex::from_hex))]
+        id: XpubIdentifier,
+    },
+}
+
+#[derive(Clap, Clone, Debug)]
+pub enum IdentityCommand {
+    /// Exports x-only public key of the identity derived from the account
+    Key {
+        /// Extended public key identifier of the account
+        #[clap(parse(try_from_str = FromHex::from_hex))]
+        id: XpubIdentifier,
+
+        /// Index of the identity
+        #[clap(long, default_value = "0")]
+        index: u32,
+    },
+
+    /// Signs identity event digest (like Nostr event id) with BIP-340
+    /// signature
+    Sign {
+        /// Extended public key identifier of the account
+        #[clap(parse(try_from_str = FromHex::from_hex))]
+        id: XpubIdentifier,
+
+        /// Event digest in hexadecimal format
+        digest: sha256::Hash,
+
+        /// Index of the identity
+        #[clap(long, default_value = "0")]
+        index: u32,
+    },
+}
+
+#[derive(Clap, Clone, Debug)]
+pub enum MultisigCommand {
+    /// Registers N-of-M group spending with `wsh(multi(...))` scripts. The
+    /// vault accounts go first in the group scripts, followed by the
+    /// external cosigners in the order they are given
+    Create {
+        /// Name of the group
+        name: String,
+
+        /// Number of signatures required to spend
+        #[clap(short, long)]
+        threshold: u8,
+
+        /// Extended public key identifier of the vault account joining the
+        /// group; may be given multiple times
+        #[clap(
+            short,
+            long = "account",
+            required = true,
+            parse(try_from_str = FromHex::from_hex)
+        )]
+        accounts: Vec<XpubIdentifier>,
+
+        /// External cosigner key, given as `[<fingerprint>/<path>]<xpub>`
+        /// or as a plain xpub; may be given multiple times
+        #[clap(short, long = "cosigner")]
+        cosigners: Vec<CosignerKey>,
+    },
+
+    /// Lists multisig groups registered in the vault
+    List,
+
+    /// Exports output descriptors of the multisig group for the receiving
+    /// and change addresses
+    Descriptor {
+        /// Multisig group id
+        id: MultisigId,
+    },
+}
+
+#[derive(Clap, Clone, Debug)]
+pub enum MuSigCommand {
+    /// Starts MuSig2 session signing the message with the account key.
+    /// Prints session id, aggregated key and the account public nonce,
+    /// which has to be sent to the other participants
+    Start {
+        /// Extended public key identifier of the account
+        #[clap(parse(try_from_str = FromHex::from_hex))]
+        id: XpubIdentifier,
+
+        /// Message digest to sign, in hexadecimal format
+        message: sha256::Hash,
+
+        /// Public key of a participant, including the account key, in the
+        /// order of aggregation; must be given for each participant
+        #[clap(short, long = "participant", required = true)]
+        participants: Vec<secp256k1::PublicKey>,
+
+        /// Tweak aggregated key for a taproot key-path spend
+        #[clap(long)]
+        taproot: bool,
+    },
+
+    /// Provides public nonces of all other participants to the session and
+    /// prints the aggregated nonce
+    Nonces {
+        /// Session id returned by `musig start`
+        session: MuSigSessionId,
+
+        /// Participant nonce, given as `<participant key>:<nonce>`; must be
+        /// given for each of the other participants
+        #[clap(short, long = "nonce", required = true)]
+        nonces: Vec<MuSigNonce>,
+    },
+
+    /// Produces partial signature of the account and closes the session. If
+    /// partial signatures of all other participants are given, prints also
+    /// the aggregated signature
+    Sign {
+        /// Session id returned by `musig start`
+        session: MuSigSessionId,
+
+        /// Partial signature of other participant in hexadecimal format;
+        /// may be given multiple times
+        #[clap(
+            short,
+            long = "partial",
+            parse(try_from_str = Vec::from_hex)
+        )]
+        partial_sigs: Vec<Vec<u8>>,
+    },
+}
+
+#[derive(Clap, Clone, Debug)]
+pub enum UtilCommand {
+    /// Encrypts secret with the ElGamal scheme used by the keyring vault.
+    /// Output is written in hexadecimal format and contains unblinding key
+    /// followed by the encrypted data.
+    Encrypt {
+        /// Public key to encrypt the secret for, like the daemon node id
+        #[clap(long)]
+        key: secp256k1::PublicKey,
+
+        /// Input file to read the secret from. If absent, the secret is read
+        /// from STDIN
+        #[clap(short, long = "in")]
+        in_file: Option<PathBuf>,
+
+        /// Output file to save encrypted data. If absent, data are written to
+        /// STDOUT
+        #[clap(short, long = "out")]
+        out_file: Option<PathBuf>,
+    },
+
+    /// Decrypts secret produced by `util encrypt` or exported by the daemon
+    Decrypt {
+        /// Source of the secret key matching the public key used for the
+        /// encryption: `@<file>`, `env:<VARIABLE>` or `-` for STDIN. If not
+        /// given, the key is taken from `KEYRING_DECRYPTION_KEY` environment
+        /// variable or read from STDIN before the encrypted data
+        #[clap(long, value_name = "SOURCE")]
+        key: Option<SecretSource>,
+
+        /// Encrypted data in hexadecimal format. If absent, and no input file
+        /// is given, data are read from STDIN
+        #[clap()]
+        data: Option<String>,
+
+        /// Input file to read encrypted data in hexadecimal format from
+        #[clap(short, long = "in")]
+        in_file: Option<PathBuf>,
+
+        /// Output file to save decrypted secret. If absent, the secret is
+        /// written to STDOUT
+        #[clap(short, long = "out")]
+        out_file: Option<PathBuf>,
+    },
+}
+
+fn parse_key_source(s: &str) -> Result<KeySource, String> {
+    let mut split = s.splitn(2, '/');
+    let fingerprint = split.next().unwrap_or_default();
+    let fingerprint = Fingerprint::from_hex(fingerprint)
+        .map_err(|err| format!("invalid master key fingerprint: {}", err))?;
+    let path = derivation::parse(split.next().unwrap_or_default())
+        .map_err(|err| err.to_string())?;
+    Ok((fingerprint, path))
+}
@@ -0,0 +1,231 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Printing of the command results in the format selected with global
+//! `--output` option. Structured formats serialize the results with serde,
+//! so their field names are the ones of the RPC types and of the result
+//! structures defined here, which must not be renamed.
+
+use std::fmt::{self, Display, Formatter};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::OutputFormat;
+use crate::rpc;
+
+/// Result of the commands which do not return any data
+#[derive(Clone, PartialEq, Eq, Debug, Serialize)]
+#[serde(crate = "serde_crate")]
+pub struct Done {
+    pub success: bool,
+    pub message: String,
+}
+
+/// Signature produced by the command, in the encoding used by the plain
+/// text output
+#[derive(Clone, PartialEq, Eq, Debug, Display, Serialize)]
+#[serde(crate = "serde_crate")]
+#[display("{signature}")]
+pub struct Signature {
+    pub signature: String,
+}
+
+/// Result of the signature verification
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize)]
+#[serde(crate = "serde_crate")]
+pub struct Verification {
+    pub valid: bool,
+}
+
+impl Display for Verification {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.valid {
+            true => f.write_str("Signature is valid"),
+            false => f.write_str("Signature is invalid"),
+        }
+    }
+}
+
+/// Secret derived or stored by the daemon
+#[derive(Clone, PartialEq, Eq, Debug, Display, Serialize)]
+#[serde(crate = "serde_crate")]
+#[display("{secret}")]
+pub struct Secret {
+    pub secret: String,
+}
+
+/// Public nonce of a multi-party signing session
+#[derive(Clone, PartialEq, Eq, Debug, Display, Serialize)]
+#[serde(crate = "serde_crate")]
+#[display("{nonce}")]
+pub struct Nonce {
+    pub nonce: String,
+}
+
+/// Path of the file written by the command
+#[derive(Clone, PartialEq, Eq, Debug, Serialize)]
+#[serde(crate = "serde_crate")]
+pub struct Written {
+    pub path: String,
+}
+
+/// Prints the data in a structured format or, for the plain text output,
+/// calls `plain` printing it in a human-readable form
+pub fn print_with<T>(
+    format: OutputFormat,
+    data: &T,
+    plain: impl FnOnce(),
+) -> Result<(), rpc::Error>
+where
+    T: Serialize + ?Sized,
+{
+    let output = match format {
+        OutputFormat::Plain => {
+            plain();
+            return Ok(());
+        }
+        OutputFormat::Json => serde_json::to_string_pretty(data)
+            .map_err(|err| rpc::Error::Output(err.to_string()))?,
+        OutputFormat::Yaml => serde_yaml::to_string(data)
+            .map_err(|err| rpc::Error::Output(err.to_string()))?,
+        OutputFormat::Table => table(
+            &serde_json::to_value(data)
+                .map_err(|err| rpc::Error::Output(err.to_string()))?,
+        ),
+    };
+    println!("{}", output.trim_end());
+    Ok(())
+}
+
+/// Prints the data, using its [`Display`] for the plain text output
+pub fn print<T>(format: OutputFormat, data: &T) -> Result<(), rpc::Error>
+where
+    T: Serialize + Display,
+{
+    print_with(format, data, || println!("{}", data))
+}
+
+/// Prints the list, with an item per line in the plain text output
+pub fn print_list<T>(
+    format: OutputFormat,
+    items: &[T],
+) -> Result<(), rpc::Error>
+where
+    T: Serialize + Display,
+{
+    print_with(format, items, || {
+        items.iter().for_each(|item| println!("{}", item))
+    })
+}
+
+/// Reports successful completion of the command, which is only logged in
+/// the plain text output
+pub fn done(format: OutputFormat, message: &str) -> Result<(), rpc::Error> {
+    let done = Done {
+        success: true,
+        message: message.to_owned(),
+    };
+    print_with(format, &done, || info!("{}", message))
+}
+
+/// Reports the file written by the command
+pub fn written(
+    format: OutputFormat,
+    path: impl Display,
+    message: &str,
+) -> Result<(), rpc::Error> {
+    let written = Written {
+        path: path.to_string(),
+    };
+    print_with(format, &written, || println!("{} {}", message, path))
+}
+
+/// Renders serialized data as a table: lists of objects have a row per item
+/// and a column per field, single objects have a row per field
+fn table(data: &Value) -> String {
+    let (header, rows) = match data {
+        Value::Array(items) => {
+            let mut header = Vec::<String>::new();
+            for item in items {
+                if let Value::Object(fields) = item {
+                    for name in fields.keys() {
+                        if !header.contains(name) {
+                            header.push(name.clone());
+                        }
+                    }
+                }
+            }
+            if header.is_empty() {
+                let rows = items.iter().map(|item| vec![cell(item)]).collect();
+                (vec![s!("value")], rows)
+            } else {
+                let rows = items
+                    .iter()
+                    .map(|item| {
+                        header
+                            .iter()
+                            .map(|name| {
+                                item.get(name).map(cell).unwrap_or_default()
+                            })
+                            .collect()
+                    })
+                    .collect();
+                (header, rows)
+            }
+        }
+        Value::Object(fields) => (
+            vec![s!("field"), s!("value")],
+            fields
+                .iter()
+                .map(|(name, value)| vec![name.clone(), cell(value)])
+                .collect(),
+        ),
+        value => return cell(value),
+    };
+
+    let mut widths = header.iter().map(|name| name.len()).collect::<Vec<_>>();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let line = |row: &[String]| {
+        row.iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_owned()
+    };
+    let mut table = vec![line(&header)];
+    table.push(line(
+        &widths
+            .iter()
+            .map(|width| "-".repeat(*width))
+            .collect::<Vec<_>>(),
+    ));
+    table.extend(rows.iter().map(|row| line(row)));
+    table.join("\n")
+}
+
+/// Single-line representation of the value in the table cell; nested lists
+/// and objects are given as compact JSON
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => s!(""),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
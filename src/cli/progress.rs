@@ -0,0 +1,81 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Progress bar of the jobs drawn on STDERR while their status is polled
+//! from the daemon. Jobs completing within [`SHOW_DELAY`] do not produce any
+//! output.
+
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::rpc::types::JobProgress;
+
+/// Time after which the progress bar is shown
+pub const SHOW_DELAY: Duration = Duration::from_secs(1);
+
+/// Interval between the job status requests
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Width of the progress bar, in characters
+const BAR_WIDTH: usize = 40;
+
+/// Progress bar of a single job
+pub struct ProgressBar {
+    submitted: Instant,
+    drawn: bool,
+}
+
+impl ProgressBar {
+    /// Starts progress bar of a job which has just been submitted
+    pub fn start() -> Self {
+        Self {
+            submitted: Instant::now(),
+            drawn: false,
+        }
+    }
+
+    /// Draws the job `progress`, unless the job was submitted less than
+    /// [`SHOW_DELAY`] ago
+    pub fn update(&mut self, progress: &JobProgress) {
+        if self.submitted.elapsed() < SHOW_DELAY {
+            return;
+        }
+        draw(progress);
+        self.drawn = true;
+    }
+
+    /// Leaves the last drawn state of the bar on the screen once the job is
+    /// completed
+    pub fn finish(self) {
+        if self.drawn {
+            eprintln!();
+        }
+    }
+}
+
+fn draw(progress: &JobProgress) {
+    let filled = match progress.total {
+        0 => 0,
+        total => BAR_WIDTH * progress.done.min(total) as usize / total as usize,
+    };
+    eprint!(
+        "\r{} [{}{}] {}/{} ({:.1} s)",
+        progress.operation,
+        "#".repeat(filled),
+        "-".repeat(BAR_WIDTH - filled),
+        progress.done,
+        progress.total,
+        progress.elapsed as f64 / 1000.0
+    );
+    let _ = io::stderr().flush();
+}
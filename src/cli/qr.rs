@@ -0,0 +1,281 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Rendering of extended public keys and output descriptors as QR codes and
+//! BC-UR (BCR-2020-005) fragments for the import into airgapped wallets.
+//! Extended public keys are encoded as `crypto-hdkey` (BCR-2020-007) and
+//! descriptors as `bytes` URs.
+
+use std::fmt::{self, Display, Formatter};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use bitcoin::util::bip32::{ChildNumber, ExtendedPubKey, KeySource};
+use qrcode::render::unicode::Dense1x2;
+use qrcode::QrCode;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+use super::QrOpts;
+use crate::rpc;
+
+/// Delay between the frames of the animated multi-part UR
+const FRAME_DELAY: Duration = Duration::from_millis(250);
+
+const CBOR_UINT: u8 = 0;
+const CBOR_BYTES: u8 = 2;
+const CBOR_ARRAY: u8 = 4;
+const CBOR_MAP: u8 = 5;
+const CBOR_TAG: u8 = 6;
+const CBOR_TRUE: u8 = 0xF5;
+const CBOR_FALSE: u8 = 0xF4;
+
+const TAG_COIN_INFO: u64 = 305;
+const TAG_KEYPATH: u64 = 304;
+
+/// Key data exported for the import into other wallets
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Exported {
+    /// Extended public key with its origin, if known
+    Xpub(ExtendedPubKey, Option<KeySource>),
+
+    /// Output descriptors of the account
+    Descriptors(Vec<String>),
+
+    /// Extended public key serialized with SLIP-132 prefix
+    Slip132(String),
+}
+
+impl Display for Exported {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Exported::Xpub(xpub, _) => Display::fmt(xpub, f),
+            Exported::Descriptors(descriptors) => {
+                f.write_str(&descriptors.join("\n"))
+            }
+            Exported::Slip132(key) => f.write_str(key),
+        }
+    }
+}
+
+impl Serialize for Exported {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(None)?;
+        match self {
+            Exported::Xpub(xpub, origin) => {
+                map.serialize_entry("xpub", &xpub.to_string())?;
+                if let Some((fingerprint, path)) = origin {
+                    map.serialize_entry(
+                        "fingerprint",
+                        &fingerprint.to_string(),
+                    )?;
+                    map.serialize_entry("path", &path.to_string())?;
+                }
+            }
+            Exported::Descriptors(descriptors) => {
+                map.serialize_entry("descriptors", descriptors)?
+            }
+            Exported::Slip132(key) => map.serialize_entry("xpub", key)?,
+        }
+        map.end()
+    }
+}
+
+impl Exported {
+    /// Type of the UR encoding the data
+    pub fn ur_type(&self) -> &'static str {
+        match self {
+            Exported::Xpub(..) => "crypto-hdkey",
+            Exported::Descriptors(_) | Exported::Slip132(_) => "bytes",
+        }
+    }
+
+    /// CBOR encoding of the data used as UR payload
+    pub fn cbor(&self) -> Vec<u8> {
+        let mut cbor = vec![];
+        match self {
+            Exported::Xpub(xpub, origin) => hdkey(&mut cbor, xpub, origin),
+            Exported::Descriptors(_) | Exported::Slip132(_) => {
+                let text = self.to_string();
+                head(&mut cbor, CBOR_BYTES, text.len() as u64);
+                cbor.extend(text.as_bytes());
+            }
+        }
+        cbor
+    }
+
+    /// Renders the data as requested by the command-line options: as text,
+    /// QR code in the terminal, PNG image with QR code and/or BC-UR
+    pub fn render(&self, opts: &QrOpts) -> Result<(), rpc::Error> {
+        let cbor = self.cbor();
+        let text = if opts.ur {
+            ur::encode(&cbor, self.ur_type()).to_uppercase()
+        } else {
+            self.to_string()
+        };
+        if let Some(ref path) = opts.png {
+            write_png(&text, path)?;
+            eprintln!("QR code is written to {}", path.display());
+        }
+
+        if !opts.ur || cbor.len() <= opts.fragment_len {
+            if opts.qr {
+                print!("{}", terminal_qr(&text)?);
+            } else if opts.png.is_none() {
+                println!("{}", text);
+            }
+            return Ok(());
+        }
+
+        let mut encoder =
+            ur::Encoder::new(&cbor, opts.fragment_len, self.ur_type())
+                .map_err(|err| rpc::Error::Rendering(err.to_string()))?;
+        if !opts.qr {
+            for _ in 0..encoder.fragment_count() {
+                println!("{}", next_part(&mut encoder)?);
+            }
+            return Ok(());
+        }
+        eprintln!(
+            "Showing animated QR code of {} UR fragments; press Ctrl-C to \
+             stop",
+            encoder.fragment_count()
+        );
+        loop {
+            let frame = terminal_qr(&next_part(&mut encoder)?)?;
+            // Clearing the screen and moving the cursor to its top
+            print!("\x1B[2J\x1B[H{}", frame);
+            thread::sleep(FRAME_DELAY);
+        }
+    }
+}
+
+fn next_part(encoder: &mut ur::Encoder) -> Result<String, rpc::Error> {
+    encoder
+        .next_part()
+        .map(|part| part.to_uppercase())
+        .map_err(|err| rpc::Error::Rendering(err.to_string()))
+}
+
+fn qr_code(text: &str) -> Result<QrCode, rpc::Error> {
+    QrCode::new(text.as_bytes())
+        .map_err(|err| rpc::Error::Rendering(err.to_string()))
+}
+
+/// Renders QR code with UTF-8 half blocks, inverted for the terminals with
+/// dark background
+fn terminal_qr(text: &str) -> Result<String, rpc::Error> {
+    Ok(qr_code(text)?
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build())
+}
+
+fn write_png(text: &str, path: &Path) -> Result<(), rpc::Error> {
+    qr_code(text)?
+        .render::<image::Luma<u8>>()
+        .min_dimensions(256, 256)
+        .build()
+        .save(path)
+        .map_err(|err| rpc::Error::Rendering(err.to_string()))
+}
+
+/// Writes CBOR data item header
+fn head(cbor: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    match value {
+        0..=23 => cbor.push(major | value as u8),
+        24..=0xFF => cbor.extend(&[major | 24, value as u8]),
+        0x100..=0xFFFF => {
+            cbor.push(major | 25);
+            cbor.extend(&(value as u16).to_be_bytes());
+        }
+        0x1_0000..=0xFFFF_FFFF => {
+            cbor.push(major | 26);
+            cbor.extend(&(value as u32).to_be_bytes());
+        }
+        _ => {
+            cbor.push(major | 27);
+            cbor.extend(&value.to_be_bytes());
+        }
+    }
+}
+
+fn bytes(cbor: &mut Vec<u8>, data: &[u8]) {
+    head(cbor, CBOR_BYTES, data.len() as u64);
+    cbor.extend(data);
+}
+
+/// Writes `crypto-hdkey` map of the extended public key
+fn hdkey(
+    cbor: &mut Vec<u8>,
+    xpub: &ExtendedPubKey,
+    origin: &Option<KeySource>,
+) {
+    let depth = xpub.depth > 0;
+    head(cbor, CBOR_MAP, 3 + origin.is_some() as u64 + depth as u64);
+
+    // key-data
+    head(cbor, CBOR_UINT, 3);
+    bytes(cbor, &xpub.public_key.key.serialize());
+
+    // chain-code
+    head(cbor, CBOR_UINT, 4);
+    bytes(cbor, &xpub.chain_code[..]);
+
+    // use-info: bitcoin on mainnet or testnet
+    head(cbor, CBOR_UINT, 5);
+    head(cbor, CBOR_TAG, TAG_COIN_INFO);
+    head(cbor, CBOR_MAP, 1);
+    head(cbor, CBOR_UINT, 2);
+    let testnet = xpub.network != bitcoin::Network::Bitcoin;
+    head(cbor, CBOR_UINT, testnet as u64);
+
+    if let Some((fingerprint, path)) = origin {
+        head(cbor, CBOR_UINT, 6);
+        head(cbor, CBOR_TAG, TAG_KEYPATH);
+        head(cbor, CBOR_MAP, 3);
+        head(cbor, CBOR_UINT, 1);
+        head(cbor, CBOR_ARRAY, path.as_ref().len() as u64 * 2);
+        for step in path.as_ref() {
+            let (index, hardened) = match *step {
+                ChildNumber::Normal { index } => (index, false),
+                ChildNumber::Hardened { index } => (index, true),
+            };
+            head(cbor, CBOR_UINT, index as u64);
+            cbor.push(if hardened { CBOR_TRUE } else { CBOR_FALSE });
+        }
+        head(cbor, CBOR_UINT, 2);
+        head(
+            cbor,
+            CBOR_UINT,
+            u32::from_be_bytes(*fingerprint.as_bytes()) as u64,
+        );
+        head(cbor, CBOR_UINT, 3);
+        head(cbor, CBOR_UINT, path.as_ref().len() as u64);
+    }
+
+    if depth {
+        // parent-fingerprint
+        head(cbor, CBOR_UINT, 8);
+        head(
+            cbor,
+            CBOR_UINT,
+            u32::from_be_bytes(*xpub.parent_fingerprint.as_bytes()) as u64,
+        );
+    }
+}
@@ -0,0 +1,132 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Reading of passphrases and keys from the sources given to the
+//! command-line options. Secrets typed in the terminal are not echoed.
+
+use std::str::FromStr;
+use std::{env, fs, io};
+
+use bitcoin::secp256k1::SecretKey;
+use zeroize::Zeroizing;
+
+use super::SecretSource;
+
+/// Service name of the operating system keychain entries read by
+/// `keychain:<ENTRY>` secret source
+#[cfg(feature = "os-keychain")]
+pub const KEYCHAIN_SERVICE: &str = "keyring-cli";
+
+impl SecretSource {
+    /// Reads the secret, removing trailing line break
+    pub fn read(&self, prompt: &str) -> io::Result<String> {
+        let mut secret = match self {
+            SecretSource::File(path) => fs::read_to_string(path)?,
+            SecretSource::Env(name) => env::var(name).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("environment variable {}: {}", name, err),
+                )
+            })?,
+            SecretSource::Fd(fd) => read_fd(*fd)?,
+            #[cfg(feature = "os-keychain")]
+            SecretSource::Keychain(entry) => {
+                os_keyring::Keyring::new(KEYCHAIN_SERVICE, entry)
+                    .get_password()
+                    .map_err(|err| {
+                        io::Error::new(
+                            io::ErrorKind::NotFound,
+                            format!("keychain entry {}: {}", entry, err),
+                        )
+                    })?
+            }
+            SecretSource::Prompt => prompt_secret(prompt)?,
+        };
+        let len = secret.trim_end_matches(&['\r', '\n'][..]).len();
+        secret.truncate(len);
+        Ok(secret)
+    }
+
+    /// Reads secret key given in hex encoding
+    pub fn read_key(&self, prompt: &str) -> io::Result<SecretKey> {
+        let hex = Zeroizing::new(self.read(prompt)?);
+        SecretKey::from_str(hex.trim()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} must be a hex-encoded secret key", prompt),
+            )
+        })
+    }
+
+    /// Detects secret source given with the environment: `default_env`
+    /// variable containing the secret itself or `<default_env>_FD` variable
+    /// with the number of the file descriptor to read the secret from
+    pub fn from_env(default_env: &str) -> Option<SecretSource> {
+        if env::var_os(default_env).is_some() {
+            return Some(SecretSource::Env(default_env.to_owned()));
+        }
+        env::var(format!("{}_FD", default_env))
+            .ok()
+            .and_then(|fd| fd.parse().ok())
+            .map(SecretSource::Fd)
+    }
+
+    /// Reads the secret from the `source` given in the command line. If the
+    /// source is absent, the secret is taken from the environment (see
+    /// [`SecretSource::from_env`]), if it is set, or read from STDIN
+    /// otherwise.
+    pub fn resolve(
+        source: &Option<SecretSource>,
+        default_env: &str,
+        prompt: &str,
+    ) -> io::Result<String> {
+        match source {
+            Some(source) => source.read(prompt),
+            None => SecretSource::from_env(default_env)
+                .unwrap_or(SecretSource::Prompt)
+                .read(prompt),
+        }
+    }
+}
+
+/// Asks for the secret in the terminal without echoing it. If STDIN is not
+/// a terminal, the secret is read from the first line of the input.
+fn prompt_secret(prompt: &str) -> io::Result<String> {
+    if atty::is(atty::Stream::Stdin) {
+        return rpassword::prompt_password_stderr(&format!("{}: ", prompt));
+    }
+    let mut secret = String::new();
+    io::stdin().read_line(&mut secret)?;
+    Ok(secret)
+}
+
+#[cfg(unix)]
+fn read_fd(fd: i32) -> io::Result<String> {
+    use std::io::Read;
+    use std::os::unix::io::FromRawFd;
+
+    // The descriptor is passed by the parent process for reading a single
+    // secret, so the file takes its ownership and closes it
+    let mut file = unsafe { fs::File::from_raw_fd(fd) };
+    let mut secret = String::new();
+    file.read_to_string(&mut secret)?;
+    Ok(secret)
+}
+
+#[cfg(not(unix))]
+fn read_fd(_fd: i32) -> io::Result<String> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "secrets can be read from file descriptors only on Unix systems",
+    ))
+}
@@ -0,0 +1,288 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Interactive shell executing commands over a single connection to the
+//! daemon. The vault session unlocked in the shell is used by all further
+//! commands, so the passphrase is asked once, and is locked when the shell
+//! exits.
+
+use clap::{App, Clap, IntoApp};
+use microservices::shell::Exec;
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+
+use super::{Client, Command, SecretSource, ShellLine};
+use crate::rpc::types::SessionToken;
+use crate::rpc::{self, FailureCode};
+
+const PROMPT: &str = "keyring> ";
+const UNLOCKED_PROMPT: &str = "keyring*> ";
+
+/// Completes command names and identifiers of the vault accounts
+struct ShellHelper {
+    commands: App<'static>,
+    accounts: Vec<String>,
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let line = &line[..pos];
+        let start = line
+            .rfind(char::is_whitespace)
+            .map(|pos| pos + 1)
+            .unwrap_or(0);
+        let word = &line[start..];
+
+        let mut app = &self.commands;
+        for name in line[..start].split_whitespace() {
+            match app.get_subcommands().find(|sub| sub.get_name() == name) {
+                Some(sub) => app = sub,
+                None => break,
+            }
+        }
+        let mut candidates = app
+            .get_subcommands()
+            .map(|sub| sub.get_name().to_owned())
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            candidates = self.accounts.clone();
+        }
+        let candidates = candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(word))
+            .map(|candidate| Pair {
+                display: candidate.clone(),
+                replacement: candidate,
+            })
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+pub struct Shell {
+    editor: Editor<ShellHelper>,
+    history: String,
+    passphrase: Option<SecretSource>,
+    /// Vault session unlocked by the shell, which is locked on exit
+    session: Option<SessionToken>,
+}
+
+impl Shell {
+    /// Prepares the shell, loading command history from the `history` file
+    /// and the account identifiers for the completion from the daemon
+    pub fn with(
+        runtime: &mut Client,
+        history: &str,
+        passphrase: Option<SecretSource>,
+    ) -> Result<Self, rpc::Error> {
+        let mut editor = Editor::new();
+        editor.set_helper(Some(ShellHelper {
+            commands: ShellLine::into_app(),
+            accounts: vec![],
+        }));
+        if editor.load_history(history).is_err() {
+            debug!("No command history is found at {}", history);
+        }
+        let mut shell = Shell {
+            editor,
+            history: history.to_owned(),
+            passphrase,
+            session: None,
+        };
+        shell.refresh(runtime)?;
+        Ok(shell)
+    }
+
+    /// Reads and executes commands until `exit` command or end of input
+    pub fn run(&mut self, runtime: &mut Client) -> Result<(), rpc::Error> {
+        eprintln!("Type `help` for the list of commands, `exit` to quit");
+        loop {
+            let prompt = match runtime.session() {
+                Some(_) => UNLOCKED_PROMPT,
+                None => PROMPT,
+            };
+            let line = match self.editor.readline(prompt) {
+                Ok(line) => line,
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => break,
+                Err(err) => {
+                    self.close(runtime)?;
+                    return Err(rpc::Error::Shell(err.to_string()));
+                }
+            };
+            let words = match shell_words::split(&line) {
+                Ok(words) if words.is_empty() => continue,
+                Ok(words) => words,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    continue;
+                }
+            };
+            self.editor.add_history_entry(line.as_str());
+
+            let result = match words[0].as_str() {
+                "exit" | "quit" => break,
+                "lock" if words.len() == 1 => self.lock(runtime),
+                _ => match ShellLine::try_parse_from(words) {
+                    Ok(ShellLine { command }) => self.exec(runtime, command),
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        continue;
+                    }
+                },
+            };
+            if let Err(err) = result {
+                eprintln!("{}", err);
+            }
+        }
+        self.close(runtime)
+    }
+
+    /// Unlocks the vault, using the new session for all further commands
+    pub fn unlock(&mut self, runtime: &mut Client) -> Result<(), rpc::Error> {
+        let session = runtime.unlock(&self.passphrase)?;
+        eprintln!(
+            "Vault is unlocked for {} seconds; `lock` locks it",
+            session.expires_in
+        );
+        runtime.set_session(Some(session.token));
+        self.session = Some(session.token);
+        Ok(())
+    }
+
+    /// Locks the vault session unlocked by the shell
+    fn lock(&mut self, runtime: &mut Client) -> Result<(), rpc::Error> {
+        let session = match self.session.take() {
+            Some(session) => session,
+            None => {
+                eprintln!("Vault was not unlocked in this shell");
+                return Ok(());
+            }
+        };
+        runtime.set_session(None);
+        let reply =
+            runtime.request(rpc::Request::Lock(rpc::message::Lock {
+                session,
+                auth_code: rpc::types::AuthCode::default(),
+            }))?;
+        match reply {
+            rpc::Reply::Success => {
+                eprintln!("Vault session is locked");
+                Ok(())
+            }
+            rpc::Reply::Failure(failure) => {
+                Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => Err(rpc::Error::UnexpectedServerResponse),
+        }
+    }
+
+    /// Executes the command, unlocking the vault and repeating the command
+    /// if the daemon reports that the vault is locked or the shell session
+    /// has expired
+    fn exec(
+        &mut self,
+        runtime: &mut Client,
+        command: Command,
+    ) -> Result<(), rpc::Error> {
+        match command {
+            Command::Shell { .. } => {
+                return Err(rpc::Error::Shell(s!("shell is already running")))
+            }
+            Command::Unlock { passphrase } => {
+                if passphrase.is_some() {
+                    self.passphrase = passphrase;
+                }
+                return self.unlock(runtime);
+            }
+            Command::Lock { session } if Some(session) == self.session => {
+                return self.lock(runtime)
+            }
+            _ => {}
+        }
+
+        let result = match command.clone().exec(runtime) {
+            Err(rpc::Error::ServerFailure(failure))
+                if failure.code == FailureCode::VaultLocked as u16
+                    || failure.code == FailureCode::UnknownSession as u16 =>
+            {
+                eprintln!("{}", failure.info);
+                if failure.code == FailureCode::UnknownSession as u16 {
+                    runtime.set_session(None);
+                    self.session = None;
+                }
+                self.unlock(runtime)?;
+                command.exec(runtime)
+            }
+            result => result,
+        };
+        self.refresh(runtime)?;
+        result
+    }
+
+    /// Updates account identifiers used for the completion
+    fn refresh(&mut self, runtime: &mut Client) -> Result<(), rpc::Error> {
+        let accounts = match runtime.request(rpc::Request::List)? {
+            rpc::Reply::Keylist(accounts) => accounts,
+            rpc::Reply::Failure(failure) => {
+                return Err(rpc::Error::ServerFailure(failure))
+            }
+            _ => return Err(rpc::Error::UnexpectedServerResponse),
+        };
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.accounts = accounts
+                .iter()
+                .flat_map(|info| {
+                    vec![info.key_id.to_string(), info.fingerprint.to_string()]
+                })
+                .collect();
+        }
+        Ok(())
+    }
+
+    /// Saves command history and locks the vault session unlocked by the
+    /// shell
+    fn close(&mut self, runtime: &mut Client) -> Result<(), rpc::Error> {
+        if let Err(err) = self.editor.save_history(&self.history) {
+            warn!(
+                "Unable to save command history to {}: {}",
+                self.history, err
+            );
+        }
+        match self.session {
+            Some(_) => self.lock(runtime),
+            None => Ok(()),
+        }
+    }
+}
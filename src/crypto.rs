@@ -0,0 +1,126 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! ElGamal encryption of secrets with the same scheme as used by the vault
+//! for storing private keys. Allows clients to pre-encrypt secrets destined
+//! for the daemon and to decrypt data exported by the daemon without
+//! re-implementing the scheme.
+//!
+//! Encrypted secrets are wrapped into a single byte string consisting of the
+//! 33-byte serialized unblinding public key followed by the ElGamal-encrypted
+//! data.
+
+use bitcoin::secp256k1::rand::{thread_rng, RngCore};
+use bitcoin::secp256k1::{self, PublicKey, SecretKey};
+use lnpbp::elgamal;
+
+/// Length of the unblinding key prefix in the wrapped secret
+pub const UNBLINDING_LEN: usize = secp256k1::constants::PUBLIC_KEY_SIZE;
+
+/// Error cases of secret encryption and decryption
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum Error {
+    /// Wrapped secret is too short to contain unblinding key
+    TooShort,
+
+    /// Decryption key is not a valid secp256k1 secret key
+    InvalidKey,
+
+    /// Wrapped secret contains invalid unblinding key
+    InvalidUnblinding,
+
+    /// Encrypted data are corrupted or decryption key does not match the
+    /// encryption key
+    Corrupted,
+
+    /// Blinding key has produced elliptic curve group overflow; please try
+    /// again
+    GroupOverflow,
+
+    /// Not enough memory to encrypt or decrypt the data
+    NotEnoughMemory,
+
+    /// Secp256k1 library is broken
+    Secp256k1Broken,
+}
+
+impl From<elgamal::Error> for Error {
+    fn from(err: elgamal::Error) -> Self {
+        match err {
+            elgamal::Error::UnpaddedLength
+            | elgamal::Error::InvalidEncryptedMessage => Self::Corrupted,
+            elgamal::Error::GroupOverflow => Self::GroupOverflow,
+            elgamal::Error::NotEnoughMemory => Self::NotEnoughMemory,
+            elgamal::Error::Secp256k1Broken => Self::Secp256k1Broken,
+        }
+    }
+}
+
+/// Encrypts `data` for the owner of `encryption_key` with a random blinding
+/// key, which is wiped out right after the encryption. Returns encrypted data
+/// and the unblinding key required for the decryption.
+pub fn encrypt(
+    data: &[u8],
+    encryption_key: PublicKey,
+) -> Result<(Vec<u8>, PublicKey), Error> {
+    let mut blinding = SecretKey::new(&mut thread_rng());
+    let unblinding = PublicKey::from_secret_key(&crate::SECP256K1, &blinding);
+    let encrypted = elgamal::encrypt(data, encryption_key, &mut blinding);
+    wipe(&mut blinding);
+    Ok((encrypted?, unblinding))
+}
+
+/// Decrypts `encrypted` data with the `decryption_key` and `unblinding` key
+/// produced by [`encrypt`]. The provided decryption key is not modified.
+pub fn decrypt(
+    encrypted: &[u8],
+    decryption_key: &SecretKey,
+    unblinding: PublicKey,
+) -> Result<Vec<u8>, Error> {
+    let mut decryption_key = *decryption_key;
+    let data = elgamal::decrypt(encrypted, &mut decryption_key, unblinding);
+    wipe(&mut decryption_key);
+    Ok(data?)
+}
+
+/// Encrypts `data` with [`encrypt`] and wraps the result together with the
+/// unblinding key into a single byte string
+pub fn wrap(data: &[u8], encryption_key: PublicKey) -> Result<Vec<u8>, Error> {
+    let (encrypted, unblinding) = encrypt(data, encryption_key)?;
+    let mut wrapped = Vec::with_capacity(UNBLINDING_LEN + encrypted.len());
+    wrapped.extend_from_slice(&unblinding.serialize());
+    wrapped.extend_from_slice(&encrypted);
+    Ok(wrapped)
+}
+
+/// Decrypts secret wrapped with [`wrap`]
+pub fn unwrap(
+    wrapped: &[u8],
+    decryption_key: &SecretKey,
+) -> Result<Vec<u8>, Error> {
+    if wrapped.len() <= UNBLINDING_LEN {
+        return Err(Error::TooShort);
+    }
+    let unblinding = PublicKey::from_slice(&wrapped[..UNBLINDING_LEN])
+        .map_err(|_| Error::InvalidUnblinding)?;
+    decrypt(&wrapped[UNBLINDING_LEN..], decryption_key, unblinding)
+}
+
+fn wipe(key: &mut SecretKey) {
+    let mut random = [0u8; 32];
+    thread_rng().fill_bytes(&mut random);
+    let _ = key
+        .add_assign(&random)
+        .map_err(|_| *key = secp256k1::key::ONE_KEY);
+}
@@ -0,0 +1,75 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! One-time tokens approving export of extended private keys. Client has to
+//! request approval for a specific account with a separate request and then
+//! present the token with the export request, so a single replayed or
+//! misdirected request can't make the daemon disclose a private key.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::rand::{thread_rng, RngCore};
+use bitcoin::XpubIdentifier;
+
+use crate::rpc::types::ApprovalToken;
+
+/// Period during which an issued approval token can be used
+pub const APPROVAL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Set of the outstanding export approvals
+#[derive(Default)]
+pub struct Approvals {
+    tokens: HashMap<ApprovalToken, (XpubIdentifier, Instant)>,
+}
+
+impl Approvals {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Issues new token approving export of the private key for the account
+    /// `key_id`
+    pub fn issue(&mut self, key_id: XpubIdentifier) -> ApprovalToken {
+        self.expire();
+        let mut random = [0u8; 32];
+        thread_rng().fill_bytes(&mut random);
+        let token = sha256::Hash::from_inner(random);
+        self.tokens.insert(token, (key_id, Instant::now()));
+        debug!("Issued export approval {} for account {}", token, key_id);
+        token
+    }
+
+    /// Consumes the `token` if it approves export for the account `key_id`.
+    /// Returns whether the export is approved.
+    pub fn consume(
+        &mut self,
+        token: ApprovalToken,
+        key_id: XpubIdentifier,
+    ) -> bool {
+        self.expire();
+        match self.tokens.get(&token) {
+            Some((approved, _)) if *approved == key_id => {
+                self.tokens.remove(&token);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expire(&mut self) {
+        self.tokens
+            .retain(|_, (_, issued)| issued.elapsed() < APPROVAL_TIMEOUT);
+    }
+}
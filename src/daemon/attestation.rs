@@ -0,0 +1,159 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Remote attestation of the daemon deployed inside a trusted execution
+//! environment. The daemon asks the platform for a quote over the report
+//! data committing to its node id and the client nonce (see
+//! [`crate::rpc::types::Attestation::report_data`]); the client checks the
+//! commitment and verifies the quote with the platform vendor before sending
+//! sensitive requests over the encrypted channel.
+//!
+//! Platforms are supported through [`Attester`] implementations; the built-in
+//! ones use the attestation pseudo-filesystem exposed by library OSes like
+//! Gramine, or an external program producing the quote.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use bitcoin::hashes::hex::ToHex;
+
+/// Configuration of the platform attestation provider
+#[derive(Clone, PartialEq, Eq, Debug, Display, Serialize, Deserialize)]
+#[serde(crate = "serde_crate", tag = "provider")]
+#[display(Debug)]
+#[non_exhaustive]
+pub enum Config {
+    /// Attestation pseudo-filesystem at `path`: the report data are written
+    /// to `user_report_data` file, after which the quote is read from
+    /// `quote` file (`/dev/attestation` under Gramine SGX)
+    Device { platform: String, path: String },
+
+    /// External `program` run with `args`, where `{report_data}` is
+    /// replaced with the hex-encoded report data; the program must write
+    /// the quote to STDOUT and exit with zero status
+    Command {
+        platform: String,
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+/// Error cases related to the platform attestation
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum Error {
+    /// Attestation device {0} is not accessible: {1}
+    Device(String, String),
+
+    /// Attestation program `{0}` has failed: {1}
+    Command(String, String),
+
+    /// Platform has produced an empty quote
+    EmptyQuote,
+}
+
+/// Producer of the platform quotes for the daemon running inside a trusted
+/// execution environment
+pub trait Attester: Send {
+    /// Name of the platform, reported to the clients together with the quote
+    fn platform(&self) -> &str;
+
+    /// Returns quote over 64 bytes of the `report_data`
+    fn quote(&mut self, report_data: &[u8; 64]) -> Result<Vec<u8>, Error>;
+}
+
+/// Constructs attestation provider from its configuration
+pub fn attester_with(config: &Config) -> Box<dyn Attester> {
+    match config {
+        Config::Device { platform, path } => Box::new(DeviceAttester {
+            platform: platform.clone(),
+            path: PathBuf::from(path),
+        }),
+        Config::Command {
+            platform,
+            program,
+            args,
+        } => Box::new(CommandAttester {
+            platform: platform.clone(),
+            program: program.clone(),
+            args: args.clone(),
+        }),
+    }
+}
+
+/// Attestation pseudo-filesystem provider; see [`Config::Device`]
+struct DeviceAttester {
+    platform: String,
+    path: PathBuf,
+}
+
+impl Attester for DeviceAttester {
+    fn platform(&self) -> &str {
+        &self.platform
+    }
+
+    fn quote(&mut self, report_data: &[u8; 64]) -> Result<Vec<u8>, Error> {
+        let err = |path: PathBuf| {
+            move |err: std::io::Error| {
+                Error::Device(path.display().to_string(), err.to_string())
+            }
+        };
+        let path = self.path.join("user_report_data");
+        fs::write(&path, &report_data[..]).map_err(err(path))?;
+        let path = self.path.join("quote");
+        let quote = fs::read(&path).map_err(err(path))?;
+        if quote.is_empty() {
+            return Err(Error::EmptyQuote);
+        }
+        Ok(quote)
+    }
+}
+
+/// External program provider; see [`Config::Command`]
+struct CommandAttester {
+    platform: String,
+    program: String,
+    args: Vec<String>,
+}
+
+impl Attester for CommandAttester {
+    fn platform(&self) -> &str {
+        &self.platform
+    }
+
+    fn quote(&mut self, report_data: &[u8; 64]) -> Result<Vec<u8>, Error> {
+        let report_data = report_data.to_hex();
+        let output = Command::new(&self.program)
+            .args(
+                self.args
+                    .iter()
+                    .map(|arg| arg.replace("{report_data}", &report_data)),
+            )
+            .output()
+            .map_err(|err| {
+                Error::Command(self.program.clone(), err.to_string())
+            })?;
+        if !output.status.success() {
+            return Err(Error::Command(
+                self.program.clone(),
+                String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+            ));
+        }
+        if output.stdout.is_empty() {
+            return Err(Error::EmptyQuote);
+        }
+        Ok(output.stdout)
+    }
+}
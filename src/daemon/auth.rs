@@ -0,0 +1,347 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Not;
+use std::time::{Duration, Instant};
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::rand::{thread_rng, RngCore};
+use serde_with::hex::Hex;
+
+use crate::error::RuntimeError;
+use crate::rpc::auth::{
+    timestamp_challenge, unix_time, Challenge, TIMESTAMP_TOLERANCE,
+};
+use crate::rpc::types::{AccountInfo, Branches, SigningPolicy};
+use crate::rpc::{Reply, Request};
+
+/// Period during which an issued challenge can be used for authorization
+pub const CHALLENGE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Maximal number of challenges which may be outstanding for a client
+pub const MAX_CHALLENGES: usize = 16;
+
+/// Configuration of a client authorized to send requests to the daemon
+#[serde_as]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(crate = "serde_crate")]
+pub struct ClientConfig {
+    /// Secret shared between the client and the daemon
+    #[serde_as(as = "Hex")]
+    pub secret: Vec<u8>,
+
+    /// Account metadata fields removed from the replies sent to the client
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub redact: BTreeSet<AccountField>,
+
+    /// Whether the client may change daemon settings at runtime with
+    /// `reconfigure` request
+    #[serde(default, skip_serializing_if = "Not::not")]
+    pub admin: bool,
+}
+
+/// Account metadata fields which may be hidden from a client
+#[derive(
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Debug,
+    Serialize,
+    Deserialize,
+)]
+#[serde(crate = "serde_crate", rename_all = "snake_case")]
+pub enum AccountField {
+    /// Account name, replaced with an empty string, and its aliases
+    Name,
+
+    /// Account description
+    Details,
+
+    /// Assets known to the account
+    Assets,
+
+    /// Key application (SegWit, nested SegWit etc)
+    Application,
+
+    /// Origin of the key account was derived from
+    KeySource,
+
+    /// Address branches, reset to the defaults
+    Branches,
+
+    /// Signing policy, reset to the default one
+    Policy,
+
+    /// Account labels and tags
+    Labels,
+}
+
+impl AccountField {
+    /// All the fields, which are hidden from the callers not authorized as
+    /// any of the configured clients
+    pub const ALL: [AccountField; 8] = [
+        AccountField::Name,
+        AccountField::Details,
+        AccountField::Assets,
+        AccountField::Application,
+        AccountField::KeySource,
+        AccountField::Branches,
+        AccountField::Policy,
+        AccountField::Labels,
+    ];
+}
+
+impl ClientConfig {
+    /// Removes account metadata fields which the client may not see from
+    /// the account information contained in the `reply`
+    pub fn redact(&self, reply: Reply) -> Reply {
+        redact(reply, &self.redact)
+    }
+}
+
+fn redact<'a>(
+    reply: Reply,
+    fields: impl IntoIterator<Item = &'a AccountField> + Copy,
+) -> Reply {
+    if fields.into_iter().next().is_none() {
+        return reply;
+    }
+    match reply {
+        Reply::Keylist(mut list) => {
+            list.iter_mut().for_each(|info| redact_info(info, fields));
+            Reply::Keylist(list)
+        }
+        Reply::AccountInfo(mut info) => {
+            redact_info(&mut info, fields);
+            Reply::AccountInfo(info)
+        }
+        Reply::BalanceList(mut list) => {
+            list.iter_mut()
+                .for_each(|balance| redact_info(&mut balance.info, fields));
+            Reply::BalanceList(list)
+        }
+        reply => reply,
+    }
+}
+
+fn redact_info<'a>(
+    info: &mut AccountInfo,
+    fields: impl IntoIterator<Item = &'a AccountField>,
+) {
+    for field in fields {
+        match field {
+            AccountField::Name => {
+                info.name = s!("");
+                info.aliases.clear();
+            }
+            AccountField::Details => info.details = None,
+            AccountField::Assets => info.assets.clear(),
+            AccountField::Application => info.application = None,
+            AccountField::KeySource => info.key_source = None,
+            AccountField::Branches => info.branches = Branches::default(),
+            AccountField::Policy => info.policy = SigningPolicy::default(),
+            AccountField::Labels => info.labels.clear(),
+        }
+    }
+}
+
+/// Authorization subsystem checking request auth codes against the
+/// challenges issued to the clients or the timestamps
+pub struct Authenticator {
+    clients: BTreeMap<String, ClientConfig>,
+    /// Outstanding challenges issued to each of the clients
+    challenges: HashMap<String, HashMap<Challenge, Instant>>,
+    /// Last timestamp used by each of the clients
+    timestamps: HashMap<String, u64>,
+}
+
+impl Authenticator {
+    pub fn with(clients: BTreeMap<String, ClientConfig>) -> Self {
+        Self {
+            clients,
+            challenges: Default::default(),
+            timestamps: Default::default(),
+        }
+    }
+
+    /// Replaces configuration of the clients. Last timestamps of the clients
+    /// which remain configured are kept, so their old requests can't be
+    /// replayed.
+    pub fn set_clients(&mut self, clients: BTreeMap<String, ClientConfig>) {
+        self.timestamps.retain(|name, _| clients.contains_key(name));
+        self.challenges.retain(|name, _| clients.contains_key(name));
+        self.clients = clients;
+    }
+
+    /// Returns configuration of the client with a given `name`
+    pub fn client(&self, name: &str) -> Option<&ClientConfig> {
+        self.clients.get(name)
+    }
+
+    /// Authorization is enforced only if some clients are configured
+    pub fn is_enabled(&self) -> bool {
+        !self.clients.is_empty()
+    }
+
+    /// Removes account metadata fields from the `reply` sent to the
+    /// `client`. If authorization is enforced, replies to the requests which
+    /// are not authorized as any of the configured clients (like `list`,
+    /// which carries no auth code) have all the [`AccountField::ALL`] fields
+    /// removed.
+    pub fn redact(&self, reply: Reply, client: Option<&str>) -> Reply {
+        match client.and_then(|name| self.clients.get(name)) {
+            Some(config) => config.redact(reply),
+            None if self.is_enabled() => redact(reply, &AccountField::ALL),
+            None => reply,
+        }
+    }
+
+    /// Issues new single-use challenge for the `client`. Challenges are
+    /// issued only to the configured clients, each of which may have at
+    /// most [`MAX_CHALLENGES`] outstanding challenges.
+    pub fn challenge(
+        &mut self,
+        client: &str,
+    ) -> Result<Challenge, RuntimeError> {
+        if !self.clients.contains_key(client) {
+            warn!("Challenge is requested for unknown client `{}`", client);
+            return Err(RuntimeError::Unauthorized);
+        }
+        self.expire();
+        let challenges = self.challenges.entry(client.to_owned()).or_default();
+        if challenges.len() >= MAX_CHALLENGES {
+            warn!("Client `{}` has too many outstanding challenges", client);
+            return Err(RuntimeError::Unauthorized);
+        }
+        let mut random = [0u8; 32];
+        thread_rng().fill_bytes(&mut random);
+        let challenge = sha256::Hash::from_inner(random);
+        challenges.insert(challenge, Instant::now());
+        trace!(
+            "Issued authorization challenge {} to `{}`",
+            challenge,
+            client
+        );
+        Ok(challenge)
+    }
+
+    /// Checks that the request auth code is computed by the client it names
+    /// for the challenge outstanding for the client, or for a timestamp
+    /// close to the current time; the challenge or timestamp is consumed.
+    /// Returns name of the authorized client.
+    pub fn authorize(
+        &mut self,
+        request: &Request,
+    ) -> Result<Option<String>, RuntimeError> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+        let auth_code = match request.clone().auth_code_mut() {
+            Some(auth_code) => auth_code.clone(),
+            None => return Ok(None),
+        };
+        let unauthorized = || {
+            warn!("Unauthorized request {}", request);
+            RuntimeError::Unauthorized
+        };
+        let name = auth_code.client;
+        let client = self.clients.get(&name).ok_or_else(unauthorized)?;
+        let hmac = request.compute_auth_hmac(&client.secret);
+        if !constant_time_eq(&hmac[..], &auth_code.hmac[..]) {
+            return Err(unauthorized());
+        }
+
+        match auth_code.timestamp {
+            Some(timestamp) => {
+                let now = unix_time();
+                let fresh = auth_code.challenge
+                    == timestamp_challenge(timestamp)
+                    && timestamp.saturating_add(TIMESTAMP_TOLERANCE) >= now
+                    && timestamp <= now + TIMESTAMP_TOLERANCE
+                    && self
+                        .timestamps
+                        .get(&name)
+                        .map(|last| timestamp > *last)
+                        .unwrap_or(true);
+                if !fresh {
+                    return Err(unauthorized());
+                }
+                self.timestamps.insert(name.clone(), timestamp);
+                debug!(
+                    "Request is authorized for client `{}` with timestamp {}",
+                    name, timestamp
+                );
+            }
+            None => {
+                self.expire();
+                self.challenges
+                    .get_mut(&name)
+                    .and_then(|challenges| {
+                        challenges.remove(&auth_code.challenge)
+                    })
+                    .ok_or_else(unauthorized)?;
+                debug!("Request is authorized for client `{}`", name);
+            }
+        }
+        Ok(Some(name))
+    }
+
+    /// Authorizes request received over gRPC, where the client presents its
+    /// `secret` instead of the auth code. Returns name of the client which
+    /// secret matches.
+    #[cfg(feature = "grpc")]
+    pub fn authorize_secret(
+        &self,
+        request: &Request,
+        secret: Option<&[u8]>,
+    ) -> Result<Option<String>, RuntimeError> {
+        if !self.is_enabled() || request.clone().auth_code_mut().is_none() {
+            return Ok(None);
+        }
+        let secret = secret.ok_or_else(|| {
+            warn!("Unauthorized gRPC request {}", request);
+            RuntimeError::Unauthorized
+        })?;
+        let client = self
+            .clients
+            .iter()
+            .find(|(_, client)| constant_time_eq(&client.secret, secret))
+            .map(|(name, _)| name.clone())
+            .ok_or_else(|| {
+                warn!("Unauthorized gRPC request {}", request);
+                RuntimeError::Unauthorized
+            })?;
+        debug!("gRPC request is authorized for client `{}`", client);
+        Ok(Some(client))
+    }
+
+    fn expire(&mut self) {
+        self.challenges.values_mut().for_each(|challenges| {
+            challenges.retain(|_, issued| issued.elapsed() < CHALLENGE_TIMEOUT)
+        });
+        self.challenges
+            .retain(|_, challenges| !challenges.is_empty());
+    }
+}
+
+/// Compares secrets in time independent of the position of the first
+/// mismatching byte
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,127 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Validation of the daemon configuration, vault and RPC socket without
+//! serving requests, used by `keyringd --check`
+
+use std::fs;
+use std::io;
+use std::net::TcpListener;
+use std::path::Path;
+
+use internet2::zmqsocket::ZmqSocketAddr;
+
+use super::Config;
+use crate::error::BootstrapError;
+use crate::rpc::types::DEFAULT_VAULT_ID;
+use crate::vault::{self, Encryption};
+use crate::Vault;
+
+/// Checks that the vaults can be read and decrypted and that the daemon is
+/// able to open its RPC socket. The vaults and the socket are not modified.
+pub fn check(config: &Config) -> Result<(), BootstrapError> {
+    info!("Configuration fingerprint: {}", config.fingerprint()?);
+
+    check_vault(DEFAULT_VAULT_ID, &config.vault, config)?;
+    for (id, vault_config) in &config.vaults {
+        check_vault(id, &vault_config.vault, config)?;
+    }
+
+    check_socket(&config.endpoint)?;
+    info!("RPC socket {} is available", config.endpoint);
+
+    Ok(())
+}
+
+fn check_vault(
+    id: &str,
+    driver: &vault::driver::Config,
+    config: &Config,
+) -> Result<(), BootstrapError> {
+    let location = match driver {
+        vault::driver::Config::File(ref fdc) => Some(&fdc.location),
+        #[cfg(feature = "sqlite")]
+        vault::driver::Config::Sqlite(ref sdc) => Some(&sdc.path),
+        _ => None,
+    };
+    if let Some(location) = location {
+        if !Path::new(location).is_file() {
+            return Err(BootstrapError::VaultIntegrity(format!(
+                "vault file {} does not exist",
+                location
+            )));
+        }
+    }
+    let vault = Vault::with(driver)?;
+    let accounts = vault
+        .list()
+        .map_err(|err| BootstrapError::VaultIntegrity(err.to_string()))?;
+    info!(
+        "Vault `{}` is readable and contains {} accounts",
+        id,
+        accounts.len()
+    );
+
+    match config.encryption {
+        Encryption::NodeKey => {
+            let mut node_key = config.node_key;
+            vault.verify_decryption_key(&mut node_key).map_err(|err| {
+                BootstrapError::VaultIntegrity(err.to_string())
+            })?;
+            info!("Vault `{}` is decryptable with the node key", id);
+        }
+        _ => info!(
+            "Vault `{}` is encrypted with a passphrase; decryption is not \
+             checked",
+            id
+        ),
+    }
+    Ok(())
+}
+
+fn check_socket(endpoint: &ZmqSocketAddr) -> Result<(), BootstrapError> {
+    match endpoint {
+        ZmqSocketAddr::Ipc(path) => {
+            let dir = Path::new(path)
+                .parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .unwrap_or_else(|| Path::new("."));
+            // Creating and removing a file is the only reliable way to check
+            // that the socket can be created with the current permissions
+            let probe = dir.join(format!(".keyringd-{}", std::process::id()));
+            fs::File::create(&probe)?;
+            fs::remove_file(&probe)?;
+            if Path::new(path).exists() {
+                warn!(
+                    "Socket file {} already exists; keyringd may be running",
+                    path
+                );
+            }
+        }
+        ZmqSocketAddr::Tcp(addr) => match TcpListener::bind(addr) {
+            Ok(_) => {}
+            Err(err) if err.kind() == io::ErrorKind::AddrInUse => warn!(
+                "Socket address {} is already in use; keyringd may be running",
+                addr
+            ),
+            Err(err) => Err(err)?,
+        },
+        ZmqSocketAddr::Inproc(name) => warn!(
+            "In-process socket {} is reachable only by the application \
+             embedding the daemon",
+            name
+        ),
+        _ => {}
+    }
+    Ok(())
+}
@@ -13,22 +13,34 @@
 
 use ::core::convert::{TryFrom, TryInto};
 use ::core::fmt::Display;
+use ::core::mem;
 use ::core::str::FromStr;
 use ::serde_with::DisplayFromStr;
 use ::settings::{self, Config as Settings, ConfigError};
-use ::std::fs::File;
+use ::std::collections::{BTreeMap, BTreeSet};
+use ::std::fs::{self, File};
 use ::std::io::Write;
+use ::std::net::SocketAddr;
+use ::std::path::Path;
 use ::std::process::exit;
 
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::hashes::{sha256, Hash};
 use bitcoin::secp256k1;
+use bitcoin::XpubIdentifier;
 use internet2::zmqsocket::ZmqSocketAddr;
+use lnpbp::chain::Chain;
 use microservices::shell::LogLevel;
 
 use super::opts::{KEYRING_VAULT_FILE, KEYRING_VAULT_FORMAT};
-use super::Opts;
-use crate::error::ConfigInitError;
+use super::{
+    attestation, ClientConfig, LogFormat, Opts, PayloadEncryption,
+    TransportEncryption,
+};
+use crate::error::{BootstrapError, ConfigInitError};
 use crate::opts::{KEYRING_DATA_DIR, KEYRING_RPC_SOCKET_NAME};
-use crate::vault;
+use crate::rpc::types::{VaultId, DEFAULT_VAULT_ID};
+use crate::{chain, passphrase, vault};
 
 #[serde_as]
 #[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
@@ -38,9 +50,107 @@ pub struct Config {
     pub node_key: secp256k1::SecretKey,
     pub data_dir: String,
     pub log_level: LogLevel,
+    /// Format of the log records: plain `text` or structured `json`
+    #[serde(default)]
+    pub log_format: LogFormat,
     #[serde_as(as = "DisplayFromStr")]
     pub endpoint: ZmqSocketAddr,
+    /// Address of the gRPC interface served alongside ZMQ RPC; requires
+    /// the daemon to be built with `grpc` feature
+    #[serde(default)]
+    pub grpc_endpoint: Option<SocketAddr>,
     pub vault: vault::driver::Config,
+    /// Encrypted backups of the vault written after each vault update
+    #[serde(default)]
+    pub backup: Option<vault::backup::Config>,
+    /// File of the hash-chained journal recording the vault updates; if
+    /// absent, the updates are not journaled
+    #[serde(default)]
+    pub journal: Option<String>,
+    /// Vaults served in addition to the default `vault`, identified by their
+    /// names; see [`crate::rpc::routed`] for the request routing
+    #[serde(default)]
+    pub vaults: BTreeMap<VaultId, VaultConfig>,
+    #[serde(default)]
+    pub chain_source: Option<chain::Config>,
+    /// File recording transactions signed by the vault; if absent, signed
+    /// transactions are not recorded
+    #[serde(default)]
+    pub ledger: Option<String>,
+    /// Directory storing Lightning revocation secrets of the accounts; if
+    /// absent, revocation storage requests are rejected
+    #[serde(default)]
+    pub revocations: Option<String>,
+    /// Provider of the platform quotes for the daemon deployed inside a
+    /// trusted execution environment; if absent, attestation requests are
+    /// rejected
+    #[serde(default)]
+    pub attestation: Option<attestation::Config>,
+    #[serde(default)]
+    pub encryption: vault::Encryption,
+    #[serde(default)]
+    pub passphrase: passphrase::Policy,
+    #[serde(default)]
+    pub clients: BTreeMap<String, ClientConfig>,
+    #[serde(default)]
+    pub transport_encryption: TransportEncryption,
+    /// Acceptance of the requests sealed to the node id, which secret fields
+    /// can't be read by the proxies between the client and the daemon
+    #[serde(default)]
+    pub payload_encryption: PayloadEncryption,
+    /// Serve only requests which do not modify the vault and do not use
+    /// private keys
+    #[serde(default)]
+    pub read_only: bool,
+    /// Identifiers of the keyrings which extended private keys (including
+    /// the keys of their sub-accounts) may be exported; export is disabled
+    /// for all keyrings by default
+    #[serde(default)]
+    pub xpriv_export: BTreeSet<XpubIdentifier>,
+    /// Serve vault data to front daemons using remote vault driver. Request
+    /// authorization must be enabled with `clients`.
+    #[serde(default)]
+    pub federation: bool,
+    /// Number of worker threads serving client requests concurrently
+    #[serde(default = "default_workers")]
+    pub workers: usize,
+    /// Number of signing keys cached by each unlocked session, so repeated
+    /// PSBT signing does not decrypt and derive the keys again; zero
+    /// disables the cache
+    #[serde(default)]
+    pub signing_cache: usize,
+    /// Command-line arguments the configuration was read with, used to
+    /// re-read the configuration file
+    #[serde(skip)]
+    source: Option<Opts>,
+}
+
+/// Configuration of a vault served by the daemon in addition to the default
+/// one. All vaults are encrypted in the same way, as configured with
+/// `encryption` setting.
+#[serde_as]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(crate = "serde_crate")]
+pub struct VaultConfig {
+    /// Chains which new keyrings are created in the vault
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(default)]
+    pub chains: Vec<Chain>,
+    /// Encrypted backups of the vault written after each vault update
+    #[serde(default)]
+    pub backup: Option<vault::backup::Config>,
+    /// File of the hash-chained journal recording the vault updates
+    #[serde(default)]
+    pub journal: Option<String>,
+    #[serde(flatten)]
+    pub vault: vault::driver::Config,
+}
+
+/// Default number of worker threads serving client requests
+pub const DEFAULT_WORKERS: usize = 4;
+
+fn default_workers() -> usize {
+    DEFAULT_WORKERS
 }
 
 impl TryFrom<Opts> for Config {
@@ -50,6 +160,7 @@ impl TryFrom<Opts> for Config {
         let log_level =
             LogLevel::from_verbosity_flag_count(opts.shared.verbose);
 
+        let source = opts.clone();
         let mut proto = Self::default();
         proto.data_dir = opts.shared.data_dir.to_string_lossy().to_string();
 
@@ -80,18 +191,57 @@ impl TryFrom<Opts> for Config {
 
         trace!("Applying command-line arguments & environment");
         me.data_dir = proto.data_dir;
-        me.log_level = log_level;
+        if opts.shared.verbose > 0 || opts.shared.init {
+            me.log_level = log_level;
+        }
+        me.source = Some(source);
+        me.read_only |= opts.read_only;
+        if let Some(workers) = opts.workers {
+            me.workers = workers;
+        }
+        if let Some(signing_cache) = opts.signing_cache {
+            me.signing_cache = signing_cache;
+        }
+        if me.workers == 0 {
+            return Err(ConfigError::Message(s!(
+                "at least one worker thread is required"
+            )));
+        }
         me.endpoint = opts
             .shared
             .rpc_socket
             .try_into()
             .expect("Only ZMQ RPC is supported");
 
-        match me.vault {
-            vault::driver::Config::File(ref mut fdc) => {
-                fdc.location = format!("{}/{}", me.data_dir, fdc.location)
+        let (data_dir, node_key, read_only) =
+            (me.data_dir.clone(), me.node_key, me.read_only);
+        locate_vault(&mut me.vault, &data_dir, node_key, read_only);
+        for (id, config) in &mut me.vaults {
+            if id == DEFAULT_VAULT_ID {
+                return Err(ConfigError::Message(format!(
+                    "vault name `{}` is reserved for the default vault",
+                    DEFAULT_VAULT_ID
+                )));
+            }
+            locate_vault(&mut config.vault, &data_dir, node_key, read_only);
+            if let Some(ref mut backup) = config.backup {
+                backup.dir = format!("{}/{}", data_dir, backup.dir);
             }
-            _ => {}
+            if let Some(ref mut journal) = config.journal {
+                *journal = format!("{}/{}", data_dir, journal);
+            }
+        }
+        if let Some(ref mut backup) = me.backup {
+            backup.dir = format!("{}/{}", me.data_dir, backup.dir);
+        }
+        if let Some(ref mut journal) = me.journal {
+            *journal = format!("{}/{}", me.data_dir, journal);
+        }
+        if let Some(ref mut ledger) = me.ledger {
+            *ledger = format!("{}/{}", me.data_dir, ledger);
+        }
+        if let Some(ref mut revocations) = me.revocations {
+            *revocations = format!("{}/{}", me.data_dir, revocations);
         }
 
         if opts.shared.init {
@@ -106,6 +256,30 @@ impl TryFrom<Opts> for Config {
             exit(0);
         }
 
+        if let Some(ref key_file) = opts.sign_config {
+            match me.sign(&conf_file, key_file) {
+                Ok(sig_file) => {
+                    println!(
+                        "Configuration signature is written to {}",
+                        sig_file
+                    );
+                    exit(0);
+                }
+                Err(err) => {
+                    error!("Error signing configuration: {}", err);
+                    eprintln!(
+                        "Unable to sign configuration file {}: {}",
+                        conf_file, err
+                    );
+                    exit(1);
+                }
+            }
+        }
+
+        if let Some(admin_key) = opts.admin_key {
+            me.verify_signature(&conf_file, admin_key)?;
+        }
+
         debug!("Configuration successfully loaded");
         Ok(me)
     }
@@ -122,15 +296,39 @@ impl Default for Config {
                 .parse()
                 .expect("Error in KEYRING_DATA_DIR constant value"),
             log_level: LogLevel::Warn,
+            log_format: LogFormat::Text,
             endpoint: KEYRING_RPC_SOCKET_NAME
                 .parse()
                 .expect("Error in KEYRING_ZMQ_ENDPOINT constant value"),
+            grpc_endpoint: None,
             vault: vault::driver::Config::File(vault::file_driver::Config {
                 location: KEYRING_VAULT_FILE
                     .parse()
                     .expect("Error in KEYRING_VAULT_FILE constant value"),
                 format: KEYRING_VAULT_FORMAT,
+                backups: vault::file_driver::DEFAULT_BACKUPS,
+                signed: false,
+                node_key: None,
+                read_only: false,
             }),
+            backup: Some(vault::backup::Config::default()),
+            journal: None,
+            vaults: BTreeMap::new(),
+            chain_source: None,
+            ledger: None,
+            revocations: None,
+            attestation: None,
+            encryption: vault::Encryption::NodeKey,
+            passphrase: passphrase::Policy::default(),
+            clients: BTreeMap::new(),
+            transport_encryption: TransportEncryption::Optional,
+            payload_encryption: PayloadEncryption::Optional,
+            read_only: false,
+            xpriv_export: BTreeSet::new(),
+            federation: false,
+            workers: DEFAULT_WORKERS,
+            signing_cache: 0,
+            source: None,
         }
     }
 }
@@ -155,6 +353,213 @@ impl Config {
     pub fn node_id(&self) -> secp256k1::PublicKey {
         secp256k1::PublicKey::from_secret_key(&crate::SECP256K1, &self.node_key)
     }
+
+    /// Computes fingerprint of the effective configuration, which is a
+    /// SHA256 hash of its canonical TOML serialization. The node secret key
+    /// is replaced with the node id before hashing, so the fingerprint can be
+    /// published without disclosing any secrets; client secrets are replaced
+    /// with their hashes for the same reason. Log level is excluded since
+    /// it may be overridden by command-line verbosity flags.
+    pub fn fingerprint(&self) -> Result<sha256::Hash, BootstrapError> {
+        let mut value = toml::Value::try_from(self)?;
+        if let Some(table) = value.as_table_mut() {
+            table.remove("node_key");
+            table.remove("log_level");
+            if let Some(clients) =
+                table.get_mut("clients").and_then(toml::Value::as_table_mut)
+            {
+                for (name, client) in clients.iter_mut() {
+                    let secret = toml::Value::String(
+                        sha256::Hash::hash(&self.clients[name].secret)
+                            .to_string(),
+                    );
+                    // Clients without redacted fields are hashed as before,
+                    // keeping fingerprints of the existing configurations
+                    match client.as_table_mut() {
+                        Some(table) if table.len() > 1 => {
+                            table.insert(s!("secret"), secret);
+                        }
+                        _ => *client = secret,
+                    }
+                }
+            }
+            table.insert(
+                s!("node_id"),
+                toml::Value::String(self.node_id().to_string()),
+            );
+        }
+        Ok(sha256::Hash::hash(value.to_string().as_bytes()))
+    }
+
+    /// Re-reads the configuration file with the command-line arguments the
+    /// daemon was started with. The re-read configuration is verified in
+    /// the same way as at the daemon start, including its signature.
+    pub fn reload(&self) -> Result<Config, ConfigError> {
+        let source = self.source.clone().ok_or_else(|| {
+            ConfigError::Message(s!(
+                "configuration was not read from a configuration file"
+            ))
+        })?;
+        // Reading configuration at the daemon start exits the process if
+        // the file is absent, so its presence is checked first
+        let conf_file: String = self.parse_param(source.config.clone());
+        Settings::new().merge(settings::File::with_name(&conf_file))?;
+        Config::try_from(source)
+    }
+
+    /// Lists settings which differ in the `other` configuration, but can't
+    /// be changed without restarting the daemon. Encryption of the vault
+    /// can't be changed, while the unlock timeout of passphrase-encrypted
+    /// vault can.
+    pub fn immutable_changes(&self, other: &Config) -> Vec<&'static str> {
+        let mut changes = vec![];
+        let mut check = |name, changed| {
+            if changed {
+                changes.push(name)
+            }
+        };
+        check("node_key", self.node_key != other.node_key);
+        check("data_dir", self.data_dir != other.data_dir);
+        check("endpoint", self.endpoint != other.endpoint);
+        check("grpc_endpoint", self.grpc_endpoint != other.grpc_endpoint);
+        check("vault", self.vault != other.vault);
+        check("backup", self.backup != other.backup);
+        check("journal", self.journal != other.journal);
+        check("vaults", self.vaults != other.vaults);
+        check("chain_source", self.chain_source != other.chain_source);
+        check("ledger", self.ledger != other.ledger);
+        check("revocations", self.revocations != other.revocations);
+        check("attestation", self.attestation != other.attestation);
+        check(
+            "encryption",
+            mem::discriminant(&self.encryption)
+                != mem::discriminant(&other.encryption),
+        );
+        check(
+            "transport_encryption",
+            self.transport_encryption != other.transport_encryption,
+        );
+        check(
+            "payload_encryption",
+            self.payload_encryption != other.payload_encryption,
+        );
+        check("read_only", self.read_only != other.read_only);
+        check("federation", self.federation != other.federation);
+        check("workers", self.workers != other.workers);
+        changes
+    }
+
+    /// Verifies that the configuration fingerprint is signed by the
+    /// administrator key. The signature is read from `<conf_file>.sig`
+    /// file containing hex-encoded DER ECDSA signature.
+    pub fn verify_signature(
+        &self,
+        conf_file: &str,
+        admin_key: secp256k1::PublicKey,
+    ) -> Result<(), ConfigError> {
+        let sig_file = format!("{}.sig", conf_file);
+        debug!("Verifying configuration signature from {}", sig_file);
+        let sig_hex = fs::read_to_string(&sig_file).map_err(|err| {
+            ConfigError::Message(format!(
+                "configuration signature file {} can't be read: {}",
+                sig_file, err
+            ))
+        })?;
+        let signature = Vec::<u8>::from_hex(sig_hex.trim())
+            .ok()
+            .and_then(|der| secp256k1::Signature::from_der(&der).ok())
+            .ok_or_else(|| {
+                ConfigError::Message(format!(
+                    "configuration signature file {} has invalid format",
+                    sig_file
+                ))
+            })?;
+        let fingerprint = self
+            .fingerprint()
+            .map_err(|err| ConfigError::Message(err.to_string()))?;
+        let message = secp256k1::Message::from_slice(&fingerprint[..])
+            .expect("SHA256 hash is always a valid message");
+        crate::SECP256K1
+            .verify(&message, &signature, &admin_key)
+            .map_err(|_| {
+                ConfigError::Message(format!(
+                    "configuration with fingerprint {} is not signed by the \
+                     administrator key {}",
+                    fingerprint, admin_key
+                ))
+            })?;
+        info!("Configuration signature is verified");
+        Ok(())
+    }
+
+    /// Signs the configuration fingerprint with the administrator private
+    /// key read from `key_file` containing the hex-encoded key. The
+    /// signature is written to `<conf_file>.sig` file in the format expected
+    /// by [`Config::verify_signature`]; returns the signature file path.
+    pub fn sign(
+        &self,
+        conf_file: &str,
+        key_file: &Path,
+    ) -> Result<String, BootstrapError> {
+        let key_hex = zeroize::Zeroizing::new(fs::read_to_string(key_file)?);
+        let mut admin_key = secp256k1::SecretKey::from_str(key_hex.trim())
+            .map_err(|_| {
+                BootstrapError::ArgParseError(format!(
+                    "administrator key file {} must contain hex-encoded \
+                     private key",
+                    key_file.display()
+                ))
+            })?;
+        let fingerprint = self.fingerprint()?;
+        let message = secp256k1::Message::from_slice(&fingerprint[..])
+            .expect("SHA256 hash is always a valid message");
+        let signature = crate::SECP256K1.sign(&message, &admin_key);
+        let admin_pubkey = secp256k1::PublicKey::from_secret_key(
+            &crate::SECP256K1,
+            &admin_key,
+        );
+        vault::secret::wipe_key(&mut admin_key);
+
+        let sig_file = format!("{}.sig", conf_file);
+        fs::write(&sig_file, signature.serialize_der().to_hex())?;
+        info!(
+            "Configuration with fingerprint {} is signed by the \
+             administrator key {}",
+            fingerprint, admin_pubkey
+        );
+        Ok(sig_file)
+    }
+}
+
+/// Resolves vault location relative to the `data_dir` and passes the node
+/// key and read-only mode to the storage driver
+fn locate_vault(
+    config: &mut vault::driver::Config,
+    data_dir: &str,
+    node_key: secp256k1::SecretKey,
+    read_only: bool,
+) {
+    match *config {
+        vault::driver::Config::File(ref mut fdc) => {
+            fdc.location = format!("{}/{}", data_dir, fdc.location);
+            fdc.node_key = Some(vault::file_driver::NodeKey(node_key));
+            fdc.read_only = read_only;
+        }
+        #[cfg(feature = "sqlite")]
+        vault::driver::Config::Sqlite(ref mut sdc) => {
+            sdc.path = format!("{}/{}", data_dir, sdc.path);
+            sdc.read_only = read_only;
+        }
+        #[cfg(feature = "os-keychain")]
+        vault::driver::Config::OsKeychain(ref mut kc) => {
+            if let Some(ref mut fdc) = kc.fallback {
+                fdc.location = format!("{}/{}", data_dir, fdc.location);
+                fdc.node_key = Some(vault::file_driver::NodeKey(node_key));
+                fdc.read_only = read_only;
+            }
+        }
+        _ => {}
+    }
 }
 
 fn init_config(conf_file: &str, config: Config) -> Result<(), ConfigInitError> {
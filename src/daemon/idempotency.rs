@@ -0,0 +1,108 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Idempotent requests. Requests modifying the vault may carry a random
+//! idempotency key chosen by the client. The daemon serves a request with a
+//! given key once and keeps its successful reply, returning it to the
+//! retries of the request, so a client which has timed out waiting for the
+//! reply does not create duplicate keyrings or accounts. Failed requests are
+//! not remembered and may be retried with the same key.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::error::RuntimeError;
+use crate::rpc::types::IdempotencyKey;
+use crate::rpc::Reply;
+
+/// Period during which the reply to an idempotent request is returned to
+/// its retries
+pub const IDEMPOTENCY_RETENTION: Duration = Duration::from_secs(600);
+
+struct Served {
+    operation: &'static str,
+    completed: Option<(Instant, Reply)>,
+}
+
+/// Replies to the idempotent requests, scoped by the name of the client
+/// which has sent the request
+#[derive(Default)]
+pub struct Idempotency {
+    requests: HashMap<(Option<String>, IdempotencyKey), Served>,
+}
+
+impl Idempotency {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers request `operation` with the `key` sent by the `client`.
+    /// Returns the reply to the request if it was already served, or fails
+    /// if the key is used by a request which is still being served or by a
+    /// request of a different type.
+    pub fn begin(
+        &mut self,
+        client: Option<String>,
+        key: IdempotencyKey,
+        operation: &'static str,
+    ) -> Result<Option<Reply>, RuntimeError> {
+        self.expire();
+        match self.requests.get(&(client.clone(), key)) {
+            Some(served) if served.operation != operation => {
+                Err(RuntimeError::IdempotencyConflict)
+            }
+            Some(Served {
+                completed: Some((_, reply)),
+                ..
+            }) => {
+                debug!("Replaying reply to {} request {}", operation, key);
+                Ok(Some(reply.clone()))
+            }
+            Some(_) => Err(RuntimeError::IdempotencyConflict),
+            None => {
+                self.requests.insert(
+                    (client, key),
+                    Served {
+                        operation,
+                        completed: None,
+                    },
+                );
+                Ok(None)
+            }
+        }
+    }
+
+    /// Keeps successful `reply` to the request with the `key`
+    pub fn complete(
+        &mut self,
+        client: Option<String>,
+        key: IdempotencyKey,
+        reply: Reply,
+    ) {
+        if let Some(served) = self.requests.get_mut(&(client, key)) {
+            served.completed = Some((Instant::now(), reply));
+        }
+    }
+
+    /// Forgets failed request with the `key`, so it may be retried
+    pub fn abandon(&mut self, client: Option<String>, key: IdempotencyKey) {
+        self.requests.remove(&(client, key));
+    }
+
+    fn expire(&mut self) {
+        self.requests.retain(|_, served| match served.completed {
+            Some((completed, _)) => completed.elapsed() < IDEMPOTENCY_RETENTION,
+            None => true,
+        });
+    }
+}
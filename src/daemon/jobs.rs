@@ -0,0 +1,134 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Asynchronous jobs. A long-running request tagged by the client with a
+//! random job id is not served by the worker receiving it: the request is
+//! queued to the job runner and the worker replies at once with the job
+//! progress. The client polls the job with `job_status` requests, receiving
+//! its progress until the job completes and the reply to the original
+//! request afterwards.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::error::RuntimeError;
+use crate::rpc::types::{JobId, JobProgress};
+use crate::rpc::Reply;
+
+/// Period during which the reply of a completed job can still be queried
+pub const JOB_RETENTION: Duration = Duration::from_secs(60);
+
+struct Job {
+    operation: &'static str,
+    done: u32,
+    total: u32,
+    started: Option<Instant>,
+    completed: Option<(Instant, Reply)>,
+}
+
+impl Job {
+    fn progress(&self, job: JobId) -> JobProgress {
+        JobProgress {
+            job,
+            operation: self.operation.to_owned(),
+            done: self.done,
+            total: self.total,
+            elapsed: self
+                .started
+                .map(|started| started.elapsed().as_millis() as u64)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Set of the jobs queued, served by the job runner or recently completed
+#[derive(Default)]
+pub struct Jobs {
+    jobs: HashMap<JobId, Job>,
+}
+
+impl Jobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `job` with the request `operation` queued for execution. A
+    /// job may re-use id of a completed job, replacing it, but not of the
+    /// job which is still queued or running.
+    pub fn submit(
+        &mut self,
+        job: JobId,
+        operation: &'static str,
+    ) -> Result<JobProgress, RuntimeError> {
+        self.expire();
+        if matches!(self.jobs.get(&job), Some(record) if record.completed.is_none())
+        {
+            return Err(RuntimeError::DuplicateJob);
+        }
+        debug!("Job {} is queued: {}", job, operation);
+        let record = Job {
+            operation,
+            done: 0,
+            total: 0,
+            started: None,
+            completed: None,
+        };
+        let progress = record.progress(job);
+        self.jobs.insert(job, record);
+        Ok(progress)
+    }
+
+    /// Marks the `job` started by the job runner; it performs `operation` of
+    /// `total` steps
+    pub fn start(&mut self, job: JobId, operation: &'static str, total: u32) {
+        if let Some(record) = self.jobs.get_mut(&job) {
+            debug!("Job {} started: {} of {} steps", job, operation, total);
+            record.operation = operation;
+            record.total = total;
+            record.started = Some(Instant::now());
+        }
+    }
+
+    /// Marks next step of the `job` completed
+    pub fn step(&mut self, job: JobId) {
+        if let Some(job) = self.jobs.get_mut(&job) {
+            job.done = job.done.saturating_add(1).min(job.total);
+        }
+    }
+
+    /// Marks the `job` completed, whether it has succeeded or not, keeping
+    /// the `reply` to its request
+    pub fn complete(&mut self, job: JobId, reply: Reply) {
+        if let Some(record) = self.jobs.get_mut(&job) {
+            debug!("Job {} completed", job);
+            record.completed = Some((Instant::now(), reply));
+        }
+    }
+
+    /// Reports progress of the `job` or, once the job is completed, the
+    /// reply to its request. Returns [`Option::None`] for unknown jobs.
+    pub fn status(&mut self, job: JobId) -> Option<Reply> {
+        self.expire();
+        self.jobs.get(&job).map(|record| match record.completed {
+            Some((_, ref reply)) => reply.clone(),
+            None => Reply::JobProgress(record.progress(job)),
+        })
+    }
+
+    fn expire(&mut self) {
+        self.jobs.retain(|_, job| match job.completed {
+            Some((completed, _)) => completed.elapsed() < JOB_RETENTION,
+            None => true,
+        });
+    }
+}
@@ -0,0 +1,126 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Ledger of the transactions signed by the vault. Each signed PSBT is
+//! recorded as a separate line of JSON appended to the ledger file, so the
+//! file can be inspected with generic tools, while accounting systems get
+//! the records with `ExportLedger` RPC request.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use bitcoin::util::bip32::Fingerprint;
+use bitcoin::util::psbt::PartiallySignedTransaction;
+
+use crate::rpc::types::LedgerEntry;
+use crate::vault::taproot;
+
+#[derive(Clone, Debug)]
+pub struct Ledger {
+    path: PathBuf,
+}
+
+impl Ledger {
+    /// Ledger records are appended to the file at `path`, which is created
+    /// with the first record
+    pub fn with(path: &str) -> Self {
+        info!("Signed transactions are recorded to the ledger {}", path);
+        Self {
+            path: PathBuf::from(path),
+        }
+    }
+
+    /// Appends `entry` to the ledger file, syncing it to the disk
+    pub fn record(&self, entry: &LedgerEntry) -> Result<(), io::Error> {
+        debug!("Recording {} to the transaction ledger", entry);
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        let mut fd = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        fd.write_all(line.as_bytes())?;
+        fd.sync_data()
+    }
+
+    /// Reads ledger records made at or after `since` time, in seconds since
+    /// UNIX epoch
+    pub fn entries(&self, since: u64) -> Result<Vec<LedgerEntry>, io::Error> {
+        let fd = match fs::File::open(&self.path) {
+            Ok(fd) => fd,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok(vec![])
+            }
+            Err(err) => return Err(err),
+        };
+        let mut entries = vec![];
+        for line in BufReader::new(fd).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: LedgerEntry = serde_json::from_str(&line)?;
+            if entry.timestamp >= since {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// Composes ledger entry for the PSBT signed at `timestamp`. Inputs and
+/// outputs are attributed to the vault if their BIP-32 derivation refers to
+/// a master key fingerprint for which `is_vault_key` returns `true`.
+pub fn entry(
+    psbt: &PartiallySignedTransaction,
+    timestamp: u64,
+    client: Option<String>,
+    is_vault_key: impl Fn(Fingerprint) -> bool,
+) -> LedgerEntry {
+    let tx = &psbt.global.unsigned_tx;
+    let mut spent = 0u64;
+    let mut input_total = Some(0u64);
+    for (index, input) in psbt.inputs.iter().enumerate() {
+        let value = taproot::spent_output(psbt, index).map(|txout| txout.value);
+        input_total = input_total.and_then(|total| Some(total + value?));
+        if input
+            .bip32_derivation
+            .values()
+            .any(|(fingerprint, _)| is_vault_key(*fingerprint))
+        {
+            spent += value.unwrap_or_default();
+        }
+    }
+    let received = psbt
+        .outputs
+        .iter()
+        .zip(&tx.output)
+        .filter(|(output, _)| {
+            output
+                .bip32_derivation
+                .values()
+                .any(|(fingerprint, _)| is_vault_key(*fingerprint))
+        })
+        .map(|(_, txout)| txout.value)
+        .sum();
+    let output_total = tx.output.iter().map(|txout| txout.value).sum::<u64>();
+    LedgerEntry {
+        txid: tx.txid(),
+        timestamp,
+        client,
+        spent,
+        received,
+        fee: input_total.and_then(|total| total.checked_sub(output_total)),
+    }
+}
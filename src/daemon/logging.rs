@@ -0,0 +1,177 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Daemon logging. Records are written to STDERR either as plain text or as
+//! structured JSON, one record per line, which can be ingested by log
+//! collectors without parsing the messages. Records made by a worker thread
+//! while it serves an RPC request carry the request type and the identifier
+//! of the key the request refers to; completion of each request is logged
+//! together with its duration.
+//!
+//! The logger is installed before the configuration is read, so the records
+//! made during the daemon bootstrap are written as plain text until the
+//! configured format is applied with [`set_format`]. The level and format
+//! of the records may be changed while the daemon is running, once the
+//! configuration is reloaded.
+
+use std::cell::RefCell;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use bitcoin::XpubIdentifier;
+use chrono::Utc;
+use env_logger::{Builder, Env, DEFAULT_FILTER_ENV};
+use log::{LevelFilter, Record};
+use microservices::shell::LogLevel;
+use serde_json::{Map, Value};
+
+use crate::rpc::Request;
+
+/// Format of the daemon log records
+#[derive(
+    Clone, Copy, PartialEq, Eq, Hash, Debug, Display, Serialize, Deserialize,
+)]
+#[serde(crate = "serde_crate", rename_all = "snake_case")]
+#[display(Debug)]
+pub enum LogFormat {
+    /// Human-readable lines
+    Text,
+
+    /// JSON object per line with `timestamp`, `level`, `target` and
+    /// `message` fields, plus `rpc_type`, `key_id` and `duration` (in
+    /// milliseconds) fields for the records related to RPC requests
+    Json,
+}
+
+impl Default for LogFormat {
+    fn default() -> Self {
+        LogFormat::Text
+    }
+}
+
+static JSON_FORMAT: AtomicBool = AtomicBool::new(false);
+
+/// Whether the records are filtered with `RUST_LOG` environment variable,
+/// which takes precedence over the configured level
+static ENV_FILTER: AtomicBool = AtomicBool::new(false);
+
+/// RPC request served by the current thread
+struct RequestContext {
+    rpc_type: &'static str,
+    key_id: Option<XpubIdentifier>,
+    duration: Option<Duration>,
+}
+
+thread_local! {
+    static REQUEST: RefCell<Option<RequestContext>> = RefCell::new(None);
+}
+
+/// Installs the daemon logger writing records of the given `level` and
+/// above, unless a filter is set with `RUST_LOG` environment variable
+pub fn init(level: LogLevel) {
+    let env_filter = std::env::var_os(DEFAULT_FILTER_ENV).is_some();
+    ENV_FILTER.store(env_filter, Ordering::Relaxed);
+    // Records are filtered by the maximum level, so it can be changed
+    // after the logger is installed
+    Builder::from_env(Env::default().default_filter_or("trace"))
+        .format(|buf, record| {
+            if JSON_FORMAT.load(Ordering::Relaxed) {
+                writeln!(buf, "{}", json_record(record))
+            } else {
+                writeln!(
+                    buf,
+                    "[{} {:<5} {}] {}",
+                    buf.timestamp(),
+                    record.level(),
+                    record.target(),
+                    record.args()
+                )
+            }
+        })
+        .init();
+    set_level(level);
+}
+
+/// Changes level of the written records to the `level` given in the daemon
+/// configuration; ignored if a filter is set with `RUST_LOG`
+pub fn set_level(level: LogLevel) {
+    if ENV_FILTER.load(Ordering::Relaxed) {
+        return;
+    }
+    let filter = format!("{:?}", level).parse().unwrap_or(LevelFilter::Trace);
+    log::set_max_level(filter);
+}
+
+/// Switches the logger to the `format` given in the daemon configuration
+pub fn set_format(format: LogFormat) {
+    JSON_FORMAT.store(format == LogFormat::Json, Ordering::Relaxed);
+}
+
+fn json_record(record: &Record) -> Value {
+    let mut fields = Map::new();
+    fields.insert(s!("timestamp"), Value::from(Utc::now().to_rfc3339()));
+    fields.insert(s!("level"), Value::from(record.level().to_string()));
+    fields.insert(s!("target"), Value::from(record.target()));
+    fields.insert(s!("message"), Value::from(record.args().to_string()));
+    REQUEST.with(|request| {
+        if let Some(request) = &*request.borrow() {
+            fields.insert(s!("rpc_type"), Value::from(request.rpc_type));
+            if let Some(key_id) = request.key_id {
+                fields.insert(s!("key_id"), Value::from(key_id.to_string()));
+            }
+            if let Some(duration) = request.duration {
+                fields.insert(
+                    s!("duration"),
+                    Value::from(duration.as_secs_f64() * 1000.0),
+                );
+            }
+        }
+    });
+    Value::Object(fields)
+}
+
+/// Scope of an RPC request served by the current thread. Records made
+/// within the scope carry request details; completion of the request is
+/// logged with its duration when the scope is dropped.
+pub struct RequestScope {
+    started: Instant,
+}
+
+impl RequestScope {
+    pub fn enter(request: &Request) -> Self {
+        REQUEST.with(|context| {
+            *context.borrow_mut() = Some(RequestContext {
+                rpc_type: request.name(),
+                key_id: request.key_id(),
+                duration: None,
+            })
+        });
+        Self {
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Drop for RequestScope {
+    fn drop(&mut self) {
+        let duration = self.started.elapsed();
+        REQUEST.with(|context| {
+            if let Some(context) = &mut *context.borrow_mut() {
+                context.duration = Some(duration);
+            }
+        });
+        info!("Request processed in {} ms", duration.as_millis());
+        REQUEST.with(|context| *context.borrow_mut() = None);
+    }
+}
@@ -11,10 +11,32 @@
 // along with this software.
 // If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
 
+mod approval;
+pub mod attestation;
+mod auth;
+mod check;
 mod config;
+mod idempotency;
+mod jobs;
+pub mod ledger;
+pub mod logging;
+pub mod musig;
 pub(crate) mod opts;
+pub mod revocation;
 mod runtime;
+mod transport;
 
-pub use config::Config;
+pub use approval::{Approvals, APPROVAL_TIMEOUT};
+pub use attestation::Attester;
+pub use auth::{AccountField, Authenticator, ClientConfig, MAX_CHALLENGES};
+pub use check::check;
+pub use config::{Config, VaultConfig};
+pub use idempotency::{Idempotency, IDEMPOTENCY_RETENTION};
+pub use jobs::{Jobs, JOB_RETENTION};
+pub use ledger::Ledger;
+pub use logging::LogFormat;
+pub use musig::{MuSigSessions, MUSIG_SESSION_TIMEOUT};
 pub use opts::Opts;
+pub use revocation::Revocations;
 pub use runtime::{run, Runtime};
+pub use transport::{Channels, PayloadEncryption, TransportEncryption};
@@ -0,0 +1,208 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! MuSig2 signing sessions of the vault accounts. A session is started for
+//! a single message and a fixed list of participants; the daemon generates
+//! the account nonce, aggregates it with the nonces of other participants
+//! once they are exchanged and produces partial signature, after which the
+//! session is closed. Secret nonces never leave the daemon and are wiped
+//! once used, so a nonce can't be reused for another signature.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::rand::{thread_rng, RngCore};
+use bitcoin::secp256k1::PublicKey;
+use bitcoin::XpubIdentifier;
+
+use crate::rpc::types::{MuSigNonce, MuSigSession, MuSigSessionId};
+use crate::vault::musig::{self, KeyAggContext, PubNonce, SecNonce};
+
+/// Period during which a MuSig2 session has to be completed
+pub const MUSIG_SESSION_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Errors of the MuSig2 session management
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum Error {
+    /// MuSig2 session {0} is not known; it may have been expired or
+    /// completed
+    UnknownSession(MuSigSessionId),
+
+    /// Nonces of MuSig2 session {0} are already exchanged
+    NoncesExchanged(MuSigSessionId),
+
+    /// Nonces of MuSig2 session {0} must be exchanged before signing
+    NoncesPending(MuSigSessionId),
+
+    /// Nonce of the participant {0} is not provided
+    MissingNonce(PublicKey),
+
+    /// {0}
+    #[from]
+    Protocol(musig::Error),
+}
+
+struct Session {
+    key_id: XpubIdentifier,
+    participant: PublicKey,
+    context: KeyAggContext,
+    message: sha256::Hash,
+    secnonce: SecNonce,
+    pubnonce: PubNonce,
+    aggnonce: Option<PubNonce>,
+    created: Instant,
+}
+
+/// Session data required to produce partial signature, taken from the
+/// registry with [`MuSigSessions::take`]
+pub struct Signing {
+    pub key_id: XpubIdentifier,
+    pub context: KeyAggContext,
+    pub message: sha256::Hash,
+    pub secnonce: SecNonce,
+    pub aggnonce: PubNonce,
+}
+
+/// Set of the MuSig2 sessions in progress
+#[derive(Default)]
+pub struct MuSigSessions {
+    sessions: HashMap<MuSigSessionId, Session>,
+}
+
+impl MuSigSessions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts session signing `message` with the `participant` key of the
+    /// account `key_id` together with the other `participants`, which must
+    /// list all keys in the order of aggregation. If `taproot` is set, the
+    /// aggregated key is tweaked to become taproot output key.
+    pub fn start(
+        &mut self,
+        key_id: XpubIdentifier,
+        participant: PublicKey,
+        participants: &[PublicKey],
+        message: sha256::Hash,
+        taproot: bool,
+    ) -> Result<MuSigSession, Error> {
+        self.expire();
+        let mut context = KeyAggContext::with(participants)?;
+        if !context.keys().contains(&participant) {
+            return Err(musig::Error::UnknownParticipant(participant).into());
+        }
+        if taproot {
+            context.tweak_taproot()?;
+        }
+        let (secnonce, pubnonce) = musig::nonce_gen();
+        let mut random = [0u8; 32];
+        thread_rng().fill_bytes(&mut random);
+        let id = sha256::Hash::from_inner(random);
+        let session = MuSigSession {
+            id,
+            key_id,
+            participant,
+            aggregated_key: context.aggregated_key().serialize().to_vec(),
+            nonce: pubnonce.serialize(),
+        };
+        self.sessions.insert(
+            id,
+            Session {
+                key_id,
+                participant,
+                context,
+                message,
+                secnonce,
+                pubnonce,
+                aggnonce: None,
+                created: Instant::now(),
+            },
+        );
+        debug!("Started MuSig2 session {} for account {}", id, key_id);
+        Ok(session)
+    }
+
+    /// Aggregates public `nonces` of all other participants with the nonce
+    /// of the session account, returning the serialized aggregated nonce
+    pub fn exchange(
+        &mut self,
+        id: MuSigSessionId,
+        nonces: &[MuSigNonce],
+    ) -> Result<Vec<u8>, Error> {
+        self.expire();
+        let session = self
+            .sessions
+            .get_mut(&id)
+            .ok_or(Error::UnknownSession(id))?;
+        if session.aggnonce.is_some() {
+            return Err(Error::NoncesExchanged(id));
+        }
+        for nonce in nonces {
+            if !session.context.keys().contains(&nonce.participant) {
+                return Err(musig::Error::UnknownParticipant(
+                    nonce.participant,
+                )
+                .into());
+            }
+        }
+        let mut pubnonces = Vec::with_capacity(session.context.keys().len());
+        for key in session.context.keys() {
+            if *key == session.participant {
+                pubnonces.push(session.pubnonce);
+                continue;
+            }
+            let nonce = nonces
+                .iter()
+                .find(|nonce| nonce.participant == *key)
+                .ok_or(Error::MissingNonce(*key))?;
+            pubnonces.push(PubNonce::from_slice(&nonce.nonce)?);
+        }
+        let aggnonce = musig::nonce_agg(&pubnonces)?;
+        session.aggnonce = Some(aggnonce);
+        Ok(aggnonce.serialize())
+    }
+
+    /// Closes the session returning data for producing partial signature.
+    /// The session can't be used after this call, even if signing fails.
+    pub fn take(&mut self, id: MuSigSessionId) -> Result<Signing, Error> {
+        self.expire();
+        match self.sessions.get(&id) {
+            None => return Err(Error::UnknownSession(id)),
+            Some(session) if session.aggnonce.is_none() => {
+                return Err(Error::NoncesPending(id))
+            }
+            Some(_) => {}
+        }
+        let session = self.sessions.remove(&id).expect("session is present");
+        debug!("Closing MuSig2 session {}", id);
+        Ok(Signing {
+            key_id: session.key_id,
+            context: session.context,
+            message: session.message,
+            secnonce: session.secnonce,
+            aggnonce: session.aggnonce.expect("nonces are exchanged"),
+        })
+    }
+
+    fn expire(&mut self) {
+        self.sessions.retain(|id, session| {
+            let alive = session.created.elapsed() < MUSIG_SESSION_TIMEOUT;
+            if !alive {
+                debug!("MuSig2 session {} is expired", id);
+            }
+            alive
+        });
+    }
+}
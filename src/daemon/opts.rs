@@ -11,8 +11,10 @@
 // along with this software.
 // If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
 
+use bitcoin::secp256k1::PublicKey;
 use clap::{AppSettings, Clap, ValueHint};
 use microservices::FileFormat;
+use std::path::PathBuf;
 
 pub const KEYRING_CONFIG: &'static str = "{data_dir}/keyringd.toml";
 #[cfg(feature = "serde_yaml")]
@@ -47,11 +49,61 @@ pub struct Opts {
         value_hint = ValueHint::FilePath
     )]
     pub config: String,
+
+    /// Public key of the administrator which must sign the configuration.
+    ///
+    /// If provided, the daemon will refuse to start unless the configuration
+    /// file is accompanied by `<config>.sig` file containing hex-encoded DER
+    /// ECDSA signature of the configuration fingerprint made with the
+    /// corresponding private key. The signature is produced with
+    /// `--sign-config`.
+    #[clap(long, env = "KEYRING_ADMIN_KEY")]
+    pub admin_key: Option<PublicKey>,
+
+    /// Signs the configuration with the administrator private key and exits.
+    ///
+    /// The key is read from the given file containing hex-encoded private
+    /// key; the signature is written to `<config>.sig` file. The
+    /// configuration must be signed again after each change.
+    #[clap(long, value_hint = ValueHint::FilePath)]
+    pub sign_config: Option<PathBuf>,
+
+    /// Validates configuration, vault and RPC socket and exits without
+    /// serving requests.
+    ///
+    /// Exit status is zero if all checks pass.
+    #[clap(long)]
+    pub check: bool,
+
+    /// Opens the vault read-only and serves only requests which do not
+    /// modify it and do not use private keys.
+    ///
+    /// Allows running watch-only replicas of the daemon over the same vault
+    /// or its replicated copy.
+    #[clap(long, env = "KEYRING_READ_ONLY")]
+    pub read_only: bool,
+
+    /// Number of worker threads serving client requests.
+    ///
+    /// Requests reading the vault are served concurrently, while requests
+    /// modifying it are processed one at a time.
+    #[clap(long, env = "KEYRING_WORKERS")]
+    pub workers: Option<usize>,
+
+    /// Number of signing keys cached by each unlocked vault session.
+    ///
+    /// Speeds up repeated PSBT signing with the same accounts; cached keys
+    /// are wiped when the session is locked or expired. Zero disables the
+    /// cache.
+    #[clap(long, env = "KEYRING_SIGNING_CACHE")]
+    pub signing_cache: Option<usize>,
 }
 
 impl Opts {
+    /// Expands configuration paths; the daemon logger is installed with
+    /// `daemon::logging::init` before the call
     pub fn process(&mut self) {
-        self.shared.process();
+        self.shared.process_paths();
         self.shared.process_dir(&mut self.config);
     }
 }
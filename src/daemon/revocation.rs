@@ -0,0 +1,362 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Storage of the Lightning per-commitment secrets revealed by the channel
+//! counterparties, which a node has to keep to punish revoked commitment
+//! transactions. Secrets of each keys account are kept in a separate
+//! namespace: an append-only log file, where each secret is synced to the
+//! disk before the append request is confirmed.
+//!
+//! Secrets of a channel are validated and indexed with the BOLT-3 compact
+//! storage, which keeps at most 49 secrets per channel and derives the rest
+//! of them. Compaction rewrites the log leaving only these secrets.
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::XpubIdentifier;
+
+use crate::rpc::types::{CommitmentSecret, LnChannelId};
+
+/// Maximal index of the per-commitment secret, which is also the index of
+/// the first secret revealed in a channel
+pub const MAX_SECRET_INDEX: u64 = (1 << 48) - 1;
+
+/// Number of the log records after which the account log is compacted
+/// automatically
+pub const COMPACTION_THRESHOLD: usize = 1024;
+
+/// Number of the secrets kept by the BOLT-3 compact storage
+const CHAIN_BUCKETS: usize = 49;
+
+/// Errors of the revocation secret storage
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum Error {
+    /// Per-commitment secret index {0} exceeds 2^48-1
+    Index(u64),
+
+    /// Per-commitment secret #{1} is out of order: the last stored secret of
+    /// the channel is #{0}
+    Order(u64, u64),
+
+    /// Per-commitment secret #{0} does not match the previously stored
+    /// secrets of the channel
+    Inconsistent(u64),
+
+    /// No revocation secrets are stored for the channel {0}
+    UnknownChannel(LnChannelId),
+
+    /// Per-commitment secret #{0} is not revealed yet
+    UnknownSecret(u64),
+
+    /// Revocation storage error: {0}
+    Storage(String),
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Storage(err.to_string())
+    }
+}
+
+/// Derives secret with `index` from the `base` secret, which index has
+/// lowest `bits` bits set to zero (BOLT-3 generation algorithm)
+fn derive_secret(base: CommitmentSecret, bits: usize, index: u64) -> [u8; 32] {
+    let mut secret = base.into_inner();
+    for bit in (0..bits).rev() {
+        if index & (1 << bit) != 0 {
+            secret[bit / 8] ^= 1 << (bit % 8);
+            secret = sha256::Hash::hash(&secret).into_inner();
+        }
+    }
+    secret
+}
+
+/// BOLT-3 compact storage of the per-commitment secrets of a channel
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SecretChain {
+    known: [Option<(u64, CommitmentSecret)>; CHAIN_BUCKETS],
+    last: Option<u64>,
+}
+
+impl Default for SecretChain {
+    fn default() -> Self {
+        Self {
+            known: [None; CHAIN_BUCKETS],
+            last: None,
+        }
+    }
+}
+
+impl SecretChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index of the last added secret
+    pub fn last(&self) -> Option<u64> {
+        self.last
+    }
+
+    /// Adds the secret with a given `index`, checking that it allows to
+    /// derive all previously added secrets. Secrets must be added in the
+    /// order of decreasing indexes, starting from any index.
+    pub fn insert(
+        &mut self,
+        index: u64,
+        secret: CommitmentSecret,
+    ) -> Result<(), Error> {
+        if index > MAX_SECRET_INDEX {
+            return Err(Error::Index(index));
+        }
+        match self.last {
+            Some(last) if last == 0 || index != last - 1 => {
+                return Err(Error::Order(last, index))
+            }
+            _ => {}
+        }
+        let bucket = Self::bucket(index);
+        for (known_index, known_secret) in
+            self.known[..bucket].iter().filter_map(|known| *known)
+        {
+            if derive_secret(secret, bucket, known_index)
+                != known_secret.into_inner()
+            {
+                return Err(Error::Inconsistent(index));
+            }
+        }
+        self.known[bucket] = Some((index, secret));
+        self.last = Some(index);
+        Ok(())
+    }
+
+    /// Puts the secret previously returned by [`SecretChain::secrets`] back
+    /// to the storage without checks, which were performed when the secret
+    /// was added
+    fn restore(&mut self, index: u64, secret: CommitmentSecret) {
+        self.known[Self::bucket(index)] = Some((index, secret));
+        self.last = Some(self.last.map_or(index, |last| last.min(index)));
+    }
+
+    /// Position of the secret in the compact storage, which is the number
+    /// of trailing zero bits of its index
+    fn bucket(index: u64) -> usize {
+        index.trailing_zeros().min(48) as usize
+    }
+
+    /// Returns secret with a given `index`, if it was added or can be
+    /// derived from the added secrets
+    pub fn get(&self, index: u64) -> Result<CommitmentSecret, Error> {
+        if index > MAX_SECRET_INDEX {
+            return Err(Error::Index(index));
+        }
+        self.known
+            .iter()
+            .enumerate()
+            .find_map(|(bucket, known)| match known {
+                Some((known_index, secret))
+                    if index >> bucket == known_index >> bucket =>
+                {
+                    Some(CommitmentSecret::from_inner(derive_secret(
+                        *secret, bucket, index,
+                    )))
+                }
+                _ => None,
+            })
+            .ok_or(Error::UnknownSecret(index))
+    }
+
+    /// Secrets kept by the compact storage, in the order of decreasing
+    /// indexes
+    pub fn secrets(&self) -> Vec<(u64, CommitmentSecret)> {
+        let mut secrets = self
+            .known
+            .iter()
+            .filter_map(|known| *known)
+            .collect::<Vec<_>>();
+        secrets.sort_by(|(a, _), (b, _)| b.cmp(a));
+        secrets
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "serde_crate")]
+struct Record {
+    channel: LnChannelId,
+    index: u64,
+    secret: CommitmentSecret,
+}
+
+/// Revocation secrets of all channels of a keys account
+pub type AccountSecrets = BTreeMap<LnChannelId, SecretChain>;
+
+/// Directory holding revocation secret logs of the keys accounts
+#[derive(Clone, Debug)]
+pub struct Revocations {
+    dir: PathBuf,
+}
+
+impl Revocations {
+    /// Secret logs are kept in the directory `dir`, which is created with
+    /// the first appended secret
+    pub fn with(dir: &str) -> Self {
+        info!("Lightning revocation secrets are stored in {}", dir);
+        Self {
+            dir: PathBuf::from(dir),
+        }
+    }
+
+    fn path(&self, account: XpubIdentifier) -> PathBuf {
+        self.dir.join(format!("{}.log", account))
+    }
+
+    /// Reads secrets of the `account`, returning them together with the
+    /// number of records in the account log
+    fn read(
+        &self,
+        account: XpubIdentifier,
+    ) -> Result<(AccountSecrets, usize), Error> {
+        let mut secrets = AccountSecrets::new();
+        let fd = match fs::File::open(self.path(account)) {
+            Ok(fd) => fd,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok((secrets, 0))
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let mut count = 0usize;
+        for line in BufReader::new(fd).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: Record = serde_json::from_str(&line)
+                .map_err(|err| Error::Storage(err.to_string()))?;
+            if record.index > MAX_SECRET_INDEX {
+                return Err(Error::Index(record.index));
+            }
+            secrets
+                .entry(record.channel)
+                .or_default()
+                .restore(record.index, record.secret);
+            count += 1;
+        }
+        Ok((secrets, count))
+    }
+
+    /// Returns secrets of all channels of the `account`
+    pub fn secrets(
+        &self,
+        account: XpubIdentifier,
+    ) -> Result<AccountSecrets, Error> {
+        self.read(account).map(|(secrets, _)| secrets)
+    }
+
+    /// Appends per-commitment secret of the `channel` to the `account` log
+    /// after checking it against the secrets already stored, and syncs the
+    /// log to the disk. Compacts the log once it grows over
+    /// [`COMPACTION_THRESHOLD`] records.
+    pub fn append(
+        &self,
+        account: XpubIdentifier,
+        channel: LnChannelId,
+        index: u64,
+        secret: CommitmentSecret,
+    ) -> Result<(), Error> {
+        let (mut secrets, count) = self.read(account)?;
+        secrets.entry(channel).or_default().insert(index, secret)?;
+
+        trace!("Appending secret #{} of channel {}", index, channel);
+        let mut line = serde_json::to_string(&Record {
+            channel,
+            index,
+            secret,
+        })
+        .map_err(|err| Error::Storage(err.to_string()))?;
+        line.push('\n');
+        fs::create_dir_all(&self.dir)?;
+        let mut fd = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(account))?;
+        fd.write_all(line.as_bytes())?;
+        fd.sync_data()?;
+
+        if count + 1 > COMPACTION_THRESHOLD {
+            self.write(account, &secrets)?;
+        }
+        Ok(())
+    }
+
+    /// Returns per-commitment secret with `index` of the `channel`
+    pub fn get(
+        &self,
+        account: XpubIdentifier,
+        channel: LnChannelId,
+        index: u64,
+    ) -> Result<CommitmentSecret, Error> {
+        self.secrets(account)?
+            .get(&channel)
+            .ok_or(Error::UnknownChannel(channel))?
+            .get(index)
+    }
+
+    /// Rewrites the `account` log leaving only the secrets kept by the
+    /// compact storage. Returns number of the records removed from the log.
+    pub fn compact(&self, account: XpubIdentifier) -> Result<usize, Error> {
+        let (secrets, count) = self.read(account)?;
+        if count == 0 {
+            return Ok(0);
+        }
+        let remaining = self.write(account, &secrets)?;
+        Ok(count - remaining)
+    }
+
+    /// Atomically replaces the `account` log with the compacted `secrets`,
+    /// returning the number of the written records
+    fn write(
+        &self,
+        account: XpubIdentifier,
+        secrets: &AccountSecrets,
+    ) -> Result<usize, Error> {
+        let path = self.path(account);
+        let tmp = path.with_extension("log.tmp");
+        let mut data = String::new();
+        let mut count = 0usize;
+        for (channel, chain) in secrets {
+            for (index, secret) in chain.secrets() {
+                data += &serde_json::to_string(&Record {
+                    channel: *channel,
+                    index,
+                    secret,
+                })
+                .map_err(|err| Error::Storage(err.to_string()))?;
+                data.push('\n');
+                count += 1;
+            }
+        }
+        let mut fd = fs::File::create(&tmp)?;
+        fd.write_all(data.as_bytes())?;
+        fd.sync_all()?;
+        fs::rename(&tmp, &path)?;
+        debug!(
+            "Revocation log of account {} is compacted to {} records",
+            account, count
+        );
+        Ok(count)
+    }
+}
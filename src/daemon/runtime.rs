@@ -12,206 +12,2375 @@
 // If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
 
 use std::any::Any;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{
+    Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard,
+};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::{fs, io};
 
-use internet2::zmqsocket::{self, ZmqType};
+use bitcoin::hashes::sha256;
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use bitcoin::XpubIdentifier;
+use internet2::zmqsocket::ZmqSocketAddr;
 use internet2::{
-    session, CreateUnmarshaller, PlainTranscoder, Session, TypedEnum,
-    Unmarshall, Unmarshaller,
+    presentation, CreateUnmarshaller, TypedEnum, Unmarshall, Unmarshaller,
 };
+use lnpbp::chain::Chain;
+use lnpbp::strict_encoding::strict_deserialize;
 use microservices::node::TryService;
+use signal_hook::consts::{SIGINT, SIGTERM};
 
-use super::Config;
+use super::transport::Received;
+use super::{
+    attestation, ledger, logging, Approvals, Attester, Authenticator, Channels,
+    Config, Idempotency, Jobs, Ledger, MuSigSessions, PayloadEncryption,
+    Revocations, TransportEncryption, APPROVAL_TIMEOUT,
+};
+use crate::chain::{self, ChainSource};
+use crate::derivation;
 use crate::error::{BootstrapError, RuntimeError};
-use crate::rpc::{message, Reply, Request};
+use crate::passphrase;
+use crate::psbt;
+use crate::rpc::auth::unix_time;
+#[cfg(feature = "grpc")]
+use crate::rpc::grpc;
+use crate::rpc::transport::{self, ChannelId};
+use crate::rpc::types::{AccountInfo, VaultId, DEFAULT_VAULT_ID};
+use crate::rpc::{self, message, types, Reply, Request};
+use crate::vault::secret::wipe_key;
+use crate::vault::{
+    self, finalizer, keymgm, musig, Backups, Encryption, Journal, Sessions,
+    SigningCache,
+};
 use crate::Vault;
 
+/// In-process socket over which the runtime distributes client requests
+/// among the workers
+const WORKERS_ENDPOINT: &str = "inproc://keyringd-workers";
+
+/// Interval at which the runtime checks whether a termination signal was
+/// received
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Period given to the requests in flight to complete after a termination
+/// signal is received
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Time during which the replies sent just before the shutdown may still be
+/// delivered to the clients, in milliseconds
+const REPLY_LINGER: i32 = 1000;
+
+/// Runs the daemon until it receives SIGTERM or SIGINT signal. SIGHUP
+/// signal makes the daemon re-read its configuration file.
 pub fn run(config: Config) -> Result<(), BootstrapError> {
     let runtime = Runtime::init(config)?;
 
-    runtime.run_or_panic("keyringd");
+    runtime.try_run_loop()?;
 
     Ok(())
 }
 
+/// Daemon runtime accepting client requests with a ZMQ ROUTER socket and
+/// dispatching them to a pool of worker threads
 pub struct Runtime {
-    /// Original configuration object
+    /// ZMQ context shared by the client and worker sockets
+    context: zmq::Context,
+
+    /// ROUTER socket receiving client requests
+    frontend: zmq::Socket,
+
+    /// Number of the worker threads
+    workers: usize,
+
+    /// Request processing state shared by the workers
+    processor: Arc<Processor>,
+
+    /// Flag set by the termination signal handlers
+    terminate: Arc<AtomicBool>,
+
+    /// Flag set by SIGHUP signal handler, requesting the configuration file
+    /// to be re-read
+    reload: Arc<AtomicBool>,
+
+    /// gRPC interface served alongside ZMQ RPC, if configured
+    #[cfg(feature = "grpc")]
+    grpc: Option<grpc::Server>,
+}
+
+/// Request processing state shared by the worker threads. Each vault is
+/// guarded by a read-write lock, so requests reading it are served
+/// concurrently, while requests modifying the vault are serialized; a
+/// request holds the lock of a single vault at a time. The rest of the
+/// state is guarded by mutexes held for a single operation; they may be
+/// acquired while a vault lock is held, but never the other way round.
+struct Processor {
+    /// Original configuration object. Settings which may be changed at
+    /// runtime are kept up to date by the parts of the state using them.
     config: Config,
 
-    /// Stored sessions
-    session_rpc: session::Raw<PlainTranscoder, zmqsocket::Connection>,
+    /// Fingerprint of the effective configuration, updated once the
+    /// configuration is reloaded
+    config_fingerprint: Mutex<sha256::Hash>,
+
+    /// Passphrase policy checked on vault unlock
+    passphrase: Mutex<passphrase::Policy>,
+
+    /// Keyrings which extended private keys may be exported
+    xpriv_export: Mutex<BTreeSet<XpubIdentifier>>,
+
+    /// Time of the daemon start, reported in the status
+    started: Instant,
+
+    /// Secure key vaults, including the default one, by their names
+    vaults: BTreeMap<VaultId, VaultInstance>,
+
+    /// Optional blockchain data source used for balance information
+    chain_source: Option<Mutex<Box<dyn ChainSource>>>,
+
+    /// Optional ledger recording transactions signed by the vault. Records
+    /// are written under the vault lock.
+    ledger: Option<Ledger>,
+
+    /// Optional storage of Lightning revocation secrets; appending a secret
+    /// checks the log before writing to it, so the storage is used by a
+    /// single worker at a time
+    revocations: Option<Mutex<Revocations>>,
+
+    /// Optional producer of the platform quotes for the daemon running inside
+    /// a trusted execution environment
+    attester: Option<Mutex<Box<dyn Attester>>>,
+
+    /// Authorization subsystem validating request auth codes
+    authenticator: Mutex<Authenticator>,
+
+    /// Outstanding approvals of private key export
+    approvals: Mutex<Approvals>,
+
+    /// MuSig2 signing sessions in progress, holding secret nonces
+    musig: Mutex<MuSigSessions>,
+
+    /// Asynchronous jobs submitted by the clients
+    jobs: Mutex<Jobs>,
+
+    /// Replies to the idempotent requests, returned to their retries
+    idempotency: Mutex<Idempotency>,
+
+    /// Queue of the job runner thread, closed on shutdown
+    job_queue: Mutex<Option<mpsc::Sender<Task>>>,
+
+    /// Encrypted channels established with the clients
+    channels: Mutex<Channels>,
+}
+
+/// Vault served by the daemon together with its routing settings
+struct VaultInstance {
+    vault: RwLock<Vault>,
 
-    /// Secure key vault
-    vault: Vault,
+    /// Type of the vault storage driver
+    driver: &'static str,
 
-    /// Unmarshaller instance used for parsing RPC request
-    unmarshaller: Unmarshaller<Request>,
+    /// Chains which new keyrings are created in the vault
+    chains: Vec<Chain>,
+
+    /// Unlocked sessions of the vault holding its decryption keys. Each of
+    /// the vaults has own sessions, so a session token is accepted only by
+    /// the vault it was issued for.
+    sessions: Mutex<Sessions>,
+
+    /// Public key used for the vault encryption, known after the first
+    /// unlock if the vault is encrypted with a passphrase
+    vault_pubkey: Mutex<Option<PublicKey>>,
+
+    /// Public key of the passphrase used to unlock the vault holding no
+    /// private keys, awaiting confirmation by the next unlock request
+    unconfirmed_pubkey: Mutex<Option<PublicKey>>,
+}
+
+impl VaultInstance {
+    fn open(
+        driver: &vault::driver::Config,
+        backup: Option<&vault::backup::Config>,
+        journal: Option<&str>,
+        chains: Vec<Chain>,
+        config: &Config,
+    ) -> Result<Self, BootstrapError> {
+        debug!("Initializing vault {}", driver);
+        let mut vault = Vault::with(driver)?;
+        if config.read_only {
+            info!("Vault is opened read-only");
+        } else {
+            if let Some(backup_config) = backup {
+                vault.enable_backups(Backups::with(
+                    backup_config,
+                    config.node_id(),
+                )?);
+            }
+            if let Some(path) = journal {
+                vault.enable_journal(Journal::open(path)?)?;
+            }
+        }
+        let mut sessions = Sessions::with(Duration::from_secs(
+            config.encryption.unlock_timeout(),
+        ));
+        sessions.enable_signing_cache(config.signing_cache);
+        Ok(Self {
+            vault: RwLock::new(vault),
+            driver: driver.name(),
+            chains,
+            sessions: Mutex::new(sessions),
+            vault_pubkey: Mutex::new(None),
+            unconfirmed_pubkey: Mutex::new(None),
+        })
+    }
+}
+
+thread_local! {
+    /// Vault the request served by the current thread is routed to
+    static ROUTE: RefCell<VaultId> = RefCell::new(s!(DEFAULT_VAULT_ID));
+}
+
+/// Locks part of the runtime state. A mutex gets poisoned only if a worker
+/// panics while holding it, which is a bug, so the panic is propagated.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<T> {
+    mutex.lock().expect("runtime state lock is poisoned")
 }
 
 impl Runtime {
     pub fn init(config: Config) -> Result<Self, BootstrapError> {
-        debug!("Initializing vault {}", config.vault);
-        let vault = Vault::with(&config.vault)?;
+        let attester =
+            config.attestation.as_ref().map(attestation::attester_with);
+        Self::with(config, attester)
+    }
+
+    /// Initializes runtime with a custom `attester`, used by the deployments
+    /// in trusted execution environments not covered by the providers
+    /// available in the daemon configuration
+    pub fn with(
+        config: Config,
+        attester: Option<Box<dyn Attester>>,
+    ) -> Result<Self, BootstrapError> {
+        let config_fingerprint = config.fingerprint()?;
+        info!(
+            "Effective configuration fingerprint: {}",
+            config_fingerprint
+        );
+
+        let mut vaults = BTreeMap::new();
+        vaults.insert(
+            s!(DEFAULT_VAULT_ID),
+            VaultInstance::open(
+                &config.vault,
+                config.backup.as_ref(),
+                config.journal.as_deref(),
+                vec![],
+                &config,
+            )?,
+        );
+        for (id, vault_config) in &config.vaults {
+            debug!("Vault `{}` is configured", id);
+            vaults.insert(
+                id.clone(),
+                VaultInstance::open(
+                    &vault_config.vault,
+                    vault_config.backup.as_ref(),
+                    vault_config.journal.as_deref(),
+                    vault_config.chains.clone(),
+                    &config,
+                )?,
+            );
+        }
+
+        let chain_source = match config.chain_source {
+            Some(ref source_config) => {
+                debug!("Connecting blockchain data source {}", source_config);
+                Some(Mutex::new(chain::source_with(source_config)?))
+            }
+            None => None,
+        };
+
+        let ledger = config.ledger.as_deref().map(Ledger::with);
+        let revocations = config
+            .revocations
+            .as_deref()
+            .map(Revocations::with)
+            .map(Mutex::new);
 
         debug!("Opening ZMQ socket {}", config.endpoint);
-        let session_rpc = session::Raw::with_zmq_unencrypted(
-            ZmqType::Rep,
-            &config.endpoint,
-            None,
-            None,
+        let context = zmq::Context::new();
+        let frontend = context.socket(zmq::ROUTER)?;
+        frontend.bind(&config.endpoint.zmq_socket_string())?;
+        if let ZmqSocketAddr::Ipc(ref path) = config.endpoint {
+            restrict_socket(path)?;
+        }
+
+        let authenticator = Authenticator::with(config.clients.clone());
+        if authenticator.is_enabled() {
+            info!("Request authorization is enabled");
+        } else {
+            warn!("No clients are configured; request authorization is off");
+        }
+
+        if let Some(ref attester) = attester {
+            info!("Remote attestation is provided by {}", attester.platform());
+        }
+
+        if config.signing_cache > 0 {
+            info!(
+                "Unlocked sessions cache up to {} signing keys",
+                config.signing_cache
+            );
+        }
+
+        info!("RPC transport encryption: {}", config.transport_encryption);
+        info!("RPC payload encryption: {}", config.payload_encryption);
+        let channels = Channels::with(config.node_key);
+
+        let terminate = Arc::new(AtomicBool::new(false));
+        for signal in &[SIGTERM, SIGINT] {
+            signal_hook::flag::register(*signal, terminate.clone())?;
+        }
+        let reload = Arc::new(AtomicBool::new(false));
+        #[cfg(unix)]
+        signal_hook::flag::register(
+            signal_hook::consts::SIGHUP,
+            reload.clone(),
         )?;
 
-        Ok(Self {
+        #[cfg(not(feature = "grpc"))]
+        if config.grpc_endpoint.is_some() {
+            return Err(BootstrapError::GrpcNotCompiled);
+        }
+
+        let workers = config.workers;
+        let processor = Processor {
+            config_fingerprint: Mutex::new(config_fingerprint),
+            passphrase: Mutex::new(config.passphrase.clone()),
+            xpriv_export: Mutex::new(config.xpriv_export.clone()),
             config,
-            session_rpc,
-            vault,
-            unmarshaller: Request::create_unmarshaller(),
+            started: Instant::now(),
+            vaults,
+            chain_source,
+            ledger,
+            revocations,
+            attester: attester.map(Mutex::new),
+            authenticator: Mutex::new(authenticator),
+            approvals: Mutex::new(Approvals::new()),
+            musig: Mutex::new(MuSigSessions::new()),
+            jobs: Mutex::new(Jobs::new()),
+            idempotency: Mutex::new(Idempotency::new()),
+            job_queue: Mutex::new(None),
+            channels: Mutex::new(channels),
+        };
+
+        let processor = Arc::new(processor);
+        #[cfg(feature = "grpc")]
+        let grpc = match processor.config.grpc_endpoint {
+            Some(endpoint) => Some(grpc::Server::spawn(
+                endpoint,
+                processor.clone() as Arc<dyn grpc::Handler>,
+            )?),
+            None => None,
+        };
+
+        Ok(Self {
+            context,
+            frontend,
+            workers,
+            processor,
+            terminate,
+            reload,
+            #[cfg(feature = "grpc")]
+            grpc,
         })
     }
+
+    /// ZMQ context of the runtime. Applications embedding the daemon connect
+    /// to the in-process RPC endpoint (`inproc://...`) with sockets of this
+    /// context (see `Client::inproc`); the runtime shutdown completes once
+    /// these sockets are closed.
+    #[cfg(feature = "embedded")]
+    pub fn context(&self) -> zmq::Context {
+        self.context.clone()
+    }
+
+    /// Forwards client requests to the workers and their replies back to the
+    /// clients. Once a termination signal is received, new requests are left
+    /// unread and the proxy returns after the replies to the requests in
+    /// flight are sent, or after [`SHUTDOWN_TIMEOUT`].
+    fn proxy(&self, backend: &zmq::Socket) -> Result<(), RuntimeError> {
+        let mut in_flight = 0usize;
+        let mut stopping: Option<Instant> = None;
+        loop {
+            if stopping.is_none() && self.terminate.load(Ordering::Relaxed) {
+                info!(
+                    "Termination signal received; completing {} requests in \
+                     flight",
+                    in_flight
+                );
+                stopping = Some(Instant::now());
+            }
+            if self.reload.swap(false, Ordering::Relaxed) {
+                info!("Reloading configuration on SIGHUP signal");
+                if let Err(err) = self.processor.reconfigure() {
+                    error!("Configuration is not changed: {}", err);
+                }
+            }
+            match stopping {
+                Some(_) if in_flight == 0 => return Ok(()),
+                Some(since) if since.elapsed() > SHUTDOWN_TIMEOUT => {
+                    warn!("{} requests are not completed in time", in_flight);
+                    return Ok(());
+                }
+                _ => {}
+            }
+            let requests = match stopping {
+                Some(_) => zmq::PollEvents::empty(),
+                None => zmq::POLLIN,
+            };
+            let mut items = [
+                backend.as_poll_item(zmq::POLLIN),
+                self.frontend.as_poll_item(requests),
+            ];
+            match zmq::poll(&mut items, POLL_INTERVAL.as_millis() as i64) {
+                Err(zmq::Error::EINTR) => continue,
+                result => result?,
+            };
+            if items[0].is_readable() {
+                forward(backend, &self.frontend)?;
+                in_flight = in_flight.saturating_sub(1);
+            }
+            if items[1].is_readable() {
+                forward(&self.frontend, backend)?;
+                in_flight += 1;
+            }
+        }
+    }
+}
+
+/// Makes IPC socket file accessible to the daemon user only, since the
+/// requests coming over the local socket are not encrypted
+#[cfg(unix)]
+fn restrict_socket(path: &str) -> Result<(), io::Error> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+}
+
+/// ZMQ IPC transport is available on Unix systems only
+#[cfg(not(unix))]
+fn restrict_socket(_: &str) -> Result<(), io::Error> {
+    Ok(())
+}
+
+/// Moves a multipart message between the client and worker sockets
+fn forward(from: &zmq::Socket, to: &zmq::Socket) -> Result<(), zmq::Error> {
+    let message = from.recv_multipart(0)?;
+    to.send_multipart(message, 0)
 }
 
 impl TryService for Runtime {
     type ErrorType = RuntimeError;
 
-    fn try_run_loop(mut self) -> Result<(), Self::ErrorType> {
-        loop {
-            match self.run() {
-                Ok(_) => debug!("API request processing complete"),
-                Err(err) => {
-                    error!("Error processing API request: {}", err);
-                    Err(err)?;
+    fn try_run_loop(self) -> Result<(), Self::ErrorType> {
+        let backend = self.context.socket(zmq::DEALER)?;
+        backend.bind(WORKERS_ENDPOINT)?;
+        info!("Starting {} request processing workers", self.workers);
+        let mut handles = Vec::with_capacity(self.workers);
+        let (queue, tasks) = mpsc::channel();
+        *lock(&self.processor.job_queue) = Some(queue);
+        let processor = self.processor.clone();
+        let job_runner = thread::Builder::new()
+            .name("job-runner".to_owned())
+            .spawn(move || {
+                for task in tasks {
+                    processor.run_job(task);
                 }
+                debug!("Job runner is stopped");
+            })
+            .expect("unable to spawn job runner thread");
+        for id in 0..self.workers {
+            let worker = Worker {
+                id,
+                context: self.context.clone(),
+                processor: self.processor.clone(),
+            };
+            let handle = thread::Builder::new()
+                .name(format!("worker-{}", id))
+                .spawn(move || worker.run_loop())
+                .expect("unable to spawn worker thread");
+            handles.push(handle);
+        }
+        trace!("Awaiting for ZMQ RPC requests...");
+        self.proxy(&backend)?;
+
+        info!("Stopping request processing workers");
+        let Runtime {
+            mut context,
+            frontend,
+            processor,
+            #[cfg(feature = "grpc")]
+            grpc,
+            ..
+        } = self;
+        #[cfg(feature = "grpc")]
+        if let Some(server) = grpc {
+            info!("Stopping gRPC interface");
+            server.shutdown();
+        }
+        frontend.set_linger(REPLY_LINGER)?;
+        drop(frontend);
+        drop(backend);
+        // Workers awaiting for requests get `ETERM` error and stop
+        context.destroy()?;
+        for handle in handles {
+            if handle.join().is_err() {
+                error!("Worker thread has panicked");
             }
         }
+        // Closing the queue stops the job runner once the queued jobs are
+        // completed
+        info!("Completing queued jobs");
+        lock(&processor.job_queue).take();
+        if job_runner.join().is_err() {
+            error!("Job runner thread has panicked");
+        }
+        Arc::try_unwrap(processor)
+            .unwrap_or_else(|_| {
+                unreachable!(
+                    "all workers, job runner and gRPC server are stopped"
+                )
+            })
+            .shutdown()
     }
 }
 
-impl Runtime {
-    fn run(&mut self) -> Result<(), RuntimeError> {
-        trace!("Awaiting for ZMQ RPC requests...");
-        let raw = self.session_rpc.recv_raw_message()?;
-        let reply = self.rpc_process(raw).unwrap_or_else(|err| err);
-        trace!("Preparing ZMQ RPC reply: {:?}", reply);
-        let data = reply.serialize();
-        trace!(
-            "Sending {} bytes back to the client over ZMQ RPC",
-            data.len()
+#[cfg(feature = "grpc")]
+impl grpc::Handler for Processor {
+    fn handle(&self, request: Request, secret: Option<Vec<u8>>) -> Reply {
+        let _scope = logging::RequestScope::enter(&request);
+        debug!("Received gRPC request: {:?}", request.type_id());
+        let client = lock(&self.authenticator)
+            .authorize_secret(&request, secret.as_deref());
+        match client {
+            Ok(client) => self
+                .dispatch(request, client, None)
+                .unwrap_or_else(|err| err),
+            Err(err) => Reply::from(err),
+        }
+    }
+}
+
+/// Request queued for the job runner
+struct Task {
+    job: types::JobId,
+    request: Request,
+    client: Option<String>,
+    /// Vault the request was explicitly routed to
+    vault: Option<VaultId>,
+    /// Key the job reply is sealed to, if the job was requested with a
+    /// sealed request
+    reply_key: Option<PublicKey>,
+}
+
+/// Worker thread serving client requests forwarded by the runtime
+struct Worker {
+    id: usize,
+    context: zmq::Context,
+    processor: Arc<Processor>,
+}
+
+impl Worker {
+    /// Serves requests until the ZMQ context is terminated. After a socket
+    /// error the worker re-connects with a new socket, since the failed one
+    /// may be left in a state where it can't receive requests.
+    fn run_loop(self) {
+        loop {
+            match self.serve() {
+                Err(RuntimeError::Zmq(zmq::Error::ETERM)) => break,
+                Err(err) => error!(
+                    "Worker {} failed processing API request: {}",
+                    self.id, err
+                ),
+                Ok(_) => {}
+            }
+        }
+        debug!("Worker {} is stopped", self.id);
+    }
+
+    fn serve(&self) -> Result<(), RuntimeError> {
+        let socket = self.context.socket(zmq::REP)?;
+        socket.connect(WORKERS_ENDPOINT)?;
+        let unmarshaller = Request::create_unmarshaller();
+        loop {
+            trace!("Worker {} awaits for ZMQ RPC requests...", self.id);
+            let raw = socket.recv_bytes(0)?;
+            let data = self.processor.process(&unmarshaller, raw);
+            trace!(
+                "Sending {} bytes back to the client over ZMQ RPC",
+                data.len()
+            );
+            socket.send(data, 0)?;
+            debug!("API request processing complete");
+        }
+    }
+}
+
+impl Processor {
+    /// Flushes the vault and wipes secret keys kept by the runtime: session
+    /// decryption keys, encrypted channel keys and the node key
+    fn shutdown(mut self) -> Result<(), RuntimeError> {
+        for (id, instance) in &self.vaults {
+            debug!("Flushing vault `{}`", id);
+            instance
+                .vault
+                .write()
+                .expect("vault lock is poisoned")
+                .flush()?;
+            lock(&instance.sessions).lock_all();
+        }
+        lock(&self.channels).wipe();
+        wipe_key(&mut self.config.node_key);
+        info!("Runtime is shut down; secret keys are wiped from the memory");
+        Ok(())
+    }
+
+    /// Re-reads the configuration file and applies the settings which may
+    /// be changed at runtime: log level and format, vault unlock timeout,
+    /// signing cache, passphrase policy, clients and private key export
+    /// policy. Nothing is changed if the file changes other settings.
+    fn reconfigure(&self) -> Result<(), RuntimeError> {
+        let reloaded = self
+            .config
+            .reload()
+            .map_err(|err| RuntimeError::Reconfiguration(err.to_string()))?;
+        let immutable = self.config.immutable_changes(&reloaded);
+        if !immutable.is_empty() {
+            return Err(RuntimeError::ImmutableSettings(immutable.join(", ")));
+        }
+        let config_fingerprint = reloaded
+            .fingerprint()
+            .map_err(|err| RuntimeError::Reconfiguration(err.to_string()))?;
+
+        logging::set_level(reloaded.log_level);
+        logging::set_format(reloaded.log_format);
+        for instance in self.vaults.values() {
+            let mut sessions = lock(&instance.sessions);
+            sessions.set_timeout(Duration::from_secs(
+                reloaded.encryption.unlock_timeout(),
+            ));
+            sessions.enable_signing_cache(reloaded.signing_cache);
+        }
+        *lock(&self.passphrase) = reloaded.passphrase;
+        *lock(&self.xpriv_export) = reloaded.xpriv_export;
+        let mut authenticator = lock(&self.authenticator);
+        authenticator.set_clients(reloaded.clients);
+        if !authenticator.is_enabled() {
+            warn!("No clients are configured; request authorization is off");
+        }
+        *lock(&self.config_fingerprint) = config_fingerprint;
+        info!(
+            "Configuration is reloaded; effective configuration fingerprint: \
+             {}",
+            config_fingerprint
         );
-        self.session_rpc.send_raw_message(&data)?;
         Ok(())
     }
 
-    fn rpc_process(&mut self, raw: Vec<u8>) -> Result<Reply, Reply> {
+    /// Vault the request served by the current thread is routed to; see
+    /// [`Processor::route`]
+    fn instance(&self) -> &VaultInstance {
+        ROUTE
+            .with(|route| self.vaults.get(&*route.borrow()))
+            .expect("requests are routed to the configured vaults only")
+    }
+
+    /// Locks unlocked sessions of the vault the request is routed to
+    fn sessions(&self) -> MutexGuard<Sessions> {
+        lock(&self.instance().sessions)
+    }
+
+    /// Acquires the vault lock for a request reading the vault
+    fn vault(&self) -> RwLockReadGuard<Vault> {
+        self.instance()
+            .vault
+            .read()
+            .expect("vault lock is poisoned")
+    }
+
+    /// Acquires exclusive vault lock for a request modifying the vault
+    fn vault_mut(&self) -> RwLockWriteGuard<Vault> {
+        self.instance()
+            .vault
+            .write()
+            .expect("vault lock is poisoned")
+    }
+
+    /// Selects vault serving the `message`: the vault the message is
+    /// explicitly routed to, the vault containing the key the message
+    /// refers to, or the vault configured for the chain of a new keyring.
+    /// Other requests are served by the default vault.
+    fn route(
+        &self,
+        message: &Request,
+        vault: Option<VaultId>,
+    ) -> Result<VaultId, RuntimeError> {
+        if let Some(id) = vault {
+            return match self.vaults.contains_key(&id) {
+                true => Ok(id),
+                false => Err(RuntimeError::UnknownVault(id)),
+            };
+        }
+        let found = match message {
+            _ if self.vaults.len() == 1 => None,
+            Request::Seed(seed) => self
+                .vaults
+                .iter()
+                .find(|(_, instance)| instance.chains.contains(&seed.chain)),
+            message => message.key_id().and_then(|key_id| {
+                self.vaults.iter().find(|(_, instance)| {
+                    instance
+                        .vault
+                        .read()
+                        .expect("vault lock is poisoned")
+                        .account_by_id(key_id)
+                        .is_some()
+                })
+            }),
+        };
+        Ok(found
+            .map(|(id, _)| id.clone())
+            .unwrap_or_else(|| s!(DEFAULT_VAULT_ID)))
+    }
+
+    fn process(
+        &self,
+        unmarshaller: &Unmarshaller<Request>,
+        raw: Vec<u8>,
+    ) -> Vec<u8> {
+        match transport::decode_frame(&raw) {
+            Some((channel, payload)) => {
+                self.process_encrypted(unmarshaller, channel, payload)
+            }
+            None => self.process_plaintext(unmarshaller, raw),
+        }
+    }
+
+    fn process_plaintext(
+        &self,
+        unmarshaller: &Unmarshaller<Request>,
+        raw: Vec<u8>,
+    ) -> Vec<u8> {
+        let reply = if self.config.transport_encryption
+            == TransportEncryption::Required
+        {
+            Reply::from(RuntimeError::EncryptionRequired)
+        } else {
+            self.rpc_process(unmarshaller, raw)
+                .unwrap_or_else(|err| err)
+        };
+        trace!("Preparing ZMQ RPC reply: {:?}", reply);
+        reply.serialize()
+    }
+
+    fn process_encrypted(
+        &self,
+        unmarshaller: &Unmarshaller<Request>,
+        channel: ChannelId,
+        payload: &[u8],
+    ) -> Vec<u8> {
+        trace!("Got frame for encrypted channel {}", channel);
+        if self.config.transport_encryption == TransportEncryption::Disabled {
+            return Reply::from(RuntimeError::EncryptionDisabled).serialize();
+        }
+        let received = lock(&self.channels).receive(channel, payload);
+        let reply = match received {
+            Ok(Received::Handshake(act)) => {
+                return transport::encode_frame(channel, &act)
+            }
+            Ok(Received::Request(raw)) => self
+                .rpc_process(unmarshaller, raw)
+                .unwrap_or_else(|err| err),
+            Err(err) => return Reply::from(err).serialize(),
+        };
+        trace!("Preparing encrypted ZMQ RPC reply: {:?}", reply);
+        let encrypted =
+            lock(&self.channels).encrypt(channel, &reply.serialize());
+        match encrypted {
+            Ok(data) => transport::encode_frame(channel, &data),
+            Err(err) => Reply::from(err).serialize(),
+        }
+    }
+
+    fn rpc_process(
+        &self,
+        unmarshaller: &Unmarshaller<Request>,
+        raw: Vec<u8>,
+    ) -> Result<Reply, Reply> {
         trace!("Got {} bytes over ZMQ RPC", raw.len());
-        let message = (&*self.unmarshaller.unmarshall(&raw)?).clone();
+        let message = match unmarshaller.unmarshall(&raw) {
+            Ok(message) => (&*message).clone(),
+            Err(presentation::Error::MessageEvenType)
+            | Err(presentation::Error::UnknownDataType) => {
+                let type_id = raw
+                    .get(..2)
+                    .and_then(|data| strict_deserialize::<u16>(data).ok())
+                    .unwrap_or_default();
+                warn!("Received request of unknown type {:#06x}", type_id);
+                Err(RuntimeError::UnsupportedRequest(
+                    type_id,
+                    rpc::MIN_PROTOCOL_VERSION,
+                    rpc::PROTOCOL_VERSION,
+                ))?
+            }
+            Err(err) => Err(err)?,
+        };
+        let _scope = logging::RequestScope::enter(&message);
         debug!("Received ZMQ RPC request: {:?}", message.type_id());
+        let client = lock(&self.authenticator).authorize(&message)?;
+        self.dispatch(message, client, None)
+    }
+
+    /// Serves request authorized for the `client`, opening sealed, tagged
+    /// and routed requests and wrapping replies to them. Requests routed to
+    /// a `vault` are served by it.
+    fn dispatch(
+        &self,
+        message: Request,
+        client: Option<String>,
+        vault: Option<VaultId>,
+    ) -> Result<Reply, Reply> {
+        let sealed = match message {
+            Request::Tagged(tagged) => {
+                let message =
+                    rpc::tagged::open(&tagged).map_err(RuntimeError::from)?;
+                debug!(
+                    "Opened {} request with id {}",
+                    message.name(),
+                    tagged.request_id
+                );
+                let reply = self
+                    .dispatch(message, client, vault)
+                    .unwrap_or_else(|err| err);
+                return Ok(rpc::tagged::tag_reply(&reply, tagged.request_id));
+            }
+            Request::Routed(routed) => {
+                let message =
+                    rpc::routed::open(&routed).map_err(RuntimeError::from)?;
+                debug!(
+                    "Opened {} request routed to vault `{}`",
+                    message.name(),
+                    routed.vault_id
+                );
+                return self.dispatch(message, client, Some(routed.vault_id));
+            }
+            Request::Sealed(sealed) => sealed,
+            message => {
+                if self.config.payload_encryption == PayloadEncryption::Required
+                    && message.has_secrets()
+                {
+                    warn!("Refusing unsealed request {}", message.name());
+                    Err(RuntimeError::SealingRequired)?
+                }
+                return self.serve(message, client, None, vault);
+            }
+        };
+        if self.config.payload_encryption == PayloadEncryption::Disabled {
+            Err(RuntimeError::SealingDisabled)?
+        }
+        let (message, reply_key) =
+            rpc::sealed::open(&sealed, &self.config.node_key)
+                .map_err(RuntimeError::from)?;
+        debug!("Opened sealed {} request", message.name());
+        let reply = self
+            .serve(message, client, Some(reply_key), vault)
+            .unwrap_or_else(|err| err);
+        Ok(rpc::sealed::seal_reply(&reply, reply_key)
+            .map_err(RuntimeError::from)?)
+    }
+
+    /// Serves opened request. Requests tagged with a job id are queued to
+    /// the job runner, replying with the job progress; replies of the jobs
+    /// started by sealed requests are sealed to the `reply_key`.
+    fn serve(
+        &self,
+        mut message: Request,
+        client: Option<String>,
+        reply_key: Option<PublicKey>,
+        vault: Option<VaultId>,
+    ) -> Result<Reply, Reply> {
+        if self.config.read_only && !message.is_read_only() {
+            warn!("Refusing request {} in read-only mode", message);
+            Err(RuntimeError::ReadOnly)?
+        }
+        match message.job_mut().and_then(|job| *job) {
+            Some(job) => {
+                self.submit_job(job, message, client, reply_key, vault)
+            }
+            None => match message.idempotency_key() {
+                Some(key) => self.execute_once(key, message, client, vault),
+                None => {
+                    let reply = self.execute(message, client.clone(), vault)?;
+                    Ok(self.redact(reply, client))
+                }
+            },
+        }
+    }
+
+    /// Serves idempotent request with the `key` unless it was already
+    /// served, in which case the kept reply is returned
+    fn execute_once(
+        &self,
+        key: types::IdempotencyKey,
+        message: Request,
+        client: Option<String>,
+        vault: Option<VaultId>,
+    ) -> Result<Reply, Reply> {
+        let served = lock(&self.idempotency).begin(
+            client.clone(),
+            key,
+            message.name(),
+        )?;
+        if let Some(reply) = served {
+            return Ok(reply);
+        }
+        let result = self
+            .execute(message, client.clone(), vault)
+            .map(|reply| self.redact(reply, client.clone()));
+        let mut idempotency = lock(&self.idempotency);
+        match result {
+            Ok(ref reply) => idempotency.complete(client, key, reply.clone()),
+            Err(_) => idempotency.abandon(client, key),
+        }
+        result
+    }
+
+    /// Redacts fields of the `reply` hidden from the `client`, see
+    /// [`Authenticator::redact`]
+    fn redact(&self, reply: Reply, client: Option<String>) -> Reply {
+        lock(&self.authenticator).redact(reply, client.as_deref())
+    }
+
+    fn submit_job(
+        &self,
+        job: types::JobId,
+        request: Request,
+        client: Option<String>,
+        reply_key: Option<PublicKey>,
+        vault: Option<VaultId>,
+    ) -> Result<Reply, Reply> {
+        let queue = lock(&self.job_queue)
+            .clone()
+            .ok_or(RuntimeError::ShuttingDown)?;
+        let progress = lock(&self.jobs).submit(job, request.name())?;
+        queue
+            .send(Task {
+                job,
+                request,
+                client,
+                vault,
+                reply_key,
+            })
+            .map_err(|_| RuntimeError::ShuttingDown)?;
+        Ok(Reply::JobProgress(progress))
+    }
+
+    /// Serves request of the job `task`, keeping the reply until the client
+    /// polls it
+    fn run_job(&self, task: Task) {
+        let _scope = logging::RequestScope::enter(&task.request);
+        let reply = self
+            .execute(task.request, task.client.clone(), task.vault)
+            .map(|reply| self.redact(reply, task.client))
+            .unwrap_or_else(|err| err);
+        let reply = match task.reply_key {
+            Some(reply_key) => rpc::sealed::seal_reply(&reply, reply_key)
+                .unwrap_or_else(|err| Reply::from(RuntimeError::from(err))),
+            None => reply,
+        };
+        lock(&self.jobs).complete(task.job, reply);
+    }
+
+    fn execute(
+        &self,
+        message: Request,
+        client: Option<String>,
+        vault: Option<VaultId>,
+    ) -> Result<Reply, Reply> {
+        let vault_id = self.route(&message, vault)?;
+        if vault_id != DEFAULT_VAULT_ID {
+            debug!("Request is served by vault `{}`", vault_id);
+        }
+        ROUTE.with(|route| *route.borrow_mut() = vault_id);
         match message {
+            Request::Challenge(client) => Ok(Reply::Challenge(
+                lock(&self.authenticator).challenge(&client)?,
+            )),
+            Request::Status => self.rpc_status(),
+            Request::Hello(hello) => self.rpc_hello(hello),
+            Request::Reconfigure(_) => self.rpc_reconfigure(client),
+            Request::Attest(attest) => self.rpc_attest(attest),
+            Request::JobStatus(status) => self.rpc_job_status(status),
+            // Sealed, tagged and routed requests are opened by `dispatch`
+            // and can't be nested
+            Request::Sealed(_) => {
+                Err(RuntimeError::from(rpc::sealed::Error::Nested))?
+            }
+            Request::Tagged(_) => {
+                Err(RuntimeError::from(rpc::tagged::Error::Nested))?
+            }
+            Request::Routed(_) => {
+                Err(RuntimeError::from(rpc::routed::Error::Nested))?
+            }
+            Request::Unlock(unlock) => self.rpc_unlock(unlock),
+            Request::Lock(lock) => self.rpc_lock(lock),
             Request::Seed(seed) => self.rpc_seed_create(seed),
             Request::List => self.rpc_list(),
+            Request::ListVaults => self.rpc_list_vaults(),
+            Request::ListWithBalances(scan) => {
+                self.rpc_list_with_balances(scan)
+            }
+            Request::FindAccounts(query) => {
+                self.rpc_find_accounts(query, client)
+            }
+            Request::ListMultisig => self.rpc_list_multisig(),
+            Request::DeleteKeyring(delete) => self.rpc_delete_keyring(delete),
+            Request::ImportDescriptors(import) => {
+                self.rpc_import_descriptors(import)
+            }
+            Request::ImportXpub(import) => self.rpc_import_xpub(import),
+            Request::ImportXpriv(import) => self.rpc_import_xpriv(import),
             Request::Derive(derive) => self.rpc_derive(derive),
+            Request::DeleteAccount(delete) => self.rpc_delete_account(delete),
+            Request::SetLifecycle(lifecycle) => {
+                self.rpc_set_lifecycle(lifecycle)
+            }
+            Request::DeriveRange(range) => self.rpc_derive_range(range),
+            Request::SetBranches(branches) => self.rpc_set_branches(branches),
+            Request::SetPolicy(policy) => self.rpc_set_policy(policy),
+            Request::SetLabel(label) => self.rpc_set_label(label),
+            Request::UpdateAccount(update) => self.rpc_update_account(update),
+            Request::CreateMultisig(create) => self.rpc_create_multisig(create),
+            Request::ExportMultisig(export) => self.rpc_export_multisig(export),
+            Request::CommitSandbox(sandbox) => self.rpc_commit_sandbox(sandbox),
+            Request::DiscardSandbox(sandbox) => {
+                self.rpc_discard_sandbox(sandbox)
+            }
+            Request::Discover(discover) => self.rpc_discover(discover),
             Request::ExportXpub(export) => self.rpc_export_xpub(export),
+            Request::ApproveExport(approve) => self.rpc_approve_export(approve),
             Request::ExportXpriv(export) => self.rpc_export_xpriv(export),
-            Request::SignPsbt(sign) => self.rpc_sign_psbt(sign),
+            Request::ExportDescriptor(export) => {
+                self.rpc_export_descriptor(export)
+            }
+            Request::IdentityKey(identity) => self.rpc_identity_key(identity),
+            Request::DeriveEntropy(derive) => self.rpc_derive_entropy(derive),
+            Request::DerivePaymentCode(derive) => {
+                self.rpc_derive_payment_code(derive)
+            }
+            Request::DeriveLnKeySet(derive) => self.rpc_derive_ln_keys(derive),
+            Request::Ecdh(ecdh) => self.rpc_ecdh(ecdh),
+            Request::Backup(backup) => self.rpc_backup(backup),
+            Request::ExportLedger(export) => self.rpc_export_ledger(export),
+            Request::Restore(restore) => self.rpc_restore(restore),
+            Request::SignPsbt(sign) => self.rpc_sign_psbt(sign, client.clone()),
             Request::SignKey(sign) => self.rpc_sign_key(sign),
             Request::SignData(sign) => self.rpc_sign_data(sign),
+            Request::SignIdentity(sign) => self.rpc_sign_identity(sign),
+            Request::SignMessage(sign) => self.rpc_sign_message(sign),
+            Request::SignInvoice(sign) => self.rpc_sign_invoice(sign),
+            Request::SignChannelAnnouncement(sign) => {
+                self.rpc_sign_announcement(sign)
+            }
+            Request::SignGossip(sign) => self.rpc_sign_gossip(sign),
+            Request::ExportVault(export) => self.rpc_export_vault(export),
+            Request::ImportVault(import) => self.rpc_import_vault(import),
+            Request::FinalizePsbt(finalize) => self.rpc_finalize_psbt(finalize),
+            Request::ComposePsbt(compose) => self.rpc_compose_psbt(compose),
+            Request::AnalyzePsbt(analyze) => self.rpc_analyze_psbt(analyze),
+            Request::CombinePsbt(combine) => self.rpc_combine_psbt(combine),
+            Request::LoadVault(_) => self.rpc_load_vault(),
+            Request::StoreVault(store) => self.rpc_store_vault(store),
+            Request::AppendRevocation(append) => {
+                self.rpc_append_revocation(append)
+            }
+            Request::QueryRevocation(query) => self.rpc_query_revocation(query),
+            Request::CompactRevocations(compact) => {
+                self.rpc_compact_revocations(compact)
+            }
+            Request::MuSigStartSession(start) => self.rpc_musig_start(start),
+            Request::MuSigNonceExchange(exchange) => {
+                self.rpc_musig_nonce_exchange(exchange)
+            }
+            Request::MuSigPartialSign(sign) => self.rpc_musig_sign(sign),
+        }
+    }
+
+    fn rpc_status(&self) -> Result<Reply, Reply> {
+        let (keyrings, accounts) = self.vault().count();
+        let locked = match self.config.encryption {
+            Encryption::Passphrase { .. } => {
+                let mut sessions = self.sessions();
+                sessions.expire();
+                sessions.is_empty()
+            }
+            _ => false,
+        };
+        Ok(Reply::Status(types::Status {
+            config_fingerprint: *lock(&self.config_fingerprint),
+            protocol_version: rpc::PROTOCOL_VERSION,
+            uptime: self.started.elapsed().as_secs(),
+            driver: self.instance().driver.to_owned(),
+            keyrings: keyrings as u32,
+            accounts: accounts as u32,
+            locked,
+        }))
+    }
+
+    fn rpc_hello(&self, hello: message::Hello) -> Result<Reply, Reply> {
+        if hello.client_version < rpc::MIN_PROTOCOL_VERSION {
+            warn!(
+                "Refusing client of outdated protocol version {}",
+                hello.client_version
+            );
+            Err(RuntimeError::IncompatibleClient(
+                hello.client_version,
+                rpc::MIN_PROTOCOL_VERSION,
+                rpc::PROTOCOL_VERSION,
+            ))?
+        }
+        debug!(
+            "Client of protocol version {} uses features: {}",
+            hello.client_version, hello.features
+        );
+        let mut features = types::Features::with(vec![
+            types::Feature::Taproot,
+            types::Feature::MuSig,
+            types::Feature::Lightning,
+        ]);
+        #[cfg(feature = "grpc")]
+        features.insert(types::Feature::Grpc);
+        #[cfg(feature = "export-secrets")]
+        features.insert(types::Feature::ExportSecrets);
+        if self.config.payload_encryption != PayloadEncryption::Disabled {
+            features.insert(types::Feature::Sealing);
+        }
+        if let Encryption::Passphrase { .. } = self.config.encryption {
+            features.insert(types::Feature::Passphrase);
+        }
+        Ok(Reply::Hello(types::Hello {
+            server_version: rpc::PROTOCOL_VERSION,
+            min_version: rpc::MIN_PROTOCOL_VERSION,
+            features,
+        }))
+    }
+
+    /// Reconfiguration changes authorization of the clients, so it is
+    /// served only to the administrators
+    fn rpc_reconfigure(&self, client: Option<String>) -> Result<Reply, Reply> {
+        let admin = client
+            .and_then(|name| {
+                lock(&self.authenticator)
+                    .client(&name)
+                    .map(|config| config.admin)
+            })
+            .unwrap_or(false);
+        if !admin {
+            warn!("Refusing reconfiguration requested by non-administrator");
+            Err(RuntimeError::Unauthorized)?
+        }
+        self.reconfigure()?;
+        Ok(Reply::Success)
+    }
+
+    fn rpc_attest(&self, attest: message::Attest) -> Result<Reply, Reply> {
+        let attester = self
+            .attester
+            .as_ref()
+            .ok_or(RuntimeError::AttestationDisabled)?;
+        let config_fingerprint = *lock(&self.config_fingerprint);
+        let report_data = types::Attestation::report_data(
+            &self.config.node_id(),
+            attest.nonce,
+            config_fingerprint,
+        );
+        let mut attester = lock(attester);
+        let quote = attester.quote(&report_data).map_err(RuntimeError::from)?;
+        debug!(
+            "Produced {} quote of {} bytes",
+            attester.platform(),
+            quote.len()
+        );
+        Ok(Reply::Attestation(types::Attestation {
+            platform: attester.platform().to_owned(),
+            config_fingerprint,
+            report_data: report_data.to_vec(),
+            quote,
+        }))
+    }
+
+    fn rpc_job_status(
+        &self,
+        status: message::JobStatus,
+    ) -> Result<Reply, Reply> {
+        Ok(lock(&self.jobs)
+            .status(status.job)
+            .ok_or(RuntimeError::UnknownJob)?)
+    }
+
+    /// Runs `operation` of `total` steps, reporting its progress as the
+    /// client `job`, if the request is served as a job. The operation is
+    /// given a callback marking each completed step.
+    fn track<T>(
+        &self,
+        job: Option<types::JobId>,
+        operation: &'static str,
+        total: u32,
+        f: impl FnOnce(&mut dyn FnMut()) -> T,
+    ) -> T {
+        let job = match job {
+            Some(job) => job,
+            None => return f(&mut || ()),
+        };
+        lock(&self.jobs).start(job, operation, total);
+        f(&mut || lock(&self.jobs).step(job))
+    }
+
+    /// Returns the key used to decrypt vault data. If a `session` token is
+    /// given, the key is taken from the unlocked session; otherwise for the
+    /// vault encrypted with the node key the `provided` key is used, while
+    /// the passphrase-encrypted vault requires an unlocked session.
+    fn decryption_key(
+        &self,
+        provided: SecretKey,
+        session: Option<types::SessionToken>,
+    ) -> Result<SecretKey, RuntimeError> {
+        match (session, &self.config.encryption) {
+            (Some(token), _) => Ok(self.sessions().decryption_key(token)?),
+            (None, Encryption::Passphrase { .. }) => {
+                Err(RuntimeError::VaultLocked)
+            }
+            (None, _) => Ok(provided),
+        }
+    }
+
+    /// Returns public key used to encrypt newly created keyrings. For the
+    /// passphrase-encrypted vault the key becomes known only after the first
+    /// unlock.
+    fn encryption_key(&self) -> Result<PublicKey, RuntimeError> {
+        match self.config.encryption {
+            Encryption::Passphrase { .. } => {
+                lock(&self.instance().vault_pubkey)
+                    .ok_or(RuntimeError::VaultLocked)
+            }
+            _ => Ok(self.config.node_id()),
+        }
+    }
+
+    fn rpc_unlock(&self, unlock: message::Unlock) -> Result<Reply, Reply> {
+        let key = match self.config.encryption {
+            Encryption::Passphrase { .. } => {
+                let policy = lock(&self.passphrase).clone();
+                policy
+                    .check(&unlock.passphrase)
+                    .map_err(RuntimeError::from)?;
+                policy
+                    .kdf
+                    .derive_key(&unlock.passphrase, self.config.node_id())
+                    .map_err(RuntimeError::from)?
+            }
+            _ => unlock.decryption_key,
+        };
+        let pubkey = PublicKey::from_secret_key(&crate::SECP256K1, &key);
+        trace!("Awaiting for the vault lock");
+        let mut check_key = key;
+        let instance = self.instance();
+        let vault = self.vault();
+        vault.verify_decryption_key(&mut check_key)?;
+        // Passphrase of the vault without private keys can't be verified, so
+        // it becomes the vault passphrase only once it is entered twice
+        if matches!(self.config.encryption, Encryption::Passphrase { .. })
+            && !vault.has_private_keys()
+            && *lock(&instance.vault_pubkey) != Some(pubkey)
+            && lock(&instance.unconfirmed_pubkey).replace(pubkey)
+                != Some(pubkey)
+        {
+            info!("Vault passphrase has to be confirmed");
+            Err(RuntimeError::PassphraseConfirmation)?
         }
+        drop(vault);
+        trace!("Vault lock released");
+        *lock(&instance.unconfirmed_pubkey) = None;
+        *lock(&instance.vault_pubkey) = Some(pubkey);
+        let mut sessions = lock(&instance.sessions);
+        let token = sessions.unlock(key);
+        info!("Vault is unlocked");
+        Ok(Reply::Session(types::Session {
+            token,
+            expires_in: sessions.timeout().as_secs(),
+        }))
     }
 
-    fn rpc_seed_create(&mut self, seed: message::Seed) -> Result<Reply, Reply> {
+    fn rpc_lock(&self, message: message::Lock) -> Result<Reply, Reply> {
+        self.sessions()
+            .lock(message.session)
+            .map_err(RuntimeError::from)?;
+        info!("Vault session is locked");
+        Ok(Reply::Success)
+    }
+
+    fn rpc_seed_create(&self, seed: message::Seed) -> Result<Reply, Reply> {
+        let encryption_key = self.encryption_key()?;
         trace!("Awaiting for the vault lock");
-        self.vault.seed(
+        let info = self.vault_mut().seed(
             seed.name,
             seed.description,
             &seed.chain,
             seed.application,
-            self.config.node_id(),
+            encryption_key,
         )?;
         trace!("Vault lock released");
-        Ok(Reply::Success)
+        Ok(Reply::AccountInfo(info))
     }
 
-    fn rpc_list(&mut self) -> Result<Reply, Reply> {
+    fn rpc_list(&self) -> Result<Reply, Reply> {
         trace!("Awaiting for the vault lock");
-        let accounts = self.vault.list()?;
+        let accounts = self.vault().list()?;
         trace!("Vault lock released");
         Ok(Reply::Keylist(accounts))
     }
 
-    fn rpc_derive(&mut self, derive: message::Derive) -> Result<Reply, Reply> {
+    fn rpc_list_vaults(&self) -> Result<Reply, Reply> {
+        let vaults = self
+            .vaults
+            .iter()
+            .map(|(id, instance)| {
+                let (keyrings, accounts) = instance
+                    .vault
+                    .read()
+                    .expect("vault lock is poisoned")
+                    .count();
+                types::VaultInfo {
+                    id: id.clone(),
+                    driver: instance.driver.to_owned(),
+                    chains: instance.chains.clone(),
+                    keyrings: keyrings as u32,
+                    accounts: accounts as u32,
+                }
+            })
+            .collect();
+        Ok(Reply::VaultList(vaults))
+    }
+
+    fn rpc_list_multisig(&self) -> Result<Reply, Reply> {
         trace!("Awaiting for the vault lock");
-        let mut seckey = self.config.node_key.clone();
-        let account = self.vault.derive(
-            derive.from,
-            derive.path,
-            derive.name,
-            Some(derive.details),
-            derive.assets,
-            &mut seckey, //TODO: &mut derive.decryption_key,
-        )?;
+        let groups = self.vault().multisig_groups();
         trace!("Vault lock released");
-        Ok(Reply::AccountInfo(account))
+        Ok(Reply::MultisigGroups(groups))
     }
 
-    fn rpc_export_xpub(
-        &mut self,
-        export: message::Export,
+    /// Searches accounts after redacting them for the `client`, so the
+    /// client can't learn hidden metadata from the search results
+    fn rpc_find_accounts(
+        &self,
+        query: types::AccountQuery,
+        client: Option<String>,
     ) -> Result<Reply, Reply> {
         trace!("Awaiting for the vault lock");
-        let key = self.vault.xpub(export.key_id)?;
+        let accounts = self.vault().list()?;
         trace!("Vault lock released");
-        Ok(Reply::XPub(key))
+        let accounts = match self.redact(Reply::Keylist(accounts), client) {
+            Reply::Keylist(accounts) => accounts,
+            _ => unreachable!("redaction preserves the reply type"),
+        };
+        Ok(Reply::Keylist(
+            accounts
+                .into_iter()
+                .filter(|info| query.matches(info))
+                .collect(),
+        ))
     }
 
-    fn rpc_export_xpriv(
-        &mut self,
-        mut export: message::Export,
+    fn rpc_list_with_balances(
+        &self,
+        scan: message::Scan,
     ) -> Result<Reply, Reply> {
+        let source = self
+            .chain_source
+            .as_ref()
+            .ok_or(RuntimeError::NoChainSource)?;
         trace!("Awaiting for the vault lock");
-        let key = self
-            .vault
-            .xpriv(export.key_id, &mut export.decryption_key)?;
+        let balances = self
+            .vault()
+            .list_with_balances(lock(source).as_ref(), scan.gap_limit)?;
         trace!("Vault lock released");
-        Ok(Reply::XPriv(key))
+        Ok(Reply::BalanceList(balances))
     }
 
-    fn rpc_sign_psbt(
-        &mut self,
-        message: message::SignPsbt,
+    fn rpc_backup(&self, backup: message::Backup) -> Result<Reply, Reply> {
+        trace!("Awaiting for the vault lock");
+        let path = self.track(backup.job, "backup", 1, |progress| {
+            let path = self.vault().backup();
+            progress();
+            path
+        })?;
+        trace!("Vault lock released");
+        Ok(Reply::Backup(path))
+    }
+
+    fn rpc_restore(&self, restore: message::Restore) -> Result<Reply, Reply> {
+        trace!("Awaiting for the vault lock");
+        let accounts = self.track(restore.job, "restore", 1, |progress| {
+            let accounts = self
+                .vault_mut()
+                .restore(&restore.snapshot, &self.config.node_key);
+            progress();
+            accounts
+        })?;
+        trace!("Vault lock released");
+        Ok(Reply::Keylist(accounts))
+    }
+
+    fn rpc_export_vault(
+        &self,
+        export: message::ExportVault,
+    ) -> Result<Reply, Reply> {
+        let policy = lock(&self.passphrase).clone();
+        if let Some(ref passphrase) = export.passphrase {
+            policy.check(passphrase).map_err(RuntimeError::from)?;
+        }
+        trace!("Awaiting for the vault lock");
+        let dump = self.vault().export_dump(
+            export
+                .passphrase
+                .as_ref()
+                .map(|passphrase| (passphrase.as_str(), policy.kdf)),
+        )?;
+        trace!("Vault lock released");
+        Ok(Reply::VaultDump(dump))
+    }
+
+    fn rpc_import_vault(
+        &self,
+        import: message::ImportVault,
+    ) -> Result<Reply, Reply> {
+        let mut decryption_key =
+            self.decryption_key(self.config.node_key, import.session)?;
+        trace!("Awaiting for the vault lock");
+        let accounts = self.vault_mut().import_dump(
+            &import.dump,
+            import.passphrase.as_deref(),
+            import.replace,
+            &mut decryption_key,
+        )?;
+        trace!("Vault lock released");
+        Ok(Reply::Keylist(accounts))
+    }
+
+    fn rpc_load_vault(&self) -> Result<Reply, Reply> {
+        self.check_federation()?;
+        trace!("Awaiting for the vault lock");
+        let data = self.vault().export_data()?;
+        trace!("Vault lock released");
+        Ok(Reply::Vault(data))
+    }
+
+    fn rpc_store_vault(
+        &self,
+        store: message::StoreVault,
+    ) -> Result<Reply, Reply> {
+        self.check_federation()?;
+        trace!("Awaiting for the vault lock");
+        self.vault_mut().import_data(&store.data)?;
+        trace!("Vault lock released");
+        Ok(Reply::Success)
+    }
+
+    /// Vault federation gives full access to the vault data, so it is
+    /// served only to authorized clients
+    fn check_federation(&self) -> Result<(), RuntimeError> {
+        if !self.config.federation || !lock(&self.authenticator).is_enabled() {
+            return Err(RuntimeError::FederationDisabled);
+        }
+        Ok(())
+    }
+
+    /// Locks revocation storage after checking that the account `key_id`,
+    /// which namespace is accessed, is known to the vault
+    fn revocations(
+        &self,
+        key_id: XpubIdentifier,
+    ) -> Result<MutexGuard<Revocations>, RuntimeError> {
+        let revocations = self
+            .revocations
+            .as_ref()
+            .ok_or(RuntimeError::RevocationsDisabled)?;
+        self.vault()
+            .account_by_id(key_id)
+            .ok_or(keymgm::Error::NotFound)?;
+        Ok(lock(revocations))
+    }
+
+    fn rpc_append_revocation(
+        &self,
+        append: message::AppendRevocation,
+    ) -> Result<Reply, Reply> {
+        self.revocations(append.key_id)?
+            .append(append.key_id, append.channel, append.index, append.secret)
+            .map_err(RuntimeError::from)?;
+        Ok(Reply::Success)
+    }
+
+    fn rpc_query_revocation(
+        &self,
+        query: message::QueryRevocation,
+    ) -> Result<Reply, Reply> {
+        let secret = self
+            .revocations(query.key_id)?
+            .get(query.key_id, query.channel, query.index)
+            .map_err(RuntimeError::from)?;
+        Ok(Reply::CommitmentSecret(secret))
+    }
+
+    fn rpc_compact_revocations(
+        &self,
+        compact: message::CompactRevocations,
+    ) -> Result<Reply, Reply> {
+        let removed = self
+            .revocations(compact.key_id)?
+            .compact(compact.key_id)
+            .map_err(RuntimeError::from)?;
+        debug!(
+            "Removed {} records from revocation log of {}",
+            removed, compact.key_id
+        );
+        Ok(Reply::Success)
+    }
+
+    fn rpc_discover(
+        &self,
+        discover: message::Discover,
+    ) -> Result<Reply, Reply> {
+        let mut seckey =
+            self.decryption_key(self.config.node_key, discover.session)?;
+        let source = self
+            .chain_source
+            .as_ref()
+            .ok_or(RuntimeError::NoChainSource)?;
+        trace!("Awaiting for the vault lock");
+        let accounts = self.track(discover.job, "discover", 0, |_| {
+            self.vault_mut().discover(
+                discover.key_id,
+                lock(source).as_ref(),
+                discover.gap_limit,
+                &mut seckey,
+            )
+        })?;
+        trace!("Vault lock released");
+        Ok(Reply::Keylist(accounts))
+    }
+
+    fn rpc_import_descriptors(
+        &self,
+        import: message::ImportDescriptors,
+    ) -> Result<Reply, Reply> {
+        trace!("Awaiting for the vault lock");
+        let accounts = self
+            .vault_mut()
+            .import_descriptors(&import.descriptors, import.collision)?;
+        trace!("Vault lock released");
+        Ok(Reply::Keylist(accounts))
+    }
+
+    fn rpc_import_xpub(
+        &self,
+        import: message::ImportXpub,
+    ) -> Result<Reply, Reply> {
+        trace!("Awaiting for the vault lock");
+        let account = self.vault_mut().import_xpub(
+            import.xpubkey,
+            import.key_source,
+            import.application,
+            import.name,
+            import.details,
+            import.collision,
+        )?;
+        trace!("Vault lock released");
+        Ok(Reply::AccountInfo(account))
+    }
+
+    fn rpc_import_xpriv(
+        &self,
+        import: message::ImportXpriv,
+    ) -> Result<Reply, Reply> {
+        let encryption_key = self.encryption_key()?;
+        trace!("Awaiting for the vault lock");
+        let account = self.vault_mut().import_xpriv(
+            import.xprivkey,
+            import.key_source,
+            import.application,
+            import.name,
+            import.details,
+            import.collision,
+            encryption_key,
+        )?;
+        trace!("Vault lock released");
+        Ok(Reply::AccountInfo(account))
+    }
+
+    fn rpc_derive(&self, derive: message::Derive) -> Result<Reply, Reply> {
+        derivation::validate(&derive.path).map_err(RuntimeError::from)?;
+        let seckey = self.decryption_key(self.config.node_key, derive.session);
+        if derive.sandbox {
+            return self.rpc_derive_sandboxed(derive, seckey?);
+        }
+        // Locked vault still allows deriving watch-only sub-accounts
+        let mut seckey = match seckey {
+            Err(RuntimeError::VaultLocked) => None,
+            result => Some(result?),
+        };
+        trace!("Awaiting for the vault lock");
+        let account = self.vault_mut().derive(
+            derive.from,
+            derive.path,
+            derive.name,
+            Some(derive.details),
+            derive.assets,
+            seckey.as_mut(), //TODO: &mut derive.decryption_key,
+        )?;
+        trace!("Vault lock released");
+        Ok(Reply::AccountInfo(account))
+    }
+
+    fn rpc_derive_sandboxed(
+        &self,
+        derive: message::Derive,
+        mut seckey: SecretKey,
+    ) -> Result<Reply, Reply> {
+        let token = derive
+            .session
+            .ok_or(vault::session::Error::SandboxRequiresSession)
+            .map_err(RuntimeError::from)?;
+        trace!("Awaiting for the vault lock");
+        let sandboxed = self.vault().derive_sandboxed(
+            derive.from,
+            derive.path,
+            derive.name,
+            Some(derive.details),
+            derive.assets,
+            &mut seckey,
+        )?;
+        trace!("Vault lock released");
+        let mut sessions = self.sessions();
+        let sandbox =
+            sessions.sandbox_mut(token).map_err(RuntimeError::from)?;
+        if sandbox.iter().any(|item| {
+            item.keyring == sandboxed.keyring
+                && item.derivation == sandboxed.derivation
+        }) {
+            Err(RuntimeError::from(keymgm::Error::DerivationAlreadyUsed))?
+        }
+        let info = AccountInfo::from(&sandboxed.account);
+        sandbox.push(sandboxed);
+        debug!("Account {} is derived into session sandbox", info.id);
+        Ok(Reply::AccountInfo(info))
+    }
+
+    fn rpc_commit_sandbox(
+        &self,
+        sandbox: message::Sandbox,
+    ) -> Result<Reply, Reply> {
+        // The sandbox is taken before the commit, so concurrent requests
+        // can't commit the same accounts twice
+        let accounts = self
+            .sessions()
+            .take_sandbox(sandbox.session)
+            .map_err(RuntimeError::from)?;
+        trace!("Awaiting for the vault lock");
+        let committed = self.vault_mut().commit_sandbox(accounts.clone());
+        trace!("Vault lock released");
+        match committed {
+            Ok(committed) => Ok(Reply::Keylist(committed)),
+            Err(err) => {
+                // Accounts which can't be committed are kept in the sandbox
+                if let Ok(sandboxed) =
+                    self.sessions().sandbox_mut(sandbox.session)
+                {
+                    sandboxed.extend(accounts);
+                }
+                Err(Reply::from(err))
+            }
+        }
+    }
+
+    fn rpc_discard_sandbox(
+        &self,
+        sandbox: message::Sandbox,
+    ) -> Result<Reply, Reply> {
+        let discarded = self
+            .sessions()
+            .take_sandbox(sandbox.session)
+            .map_err(RuntimeError::from)?;
+        info!("{} sandboxed accounts are discarded", discarded.len());
+        Ok(Reply::Success)
+    }
+
+    fn rpc_delete_keyring(
+        &self,
+        delete: message::Delete,
+    ) -> Result<Reply, Reply> {
+        let mut seckey =
+            self.decryption_key(delete.decryption_key, delete.session)?;
+        trace!("Awaiting for the vault lock");
+        self.vault_mut().delete_keyring(
+            delete.key_id,
+            delete.purge,
+            &mut seckey,
+        )?;
+        trace!("Vault lock released");
+        Ok(Reply::Success)
+    }
+
+    fn rpc_delete_account(
+        &self,
+        delete: message::Delete,
+    ) -> Result<Reply, Reply> {
+        let mut seckey =
+            self.decryption_key(delete.decryption_key, delete.session)?;
+        trace!("Awaiting for the vault lock");
+        self.vault_mut().delete_account(
+            delete.key_id,
+            delete.purge,
+            &mut seckey,
+        )?;
+        trace!("Vault lock released");
+        Ok(Reply::Success)
+    }
+
+    fn rpc_set_lifecycle(
+        &self,
+        lifecycle: message::SetLifecycle,
+    ) -> Result<Reply, Reply> {
+        trace!("Awaiting for the vault lock");
+        let info = self
+            .vault_mut()
+            .set_lifecycle(lifecycle.key_id, lifecycle.state)?;
+        trace!("Vault lock released");
+        Ok(Reply::AccountInfo(info))
+    }
+
+    fn rpc_derive_range(
+        &self,
+        range: message::DeriveRange,
+    ) -> Result<Reply, Reply> {
+        trace!("Awaiting for the vault lock");
+        let keys =
+            self.track(range.job, "derive_range", range.count, |progress| {
+                self.vault().derive_range(
+                    range.key_id,
+                    range.template.as_ref(),
+                    range.internal,
+                    range.start,
+                    range.count,
+                    progress,
+                )
+            })?;
+        trace!("Vault lock released");
+        Ok(Reply::DerivedKeys(keys))
+    }
+
+    fn rpc_set_branches(
+        &self,
+        branches: message::SetBranches,
+    ) -> Result<Reply, Reply> {
+        trace!("Awaiting for the vault lock");
+        let info = self
+            .vault_mut()
+            .set_branches(branches.key_id, branches.branches)?;
+        trace!("Vault lock released");
+        Ok(Reply::AccountInfo(info))
+    }
+
+    fn rpc_set_policy(
+        &self,
+        policy: message::SetPolicy,
+    ) -> Result<Reply, Reply> {
+        trace!("Awaiting for the vault lock");
+        let info = self.vault_mut().set_policy(policy.key_id, policy.policy)?;
+        trace!("Vault lock released");
+        Ok(Reply::AccountInfo(info))
+    }
+
+    fn rpc_set_label(&self, label: message::SetLabel) -> Result<Reply, Reply> {
+        trace!("Awaiting for the vault lock");
+        let info = self.vault_mut().set_label(
+            label.key_id,
+            label.label,
+            label.value,
+        )?;
+        trace!("Vault lock released");
+        Ok(Reply::AccountInfo(info))
+    }
+
+    fn rpc_update_account(
+        &self,
+        update: message::UpdateAccount,
+    ) -> Result<Reply, Reply> {
+        trace!("Awaiting for the vault lock");
+        let info = self.vault_mut().update_account(
+            update.key_id,
+            update.name,
+            update.details,
+            update.assets,
+            update.mode,
+        )?;
+        trace!("Vault lock released");
+        Ok(Reply::AccountInfo(info))
+    }
+
+    fn rpc_create_multisig(
+        &self,
+        create: message::CreateMultisig,
     ) -> Result<Reply, Reply> {
         trace!("Awaiting for the vault lock");
-        let mut seckey = self.config.node_key.clone();
-        let psbt = self.vault.sign_psbt(
-            message.psbt,
-            &mut seckey, //TODO: &mut derive.decryption_key,
+        let group = self.vault_mut().create_multisig(
+            create.name,
+            create.threshold,
+            &create.accounts,
+            create.cosigners,
         )?;
         trace!("Vault lock released");
+        Ok(Reply::Multisig(group))
+    }
+
+    fn rpc_export_multisig(
+        &self,
+        export: message::ExportMultisig,
+    ) -> Result<Reply, Reply> {
+        trace!("Awaiting for the vault lock");
+        let descriptors = self.vault().multisig_descriptors(export.group)?;
+        trace!("Vault lock released");
+        Ok(Reply::Descriptors(descriptors))
+    }
+
+    fn rpc_export_xpub(&self, export: message::Export) -> Result<Reply, Reply> {
+        trace!("Awaiting for the vault lock");
+        let key = self.vault().xpub(export.key_id)?;
+        trace!("Vault lock released");
+        match export.slip132 {
+            Some(prefix) => Ok(Reply::EncodedXPub(
+                prefix.encode(&key).map_err(RuntimeError::from)?,
+            )),
+            None => Ok(Reply::XPub(key)),
+        }
+    }
+
+    fn rpc_approve_export(
+        &self,
+        approve: message::ApproveExport,
+    ) -> Result<Reply, Reply> {
+        if cfg!(not(feature = "export-secrets")) {
+            Err(RuntimeError::SecretExportDisabled)?
+        }
+        self.check_export_policy(approve.key_id)?;
+        let token = lock(&self.approvals).issue(approve.key_id);
+        Ok(Reply::Approval(types::Approval {
+            token,
+            expires_in: APPROVAL_TIMEOUT.as_secs(),
+        }))
+    }
+
+    /// Private keys can be exported only for the keyrings listed in the
+    /// daemon configuration
+    fn check_export_policy(
+        &self,
+        key_id: XpubIdentifier,
+    ) -> Result<(), RuntimeError> {
+        let vault = self.vault();
+        let keyring = vault
+            .keyring_by_account(key_id)
+            .ok_or(keymgm::Error::NotFound)?;
+        if !lock(&self.xpriv_export).contains(&keyring.identifier()) {
+            warn!(
+                "Refusing to export private key {}: export is not enabled \
+                 for keyring {}",
+                key_id,
+                keyring.identifier()
+            );
+            return Err(RuntimeError::ExportNotAllowed(key_id));
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "export-secrets")]
+    fn rpc_export_xpriv(
+        &self,
+        export: message::ExportXpriv,
+    ) -> Result<Reply, Reply> {
+        self.check_export_policy(export.key_id)?;
+        if !lock(&self.approvals).consume(export.approval, export.key_id) {
+            warn!(
+                "Refusing to export private key {} without valid approval",
+                export.key_id
+            );
+            Err(RuntimeError::ExportNotApproved)?
+        }
+        let mut seckey =
+            self.decryption_key(export.decryption_key, export.session)?;
+        trace!("Awaiting for the vault lock");
+        let key = self.vault().xpriv(export.key_id, &mut seckey)?;
+        trace!("Vault lock released");
+        Ok(Reply::XPriv(key))
+    }
+
+    #[cfg(not(feature = "export-secrets"))]
+    fn rpc_export_xpriv(
+        &self,
+        export: message::ExportXpriv,
+    ) -> Result<Reply, Reply> {
+        warn!(
+            "Refusing to export private key {}: daemon is built without \
+             secret export support",
+            export.key_id
+        );
+        Err(RuntimeError::SecretExportDisabled)?
+    }
+
+    fn rpc_export_descriptor(
+        &self,
+        export: message::Export,
+    ) -> Result<Reply, Reply> {
+        trace!("Awaiting for the vault lock");
+        let descriptors = self.vault().descriptors(export.key_id)?;
+        trace!("Vault lock released");
+        Ok(Reply::Descriptors(descriptors))
+    }
+
+    fn rpc_identity_key(
+        &self,
+        message: message::IdentityKey,
+    ) -> Result<Reply, Reply> {
+        let mut seckey =
+            self.decryption_key(message.decryption_key, message.session)?;
+        trace!("Awaiting for the vault lock");
+        let identity = self.vault().identity_key(
+            message.key_id,
+            message.index,
+            &mut seckey,
+        )?;
+        trace!("Vault lock released");
+        Ok(Reply::IdentityKey(identity))
+    }
+
+    /// BIP-85 child secrets are returned in plain text, so they are
+    /// available only when the daemon is built with secret export support
+    #[cfg(feature = "export-secrets")]
+    fn rpc_derive_entropy(
+        &self,
+        derive: message::DeriveEntropy,
+    ) -> Result<Reply, Reply> {
+        let mut seckey =
+            self.decryption_key(derive.decryption_key, derive.session)?;
+        trace!("Awaiting for the vault lock");
+        let secret = self.vault().derive_entropy(
+            derive.key_id,
+            derive.application,
+            derive.index,
+            &mut seckey,
+        )?;
+        trace!("Vault lock released");
+        Ok(Reply::DerivedSecret(secret))
+    }
+
+    #[cfg(not(feature = "export-secrets"))]
+    fn rpc_derive_entropy(
+        &self,
+        derive: message::DeriveEntropy,
+    ) -> Result<Reply, Reply> {
+        warn!(
+            "Refusing to derive BIP-85 secret for {}: daemon is built without \
+             secret export support",
+            derive.key_id
+        );
+        Err(RuntimeError::SecretExportDisabled)?
+    }
+
+    fn rpc_derive_payment_code(
+        &self,
+        derive: message::DerivePaymentCode,
+    ) -> Result<Reply, Reply> {
+        let mut seckey =
+            self.decryption_key(derive.decryption_key, derive.session)?;
+        trace!("Awaiting for the vault lock");
+        let info = self.vault_mut().payment_code(
+            derive.key_id,
+            derive.account,
+            derive.counterparty,
+            derive.contact,
+            derive.start,
+            derive.count,
+            &mut seckey,
+        )?;
+        trace!("Vault lock released");
+        Ok(Reply::PaymentCode(info))
+    }
+
+    fn rpc_derive_ln_keys(
+        &self,
+        derive: message::DeriveLnKeySet,
+    ) -> Result<Reply, Reply> {
+        trace!("Awaiting for the vault lock");
+        let key_set = self.vault().ln_key_set(derive.key_id, derive.channel)?;
+        trace!("Vault lock released");
+        Ok(Reply::LnKeySet(key_set))
+    }
+
+    fn rpc_ecdh(&self, ecdh: message::Ecdh) -> Result<Reply, Reply> {
+        let mut seckey =
+            self.decryption_key(ecdh.decryption_key, ecdh.session)?;
+        trace!("Awaiting for the vault lock");
+        let secret =
+            self.vault().ecdh(ecdh.key_id, ecdh.pubkey, &mut seckey)?;
+        trace!("Vault lock released");
+        Ok(Reply::SharedSecret(secret))
+    }
+
+    /// If the transaction ledger is enabled, PSBTs which got signatures from
+    /// the vault are recorded before the reply, and the signed PSBT is not
+    /// returned if the record can't be written.
+    fn rpc_sign_psbt(
+        &self,
+        message: message::SignPsbt,
+        client: Option<String>,
+    ) -> Result<Reply, Reply> {
+        self.vault().check_psbt_signers(&message.psbt)?;
+        let unsigned = self.ledger.as_ref().map(|_| message.psbt.clone());
+        let mut seckey =
+            self.decryption_key(self.config.node_key, message.session)?;
+        // Signing cache is taken out of the session for the time of signing,
+        // so the sessions are not locked together with the vault. If signing
+        // fails, the cache is dropped and its keys are wiped.
+        let session_cache = match message.session {
+            Some(token) => self
+                .sessions()
+                .take_signing_cache(token)
+                .map_err(RuntimeError::from)?,
+            None => None,
+        };
+        let restore = session_cache.is_some();
+        let mut cache = session_cache.unwrap_or_else(SigningCache::unbounded);
+        let inputs = message.psbt.inputs.len() as u32;
+        trace!("Awaiting for the vault lock");
+        let mut vault = self.vault_mut();
+        let psbt =
+            self.track(message.job, "sign_psbt", inputs, |progress| {
+                let mut taproot_seckey = seckey;
+                let psbt = vault.sign_psbt_cached(
+                    message.psbt,
+                    message.force,
+                    &mut seckey, //TODO: &mut derive.decryption_key,
+                    &mut cache,
+                    progress,
+                )?;
+                vault.sign_psbt_taproot_cached(
+                    psbt,
+                    &mut taproot_seckey,
+                    &mut cache,
+                    progress,
+                )
+            })?;
+        // Ledger is written under the vault lock, so the entries follow the
+        // order in which PSBTs are signed
+        if let (Some(ledger), Some(unsigned)) = (&self.ledger, unsigned) {
+            if psbt != unsigned {
+                let vault = &*vault;
+                let entry =
+                    ledger::entry(&psbt, unix_time(), client, |fingerprint| {
+                        vault.keyring_by_fingerprint(fingerprint).is_some()
+                    });
+                ledger
+                    .record(&entry)
+                    .map_err(|err| RuntimeError::Ledger(err.to_string()))?;
+            }
+        }
+        drop(vault);
+        trace!("Vault lock released");
+        if let (true, Some(token)) = (restore, message.session) {
+            self.sessions().restore_signing_cache(token, cache);
+        }
         Ok(Reply::Psbt(psbt))
     }
 
-    fn rpc_sign_key(
-        &mut self,
-        mut message: message::SignKey,
+    fn rpc_export_ledger(
+        &self,
+        message: message::ExportLedger,
+    ) -> Result<Reply, Reply> {
+        let ledger =
+            self.ledger.as_ref().ok_or(RuntimeError::LedgerDisabled)?;
+        let entries = ledger
+            .entries(message.since)
+            .map_err(|err| RuntimeError::Ledger(err.to_string()))?;
+        Ok(Reply::Ledger(entries))
+    }
+
+    fn rpc_finalize_psbt(
+        &self,
+        message: message::FinalizePsbt,
+    ) -> Result<Reply, Reply> {
+        let mut psbt = message.psbt;
+        let pending = finalizer::finalize(&mut psbt);
+        debug!(
+            "PSBT is finalized; {} of {} inputs remain non-final",
+            pending,
+            psbt.inputs.len()
+        );
+        if !message.extract {
+            return Ok(Reply::Psbt(psbt));
+        }
+        let tx = finalizer::extract(psbt).map_err(RuntimeError::from)?;
+        Ok(Reply::Transaction(tx))
+    }
+
+    fn rpc_compose_psbt(
+        &self,
+        message: message::ComposePsbt,
+    ) -> Result<Reply, Reply> {
+        trace!("Awaiting for the vault lock");
+        let psbt = self.vault().compose_psbt(
+            &message.inputs,
+            &message.outputs,
+            message.lock_time,
+        )?;
+        trace!("Vault lock released");
+        Ok(Reply::Psbt(psbt))
+    }
+
+    fn rpc_analyze_psbt(
+        &self,
+        message: message::AnalyzePsbt,
     ) -> Result<Reply, Reply> {
         trace!("Awaiting for the vault lock");
+        let analysis = self.vault().analyze_psbt(&message.psbt);
+        trace!("Vault lock released");
+        Ok(Reply::PsbtAnalysis(analysis))
+    }
+
+    fn rpc_combine_psbt(
+        &self,
+        message: message::CombinePsbt,
+    ) -> Result<Reply, Reply> {
+        let count = message.psbts.len();
+        let psbt = psbt::combine(message.psbts).map_err(RuntimeError::from)?;
+        debug!(
+            "{} PSBTs combined for transaction {}",
+            count,
+            psbt.global.unsigned_tx.txid()
+        );
+        Ok(Reply::Psbt(psbt))
+    }
+
+    fn rpc_sign_key(&self, message: message::SignKey) -> Result<Reply, Reply> {
+        self.vault().signing_account(message.key_id)?;
+        let mut seckey =
+            self.decryption_key(message.decryption_key, message.session)?;
+        trace!("Awaiting for the vault lock");
         trace!("Lock acquired");
-        let signature = self
-            .vault
-            .sign_key(message.key_id, &mut message.decryption_key)?;
+        let signature = self.vault().sign_key(message.key_id, &mut seckey)?;
         trace!("Vault lock released");
         Ok(Reply::Signature(signature))
     }
 
     fn rpc_sign_data(
-        &mut self,
-        mut message: message::SignData,
+        &self,
+        message: message::SignData,
     ) -> Result<Reply, Reply> {
+        self.vault().signing_account(message.key_id)?;
+        let mut seckey =
+            self.decryption_key(message.decryption_key, message.session)?;
         trace!("Awaiting for the vault lock");
         trace!("Lock acquired");
-        let signature = self.vault.sign_data(
+        let signature = self.vault().sign_data(
+            message.key_id,
+            &message.data,
+            &mut seckey,
+        )?;
+        trace!("Vault lock released");
+        Ok(Reply::Signature(signature))
+    }
+
+    fn rpc_sign_message(
+        &self,
+        message: message::SignMessage,
+    ) -> Result<Reply, Reply> {
+        self.vault().signing_account(message.key_id)?;
+        let mut seckey =
+            self.decryption_key(message.decryption_key, message.session)?;
+        trace!("Awaiting for the vault lock");
+        let signature = self.vault().sign_message(
+            message.key_id,
+            &message.message,
+            &mut seckey,
+        )?;
+        trace!("Vault lock released");
+        Ok(Reply::MessageSignature(signature))
+    }
+
+    fn rpc_sign_identity(
+        &self,
+        message: message::SignIdentity,
+    ) -> Result<Reply, Reply> {
+        self.vault().signing_account(message.key_id)?;
+        let mut seckey =
+            self.decryption_key(message.decryption_key, message.session)?;
+        trace!("Awaiting for the vault lock");
+        let signature = self.vault().sign_identity(
+            message.key_id,
+            message.index,
+            message.digest,
+            &mut seckey,
+        )?;
+        trace!("Vault lock released");
+        Ok(Reply::IdentitySignature(signature))
+    }
+
+    fn rpc_sign_invoice(
+        &self,
+        message: message::SignInvoice,
+    ) -> Result<Reply, Reply> {
+        self.vault().signing_account(message.key_id)?;
+        let mut seckey =
+            self.decryption_key(message.decryption_key, message.session)?;
+        trace!("Awaiting for the vault lock");
+        let signature = self.vault().sign_invoice(
             message.key_id,
+            &message.hrp,
             &message.data,
-            &mut message.decryption_key,
+            &mut seckey,
+        )?;
+        trace!("Vault lock released");
+        Ok(Reply::InvoiceSignature(signature))
+    }
+
+    fn rpc_sign_announcement(
+        &self,
+        message: message::SignChannelAnnouncement,
+    ) -> Result<Reply, Reply> {
+        self.vault().signing_account(message.key_id)?;
+        let mut seckey =
+            self.decryption_key(message.decryption_key, message.session)?;
+        trace!("Awaiting for the vault lock");
+        let signatures = self.vault().sign_channel_announcement(
+            message.key_id,
+            message.channel,
+            &message.announcement,
+            &mut seckey,
+        )?;
+        trace!("Vault lock released");
+        Ok(Reply::AnnouncementSignatures(signatures))
+    }
+
+    fn rpc_sign_gossip(
+        &self,
+        message: message::SignGossip,
+    ) -> Result<Reply, Reply> {
+        self.vault().signing_account(message.key_id)?;
+        let mut seckey =
+            self.decryption_key(message.decryption_key, message.session)?;
+        trace!("Awaiting for the vault lock");
+        let signature = self.vault().sign_gossip(
+            message.key_id,
+            &message.message,
+            &mut seckey,
         )?;
         trace!("Vault lock released");
         Ok(Reply::Signature(signature))
     }
+
+    fn rpc_musig_start(
+        &self,
+        start: message::MuSigStartSession,
+    ) -> Result<Reply, Reply> {
+        trace!("Awaiting for the vault lock");
+        let participant = self.vault().musig_key(start.key_id)?;
+        trace!("Vault lock released");
+        let session = lock(&self.musig)
+            .start(
+                start.key_id,
+                participant,
+                &start.participants,
+                start.message,
+                start.taproot,
+            )
+            .map_err(RuntimeError::from)?;
+        Ok(Reply::MuSigSession(session))
+    }
+
+    fn rpc_musig_nonce_exchange(
+        &self,
+        exchange: message::MuSigNonceExchange,
+    ) -> Result<Reply, Reply> {
+        let aggnonce = lock(&self.musig)
+            .exchange(exchange.id, &exchange.nonces)
+            .map_err(RuntimeError::from)?;
+        Ok(Reply::MuSigNonce(aggnonce))
+    }
+
+    /// The session is closed before signing, so its secret nonce is never
+    /// used twice, even if the signing fails
+    fn rpc_musig_sign(
+        &self,
+        sign: message::MuSigPartialSign,
+    ) -> Result<Reply, Reply> {
+        let mut partial_sigs = sign
+            .partial_sigs
+            .iter()
+            .map(|sig| {
+                let mut partial = [0u8; musig::PARTIAL_SIG_LEN];
+                if sig.len() != musig::PARTIAL_SIG_LEN {
+                    return Err(musig::Error::InvalidPartialSignature(
+                        musig::PARTIAL_SIG_LEN,
+                    ));
+                }
+                partial.copy_from_slice(sig);
+                Ok(partial)
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(RuntimeError::from)?;
+        let mut seckey =
+            self.decryption_key(sign.decryption_key, sign.session)?;
+        let signing = lock(&self.musig)
+            .take(sign.id)
+            .map_err(RuntimeError::from)?;
+        trace!("Awaiting for the vault lock");
+        let partial = self.vault().musig_partial_sign(
+            signing.key_id,
+            signing.secnonce,
+            &signing.context,
+            &signing.aggnonce,
+            signing.message,
+            &mut seckey,
+        )?;
+        trace!("Vault lock released");
+
+        let signature = if partial_sigs.is_empty() {
+            None
+        } else {
+            partial_sigs.push(partial);
+            let signature = musig::aggregate(
+                &signing.context,
+                &signing.aggnonce,
+                signing.message,
+                &partial_sigs,
+            )
+            .map_err(RuntimeError::from)?;
+            Some(signature[..].to_vec())
+        };
+        Ok(Reply::MuSigSignature(types::MuSigSignature {
+            partial: partial.to_vec(),
+            signature,
+        }))
+    }
 }
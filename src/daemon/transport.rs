@@ -0,0 +1,209 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use bitcoin::secp256k1::rand::thread_rng;
+use bitcoin::secp256k1::SecretKey;
+use internet2::session::noise::{HandshakeState, IHandshakeState};
+use internet2::{Decrypt, Encrypt, NoiseTranscoder};
+
+use crate::error::RuntimeError;
+use crate::rpc::transport::ChannelId;
+use crate::vault::secret::wipe_key;
+
+/// Period of inactivity after which encrypted channel is closed
+pub const CHANNEL_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Policy for the encryption of RPC requests and replies
+#[derive(
+    Clone, Copy, PartialEq, Eq, Hash, Debug, Display, Serialize, Deserialize,
+)]
+#[serde(crate = "serde_crate", rename_all = "snake_case")]
+#[display(Debug)]
+pub enum TransportEncryption {
+    /// Both plaintext and encrypted requests are accepted
+    Optional,
+
+    /// Only requests sent over encrypted channels are accepted
+    Required,
+
+    /// Encrypted channels are not supported
+    Disabled,
+}
+
+impl Default for TransportEncryption {
+    fn default() -> Self {
+        TransportEncryption::Optional
+    }
+}
+
+/// Policy for the requests sealed to the daemon node id, which payloads are
+/// encrypted end-to-end independently of the transport encryption; see
+/// [`crate::rpc::sealed`]
+#[derive(
+    Clone, Copy, PartialEq, Eq, Hash, Debug, Display, Serialize, Deserialize,
+)]
+#[serde(crate = "serde_crate", rename_all = "snake_case")]
+#[display(Debug)]
+pub enum PayloadEncryption {
+    /// Both plaintext and sealed requests are accepted
+    Optional,
+
+    /// Requests carrying secrets are accepted only if sealed
+    Required,
+
+    /// Sealed requests are rejected
+    Disabled,
+}
+
+impl Default for PayloadEncryption {
+    fn default() -> Self {
+        PayloadEncryption::Optional
+    }
+}
+
+/// Result of processing a frame received over encrypted channel
+pub enum Received {
+    /// Handshake act which must be sent back to the client
+    Handshake(Vec<u8>),
+
+    /// Decrypted request data
+    Request(Vec<u8>),
+}
+
+enum ChannelState {
+    Handshake(HandshakeState),
+    Established(NoiseTranscoder),
+}
+
+struct Channel {
+    state: ChannelState,
+    used: Instant,
+}
+
+/// Set of the encrypted channels established with the clients
+pub struct Channels {
+    node_key: SecretKey,
+    channels: HashMap<ChannelId, Channel>,
+}
+
+impl Channels {
+    /// Constructs empty set of channels, using `node_key` as the daemon
+    /// static key in channel handshakes
+    pub fn with(node_key: SecretKey) -> Self {
+        Self {
+            node_key,
+            channels: Default::default(),
+        }
+    }
+
+    /// Processes `payload` of the frame received over the `channel`. Frames
+    /// with unknown channel ids start new handshake.
+    pub fn receive(
+        &mut self,
+        channel: ChannelId,
+        payload: &[u8],
+    ) -> Result<Received, RuntimeError> {
+        self.expire();
+        let (state, used) = match self.channels.remove(&channel) {
+            Some(Channel { state, used }) => (state, used),
+            None => {
+                trace!("Starting handshake for channel {}", channel);
+                let ephemeral_key = SecretKey::new(&mut thread_rng());
+                let handshake = HandshakeState::new_responder(
+                    &self.node_key,
+                    &ephemeral_key,
+                );
+                (ChannelState::Handshake(handshake), Instant::now())
+            }
+        };
+        let (received, state) = match state {
+            ChannelState::Handshake(handshake) => {
+                let (act, handshake) =
+                    handshake.next(payload).map_err(|err| {
+                        warn!("Handshake on channel {} failed", channel);
+                        RuntimeError::Handshake(err.to_string())
+                    })?;
+                let state = match handshake {
+                    HandshakeState::Complete(Some((transcoder, remote))) => {
+                        debug!(
+                            "Encrypted channel {} is established with {}",
+                            channel, remote
+                        );
+                        ChannelState::Established(transcoder)
+                    }
+                    handshake => ChannelState::Handshake(handshake),
+                };
+                (Received::Handshake(act.unwrap_or_default()), state)
+            }
+            ChannelState::Established(mut transcoder) => {
+                match transcoder.decrypt(payload) {
+                    Ok(data) => (
+                        Received::Request(data),
+                        ChannelState::Established(transcoder),
+                    ),
+                    Err(_) => {
+                        // Malformed frame must not close the channel, which
+                        // otherwise can be torn down by anyone knowing its id
+                        warn!("Unable to decrypt frame on channel {}", channel);
+                        self.channels.insert(
+                            channel,
+                            Channel {
+                                state: ChannelState::Established(transcoder),
+                                used,
+                            },
+                        );
+                        return Err(RuntimeError::Decryption);
+                    }
+                }
+            }
+        };
+        self.channels.insert(
+            channel,
+            Channel {
+                state,
+                used: Instant::now(),
+            },
+        );
+        Ok(received)
+    }
+
+    /// Encrypts reply `data` for the established `channel`
+    pub fn encrypt(
+        &mut self,
+        channel: ChannelId,
+        data: &[u8],
+    ) -> Result<Vec<u8>, RuntimeError> {
+        match self.channels.get_mut(&channel) {
+            Some(Channel {
+                state: ChannelState::Established(transcoder),
+                ..
+            }) => Ok(transcoder.encrypt(data)),
+            _ => Err(RuntimeError::UnknownChannel),
+        }
+    }
+
+    /// Closes all channels and wipes the node key copy from the memory; the
+    /// set can't be used for new handshakes afterwards
+    pub fn wipe(&mut self) {
+        self.channels.clear();
+        wipe_key(&mut self.node_key);
+    }
+
+    fn expire(&mut self) {
+        self.channels
+            .retain(|_, channel| channel.used.elapsed() < CHANNEL_TIMEOUT);
+    }
+}
@@ -0,0 +1,350 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Typed construction and validation of BIP-32 derivation paths.
+//!
+//! Paths following BIP-43 layout `m/purpose'/coin_type'/account'/change/index`
+//! are built level by level with [`PathBuilder`], so hardened markers can't
+//! be misplaced. Paths given as strings are read with [`parse`], which accepts
+//! both `'` and `h` hardened markers and an optional `m/` prefix, and are
+//! checked with [`validate`] against the layout of the standard purposes:
+//!
+//! | Purpose | Levels                                                 |
+//! |---------|--------------------------------------------------------|
+//! | 44, 49, 84, 86 | `purpose'/coin_type'/account'/change/index`     |
+//! | 48      | `purpose'/coin_type'/account'/script_type'/change/index` |
+//!
+//! Paths with other purposes are checked for the BIP-32 depth limit only.
+//! Parsed and built paths are displayed in the canonical form with `'`
+//! markers, which is read back by [`parse`] into the same path.
+
+use std::str::FromStr;
+
+use bitcoin::util::bip32::{self, ChildNumber, DerivationPath};
+use bitcoin::Network;
+
+/// Maximal depth of the derivation path, limited by the size of the depth
+/// field of BIP-32 extended keys
+pub const MAX_DEPTH: usize = 255;
+
+/// Purposes (BIP-43) of the standard derivation path layouts
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Display)]
+#[repr(u32)]
+pub enum Purpose {
+    /// Single-key P2PKH accounts (BIP-44)
+    #[display("BIP-44")]
+    Bip44 = 44,
+
+    /// Multi-signature accounts (BIP-48)
+    #[display("BIP-48")]
+    Bip48 = 48,
+
+    /// Single-key P2WPKH-in-P2SH accounts (BIP-49)
+    #[display("BIP-49")]
+    Bip49 = 49,
+
+    /// Single-key P2WPKH accounts (BIP-84)
+    #[display("BIP-84")]
+    Bip84 = 84,
+
+    /// Single-key P2TR accounts (BIP-86)
+    #[display("BIP-86")]
+    Bip86 = 86,
+}
+
+impl Purpose {
+    /// Returns standard purpose with a given index, if any
+    pub fn from_index(index: u32) -> Option<Purpose> {
+        Some(match index {
+            44 => Purpose::Bip44,
+            48 => Purpose::Bip48,
+            49 => Purpose::Bip49,
+            84 => Purpose::Bip84,
+            86 => Purpose::Bip86,
+            _ => return None,
+        })
+    }
+
+    /// Index of the purpose level
+    pub fn index(self) -> u32 {
+        self as u32
+    }
+
+    /// Levels of the derivation path following the purpose level
+    pub fn levels(self) -> &'static [Level] {
+        match self {
+            Purpose::Bip48 => &[
+                Level::CoinType,
+                Level::Account,
+                Level::ScriptType,
+                Level::Change,
+                Level::Index,
+            ],
+            _ => {
+                &[Level::CoinType, Level::Account, Level::Change, Level::Index]
+            }
+        }
+    }
+}
+
+/// Levels of the standard derivation paths
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Display)]
+pub enum Level {
+    #[display("purpose")]
+    Purpose,
+
+    #[display("coin type")]
+    CoinType,
+
+    #[display("account")]
+    Account,
+
+    #[display("script type")]
+    ScriptType,
+
+    #[display("change")]
+    Change,
+
+    #[display("address index")]
+    Index,
+}
+
+impl Level {
+    /// Returns whether the level index must be hardened
+    pub fn is_hardened(self) -> bool {
+        !matches!(self, Level::Change | Level::Index)
+    }
+}
+
+/// Errors in the derivation paths
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum Error {
+    /// Invalid derivation path: {0}
+    #[from]
+    Bip32(bip32::Error),
+
+    /// Index {0} is too large; indexes must be less than 2^31, and hardened
+    /// ones are marked with `'` or `h` instead
+    IndexRange(u32),
+
+    /// Derivation path is {0} levels deep, exceeding the limit of 255 levels
+    Depth(usize),
+
+    /// {0} path can't be deeper than {1} levels
+    PurposeDepth(Purpose, usize),
+
+    /// {1} level of {0} path must be hardened; mark it with `'`
+    NotHardened(Purpose, Level),
+
+    /// {1} level of {0} path must not be hardened
+    Hardened(Purpose, Level),
+
+    /// {0} path has change level {1}; only 0 (receive) and 1 (change) are
+    /// allowed
+    Change(Purpose, u32),
+
+    /// BIP-48 path has script type {0}'; only 1' (nested SegWit) and 2'
+    /// (native SegWit) are allowed
+    ScriptType(u32),
+}
+
+/// Builder of derivation paths following BIP-43 layout. Levels are added in
+/// the order they are called; index range errors are reported by
+/// [`PathBuilder::build`], which also validates the resulting path.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct PathBuilder {
+    steps: Vec<ChildNumber>,
+    invalid: Option<u32>,
+}
+
+impl PathBuilder {
+    /// Starts path at the master key
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts account path `m/purpose'/coin_type'/account'` with the coin
+    /// type of the `network`; BIP-48 paths have to be completed with
+    /// [`PathBuilder::script_type`]
+    pub fn account_path(
+        purpose: Purpose,
+        network: Network,
+        account: u32,
+    ) -> Self {
+        Self::new()
+            .purpose(purpose)
+            .coin_for(network)
+            .account(account)
+    }
+
+    /// Adds hardened `index`
+    pub fn hardened(mut self, index: u32) -> Self {
+        self.push(ChildNumber::from_hardened_idx(index), index);
+        self
+    }
+
+    /// Adds normal (non-hardened) `index`
+    pub fn normal(mut self, index: u32) -> Self {
+        self.push(ChildNumber::from_normal_idx(index), index);
+        self
+    }
+
+    /// Adds hardened purpose level
+    pub fn purpose(self, purpose: Purpose) -> Self {
+        self.hardened(purpose.index())
+    }
+
+    /// Adds hardened coin type level
+    pub fn coin(self, coin_type: u32) -> Self {
+        self.hardened(coin_type)
+    }
+
+    /// Adds hardened coin type level used for the `network`: 0 for mainnet
+    /// and 1 for all test networks
+    pub fn coin_for(self, network: Network) -> Self {
+        self.coin(match network {
+            Network::Bitcoin => 0,
+            _ => 1,
+        })
+    }
+
+    /// Adds hardened account level
+    pub fn account(self, account: u32) -> Self {
+        self.hardened(account)
+    }
+
+    /// Adds hardened script type level of BIP-48 paths
+    pub fn script_type(self, script_type: u32) -> Self {
+        self.hardened(script_type)
+    }
+
+    /// Adds change level: 1 for change addresses and 0 for receive ones
+    pub fn change(self, change: bool) -> Self {
+        self.normal(change as u32)
+    }
+
+    /// Adds address index level
+    pub fn index(self, index: u32) -> Self {
+        self.normal(index)
+    }
+
+    /// Returns the path, checking it with [`validate`]
+    pub fn build(self) -> Result<DerivationPath, Error> {
+        if let Some(index) = self.invalid {
+            return Err(Error::IndexRange(index));
+        }
+        let path = DerivationPath::from(self.steps);
+        validate(&path)?;
+        Ok(path)
+    }
+
+    fn push(&mut self, child: Result<ChildNumber, bip32::Error>, index: u32) {
+        match child {
+            Ok(child) => self.steps.push(child),
+            Err(_) => {
+                self.invalid.get_or_insert(index);
+            }
+        }
+    }
+}
+
+/// Checks the path against the depth limit and, for paths starting with one
+/// of the standard purposes, against the purpose layout
+pub fn validate(path: &DerivationPath) -> Result<(), Error> {
+    let steps = path.as_ref();
+    if steps.len() > MAX_DEPTH {
+        return Err(Error::Depth(steps.len()));
+    }
+    let (purpose, first, rest) = match steps.split_first() {
+        Some((first, rest)) => match Purpose::from_index(index(*first)) {
+            Some(purpose) => (purpose, first, rest),
+            None => return Ok(()),
+        },
+        None => return Ok(()),
+    };
+    if !first.is_hardened() {
+        return Err(Error::NotHardened(purpose, Level::Purpose));
+    }
+    let levels = purpose.levels();
+    if rest.len() > levels.len() {
+        return Err(Error::PurposeDepth(purpose, levels.len() + 1));
+    }
+    for (child, level) in rest.iter().zip(levels) {
+        match (child.is_hardened(), level.is_hardened()) {
+            (false, true) => return Err(Error::NotHardened(purpose, *level)),
+            (true, false) => return Err(Error::Hardened(purpose, *level)),
+            _ => {}
+        }
+        match (level, index(*child)) {
+            (Level::Change, change) if change > 1 => {
+                return Err(Error::Change(purpose, change))
+            }
+            (Level::ScriptType, script_type)
+                if script_type != 1 && script_type != 2 =>
+            {
+                return Err(Error::ScriptType(script_type))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Parses derivation path written with or without `m/` prefix, using `'` or
+/// `h` hardened markers, and validates it with [`validate`]
+pub fn parse(s: &str) -> Result<DerivationPath, Error> {
+    let s = s.trim();
+    let s = s
+        .strip_prefix('m')
+        .or_else(|| s.strip_prefix('M'))
+        .unwrap_or(s)
+        .trim_matches('/');
+    let path = if s.is_empty() {
+        DerivationPath::master()
+    } else {
+        let steps = s
+            .split('/')
+            .map(|step| {
+                let step = step.trim();
+                let (index, hardened) = match step
+                    .strip_suffix('\'')
+                    .or_else(|| step.strip_suffix('h'))
+                    .or_else(|| step.strip_suffix('H'))
+                {
+                    Some(index) => (index, true),
+                    None => (step, false),
+                };
+                let index = u32::from_str(index)
+                    .map_err(|_| bip32::Error::InvalidChildNumberFormat)?;
+                if hardened {
+                    ChildNumber::from_hardened_idx(index)
+                } else {
+                    ChildNumber::from_normal_idx(index)
+                }
+                .map_err(|_| Error::IndexRange(index))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        DerivationPath::from(steps)
+    };
+    validate(&path)?;
+    Ok(path)
+}
+
+fn index(child: ChildNumber) -> u32 {
+    match child {
+        ChildNumber::Normal { index } | ChildNumber::Hardened { index } => {
+            index
+        }
+    }
+}
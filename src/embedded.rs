@@ -0,0 +1,242 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Vault embedded into an application without the daemon. Applications like
+//! mobile wallets use [`Embedded`] handle instead of the RPC client, so no
+//! sockets are opened. The handle follows the daemon rules: a vault
+//! encrypted with a passphrase must be unlocked before private keys are
+//! used, a read-only vault refuses modifications and signing policies and
+//! key lifecycles are enforced by the vault itself. Requests do not carry
+//! auth codes, since there is no transport to authorize.
+
+use std::collections::HashSet;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use bitcoin::util::bip32::DerivationPath;
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::XpubIdentifier;
+use lnpbp::chain::{AssetId, Chain};
+use slip132::KeyApplication;
+
+use crate::daemon::Config;
+use crate::derivation;
+use crate::error::{BootstrapError, RuntimeError};
+use crate::rpc::types::{AccountInfo, Session, SessionToken};
+use crate::vault::{Backups, Encryption, Journal, Sessions, SigningCache};
+use crate::Vault;
+
+/// Handle of the vault opened by the application
+pub struct Embedded {
+    config: Config,
+    vault: Mutex<Vault>,
+    sessions: Mutex<Sessions>,
+    /// Public key used for the vault encryption, known after the first
+    /// unlock if the vault is encrypted with a passphrase
+    vault_pubkey: Mutex<Option<PublicKey>>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<T> {
+    mutex.lock().expect("embedded vault lock is poisoned")
+}
+
+impl Embedded {
+    /// Opens the vault with the storage driver, encryption, backups and
+    /// journal given in the daemon `config`
+    pub fn open(config: Config) -> Result<Self, BootstrapError> {
+        debug!("Opening embedded vault {}", config.vault);
+        let mut vault = Vault::with(&config.vault)?;
+        if config.read_only {
+            info!("Vault is opened read-only");
+        } else {
+            if let Some(ref backup_config) = config.backup {
+                vault.enable_backups(Backups::with(
+                    backup_config,
+                    config.node_id(),
+                )?);
+            }
+            if let Some(ref path) = config.journal {
+                vault.enable_journal(Journal::open(path)?)?;
+            }
+        }
+        let mut sessions = Sessions::with(Duration::from_secs(
+            config.encryption.unlock_timeout(),
+        ));
+        sessions.enable_signing_cache(config.signing_cache);
+        Ok(Self {
+            config,
+            vault: Mutex::new(vault),
+            sessions: Mutex::new(sessions),
+            vault_pubkey: Mutex::new(None),
+        })
+    }
+
+    /// Unlocks the vault for a session. The `passphrase` is required for
+    /// the vault encrypted with a passphrase; other vaults are decrypted
+    /// with the node key from the configuration.
+    pub fn unlock(&self, passphrase: &str) -> Result<Session, RuntimeError> {
+        let key = match self.config.encryption {
+            Encryption::Passphrase { .. } => {
+                let policy = &self.config.passphrase;
+                policy.check(passphrase)?;
+                policy.kdf.derive_key(passphrase, self.config.node_id())?
+            }
+            _ => self.config.node_key,
+        };
+        let mut check_key = key;
+        lock(&self.vault).verify_decryption_key(&mut check_key)?;
+        *lock(&self.vault_pubkey) =
+            Some(PublicKey::from_secret_key(&crate::SECP256K1, &key));
+        let mut sessions = lock(&self.sessions);
+        let token = sessions.unlock(key);
+        info!("Vault is unlocked");
+        Ok(Session {
+            token,
+            expires_in: sessions.timeout().as_secs(),
+        })
+    }
+
+    /// Locks the unlocked vault `session`, wiping its decryption key
+    pub fn lock(&self, session: SessionToken) -> Result<(), RuntimeError> {
+        lock(&self.sessions).lock(session)?;
+        info!("Vault session is locked");
+        Ok(())
+    }
+
+    /// Lists accounts of the vault
+    pub fn list(&self) -> Result<Vec<AccountInfo>, RuntimeError> {
+        lock(&self.vault).list()
+    }
+
+    /// Creates new keyring from a random seed, returning information about
+    /// its master account
+    pub fn new_seed(
+        &self,
+        name: impl ToString,
+        description: Option<impl ToString>,
+        chain: &Chain,
+        application: KeyApplication,
+    ) -> Result<AccountInfo, RuntimeError> {
+        self.check_writable()?;
+        let encryption_key = self.encryption_key()?;
+        lock(&self.vault).seed(
+            name,
+            description,
+            chain,
+            application,
+            encryption_key,
+        )
+    }
+
+    /// Derives sub-account with the `path` from the account `from`. When the
+    /// vault is locked the sub-account is derived as a watch-only one, which
+    /// requires all derivation steps below the closest account to be normal.
+    pub fn derive(
+        &self,
+        from: XpubIdentifier,
+        path: DerivationPath,
+        name: impl ToString,
+        details: Option<impl ToString>,
+        assets: HashSet<AssetId>,
+        session: Option<SessionToken>,
+    ) -> Result<AccountInfo, RuntimeError> {
+        self.check_writable()?;
+        derivation::validate(&path)?;
+        let mut seckey = match self.decryption_key(session) {
+            Err(RuntimeError::VaultLocked) => None,
+            result => Some(result?),
+        };
+        lock(&self.vault).derive(
+            from,
+            path,
+            name,
+            details,
+            assets,
+            seckey.as_mut(),
+        )
+    }
+
+    /// Signs all PSBT inputs which can be signed with the vault keys,
+    /// including P2TR key path spends
+    pub fn sign_psbt(
+        &self,
+        psbt: PartiallySignedTransaction,
+        session: Option<SessionToken>,
+    ) -> Result<PartiallySignedTransaction, RuntimeError> {
+        self.check_writable()?;
+        let mut vault = lock(&self.vault);
+        vault.check_psbt_signers(&psbt)?;
+        let mut seckey = self.decryption_key(session)?;
+        let mut taproot_seckey = seckey;
+        let session_cache = match session {
+            Some(token) => lock(&self.sessions).take_signing_cache(token)?,
+            None => None,
+        };
+        let restore = session_cache.is_some();
+        let mut cache = session_cache.unwrap_or_else(SigningCache::unbounded);
+        let psbt = vault.sign_psbt_cached(
+            psbt,
+            false,
+            &mut seckey,
+            &mut cache,
+            &mut || (),
+        )?;
+        let psbt = vault.sign_psbt_taproot_cached(
+            psbt,
+            &mut taproot_seckey,
+            &mut cache,
+            &mut || (),
+        )?;
+        if let (true, Some(token)) = (restore, session) {
+            lock(&self.sessions).restore_signing_cache(token, cache);
+        }
+        Ok(psbt)
+    }
+
+    /// Read-only vault refuses modifications, including PSBT signing, which
+    /// updates the rate limits of the signing policies
+    fn check_writable(&self) -> Result<(), RuntimeError> {
+        if self.config.read_only {
+            return Err(RuntimeError::ReadOnly);
+        }
+        Ok(())
+    }
+
+    /// Returns the key decrypting the vault: the key of the unlocked
+    /// `session` or, for the vault not encrypted with a passphrase, the node
+    /// key
+    fn decryption_key(
+        &self,
+        session: Option<SessionToken>,
+    ) -> Result<SecretKey, RuntimeError> {
+        match (session, &self.config.encryption) {
+            (Some(token), _) => Ok(lock(&self.sessions).decryption_key(token)?),
+            (None, Encryption::Passphrase { .. }) => {
+                Err(RuntimeError::VaultLocked)
+            }
+            (None, _) => Ok(self.config.node_key),
+        }
+    }
+
+    /// Returns public key used to encrypt newly created keyrings, which for
+    /// the vault encrypted with a passphrase is known after the first unlock
+    fn encryption_key(&self) -> Result<PublicKey, RuntimeError> {
+        match self.config.encryption {
+            Encryption::Passphrase { .. } => {
+                lock(&self.vault_pubkey).ok_or(RuntimeError::VaultLocked)
+            }
+            _ => Ok(self.config.node_id()),
+        }
+    }
+}
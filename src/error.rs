@@ -15,62 +15,348 @@
 use settings::ConfigError;
 use std::io;
 
+use crate::derivation;
+#[cfg(feature = "_vault")]
+use crate::{chain, vault};
 #[cfg(any(feature = "server", feature = "embedded"))]
-use crate::vault;
+use crate::{daemon, passphrase};
 
 #[cfg(any(feature = "shell", feature = "embedded"))]
 #[derive(Debug, Display, Error, From)]
-#[display(Debug)]
+#[display(doc_comments)]
 pub enum ConfigInitError {
+    /// I/O error: {0}
     #[from]
     IoError(io::Error),
 
+    /// Error serializing configuration: {0}
     #[from]
     Toml(toml::ser::Error),
 }
 
 #[derive(Debug, Display, Error, From)]
-#[display(Debug)]
+#[display(doc_comments)]
 pub enum BootstrapError {
+    /// Configuration error: {0}
     #[cfg(any(feature = "shell", feature = "server", feature = "embedded"))]
     #[from]
     ConfigError(ConfigError),
 
+    /// Tor is not yet supported
     TorNotYetSupported,
 
+    /// I/O error: {0}
     #[from]
     IoError(io::Error),
 
+    /// Error parsing arguments: {0}
     #[from]
     ArgParseError(String),
 
+    /// RPC transport error: {0}
     #[from]
     TransportError(internet2::transport::Error),
 
+    /// ZMQ socket error: {0}
     #[cfg(any(feature = "server", feature = "embedded"))]
     #[from]
+    Zmq(zmq::Error),
+
+    /// Vault storage error: {0}
+    #[cfg(feature = "_vault")]
+    #[from]
     VaultError(vault::driver::Error),
 
+    /// Vault file {0} is used by another process ({1}); please make sure
+    /// that other keyringd instance is not running with the same vault
+    #[cfg(feature = "_vault")]
+    VaultLocked(String, String),
+
+    /// Vault integrity check failed: {0}
+    #[cfg(feature = "_vault")]
+    VaultIntegrity(String),
+
+    /// Vault journal error: {0}
+    #[cfg(feature = "_vault")]
+    #[from]
+    Journal(vault::journal::Error),
+
+    /// Blockchain data source error: {0}
+    #[cfg(feature = "_vault")]
+    #[from]
+    ChainSource(chain::Error),
+
+    /// Unable to establish encrypted RPC channel: {0}
+    #[cfg(feature = "_rpc")]
+    #[from]
+    Channel(crate::rpc::Error),
+
+    /// Unable to negotiate RPC protocol with the daemon: {0}
+    #[cfg(feature = "_rpc")]
+    Negotiation(crate::rpc::Error),
+
+    /// In-process RPC endpoint {0} is reachable only from the application
+    /// running the daemon runtime
+    InprocEndpoint(String),
+
+    /// gRPC interface is not supported by the current build; please
+    /// re-compile with `grpc` feature
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    GrpcNotCompiled,
+
+    /// Unable to initialize configuration
     #[cfg(any(feature = "server", feature = "embedded"))]
     ConfigInitError,
 
+    /// Daemon configuration can't be serialized for its fingerprint: {0}
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    #[from]
+    ConfigSerialization(toml::ser::Error),
+
+    /// Daemon runtime failure: {0}
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    #[from]
+    Runtime(RuntimeError),
+
+    /// Other bootstrap error
     Other,
 }
 
 #[derive(Debug, Display, Error, From)]
-#[display(Debug)]
+#[display(doc_comments)]
 pub enum RuntimeError {
+    /// RPC transport error
     #[from(internet2::transport::Error)]
     Transport,
 
+    /// Malformed RPC message
     #[from(internet2::presentation::Error)]
     Message,
 
+    /// ZMQ socket error: {0}
     #[cfg(any(feature = "server", feature = "embedded"))]
     #[from]
+    Zmq(zmq::Error),
+
+    /// Vault storage error: {0}
+    #[cfg(feature = "_vault")]
+    #[from]
     VaultDriver(vault::driver::Error),
 
-    #[cfg(any(feature = "server", feature = "embedded"))]
+    /// {0}
+    #[cfg(feature = "_vault")]
     #[from]
     KeyManagement(vault::keymgm::Error),
+
+    /// {0}
+    #[cfg(feature = "_vault")]
+    #[from]
+    PolicyViolation(vault::policy::PolicyViolation),
+
+    /// {0}
+    #[cfg(feature = "_vault")]
+    #[from]
+    Multisig(vault::multisig::Error),
+
+    /// {0}
+    #[cfg(feature = "_vault")]
+    #[from]
+    MuSig(vault::musig::Error),
+
+    /// {0}
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    #[from]
+    MuSigSession(daemon::musig::Error),
+
+    /// {0}
+    #[from]
+    DerivationPath(derivation::Error),
+
+    /// Blockchain data source error: {0}
+    #[cfg(feature = "_vault")]
+    #[from]
+    ChainSource(chain::Error),
+
+    /// Blockchain data source is not configured
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    NoChainSource,
+
+    /// Vault backups are not configured for the daemon
+    #[cfg(feature = "_vault")]
+    BackupsDisabled,
+
+    /// {0}
+    #[cfg(feature = "node")]
+    #[from]
+    Interchange(vault::interchange::Error),
+
+    /// {0}
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    #[from]
+    PsbtCombine(crate::psbt::Error),
+
+    /// {0}
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    #[from]
+    KeyPrefix(crate::rpc::types::KeyPrefixError),
+
+    /// Transaction ledger is not configured for the daemon
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    LedgerDisabled,
+
+    /// Transaction ledger error: {0}
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    Ledger(String),
+
+    /// Storage of Lightning revocation secrets is not configured for the
+    /// daemon
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    RevocationsDisabled,
+
+    /// {0}
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    #[from]
+    Revocation(daemon::revocation::Error),
+
+    /// Job is not known to the daemon; its request has not been received yet
+    /// or was completed long ago
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    UnknownJob,
+
+    /// Job with the same id is already queued or running
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    DuplicateJob,
+
+    /// Daemon is shutting down and does not accept new jobs
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    ShuttingDown,
+
+    /// Remote attestation is not configured for the daemon
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    AttestationDisabled,
+
+    /// Platform attestation error: {0}
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    #[from]
+    Attestation(daemon::attestation::Error),
+
+    /// {0}
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    #[from]
+    Passphrase(passphrase::Error),
+
+    /// Vault is locked; please unlock it first
+    #[cfg(feature = "_vault")]
+    VaultLocked,
+
+    /// Vault holds no private keys to verify the passphrase with; please
+    /// confirm the passphrase by repeating the unlock request with it
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    PassphraseConfirmation,
+
+    /// {0}
+    #[cfg(feature = "_vault")]
+    #[from]
+    Session(vault::session::Error),
+
+    /// Request is not authorized
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    Unauthorized,
+
+    /// Daemon accepts only requests sent over encrypted channel
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    EncryptionRequired,
+
+    /// Encrypted channels are disabled in daemon configuration
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    EncryptionDisabled,
+
+    /// Encrypted channel handshake failed: {0}
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    Handshake(String),
+
+    /// Encrypted channel is not known; it may have been expired
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    UnknownChannel,
+
+    /// Unable to decrypt data received over encrypted channel
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    Decryption,
+
+    /// Daemon accepts requests carrying secrets only if they are sealed to
+    /// the daemon node id
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    SealingRequired,
+
+    /// Sealed requests are disabled in daemon configuration
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    SealingDisabled,
+
+    /// Unable to open sealed request: {0}
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    #[from]
+    Sealed(crate::rpc::sealed::Error),
+
+    /// Unable to open tagged request: {0}
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    #[from]
+    Tagged(crate::rpc::tagged::Error),
+
+    /// Unable to open routed request: {0}
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    #[from]
+    Routed(crate::rpc::routed::Error),
+
+    /// Vault {0} is not configured in the daemon
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    UnknownVault(crate::rpc::types::VaultId),
+
+    /// Idempotency key is used by another request, which is either still
+    /// served or has a different type
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    IdempotencyConflict,
+
+    /// Export of private keys is disabled: daemon is built without
+    /// `export-secrets` feature
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    SecretExportDisabled,
+
+    /// Export of private keys of the keyring containing account {0} is not
+    /// enabled in the daemon configuration
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    ExportNotAllowed(bitcoin::XpubIdentifier),
+
+    /// Export of the private key is not approved or the approval has expired;
+    /// please request new approval token
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    ExportNotApproved,
+
+    /// Request type {0} is not supported by the daemon, which implements RPC
+    /// protocol versions {1} to {2}; the client is probably newer than the
+    /// daemon
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    UnsupportedRequest(u16, u16, u16),
+
+    /// Client implements RPC protocol version {0}, while the daemon serves
+    /// clients of protocol versions {1} to {2}; please upgrade the client
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    IncompatibleClient(u16, u16, u16),
+
+    /// Vault federation is disabled in the daemon configuration
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    FederationDisabled,
+
+    /// Daemon is running in read-only mode and does not serve requests
+    /// modifying the vault or using private keys
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    ReadOnly,
+
+    /// Unable to reload daemon configuration: {0}
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    Reconfiguration(String),
+
+    /// Configuration settings {0} can't be changed without restarting the
+    /// daemon
+    #[cfg(any(feature = "server", feature = "embedded"))]
+    ImmutableSettings(String),
 }
@@ -0,0 +1,476 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! C interface of the [`Embedded`] vault for the applications written in
+//! other languages, like iOS and Android wallets; the declarations are
+//! provided by `include/keyring.h`.
+//!
+//! All functions return [`KeyringError`] code; for the codes other than
+//! [`KeyringError::Ok`] the description of the error is returned by
+//! [`keyring_last_error`] called from the same thread. Data returned by the
+//! library are owned by the caller and must be released with the matching
+//! `keyring_free_*` function. Panics do not cross the interface boundary and
+//! are reported with [`KeyringError::Panic`] code.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+
+use bitcoin::consensus::encode::{deserialize, serialize};
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::SecretKey;
+use bitcoin::util::psbt::PartiallySignedTransaction;
+
+use crate::daemon::opts::{KEYRING_VAULT_FILE, KEYRING_VAULT_FORMAT};
+use crate::daemon::Config;
+use crate::error::{BootstrapError, RuntimeError};
+use crate::rpc::types::{AccountInfo, SessionToken};
+use crate::vault::delegated::{LoadCallback, SaveCallback};
+use crate::vault::encryption::DEFAULT_UNLOCK_TIMEOUT;
+use crate::vault::{delegated, driver, file_driver, keymgm, Encryption};
+use crate::Embedded;
+
+/// Result codes of the interface functions. The values are part of the
+/// interface and are never re-assigned.
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum KeyringError {
+    Ok = 0,
+    /// Null pointer or malformed data passed to the function
+    InvalidArgument = 1,
+    /// Vault can't be opened with the given configuration
+    Bootstrap = 2,
+    /// Vault must be unlocked with a passphrase first
+    VaultLocked = 3,
+    /// Session is not known; it may have been expired or locked
+    UnknownSession = 4,
+    /// Passphrase does not satisfy the passphrase policy
+    Passphrase = 5,
+    /// Vault is opened read-only
+    ReadOnly = 6,
+    /// Signing policy of the account forbids the operation
+    PolicyViolation = 7,
+    /// Private keys of the account are not known to the vault
+    WatchOnly = 8,
+    /// Other vault error
+    Failure = 9,
+    /// Library bug; the handle must not be used anymore
+    Panic = 10,
+}
+
+/// Vault configuration. The vault is stored in the `data_dir` unless both
+/// storage callbacks are given, in which case storage is delegated to the
+/// application.
+#[repr(C)]
+pub struct KeyringConfig {
+    /// Zero-terminated UTF-8 path of the directory keeping the vault file
+    pub data_dir: *const c_char,
+    /// Node key encrypting the vault unless it is protected by a passphrase
+    pub node_key: [u8; 32],
+    /// Protect the vault with a passphrase provided to [`keyring_unlock`]
+    pub passphrase: bool,
+    /// Number of seconds the passphrase-protected vault stays unlocked; zero
+    /// selects the default timeout
+    pub unlock_timeout: u64,
+    /// Refuse all vault modifications and signing
+    pub read_only: bool,
+    pub load_cb: Option<LoadCallback>,
+    pub save_cb: Option<SaveCallback>,
+}
+
+/// Unlocked vault session
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct KeyringSession {
+    pub token: [u8; 32],
+    /// Number of seconds after which the session expires
+    pub expires_in: u64,
+}
+
+/// Account of the vault
+#[repr(C)]
+pub struct KeyringAccount {
+    pub id: [u8; 20],
+    /// Identifier of the keyring master key the account belongs to
+    pub key_id: [u8; 20],
+    pub fingerprint: [u8; 4],
+    /// Zero-terminated UTF-8 account name
+    pub name: *mut c_char,
+    /// Zero-terminated UTF-8 account details or null
+    pub details: *mut c_char,
+    pub watch_only: bool,
+}
+
+/// Accounts returned by [`keyring_list`]; released with
+/// [`keyring_free_accounts`]
+#[repr(C)]
+pub struct KeyringAccountList {
+    pub accounts: *mut KeyringAccount,
+    pub len: usize,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+/// Error reported over the interface
+struct Failure(KeyringError, String);
+
+impl Failure {
+    fn null(argument: &str) -> Self {
+        Failure(
+            KeyringError::InvalidArgument,
+            format!("`{}` must not be null", argument),
+        )
+    }
+
+    fn invalid(argument: &str, err: impl ToString) -> Self {
+        Failure(
+            KeyringError::InvalidArgument,
+            format!("invalid `{}`: {}", argument, err.to_string()),
+        )
+    }
+}
+
+impl From<RuntimeError> for Failure {
+    fn from(err: RuntimeError) -> Self {
+        let code = match err {
+            RuntimeError::VaultLocked => KeyringError::VaultLocked,
+            RuntimeError::Session(_) => KeyringError::UnknownSession,
+            RuntimeError::Passphrase(_) => KeyringError::Passphrase,
+            RuntimeError::ReadOnly => KeyringError::ReadOnly,
+            RuntimeError::PolicyViolation(_) => KeyringError::PolicyViolation,
+            RuntimeError::KeyManagement(keymgm::Error::WatchOnly) => {
+                KeyringError::WatchOnly
+            }
+            _ => KeyringError::Failure,
+        };
+        Failure(code, err.to_string())
+    }
+}
+
+impl From<BootstrapError> for Failure {
+    fn from(err: BootstrapError) -> Self {
+        Failure(KeyringError::Bootstrap, err.to_string())
+    }
+}
+
+/// Runs body of an interface function, recording its error for
+/// [`keyring_last_error`]
+fn call(f: impl FnOnce() -> Result<(), Failure>) -> KeyringError {
+    let Failure(code, message) = match panic::catch_unwind(AssertUnwindSafe(f))
+    {
+        Ok(Ok(())) => return KeyringError::Ok,
+        Ok(Err(failure)) => failure,
+        Err(_) => Failure(KeyringError::Panic, s!("keyring library panicked")),
+    };
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(c_string(message)));
+    code
+}
+
+/// Converts string for returning it to the caller; zero characters, which
+/// can't be represented in C strings, are removed
+fn c_string(s: impl Into<String>) -> CString {
+    let mut s = s.into();
+    s.retain(|c| c != '\0');
+    CString::new(s).expect("zero characters are removed")
+}
+
+unsafe fn read_str<'a>(
+    ptr: *const c_char,
+    argument: &str,
+) -> Result<&'a str, Failure> {
+    if ptr.is_null() {
+        return Err(Failure::null(argument));
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|err| Failure::invalid(argument, err))
+}
+
+unsafe fn read_session(session: *const KeyringSession) -> Option<SessionToken> {
+    session
+        .as_ref()
+        .map(|session| SessionToken::from_inner(session.token))
+}
+
+unsafe fn vault<'a>(handle: *const Embedded) -> Result<&'a Embedded, Failure> {
+    handle.as_ref().ok_or_else(|| Failure::null("handle"))
+}
+
+impl KeyringConfig {
+    unsafe fn daemon_config(&self) -> Result<Config, Failure> {
+        let node_key = SecretKey::from_slice(&self.node_key)
+            .map_err(|err| Failure::invalid("node_key", err))?;
+        let data_dir = if self.data_dir.is_null() {
+            None
+        } else {
+            Some(read_str(self.data_dir, "data_dir")?.to_owned())
+        };
+        let vault = match (self.load_cb, self.save_cb, &data_dir) {
+            (Some(load_cb), Some(save_cb), _) => {
+                driver::Config::Delegated(delegated::Config {
+                    load_cb,
+                    save_cb,
+                })
+            }
+            (None, None, Some(data_dir)) => {
+                driver::Config::File(file_driver::Config {
+                    location: Path::new(data_dir)
+                        .join(KEYRING_VAULT_FILE)
+                        .display()
+                        .to_string(),
+                    format: KEYRING_VAULT_FORMAT,
+                    backups: file_driver::DEFAULT_BACKUPS,
+                    signed: false,
+                    node_key: None,
+                    read_only: self.read_only,
+                })
+            }
+            (None, None, None) => return Err(Failure::null("data_dir")),
+            _ => {
+                return Err(Failure::invalid(
+                    "save_cb",
+                    "both storage callbacks must be given",
+                ))
+            }
+        };
+        let encryption = match (self.passphrase, self.unlock_timeout) {
+            (false, _) => Encryption::NodeKey,
+            (true, 0) => Encryption::Passphrase {
+                unlock_timeout: DEFAULT_UNLOCK_TIMEOUT,
+            },
+            (true, unlock_timeout) => Encryption::Passphrase { unlock_timeout },
+        };
+        Ok(Config {
+            node_key,
+            data_dir: data_dir.unwrap_or_default(),
+            vault,
+            backup: None,
+            encryption,
+            read_only: self.read_only,
+            ..Config::default()
+        })
+    }
+}
+
+impl From<AccountInfo> for KeyringAccount {
+    fn from(info: AccountInfo) -> Self {
+        KeyringAccount {
+            id: info.id.into_inner(),
+            key_id: info.key_id.into_inner(),
+            fingerprint: info.fingerprint.to_bytes(),
+            name: c_string(info.name).into_raw(),
+            details: info
+                .details
+                .map(|details| c_string(details).into_raw())
+                .unwrap_or(ptr::null_mut()),
+            watch_only: info.watch_only,
+        }
+    }
+}
+
+/// Opens the vault, returning its handle, which must be released with
+/// [`keyring_close`]
+///
+/// # Safety
+///
+/// `config` must point to a valid configuration, which strings are
+/// zero-terminated; `handle` must be a valid pointer to write the handle to.
+#[no_mangle]
+pub unsafe extern "C" fn keyring_open(
+    config: *const KeyringConfig,
+    handle: *mut *mut Embedded,
+) -> KeyringError {
+    call(|| {
+        let config = config.as_ref().ok_or_else(|| Failure::null("config"))?;
+        if handle.is_null() {
+            return Err(Failure::null("handle"));
+        }
+        let embedded = Embedded::open(config.daemon_config()?)?;
+        *handle = Box::into_raw(Box::new(embedded));
+        Ok(())
+    })
+}
+
+/// Closes the vault, wiping the keys of the unlocked sessions
+///
+/// # Safety
+///
+/// `handle` must be returned by [`keyring_open`] and not used after the call.
+#[no_mangle]
+pub unsafe extern "C" fn keyring_close(handle: *mut Embedded) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Unlocks the vault protected with a passphrase; for other vaults
+/// `passphrase` may be null
+///
+/// # Safety
+///
+/// `handle` must be returned by [`keyring_open`]; `passphrase` must be null
+/// or a zero-terminated string; `session` must be a valid pointer to write
+/// the unlocked session to.
+#[no_mangle]
+pub unsafe extern "C" fn keyring_unlock(
+    handle: *const Embedded,
+    passphrase: *const c_char,
+    session: *mut KeyringSession,
+) -> KeyringError {
+    call(|| {
+        let embedded = vault(handle)?;
+        let session =
+            session.as_mut().ok_or_else(|| Failure::null("session"))?;
+        let passphrase = if passphrase.is_null() {
+            ""
+        } else {
+            read_str(passphrase, "passphrase")?
+        };
+        let unlocked = embedded.unlock(passphrase)?;
+        *session = KeyringSession {
+            token: unlocked.token.into_inner(),
+            expires_in: unlocked.expires_in,
+        };
+        Ok(())
+    })
+}
+
+/// Locks the vault `session`
+///
+/// # Safety
+///
+/// `handle` must be returned by [`keyring_open`]; `session` must point to a
+/// session returned by [`keyring_unlock`].
+#[no_mangle]
+pub unsafe extern "C" fn keyring_lock(
+    handle: *const Embedded,
+    session: *const KeyringSession,
+) -> KeyringError {
+    call(|| {
+        let token =
+            read_session(session).ok_or_else(|| Failure::null("session"))?;
+        vault(handle)?.lock(token)?;
+        Ok(())
+    })
+}
+
+/// Lists accounts of the vault; the list must be released with
+/// [`keyring_free_accounts`]
+///
+/// # Safety
+///
+/// `handle` must be returned by [`keyring_open`]; `list` must be a valid
+/// pointer to write the list to.
+#[no_mangle]
+pub unsafe extern "C" fn keyring_list(
+    handle: *const Embedded,
+    list: *mut KeyringAccountList,
+) -> KeyringError {
+    call(|| {
+        let accounts = vault(handle)?.list()?;
+        let list = list.as_mut().ok_or_else(|| Failure::null("list"))?;
+        let accounts = accounts
+            .into_iter()
+            .map(KeyringAccount::from)
+            .collect::<Vec<_>>()
+            .into_boxed_slice();
+        list.len = accounts.len();
+        list.accounts = Box::into_raw(accounts) as *mut KeyringAccount;
+        Ok(())
+    })
+}
+
+/// Signs inputs of the base64-encoded PSBT with the vault keys, returning
+/// base64-encoded signed PSBT, which must be released with
+/// [`keyring_free_string`]. The `session` may be null for the vault not
+/// protected with a passphrase.
+///
+/// # Safety
+///
+/// `handle` must be returned by [`keyring_open`]; `psbt` must be a
+/// zero-terminated string; `session` must be null or point to a session
+/// returned by [`keyring_unlock`]; `signed_psbt` must be a valid pointer to write
+/// the string to.
+#[no_mangle]
+pub unsafe extern "C" fn keyring_sign_psbt(
+    handle: *const Embedded,
+    psbt: *const c_char,
+    session: *const KeyringSession,
+    signed_psbt: *mut *mut c_char,
+) -> KeyringError {
+    call(|| {
+        let embedded = vault(handle)?;
+        if signed_psbt.is_null() {
+            return Err(Failure::null("signed_psbt"));
+        }
+        let data = base64::decode(read_str(psbt, "psbt")?)
+            .map_err(|err| Failure::invalid("psbt", err))?;
+        let psbt: PartiallySignedTransaction =
+            deserialize(&data).map_err(|err| Failure::invalid("psbt", err))?;
+        let psbt = embedded.sign_psbt(psbt, read_session(session))?;
+        *signed_psbt = c_string(base64::encode(&serialize(&psbt))).into_raw();
+        Ok(())
+    })
+}
+
+/// Returns description of the last error occurred in the calling thread, or
+/// null if there were no errors. The string is valid until the next failed
+/// call made from the thread.
+#[no_mangle]
+pub extern "C" fn keyring_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map(|message| message.as_ptr())
+            .unwrap_or(ptr::null())
+    })
+}
+
+/// Releases the list returned by [`keyring_list`], leaving it empty
+///
+/// # Safety
+///
+/// `list` must be null or point to a list filled by [`keyring_list`].
+#[no_mangle]
+pub unsafe extern "C" fn keyring_free_accounts(list: *mut KeyringAccountList) {
+    let list = match list.as_mut() {
+        Some(list) if !list.accounts.is_null() => list,
+        _ => return,
+    };
+    let accounts =
+        Box::from_raw(ptr::slice_from_raw_parts_mut(list.accounts, list.len));
+    for account in accounts.iter() {
+        keyring_free_string(account.name);
+        keyring_free_string(account.details);
+    }
+    list.accounts = ptr::null_mut();
+    list.len = 0;
+}
+
+/// Releases the string returned by the library
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by the library and not released
+/// yet.
+#[no_mangle]
+pub unsafe extern "C" fn keyring_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
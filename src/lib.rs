@@ -23,7 +23,7 @@
     // missing_docs,
 )]
 
-#[cfg_attr(any(feature = "server", feature = "embedded"), macro_use)]
+#[cfg_attr(feature = "_vault", macro_use)]
 extern crate amplify;
 #[macro_use]
 extern crate amplify_derive;
@@ -46,19 +46,43 @@ extern crate serde_crate as serde;
 #[macro_use]
 extern crate serde_with;
 
+#[cfg(feature = "_vault")]
+pub mod chain;
 #[cfg(feature = "cli")]
 pub mod cli;
+#[cfg(any(feature = "_vault", feature = "client"))]
+pub mod crypto;
+pub mod derivation;
+#[cfg(feature = "embedded")]
+pub mod embedded;
 mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod lifecycle;
+#[cfg(feature = "lnp")]
+pub mod lnp;
+#[cfg(feature = "mock")]
+pub mod mock;
 #[cfg(any(feature = "shell", feature = "embedded"))]
 pub(crate) mod opts;
+#[cfg(any(feature = "node", feature = "cli"))]
+pub mod passphrase;
+#[cfg(any(feature = "node", feature = "client"))]
+pub mod psbt;
 #[cfg(feature = "_rpc")]
 pub mod rpc;
+#[cfg(any(feature = "_vault", feature = "client"))]
+pub mod signed_message;
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub mod wasm;
 
 #[cfg(feature = "node")]
 pub mod daemon;
-#[cfg(feature = "node")]
+#[cfg(feature = "_vault")]
 pub mod vault;
-#[cfg(feature = "node")]
+#[cfg(feature = "embedded")]
+pub use embedded::Embedded;
+#[cfg(feature = "_vault")]
 pub use vault::Vault;
 
 pub use error::RuntimeError;
@@ -0,0 +1,127 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Key lifecycle state machine. Each keys account passes through a set of
+//! well-defined states, and the state determines which operations are allowed
+//! with the account keys:
+//!
+//! | State    | Derive | Sign | Export xpub | Export xpriv |
+//! |----------|--------|------|-------------|--------------|
+//! | pending  |        |      | +           |              |
+//! | active   | +      | +    | +           | +            |
+//! | retiring |        | +    | +           |              |
+//! | revoked  |        |      |             |              |
+//!
+//! Allowed transitions are `pending -> active`, `active -> retiring` and from
+//! any non-revoked state to `revoked`. Revoked is the final state.
+
+use std::str::FromStr;
+
+/// Lifecycle state of a keys account
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "lowercase")
+)]
+#[derive(
+    Copy, Clone, PartialEq, Eq, Hash, Debug, Display, StrictEncode, StrictDecode,
+)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub enum Lifecycle {
+    /// Key is created, but not yet approved for use
+    #[display("pending")]
+    Pending,
+
+    /// Key is in active use
+    #[display("active")]
+    Active,
+
+    /// Key is being phased out: it can still sign, but no new keys can be
+    /// derived from it
+    #[display("retiring")]
+    Retiring,
+
+    /// Key is revoked and can't be used for any operation
+    #[display("revoked")]
+    Revoked,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Lifecycle::Active
+    }
+}
+
+/// Operations restricted by the key lifecycle state
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Display)]
+pub enum Operation {
+    /// Derivation of new subaccounts
+    #[display("derive")]
+    Derive,
+
+    /// Any form of signing: PSBTs, keys, data
+    #[display("sign")]
+    Sign,
+
+    /// Export of extended public key
+    #[display("export_xpub")]
+    ExportXpub,
+
+    /// Export of extended private key
+    #[display("export_xpriv")]
+    ExportXpriv,
+}
+
+/// Error parsing lifecycle state string
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display("unknown key lifecycle state `{0}`")]
+pub struct ParseError(String);
+
+impl FromStr for Lifecycle {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "pending" => Lifecycle::Pending,
+            "active" => Lifecycle::Active,
+            "retiring" => Lifecycle::Retiring,
+            "revoked" => Lifecycle::Revoked,
+            _ => Err(ParseError(s.to_owned()))?,
+        })
+    }
+}
+
+impl Lifecycle {
+    /// Checks whether a given operation is allowed in the current state
+    pub fn allows(self, operation: Operation) -> bool {
+        match (self, operation) {
+            (Lifecycle::Active, _) => true,
+            (Lifecycle::Pending, Operation::ExportXpub) => true,
+            (Lifecycle::Retiring, Operation::Sign)
+            | (Lifecycle::Retiring, Operation::ExportXpub) => true,
+            _ => false,
+        }
+    }
+
+    /// Checks whether transition from the current state to the `next` one
+    /// is allowed
+    pub fn can_transit(self, next: Lifecycle) -> bool {
+        match (self, next) {
+            (Lifecycle::Pending, Lifecycle::Active)
+            | (Lifecycle::Active, Lifecycle::Retiring) => true,
+            (Lifecycle::Revoked, _) => false,
+            (_, Lifecycle::Revoked) => true,
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,368 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Signer of LNP Node channels backed by keyringd, so the Lightning node does
+//! not keep channel private keys itself.
+//!
+//! Channel keys are derived from a keyring dedicated to the Lightning node
+//! with the path `<basepoint>/<channel index>` relative to the keyring master
+//! key, where the channel index is assigned by the node. Commitment
+//! transactions are signed by the daemon as PSBTs spending the channel
+//! funding output, so they are subject to the keyring signing policy, and
+//! per-commitment secrets received from the channel counterparties are kept
+//! in the daemon revocation storage.
+
+use bitcoin::secp256k1::{self, Signature};
+use bitcoin::util::bip32::{self, ChildNumber, DerivationPath, Fingerprint};
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::{PublicKey, Script, Transaction, TxOut, XpubIdentifier};
+
+use crate::cli::{self, Client};
+use crate::error::BootstrapError;
+pub use crate::rpc::types::Basepoint;
+use crate::rpc::types::{
+    AnnouncementSignatures, AuthCode, CommitmentSecret, DerivationTemplate,
+    LnChannelId, LnKeySet, SessionToken, SharedSecret,
+};
+use crate::rpc::{self, message, Reply, Request};
+
+/// Errors of the channel signer
+#[derive(Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum Error {
+    /// Unable to connect to the daemon: {0}
+    #[from]
+    Bootstrap(BootstrapError),
+
+    /// {0}
+    #[from]
+    Rpc(rpc::Error),
+
+    /// Keyring {0} is not known to the daemon
+    UnknownKeyring(XpubIdentifier),
+
+    /// Invalid channel index: {0}
+    #[from]
+    ChannelIndex(bip32::Error),
+
+    /// Commitment transaction is not signed by the daemon; the keyring is
+    /// probably watch-only
+    NotSigned,
+
+    /// Daemon returned malformed signature
+    Signature,
+}
+
+/// Signer interface used by LNP Node for the channel operations
+pub trait ChannelSigner {
+    type Error: std::error::Error;
+
+    /// Returns public key of the `basepoint` of the `channel`
+    fn basepoint(
+        &mut self,
+        channel: u32,
+        basepoint: Basepoint,
+    ) -> Result<PublicKey, Self::Error>;
+
+    /// Returns public key used in the 2-of-2 funding output of the `channel`
+    fn funding_pubkey(
+        &mut self,
+        channel: u32,
+    ) -> Result<PublicKey, Self::Error> {
+        self.basepoint(channel, Basepoint::Funding)
+    }
+
+    /// Signs `commitment` transaction of the `channel`, which single input
+    /// spends the P2WSH funding output with `funding_script` and
+    /// `funding_amount`, with the channel funding key
+    fn sign_commitment(
+        &mut self,
+        channel: u32,
+        commitment: &Transaction,
+        funding_script: &Script,
+        funding_amount: u64,
+    ) -> Result<Signature, Self::Error>;
+
+    /// Stores per-commitment secret with `index` revealed by the channel
+    /// counterparty
+    fn store_revocation(
+        &mut self,
+        channel_id: LnChannelId,
+        index: u64,
+        secret: CommitmentSecret,
+    ) -> Result<(), Self::Error>;
+
+    /// Returns per-commitment secret with `index` stored for the channel
+    fn revocation(
+        &mut self,
+        channel_id: LnChannelId,
+        index: u64,
+    ) -> Result<CommitmentSecret, Self::Error>;
+}
+
+/// Channel signer using keys of a keyring kept by keyringd
+pub struct RpcSigner {
+    client: Client,
+    key_id: XpubIdentifier,
+    fingerprint: Fingerprint,
+    session: Option<SessionToken>,
+}
+
+impl RpcSigner {
+    /// Connects to the daemon using `config` and checks that the keyring
+    /// `key_id` used by the Lightning node is known to it
+    pub fn with(
+        config: cli::Config,
+        key_id: XpubIdentifier,
+    ) -> Result<Self, Error> {
+        let mut client = Client::with(config)?;
+        let fingerprint = match client.request(Request::List)? {
+            Reply::Keylist(accounts) => accounts
+                .into_iter()
+                .find(|account| account.id == key_id)
+                .map(|account| account.fingerprint)
+                .ok_or(Error::UnknownKeyring(key_id))?,
+            reply => Err(unexpected(reply))?,
+        };
+        Ok(Self {
+            client,
+            key_id,
+            fingerprint,
+            session: None,
+        })
+    }
+
+    /// Sets the session of the vault unlocked with a passphrase, which is
+    /// used for all operations with the node and channel private keys
+    pub fn set_session(&mut self, session: Option<SessionToken>) {
+        self.session = session;
+    }
+
+    /// Returns node identity key and all basepoints of the `channel`
+    pub fn key_set(&mut self, channel: u32) -> Result<LnKeySet, Error> {
+        let request = Request::DeriveLnKeySet(message::DeriveLnKeySet {
+            key_id: self.key_id,
+            channel,
+            auth_code: AuthCode::default(),
+        });
+        match self.client.request(request)? {
+            Reply::LnKeySet(key_set) => Ok(key_set),
+            reply => Err(unexpected(reply).into()),
+        }
+    }
+
+    /// Performs ECDH of the remote node `pubkey` with the node identity key
+    /// kept by the daemon, as required by the BOLT-8 handshake. Keyring
+    /// signing policy must allow ECDH.
+    pub fn ecdh(
+        &mut self,
+        pubkey: secp256k1::PublicKey,
+    ) -> Result<SharedSecret, Error> {
+        let request = Request::Ecdh(message::Ecdh {
+            key_id: self.key_id,
+            pubkey,
+            decryption_key: Client::DECRYPTION_KEY_PLACEHOLDER,
+            session: self.session,
+            auth_code: AuthCode::default(),
+        });
+        match self.client.request(request)? {
+            Reply::SharedSecret(secret) => Ok(secret),
+            reply => Err(unexpected(reply).into()),
+        }
+    }
+
+    /// Signs BOLT-11 invoice given by its human-readable part and 5-bit
+    /// data values, returning compact signature followed by the recovery id
+    pub fn sign_invoice(
+        &mut self,
+        hrp: &str,
+        data: Vec<u8>,
+    ) -> Result<Vec<u8>, Error> {
+        let request = Request::SignInvoice(message::SignInvoice {
+            key_id: self.key_id,
+            hrp: hrp.to_owned(),
+            data,
+            decryption_key: Client::DECRYPTION_KEY_PLACEHOLDER,
+            session: self.session,
+            auth_code: AuthCode::default(),
+        });
+        match self.client.request(request)? {
+            Reply::InvoiceSignature(signature) => Ok(signature),
+            reply => Err(unexpected(reply).into()),
+        }
+    }
+
+    /// Signs `channel_announcement` wire message of the `channel` with the
+    /// node key and the channel funding key
+    pub fn sign_channel_announcement(
+        &mut self,
+        channel: u32,
+        announcement: Vec<u8>,
+    ) -> Result<AnnouncementSignatures, Error> {
+        let request = Request::SignChannelAnnouncement(
+            message::SignChannelAnnouncement {
+                key_id: self.key_id,
+                channel,
+                announcement,
+                decryption_key: Client::DECRYPTION_KEY_PLACEHOLDER,
+                session: self.session,
+                auth_code: AuthCode::default(),
+            },
+        );
+        match self.client.request(request)? {
+            Reply::AnnouncementSignatures(signatures) => Ok(signatures),
+            reply => Err(unexpected(reply).into()),
+        }
+    }
+
+    /// Signs `node_announcement` or `channel_update` wire message with the
+    /// node key
+    pub fn sign_gossip(
+        &mut self,
+        message: Vec<u8>,
+    ) -> Result<Signature, Error> {
+        let request = Request::SignGossip(message::SignGossip {
+            key_id: self.key_id,
+            message,
+            decryption_key: Client::DECRYPTION_KEY_PLACEHOLDER,
+            session: self.session,
+            auth_code: AuthCode::default(),
+        });
+        match self.client.request(request)? {
+            Reply::Signature(signature) => Ok(signature),
+            reply => Err(unexpected(reply).into()),
+        }
+    }
+}
+
+impl ChannelSigner for RpcSigner {
+    type Error = Error;
+
+    fn basepoint(
+        &mut self,
+        channel: u32,
+        basepoint: Basepoint,
+    ) -> Result<PublicKey, Error> {
+        let template = DerivationTemplate {
+            prefix: DerivationPath::from(vec![ChildNumber::from_normal_idx(
+                basepoint as u32,
+            )?]),
+            suffix: DerivationPath::master(),
+        };
+        let request = Request::DeriveRange(message::DeriveRange {
+            key_id: self.key_id,
+            template: Some(template),
+            internal: false,
+            start: channel,
+            count: 1,
+            job: None,
+            auth_code: AuthCode::default(),
+        });
+        match self.client.request(request)? {
+            Reply::DerivedKeys(keys) if keys.len() == 1 => Ok(keys[0].pubkey),
+            reply => Err(unexpected(reply).into()),
+        }
+    }
+
+    fn sign_commitment(
+        &mut self,
+        channel: u32,
+        commitment: &Transaction,
+        funding_script: &Script,
+        funding_amount: u64,
+    ) -> Result<Signature, Error> {
+        let funding_pubkey = self.funding_pubkey(channel)?;
+        let mut unsigned = commitment.clone();
+        for input in &mut unsigned.input {
+            input.script_sig = Script::new();
+            input.witness = vec![];
+        }
+        let mut psbt = PartiallySignedTransaction::from_unsigned_tx(unsigned)
+            .expect("input scripts and witnesses are removed");
+        if let Some(input) = psbt.inputs.first_mut() {
+            input.witness_utxo = Some(TxOut {
+                value: funding_amount,
+                script_pubkey: funding_script.to_v0_p2wsh(),
+            });
+            input.witness_script = Some(funding_script.clone());
+            input.bip32_derivation.insert(
+                funding_pubkey,
+                (self.fingerprint, Basepoint::Funding.path(channel)?),
+            );
+        }
+        let request = Request::SignPsbt(message::SignPsbt {
+            psbt,
+            force: false,
+            decryption_key: Client::DECRYPTION_KEY_PLACEHOLDER,
+            session: self.session,
+            job: None,
+            auth_code: AuthCode::default(),
+        });
+        let psbt = match self.client.request(request)? {
+            Reply::Psbt(psbt) => psbt,
+            reply => Err(unexpected(reply))?,
+        };
+        let partial_sig = psbt
+            .inputs
+            .first()
+            .and_then(|input| input.partial_sigs.get(&funding_pubkey))
+            .ok_or(Error::NotSigned)?;
+        // Partial signature is DER-encoded signature followed by the sighash
+        // type byte
+        let (_, der) = partial_sig.split_last().ok_or(Error::Signature)?;
+        Signature::from_der(der).map_err(|_| Error::Signature)
+    }
+
+    fn store_revocation(
+        &mut self,
+        channel_id: LnChannelId,
+        index: u64,
+        secret: CommitmentSecret,
+    ) -> Result<(), Error> {
+        let request = Request::AppendRevocation(message::AppendRevocation {
+            key_id: self.key_id,
+            channel: channel_id,
+            index,
+            secret,
+            auth_code: AuthCode::default(),
+        });
+        match self.client.request(request)? {
+            Reply::Success => Ok(()),
+            reply => Err(unexpected(reply).into()),
+        }
+    }
+
+    fn revocation(
+        &mut self,
+        channel_id: LnChannelId,
+        index: u64,
+    ) -> Result<CommitmentSecret, Error> {
+        let request = Request::QueryRevocation(message::QueryRevocation {
+            key_id: self.key_id,
+            channel: channel_id,
+            index,
+            auth_code: AuthCode::default(),
+        });
+        match self.client.request(request)? {
+            Reply::CommitmentSecret(secret) => Ok(secret),
+            reply => Err(unexpected(reply).into()),
+        }
+    }
+}
+
+fn unexpected(reply: Reply) -> rpc::Error {
+    match reply {
+        Reply::Failure(failure) => rpc::Error::ServerFailure(failure),
+        _ => rpc::Error::UnexpectedServerResponse,
+    }
+}
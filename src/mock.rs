@@ -0,0 +1,393 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Mock keyring daemon implementing RPC protocol with deterministic fake keys
+//! and canned replies. It does not use vault and does not store any data, so
+//! it can be used by client applications for running integration tests
+//! without real secrets. Accounts derived by the clients are kept in memory
+//! until the daemon stops.
+//!
+//! NB: All private keys used by the mock daemon are derived from a publicly
+//! known seed; never use them for anything except tests.
+
+use std::collections::BTreeMap;
+use std::thread;
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1;
+use bitcoin::util::bip143::SigHashCache;
+use bitcoin::util::bip32::{
+    DerivationPath, ExtendedPrivKey, ExtendedPubKey, Fingerprint,
+};
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::{SigHashType, XpubIdentifier};
+use internet2::zmqsocket::{self, ZmqSocketAddr, ZmqType};
+use internet2::{
+    session, CreateUnmarshaller, PlainTranscoder, Session, TypedEnum,
+    Unmarshall, Unmarshaller,
+};
+use microservices::node::TryService;
+use microservices::rpc::Failure;
+
+use crate::error::{BootstrapError, RuntimeError};
+use crate::rpc::types::{
+    AccountBalance, AccountInfo, Features, Hello, Session, Status,
+};
+use crate::rpc::{Reply, Request};
+use crate::vault::{input_sighash, taproot};
+
+/// Seed from which all mock daemon keys are derived
+pub const MOCK_SEED: &'static [u8] = b"keyring mock daemon seed";
+
+/// Failure code returned by the mock daemon for unknown keys and unsupported
+/// requests
+pub const MOCK_FAILURE_CODE: u16 = 0xFFFF;
+
+/// Runs mock daemon on a given endpoint in the current thread
+pub fn run(endpoint: ZmqSocketAddr) -> Result<(), BootstrapError> {
+    let runtime = MockRuntime::init(endpoint)?;
+
+    runtime.run_or_panic("keyring-mockd");
+
+    Ok(())
+}
+
+/// Spawns mock daemon on a given endpoint in a separate thread, which is
+/// useful for integration tests of client applications
+pub fn spawn(endpoint: ZmqSocketAddr) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        run(endpoint).expect("Error running keyring mock daemon")
+    })
+}
+
+pub struct MockRuntime {
+    /// Stored sessions
+    session_rpc: session::Raw<PlainTranscoder, zmqsocket::Connection>,
+
+    /// Master extended private key deterministically derived from
+    /// [`MOCK_SEED`]
+    master: ExtendedPrivKey,
+
+    /// Accounts derived by the clients: their names and derivation paths
+    /// from the master key
+    derived: BTreeMap<XpubIdentifier, (String, DerivationPath)>,
+
+    /// Unmarshaller instance used for parsing RPC request
+    unmarshaller: Unmarshaller<Request>,
+}
+
+impl MockRuntime {
+    pub fn init(endpoint: ZmqSocketAddr) -> Result<Self, BootstrapError> {
+        debug!("Opening mock daemon ZMQ socket {}", endpoint);
+        let session_rpc = session::Raw::with_zmq_unencrypted(
+            ZmqType::Rep,
+            &endpoint,
+            None,
+            None,
+        )?;
+
+        let master = ExtendedPrivKey::new_master(
+            bitcoin::Network::Testnet,
+            &sha256::Hash::hash(MOCK_SEED)[..],
+        )
+        .expect("Mock seed is always valid");
+
+        Ok(Self {
+            session_rpc,
+            master,
+            derived: BTreeMap::new(),
+            unmarshaller: Request::create_unmarshaller(),
+        })
+    }
+
+    /// Returns master extended public key of the mock daemon
+    pub fn master_xpub(&self) -> ExtendedPubKey {
+        ExtendedPubKey::from_private(&crate::SECP256K1, &self.master)
+    }
+
+    fn account_info(
+        &self,
+        xpub: &ExtendedPubKey,
+        name: impl ToString,
+        key_source: Option<(Fingerprint, DerivationPath)>,
+    ) -> AccountInfo {
+        AccountInfo {
+            id: xpub.identifier(),
+            name: name.to_string(),
+            details: None,
+            key_id: xpub.identifier(),
+            fingerprint: xpub.fingerprint(),
+            assets: Default::default(),
+            application: None,
+            key_source,
+            lifecycle: Default::default(),
+            branches: Default::default(),
+            policy: Default::default(),
+            aliases: vec![],
+            labels: Default::default(),
+            watch_only: false,
+            chain: None,
+        }
+    }
+
+    /// Lists the master account followed by the derived accounts
+    fn accounts(&self) -> Vec<AccountInfo> {
+        let master_xpub = self.master_xpub();
+        let mut accounts =
+            vec![self.account_info(&master_xpub, "Mock account", None)];
+        for (name, path) in self.derived.values() {
+            let xpriv = self
+                .master
+                .derive_priv(&crate::SECP256K1, path)
+                .expect("derivation of the mock account is checked");
+            accounts.push(self.account_info(
+                &ExtendedPubKey::from_private(&crate::SECP256K1, &xpriv),
+                name,
+                Some((master_xpub.fingerprint(), path.clone())),
+            ));
+        }
+        accounts
+    }
+
+    /// Derivation path of the account with a given `id` from the master key
+    fn path_by_id(&self, id: XpubIdentifier) -> Option<DerivationPath> {
+        if self.master_xpub().identifier() == id {
+            Some(DerivationPath::master())
+        } else {
+            self.derived.get(&id).map(|(_, path)| path.clone())
+        }
+    }
+
+    fn xpriv_by_id(&self, id: XpubIdentifier) -> Option<ExtendedPrivKey> {
+        self.path_by_id(id).map(|path| {
+            self.master
+                .derive_priv(&crate::SECP256K1, &path)
+                .expect("derivation of the mock account is checked")
+        })
+    }
+
+    /// Signs SegWit v0 and legacy inputs with the keys which BIP-32
+    /// derivations start at the mock master key. Taproot inputs and the keys
+    /// of other keyrings are left unsigned.
+    fn sign_psbt(
+        &self,
+        mut psbt: PartiallySignedTransaction,
+    ) -> Result<PartiallySignedTransaction, Reply> {
+        let fingerprint = self.master_xpub().fingerprint();
+        let spent_outputs = (0..psbt.inputs.len())
+            .map(|index| taproot::spent_output(&psbt, index))
+            .collect::<Vec<_>>();
+        let taproot_inputs = (0..psbt.inputs.len())
+            .map(|index| taproot::is_taproot_input(&psbt, index))
+            .collect::<Vec<_>>();
+        let tx = &psbt.global.unsigned_tx;
+        let mut sighash_cache = SigHashCache::new(tx);
+        for (index, inp) in psbt.inputs.iter_mut().enumerate() {
+            if taproot_inputs[index] {
+                continue;
+            }
+            for (pubkey, (key_fingerprint, derivation)) in &inp.bip32_derivation
+            {
+                if *key_fingerprint != fingerprint {
+                    continue;
+                }
+                let xpriv = self
+                    .master
+                    .derive_priv(&crate::SECP256K1, derivation)
+                    .map_err(Self::failure)?;
+                if xpriv.private_key.public_key(&crate::SECP256K1) != *pubkey {
+                    return Err(Self::failure(format!(
+                        "Key {} does not match its derivation {}",
+                        pubkey, derivation
+                    )));
+                }
+                let spent = spent_outputs[index].as_ref().ok_or_else(|| {
+                    Self::failure(format!(
+                        "Input {} has no spent output data",
+                        index
+                    ))
+                })?;
+                let sig_hash = input_sighash(
+                    tx,
+                    &mut sighash_cache,
+                    index,
+                    inp,
+                    spent,
+                    pubkey,
+                )
+                .map_err(Self::failure)?;
+                let signature = crate::SECP256K1.sign(
+                    &secp256k1::Message::from_slice(&sig_hash[..])
+                        .expect("signature hash is always a valid message"),
+                    &xpriv.private_key.key,
+                );
+                let mut partial_sig = signature.serialize_der().to_vec();
+                partial_sig.push(SigHashType::All.as_u32() as u8);
+                inp.sighash_type = Some(SigHashType::All);
+                inp.partial_sigs.insert(*pubkey, partial_sig);
+            }
+        }
+        Ok(psbt)
+    }
+
+    fn failure(info: impl ToString) -> Reply {
+        Reply::Failure(Failure {
+            code: MOCK_FAILURE_CODE,
+            info: info.to_string(),
+        })
+    }
+}
+
+impl TryService for MockRuntime {
+    type ErrorType = RuntimeError;
+
+    fn try_run_loop(mut self) -> Result<(), Self::ErrorType> {
+        loop {
+            let raw = self.session_rpc.recv_raw_message()?;
+            let reply = match self.unmarshaller.unmarshall(&raw) {
+                Ok(request) => self.process((&*request).clone()),
+                Err(err) => Reply::from(err),
+            };
+            trace!("Mock daemon reply: {}", reply);
+            self.session_rpc.send_raw_message(&reply.serialize())?;
+        }
+    }
+}
+
+impl MockRuntime {
+    fn process(&mut self, request: Request) -> Reply {
+        debug!("Mock daemon received request: {}", request);
+        let master_xpub = self.master_xpub();
+        match request {
+            Request::Challenge(_) => {
+                Reply::Challenge(sha256::Hash::hash(&master_xpub.encode()))
+            }
+            Request::Status => Reply::Status(Status {
+                config_fingerprint: sha256::Hash::hash(MOCK_SEED),
+                protocol_version: crate::rpc::PROTOCOL_VERSION,
+                uptime: 0,
+                driver: "Mock".to_owned(),
+                keyrings: 1,
+                accounts: 1 + self.derived.len() as u32,
+                locked: false,
+            }),
+            Request::Hello(_) => Reply::Hello(Hello {
+                server_version: crate::rpc::PROTOCOL_VERSION,
+                min_version: crate::rpc::MIN_PROTOCOL_VERSION,
+                features: Features::default(),
+            }),
+            Request::List => Reply::Keylist(self.accounts()),
+            Request::ListWithBalances(_) => Reply::BalanceList(
+                self.accounts()
+                    .into_iter()
+                    .map(|info| AccountBalance {
+                        info,
+                        tx_count: 0,
+                        received: 0,
+                        balance: 0,
+                    })
+                    .collect(),
+            ),
+            Request::Unlock(_) => Reply::Session(Session {
+                token: sha256::Hash::hash(&master_xpub.encode()),
+                expires_in: u64::MAX,
+            }),
+            Request::Lock(_) => Reply::Success,
+            Request::Seed(seed) => Reply::AccountInfo(self.account_info(
+                &master_xpub,
+                seed.name,
+                None,
+            )),
+            Request::ExportXpub(export) => {
+                let xpub = self.xpriv_by_id(export.key_id).map(|xpriv| {
+                    ExtendedPubKey::from_private(&crate::SECP256K1, &xpriv)
+                });
+                match (xpub, export.slip132) {
+                    (Some(xpub), None) => Reply::XPub(xpub),
+                    (Some(xpub), Some(prefix)) => match prefix.encode(&xpub) {
+                        Ok(encoded) => Reply::EncodedXPub(encoded),
+                        Err(err) => Self::failure(&err.to_string()),
+                    },
+                    (None, _) => Self::failure("Account is not found"),
+                }
+            }
+            #[cfg(feature = "export-secrets")]
+            Request::ExportXpriv(export) => {
+                match self.xpriv_by_id(export.key_id) {
+                    Some(xpriv) => Reply::XPriv(xpriv),
+                    None => Self::failure("Account is not found"),
+                }
+            }
+            #[cfg(not(feature = "export-secrets"))]
+            Request::ExportXpriv(_) => {
+                Self::failure("Export of private keys is disabled")
+            }
+            Request::Derive(derive) => {
+                let path = match self.path_by_id(derive.from) {
+                    Some(parent) => parent.extend(&derive.path),
+                    None => return Self::failure("Account is not found"),
+                };
+                match self.master.derive_priv(&crate::SECP256K1, &path) {
+                    Ok(xpriv) => {
+                        let xpub = ExtendedPubKey::from_private(
+                            &crate::SECP256K1,
+                            &xpriv,
+                        );
+                        let info = self.account_info(
+                            &xpub,
+                            &derive.name,
+                            Some((master_xpub.fingerprint(), path.clone())),
+                        );
+                        if xpub.identifier() != master_xpub.identifier() {
+                            self.derived
+                                .insert(xpub.identifier(), (derive.name, path));
+                        }
+                        Reply::AccountInfo(info)
+                    }
+                    Err(err) => Self::failure(err),
+                }
+            }
+            Request::SignPsbt(sign) => match self.sign_psbt(sign.psbt) {
+                Ok(psbt) => Reply::Psbt(psbt),
+                Err(failure) => failure,
+            },
+            Request::SignKey(sign) => match self.xpriv_by_id(sign.key_id) {
+                Some(xpriv) => {
+                    let xpub =
+                        ExtendedPubKey::from_private(&crate::SECP256K1, &xpriv);
+                    self.sign(&xpriv, &xpub.public_key.key.serialize())
+                }
+                None => Self::failure("Account is not found"),
+            },
+            Request::SignData(sign) => match self.xpriv_by_id(sign.key_id) {
+                Some(xpriv) => self.sign(&xpriv, &sign.data),
+                None => Self::failure("Account is not found"),
+            },
+            _ => Self::failure(format!(
+                "Request {} is not supported by the mock daemon",
+                request
+            )),
+        }
+    }
+
+    fn sign(&self, xpriv: &ExtendedPrivKey, data: &[u8]) -> Reply {
+        let digest = sha256::Hash::hash(data);
+        Reply::Signature(
+            crate::SECP256K1.sign(
+                &secp256k1::Message::from_slice(&digest[..])
+                    .expect("SHA256 hash is always a valid message"),
+                &xpriv.private_key.key,
+            ),
+        )
+    }
+}
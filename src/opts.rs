@@ -35,7 +35,7 @@ pub const KEYRING_DATA_DIR: &'static str = "~/Documents";
 pub const KEYRING_DATA_DIR: &'static str = ".";
 
 pub const KEYRING_RPC_SOCKET_NAME: &'static str =
-    "lnpz://0.0.0.0:20202?api=rpc"; //"ipc:{data_dir}/zmq.rpc";
+    "lnpz://0.0.0.0:20202?api=rpc";
 
 #[derive(Clap, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Opts {
@@ -81,10 +81,11 @@ pub struct Opts {
 
     /// ZMQ socket name/address for daemon RPC interface
     ///
-    /// Internal interface for control PRC protocol communications
-    /// Defaults to `ctl.rpc` file inside `--data-dir` directory, unless
-    /// `--use-threads` is specified; in that cases uses in-memory
-    /// communication protocol.
+    /// Either TCP address (`lnpz://<ip>:<port>?api=rpc`), Unix domain socket
+    /// path (`ipc:<path>`, where `{data_dir}` is replaced with `--data-dir`
+    /// value, like `ipc:{data_dir}/keyring.rpc`) or, for applications
+    /// embedding the daemon, in-process endpoint name (`inproc://<name>`).
+    /// Local sockets do not require network ports to be opened.
     #[clap(
         short = 'x',
         long,
@@ -112,6 +113,12 @@ pub struct Opts {
 impl Opts {
     pub fn process(&mut self) {
         LogLevel::from_verbosity_flag_count(self.verbose).apply();
+        self.process_paths();
+    }
+
+    /// Expands data directory and RPC socket paths without initializing the
+    /// logger, for applications installing their own one
+    pub fn process_paths(&mut self) {
         let mut me = self.clone();
 
         me.data_dir = PathBuf::from(
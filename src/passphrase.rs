@@ -0,0 +1,284 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Passphrase policy shared by all features deriving keys from user
+//! passphrases: key-derivation function parameters, minimal passphrase
+//! strength and screening against lists of compromised passwords.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use bitcoin::hashes::{sha1, Hash};
+#[cfg(feature = "node")]
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+
+/// Default minimal passphrase entropy, in bits
+pub const DEFAULT_MIN_ENTROPY: u32 = 60;
+
+/// Key-derivation function used to turn passphrase into a secret key
+#[derive(
+    Clone, Copy, PartialEq, Eq, Hash, Debug, Display, Serialize, Deserialize,
+)]
+#[serde(crate = "serde_crate", tag = "algorithm", rename_all = "snake_case")]
+pub enum Kdf {
+    /// Argon2id memory-hard function
+    #[display("argon2id(m_cost: {m_cost}, t_cost: {t_cost}, lanes: {lanes})")]
+    Argon2id {
+        /// Memory size, in KiB
+        m_cost: u32,
+
+        /// Number of iterations
+        t_cost: u32,
+
+        /// Degree of parallelism
+        lanes: u32,
+    },
+
+    /// Scrypt memory-hard function
+    #[display("scrypt(log_n: {log_n}, r: {r}, p: {p})")]
+    Scrypt {
+        /// Binary logarithm of the CPU/memory cost parameter
+        log_n: u8,
+
+        /// Block size parameter
+        r: u32,
+
+        /// Parallelization parameter
+        p: u32,
+    },
+}
+
+impl Default for Kdf {
+    fn default() -> Self {
+        Kdf::Argon2id {
+            m_cost: 65536,
+            t_cost: 3,
+            lanes: 4,
+        }
+    }
+}
+
+/// Policy applied to all passphrases provided by the users
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(crate = "serde_crate")]
+pub struct Policy {
+    /// Key-derivation function and its parameters
+    #[serde(default)]
+    pub kdf: Kdf,
+
+    /// Minimal estimated passphrase entropy, in bits
+    #[serde(default = "default_min_entropy")]
+    pub min_entropy: u32,
+
+    /// Path to the list of compromised passwords, containing hex-encoded
+    /// SHA1 hashes of the passwords, one per line (the format used by "Have
+    /// I Been Pwned" service, with optional `:count` suffix)
+    #[serde(default)]
+    pub breach_list: Option<String>,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            kdf: Kdf::default(),
+            min_entropy: DEFAULT_MIN_ENTROPY,
+            breach_list: None,
+        }
+    }
+}
+
+fn default_min_entropy() -> u32 {
+    DEFAULT_MIN_ENTROPY
+}
+
+/// Error cases related to passphrase processing
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum Error {
+    /// Passphrase is too weak: its estimated entropy is {0} bits, while at
+    /// least {1} bits are required
+    Weak(u32, u32),
+
+    /// Passphrase is found in the list of compromised passwords
+    Compromised,
+
+    /// Unable to read list of compromised passwords: {0}
+    BreachList(String),
+
+    /// Invalid key-derivation function parameters {0}
+    InvalidParams(Kdf),
+
+    /// Key derived from the passphrase is not a valid secp256k1 secret key;
+    /// please use other passphrase
+    InvalidKey,
+}
+
+/// Hook for screening passphrases against sources of known compromised
+/// passwords
+pub trait Screening {
+    /// Detects whether the passphrase is known to be compromised
+    fn is_compromised(&self, passphrase: &str) -> Result<bool, Error>;
+}
+
+/// List of compromised passwords stored in a file; see
+/// [`Policy::breach_list`] for the file format. The file is scanned on each
+/// check, so large lists are not kept in memory.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BreachList {
+    path: String,
+}
+
+impl BreachList {
+    pub fn with(path: impl ToString) -> Self {
+        Self {
+            path: path.to_string(),
+        }
+    }
+}
+
+impl Screening for BreachList {
+    fn is_compromised(&self, passphrase: &str) -> Result<bool, Error> {
+        let hash = sha1::Hash::hash(passphrase.as_bytes()).to_string();
+        let file = File::open(&self.path)
+            .map_err(|err| Error::BreachList(err.to_string()))?;
+        for line in BufReader::new(file).lines() {
+            let line =
+                line.map_err(|err| Error::BreachList(err.to_string()))?;
+            let entry = line.split(':').next().unwrap_or_default().trim();
+            if entry.eq_ignore_ascii_case(&hash) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// Estimates passphrase entropy in bits from its length and the size of the
+/// alphabet formed by the character classes used in the passphrase
+pub fn estimate_entropy(passphrase: &str) -> u32 {
+    let (mut lower, mut upper, mut digit, mut symbol, mut other) =
+        (false, false, false, false, false);
+    for c in passphrase.chars() {
+        match c {
+            'a'..='z' => lower = true,
+            'A'..='Z' => upper = true,
+            '0'..='9' => digit = true,
+            c if c.is_ascii() => symbol = true,
+            _ => other = true,
+        }
+    }
+    let alphabet = [
+        (lower, 26),
+        (upper, 26),
+        (digit, 10),
+        (symbol, 33),
+        (other, 100),
+    ]
+    .iter()
+    .filter(|(used, _)| *used)
+    .map(|(_, size)| *size)
+    .sum::<u32>();
+    if alphabet == 0 {
+        return 0;
+    }
+    (passphrase.chars().count() as f64 * (alphabet as f64).log2()) as u32
+}
+
+impl Policy {
+    /// Checks passphrase against the policy requirements, screening it with
+    /// the configured breach list
+    pub fn check(&self, passphrase: &str) -> Result<(), Error> {
+        match self.breach_list {
+            Some(ref path) => {
+                self.check_with(passphrase, &BreachList::with(path))
+            }
+            None => self.check_strength(passphrase),
+        }
+    }
+
+    /// Checks passphrase against the policy requirements, using custom
+    /// `screening` hook instead of the configured breach list
+    pub fn check_with(
+        &self,
+        passphrase: &str,
+        screening: &dyn Screening,
+    ) -> Result<(), Error> {
+        self.check_strength(passphrase)?;
+        if screening.is_compromised(passphrase)? {
+            return Err(Error::Compromised);
+        }
+        Ok(())
+    }
+
+    fn check_strength(&self, passphrase: &str) -> Result<(), Error> {
+        let entropy = estimate_entropy(passphrase);
+        if entropy < self.min_entropy {
+            return Err(Error::Weak(entropy, self.min_entropy));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "node")]
+impl Kdf {
+    /// Derives secret key from the user `passphrase`. Since passphrases are
+    /// low-entropy data, the derivation must be salted with some
+    /// installation-specific public key, like the daemon node id.
+    pub fn derive_key(
+        &self,
+        passphrase: &str,
+        salt: PublicKey,
+    ) -> Result<SecretKey, Error> {
+        let mut output = match *self {
+            Kdf::Argon2id {
+                m_cost,
+                t_cost,
+                lanes,
+            } => {
+                let config = argon2::Config {
+                    variant: argon2::Variant::Argon2id,
+                    version: argon2::Version::Version13,
+                    mem_cost: m_cost,
+                    time_cost: t_cost,
+                    lanes,
+                    thread_mode: argon2::ThreadMode::Sequential,
+                    secret: &[],
+                    ad: &[],
+                    hash_length: 32,
+                };
+                argon2::hash_raw(
+                    passphrase.as_bytes(),
+                    &salt.serialize(),
+                    &config,
+                )
+                .map_err(|_| Error::InvalidParams(*self))?
+            }
+            Kdf::Scrypt { log_n, r, p } => {
+                let params = scrypt::ScryptParams::new(log_n, r, p)
+                    .map_err(|_| Error::InvalidParams(*self))?;
+                let mut output = vec![0u8; 32];
+                scrypt::scrypt(
+                    passphrase.as_bytes(),
+                    &salt.serialize(),
+                    &params,
+                    &mut output,
+                )
+                .expect("32-byte output length is always valid for scrypt");
+                output
+            }
+        };
+        let key = SecretKey::from_slice(&output).map_err(|_| Error::InvalidKey);
+        output.iter_mut().for_each(|byte| *byte = 0);
+        key
+    }
+}
@@ -0,0 +1,90 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! PSBT combiner (BIP-174 role) merging partially signed copies of the same
+//! transaction produced by different cosigners. Before merging, the copies
+//! are checked to describe the same unsigned transaction and not to disagree
+//! on the spent outputs and scripts of the inputs, which the BIP-174 merge
+//! procedure would otherwise resolve silently in favour of the first copy.
+
+use bitcoin::util::psbt::{self, Input, PartiallySignedTransaction};
+use bitcoin::Txid;
+
+/// Errors of combining PSBTs
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum Error {
+    /// No PSBTs were given to combine
+    NoPsbts,
+
+    /// PSBT #{0} spends transaction {1}, while the first PSBT spends {2}
+    TxMismatch(usize, Txid, Txid),
+
+    /// PSBT #{0} has {1} for input #{2} different from the first PSBT
+    InputMismatch(usize, &'static str, usize),
+
+    /// Unable to merge PSBT #{0}: {1}
+    Merge(usize, String),
+}
+
+/// Combines partially signed copies of the same transaction into a single
+/// PSBT containing signatures and other data from all of them. PSBT numbers
+/// in the returned errors start with zero.
+pub fn combine(
+    psbts: impl IntoIterator<Item = PartiallySignedTransaction>,
+) -> Result<PartiallySignedTransaction, Error> {
+    let mut psbts = psbts.into_iter();
+    let mut combined = psbts.next().ok_or(Error::NoPsbts)?;
+    let txid = combined.global.unsigned_tx.txid();
+    for (no, psbt) in psbts.enumerate().map(|(no, psbt)| (no + 1, psbt)) {
+        // Unsigned transaction has empty script sigs and no witnesses, so its
+        // txid commits to all transaction data
+        let other_txid = psbt.global.unsigned_tx.txid();
+        if other_txid != txid {
+            return Err(Error::TxMismatch(no, other_txid, txid));
+        }
+        for (index, (input, other)) in
+            combined.inputs.iter().zip(&psbt.inputs).enumerate()
+        {
+            if let Some(field) = input_conflict(input, other) {
+                return Err(Error::InputMismatch(no, field, index));
+            }
+        }
+        combined
+            .merge(psbt)
+            .map_err(|err: psbt::Error| Error::Merge(no, err.to_string()))?;
+    }
+    Ok(combined)
+}
+
+/// Returns name of the input field which is present in both inputs with
+/// different values
+fn input_conflict(input: &Input, other: &Input) -> Option<&'static str> {
+    fn differs<T: PartialEq>(a: &Option<T>, b: &Option<T>) -> bool {
+        matches!((a, b), (Some(a), Some(b)) if a != b)
+    }
+
+    if differs(&input.non_witness_utxo, &other.non_witness_utxo) {
+        Some("non-witness UTXO")
+    } else if differs(&input.witness_utxo, &other.witness_utxo) {
+        Some("witness UTXO")
+    } else if differs(&input.redeem_script, &other.redeem_script) {
+        Some("redeem script")
+    } else if differs(&input.witness_script, &other.witness_script) {
+        Some("witness script")
+    } else if differs(&input.sighash_type, &other.sighash_type) {
+        Some("sighash type")
+    } else {
+        None
+    }
+}
@@ -0,0 +1,223 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Request authorization with HMAC-based challenge/response. Client obtains
+//! a single-use challenge issued by the daemon for the client name and
+//! computes auth code as HMAC-SHA256 over the challenge and the request
+//! payload, keyed with the client secret shared with the daemon. The auth
+//! code names the client and the challenge it answers, so the daemon checks
+//! it against a single secret and challenge.
+//!
+//! Clients which can't afford additional roundtrip may use time-stamped
+//! authorization instead: the challenge is derived from the current UNIX
+//! time in seconds (see [`timestamp_challenge`]) and the daemon accepts auth
+//! codes for timestamps within [`TIMESTAMP_TOLERANCE`] from its own clock,
+//! each of which must be greater than the previous timestamp used by the
+//! client. [`NonceGenerator`] produces such timestamps.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bitcoin::hashes::{hmac, sha256, Hash, HashEngine};
+use internet2::TypedEnum;
+
+use super::types::AuthCode;
+use super::{FailureCode, Request};
+
+/// Code of [`microservices::rpc::Failure`] returned by the daemon when the
+/// request auth code does not match
+pub const AUTH_FAILURE_CODE: u16 = FailureCode::Unauthorized as u16;
+
+/// Challenge issued by the daemon for a single authorized request
+pub type Challenge = sha256::Hash;
+
+/// Maximal difference between the client and daemon clocks accepted for the
+/// time-stamped authorization, in seconds
+pub const TIMESTAMP_TOLERANCE: u64 = 30;
+
+/// Derives challenge for the time-stamped authorization from the
+/// `timestamp`, which is a number of seconds since UNIX epoch
+pub fn timestamp_challenge(timestamp: u64) -> Challenge {
+    let mut engine = sha256::Hash::engine();
+    engine.input(b"keyring-auth-timestamp");
+    engine.input(&timestamp.to_be_bytes());
+    sha256::Hash::from_engine(engine)
+}
+
+/// Returns current UNIX time in seconds
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Client-side generator of the time-stamped nonces. The daemon rejects
+/// timestamps which are not greater than the previous one used by the
+/// client, so the generator never returns the same timestamp twice, running
+/// ahead of the clock if the requests are sent more often than once per
+/// second.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct NonceGenerator {
+    last: u64,
+}
+
+impl NonceGenerator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns next timestamp, which is the current UNIX time or the
+    /// previous timestamp plus one second, whichever is greater
+    pub fn next(&mut self) -> u64 {
+        self.last = unix_time().max(self.last + 1);
+        self.last
+    }
+
+    /// Sets the request auth code of the `client` for the next timestamp,
+    /// returning the timestamp used. Does nothing for requests which do not
+    /// require authorization.
+    pub fn authorize(
+        &mut self,
+        request: &mut Request,
+        client: &str,
+        secret: &[u8],
+    ) -> u64 {
+        let timestamp = self.next();
+        request.authorize(secret, AuthCode::with_timestamp(client, timestamp));
+        timestamp
+    }
+}
+
+impl AuthCode {
+    /// Constructs auth code of the `client` answering the `challenge` issued
+    /// by the daemon; the HMAC is set by [`Request::authorize`]
+    pub fn with_challenge(client: &str, challenge: Challenge) -> Self {
+        AuthCode {
+            client: client.to_owned(),
+            challenge,
+            ..AuthCode::default()
+        }
+    }
+
+    /// Constructs time-stamped auth code of the `client`; the HMAC is set by
+    /// [`Request::authorize`]
+    pub fn with_timestamp(client: &str, timestamp: u64) -> Self {
+        AuthCode {
+            client: client.to_owned(),
+            challenge: timestamp_challenge(timestamp),
+            timestamp: Some(timestamp),
+            ..AuthCode::default()
+        }
+    }
+}
+
+impl Request {
+    /// Returns mutable reference to the request auth code, or
+    /// [`Option::None`] for requests which do not require authorization
+    pub fn auth_code_mut(&mut self) -> Option<&mut AuthCode> {
+        Some(match self {
+            Request::Unlock(req) => &mut req.auth_code,
+            Request::Lock(req) => &mut req.auth_code,
+            Request::Sealed(req) => &mut req.auth_code,
+            Request::Tagged(req) => &mut req.auth_code,
+            Request::Reconfigure(req) => &mut req.auth_code,
+            Request::Routed(req) => &mut req.auth_code,
+            Request::Seed(req) => &mut req.auth_code,
+            Request::DeleteKeyring(req) => &mut req.auth_code,
+            Request::ImportDescriptors(req) => &mut req.auth_code,
+            Request::ImportXpub(req) => &mut req.auth_code,
+            Request::ImportXpriv(req) => &mut req.auth_code,
+            Request::Restore(req) => &mut req.auth_code,
+            Request::ExportXpub(req) => &mut req.auth_code,
+            Request::ApproveExport(req) => &mut req.auth_code,
+            Request::ExportXpriv(req) => &mut req.auth_code,
+            Request::ExportDescriptor(req) => &mut req.auth_code,
+            Request::IdentityKey(req) => &mut req.auth_code,
+            Request::DeriveEntropy(req) => &mut req.auth_code,
+            Request::Backup(req) => &mut req.auth_code,
+            Request::ExportLedger(req) => &mut req.auth_code,
+            Request::Derive(req) => &mut req.auth_code,
+            Request::DeleteAccount(req) => &mut req.auth_code,
+            Request::SetLifecycle(req) => &mut req.auth_code,
+            Request::DeriveRange(req) => &mut req.auth_code,
+            Request::SetBranches(req) => &mut req.auth_code,
+            Request::CommitSandbox(req) => &mut req.auth_code,
+            Request::DiscardSandbox(req) => &mut req.auth_code,
+            Request::Discover(req) => &mut req.auth_code,
+            Request::SignPsbt(req) => &mut req.auth_code,
+            Request::SignKey(req) => &mut req.auth_code,
+            Request::SignData(req) => &mut req.auth_code,
+            Request::SignIdentity(req) => &mut req.auth_code,
+            Request::SignMessage(req) => &mut req.auth_code,
+            Request::ComposePsbt(req) => &mut req.auth_code,
+            Request::AnalyzePsbt(req) => &mut req.auth_code,
+            Request::SetPolicy(req) => &mut req.auth_code,
+            Request::LoadVault(req) => &mut req.auth_code,
+            Request::StoreVault(req) => &mut req.auth_code,
+            Request::SetLabel(req) => &mut req.auth_code,
+            Request::UpdateAccount(req) => &mut req.auth_code,
+            Request::CreateMultisig(req) => &mut req.auth_code,
+            Request::ExportMultisig(req) => &mut req.auth_code,
+            Request::AppendRevocation(req) => &mut req.auth_code,
+            Request::QueryRevocation(req) => &mut req.auth_code,
+            Request::CompactRevocations(req) => &mut req.auth_code,
+            Request::MuSigStartSession(req) => &mut req.auth_code,
+            Request::MuSigNonceExchange(req) => &mut req.auth_code,
+            Request::MuSigPartialSign(req) => &mut req.auth_code,
+            Request::DerivePaymentCode(req) => &mut req.auth_code,
+            Request::DeriveLnKeySet(req) => &mut req.auth_code,
+            Request::Ecdh(req) => &mut req.auth_code,
+            Request::SignInvoice(req) => &mut req.auth_code,
+            Request::SignChannelAnnouncement(req) => &mut req.auth_code,
+            Request::SignGossip(req) => &mut req.auth_code,
+            Request::ExportVault(req) => &mut req.auth_code,
+            Request::ImportVault(req) => &mut req.auth_code,
+            _ => return None,
+        })
+    }
+
+    /// Computes HMAC of the request auth code using the client `secret`.
+    /// The HMAC is computed over the challenge named by the auth code and the
+    /// request serialization with zero HMAC, so it commits to the client
+    /// name and the challenge as well.
+    pub fn compute_auth_hmac(&self, secret: &[u8]) -> sha256::Hash {
+        let mut request = self.clone();
+        let challenge = match request.auth_code_mut() {
+            Some(auth_code) => {
+                auth_code.hmac = sha256::Hash::from_inner([0u8; 32]);
+                auth_code.challenge
+            }
+            None => sha256::Hash::from_inner([0u8; 32]),
+        };
+        let mut engine = hmac::HmacEngine::<sha256::Hash>::new(secret);
+        engine.input(&challenge[..]);
+        engine.input(&request.serialize());
+        sha256::Hash::from_inner(
+            hmac::Hmac::<sha256::Hash>::from_engine(engine).into_inner(),
+        )
+    }
+
+    /// Sets request `auth_code` with HMAC computed by
+    /// [`Request::compute_auth_hmac`]. Does nothing for requests which do
+    /// not require authorization.
+    pub fn authorize(&mut self, secret: &[u8], auth_code: AuthCode) {
+        match self.auth_code_mut() {
+            Some(code) => *code = auth_code,
+            None => return,
+        }
+        let hmac = self.compute_auth_hmac(secret);
+        if let Some(code) = self.auth_code_mut() {
+            code.hmac = hmac;
+        }
+    }
+}
@@ -16,24 +16,125 @@ use bitcoin;
 use microservices::rpc::Failure;
 
 #[derive(Clone, Debug, Display, Error, From)]
-#[display(Debug)]
+#[display(doc_comments)]
 pub enum Error {
+    /// Unexpected server response
     UnexpectedServerResponse,
 
+    /// I/O error: {0}
     #[from(std::io::Error)]
     Io(IoError),
 
+    /// Bitcoin data encoding error
     #[from(bitcoin::consensus::encode::Error)]
     Encoding,
 
+    /// Server returned failure: {0}
     #[from]
     ServerFailure(Failure),
 
+    /// Error in RPC message presentation: {0}
     #[from]
     PresentationError(internet2::presentation::Error),
 
+    /// RPC transport error: {0}
     #[from]
     TransportError(internet2::transport::Error),
+
+    /// Encrypted channel handshake failed: {0}
+    Handshake(String),
+
+    /// Daemon node id is not configured; it is required to establish
+    /// encrypted channel, seal requests and check daemon attestation
+    DaemonIdRequired,
+
+    /// Unable to decrypt server reply
+    Decryption,
+
+    /// Sealed payload error: {0}
+    #[from]
+    Sealed(super::sealed::Error),
+
+    /// Tagged payload error: {0}
+    #[from]
+    Tagged(super::tagged::Error),
+
+    /// Routed payload error: {0}
+    #[from]
+    Routed(super::routed::Error),
+
+    /// Daemon implements RPC protocol version {0}, while the client requires
+    /// versions {1} to {2}; please upgrade the daemon
+    IncompatibleProtocol(u16, u16, u16),
+
+    /// Daemon does not support {0} feature required by the client
+    /// configuration
+    FeatureUnsupported(super::types::Feature),
+
+    /// Daemon attestation is not bound to the daemon node id and the nonce
+    /// of the request; the quote may be replayed or produced for another
+    /// daemon
+    AttestationBinding,
+
+    /// Attestation quote is rejected by the verifier: {0}
+    AttestationRejected(String),
+
+    /// Secret encryption error: {0}
+    #[cfg(any(feature = "node", feature = "client"))]
+    Crypto(crate::crypto::Error),
+
+    /// Message signature error: {0}
+    #[cfg(any(feature = "node", feature = "client"))]
+    MessageSignature(crate::signed_message::Error),
+
+    /// PSBT combiner error: {0}
+    #[cfg(any(feature = "node", feature = "client"))]
+    PsbtCombine(crate::psbt::Error),
+
+    /// Unable to read vault snapshot {0}: {1}
+    #[cfg(feature = "node")]
+    VaultSnapshot(String, String),
+
+    /// Vault journal {0} is not consistent: {1}
+    #[cfg(feature = "node")]
+    Journal(String, String),
+
+    /// Unable to write example vault {0}: {1}
+    #[cfg(feature = "node")]
+    ExampleVault(String, String),
+
+    /// Unable to render QR code or BC-UR: {0}
+    #[cfg(feature = "cli")]
+    Rendering(String),
+
+    /// Interactive shell error: {0}
+    #[cfg(feature = "cli")]
+    Shell(String),
+
+    /// Unable to format command output: {0}
+    #[cfg(feature = "cli")]
+    Output(String),
+}
+
+#[cfg(any(feature = "node", feature = "client"))]
+impl From<crate::signed_message::Error> for Error {
+    fn from(err: crate::signed_message::Error) -> Self {
+        Error::MessageSignature(err)
+    }
+}
+
+#[cfg(any(feature = "node", feature = "client"))]
+impl From<crate::psbt::Error> for Error {
+    fn from(err: crate::psbt::Error) -> Self {
+        Error::PsbtCombine(err)
+    }
+}
+
+#[cfg(any(feature = "node", feature = "client"))]
+impl From<crate::crypto::Error> for Error {
+    fn from(err: crate::crypto::Error) -> Self {
+        Error::Crypto(err)
+    }
 }
 
 impl microservices::error::Error for Error {}
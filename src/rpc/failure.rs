@@ -0,0 +1,320 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Codes of [`microservices::rpc::Failure`] returned by the daemon. The codes
+//! are part of the RPC protocol and are never reassigned; new codes may be
+//! added in the future, so clients must handle unknown codes as
+//! [`FailureCode::Other`]. The high byte of a code denotes its class:
+//!
+//! * `0x00` - unclassified failure;
+//! * `0x01` - RPC transport and message encoding failures;
+//! * `0x02` - key management failures of the vault;
+//! * `0x03` - vault storage driver failures;
+//! * `0x04` - authorization and signing policy failures;
+//! * `0x05` - daemon configuration and job failures.
+//!
+//! The failure info is a human-readable description of the error, which is
+//! not a subject for parsing by the clients.
+
+use std::convert::TryFrom;
+
+#[cfg(any(feature = "server", feature = "embedded"))]
+use crate::error::RuntimeError;
+#[cfg(any(feature = "server", feature = "embedded"))]
+use crate::psbt;
+#[cfg(feature = "_vault")]
+use crate::vault::{driver, keymgm};
+#[cfg(any(feature = "server", feature = "embedded"))]
+use crate::vault::{interchange, multisig, session};
+
+/// Code of [`microservices::rpc::Failure`] returned by the daemon
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Display)]
+#[display(doc_comments)]
+#[repr(u16)]
+#[non_exhaustive]
+pub enum FailureCode {
+    /// unclassified failure
+    Other = 0x0000,
+
+    /// RPC transport failure
+    Transport = 0x0101,
+
+    /// malformed RPC message
+    Message = 0x0102,
+
+    /// encrypted channel or sealed request can't be processed
+    Encryption = 0x0103,
+
+    /// request must be sent over encrypted channel or sealed
+    EncryptionRequired = 0x0104,
+
+    /// client protocol version is not supported by the daemon
+    IncompatibleProtocol = 0x0105,
+
+    /// key management failure
+    KeyManagement = 0x0200,
+
+    /// account, keyring or other requested item is not found
+    NotFound = 0x0201,
+
+    /// decryption key does not match the encrypted private key
+    DecryptionKey = 0x0202,
+
+    /// derivation path or key is already used in the vault
+    AlreadyExists = 0x0203,
+
+    /// derivation path is invalid for the requested operation
+    InvalidDerivation = 0x0204,
+
+    /// operation is not allowed at the current account lifecycle stage
+    Lifecycle = 0x0205,
+
+    /// vault is locked
+    VaultLocked = 0x0206,
+
+    /// session is not known or has expired
+    UnknownSession = 0x0207,
+
+    /// passphrase is wrong or does not satisfy the passphrase policy
+    Passphrase = 0x0208,
+
+    /// PSBT belongs to a network other than the chain of the signing keyring
+    ChainMismatch = 0x0209,
+
+    /// PSBTs to combine describe different transactions or disagree on the
+    /// data of the same input
+    PsbtMismatch = 0x020A,
+
+    /// passphrase of the vault holding no private keys must be confirmed by
+    /// repeating the unlock request
+    PassphraseConfirmation = 0x020B,
+
+    /// vault storage failure
+    Storage = 0x0300,
+
+    /// vault data are corrupted
+    Corrupted = 0x0301,
+
+    /// vault data were modified outside of the daemon
+    Tampered = 0x0302,
+
+    /// vault format is not supported
+    UnsupportedFormat = 0x0303,
+
+    /// request auth code does not match
+    Unauthorized = 0x0401,
+
+    /// request type is not supported by the daemon; failure info contains
+    /// the request type id and the range of supported protocol versions
+    UnsupportedRequest = 0x0402,
+
+    /// operation requires private key of a watch-only account
+    WatchOnly = 0x0403,
+
+    /// operation violates signing policy of some account; failure info
+    /// lists the violated policy rules
+    PolicyViolation = 0x0404,
+
+    /// daemon is running in read-only mode
+    ReadOnly = 0x0405,
+
+    /// export of private keys is not allowed or not approved
+    ExportDenied = 0x0406,
+
+    /// requested feature is disabled in the daemon configuration
+    Disabled = 0x0500,
+
+    /// job is not known or is already queued
+    Job = 0x0501,
+
+    /// daemon is shutting down
+    ShuttingDown = 0x0502,
+
+    /// configuration can't be reloaded or changes settings which require
+    /// restarting the daemon
+    Configuration = 0x0503,
+}
+
+impl From<FailureCode> for u16 {
+    fn from(code: FailureCode) -> Self {
+        code as u16
+    }
+}
+
+impl TryFrom<u16> for FailureCode {
+    type Error = u16;
+
+    fn try_from(code: u16) -> Result<Self, Self::Error> {
+        Ok(match code {
+            0x0000 => FailureCode::Other,
+            0x0101 => FailureCode::Transport,
+            0x0102 => FailureCode::Message,
+            0x0103 => FailureCode::Encryption,
+            0x0104 => FailureCode::EncryptionRequired,
+            0x0105 => FailureCode::IncompatibleProtocol,
+            0x0200 => FailureCode::KeyManagement,
+            0x0201 => FailureCode::NotFound,
+            0x0202 => FailureCode::DecryptionKey,
+            0x0203 => FailureCode::AlreadyExists,
+            0x0204 => FailureCode::InvalidDerivation,
+            0x0205 => FailureCode::Lifecycle,
+            0x0206 => FailureCode::VaultLocked,
+            0x0207 => FailureCode::UnknownSession,
+            0x0208 => FailureCode::Passphrase,
+            0x0209 => FailureCode::ChainMismatch,
+            0x020A => FailureCode::PsbtMismatch,
+            0x020B => FailureCode::PassphraseConfirmation,
+            0x0300 => FailureCode::Storage,
+            0x0301 => FailureCode::Corrupted,
+            0x0302 => FailureCode::Tampered,
+            0x0303 => FailureCode::UnsupportedFormat,
+            0x0401 => FailureCode::Unauthorized,
+            0x0402 => FailureCode::UnsupportedRequest,
+            0x0403 => FailureCode::WatchOnly,
+            0x0404 => FailureCode::PolicyViolation,
+            0x0405 => FailureCode::ReadOnly,
+            0x0406 => FailureCode::ExportDenied,
+            0x0500 => FailureCode::Disabled,
+            0x0501 => FailureCode::Job,
+            0x0502 => FailureCode::ShuttingDown,
+            0x0503 => FailureCode::Configuration,
+            unknown => return Err(unknown),
+        })
+    }
+}
+
+impl From<&internet2::presentation::Error> for FailureCode {
+    fn from(_: &internet2::presentation::Error) -> Self {
+        FailureCode::Message
+    }
+}
+
+#[cfg(feature = "_vault")]
+impl From<&keymgm::Error> for FailureCode {
+    fn from(err: &keymgm::Error) -> Self {
+        match err {
+            keymgm::Error::NotFound | keymgm::Error::UnknownContact(_) => {
+                FailureCode::NotFound
+            }
+            keymgm::Error::SecretKeyCorrupted => FailureCode::DecryptionKey,
+            keymgm::Error::DerivationAlreadyUsed
+            | keymgm::Error::KnownKey(_) => FailureCode::AlreadyExists,
+            keymgm::Error::HardenedDerivation
+            | keymgm::Error::DerivationRange(_) => {
+                FailureCode::InvalidDerivation
+            }
+            keymgm::Error::LifecycleRestriction(..)
+            | keymgm::Error::LifecycleTransition(..) => FailureCode::Lifecycle,
+            keymgm::Error::WatchOnly => FailureCode::WatchOnly,
+            keymgm::Error::ChainMismatch(..) => FailureCode::ChainMismatch,
+            _ => FailureCode::KeyManagement,
+        }
+    }
+}
+
+#[cfg(feature = "_vault")]
+impl From<&driver::Error> for FailureCode {
+    fn from(err: &driver::Error) -> Self {
+        match err {
+            driver::Error::Storage(_) => FailureCode::Storage,
+            driver::Error::Corrupted(_) => FailureCode::Corrupted,
+            driver::Error::Tampered(_) => FailureCode::Tampered,
+            driver::Error::UnsupportedFormat(..) => {
+                FailureCode::UnsupportedFormat
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "server", feature = "embedded"))]
+impl From<&RuntimeError> for FailureCode {
+    fn from(err: &RuntimeError) -> Self {
+        match err {
+            RuntimeError::Transport | RuntimeError::Zmq(_) => {
+                FailureCode::Transport
+            }
+            RuntimeError::Message => FailureCode::Message,
+            RuntimeError::VaultDriver(err) => err.into(),
+            RuntimeError::KeyManagement(err) => err.into(),
+            RuntimeError::PolicyViolation(_)
+            | RuntimeError::Multisig(multisig::Error::ScriptMismatch(_)) => {
+                FailureCode::PolicyViolation
+            }
+            RuntimeError::DerivationPath(_) => FailureCode::InvalidDerivation,
+            RuntimeError::NoChainSource
+            | RuntimeError::BackupsDisabled
+            | RuntimeError::LedgerDisabled
+            | RuntimeError::RevocationsDisabled
+            | RuntimeError::AttestationDisabled
+            | RuntimeError::EncryptionDisabled
+            | RuntimeError::SealingDisabled
+            | RuntimeError::FederationDisabled => FailureCode::Disabled,
+            RuntimeError::UnknownJob | RuntimeError::DuplicateJob => {
+                FailureCode::Job
+            }
+            RuntimeError::ShuttingDown => FailureCode::ShuttingDown,
+            RuntimeError::Reconfiguration(_)
+            | RuntimeError::ImmutableSettings(_) => FailureCode::Configuration,
+            RuntimeError::Passphrase(_)
+            | RuntimeError::Interchange(interchange::Error::Passphrase(_))
+            | RuntimeError::Interchange(interchange::Error::Crypto(_))
+            | RuntimeError::Interchange(
+                interchange::Error::PassphraseRequired,
+            ) => FailureCode::Passphrase,
+            RuntimeError::PassphraseConfirmation => {
+                FailureCode::PassphraseConfirmation
+            }
+            RuntimeError::Interchange(interchange::Error::Version(_))
+            | RuntimeError::Interchange(interchange::Error::Encryption(_))
+            | RuntimeError::Interchange(interchange::Error::Kdf(_)) => {
+                FailureCode::UnsupportedFormat
+            }
+            RuntimeError::Interchange(_) => FailureCode::Corrupted,
+            RuntimeError::VaultLocked
+            | RuntimeError::Session(session::Error::SandboxRequiresSession) => {
+                FailureCode::VaultLocked
+            }
+            RuntimeError::Session(session::Error::UnknownSession(_)) => {
+                FailureCode::UnknownSession
+            }
+            RuntimeError::Unauthorized => FailureCode::Unauthorized,
+            RuntimeError::EncryptionRequired
+            | RuntimeError::SealingRequired => FailureCode::EncryptionRequired,
+            RuntimeError::Handshake(_)
+            | RuntimeError::UnknownChannel
+            | RuntimeError::Decryption
+            | RuntimeError::Sealed(_) => FailureCode::Encryption,
+            RuntimeError::Tagged(_) | RuntimeError::Routed(_) => {
+                FailureCode::Message
+            }
+            RuntimeError::UnknownVault(_) => FailureCode::NotFound,
+            RuntimeError::IdempotencyConflict => FailureCode::AlreadyExists,
+            RuntimeError::SecretExportDisabled
+            | RuntimeError::ExportNotAllowed(_)
+            | RuntimeError::ExportNotApproved => FailureCode::ExportDenied,
+            RuntimeError::UnsupportedRequest(..) => {
+                FailureCode::UnsupportedRequest
+            }
+            RuntimeError::IncompatibleClient(..) => {
+                FailureCode::IncompatibleProtocol
+            }
+            RuntimeError::ReadOnly => FailureCode::ReadOnly,
+            RuntimeError::KeyPrefix(_) => FailureCode::ChainMismatch,
+            RuntimeError::PsbtCombine(psbt::Error::TxMismatch(..))
+            | RuntimeError::PsbtCombine(psbt::Error::InputMismatch(..)) => {
+                FailureCode::PsbtMismatch
+            }
+            _ => FailureCode::Other,
+        }
+    }
+}
@@ -0,0 +1,412 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! gRPC interface exposing the main vault operations to clients which do not
+//! implement strict encoding of the ZMQ RPC messages. The service is defined
+//! in `api/keyring.proto`; each call is converted into the ZMQ RPC
+//! [`Request`] and served by the same [`Handler`] as the ZMQ requests, so
+//! authorization, read-only mode and logging apply to both interfaces.
+
+use std::convert::TryFrom;
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+
+use bitcoin::consensus::{deserialize, serialize};
+use bitcoin::hashes::hex::FromHex;
+use bitcoin::secp256k1::{self, SecretKey};
+use tokio::sync::oneshot;
+use tonic::metadata::MetadataMap;
+use tonic::{Code, Response, Status};
+
+use super::types::{self, SessionToken};
+use super::{message, FailureCode, Reply, Request};
+
+/// Types and service stubs generated from `api/keyring.proto`
+pub mod proto {
+    tonic::include_proto!("keyring");
+}
+
+use proto::keyring_server::{Keyring, KeyringServer};
+
+/// Processor of the requests received over gRPC
+pub trait Handler: Send + Sync + 'static {
+    /// Serves `request`, which is authorized with the client `secret` taken
+    /// from the call metadata instead of the request auth code
+    fn handle(&self, request: Request, secret: Option<Vec<u8>>) -> Reply;
+}
+
+/// Running gRPC server
+pub struct Server {
+    shutdown: oneshot::Sender<()>,
+    thread: thread::JoinHandle<()>,
+}
+
+impl Server {
+    /// Starts gRPC server on `endpoint` in a separate thread with its own
+    /// asynchronous runtime; requests are served by the `handler` on the
+    /// runtime blocking threads
+    pub fn spawn(
+        endpoint: SocketAddr,
+        handler: Arc<dyn Handler>,
+    ) -> Result<Self, std::io::Error> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("grpc")
+            .build()?;
+        let (shutdown, signal) = oneshot::channel::<()>();
+        let thread = thread::Builder::new().name("grpc".to_owned()).spawn(
+            move || {
+                let server = tonic::transport::Server::builder()
+                    .add_service(KeyringServer::new(Service { handler }))
+                    .serve_with_shutdown(endpoint, async {
+                        signal.await.ok();
+                    });
+                if let Err(err) = runtime.block_on(server) {
+                    error!("gRPC server failure: {}", err);
+                }
+            },
+        )?;
+        info!("gRPC interface is listening on {}", endpoint);
+        if !endpoint.ip().is_loopback() {
+            warn!(
+                "gRPC interface is not bound to the loopback interface; client \
+                 secrets are sent unencrypted unless TLS is terminated by a proxy"
+            );
+        }
+        Ok(Server { shutdown, thread })
+    }
+
+    /// Stops accepting new calls and waits for the calls in flight
+    pub fn shutdown(self) {
+        self.shutdown.send(()).ok();
+        if self.thread.join().is_err() {
+            error!("gRPC server thread has panicked");
+        }
+    }
+}
+
+struct Service {
+    handler: Arc<dyn Handler>,
+}
+
+impl Service {
+    async fn call(
+        &self,
+        metadata: &MetadataMap,
+        request: Request,
+    ) -> Result<Reply, Status> {
+        let secret = bearer(metadata)?;
+        let handler = self.handler.clone();
+        let reply = tokio::task::spawn_blocking(move || {
+            handler.handle(request, secret)
+        })
+        .await
+        .map_err(|err| Status::internal(err.to_string()))?;
+        match reply {
+            Reply::Failure(failure) => {
+                let code = match FailureCode::try_from(failure.code) {
+                    Ok(FailureCode::Unauthorized)
+                    | Ok(FailureCode::UnknownSession) => Code::Unauthenticated,
+                    Ok(FailureCode::PolicyViolation)
+                    | Ok(FailureCode::ExportDenied)
+                    | Ok(FailureCode::DecryptionKey) => Code::PermissionDenied,
+                    Ok(FailureCode::UnsupportedRequest)
+                    | Ok(FailureCode::IncompatibleProtocol)
+                    | Ok(FailureCode::Disabled) => Code::Unimplemented,
+                    Ok(FailureCode::WatchOnly)
+                    | Ok(FailureCode::VaultLocked)
+                    | Ok(FailureCode::ReadOnly)
+                    | Ok(FailureCode::Lifecycle) => Code::FailedPrecondition,
+                    Ok(FailureCode::NotFound) => Code::NotFound,
+                    Ok(FailureCode::AlreadyExists) => Code::AlreadyExists,
+                    Ok(FailureCode::InvalidDerivation) => Code::InvalidArgument,
+                    Ok(FailureCode::ShuttingDown) => Code::Unavailable,
+                    _ => Code::Aborted,
+                };
+                Err(Status::new(code, failure.info))
+            }
+            reply => Ok(reply),
+        }
+    }
+}
+
+/// Extracts client secret from `authorization: Bearer <hex>` metadata
+fn bearer(metadata: &MetadataMap) -> Result<Option<Vec<u8>>, Status> {
+    let value = match metadata.get("authorization") {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    value
+        .to_str()
+        .ok()
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|secret| Vec::<u8>::from_hex(secret.trim()).ok())
+        .map(Some)
+        .ok_or_else(|| {
+            Status::unauthenticated("malformed authorization metadata")
+        })
+}
+
+fn parse<T>(field: &str, value: &str) -> Result<T, Status>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value.parse().map_err(|err| {
+        Status::invalid_argument(format!("invalid `{}`: {}", field, err))
+    })
+}
+
+/// Parses optional field, where an empty string stands for a missing value
+fn parse_optional<T>(field: &str, value: &str) -> Result<Option<T>, Status>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match value {
+        "" => Ok(None),
+        value => parse(field, value).map(Some),
+    }
+}
+
+/// Decryption key and session token for the requests using private keys.
+/// If no key is given, a dummy key is used, so unless the session is
+/// provided the request fails for the vault encrypted with the node key.
+fn unlock(
+    unlock: Option<proto::Unlock>,
+) -> Result<(SecretKey, Option<SessionToken>), Status> {
+    let unlock = unlock.unwrap_or_default();
+    let session = match unlock.session.as_str() {
+        "" => None,
+        token => Some(parse("session", token)?),
+    };
+    let decryption_key = match unlock.decryption_key.as_str() {
+        "" => secp256k1::key::ONE_KEY,
+        key => parse("decryption_key", key)?,
+    };
+    Ok((decryption_key, session))
+}
+
+fn unexpected() -> Status {
+    Status::internal("unexpected daemon reply")
+}
+
+impl From<types::AccountInfo> for proto::AccountInfo {
+    fn from(info: types::AccountInfo) -> Self {
+        proto::AccountInfo {
+            id: info.id.to_string(),
+            name: info.name,
+            details: info.details.unwrap_or_default(),
+            key_id: info.key_id.to_string(),
+            fingerprint: info.fingerprint.to_string(),
+            assets: info.assets.iter().map(ToString::to_string).collect(),
+            application: info
+                .application
+                .map(|application| format!("{:?}", application))
+                .unwrap_or_default(),
+            key_source: info
+                .key_source
+                .map(|(fingerprint, path)| format!("[{}]{}", fingerprint, path))
+                .unwrap_or_default(),
+            lifecycle: info.lifecycle.to_string(),
+            watch_only: info.watch_only,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Keyring for Service {
+    async fn status(
+        &self,
+        request: tonic::Request<proto::StatusRequest>,
+    ) -> Result<Response<proto::StatusReply>, Status> {
+        match self.call(request.metadata(), Request::Status).await? {
+            Reply::Status(status) => Ok(Response::new(proto::StatusReply {
+                config_fingerprint: status.config_fingerprint.to_string(),
+                protocol_version: status.protocol_version as u32,
+                uptime: status.uptime,
+                driver: status.driver,
+                keyrings: status.keyrings,
+                accounts: status.accounts,
+                locked: status.locked,
+            })),
+            _ => Err(unexpected()),
+        }
+    }
+
+    async fn list(
+        &self,
+        request: tonic::Request<proto::ListRequest>,
+    ) -> Result<Response<proto::ListReply>, Status> {
+        match self.call(request.metadata(), Request::List).await? {
+            Reply::Keylist(accounts) => Ok(Response::new(proto::ListReply {
+                accounts: accounts.into_iter().map(From::from).collect(),
+            })),
+            _ => Err(unexpected()),
+        }
+    }
+
+    async fn seed(
+        &self,
+        request: tonic::Request<proto::SeedRequest>,
+    ) -> Result<Response<proto::SeedReply>, Status> {
+        let (metadata, seed) = (request.metadata(), request.get_ref());
+        let description = match seed.description.as_str() {
+            "" => None,
+            description => Some(description.to_owned()),
+        };
+        let message = message::Seed {
+            name: seed.name.clone(),
+            chain: parse("chain", &seed.chain)?,
+            application: parse("application", &seed.application)?,
+            description,
+            idempotency_key: parse_optional(
+                "idempotency_key",
+                &seed.idempotency_key,
+            )?,
+            auth_code: types::AuthCode::default(),
+        };
+        match self.call(metadata, Request::Seed(message)).await? {
+            Reply::AccountInfo(info) => Ok(Response::new(proto::SeedReply {
+                account: Some(info.into()),
+            })),
+            _ => Err(unexpected()),
+        }
+    }
+
+    async fn derive(
+        &self,
+        request: tonic::Request<proto::DeriveRequest>,
+    ) -> Result<Response<proto::AccountInfo>, Status> {
+        let (metadata, derive) = (request.metadata(), request.get_ref());
+        let (decryption_key, session) = unlock(derive.unlock.clone())?;
+        let message = message::Derive {
+            from: parse("from", &derive.from)?,
+            path: parse("path", &derive.path)?,
+            name: derive.name.clone(),
+            details: derive.details.clone(),
+            assets: derive
+                .assets
+                .iter()
+                .map(|asset| parse("assets", asset))
+                .collect::<Result<_, _>>()?,
+            sandbox: false,
+            idempotency_key: parse_optional(
+                "idempotency_key",
+                &derive.idempotency_key,
+            )?,
+            decryption_key,
+            session,
+            auth_code: types::AuthCode::default(),
+        };
+        match self.call(metadata, Request::Derive(message)).await? {
+            Reply::AccountInfo(info) => Ok(Response::new(info.into())),
+            _ => Err(unexpected()),
+        }
+    }
+
+    async fn export_xpub(
+        &self,
+        request: tonic::Request<proto::ExportXpubRequest>,
+    ) -> Result<Response<proto::ExtendedKey>, Status> {
+        let (metadata, export) = (request.metadata(), request.get_ref());
+        let message = message::Export {
+            key_id: parse("key_id", &export.key_id)?,
+            slip132: parse_optional("prefix", &export.prefix)?,
+            decryption_key: secp256k1::key::ONE_KEY,
+            session: None,
+            auth_code: types::AuthCode::default(),
+        };
+        match self.call(metadata, Request::ExportXpub(message)).await? {
+            Reply::XPub(xpub) => Ok(Response::new(proto::ExtendedKey {
+                key: xpub.to_string(),
+            })),
+            Reply::EncodedXPub(key) => {
+                Ok(Response::new(proto::ExtendedKey { key }))
+            }
+            _ => Err(unexpected()),
+        }
+    }
+
+    async fn export_xpriv(
+        &self,
+        request: tonic::Request<proto::ExportXprivRequest>,
+    ) -> Result<Response<proto::ExtendedKey>, Status> {
+        let (metadata, export) = (request.metadata(), request.get_ref());
+        let (decryption_key, session) = unlock(export.unlock.clone())?;
+        let message = message::ExportXpriv {
+            key_id: parse("key_id", &export.key_id)?,
+            approval: parse("approval", &export.approval)?,
+            decryption_key,
+            session,
+            auth_code: types::AuthCode::default(),
+        };
+        match self.call(metadata, Request::ExportXpriv(message)).await? {
+            #[cfg(feature = "export-secrets")]
+            Reply::XPriv(xpriv) => Ok(Response::new(proto::ExtendedKey {
+                key: xpriv.to_string(),
+            })),
+            _ => Err(unexpected()),
+        }
+    }
+
+    async fn sign_psbt(
+        &self,
+        request: tonic::Request<proto::SignPsbtRequest>,
+    ) -> Result<Response<proto::SignPsbtReply>, Status> {
+        let (metadata, sign) = (request.metadata(), request.get_ref());
+        let (decryption_key, session) = unlock(sign.unlock.clone())?;
+        let message = message::SignPsbt {
+            psbt: deserialize(&sign.psbt).map_err(|err| {
+                Status::invalid_argument(format!("invalid `psbt`: {}", err))
+            })?,
+            force: sign.force,
+            decryption_key,
+            session,
+            job: None,
+            auth_code: types::AuthCode::default(),
+        };
+        match self.call(metadata, Request::SignPsbt(message)).await? {
+            Reply::Psbt(psbt) => Ok(Response::new(proto::SignPsbtReply {
+                psbt: serialize(&psbt),
+            })),
+            _ => Err(unexpected()),
+        }
+    }
+
+    async fn sign_data(
+        &self,
+        request: tonic::Request<proto::SignDataRequest>,
+    ) -> Result<Response<proto::SignDataReply>, Status> {
+        let (metadata, sign) = (request.metadata(), request.get_ref());
+        let (decryption_key, session) = unlock(sign.unlock.clone())?;
+        let message = message::SignData {
+            key_id: parse("key_id", &sign.key_id)?,
+            data: sign.data.clone(),
+            decryption_key,
+            session,
+            auth_code: types::AuthCode::default(),
+        };
+        match self.call(metadata, Request::SignData(message)).await? {
+            Reply::Signature(signature) => {
+                Ok(Response::new(proto::SignDataReply {
+                    signature: signature.serialize_der().to_vec(),
+                }))
+            }
+            _ => Err(unexpected()),
+        }
+    }
+}
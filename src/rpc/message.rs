@@ -14,13 +14,23 @@
 use std::collections::HashSet;
 
 use bitcoin::hash_types::XpubIdentifier;
-use bitcoin::secp256k1::SecretKey;
-use bitcoin::util::bip32::DerivationPath;
+use bitcoin::hashes::sha256;
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use bitcoin::util::bip32::{
+    DerivationPath, ExtendedPrivKey, ExtendedPubKey, KeySource,
+};
 use bitcoin::util::psbt::PartiallySignedTransaction;
 use lnpbp::chain::{AssetId, Chain};
 use slip132::KeyApplication;
 
-use super::types::AuthCode;
+use super::types::{
+    ApprovalToken, AuthCode, Bip85Application, Branches, CollisionPolicy,
+    CommitmentSecret, CosignerKey, DerivationTemplate, Features,
+    IdempotencyKey, JobId, KeyPrefix, LnChannelId, MuSigNonce, MuSigSessionId,
+    MultisigId, PaymentCode, PsbtInput, PsbtOutput, RequestId, SessionToken,
+    SigningPolicy, UpdateMode, VaultId,
+};
+use crate::lifecycle::Lifecycle;
 
 #[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
 #[strict_encoding_crate(lnpbp::strict_encoding)]
@@ -30,6 +40,227 @@ pub struct Seed {
     pub chain: Chain,
     pub application: KeyApplication,
     pub description: Option<String>,
+    /// Key under which the daemon remembers the reply, so retries of the
+    /// request do not create more keyrings
+    pub idempotency_key: Option<IdempotencyKey>,
+    pub auth_code: AuthCode,
+}
+
+/// Requests platform quote attesting that the daemon runs inside a trusted
+/// execution environment. The `nonce` chosen by the client is committed to
+/// by the quote report data, so the quote can't be replayed.
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{nonce}")]
+pub struct Attest {
+    pub nonce: sha256::Hash,
+}
+
+/// Opens the client session announcing the RPC protocol version and the
+/// features used by the client; see [`super::types::Hello`]
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("v{client_version}, {features}")]
+pub struct Hello {
+    pub client_version: u16,
+    pub features: Features,
+}
+
+/// Makes the daemon re-read its configuration file and apply the settings
+/// which may be changed at runtime; served only to the clients configured
+/// as administrators
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("...")]
+pub struct Reconfigure {
+    pub auth_code: AuthCode,
+}
+
+/// Requests status of the `job` submitted by the client: its progress while
+/// the job is queued or running, and the reply to the job request once it
+/// is completed
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{job}")]
+pub struct JobStatus {
+    pub job: JobId,
+}
+
+/// Request sealed to the daemon node id with [`super::sealed::seal`]
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("...")]
+pub struct Sealed {
+    pub payload: Vec<u8>,
+    pub auth_code: AuthCode,
+}
+
+/// Request tagged by the client with [`super::tagged::tag`]; the reply to
+/// the wrapped request is returned as [`super::Reply::Tagged`] with the same
+/// `request_id`
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{request_id}")]
+pub struct Tagged {
+    pub request_id: RequestId,
+    pub payload: Vec<u8>,
+    pub auth_code: AuthCode,
+}
+
+/// Request routed by the client to the vault with `vault_id` with
+/// [`super::routed::route`]
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{vault_id}")]
+pub struct Routed {
+    pub vault_id: VaultId,
+    pub payload: Vec<u8>,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("...")]
+pub struct Unlock {
+    pub passphrase: String,
+    pub decryption_key: SecretKey,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{session}")]
+pub struct Lock {
+    pub session: SessionToken,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{gap_limit}")]
+pub struct Scan {
+    pub gap_limit: u32,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("...")]
+pub struct Backup {
+    pub job: Option<JobId>,
+    pub auth_code: AuthCode,
+}
+
+/// Requests records of the transaction ledger made at or after `since` time
+/// (in seconds since UNIX epoch)
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{since}")]
+pub struct ExportLedger {
+    pub since: u64,
+    pub auth_code: AuthCode,
+}
+
+/// Requests vault data of the backend daemon by the front daemon using remote
+/// vault driver
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("...")]
+pub struct LoadVault {
+    pub auth_code: AuthCode,
+}
+
+/// Stores per-commitment secret with `index` revealed by the counterparty of
+/// a Lightning channel, which revocation data are kept in the namespace of
+/// the account `key_id`
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}, {channel}, #{index}")]
+pub struct AppendRevocation {
+    pub key_id: XpubIdentifier,
+    pub channel: LnChannelId,
+    pub index: u64,
+    pub secret: CommitmentSecret,
+    pub auth_code: AuthCode,
+}
+
+/// Requests stored or derived per-commitment secret with `index` of a
+/// Lightning channel
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}, {channel}, #{index}")]
+pub struct QueryRevocation {
+    pub key_id: XpubIdentifier,
+    pub channel: LnChannelId,
+    pub index: u64,
+    pub auth_code: AuthCode,
+}
+
+/// Compacts revocation secret log of the account `key_id`
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}")]
+pub struct CompactRevocations {
+    pub key_id: XpubIdentifier,
+    pub auth_code: AuthCode,
+}
+
+/// Replaces vault data of the backend daemon with the data from the front
+/// daemon using remote vault driver
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("...")]
+pub struct StoreVault {
+    /// Strict-encoded keyrings
+    pub data: Vec<u8>,
+    pub auth_code: AuthCode,
+}
+
+/// Replaces vault data with the backup snapshot
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("...")]
+pub struct Restore {
+    /// Encrypted snapshot data as written to the backup file
+    pub snapshot: Vec<u8>,
+    pub job: Option<JobId>,
+    pub auth_code: AuthCode,
+}
+
+/// Requests portable dump of all vault keyrings; see
+/// [`crate::vault::interchange`]
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("...")]
+pub struct ExportVault {
+    /// Passphrase encrypting the dump, if any
+    pub passphrase: Option<String>,
+    pub auth_code: AuthCode,
+}
+
+/// Adds keyrings from the portable vault dump to the vault, or replaces all
+/// vault keyrings with them
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("replace: {replace}, ...")]
+pub struct ImportVault {
+    /// Vault dump as produced by [`ExportVault`] request
+    pub dump: Vec<u8>,
+    /// Passphrase of the encrypted dump
+    pub passphrase: Option<String>,
+    pub replace: bool,
+    pub session: Option<SessionToken>,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}, {gap_limit}, ...")]
+pub struct Discover {
+    pub key_id: XpubIdentifier,
+    pub gap_limit: u32,
+    pub decryption_key: SecretKey,
+    pub session: Option<SessionToken>,
+    pub job: Option<JobId>,
     pub auth_code: AuthCode,
 }
 
@@ -38,7 +269,135 @@ pub struct Seed {
 #[display("{key_id}, ...")]
 pub struct Export {
     pub key_id: XpubIdentifier,
+    /// SLIP-132 prefix of the exported extended public key; if given, the
+    /// key is returned as [`crate::rpc::Reply::EncodedXPub`]. Ignored by
+    /// the descriptor export.
+    pub slip132: Option<KeyPrefix>,
+    pub decryption_key: SecretKey,
+    pub session: Option<SessionToken>,
+    pub auth_code: AuthCode,
+}
+
+/// Requests one-time token approving export of the extended private key for
+/// the account `key_id`
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}")]
+pub struct ApproveExport {
+    pub key_id: XpubIdentifier,
+    pub auth_code: AuthCode,
+}
+
+/// Extended private key export, which must be approved with a token issued
+/// for [`ApproveExport`] request
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}, ...")]
+pub struct ExportXpriv {
+    pub key_id: XpubIdentifier,
+    pub approval: ApprovalToken,
+    pub decryption_key: SecretKey,
+    pub session: Option<SessionToken>,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}, {index}, ...")]
+pub struct IdentityKey {
+    pub key_id: XpubIdentifier,
+    pub index: u32,
+    pub decryption_key: SecretKey,
+    pub session: Option<SessionToken>,
+    pub auth_code: AuthCode,
+}
+
+/// Derivation of the BIP-85 child secret with a given `index` for the
+/// `application`
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}, {application}, {index}, ...")]
+pub struct DeriveEntropy {
+    pub key_id: XpubIdentifier,
+    pub application: Bip85Application,
+    pub index: u32,
+    pub decryption_key: SecretKey,
+    pub session: Option<SessionToken>,
+    pub auth_code: AuthCode,
+}
+
+/// Derivation of the BIP-47 payment code of the account with the keys of
+/// `count` payments to and from the `counterparty`, starting with `start`
+/// index. Counterparty is either given by its payment code or by the
+/// `contact` name under which the code was saved; if both are given, the
+/// code is saved under the name.
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}, {account}, {start}, {count}, ...")]
+pub struct DerivePaymentCode {
+    pub key_id: XpubIdentifier,
+    pub account: u32,
+    pub counterparty: Option<PaymentCode>,
+    pub contact: Option<String>,
+    pub start: u32,
+    pub count: u32,
     pub decryption_key: SecretKey,
+    pub session: Option<SessionToken>,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{descriptors:#?}")]
+pub struct ImportDescriptors {
+    pub descriptors: Vec<String>,
+    pub collision: CollisionPolicy,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{xpubkey}, {name}, ...")]
+pub struct ImportXpub {
+    pub xpubkey: ExtendedPubKey,
+    pub key_source: Option<KeySource>,
+    pub application: Option<KeyApplication>,
+    pub name: String,
+    pub details: Option<String>,
+    pub collision: CollisionPolicy,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{name}, ...")]
+pub struct ImportXpriv {
+    pub xprivkey: ExtendedPrivKey,
+    pub key_source: Option<KeySource>,
+    pub application: Option<KeyApplication>,
+    pub name: String,
+    pub details: Option<String>,
+    pub collision: CollisionPolicy,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}, purge: {purge}, ...")]
+pub struct Delete {
+    pub key_id: XpubIdentifier,
+    pub purge: bool,
+    pub decryption_key: SecretKey,
+    pub session: Option<SessionToken>,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}, {state}")]
+pub struct SetLifecycle {
+    pub key_id: XpubIdentifier,
+    pub state: Lifecycle,
     pub auth_code: AuthCode,
 }
 
@@ -51,16 +410,224 @@ pub struct Derive {
     pub name: String,
     pub details: String,
     pub assets: HashSet<AssetId>,
+    /// Derive the account into the session sandbox instead of the vault;
+    /// requires `session` to be present
+    pub sandbox: bool,
+    /// Key under which the daemon remembers the reply, so retries of the
+    /// request do not derive the account again
+    pub idempotency_key: Option<IdempotencyKey>,
     pub decryption_key: SecretKey,
+    pub session: Option<SessionToken>,
     pub auth_code: AuthCode,
 }
 
 #[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
 #[strict_encoding_crate(lnpbp::strict_encoding)]
-#[display("...")]
+#[display("{session}")]
+pub struct Sandbox {
+    pub session: SessionToken,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}, {template:?}, {start}, {count}")]
+pub struct DeriveRange {
+    pub key_id: XpubIdentifier,
+    /// Derivation template; if absent, the account external or internal
+    /// branch is used depending on `internal` flag
+    pub template: Option<DerivationTemplate>,
+    pub internal: bool,
+    pub start: u32,
+    pub count: u32,
+    pub job: Option<JobId>,
+    pub auth_code: AuthCode,
+}
+
+/// Derivation of the Lightning node identity key and the basepoints of the
+/// `channel` from the node keyring
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}, {channel}")]
+pub struct DeriveLnKeySet {
+    pub key_id: XpubIdentifier,
+    pub channel: u32,
+    pub auth_code: AuthCode,
+}
+
+/// ECDH of the remote node `pubkey` with the node identity key of the
+/// account, performed for the static key operations of the BOLT-8 Noise_XK
+/// handshake
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}, {pubkey}, ...")]
+pub struct Ecdh {
+    pub key_id: XpubIdentifier,
+    pub pubkey: PublicKey,
+    pub decryption_key: SecretKey,
+    pub session: Option<SessionToken>,
+    pub auth_code: AuthCode,
+}
+
+/// Signing of the BOLT-11 invoice with the node key. Invoice is given by its
+/// human-readable part and the 5-bit values of the data part, not including
+/// the signature.
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}, {hrp}, ...")]
+pub struct SignInvoice {
+    pub key_id: XpubIdentifier,
+    pub hrp: String,
+    pub data: Vec<u8>,
+    pub decryption_key: SecretKey,
+    pub session: Option<SessionToken>,
+    pub auth_code: AuthCode,
+}
+
+/// Signing of the BOLT-7 `channel_announcement` wire message of the
+/// `channel` with the node key and the channel funding key
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}, {channel}, ...")]
+pub struct SignChannelAnnouncement {
+    pub key_id: XpubIdentifier,
+    pub channel: u32,
+    pub announcement: Vec<u8>,
+    pub decryption_key: SecretKey,
+    pub session: Option<SessionToken>,
+    pub auth_code: AuthCode,
+}
+
+/// Signing of the BOLT-7 `node_announcement` or `channel_update` wire
+/// message with the node key
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}, ...")]
+pub struct SignGossip {
+    pub key_id: XpubIdentifier,
+    pub message: Vec<u8>,
+    pub decryption_key: SecretKey,
+    pub session: Option<SessionToken>,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}, {branches}")]
+pub struct SetBranches {
+    pub key_id: XpubIdentifier,
+    pub branches: Branches,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}, {policy}")]
+pub struct SetPolicy {
+    pub key_id: XpubIdentifier,
+    pub policy: SigningPolicy,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}, {label}")]
+pub struct SetLabel {
+    pub key_id: XpubIdentifier,
+    pub label: String,
+    /// Value of the label; the label is removed if no value is given
+    pub value: Option<String>,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}, ...")]
+pub struct UpdateAccount {
+    pub key_id: XpubIdentifier,
+    /// New account name; kept unchanged if not given
+    pub name: Option<String>,
+    /// New account details; kept unchanged if not given
+    pub details: Option<String>,
+    /// Assets to be added, replaced or removed according to the `mode`; the
+    /// asset list is kept unchanged if not given
+    pub assets: Option<HashSet<AssetId>>,
+    pub mode: UpdateMode,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{name}, {threshold}-of-...")]
+pub struct CreateMultisig {
+    pub name: String,
+    pub threshold: u8,
+    /// Vault accounts participating in the group; the group is kept by the
+    /// first of them
+    pub accounts: Vec<XpubIdentifier>,
+    /// External cosigner keys, following the vault accounts in the group
+    pub cosigners: Vec<CosignerKey>,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{group}")]
+pub struct ExportMultisig {
+    pub group: MultisigId,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("force: {force}, ...")]
 pub struct SignPsbt {
     pub psbt: PartiallySignedTransaction,
+    /// Signs inputs transferring RGB assets even with the accounts which
+    /// are not bound to these assets
+    pub force: bool,
     pub decryption_key: SecretKey,
+    pub session: Option<SessionToken>,
+    pub job: Option<JobId>,
+    pub auth_code: AuthCode,
+}
+
+/// Request to analyze PSBT without signing it
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("...")]
+pub struct AnalyzePsbt {
+    pub psbt: PartiallySignedTransaction,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("extract: {extract}, ...")]
+pub struct FinalizePsbt {
+    pub psbt: PartiallySignedTransaction,
+    /// Extract network-serialized transaction instead of returning finalized
+    /// PSBT; fails if some of the inputs can't be finalized
+    pub extract: bool,
+}
+
+/// Request to combine partially signed copies of the same transaction
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("...")]
+pub struct CombinePsbt {
+    /// PSBTs produced by different cosigners; all of them must have the
+    /// same unsigned transaction
+    pub psbts: Vec<PartiallySignedTransaction>,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("lock_time: {lock_time}, ...")]
+pub struct ComposePsbt {
+    pub inputs: Vec<PsbtInput>,
+    pub outputs: Vec<PsbtOutput>,
+    pub lock_time: u32,
     pub auth_code: AuthCode,
 }
 
@@ -70,6 +637,7 @@ pub struct SignPsbt {
 pub struct SignKey {
     pub key_id: XpubIdentifier,
     pub decryption_key: SecretKey,
+    pub session: Option<SessionToken>,
     pub auth_code: AuthCode,
 }
 
@@ -80,5 +648,67 @@ pub struct SignData {
     pub key_id: XpubIdentifier,
     pub data: Vec<u8>,
     pub decryption_key: SecretKey,
+    pub session: Option<SessionToken>,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}, ...")]
+pub struct SignMessage {
+    pub key_id: XpubIdentifier,
+    pub message: String,
+    pub decryption_key: SecretKey,
+    pub session: Option<SessionToken>,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}, {index}, {digest}, ...")]
+pub struct SignIdentity {
+    pub key_id: XpubIdentifier,
+    pub index: u32,
+    pub digest: sha256::Hash,
+    pub decryption_key: SecretKey,
+    pub session: Option<SessionToken>,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{key_id}, {message}, ...")]
+pub struct MuSigStartSession {
+    pub key_id: XpubIdentifier,
+    /// Keys of all participants, including the account key, in the order of
+    /// aggregation agreed between the participants
+    pub participants: Vec<PublicKey>,
+    pub message: sha256::Hash,
+    /// Whether the aggregated key has to be tweaked to become taproot output
+    /// key for a key-path spend
+    pub taproot: bool,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{id}, ...")]
+pub struct MuSigNonceExchange {
+    pub id: MuSigSessionId,
+    /// Public nonces of all participants except the vault account
+    pub nonces: Vec<MuSigNonce>,
+    pub auth_code: AuthCode,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{id}, ...")]
+pub struct MuSigPartialSign {
+    pub id: MuSigSessionId,
+    /// Partial signatures of all other participants, which are aggregated
+    /// into the final signature if given
+    pub partial_sigs: Vec<Vec<u8>>,
+    pub decryption_key: SecretKey,
+    pub session: Option<SessionToken>,
     pub auth_code: AuthCode,
 }
@@ -11,12 +11,33 @@
 // along with this software.
 // If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
 
+pub mod auth;
 mod error;
+mod failure;
+#[cfg(feature = "grpc")]
+pub mod grpc;
 pub mod message;
 mod reply;
 mod request;
+pub mod routed;
+pub mod sealed;
+pub mod tagged;
+pub mod transport;
 pub mod types;
 
 pub use error::Error;
-pub use reply::Reply;
+pub use failure::FailureCode;
+pub use reply::{
+    Reply, POLICY_VIOLATION_FAILURE_CODE, UNSUPPORTED_REQUEST_FAILURE_CODE,
+    WATCH_ONLY_FAILURE_CODE,
+};
 pub use request::Request;
+
+/// Version of the RPC protocol implemented by this crate. It must be
+/// increased each time new request or reply types are added.
+pub const PROTOCOL_VERSION: u16 = 30;
+
+/// The oldest RPC protocol version which requests are still understood by
+/// the daemon. Version 30 changed the auth code carried by the requests, so
+/// requests of the older clients can't be decoded.
+pub const MIN_PROTOCOL_VERSION: u16 = 30;
@@ -14,9 +14,27 @@
 //use bitcoin::util::bip32::{ExtendedPrivKey, ExtendedPubKey};
 use internet2::presentation::Error;
 
+use super::FailureCode;
 #[cfg(any(feature = "server", feature = "embedded"))]
 use crate::error::RuntimeError;
 
+/// Code of [`microservices::rpc::Failure`] returned by the daemon for
+/// operations requiring private key which were requested for a watch-only
+/// account
+pub const WATCH_ONLY_FAILURE_CODE: u16 = FailureCode::WatchOnly as u16;
+
+/// Code of [`microservices::rpc::Failure`] returned by the daemon for
+/// requests of unknown type. Failure info contains the request type id and
+/// the range of RPC protocol versions supported by the daemon.
+pub const UNSUPPORTED_REQUEST_FAILURE_CODE: u16 =
+    FailureCode::UnsupportedRequest as u16;
+
+/// Code of [`microservices::rpc::Failure`] returned by the daemon when PSBT
+/// violates signing policy of some account. Failure info lists the violated
+/// policy rules.
+pub const POLICY_VIOLATION_FAILURE_CODE: u16 =
+    FailureCode::PolicyViolation as u16;
+
 #[derive(Clone, Debug, Display, Api)]
 #[api(encoding = "strict")]
 #[non_exhaustive]
@@ -29,6 +47,45 @@ pub enum Reply {
     #[display("failure({0})")]
     Failure(microservices::rpc::Failure),
 
+    #[api(type = 0x0104)]
+    #[display("status({0})")]
+    Status(crate::rpc::types::Status),
+
+    #[api(type = 0x0106)]
+    #[display("session({0})")]
+    Session(crate::rpc::types::Session),
+
+    #[api(type = 0x0108)]
+    #[display("challenge({0})")]
+    Challenge(crate::rpc::auth::Challenge),
+
+    #[api(type = 0x010A)]
+    #[display("approval({0})")]
+    Approval(crate::rpc::types::Approval),
+
+    #[api(type = 0x010C)]
+    #[display("attestation({0})")]
+    Attestation(crate::rpc::types::Attestation),
+
+    #[api(type = 0x010E)]
+    #[display("job_progress({0})")]
+    JobProgress(crate::rpc::types::JobProgress),
+
+    /// Reply sealed to the key chosen by the client with
+    /// [`crate::rpc::sealed::seal_reply`]
+    #[api(type = 0x0110)]
+    #[display("sealed(...)")]
+    Sealed(Vec<u8>),
+
+    /// Reply to [`crate::rpc::Request::Tagged`]
+    #[api(type = 0x0112)]
+    #[display("tagged({0})")]
+    Tagged(crate::rpc::types::TaggedReply),
+
+    #[api(type = 0x0114)]
+    #[display("hello({0})")]
+    Hello(crate::rpc::types::Hello),
+
     #[api(type = 0x0200)]
     #[display("keylist(...)")]
     Keylist(Vec<crate::rpc::types::AccountInfo>),
@@ -37,14 +94,85 @@ pub enum Reply {
     #[display("account_info({0})")]
     AccountInfo(crate::rpc::types::AccountInfo),
 
+    #[api(type = 0x0204)]
+    #[display("balance_list(...)")]
+    BalanceList(Vec<crate::rpc::types::AccountBalance>),
+
+    #[api(type = 0x0206)]
+    #[display("derived_keys(...)")]
+    DerivedKeys(Vec<crate::rpc::types::DerivedKey>),
+
+    #[api(type = 0x0208)]
+    #[display("identity_key({0})")]
+    IdentityKey(crate::rpc::types::IdentityKey),
+
+    #[api(type = 0x020A)]
+    #[display("multisig({0})")]
+    Multisig(crate::rpc::types::MultisigGroup),
+
+    #[api(type = 0x020C)]
+    #[display("multisig_groups(...)")]
+    MultisigGroups(Vec<crate::rpc::types::MultisigGroup>),
+
+    #[api(type = 0x020E)]
+    #[display("payment_code({0})")]
+    PaymentCode(crate::rpc::types::PaymentCodeInfo),
+
+    #[api(type = 0x0210)]
+    #[display("ln_key_set({0})")]
+    LnKeySet(crate::rpc::types::LnKeySet),
+
+    #[api(type = 0x0212)]
+    #[display("vault_list(...)")]
+    VaultList(Vec<crate::rpc::types::VaultInfo>),
+
+    #[api(type = 0x0214)]
+    #[display("psbt_analysis({0})")]
+    PsbtAnalysis(crate::rpc::types::PsbtAnalysis),
+
     #[api(type = 0x0300)]
     #[display("xpriv(...)")]
-    XPriv(::bitcoin::util::bip32::ExtendedPrivKey),
+    XPriv(crate::rpc::types::ExportedXpriv),
 
     #[api(type = 0x0302)]
     #[display("xpub({0})")]
     XPub(::bitcoin::util::bip32::ExtendedPubKey),
 
+    #[api(type = 0x0304)]
+    #[display("descriptors(...)")]
+    Descriptors(Vec<String>),
+
+    /// Path to the vault backup snapshot
+    #[api(type = 0x0306)]
+    #[display("backup({0})")]
+    Backup(String),
+
+    #[api(type = 0x0308)]
+    #[display("ledger(...)")]
+    Ledger(Vec<crate::rpc::types::LedgerEntry>),
+
+    /// BIP-85 child secret encoded in the application format; available only
+    /// with `export-secrets` feature
+    #[cfg(feature = "export-secrets")]
+    #[api(type = 0x030A)]
+    #[display("derived_secret(...)")]
+    DerivedSecret(String),
+
+    /// Extended public key serialized with the requested SLIP-132 prefix
+    #[api(type = 0x030C)]
+    #[display("encoded_xpub({0})")]
+    EncodedXPub(String),
+
+    /// Strict-encoded vault data for the remote vault driver
+    #[api(type = 0x0400)]
+    #[display("vault(...)")]
+    Vault(Vec<u8>),
+
+    /// Portable vault dump; see [`crate::vault::interchange`]
+    #[api(type = 0x0402)]
+    #[display("vault_dump(...)")]
+    VaultDump(Vec<u8>),
+
     #[api(type = 0x0500)]
     #[display("signature({0})")]
     Signature(::bitcoin::secp256k1::Signature),
@@ -52,14 +180,57 @@ pub enum Reply {
     #[api(type = 0x0502)]
     #[display("psbt(...)")]
     Psbt(::bitcoin::util::psbt::PartiallySignedTransaction),
+
+    #[api(type = 0x0504)]
+    #[display("identity_signature({0})")]
+    IdentitySignature(crate::rpc::types::IdentitySignature),
+
+    #[api(type = 0x0506)]
+    #[display("message_signature(...)")]
+    MessageSignature(Vec<u8>),
+
+    #[api(type = 0x0508)]
+    #[display("transaction(...)")]
+    Transaction(::bitcoin::Transaction),
+
+    #[api(type = 0x050A)]
+    #[display("musig_session({0})")]
+    MuSigSession(crate::rpc::types::MuSigSession),
+
+    /// Aggregated public nonce of a MuSig2 session
+    #[api(type = 0x050C)]
+    #[display("musig_nonce(...)")]
+    MuSigNonce(Vec<u8>),
+
+    #[api(type = 0x050E)]
+    #[display("musig_signature(...)")]
+    MuSigSignature(crate::rpc::types::MuSigSignature),
+
+    /// BOLT-11 invoice signature: compact signature followed by the
+    /// recovery id
+    #[api(type = 0x0510)]
+    #[display("invoice_signature(...)")]
+    InvoiceSignature(Vec<u8>),
+
+    #[api(type = 0x0512)]
+    #[display("announcement_signatures({0})")]
+    AnnouncementSignatures(crate::rpc::types::AnnouncementSignatures),
+
+    /// Per-commitment secret of a Lightning channel
+    #[api(type = 0x0600)]
+    #[display("commitment_secret(...)")]
+    CommitmentSecret(crate::rpc::types::CommitmentSecret),
+
+    /// BOLT-8 ECDH shared secret
+    #[api(type = 0x0602)]
+    #[display("shared_secret(...)")]
+    SharedSecret(crate::rpc::types::SharedSecret),
 }
 
 impl From<Error> for Reply {
     fn from(err: Error) -> Self {
-        // TODO: Save error code taken from `Error::to_value()` after
-        //       implementation of `ToValue` trait and derive macro for enums
         Reply::Failure(microservices::rpc::Failure {
-            code: 0,
+            code: FailureCode::from(&err).into(),
             info: format!("{}", err),
         })
     }
@@ -68,10 +239,8 @@ impl From<Error> for Reply {
 #[cfg(any(feature = "server", feature = "embedded"))]
 impl From<RuntimeError> for Reply {
     fn from(err: RuntimeError) -> Self {
-        // TODO: Save error code taken from `Error::to_value()` after
-        //       implementation of `ToValue` trait and derive macro for enums
         Reply::Failure(microservices::rpc::Failure {
-            code: 0,
+            code: FailureCode::from(&err).into(),
             info: format!("{}", err),
         })
     }
@@ -11,30 +11,166 @@
 // along with this software.
 // If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
 
+use bitcoin::XpubIdentifier;
+
+use crate::rpc::types::{IdempotencyKey, JobId};
+
 #[derive(Clone, Debug, Display, Api)]
 #[api(encoding = "strict")]
 #[non_exhaustive]
 pub enum Request {
+    #[api(type = 0x0002)]
+    #[display("challenge({0})")]
+    Challenge(String),
+
+    #[api(type = 0x0004)]
+    #[display("status()")]
+    Status,
+
+    #[api(type = 0x0006)]
+    #[display("unlock({0})")]
+    Unlock(crate::rpc::message::Unlock),
+
+    #[api(type = 0x0008)]
+    #[display("lock({0})")]
+    Lock(crate::rpc::message::Lock),
+
+    #[api(type = 0x000A)]
+    #[display("attest({0})")]
+    Attest(crate::rpc::message::Attest),
+
+    #[api(type = 0x000C)]
+    #[display("job_status({0})")]
+    JobStatus(crate::rpc::message::JobStatus),
+
+    #[api(type = 0x000E)]
+    #[display("sealed(...)")]
+    Sealed(crate::rpc::message::Sealed),
+
     #[api(type = 0x0010)]
     #[display("list()")]
     List,
 
+    #[api(type = 0x0012)]
+    #[display("list_with_balances({0})")]
+    ListWithBalances(crate::rpc::message::Scan),
+
+    #[api(type = 0x0014)]
+    #[display("find_accounts({0})")]
+    FindAccounts(crate::rpc::types::AccountQuery),
+
+    #[api(type = 0x0016)]
+    #[display("list_multisig()")]
+    ListMultisig,
+
+    #[api(type = 0x0018)]
+    #[display("tagged({0})")]
+    Tagged(crate::rpc::message::Tagged),
+
+    #[api(type = 0x001A)]
+    #[display("hello({0})")]
+    Hello(crate::rpc::message::Hello),
+
+    #[api(type = 0x001C)]
+    #[display("reconfigure({0})")]
+    Reconfigure(crate::rpc::message::Reconfigure),
+
+    #[api(type = 0x001E)]
+    #[display("routed({0})")]
+    Routed(crate::rpc::message::Routed),
+
     #[api(type = 0x0020)]
     #[display("seed({0})")]
     Seed(crate::rpc::message::Seed),
 
+    #[api(type = 0x0022)]
+    #[display("delete_keyring({0})")]
+    DeleteKeyring(crate::rpc::message::Delete),
+
+    #[api(type = 0x0024)]
+    #[display("import_descriptors({0})")]
+    ImportDescriptors(crate::rpc::message::ImportDescriptors),
+
+    #[api(type = 0x0026)]
+    #[display("import_xpub({0})")]
+    ImportXpub(crate::rpc::message::ImportXpub),
+
+    #[api(type = 0x0028)]
+    #[display("import_xpriv({0})")]
+    ImportXpriv(crate::rpc::message::ImportXpriv),
+
+    #[api(type = 0x002A)]
+    #[display("restore({0})")]
+    Restore(crate::rpc::message::Restore),
+
+    #[api(type = 0x002C)]
+    #[display("list_vaults()")]
+    ListVaults,
+
     #[api(type = 0x0030)]
     #[display("exporT_xpub({0})")]
     ExportXpub(crate::rpc::message::Export),
 
     #[api(type = 0x0032)]
     #[display("export_xpriv({0})")]
-    ExportXpriv(crate::rpc::message::Export),
+    ExportXpriv(crate::rpc::message::ExportXpriv),
+
+    #[api(type = 0x0034)]
+    #[display("export_descriptor({0})")]
+    ExportDescriptor(crate::rpc::message::Export),
+
+    #[api(type = 0x0036)]
+    #[display("identity_key({0})")]
+    IdentityKey(crate::rpc::message::IdentityKey),
+
+    #[api(type = 0x0038)]
+    #[display("backup({0})")]
+    Backup(crate::rpc::message::Backup),
+
+    #[api(type = 0x003A)]
+    #[display("export_ledger({0})")]
+    ExportLedger(crate::rpc::message::ExportLedger),
+
+    #[api(type = 0x003C)]
+    #[display("approve_export({0})")]
+    ApproveExport(crate::rpc::message::ApproveExport),
+
+    #[api(type = 0x003E)]
+    #[display("derive_entropy({0})")]
+    DeriveEntropy(crate::rpc::message::DeriveEntropy),
 
     #[api(type = 0x0040)]
     #[display("derive({0})")]
     Derive(crate::rpc::message::Derive),
 
+    #[api(type = 0x0042)]
+    #[display("delete_account({0})")]
+    DeleteAccount(crate::rpc::message::Delete),
+
+    #[api(type = 0x0044)]
+    #[display("set_lifecycle({0})")]
+    SetLifecycle(crate::rpc::message::SetLifecycle),
+
+    #[api(type = 0x0046)]
+    #[display("derive_range({0})")]
+    DeriveRange(crate::rpc::message::DeriveRange),
+
+    #[api(type = 0x0048)]
+    #[display("set_branches({0})")]
+    SetBranches(crate::rpc::message::SetBranches),
+
+    #[api(type = 0x004A)]
+    #[display("commit_sandbox({0})")]
+    CommitSandbox(crate::rpc::message::Sandbox),
+
+    #[api(type = 0x004C)]
+    #[display("discard_sandbox({0})")]
+    DiscardSandbox(crate::rpc::message::Sandbox),
+
+    #[api(type = 0x004E)]
+    #[display("discover({0})")]
+    Discover(crate::rpc::message::Discover),
+
     #[api(type = 0x0050)]
     #[display("sign_psbt({0})")]
     SignPsbt(crate::rpc::message::SignPsbt),
@@ -46,4 +182,411 @@ pub enum Request {
     #[api(type = 0x0054)]
     #[display("sign_data({0})")]
     SignData(crate::rpc::message::SignData),
+
+    #[api(type = 0x0056)]
+    #[display("sign_identity({0})")]
+    SignIdentity(crate::rpc::message::SignIdentity),
+
+    #[api(type = 0x0058)]
+    #[display("sign_message({0})")]
+    SignMessage(crate::rpc::message::SignMessage),
+
+    #[api(type = 0x005A)]
+    #[display("finalize_psbt({0})")]
+    FinalizePsbt(crate::rpc::message::FinalizePsbt),
+
+    #[api(type = 0x005C)]
+    #[display("compose_psbt({0})")]
+    ComposePsbt(crate::rpc::message::ComposePsbt),
+
+    #[api(type = 0x005E)]
+    #[display("set_policy({0})")]
+    SetPolicy(crate::rpc::message::SetPolicy),
+
+    #[api(type = 0x0060)]
+    #[display("load_vault({0})")]
+    LoadVault(crate::rpc::message::LoadVault),
+
+    #[api(type = 0x0062)]
+    #[display("store_vault({0})")]
+    StoreVault(crate::rpc::message::StoreVault),
+
+    #[api(type = 0x0064)]
+    #[display("set_label({0})")]
+    SetLabel(crate::rpc::message::SetLabel),
+
+    #[api(type = 0x0066)]
+    #[display("update_account({0})")]
+    UpdateAccount(crate::rpc::message::UpdateAccount),
+
+    #[api(type = 0x0068)]
+    #[display("create_multisig({0})")]
+    CreateMultisig(crate::rpc::message::CreateMultisig),
+
+    #[api(type = 0x006A)]
+    #[display("export_multisig({0})")]
+    ExportMultisig(crate::rpc::message::ExportMultisig),
+
+    #[api(type = 0x0070)]
+    #[display("append_revocation({0})")]
+    AppendRevocation(crate::rpc::message::AppendRevocation),
+
+    #[api(type = 0x0072)]
+    #[display("query_revocation({0})")]
+    QueryRevocation(crate::rpc::message::QueryRevocation),
+
+    #[api(type = 0x0074)]
+    #[display("compact_revocations({0})")]
+    CompactRevocations(crate::rpc::message::CompactRevocations),
+
+    #[api(type = 0x0080)]
+    #[display("musig_start_session({0})")]
+    MuSigStartSession(crate::rpc::message::MuSigStartSession),
+
+    #[api(type = 0x0082)]
+    #[display("musig_nonce_exchange({0})")]
+    MuSigNonceExchange(crate::rpc::message::MuSigNonceExchange),
+
+    #[api(type = 0x0084)]
+    #[display("musig_partial_sign({0})")]
+    MuSigPartialSign(crate::rpc::message::MuSigPartialSign),
+
+    #[api(type = 0x0086)]
+    #[display("derive_payment_code({0})")]
+    DerivePaymentCode(crate::rpc::message::DerivePaymentCode),
+
+    #[api(type = 0x0088)]
+    #[display("derive_ln_key_set({0})")]
+    DeriveLnKeySet(crate::rpc::message::DeriveLnKeySet),
+
+    #[api(type = 0x008A)]
+    #[display("ecdh({0})")]
+    Ecdh(crate::rpc::message::Ecdh),
+
+    #[api(type = 0x008C)]
+    #[display("sign_invoice({0})")]
+    SignInvoice(crate::rpc::message::SignInvoice),
+
+    #[api(type = 0x008E)]
+    #[display("sign_channel_announcement({0})")]
+    SignChannelAnnouncement(crate::rpc::message::SignChannelAnnouncement),
+
+    #[api(type = 0x0090)]
+    #[display("sign_gossip({0})")]
+    SignGossip(crate::rpc::message::SignGossip),
+
+    #[api(type = 0x0092)]
+    #[display("export_vault({0})")]
+    ExportVault(crate::rpc::message::ExportVault),
+
+    #[api(type = 0x0094)]
+    #[display("import_vault({0})")]
+    ImportVault(crate::rpc::message::ImportVault),
+
+    /// Reports PSBT inputs signable by the vault, amounts and violated
+    /// signing rules without signing the PSBT
+    #[api(type = 0x0096)]
+    #[display("analyze_psbt({0})")]
+    AnalyzePsbt(crate::rpc::message::AnalyzePsbt),
+
+    /// Combines partially signed copies of the same transaction from
+    /// multiple cosigners into a single PSBT
+    #[api(type = 0x0098)]
+    #[display("combine_psbt({0})")]
+    CombinePsbt(crate::rpc::message::CombinePsbt),
+}
+
+impl Request {
+    /// Detects requests which neither modify the vault nor use private keys,
+    /// and thus can be served by a read-only daemon replica
+    pub fn is_read_only(&self) -> bool {
+        match self {
+            Request::Challenge(_)
+            | Request::Status
+            | Request::Hello(_)
+            | Request::Reconfigure(_)
+            | Request::Attest(_)
+            | Request::JobStatus(_)
+            // Sealed, tagged and routed requests are checked once opened
+            | Request::Sealed(_)
+            | Request::Tagged(_)
+            | Request::Routed(_)
+            | Request::List
+            | Request::ListVaults
+            | Request::ListWithBalances(_)
+            | Request::FindAccounts(_)
+            | Request::ListMultisig
+            | Request::ExportXpub(_)
+            | Request::ExportDescriptor(_)
+            | Request::ExportMultisig(_)
+            | Request::ExportLedger(_)
+            | Request::DeriveRange(_)
+            | Request::DeriveLnKeySet(_)
+            | Request::FinalizePsbt(_)
+            | Request::ComposePsbt(_)
+            | Request::AnalyzePsbt(_)
+            | Request::CombinePsbt(_)
+            | Request::QueryRevocation(_)
+            | Request::ExportVault(_) => true,
+            Request::Unlock(_)
+            | Request::Lock(_)
+            | Request::Seed(_)
+            | Request::DeleteKeyring(_)
+            | Request::ImportDescriptors(_)
+            | Request::ImportXpub(_)
+            | Request::ImportXpriv(_)
+            | Request::Restore(_)
+            | Request::ApproveExport(_)
+            | Request::ExportXpriv(_)
+            | Request::IdentityKey(_)
+            | Request::DeriveEntropy(_)
+            | Request::Backup(_)
+            | Request::Derive(_)
+            | Request::DeleteAccount(_)
+            | Request::SetLifecycle(_)
+            | Request::SetBranches(_)
+            | Request::SetPolicy(_)
+            | Request::SetLabel(_)
+            | Request::UpdateAccount(_)
+            | Request::CreateMultisig(_)
+            | Request::CommitSandbox(_)
+            | Request::DiscardSandbox(_)
+            | Request::Discover(_)
+            | Request::SignPsbt(_)
+            | Request::SignKey(_)
+            | Request::SignData(_)
+            | Request::SignIdentity(_)
+            | Request::SignMessage(_)
+            | Request::LoadVault(_)
+            | Request::StoreVault(_)
+            | Request::AppendRevocation(_)
+            | Request::CompactRevocations(_)
+            | Request::MuSigStartSession(_)
+            | Request::MuSigNonceExchange(_)
+            | Request::MuSigPartialSign(_)
+            | Request::DerivePaymentCode(_)
+            | Request::Ecdh(_)
+            | Request::SignInvoice(_)
+            | Request::SignChannelAnnouncement(_)
+            | Request::SignGossip(_)
+            | Request::ImportVault(_) => false,
+        }
+    }
+
+    /// Detects requests carrying secrets (passphrases, private and decryption
+    /// keys, session and approval tokens, vault data) or receiving secrets in
+    /// the reply, which clients seal to the daemon when payload
+    /// encryption is enabled; see [`crate::rpc::sealed`]
+    pub fn has_secrets(&self) -> bool {
+        match self {
+            Request::Unlock(_)
+            | Request::Lock(_)
+            | Request::ImportXpriv(_)
+            | Request::Restore(_)
+            | Request::ExportXpub(_)
+            | Request::ApproveExport(_)
+            | Request::ExportXpriv(_)
+            | Request::ExportDescriptor(_)
+            | Request::ExportMultisig(_)
+            | Request::IdentityKey(_)
+            | Request::DeriveEntropy(_)
+            | Request::Derive(_)
+            | Request::DeleteKeyring(_)
+            | Request::DeleteAccount(_)
+            | Request::CommitSandbox(_)
+            | Request::DiscardSandbox(_)
+            | Request::Discover(_)
+            | Request::SignPsbt(_)
+            | Request::SignKey(_)
+            | Request::SignData(_)
+            | Request::SignIdentity(_)
+            | Request::SignMessage(_)
+            | Request::LoadVault(_)
+            | Request::StoreVault(_)
+            | Request::AppendRevocation(_)
+            | Request::QueryRevocation(_)
+            | Request::MuSigPartialSign(_)
+            | Request::DerivePaymentCode(_)
+            | Request::Ecdh(_)
+            | Request::SignInvoice(_)
+            | Request::SignChannelAnnouncement(_)
+            | Request::SignGossip(_)
+            | Request::ExportVault(_)
+            | Request::ImportVault(_) => true,
+            Request::Challenge(_)
+            | Request::Status
+            | Request::Hello(_)
+            | Request::Reconfigure(_)
+            | Request::Attest(_)
+            | Request::JobStatus(_)
+            | Request::Sealed(_)
+            | Request::Tagged(_)
+            | Request::Routed(_)
+            | Request::List
+            | Request::ListVaults
+            | Request::ListWithBalances(_)
+            | Request::FindAccounts(_)
+            | Request::ListMultisig
+            | Request::Seed(_)
+            | Request::ImportDescriptors(_)
+            | Request::ImportXpub(_)
+            | Request::Backup(_)
+            | Request::ExportLedger(_)
+            | Request::SetLifecycle(_)
+            | Request::DeriveRange(_)
+            | Request::DeriveLnKeySet(_)
+            | Request::SetBranches(_)
+            | Request::SetPolicy(_)
+            | Request::SetLabel(_)
+            | Request::UpdateAccount(_)
+            | Request::CreateMultisig(_)
+            | Request::FinalizePsbt(_)
+            | Request::ComposePsbt(_)
+            | Request::AnalyzePsbt(_)
+            | Request::CombinePsbt(_)
+            | Request::CompactRevocations(_)
+            | Request::MuSigStartSession(_)
+            | Request::MuSigNonceExchange(_) => false,
+        }
+    }
+
+    /// Name of the request type, as used in the logs
+    pub fn name(&self) -> &'static str {
+        match self {
+            Request::Challenge(_) => "challenge",
+            Request::Status => "status",
+            Request::Hello(_) => "hello",
+            Request::Reconfigure(_) => "reconfigure",
+            Request::Attest(_) => "attest",
+            Request::JobStatus(_) => "job_status",
+            Request::Sealed(_) => "sealed",
+            Request::Tagged(_) => "tagged",
+            Request::Routed(_) => "routed",
+            Request::Unlock(_) => "unlock",
+            Request::Lock(_) => "lock",
+            Request::List => "list",
+            Request::ListVaults => "list_vaults",
+            Request::ListWithBalances(_) => "list_with_balances",
+            Request::FindAccounts(_) => "find_accounts",
+            Request::ListMultisig => "list_multisig",
+            Request::Seed(_) => "seed",
+            Request::DeleteKeyring(_) => "delete_keyring",
+            Request::ImportDescriptors(_) => "import_descriptors",
+            Request::ImportXpub(_) => "import_xpub",
+            Request::ImportXpriv(_) => "import_xpriv",
+            Request::Restore(_) => "restore",
+            Request::ExportXpub(_) => "export_xpub",
+            Request::ExportXpriv(_) => "export_xpriv",
+            Request::ExportDescriptor(_) => "export_descriptor",
+            Request::IdentityKey(_) => "identity_key",
+            Request::Backup(_) => "backup",
+            Request::ExportLedger(_) => "export_ledger",
+            Request::ApproveExport(_) => "approve_export",
+            Request::DeriveEntropy(_) => "derive_entropy",
+            Request::Derive(_) => "derive",
+            Request::DeleteAccount(_) => "delete_account",
+            Request::SetLifecycle(_) => "set_lifecycle",
+            Request::DeriveRange(_) => "derive_range",
+            Request::SetBranches(_) => "set_branches",
+            Request::CommitSandbox(_) => "commit_sandbox",
+            Request::DiscardSandbox(_) => "discard_sandbox",
+            Request::Discover(_) => "discover",
+            Request::SignPsbt(_) => "sign_psbt",
+            Request::SignKey(_) => "sign_key",
+            Request::SignData(_) => "sign_data",
+            Request::SignIdentity(_) => "sign_identity",
+            Request::SignMessage(_) => "sign_message",
+            Request::FinalizePsbt(_) => "finalize_psbt",
+            Request::ComposePsbt(_) => "compose_psbt",
+            Request::AnalyzePsbt(_) => "analyze_psbt",
+            Request::CombinePsbt(_) => "combine_psbt",
+            Request::SetPolicy(_) => "set_policy",
+            Request::LoadVault(_) => "load_vault",
+            Request::StoreVault(_) => "store_vault",
+            Request::SetLabel(_) => "set_label",
+            Request::UpdateAccount(_) => "update_account",
+            Request::CreateMultisig(_) => "create_multisig",
+            Request::ExportMultisig(_) => "export_multisig",
+            Request::AppendRevocation(_) => "append_revocation",
+            Request::QueryRevocation(_) => "query_revocation",
+            Request::CompactRevocations(_) => "compact_revocations",
+            Request::MuSigStartSession(_) => "musig_start_session",
+            Request::MuSigNonceExchange(_) => "musig_nonce_exchange",
+            Request::MuSigPartialSign(_) => "musig_partial_sign",
+            Request::DerivePaymentCode(_) => "derive_payment_code",
+            Request::DeriveLnKeySet(_) => "derive_ln_key_set",
+            Request::Ecdh(_) => "ecdh",
+            Request::SignInvoice(_) => "sign_invoice",
+            Request::SignChannelAnnouncement(_) => "sign_channel_announcement",
+            Request::SignGossip(_) => "sign_gossip",
+            Request::ExportVault(_) => "export_vault",
+            Request::ImportVault(_) => "import_vault",
+        }
+    }
+
+    /// Returns mutable reference to the job id of the long-running requests,
+    /// which are served as asynchronous jobs once the id is set, or
+    /// [`Option::None`] for other requests. Status of the job is polled with
+    /// [`Request::JobStatus`].
+    pub fn job_mut(&mut self) -> Option<&mut Option<JobId>> {
+        match self {
+            Request::Backup(req) => Some(&mut req.job),
+            Request::Restore(req) => Some(&mut req.job),
+            Request::DeriveRange(req) => Some(&mut req.job),
+            Request::SignPsbt(req) => Some(&mut req.job),
+            Request::Discover(req) => Some(&mut req.job),
+            _ => None,
+        }
+    }
+
+    /// Returns idempotency key of the requests modifying the vault which
+    /// may be retried by the clients, or [`Option::None`] if the key is not
+    /// given or not supported by the request type
+    pub fn idempotency_key(&self) -> Option<IdempotencyKey> {
+        match self {
+            Request::Seed(req) => req.idempotency_key,
+            Request::Derive(req) => req.idempotency_key,
+            _ => None,
+        }
+    }
+
+    /// Identifier of the keyring or account the request refers to
+    pub fn key_id(&self) -> Option<XpubIdentifier> {
+        match self {
+            Request::Derive(derive) => Some(derive.from),
+            Request::DeleteKeyring(message)
+            | Request::DeleteAccount(message) => Some(message.key_id),
+            Request::ExportXpub(message)
+            | Request::ExportDescriptor(message) => Some(message.key_id),
+            Request::ExportXpriv(message) => Some(message.key_id),
+            Request::IdentityKey(message) => Some(message.key_id),
+            Request::ApproveExport(message) => Some(message.key_id),
+            Request::DeriveEntropy(message) => Some(message.key_id),
+            Request::SetLifecycle(message) => Some(message.key_id),
+            Request::DeriveRange(message) => Some(message.key_id),
+            Request::SetBranches(message) => Some(message.key_id),
+            Request::SetPolicy(message) => Some(message.key_id),
+            Request::SetLabel(message) => Some(message.key_id),
+            Request::UpdateAccount(message) => Some(message.key_id),
+            Request::CreateMultisig(message) => {
+                message.accounts.first().copied()
+            }
+            Request::Discover(message) => Some(message.key_id),
+            Request::SignKey(message) => Some(message.key_id),
+            Request::SignData(message) => Some(message.key_id),
+            Request::SignIdentity(message) => Some(message.key_id),
+            Request::SignMessage(message) => Some(message.key_id),
+            Request::AppendRevocation(message) => Some(message.key_id),
+            Request::QueryRevocation(message) => Some(message.key_id),
+            Request::CompactRevocations(message) => Some(message.key_id),
+            Request::MuSigStartSession(message) => Some(message.key_id),
+            Request::DerivePaymentCode(message) => Some(message.key_id),
+            Request::DeriveLnKeySet(message) => Some(message.key_id),
+            Request::Ecdh(message) => Some(message.key_id),
+            Request::SignInvoice(message) => Some(message.key_id),
+            Request::SignChannelAnnouncement(message) => Some(message.key_id),
+            Request::SignGossip(message) => Some(message.key_id),
+            _ => None,
+        }
+    }
 }
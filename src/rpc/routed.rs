@@ -0,0 +1,60 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Routing of requests to the vaults. A daemon may serve several vaults;
+//! requests referring to a key are served by the vault containing the key,
+//! keyrings for a chain are created in the vault configured for the chain,
+//! and other requests are served by the default vault. Clients override
+//! this by wrapping a request into [`Request::Routed`] naming the vault.
+//! Routed requests wrap sealed requests (see [`super::sealed`]) and may be
+//! tagged (see [`super::tagged`]).
+
+use internet2::{CreateUnmarshaller, TypedEnum, Unmarshall};
+
+use super::types::{AuthCode, VaultId};
+use super::{message, Request};
+
+/// Errors routing and opening RPC requests
+#[derive(Clone, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum Error {
+    /// Routed payload contains invalid RPC message: {0}
+    #[from]
+    Presentation(internet2::presentation::Error),
+
+    /// Routed request contains another routed or tagged request
+    Nested,
+}
+
+/// Wraps the `request` into a request routed to the vault `vault_id`. The
+/// returned request has to be authorized with the client secret, if
+/// required by the daemon.
+pub fn route(request: &Request, vault_id: VaultId) -> Result<Request, Error> {
+    if let Request::Routed(_) | Request::Tagged(_) = request {
+        return Err(Error::Nested);
+    }
+    Ok(Request::Routed(message::Routed {
+        vault_id,
+        payload: request.serialize(),
+        auth_code: AuthCode::default(),
+    }))
+}
+
+/// Extracts the request wrapped with [`route`]
+pub fn open(routed: &message::Routed) -> Result<Request, Error> {
+    let request = Request::create_unmarshaller().unmarshall(&routed.payload)?;
+    match &*request {
+        Request::Routed(_) | Request::Tagged(_) => Err(Error::Nested),
+        request => Ok(request.clone()),
+    }
+}
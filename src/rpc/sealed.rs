@@ -0,0 +1,111 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Application-layer encryption of RPC payloads. Requests carrying secrets
+//! (see [`Request::has_secrets`]) may be sealed by the client to the daemon
+//! node id and sent as [`Request::Sealed`]; the daemon replies with
+//! [`Reply::Sealed`] readable only with the secret key of the reply key
+//! chosen by the client. Unlike the encrypted transport, which ends at the
+//! first hop, sealed payloads are opened by the daemon itself, so proxies
+//! and compromised transports never see the secret fields.
+//!
+//! Sealed request contains the 33-byte serialized reply key followed by the
+//! request serialization, wrapped with [`crypto::wrap`]; sealed reply is the
+//! reply serialization wrapped in the same way. Since the reply key is
+//! sealed together with the request, it can't be replaced on the way to the
+//! daemon.
+
+use bitcoin::secp256k1::{self, PublicKey, SecretKey};
+use internet2::{CreateUnmarshaller, TypedEnum, Unmarshall};
+
+use super::types::AuthCode;
+use super::{message, Reply, Request};
+use crate::crypto;
+
+/// Length of the reply key prefix in the sealed request
+pub const REPLY_KEY_LEN: usize = secp256k1::constants::PUBLIC_KEY_SIZE;
+
+/// Errors sealing and opening RPC payloads
+#[derive(Clone, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum Error {
+    /// {0}
+    #[from]
+    Crypto(crypto::Error),
+
+    /// Sealed payload contains invalid RPC message: {0}
+    #[from]
+    Presentation(internet2::presentation::Error),
+
+    /// Sealed request contains invalid reply key
+    InvalidReplyKey,
+
+    /// Sealed request contains another sealed, tagged or routed request
+    Nested,
+}
+
+/// Seals the `request` for the daemon with `daemon_id`, which must seal the
+/// reply to the `reply_key`. The returned request has to be authorized with
+/// the client secret, if required by the daemon.
+pub fn seal(
+    request: &Request,
+    daemon_id: PublicKey,
+    reply_key: PublicKey,
+) -> Result<Request, Error> {
+    if let Request::Sealed(_) | Request::Tagged(_) | Request::Routed(_) =
+        request
+    {
+        return Err(Error::Nested);
+    }
+    let mut data = reply_key.serialize().to_vec();
+    data.extend(request.serialize());
+    Ok(Request::Sealed(message::Sealed {
+        payload: crypto::wrap(&data, daemon_id)?,
+        auth_code: AuthCode::default(),
+    }))
+}
+
+/// Opens request sealed with [`seal`] using the daemon `node_key`. Returns
+/// the request and the key the reply must be sealed to.
+pub fn open(
+    sealed: &message::Sealed,
+    node_key: &SecretKey,
+) -> Result<(Request, PublicKey), Error> {
+    let data = crypto::unwrap(&sealed.payload, node_key)?;
+    if data.len() <= REPLY_KEY_LEN {
+        return Err(Error::InvalidReplyKey);
+    }
+    let reply_key = PublicKey::from_slice(&data[..REPLY_KEY_LEN])
+        .map_err(|_| Error::InvalidReplyKey)?;
+    let request =
+        Request::create_unmarshaller().unmarshall(&data[REPLY_KEY_LEN..])?;
+    match &*request {
+        Request::Sealed(_) | Request::Tagged(_) | Request::Routed(_) => {
+            Err(Error::Nested)
+        }
+        request => Ok((request.clone(), reply_key)),
+    }
+}
+
+/// Seals the `reply` to the `reply_key` provided by the client
+pub fn seal_reply(reply: &Reply, reply_key: PublicKey) -> Result<Reply, Error> {
+    Ok(Reply::Sealed(crypto::wrap(&reply.serialize(), reply_key)?))
+}
+
+/// Opens reply data sealed with [`seal_reply`] using the secret `key`
+/// matching the reply key
+pub fn open_reply(sealed: &[u8], key: &SecretKey) -> Result<Reply, Error> {
+    let data = crypto::unwrap(sealed, key)?;
+    let reply = Reply::create_unmarshaller().unmarshall(&data)?;
+    Ok((&*reply).clone())
+}
@@ -0,0 +1,72 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Request/reply correlation. Any request may be wrapped into
+//! [`Request::Tagged`] carrying the request id chosen by the client; the
+//! daemon wraps the reply to it into [`Reply::Tagged`] with the same id.
+//! Tags wrap sealed requests (see [`super::sealed`]), so the request id
+//! remains readable by the proxies, but can't be sealed themselves.
+
+use internet2::{CreateUnmarshaller, TypedEnum, Unmarshall};
+
+use super::types::{AuthCode, RequestId, TaggedReply};
+use super::{message, Reply, Request};
+
+/// Errors tagging and opening RPC payloads
+#[derive(Clone, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum Error {
+    /// Tagged payload contains invalid RPC message: {0}
+    #[from]
+    Presentation(internet2::presentation::Error),
+
+    /// Tagged request contains another tagged request
+    Nested,
+}
+
+/// Wraps the `request` into a tagged request with `request_id`. The returned
+/// request has to be authorized with the client secret, if required by the
+/// daemon.
+pub fn tag(request: &Request, request_id: RequestId) -> Result<Request, Error> {
+    if let Request::Tagged(_) = request {
+        return Err(Error::Nested);
+    }
+    Ok(Request::Tagged(message::Tagged {
+        request_id,
+        payload: request.serialize(),
+        auth_code: AuthCode::default(),
+    }))
+}
+
+/// Extracts the request wrapped with [`tag`]
+pub fn open(tagged: &message::Tagged) -> Result<Request, Error> {
+    let request = Request::create_unmarshaller().unmarshall(&tagged.payload)?;
+    match &*request {
+        Request::Tagged(_) => Err(Error::Nested),
+        request => Ok(request.clone()),
+    }
+}
+
+/// Wraps the `reply` to the request with `request_id`
+pub fn tag_reply(reply: &Reply, request_id: RequestId) -> Reply {
+    Reply::Tagged(TaggedReply {
+        request_id,
+        payload: reply.serialize(),
+    })
+}
+
+/// Extracts the reply wrapped with [`tag_reply`]
+pub fn open_reply(tagged: &TaggedReply) -> Result<Reply, Error> {
+    let reply = Reply::create_unmarshaller().unmarshall(&tagged.payload)?;
+    Ok((&*reply).clone())
+}
@@ -0,0 +1,53 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Framing of the encrypted RPC transport. ZMQ REQ/REP sockets do not
+//! distinguish clients, so each encrypted frame carries the id of the channel
+//! randomly chosen by the client. Channel is established with Noise_XK
+//! handshake (the same as used by Brontide in Lightning network), where the
+//! client must know daemon node id in advance: the client sends act one and
+//! act three, and the daemon replies with act two and an empty frame. After
+//! the handshake request and reply payloads are encrypted with the channel
+//! Noise transcoder. Frames are prefixed with [`FRAME_MARKER`], which is not
+//! a valid RPC message type, so the daemon can accept plaintext requests on
+//! the same socket.
+
+use bitcoin::hashes::{sha256, Hash};
+
+/// Prefix of all frames sent over encrypted channels
+pub const FRAME_MARKER: [u8; 2] = [0xFF, 0xFF];
+
+/// Identifier of the encrypted channel
+pub type ChannelId = sha256::Hash;
+
+/// Composes frame for a given `channel` with the `payload`
+pub fn encode_frame(channel: ChannelId, payload: &[u8]) -> Vec<u8> {
+    let mut frame =
+        Vec::with_capacity(FRAME_MARKER.len() + ChannelId::LEN + payload.len());
+    frame.extend_from_slice(&FRAME_MARKER);
+    frame.extend_from_slice(&channel[..]);
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// Parses frame into the channel id and payload. Returns [`Option::None`] if
+/// the data are not an encrypted channel frame.
+pub fn decode_frame(data: &[u8]) -> Option<(ChannelId, &[u8])> {
+    let header_len = FRAME_MARKER.len() + ChannelId::LEN;
+    if data.len() < header_len || data[..FRAME_MARKER.len()] != FRAME_MARKER {
+        return None;
+    }
+    let channel =
+        ChannelId::from_slice(&data[FRAME_MARKER.len()..header_len]).ok()?;
+    Some((channel, &data[header_len..]))
+}
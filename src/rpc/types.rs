@@ -12,19 +12,136 @@
 // If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
 
 #[cfg(feature = "serde")]
-use serde_with::DisplayFromStr;
-use std::collections::HashSet;
+use serde_with::{hex::Hex, DisplayFromStr};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::io;
+use std::str::FromStr;
 
 use bitcoin::hash_types::XpubIdentifier;
-use bitcoin::util::bip32::Fingerprint;
-use bitcoin::util::bip32::KeySource;
-use lnpbp::chain::AssetId;
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::hashes::{hex, sha256, Hash, HashEngine};
+use bitcoin::secp256k1;
+use bitcoin::util::base58;
+use bitcoin::util::bip32::{
+    self, ChainCode, ChildNumber, DerivationPath, ExtendedPubKey, Fingerprint,
+    KeySource,
+};
+use bitcoin::{Address, OutPoint, Script, Txid};
+use lnpbp::chain::{AssetId, Chain};
+use lnpbp::strict_encoding::{self, StrictDecode, StrictEncode};
 use slip132::KeyApplication;
 
-#[cfg(feature = "node")]
+use crate::lifecycle::Lifecycle;
+#[cfg(feature = "_vault")]
 use crate::vault::{Keyring, KeysAccount};
 
-pub type AuthCode = u32;
+/// Authorization of a request computed by a client configured in the daemon.
+/// Requests sent without authorization carry the default auth code with an
+/// empty client name.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct AuthCode {
+    /// Name of the client, under which its secret is configured in the
+    /// daemon
+    pub client: String,
+
+    /// Challenge the request answers: either issued by the daemon for the
+    /// client or derived from the `timestamp`; see [`crate::rpc::auth`]
+    pub challenge: sha256::Hash,
+
+    /// Timestamp of the time-stamped authorization, in seconds since UNIX
+    /// epoch
+    pub timestamp: Option<u64>,
+
+    /// HMAC-SHA256 over the challenge and the request payload keyed with
+    /// the client secret
+    pub hmac: sha256::Hash,
+}
+
+impl Default for AuthCode {
+    fn default() -> Self {
+        AuthCode {
+            client: s!(""),
+            challenge: sha256::Hash::from_inner([0u8; 32]),
+            timestamp: None,
+            hmac: sha256::Hash::from_inner([0u8; 32]),
+        }
+    }
+}
+
+/// Token identifying unlocked vault session
+pub type SessionToken = sha256::Hash;
+
+/// One-time token approving export of an extended private key
+pub type ApprovalToken = sha256::Hash;
+
+/// Random identifier of a long-running request chosen by the client, with
+/// which progress of the request is polled while it is served
+pub type JobId = sha256::Hash;
+
+/// Identifier chosen by the client for a tagged request, which is echoed
+/// back in the reply, so the client can match replies to the requests
+pub type RequestId = u64;
+
+/// Random key chosen by the client for a request modifying the vault. The
+/// daemon serves requests with the same key once, replying to the retries
+/// with the reply to the first request.
+pub type IdempotencyKey = sha256::Hash;
+
+/// Name of a vault served by the daemon, as given in the daemon
+/// configuration
+pub type VaultId = String;
+
+/// Name of the vault configured with `vault` section of the daemon
+/// configuration, which serves requests not routed to other vaults
+pub const DEFAULT_VAULT_ID: &str = "default";
+
+/// Identifier of a Lightning channel which revocation secrets are stored by
+/// the daemon
+pub type LnChannelId = sha256::Hash;
+
+/// BOLT-8 ECDH shared secret: SHA256 hash of the compressed point produced
+/// by the multiplication of the remote public key by the node private key
+pub type SharedSecret = sha256::Hash;
+
+/// BOLT-3 per-commitment secret revealed by a Lightning channel
+/// counterparty; the secrets are produced by a chain of SHA256 hashes
+pub type CommitmentSecret = sha256::Hash;
+
+/// Extended private key carried by [`super::Reply::XPriv`]
+#[cfg(feature = "export-secrets")]
+pub type ExportedXpriv = bip32::ExtendedPrivKey;
+
+/// Extended private key carried by [`super::Reply::XPriv`]. Without
+/// `export-secrets` feature the type is uninhabited, so the reply can't be
+/// constructed, and decoding it always fails.
+#[cfg(not(feature = "export-secrets"))]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExportedXpriv {}
+
+#[cfg(not(feature = "export-secrets"))]
+impl StrictEncode for ExportedXpriv {
+    fn strict_encode<E: io::Write>(
+        &self,
+        _: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        match *self {}
+    }
+}
+
+#[cfg(not(feature = "export-secrets"))]
+impl StrictDecode for ExportedXpriv {
+    fn strict_decode<D: io::Read>(
+        _: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        Err(strict_encoding::Error::DataIntegrityError(
+            "extended private keys are not accepted without `export-secrets` \
+             feature"
+                .to_string(),
+        ))
+    }
+}
 
 #[cfg_attr(feature = "serde", serde_as)]
 #[cfg_attr(
@@ -32,8 +149,7 @@ pub type AuthCode = u32;
     derive(Serialize, Deserialize),
     serde(crate = "serde_crate")
 )]
-#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
-#[display("AccountInfo({id}, {name}, {key_id}, {fingerprint}, ...)")]
+#[derive(Clone, PartialEq, Eq, Debug, StrictEncode, StrictDecode)]
 #[strict_encoding_crate(lnpbp::strict_encoding)]
 #[non_exhaustive]
 pub struct AccountInfo {
@@ -46,18 +162,2085 @@ pub struct AccountInfo {
     pub assets: HashSet<AssetId>,
     pub application: Option<KeyApplication>,
     pub key_source: Option<KeySource>,
+    pub lifecycle: Lifecycle,
+    pub watch_only: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub branches: Branches,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub policy: SigningPolicy,
+    /// Other names under which the account key was imported
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub aliases: Vec<String>,
+    /// Key/value metadata of the account; tags are labels with empty values
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub labels: BTreeMap<String, String>,
+    /// Chain of the keyring the account belongs to, if known
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub chain: Option<Chain>,
+}
+
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
+#[display("{info}, {balance} sat of {received} sat received")]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[non_exhaustive]
+pub struct AccountBalance {
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub info: AccountInfo,
+    pub tx_count: u32,
+    pub received: u64,
+    pub balance: u64,
+}
+
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
+#[display(
+    "Status(protocol v{protocol_version}, {driver} vault with {keyrings} \
+     keyrings and {accounts} accounts, up {uptime} sec)"
+)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[non_exhaustive]
+pub struct Status {
+    pub config_fingerprint: sha256::Hash,
+
+    /// Version of the RPC protocol implemented by the daemon
+    pub protocol_version: u16,
+
+    /// Number of seconds passed since the daemon start
+    pub uptime: u64,
+
+    /// Type of the vault storage driver
+    pub driver: String,
+
+    /// Number of keyrings in the vault, not counting archived ones
+    pub keyrings: u32,
+
+    /// Number of keys accounts in the vault, including master accounts of
+    /// the keyrings
+    pub accounts: u32,
+
+    /// Whether the vault is encrypted with a passphrase and there is no
+    /// unlocked session
+    pub locked: bool,
+}
+
+/// Optional capability of the daemon or the client, announced with
+/// [`crate::rpc::Request::Hello`]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "kebab-case")
+)]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display)]
+#[repr(u8)]
+pub enum Feature {
+    /// Signing of P2TR key and script path spends
+    #[display("taproot")]
+    Taproot = 0,
+
+    /// MuSig2 multi-party signing sessions
+    #[display("musig")]
+    MuSig = 1,
+
+    /// gRPC interface
+    #[display("grpc")]
+    Grpc = 2,
+
+    /// Lightning node keys, ECDH and gossip signing
+    #[display("lightning")]
+    Lightning = 3,
+
+    /// Export of extended private keys
+    #[display("export-secrets")]
+    ExportSecrets = 4,
+
+    /// Requests sealed to the daemon node id
+    #[display("sealing")]
+    Sealing = 5,
+
+    /// Vault is encrypted with a passphrase, so the requests using private
+    /// keys must be served with a session returned by `unlock` request
+    #[display("passphrase")]
+    Passphrase = 6,
+}
+
+impl Feature {
+    /// All features known to this version of the protocol
+    pub const ALL: [Feature; 7] = [
+        Feature::Taproot,
+        Feature::MuSig,
+        Feature::Grpc,
+        Feature::Lightning,
+        Feature::ExportSecrets,
+        Feature::Sealing,
+        Feature::Passphrase,
+    ];
+}
+
+/// Set of [`Feature`]s encoded as a bit mask, where the feature value is the
+/// bit number. Bits of the features unknown to this version are kept, so
+/// the set may be decoded by older peers.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(
+    Copy, Clone, PartialEq, Eq, Hash, Debug, Default, StrictEncode, StrictDecode,
+)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct Features {
+    bits: u64,
+}
+
+impl Features {
+    pub fn with(features: impl IntoIterator<Item = Feature>) -> Self {
+        let mut set = Features::default();
+        for feature in features {
+            set.insert(feature);
+        }
+        set
+    }
+
+    pub fn insert(&mut self, feature: Feature) {
+        self.bits |= 1 << feature as u8;
+    }
+
+    pub fn contains(&self, feature: Feature) -> bool {
+        self.bits & (1 << feature as u8) != 0
+    }
+
+    /// Iterates over the features known to this version of the protocol
+    pub fn iter(&self) -> impl Iterator<Item = Feature> + '_ {
+        Feature::ALL
+            .iter()
+            .copied()
+            .filter(move |feature| self.contains(*feature))
+    }
+}
+
+impl fmt::Display for Features {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = self.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+        f.write_str(&names.join(", "))
+    }
+}
+
+/// Reply to [`crate::rpc::Request::Hello`], announcing the range of the RPC
+/// protocol versions and the features supported by the daemon
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
+#[display("v{server_version} (since v{min_version}): {features}")]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct Hello {
+    /// Version of the RPC protocol implemented by the daemon
+    pub server_version: u16,
+
+    /// The oldest protocol version of the clients served by the daemon
+    pub min_version: u16,
+
+    /// Features supported by the daemon
+    pub features: Features,
+}
+
+/// Vault served by the daemon, listed with
+/// [`crate::rpc::Request::ListVaults`]
+#[cfg_attr(feature = "serde", serde_as)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, PartialEq, Eq, Debug, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct VaultInfo {
+    pub id: VaultId,
+
+    /// Type of the vault storage driver
+    pub driver: String,
+
+    /// Chains which new keyrings are created in the vault; empty for the
+    /// default vault
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    pub chains: Vec<Chain>,
+
+    /// Number of keyrings in the vault, not counting archived ones
+    pub keyrings: u32,
+
+    /// Number of keys accounts in the vault, including master accounts of
+    /// the keyrings
+    pub accounts: u32,
+}
+
+impl fmt::Display for VaultInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} vault with {} keyrings and {} accounts",
+            self.id, self.driver, self.keyrings, self.accounts
+        )?;
+        if !self.chains.is_empty() {
+            let chains =
+                self.chains.iter().map(Chain::to_string).collect::<Vec<_>>();
+            write!(f, " for {}", chains.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Quote produced by the trusted execution environment (SGX, SEV-SNP etc)
+/// the daemon runs in. The quote is signed by the platform and carries
+/// [`Attestation::report_data`], binding it to the daemon node id, which
+/// terminates encrypted RPC channels, and to the nonce provided by the
+/// client.
+#[cfg_attr(feature = "serde", serde_as)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
+#[display("Attestation({platform}, {config_fingerprint})")]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct Attestation {
+    /// Platform which has produced the quote, as named in the daemon
+    /// configuration
+    pub platform: String,
+
+    /// Fingerprint of the daemon configuration, committed to by the second
+    /// half of the report data
+    pub config_fingerprint: sha256::Hash,
+
+    /// 64 bytes of the report data included into the quote
+    #[serde_as(as = "Hex")]
+    pub report_data: Vec<u8>,
+
+    /// Platform-specific quote, which must be verified with the platform
+    /// vendor attestation service
+    #[serde_as(as = "Hex")]
+    pub quote: Vec<u8>,
+}
+
+/// Progress of a job, reported in the steps specific to the request type:
+/// derived keys, signed PSBT inputs or written snapshots. Jobs which number
+/// of steps is not known in advance, like account discovery, report zero
+/// total steps.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
+#[display("{operation}: {done}/{total}")]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct JobProgress {
+    /// Job id assigned by the client
+    pub job: JobId,
+
+    /// Name of the request type
+    pub operation: String,
+
+    /// Number of the completed steps
+    pub done: u32,
+
+    /// Total number of steps
+    pub total: u32,
+
+    /// Time passed since the job runner has started serving the request, in
+    /// milliseconds; zero for the queued jobs
+    pub elapsed: u64,
+}
+
+/// Reply to a tagged request, carrying the serialized reply to the wrapped
+/// request; see [`crate::rpc::tagged`]
+#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
+#[display("{request_id}")]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct TaggedReply {
+    /// Identifier of the request the reply corresponds to
+    pub request_id: RequestId,
+
+    /// Serialized reply to the wrapped request
+    pub payload: Vec<u8>,
+}
+
+impl Attestation {
+    /// Computes report data for the quote: commitment to the daemon
+    /// `node_id` and the client `nonce`, followed by the configuration
+    /// fingerprint
+    pub fn report_data(
+        node_id: &bitcoin::secp256k1::PublicKey,
+        nonce: sha256::Hash,
+        config_fingerprint: sha256::Hash,
+    ) -> [u8; 64] {
+        let mut engine = sha256::Hash::engine();
+        engine.input(b"keyring-attestation");
+        engine.input(&node_id.serialize());
+        engine.input(&nonce[..]);
+        let mut report_data = [0u8; 64];
+        report_data[..32]
+            .copy_from_slice(&sha256::Hash::from_engine(engine)[..]);
+        report_data[32..].copy_from_slice(&config_fingerprint[..]);
+        report_data
+    }
+
+    /// Checks that the report data are bound to the daemon `node_id` and
+    /// the `nonce` sent by the client. The quote itself is not verified.
+    pub fn is_bound(
+        &self,
+        node_id: &bitcoin::secp256k1::PublicKey,
+        nonce: sha256::Hash,
+    ) -> bool {
+        self.report_data[..]
+            == Self::report_data(node_id, nonce, self.config_fingerprint)[..]
+    }
+}
+
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
+#[display("Session({token}, expires in {expires_in} sec)")]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct Session {
+    pub token: SessionToken,
+    pub expires_in: u64,
+}
+
+/// Record of a transaction signed by the vault, kept in the daemon ledger for
+/// reconciliation with accounting systems. Amounts are given in satoshis.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
+#[display("{txid}: spent {spent} sat, received {received} sat")]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct LedgerEntry {
+    pub txid: Txid,
+
+    /// Time of signing, in seconds since UNIX epoch
+    pub timestamp: u64,
+
+    /// Name of the authorized client which has requested the signature, if
+    /// request authorization is enabled
+    pub client: Option<String>,
+
+    /// Total amount of the inputs spending outputs of the vault accounts
+    pub spent: u64,
+
+    /// Total amount of the outputs paying to the vault accounts (like
+    /// change outputs)
+    pub received: u64,
+
+    /// Transaction fee; absent if amounts of some inputs are unknown
+    pub fee: Option<u64>,
+}
+
+/// Approval of an extended private key export issued by the daemon
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
+#[display("Approval({token}, expires in {expires_in} sec)")]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct Approval {
+    pub token: ApprovalToken,
+    pub expires_in: u64,
+}
+
+/// Layout of the account derivation branches used for address derivation and
+/// discovery
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(
+    Clone, Copy, PartialEq, Eq, Hash, Debug, Display, StrictEncode, StrictDecode,
+)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("external: {external}, internal: {internal}")]
+pub struct Branches {
+    /// Derivation index of the external (receiving) addresses branch
+    pub external: u32,
+
+    /// Derivation index of the internal (change) addresses branch
+    pub internal: u32,
+
+    /// Number of consecutive unused addresses after which address discovery
+    /// stops; if not set, the value requested by the client is used
+    pub gap_limit: Option<u32>,
+}
+
+impl Default for Branches {
+    fn default() -> Self {
+        Self {
+            external: 0,
+            internal: 1,
+            gap_limit: None,
+        }
+    }
+}
+
+impl Branches {
+    /// Checks that the branches are distinct normal derivation indexes and
+    /// that the gap limit, if given, is not zero
+    pub fn is_valid(&self) -> bool {
+        self.external != self.internal
+            && ChildNumber::from_normal_idx(self.external).is_ok()
+            && ChildNumber::from_normal_idx(self.internal).is_ok()
+            && self.gap_limit != Some(0)
+    }
+
+    /// Returns derivation template for the internal (`internal = true`) or
+    /// external branch addresses
+    pub fn template(&self, internal: bool) -> DerivationTemplate {
+        let branch = if internal {
+            self.internal
+        } else {
+            self.external
+        };
+        DerivationTemplate {
+            prefix: DerivationPath::from(vec![ChildNumber::Normal {
+                index: branch,
+            }]),
+            suffix: DerivationPath::master(),
+        }
+    }
+}
+
+/// Key derived from an account for a single index of the derivation range
+#[cfg_attr(feature = "serde", serde_as)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, PartialEq, Eq, Debug, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct DerivedKey {
+    pub index: u32,
+    #[serde_as(as = "DisplayFromStr")]
+    pub path: DerivationPath,
+    #[serde_as(as = "DisplayFromStr")]
+    pub pubkey: bitcoin::PublicKey,
+    /// Address for the key, if the account has single-key application
+    pub address: Option<String>,
+}
+
+/// Identity key for Nostr/DID-style protocols derived from an account
+#[cfg_attr(feature = "serde", serde_as)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, PartialEq, Eq, Debug, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct IdentityKey {
+    pub key_id: XpubIdentifier,
+    pub index: u32,
+    #[serde_as(as = "DisplayFromStr")]
+    pub path: DerivationPath,
+    /// BIP-340 x-only public key
+    #[serde_as(as = "Hex")]
+    pub pubkey: Vec<u8>,
+}
+
+impl fmt::Display for IdentityKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}, {})",
+            self.pubkey.to_hex(),
+            self.key_id,
+            self.path
+        )
+    }
+}
+
+/// BIP-340 signature of an identity event
+#[cfg_attr(feature = "serde", serde_as)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, PartialEq, Eq, Debug, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct IdentitySignature {
+    /// Identity key which has produced the signature
+    pub key: IdentityKey,
+    #[serde_as(as = "Hex")]
+    pub signature: Vec<u8>,
+}
+
+impl fmt::Display for IdentitySignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} by {}", self.signature.to_hex(), self.key)
+    }
+}
+
+/// Handling of the imported key which is already present in the vault
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "lowercase")
+)]
+#[derive(
+    Copy, Clone, PartialEq, Eq, Hash, Debug, Display, StrictEncode, StrictDecode,
+)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub enum CollisionPolicy {
+    /// Import fails with the known key error
+    #[display("reject")]
+    Reject,
+
+    /// Existing account is kept unchanged
+    #[display("skip")]
+    Skip,
+
+    /// Name and details of the existing account are replaced with the
+    /// imported ones; key application is set if it was not known
+    #[display("merge")]
+    Merge,
+
+    /// Imported name is added to the aliases of the existing account
+    #[display("alias")]
+    Alias,
+}
+
+impl Default for CollisionPolicy {
+    fn default() -> Self {
+        CollisionPolicy::Reject
+    }
+}
+
+/// Error parsing key collision policy string
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(
+    "unknown key collision policy `{0}`; possible values are reject, skip, \
+     merge and alias"
+)]
+pub struct CollisionParseError(String);
+
+impl FromStr for CollisionPolicy {
+    type Err = CollisionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "reject" => CollisionPolicy::Reject,
+            "skip" => CollisionPolicy::Skip,
+            "merge" => CollisionPolicy::Merge,
+            "alias" => CollisionPolicy::Alias,
+            _ => Err(CollisionParseError(s.to_owned()))?,
+        })
+    }
+}
+
+/// Mode for an update of the account asset list
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "kebab-case")
+)]
+#[derive(
+    Copy, Clone, PartialEq, Eq, Hash, Debug, Display, StrictEncode, StrictDecode,
+)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub enum UpdateMode {
+    /// Add new qualifiers to existing ones
+    #[display("add")]
+    Add,
+
+    /// Add new qualifiers **replacing** existing ones
+    #[display("replace")]
+    Replace,
+
+    /// Removes qualifiers from the provided list; if some of the qualifiers
+    /// are not found just ignore them and process the rest
+    #[display("remove-ignore")]
+    RemoveIgnore,
+
+    /// Removes qualifiers from the provided list; if any of the qualifiers
+    /// is not found then the function fails returning error, not updating any
+    /// of the qualifiers
+    #[display("remove-or-fail")]
+    RemoveOrFail,
+}
+
+impl Default for UpdateMode {
+    fn default() -> Self {
+        Self::Add
+    }
+}
+
+/// Error parsing update mode string
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(
+    "unknown update mode `{0}`; possible values are add, replace, \
+     remove-ignore and remove-or-fail"
+)]
+pub struct UpdateModeParseError(String);
+
+impl FromStr for UpdateMode {
+    type Err = UpdateModeParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "add" => UpdateMode::Add,
+            "replace" => UpdateMode::Replace,
+            "remove-ignore" => UpdateMode::RemoveIgnore,
+            "remove-or-fail" => UpdateMode::RemoveOrFail,
+            _ => Err(UpdateModeParseError(s.to_owned()))?,
+        })
+    }
+}
+
+/// SLIP-132 prefix of the extended public key serialization, specifying the
+/// key application and network. Prefixes differ by case (`ypub` and `Ypub`),
+/// so they are parsed case-sensitively.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(
+    Copy, Clone, PartialEq, Eq, Hash, Debug, Display, StrictEncode, StrictDecode,
+)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub enum KeyPrefix {
+    /// P2PKH or P2SH on mainnet (BIP-32)
+    #[display("xpub")]
+    #[cfg_attr(feature = "serde", serde(rename = "xpub"))]
+    Xpub,
+
+    /// P2WPKH nested in P2SH on mainnet (BIP-49)
+    #[display("ypub")]
+    #[cfg_attr(feature = "serde", serde(rename = "ypub"))]
+    Ypub,
+
+    /// P2WPKH on mainnet (BIP-84)
+    #[display("zpub")]
+    #[cfg_attr(feature = "serde", serde(rename = "zpub"))]
+    Zpub,
+
+    /// Multi-signature P2WSH nested in P2SH on mainnet
+    #[display("Ypub")]
+    #[cfg_attr(feature = "serde", serde(rename = "Ypub"))]
+    YpubMultisig,
+
+    /// Multi-signature P2WSH on mainnet
+    #[display("Zpub")]
+    #[cfg_attr(feature = "serde", serde(rename = "Zpub"))]
+    ZpubMultisig,
+
+    /// P2PKH or P2SH on testnet (BIP-32)
+    #[display("tpub")]
+    #[cfg_attr(feature = "serde", serde(rename = "tpub"))]
+    Tpub,
+
+    /// P2WPKH nested in P2SH on testnet (BIP-49)
+    #[display("upub")]
+    #[cfg_attr(feature = "serde", serde(rename = "upub"))]
+    Upub,
+
+    /// P2WPKH on testnet (BIP-84)
+    #[display("vpub")]
+    #[cfg_attr(feature = "serde", serde(rename = "vpub"))]
+    Vpub,
+
+    /// Multi-signature P2WSH nested in P2SH on testnet
+    #[display("Upub")]
+    #[cfg_attr(feature = "serde", serde(rename = "Upub"))]
+    UpubMultisig,
+
+    /// Multi-signature P2WSH on testnet
+    #[display("Vpub")]
+    #[cfg_attr(feature = "serde", serde(rename = "Vpub"))]
+    VpubMultisig,
+}
+
+impl KeyPrefix {
+    /// Version bytes of the extended public key serialization
+    pub fn version(self) -> [u8; 4] {
+        match self {
+            KeyPrefix::Xpub => [0x04, 0x88, 0xB2, 0x1E],
+            KeyPrefix::Ypub => [0x04, 0x9D, 0x7C, 0xB2],
+            KeyPrefix::Zpub => [0x04, 0xB2, 0x47, 0x46],
+            KeyPrefix::YpubMultisig => [0x02, 0x95, 0xB4, 0x3F],
+            KeyPrefix::ZpubMultisig => [0x02, 0xAA, 0x7E, 0xD3],
+            KeyPrefix::Tpub => [0x04, 0x35, 0x87, 0xCF],
+            KeyPrefix::Upub => [0x04, 0x4A, 0x52, 0x62],
+            KeyPrefix::Vpub => [0x04, 0x5F, 0x1C, 0xF6],
+            KeyPrefix::UpubMultisig => [0x02, 0x42, 0x89, 0xEF],
+            KeyPrefix::VpubMultisig => [0x02, 0x57, 0x54, 0x83],
+        }
+    }
+
+    /// Detects prefixes used for the test networks
+    pub fn is_testnet(self) -> bool {
+        match self {
+            KeyPrefix::Xpub
+            | KeyPrefix::Ypub
+            | KeyPrefix::Zpub
+            | KeyPrefix::YpubMultisig
+            | KeyPrefix::ZpubMultisig => false,
+            KeyPrefix::Tpub
+            | KeyPrefix::Upub
+            | KeyPrefix::Vpub
+            | KeyPrefix::UpubMultisig
+            | KeyPrefix::VpubMultisig => true,
+        }
+    }
+
+    /// Serializes extended public key with the version bytes of the prefix,
+    /// keeping the key data. Fails if the prefix is used for a network other
+    /// than the network of the key.
+    pub fn encode(
+        self,
+        xpub: &ExtendedPubKey,
+    ) -> Result<String, KeyPrefixError> {
+        if self.is_testnet() != (xpub.network != bitcoin::Network::Bitcoin) {
+            return Err(KeyPrefixError::Network(self, xpub.network));
+        }
+        let mut data = base58::from_check(&xpub.to_string())
+            .expect("extended public key serialization is always valid");
+        data[..4].copy_from_slice(&self.version());
+        Ok(base58::check_encode_slice(&data))
+    }
+}
+
+/// Error parsing SLIP-132 prefix or converting extended public key
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum KeyPrefixError {
+    /// Unknown SLIP-132 prefix `{0}`; possible values are xpub, ypub, zpub,
+    /// Ypub, Zpub, tpub, upub, vpub, Upub and Vpub
+    Unknown(String),
+
+    /// SLIP-132 prefix {0} can't be used for the key of {1} network
+    Network(KeyPrefix, bitcoin::Network),
+}
+
+impl FromStr for KeyPrefix {
+    type Err = KeyPrefixError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "xpub" => KeyPrefix::Xpub,
+            "ypub" => KeyPrefix::Ypub,
+            "zpub" => KeyPrefix::Zpub,
+            "Ypub" => KeyPrefix::YpubMultisig,
+            "Zpub" => KeyPrefix::ZpubMultisig,
+            "tpub" => KeyPrefix::Tpub,
+            "upub" => KeyPrefix::Upub,
+            "vpub" => KeyPrefix::Vpub,
+            "Upub" => KeyPrefix::UpubMultisig,
+            "Vpub" => KeyPrefix::VpubMultisig,
+            _ => Err(KeyPrefixError::Unknown(s.to_owned()))?,
+        })
+    }
+}
+
+/// Label of the account in the account search query: the account must have
+/// a label with the `key` and, if given, with the `value`. Written as
+/// `<key>` or `<key>=<value>`.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(
+    Clone, PartialEq, Eq, Hash, Debug, Default, StrictEncode, StrictDecode,
+)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct LabelQuery {
+    pub key: String,
+    pub value: Option<String>,
+}
+
+impl fmt::Display for LabelQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.key)?;
+        if let Some(ref value) = self.value {
+            write!(f, "={}", value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Error parsing [`LabelQuery`]
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display("label must be given as `<key>` or `<key>=<value>`, not `{0}`")]
+pub struct LabelParseError(String);
+
+impl FromStr for LabelQuery {
+    type Err = LabelParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut split = s.splitn(2, '=');
+        let key = split.next().unwrap_or_default();
+        if key.is_empty() {
+            return Err(LabelParseError(s.to_owned()));
+        }
+        Ok(Self {
+            key: key.to_owned(),
+            value: split.next().map(str::to_owned),
+        })
+    }
+}
+
+/// Criteria of the account search; accounts must match all of the given
+/// criteria
+#[derive(Clone, PartialEq, Eq, Debug, Default, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct AccountQuery {
+    pub labels: Vec<LabelQuery>,
+    pub asset: Option<AssetId>,
+    pub application: Option<KeyApplication>,
+    /// Fingerprint of the account key or of the master key it originates
+    /// from
+    pub fingerprint: Option<Fingerprint>,
+    /// Chain of the keyring the account belongs to
+    pub chain: Option<Chain>,
+}
+
+impl AccountQuery {
+    /// Checks whether the account matches the query
+    pub fn matches(&self, info: &AccountInfo) -> bool {
+        let labels = self.labels.iter().all(|label| {
+            match (info.labels.get(&label.key), &label.value) {
+                (Some(value), Some(expected)) => value == expected,
+                (Some(_), None) => true,
+                (None, _) => false,
+            }
+        });
+        let asset = self
+            .asset
+            .map_or(true, |asset| info.assets.contains(&asset));
+        let application = self
+            .application
+            .map_or(true, |application| info.application == Some(application));
+        let fingerprint = self.fingerprint.map_or(true, |fingerprint| {
+            info.fingerprint == fingerprint
+                || info
+                    .key_source
+                    .as_ref()
+                    .map_or(false, |(master, _)| *master == fingerprint)
+        });
+        let chain = self
+            .chain
+            .as_ref()
+            .map_or(true, |chain| info.chain.as_ref() == Some(chain));
+        labels && asset && application && fingerprint && chain
+    }
+}
+
+impl fmt::Display for AccountQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut criteria = self
+            .labels
+            .iter()
+            .map(|label| format!("label {}", label))
+            .collect::<Vec<_>>();
+        if let Some(asset) = self.asset {
+            criteria.push(format!("asset {}", asset));
+        }
+        if let Some(application) = self.application {
+            criteria.push(format!("application {:?}", application));
+        }
+        if let Some(fingerprint) = self.fingerprint {
+            criteria.push(format!("fingerprint {}", fingerprint));
+        }
+        if let Some(ref chain) = self.chain {
+            criteria.push(format!("chain {}", chain));
+        }
+        if criteria.is_empty() {
+            f.write_str("any account")
+        } else {
+            f.write_str(&criteria.join(", "))
+        }
+    }
+}
+
+/// Application of the BIP-85 child entropy, defining its derivation path and
+/// encoding
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Bip85Application {
+    /// BIP-39 mnemonic with 12, 18 or 24 `words` in a language with a given
+    /// BIP-85 code (0 for English)
+    Mnemonic { language: u32, words: u32 },
+
+    /// WIF-encoded private key for HD-seed wallets
+    Wif,
+
+    /// Extended private key
+    Xprv,
+
+    /// Hex-encoded entropy of 16 to 64 `bytes`
+    Hex { bytes: u32 },
+}
+
+impl Bip85Application {
+    /// Checks that the number of words or bytes is supported by the
+    /// application
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            Bip85Application::Mnemonic { language, words } => {
+                language <= 8 && matches!(words, 12 | 18 | 24)
+            }
+            Bip85Application::Hex { bytes } => (16..=64).contains(&bytes),
+            Bip85Application::Wif | Bip85Application::Xprv => true,
+        }
+    }
+}
+
+impl fmt::Display for Bip85Application {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bip85Application::Mnemonic { language, words } => {
+                write!(f, "mnemonic:{}:{}", words, language)
+            }
+            Bip85Application::Wif => f.write_str("wif"),
+            Bip85Application::Xprv => f.write_str("xprv"),
+            Bip85Application::Hex { bytes } => write!(f, "hex:{}", bytes),
+        }
+    }
+}
+
+/// Error parsing [`Bip85Application`]
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum Bip85ParseError {
+    /// Unknown BIP-85 application `{0}`; supported applications are
+    /// `mnemonic[:<words>[:<language>]]`, `wif`, `xprv` and `hex[:<bytes>]`
+    UnknownApplication(String),
+
+    /// Invalid parameter `{0}` of BIP-85 application
+    Parameter(String),
+
+    /// Unsupported parameters of BIP-85 application `{0}`: mnemonic must
+    /// have 12, 18 or 24 words in a language with code 0 to 8, and hex
+    /// entropy must be 16 to 64 bytes long
+    Unsupported(Bip85Application),
+}
+
+impl FromStr for Bip85Application {
+    type Err = Bip85ParseError;
+
+    /// Parses application given as `mnemonic[:<words>[:<language>]]`, `wif`,
+    /// `xprv` or `hex[:<bytes>]`. Mnemonics have 24 English words and hex
+    /// entropy is 64 bytes long by default.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let name = parts.next().unwrap_or_default().to_lowercase();
+        let mut param = |default: u32| -> Result<u32, Self::Err> {
+            parts.next().map_or(Ok(default), |param| {
+                param
+                    .parse()
+                    .map_err(|_| Bip85ParseError::Parameter(param.to_string()))
+            })
+        };
+        let application = match name.as_str() {
+            "mnemonic" | "bip39" => {
+                let words = param(24)?;
+                Bip85Application::Mnemonic {
+                    language: param(0)?,
+                    words,
+                }
+            }
+            "wif" => Bip85Application::Wif,
+            "xprv" => Bip85Application::Xprv,
+            "hex" => Bip85Application::Hex { bytes: param(64)? },
+            _ => {
+                return Err(Bip85ParseError::UnknownApplication(s.to_string()))
+            }
+        };
+        if parts.next().is_some() {
+            return Err(Bip85ParseError::UnknownApplication(s.to_string()));
+        }
+        if !application.is_valid() {
+            return Err(Bip85ParseError::Unsupported(application));
+        }
+        Ok(application)
+    }
+}
+
+impl StrictEncode for Bip85Application {
+    fn strict_encode<E: io::Write>(
+        &self,
+        mut e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        Ok(match *self {
+            Bip85Application::Mnemonic { language, words } => {
+                0u8.strict_encode(&mut e)?
+                    + language.strict_encode(&mut e)?
+                    + words.strict_encode(&mut e)?
+            }
+            Bip85Application::Wif => 1u8.strict_encode(&mut e)?,
+            Bip85Application::Xprv => 2u8.strict_encode(&mut e)?,
+            Bip85Application::Hex { bytes } => {
+                3u8.strict_encode(&mut e)? + bytes.strict_encode(&mut e)?
+            }
+        })
+    }
+}
+
+impl StrictDecode for Bip85Application {
+    fn strict_decode<D: io::Read>(
+        mut d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        Ok(match u8::strict_decode(&mut d)? {
+            0 => Bip85Application::Mnemonic {
+                language: u32::strict_decode(&mut d)?,
+                words: u32::strict_decode(&mut d)?,
+            },
+            1 => Bip85Application::Wif,
+            2 => Bip85Application::Xprv,
+            3 => Bip85Application::Hex {
+                bytes: u32::strict_decode(&mut d)?,
+            },
+            tag => {
+                return Err(strict_encoding::Error::DataIntegrityError(
+                    format!("unknown BIP-85 application type {}", tag),
+                ))
+            }
+        })
+    }
+}
+
+/// Limit on the number of PSBTs signed by an account within a time period
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(
+    Clone, Copy, PartialEq, Eq, Hash, Debug, Display, StrictEncode, StrictDecode,
+)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+#[display("{count}/{period}")]
+pub struct RateLimit {
+    /// Maximal number of signed PSBTs
+    pub count: u32,
+
+    /// Time period, in seconds
+    pub period: u64,
+}
+
+impl FromStr for RateLimit {
+    type Err = PolicyParseError;
+
+    /// Parses rate limit given as `<count>/<period>`, with the period in
+    /// seconds
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '/');
+        match (parts.next(), parts.next()) {
+            (Some(count), Some(period)) => Ok(RateLimit {
+                count: count
+                    .parse()
+                    .map_err(|_| PolicyParseError::RateLimit(s.to_string()))?,
+                period: period
+                    .parse()
+                    .ok()
+                    .filter(|period| *period > 0)
+                    .ok_or_else(|| {
+                        PolicyParseError::RateLimit(s.to_string())
+                    })?,
+            }),
+            _ => Err(PolicyParseError::RateLimit(s.to_string())),
+        }
+    }
+}
+
+/// Error parsing signing policy rules
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum PolicyParseError {
+    /// Invalid rate limit `{0}`; it must be given as `<count>/<period>`,
+    /// with non-zero period in seconds
+    RateLimit(String),
+}
+
+/// Signing policy of the keys account, evaluated by the daemon before
+/// signing PSBTs spending the account funds. Default policy has no
+/// restrictions on signing, while ECDH with the account node key is denied.
+#[cfg_attr(feature = "serde", serde_as)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(
+    Clone, PartialEq, Eq, Hash, Debug, Default, StrictEncode, StrictDecode,
+)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct SigningPolicy {
+    /// Maximal amount, in satoshis, which a transaction may send to the
+    /// outputs not belonging to the account keyring
+    pub max_amount: Option<u64>,
+
+    /// Scripts of the addresses which the transaction may send funds to;
+    /// outputs returning funds to the account keyring are always allowed.
+    /// Empty list allows any destination.
+    #[serde_as(as = "Vec<Hex>")]
+    pub destinations: Vec<Script>,
+
+    /// Sighash types allowed for the signed inputs; empty list allows only
+    /// `SIGHASH_ALL` and BIP-341 `SIGHASH_DEFAULT`
+    pub sighash_types: Vec<u8>,
+
+    /// Limit on the number of signed PSBTs
+    pub rate_limit: Option<RateLimit>,
+
+    /// Allows ECDH with the account Lightning node key, performed by the
+    /// daemon for BOLT-8 handshakes of the external Lightning nodes
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub allow_ecdh: bool,
+}
+
+impl SigningPolicy {
+    /// Detects whether the policy has no restrictions
+    pub fn is_unrestricted(&self) -> bool {
+        *self == SigningPolicy::default()
+    }
+
+    /// Checks that the allowed sighash types are standard ones (including
+    /// BIP-341 `SIGHASH_DEFAULT`) and that the rate limit period is not zero
+    pub fn is_valid(&self) -> bool {
+        self.sighash_types.iter().all(
+            |sighash_type| matches!(sighash_type, 0x00..=0x03 | 0x81..=0x83),
+        ) && self.rate_limit.map(|rate_limit| rate_limit.period) != Some(0)
+    }
+}
+
+impl fmt::Display for SigningPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_unrestricted() {
+            return f.write_str("unrestricted");
+        }
+        let mut rules = vec![];
+        if let Some(max_amount) = self.max_amount {
+            rules.push(format!("max amount {} sat", max_amount));
+        }
+        if !self.destinations.is_empty() {
+            rules.push(format!("{} destinations", self.destinations.len()));
+        }
+        if !self.sighash_types.is_empty() {
+            let types = self
+                .sighash_types
+                .iter()
+                .map(|sighash_type| format!("{:#04x}", sighash_type))
+                .collect::<Vec<_>>();
+            rules.push(format!("sighash types {}", types.join(", ")));
+        }
+        if let Some(rate_limit) = self.rate_limit {
+            rules.push(format!("rate limit {} sec", rate_limit));
+        }
+        if self.allow_ecdh {
+            rules.push(s!("ECDH allowed"));
+        }
+        f.write_str(&rules.join(", "))
+    }
+}
+
+/// Identifier of the multisig account group, see [`MultisigGroup::id`]
+pub type MultisigId = sha256::Hash;
+
+/// Extended public key of a multisig group cosigner with its origin, written
+/// as `[<fingerprint>/<path>]<xpub>` like in output descriptors. Keys given
+/// without origin are considered to be master keys.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, PartialEq, Eq, Debug, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct CosignerKey {
+    pub xpubkey: ExtendedPubKey,
+    pub origin: KeySource,
+}
+
+impl CosignerKey {
+    /// Constructs cosigner key for a master extended public key
+    pub fn master(xpubkey: ExtendedPubKey) -> Self {
+        CosignerKey {
+            origin: (xpubkey.fingerprint(), DerivationPath::master()),
+            xpubkey,
+        }
+    }
+}
+
+impl fmt::Display for CosignerKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (fingerprint, path) = &self.origin;
+        write!(
+            f,
+            "[{}{}]{}",
+            fingerprint,
+            path.to_string().trim_start_matches('m'),
+            self.xpubkey
+        )
+    }
+}
+
+/// Error parsing [`CosignerKey`]
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum CosignerParseError {
+    /// Key origin must be given as `[<fingerprint>/<path>]`
+    Origin,
+
+    /// Invalid key origin fingerprint: {0}
+    #[from]
+    Fingerprint(hex::Error),
+
+    /// Invalid extended public key or origin derivation path: {0}
+    #[from]
+    Key(bip32::Error),
+}
+
+impl FromStr for CosignerKey {
+    type Err = CosignerParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (origin, xpubkey) = match s.strip_prefix('[') {
+            Some(rest) => {
+                let mut parts = rest.splitn(2, ']');
+                match (parts.next(), parts.next()) {
+                    (Some(origin), Some(xpubkey)) => (Some(origin), xpubkey),
+                    _ => return Err(CosignerParseError::Origin),
+                }
+            }
+            None => (None, s),
+        };
+        let xpubkey = ExtendedPubKey::from_str(xpubkey)?;
+        let origin = match origin {
+            Some(origin) => {
+                let mut parts = origin.splitn(2, '/');
+                let fingerprint =
+                    Fingerprint::from_hex(parts.next().unwrap_or_default())?;
+                let path = match parts.next() {
+                    Some(path) => {
+                        DerivationPath::from_str(&format!("m/{}", path))?
+                    }
+                    None => DerivationPath::master(),
+                };
+                (fingerprint, path)
+            }
+            None => return Ok(CosignerKey::master(xpubkey)),
+        };
+        Ok(CosignerKey { xpubkey, origin })
+    }
+}
+
+/// Maximal number of cosigners in a multisig group, limited by the
+/// `OP_CHECKMULTISIG` opcode
+pub const MAX_COSIGNERS: usize = 20;
+
+/// N-of-M account group spending with `wsh(multi(...))` scripts, in which
+/// some of the cosigners are the vault accounts, while others are external
+/// keys. Order of the cosigners defines order of the keys in the scripts.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, PartialEq, Eq, Debug, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct MultisigGroup {
+    pub name: String,
+
+    /// Number of signatures required to spend group funds
+    pub threshold: u8,
+
+    pub cosigners: Vec<CosignerKey>,
+}
+
+impl MultisigGroup {
+    /// Returns identifier of the group, committing to the threshold and the
+    /// cosigner keys in their order, but not to the group name
+    pub fn id(&self) -> MultisigId {
+        let mut engine = sha256::Hash::engine();
+        engine.input(&[self.threshold]);
+        for cosigner in &self.cosigners {
+            engine.input(&cosigner.xpubkey.encode());
+        }
+        sha256::Hash::from_engine(engine)
+    }
+}
+
+impl fmt::Display for MultisigGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}, {}-of-{}",
+            self.id(),
+            self.name,
+            self.threshold,
+            self.cosigners.len()
+        )
+    }
+}
+
+/// Identifier of a MuSig2 signing session held by the daemon
+pub type MuSigSessionId = sha256::Hash;
+
+/// MuSig2 signing session started by the daemon for one of the vault
+/// accounts
+#[cfg_attr(feature = "serde", serde_as)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, PartialEq, Eq, Debug, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct MuSigSession {
+    pub id: MuSigSessionId,
+    pub key_id: XpubIdentifier,
+
+    /// Participant key of the vault account
+    #[serde_as(as = "DisplayFromStr")]
+    pub participant: secp256k1::PublicKey,
+
+    /// BIP-340 x-only aggregated key of all participants
+    #[serde_as(as = "Hex")]
+    pub aggregated_key: Vec<u8>,
+
+    /// Public nonce of the vault account, which has to be sent to the
+    /// other participants
+    #[serde_as(as = "Hex")]
+    pub nonce: Vec<u8>,
+}
+
+impl fmt::Display for MuSigSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}, aggregated key {})",
+            self.id,
+            self.key_id,
+            self.aggregated_key.to_hex()
+        )
+    }
+}
+
+/// Public nonce of a MuSig2 session participant
+#[cfg_attr(feature = "serde", serde_as)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, PartialEq, Eq, Debug, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct MuSigNonce {
+    #[serde_as(as = "DisplayFromStr")]
+    pub participant: secp256k1::PublicKey,
+
+    #[serde_as(as = "Hex")]
+    pub nonce: Vec<u8>,
+}
+
+impl fmt::Display for MuSigNonce {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.participant, self.nonce.to_hex())
+    }
+}
+
+impl FromStr for MuSigNonce {
+    type Err = MuSigNonceParseError;
+
+    /// Parses nonce given as `<participant key>:<nonce>`, both in
+    /// hexadecimal format
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some(participant), Some(nonce)) => Ok(MuSigNonce {
+                participant: secp256k1::PublicKey::from_str(participant)?,
+                nonce: Vec::from_hex(nonce)?,
+            }),
+            _ => Err(MuSigNonceParseError::Format),
+        }
+    }
+}
+
+/// Error parsing [`MuSigNonce`]
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum MuSigNonceParseError {
+    /// MuSig2 nonce must be given as `<participant key>:<nonce>`
+    Format,
+
+    /// Invalid participant key: {0}
+    #[from]
+    Participant(secp256k1::Error),
+
+    /// Invalid nonce: {0}
+    #[from]
+    Nonce(hex::Error),
+}
+
+/// Partial signature produced by the vault account in a MuSig2 session
+#[cfg_attr(feature = "serde", serde_as)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, PartialEq, Eq, Debug, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct MuSigSignature {
+    #[serde_as(as = "Hex")]
+    pub partial: Vec<u8>,
+
+    /// BIP-340 signature aggregated from the partial signatures of all
+    /// participants, if they were provided to the daemon
+    #[serde_as(as = "Option<Hex>")]
+    pub signature: Option<Vec<u8>>,
+}
+
+/// Length of the BIP-47 version 1 payment code payload
+pub const PAYMENT_CODE_LEN: usize = 80;
+
+/// Base58 prefix byte of the serialized payment codes, making them start
+/// with `PM8T`
+const PAYMENT_CODE_PREFIX: u8 = 0x47;
+
+/// BIP-47 version 1 reusable payment code: public key and chain code of the
+/// `m/47'/<coin>'/<account>'` extended key
+#[cfg_attr(feature = "serde", serde_as)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct PaymentCode {
+    #[serde_as(as = "DisplayFromStr")]
+    pub pubkey: secp256k1::PublicKey,
+    pub chain_code: ChainCode,
+}
+
+impl PaymentCode {
+    /// Serializes payment code into the binary payload: version, features,
+    /// compressed public key, chain code and zero padding
+    pub fn serialize(&self) -> [u8; PAYMENT_CODE_LEN] {
+        let mut data = [0u8; PAYMENT_CODE_LEN];
+        data[0] = 0x01;
+        data[2..35].copy_from_slice(&self.pubkey.serialize());
+        data[35..67].copy_from_slice(self.chain_code.as_bytes());
+        data
+    }
+
+    /// Parses binary payload of the version 1 payment code
+    pub fn from_slice(data: &[u8]) -> Result<Self, PaymentCodeParseError> {
+        if data.len() != PAYMENT_CODE_LEN {
+            return Err(PaymentCodeParseError::Length(data.len()));
+        }
+        if data[0] != 0x01 {
+            return Err(PaymentCodeParseError::Version(data[0]));
+        }
+        if data[2] != 0x02 && data[2] != 0x03 {
+            return Err(PaymentCodeParseError::Key(
+                secp256k1::Error::InvalidPublicKey,
+            ));
+        }
+        Ok(PaymentCode {
+            pubkey: secp256k1::PublicKey::from_slice(&data[2..35])?,
+            chain_code: ChainCode::from(&data[35..67]),
+        })
+    }
+
+    /// Returns extended public key from which payment and notification keys
+    /// of the code owner are derived
+    pub fn xpubkey(&self, network: bitcoin::Network) -> ExtendedPubKey {
+        ExtendedPubKey {
+            network,
+            depth: 3,
+            parent_fingerprint: Fingerprint::default(),
+            child_number: ChildNumber::from(0),
+            public_key: bitcoin::PublicKey {
+                compressed: true,
+                key: self.pubkey,
+            },
+            chain_code: self.chain_code,
+        }
+    }
+}
+
+impl fmt::Display for PaymentCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut data = Vec::with_capacity(PAYMENT_CODE_LEN + 1);
+        data.push(PAYMENT_CODE_PREFIX);
+        data.extend_from_slice(&self.serialize());
+        f.write_str(&base58::check_encode_slice(&data))
+    }
+}
+
+impl FromStr for PaymentCode {
+    type Err = PaymentCodeParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let data = base58::from_check(s)?;
+        match data.first() {
+            Some(&PAYMENT_CODE_PREFIX) => PaymentCode::from_slice(&data[1..]),
+            Some(prefix) => Err(PaymentCodeParseError::Prefix(*prefix)),
+            None => Err(PaymentCodeParseError::Length(0)),
+        }
+    }
+}
+
+impl StrictEncode for PaymentCode {
+    fn strict_encode<E: io::Write>(
+        &self,
+        e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        self.serialize().to_vec().strict_encode(e)
+    }
+}
+
+impl StrictDecode for PaymentCode {
+    fn strict_decode<D: io::Read>(
+        d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        PaymentCode::from_slice(&Vec::<u8>::strict_decode(d)?).map_err(|err| {
+            strict_encoding::Error::DataIntegrityError(err.to_string())
+        })
+    }
+}
+
+/// Error parsing [`PaymentCode`]
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum PaymentCodeParseError {
+    /// Invalid payment code encoding: {0}
+    #[from]
+    Base58(base58::Error),
+
+    /// Unknown payment code prefix byte {0}
+    Prefix(u8),
+
+    /// Payment code version {0} is not supported; only version 1 codes can
+    /// be used
+    Version(u8),
+
+    /// Payment code payload must be 80 bytes long, while {0} bytes were
+    /// given
+    Length(usize),
+
+    /// Invalid payment code public key: {0}
+    #[from]
+    Key(secp256k1::Error),
+}
+
+/// Direction of the payments between the BIP-47 payment code owners
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "lowercase")
+)]
+#[derive(
+    Copy, Clone, PartialEq, Eq, Hash, Debug, Display, StrictEncode, StrictDecode,
+)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub enum PaymentDirection {
+    /// Payment from the vault account to the counterparty
+    #[display("send")]
+    Send,
+
+    /// Payment from the counterparty to the vault account
+    #[display("receive")]
+    Receive,
+}
+
+/// Key of a payment between the vault account and the counterparty
+/// derived with BIP-47 shared secret
+#[cfg_attr(feature = "serde", serde_as)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, PartialEq, Eq, Debug, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct PaymentKey {
+    pub direction: PaymentDirection,
+    pub index: u32,
+    #[serde_as(as = "DisplayFromStr")]
+    pub pubkey: bitcoin::PublicKey,
+    /// P2PKH address of the key
+    pub address: String,
+}
+
+impl fmt::Display for PaymentKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} #{}: {} {}",
+            self.direction, self.index, self.address, self.pubkey
+        )
+    }
+}
+
+/// BIP-47 payment code of a vault account together with the keys of the
+/// payments with a counterparty
+#[cfg_attr(feature = "serde", serde_as)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, PartialEq, Eq, Debug, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct PaymentCodeInfo {
+    pub key_id: XpubIdentifier,
+    #[serde_as(as = "DisplayFromStr")]
+    pub code: PaymentCode,
+    /// Address receiving notification transactions from the senders
+    pub notification_address: String,
+    /// Counterparty payment codes saved with the account under their names
+    #[serde_as(as = "BTreeMap<_, DisplayFromStr>")]
+    pub contacts: BTreeMap<String, PaymentCode>,
+    /// Counterparty for which the payment keys were derived
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub counterparty: Option<PaymentCode>,
+    pub payments: Vec<PaymentKey>,
+}
+
+impl fmt::Display for PaymentCodeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}, notification address {})",
+            self.code, self.key_id, self.notification_address
+        )
+    }
+}
+
+/// Lightning channel basepoints derived from the Lightning node keyring; the
+/// value is the first segment of the key derivation path
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Display)]
+#[display(Debug)]
+#[repr(u32)]
+pub enum Basepoint {
+    Funding = 0,
+    Revocation = 1,
+    Payment = 2,
+    DelayedPayment = 3,
+    Htlc = 4,
+}
+
+impl Basepoint {
+    /// Returns derivation path `<basepoint>/<channel index>` of the
+    /// basepoint key relative to the keyring master key
+    pub fn path(self, channel: u32) -> Result<DerivationPath, bip32::Error> {
+        Ok(DerivationPath::from(vec![
+            ChildNumber::from_normal_idx(self as u32)?,
+            ChildNumber::from_normal_idx(channel)?,
+        ]))
+    }
+}
+
+/// Public keys of the Lightning node and one of its channels: node identity
+/// key and BOLT-3 channel basepoints
+#[cfg_attr(feature = "serde", serde_as)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, PartialEq, Eq, Debug, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct LnKeySet {
+    pub key_id: XpubIdentifier,
+    pub channel: u32,
+    #[serde_as(as = "DisplayFromStr")]
+    pub node_id: secp256k1::PublicKey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub funding_pubkey: secp256k1::PublicKey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub revocation_basepoint: secp256k1::PublicKey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub payment_basepoint: secp256k1::PublicKey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub delayed_payment_basepoint: secp256k1::PublicKey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub htlc_basepoint: secp256k1::PublicKey,
+}
+
+impl fmt::Display for LnKeySet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "channel #{} of node {} ({})",
+            self.channel, self.node_id, self.key_id
+        )
+    }
+}
+
+/// Signatures of the BOLT-7 `channel_announcement` message made by the
+/// node key and the channel funding key of one of the channel sides
+#[cfg_attr(feature = "serde", serde_as)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, PartialEq, Eq, Debug, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct AnnouncementSignatures {
+    #[serde_as(as = "DisplayFromStr")]
+    pub node_signature: secp256k1::Signature,
+    #[serde_as(as = "DisplayFromStr")]
+    pub bitcoin_signature: secp256k1::Signature,
+}
+
+impl fmt::Display for AnnouncementSignatures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "node {}, bitcoin {}",
+            self.node_signature, self.bitcoin_signature
+        )
+    }
+}
+
+/// Template of a relative derivation path with a single `*` wildcard, which
+/// is replaced with each of the indexes from a derivation range, like `0/*`.
+/// Since keys are derived from the extended public key, all path segments
+/// must be normal (non-hardened).
+#[derive(Clone, PartialEq, Eq, Hash, Debug, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct DerivationTemplate {
+    pub prefix: DerivationPath,
+    pub suffix: DerivationPath,
+}
+
+/// Error parsing [`DerivationTemplate`]
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum TemplateError {
+    /// Derivation template must contain exactly one `*` wildcard
+    Wildcard,
+
+    /// Derivation template can't contain hardened path segments
+    Hardened,
+
+    /// Invalid derivation path segment in the template: {0}
+    #[from]
+    Path(bip32::Error),
+}
+
+impl DerivationTemplate {
+    /// Returns derivation path for a given `index` substituting the wildcard
+    pub fn path(&self, index: u32) -> Result<DerivationPath, bip32::Error> {
+        Ok(self
+            .prefix
+            .child(ChildNumber::from_normal_idx(index)?)
+            .extend(&self.suffix))
+    }
+}
+
+impl FromStr for DerivationTemplate {
+    type Err = TemplateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim_start_matches('m').trim_start_matches('/');
+        let mut parts = s.split('*');
+        let (prefix, suffix) = match (parts.next(), parts.next(), parts.next())
+        {
+            (Some(prefix), Some(suffix), None) => (prefix, suffix),
+            _ => return Err(TemplateError::Wildcard),
+        };
+        let parse = |path: &str| -> Result<DerivationPath, TemplateError> {
+            let path = path.trim_matches('/');
+            let path = if path.is_empty() {
+                DerivationPath::master()
+            } else {
+                DerivationPath::from_str(&format!("m/{}", path))?
+            };
+            if path
+                .as_ref()
+                .iter()
+                .any(|child| matches!(child, ChildNumber::Hardened { .. }))
+            {
+                return Err(TemplateError::Hardened);
+            }
+            Ok(path)
+        };
+        Ok(Self {
+            prefix: parse(prefix)?,
+            suffix: parse(suffix)?,
+        })
+    }
+}
+
+impl fmt::Display for DerivationTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for child in self.prefix.as_ref() {
+            write!(f, "{}/", child)?;
+        }
+        f.write_str("*")?;
+        for child in self.suffix.as_ref() {
+            write!(f, "/{}", child)?;
+        }
+        Ok(())
+    }
+}
+
+/// Previous output controlled by one of the vault accounts, which is spent
+/// by a composed PSBT
+#[derive(Clone, PartialEq, Eq, Debug, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct PsbtInput {
+    pub outpoint: OutPoint,
+    /// Amount of the spent output, in satoshis
+    pub amount: u64,
+    /// Account controlling the spent output
+    pub key_id: XpubIdentifier,
+    /// Derivation path of the output key relative to the account
+    pub terminal: DerivationPath,
+}
+
+/// Output of a composed PSBT
+#[derive(Clone, PartialEq, Eq, Debug, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct PsbtOutput {
+    pub script_pubkey: Script,
+    /// Amount sent to the output, in satoshis
+    pub amount: u64,
+}
+
+/// Input of a PSBT as seen by the vault before signing
+#[cfg_attr(feature = "serde", serde_as)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, PartialEq, Eq, Debug, StrictEncode, StrictDecode)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct InputAnalysis {
+    pub index: u32,
+    #[serde_as(as = "DisplayFromStr")]
+    pub outpoint: OutPoint,
+    /// Amount of the spent output, if the PSBT provides it
+    pub amount: Option<u64>,
+    /// Keyring which key is used by the input, if it belongs to the vault
+    pub keyring: Option<XpubIdentifier>,
+    /// Derivation of the input key from the keyring master key
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub derivation: Option<DerivationPath>,
+    /// Whether the input spends P2TR output
+    pub taproot: bool,
+    /// Whether the vault holds private key for the input and the account
+    /// lifecycle state allows signing
+    pub signable: bool,
+    /// Whether the input already has a signature or is finalized
+    pub signed: bool,
+}
+
+impl fmt::Display for InputAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{} {}", self.index, self.outpoint)?;
+        match self.amount {
+            Some(amount) => write!(f, ", {} sat", amount)?,
+            None => f.write_str(", unknown amount")?,
+        }
+        match (self.keyring, &self.derivation) {
+            (Some(keyring), Some(derivation)) => {
+                write!(f, ", keyring {} at {}", keyring, derivation)?
+            }
+            _ => f.write_str(", foreign key")?,
+        }
+        if self.signed {
+            f.write_str(", signed")?;
+        }
+        if self.signable {
+            f.write_str(", signable")
+        } else {
+            f.write_str(", not signable")
+        }
+    }
+}
+
+/// Report of the PSBT analysis made by the vault without signing it.
+/// Amounts are given in satoshis.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
+#[display(
+    "{txid}: spends {spent} sat of the vault, receives {received} sat to the \
+     vault"
+)]
+#[strict_encoding_crate(lnpbp::strict_encoding)]
+pub struct PsbtAnalysis {
+    pub txid: Txid,
+    pub inputs: Vec<InputAnalysis>,
+
+    /// Total amount of the inputs; absent if amounts of some inputs are
+    /// unknown
+    pub input_total: Option<u64>,
+
+    /// Total amount of the outputs
+    pub output_total: u64,
+
+    /// Transaction fee; absent if amounts of some inputs are unknown
+    pub fee: Option<u64>,
+
+    /// Total amount of the inputs signed with the vault keys
+    pub spent: u64,
+
+    /// Total amount of the outputs paying to the vault keyrings
+    pub received: u64,
+
+    /// Reasons for which the vault would refuse to sign the PSBT, like
+    /// violated signing policies
+    pub issues: Vec<String>,
+}
+
+/// Error parsing [`PsbtInput`] or [`PsbtOutput`]
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum ComposeParseError {
+    /// Transaction input must be given as
+    /// `<txid>:<vout>:<amount>:<key_id>:<path>`
+    InputFormat,
+
+    /// Transaction output must be given as `<address>:<amount>`
+    OutputFormat,
+
+    /// Invalid amount `{0}`; amounts must be given in satoshis
+    Amount(String),
+
+    /// Invalid transaction or key id: {0}
+    #[from]
+    Hex(hex::Error),
+
+    /// Invalid address: {0}
+    Address(String),
+
+    /// Invalid derivation path: {0}
+    #[from]
+    Path(bip32::Error),
+}
+
+fn parse_amount(s: &str) -> Result<u64, ComposeParseError> {
+    s.parse()
+        .map_err(|_| ComposeParseError::Amount(s.to_string()))
+}
+
+impl FromStr for PsbtInput {
+    type Err = ComposeParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.splitn(5, ':').collect::<Vec<_>>();
+        let (txid, vout, amount, key_id, path) = match parts[..] {
+            [txid, vout, amount, key_id, path] => {
+                (txid, vout, amount, key_id, path)
+            }
+            _ => return Err(ComposeParseError::InputFormat),
+        };
+        let path = path.trim_start_matches('m').trim_matches('/');
+        Ok(Self {
+            outpoint: OutPoint::new(
+                Txid::from_str(txid)?,
+                vout.parse().map_err(|_| ComposeParseError::InputFormat)?,
+            ),
+            amount: parse_amount(amount)?,
+            key_id: XpubIdentifier::from_str(key_id)?,
+            terminal: if path.is_empty() {
+                DerivationPath::master()
+            } else {
+                DerivationPath::from_str(&format!("m/{}", path))?
+            },
+        })
+    }
+}
+
+impl FromStr for PsbtOutput {
+    type Err = ComposeParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.rsplitn(2, ':');
+        let (amount, address) = match (parts.next(), parts.next()) {
+            (Some(amount), Some(address)) => (amount, address),
+            _ => return Err(ComposeParseError::OutputFormat),
+        };
+        Ok(Self {
+            script_pubkey: Address::from_str(address)
+                .map_err(|err| ComposeParseError::Address(err.to_string()))?
+                .script_pubkey(),
+            amount: parse_amount(amount)?,
+        })
+    }
+}
+
+impl fmt::Display for AccountInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)?;
+        if !self.aliases.is_empty() {
+            write!(f, " (aka {})", self.aliases.join(", "))?;
+        }
+        write!(f, " [{}] {}", self.fingerprint, self.id)?;
+        if let Some((fingerprint, ref path)) = self.key_source {
+            write!(f, ", derived from [{}]{}", fingerprint, path)?;
+        }
+        write!(f, ", {}", self.lifecycle)?;
+        if self.watch_only {
+            f.write_str(", watch-only")?;
+        }
+        if !self.labels.is_empty() {
+            let labels = self
+                .labels
+                .iter()
+                .map(|(key, value)| match value.len() {
+                    0 => key.clone(),
+                    _ => format!("{}={}", key, value),
+                })
+                .collect::<Vec<_>>();
+            write!(f, ", labels {}", labels.join(" "))?;
+        }
+        Ok(())
+    }
 }
 
-#[cfg(feature = "node")]
+#[cfg(feature = "_vault")]
 impl From<&Keyring> for AccountInfo {
     fn from(keyring: &Keyring) -> Self {
         let mut info = AccountInfo::from(keyring.master_account());
         info.key_source = keyring.key_source().clone();
+        info.chain = Some(keyring.chain());
         info
     }
 }
 
-#[cfg(feature = "node")]
+#[cfg(feature = "_vault")]
 impl From<&KeysAccount> for AccountInfo {
     fn from(account: &KeysAccount) -> Self {
         let details = match account.details().len() {
@@ -70,14 +2253,16 @@ impl From<&KeysAccount> for AccountInfo {
             details,
             key_id: account.identifier(),
             fingerprint: account.fingerprint(),
-            application: None,
-            // TODO: Re-emable after KeyApplications will get to rust-bitcoin
-            /* account
-            .xpubkey()
-            .version
-            .application::<DefaultResolver>(), */
+            application: *account.application(),
             assets: account.assets().clone(),
             key_source: None,
+            lifecycle: *account.lifecycle(),
+            branches: *account.branches(),
+            policy: account.policy().clone(),
+            aliases: account.aliases().clone(),
+            labels: account.labels().clone(),
+            watch_only: account.is_watch_only(),
+            chain: None,
         }
     }
 }
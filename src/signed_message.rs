@@ -0,0 +1,171 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Signing of arbitrary text messages in the Bitcoin Signed Message format
+//! with the BIP-137 signature header, which encodes both the public key
+//! recovery id and the type of the address the signature is made for.
+
+use bitcoin::hashes::sha256d;
+use bitcoin::secp256k1::recovery::{RecoverableSignature, RecoveryId};
+use bitcoin::secp256k1::{self, Message};
+use bitcoin::util::misc::signed_msg_hash;
+use bitcoin::{Address, PublicKey};
+
+/// Length of the serialized signature with the header byte
+pub const SIGNATURE_LEN: usize = 65;
+
+/// Type of the address the message signature is made for, defining the range
+/// of the BIP-137 header byte values
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Display)]
+#[display(Debug)]
+pub enum SignatureType {
+    /// P2PKH address with uncompressed public key
+    P2pkhUncompressed,
+
+    /// P2PKH address with compressed public key
+    P2pkh,
+
+    /// P2WPKH address nested into P2SH
+    P2shP2wpkh,
+
+    /// Native segwit P2WPKH address
+    P2wpkh,
+}
+
+impl SignatureType {
+    fn header_base(self) -> u8 {
+        match self {
+            SignatureType::P2pkhUncompressed => 27,
+            SignatureType::P2pkh => 31,
+            SignatureType::P2shP2wpkh => 35,
+            SignatureType::P2wpkh => 39,
+        }
+    }
+
+    fn from_header(header: u8) -> Option<Self> {
+        Some(match header {
+            27..=30 => SignatureType::P2pkhUncompressed,
+            31..=34 => SignatureType::P2pkh,
+            35..=38 => SignatureType::P2shP2wpkh,
+            39..=42 => SignatureType::P2wpkh,
+            _ => return None,
+        })
+    }
+}
+
+/// Errors in message signature verification
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum Error {
+    /// Message signature must be {0} bytes long
+    InvalidLength(usize),
+
+    /// Message signature has unknown header byte {0}
+    InvalidHeader(u8),
+
+    /// Message signature is invalid
+    InvalidSignature,
+}
+
+/// Computes hash of the message prefixed with `\x18Bitcoin Signed
+/// Message:\n` and the message length, which is the actual data being signed
+pub fn hash(message: &str) -> sha256d::Hash {
+    signed_msg_hash(message)
+}
+
+/// Serializes recoverable signature with BIP-137 header byte for the given
+/// signature type
+pub fn serialize(
+    signature: &RecoverableSignature,
+    signature_type: SignatureType,
+) -> [u8; SIGNATURE_LEN] {
+    let (recovery_id, compact) = signature.serialize_compact();
+    let mut data = [0u8; SIGNATURE_LEN];
+    data[0] = signature_type.header_base() + recovery_id.to_i32() as u8;
+    data[1..].copy_from_slice(&compact);
+    data
+}
+
+/// Recovers public key from the serialized message signature, returning it
+/// together with the type of the address the signature was made for
+pub fn recover(
+    signature: &[u8],
+    message: &str,
+) -> Result<(PublicKey, SignatureType), Error> {
+    if signature.len() != SIGNATURE_LEN {
+        return Err(Error::InvalidLength(SIGNATURE_LEN));
+    }
+    let header = signature[0];
+    let signature_type = SignatureType::from_header(header)
+        .ok_or(Error::InvalidHeader(header))?;
+    let recovery_id = RecoveryId::from_i32(((header - 27) % 4) as i32)
+        .map_err(|_| Error::InvalidHeader(header))?;
+    let signature =
+        RecoverableSignature::from_compact(&signature[1..], recovery_id)
+            .map_err(|_| Error::InvalidSignature)?;
+    let key = crate::SECP256K1
+        .recover(&digest(message), &signature)
+        .map_err(|_| Error::InvalidSignature)?;
+    Ok((
+        PublicKey {
+            compressed: signature_type != SignatureType::P2pkhUncompressed,
+            key,
+        },
+        signature_type,
+    ))
+}
+
+/// Verifies that the message signature is made with the given public key
+pub fn verify_pubkey(
+    signature: &[u8],
+    message: &str,
+    pubkey: &PublicKey,
+) -> Result<bool, Error> {
+    let (recovered, _) = recover(signature, message)?;
+    Ok(recovered.key == pubkey.key)
+}
+
+/// Verifies that the message signature is made with the key controlling the
+/// given address. Since BIP-137 header does not distinguish segwit addresses
+/// in some wallets, the address is compared against all single-key address
+/// types for the recovered public key.
+pub fn verify_address(
+    signature: &[u8],
+    message: &str,
+    address: &Address,
+) -> Result<bool, Error> {
+    let (pubkey, _) = recover(signature, message)?;
+    let network = address.network;
+    let candidates = [
+        Some(Address::p2pkh(&pubkey, network)),
+        Address::p2shwpkh(&pubkey, network).ok(),
+        Address::p2wpkh(&pubkey, network).ok(),
+    ];
+    Ok(candidates
+        .iter()
+        .flatten()
+        .any(|candidate| candidate.script_pubkey() == address.script_pubkey()))
+}
+
+/// Creates recoverable signature of the message with a given secret key
+pub fn sign(
+    message: &str,
+    seckey: &secp256k1::SecretKey,
+) -> RecoverableSignature {
+    crate::SECP256K1.sign_recoverable(&digest(message), seckey)
+}
+
+fn digest(message: &str) -> Message {
+    Message::from_slice(&hash(message)[..])
+        .expect("message hash is always 32 bytes")
+}
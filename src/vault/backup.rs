@@ -0,0 +1,185 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Encrypted vault backups. After each update of the vault its strict-encoded
+//! snapshot is encrypted for the daemon node key with the scheme from
+//! [`crate::crypto`] and written into the backup directory under a name
+//! containing the snapshot creation time. Only the configured number of the
+//! most recent snapshots is retained.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use chrono::Utc;
+use lnpbp::strict_encoding::{strict_deserialize, strict_serialize};
+
+use super::{driver, Keyring};
+use crate::crypto;
+use crate::error::BootstrapError;
+
+/// Default directory for the backups, relative to the daemon data directory
+pub const DEFAULT_DIR: &str = "backups";
+
+/// Default number of retained backup snapshots
+pub const DEFAULT_RETENTION: u16 = 10;
+
+const PREFIX: &str = "vault-";
+const EXTENSION: &str = "backup";
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(crate = "serde_crate")]
+pub struct Config {
+    /// Directory for the backup snapshots
+    #[serde(default = "default_dir")]
+    pub dir: String,
+
+    /// Number of the most recent snapshots to keep; zero keeps all of them
+    #[serde(default = "default_retention")]
+    pub retention: u16,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            dir: default_dir(),
+            retention: DEFAULT_RETENTION,
+        }
+    }
+}
+
+fn default_dir() -> String {
+    DEFAULT_DIR.to_string()
+}
+
+fn default_retention() -> u16 {
+    DEFAULT_RETENTION
+}
+
+#[derive(Clone, Debug)]
+pub struct Backups {
+    dir: PathBuf,
+    retention: u16,
+    encryption_key: PublicKey,
+}
+
+impl Backups {
+    /// Sets up backups into the configured directory, creating it if
+    /// necessary. Snapshots are encrypted for `encryption_key`.
+    pub fn with(
+        config: &Config,
+        encryption_key: PublicKey,
+    ) -> Result<Self, BootstrapError> {
+        info!(
+            "Vault backups are written to {} (retaining {} snapshots)",
+            config.dir, config.retention
+        );
+        fs::create_dir_all(&config.dir)?;
+        Ok(Self {
+            dir: PathBuf::from(&config.dir),
+            retention: config.retention,
+            encryption_key,
+        })
+    }
+
+    /// Writes encrypted snapshot of `keyrings` and removes the snapshots
+    /// beyond the retention count. Returns path to the new snapshot.
+    pub fn write(
+        &self,
+        keyrings: &Vec<Keyring>,
+    ) -> Result<PathBuf, driver::Error> {
+        let name = format!(
+            "{}{}.{}",
+            PREFIX,
+            Utc::now().format("%Y%m%d-%H%M%S%.3f"),
+            EXTENSION
+        );
+        let path = self.dir.join(name);
+        debug!("Writing vault backup {}", path.display());
+        let data =
+            crypto::wrap(&strict_serialize(keyrings)?, self.encryption_key)?;
+        let temp_path = path.with_extension("tmp");
+        let mut fd = fs::File::create(&temp_path)?;
+        fd.write_all(&data)?;
+        fd.sync_all()?;
+        fs::rename(&temp_path, &path)?;
+        self.prune()?;
+        Ok(path)
+    }
+
+    /// Decrypts snapshot `data` written by [`Backups::write`]
+    pub fn read(
+        data: &[u8],
+        decryption_key: &SecretKey,
+    ) -> Result<Vec<Keyring>, driver::Error> {
+        let mut snapshot = crypto::unwrap(data, decryption_key)?;
+        let keyrings = strict_deserialize(&snapshot);
+        snapshot.iter_mut().for_each(|byte| *byte = 0);
+        Ok(keyrings?)
+    }
+
+    /// Returns paths of the existing snapshots, starting from the oldest one
+    pub fn list(&self) -> Result<Vec<PathBuf>, driver::Error> {
+        let mut snapshots = fs::read_dir(&self.dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, io::Error>>()?
+            .into_iter()
+            .filter(|path| is_snapshot(path))
+            .collect::<Vec<_>>();
+        // Snapshot names contain creation time, so the lexicographic order
+        // is chronological
+        snapshots.sort();
+        Ok(snapshots)
+    }
+
+    /// Overwrites all existing snapshots with zeros and removes them, since
+    /// they contain the encrypted keys being purged. Returns the list of the
+    /// removed snapshots.
+    pub fn shred(&self) -> Result<Vec<String>, driver::Error> {
+        let mut replicas = vec![];
+        for path in self.list()? {
+            let mut fd = fs::OpenOptions::new().write(true).open(&path)?;
+            let len = fd.metadata()?.len();
+            io::copy(&mut io::repeat(0).take(len), &mut fd)?;
+            fd.sync_all()?;
+            fs::remove_file(&path)?;
+            replicas.push(path.display().to_string());
+        }
+        Ok(replicas)
+    }
+
+    fn prune(&self) -> Result<(), driver::Error> {
+        if self.retention == 0 {
+            return Ok(());
+        }
+        let snapshots = self.list()?;
+        let excess = snapshots.len().saturating_sub(self.retention as usize);
+        for path in &snapshots[..excess] {
+            trace!("Removing outdated vault backup {}", path.display());
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+fn is_snapshot(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext == EXTENSION)
+        .unwrap_or_default()
+        && path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(|name| name.starts_with(PREFIX))
+            .unwrap_or_default()
+}
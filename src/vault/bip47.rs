@@ -0,0 +1,148 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Reusable payment codes (BIP-47, version 1). The payment code is the
+//! public part of `m/47'/<coin>'/<account>'` key derived from the account
+//! private key; its first child is the notification key, and the other
+//! children are combined with the counterparty keys by ECDH to produce
+//! unique payment keys for each payment between the parties.
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use bitcoin::util::bip32::{ChildNumber, DerivationPath};
+use bitcoin::{Address, Network};
+
+use super::keymgm::Error;
+use super::secret::SecretXpriv;
+use super::KeysAccount;
+use crate::rpc::types::{PaymentCode, PaymentDirection, PaymentKey};
+use crate::SECP256K1;
+
+/// Purpose of the BIP-47 derivation path
+pub const BIP47_PURPOSE: u32 = 47;
+
+/// Returns derivation path of the payment code for the BIP-47 `account`
+pub fn path(network: Network, account: u32) -> Result<DerivationPath, Error> {
+    let coin = match network {
+        Network::Bitcoin => 0,
+        _ => 1,
+    };
+    Ok(DerivationPath::from(vec![
+        ChildNumber::from_hardened_idx(BIP47_PURPOSE)?,
+        ChildNumber::from_hardened_idx(coin)?,
+        ChildNumber::from_hardened_idx(account)?,
+    ]))
+}
+
+/// Derives extended private key of the payment code for the BIP-47
+/// `account` from the vault account private key. The decryption key is
+/// wiped out right after the derivation, and the derived key is wiped when
+/// dropped.
+pub fn xprivkey(
+    account: &KeysAccount,
+    bip47_account: u32,
+    decryption_key: &mut SecretKey,
+) -> Result<SecretXpriv, Error> {
+    let path = path(account.network(), bip47_account)?;
+    Ok(account.xprivkey(decryption_key)?.derive_priv(&path)?)
+}
+
+/// Returns payment code of the extended private key produced by
+/// [`xprivkey`]
+pub fn payment_code(xpriv: &SecretXpriv) -> PaymentCode {
+    PaymentCode {
+        pubkey: PublicKey::from_secret_key(&SECP256K1, xpriv.secret_key()),
+        chain_code: xpriv.chain_code,
+    }
+}
+
+/// Returns public key of the payment code child with a given `index`; the
+/// key with index 0 is the notification key
+pub fn child_key(
+    code: &PaymentCode,
+    index: u32,
+) -> Result<bitcoin::PublicKey, Error> {
+    // Network does not affect public derivation
+    Ok(code
+        .xpubkey(Network::Bitcoin)
+        .ckd_pub(&SECP256K1, ChildNumber::from_normal_idx(index)?)?
+        .public_key)
+}
+
+/// Returns P2PKH address receiving notification transactions to the owner
+/// of the payment `code`
+pub fn notification_address(
+    code: &PaymentCode,
+    network: Network,
+) -> Result<Address, Error> {
+    Ok(Address::p2pkh(&child_key(code, 0)?, network))
+}
+
+/// Computes BIP-47 shared secret `SHA256(x(a·B))`, used to tweak the payment
+/// key of the receiver
+fn shared_secret(
+    seckey: &SecretKey,
+    pubkey: &PublicKey,
+) -> Result<SecretKey, Error> {
+    let mut point = *pubkey;
+    point.mul_assign(&SECP256K1, &seckey[..])?;
+    let secret = sha256::Hash::hash(&point.serialize()[1..]);
+    // Probability of the hash exceeding the curve order is negligible
+    Ok(SecretKey::from_slice(&secret[..])?)
+}
+
+/// Derives payment key with a given `index` between the owner of `xpriv`
+/// and the `counterparty`. Keys of the payments sent to the counterparty are
+/// computed from the sender notification key and the counterparty child
+/// key, while the received payments use the account child key and the
+/// counterparty notification key.
+pub fn payment_key(
+    xpriv: &SecretXpriv,
+    counterparty: &PaymentCode,
+    direction: PaymentDirection,
+    index: u32,
+    network: Network,
+) -> Result<PaymentKey, Error> {
+    let (seckey, pubkey, mut payment) = match direction {
+        PaymentDirection::Send => {
+            let seckey = *xpriv
+                .derive_priv(&[ChildNumber::from_normal_idx(0)?])?
+                .secret_key();
+            let pubkey = child_key(counterparty, index)?.key;
+            (seckey, pubkey, pubkey)
+        }
+        PaymentDirection::Receive => {
+            let seckey = *xpriv
+                .derive_priv(&[ChildNumber::from_normal_idx(index)?])?
+                .secret_key();
+            let pubkey = child_key(counterparty, 0)?.key;
+            (
+                seckey,
+                pubkey,
+                PublicKey::from_secret_key(&SECP256K1, &seckey),
+            )
+        }
+    };
+    let secret = shared_secret(&seckey, &pubkey)?;
+    payment.add_exp_assign(&SECP256K1, &secret[..])?;
+    let pubkey = bitcoin::PublicKey {
+        compressed: true,
+        key: payment,
+    };
+    Ok(PaymentKey {
+        direction,
+        index,
+        pubkey,
+        address: Address::p2pkh(&pubkey, network).to_string(),
+    })
+}
@@ -0,0 +1,143 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Deterministic entropy from BIP-32 keychains (BIP-85). Child entropy is
+//! derived from the account private key under `m/83696968'` path, so a
+//! single keyring backup allows to restore seeds, mnemonics and keys of any
+//! number of other wallets and applications.
+
+use bip39::{Language, Mnemonic};
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::hashes::{hmac, sha512, Hash, HashEngine};
+use bitcoin::secp256k1::SecretKey;
+use bitcoin::util::bip32::{
+    ChainCode, ChildNumber, DerivationPath, ExtendedPrivKey, Fingerprint,
+};
+use bitcoin::PrivateKey;
+use zeroize::Zeroize;
+
+use super::keymgm::Error;
+use super::KeysAccount;
+use crate::rpc::types::Bip85Application;
+
+/// Purpose of the BIP-85 derivation path
+pub const BIP85_PURPOSE: u32 = 83696968;
+
+/// Key of HMAC-SHA512 producing entropy from the derived private key
+const ENTROPY_HMAC_KEY: &[u8] = b"bip-entropy-from-k";
+
+/// Returns language of the BIP-39 wordlist with a given BIP-85 code
+fn language(code: u32) -> Option<Language> {
+    Some(match code {
+        0 => Language::English,
+        1 => Language::Japanese,
+        2 => Language::Korean,
+        3 => Language::Spanish,
+        4 => Language::SimplifiedChinese,
+        5 => Language::TraditionalChinese,
+        6 => Language::French,
+        7 => Language::Italian,
+        8 => Language::Czech,
+        _ => return None,
+    })
+}
+
+/// Returns derivation path of the child entropy with a given `index` for
+/// the `application`
+pub fn path(
+    application: Bip85Application,
+    index: u32,
+) -> Result<DerivationPath, Error> {
+    let mut path = vec![BIP85_PURPOSE];
+    match application {
+        Bip85Application::Mnemonic { language, words } => {
+            path.extend(&[39, language, words])
+        }
+        Bip85Application::Wif => path.push(2),
+        Bip85Application::Xprv => path.push(32),
+        Bip85Application::Hex { bytes } => path.extend(&[128169, bytes]),
+    }
+    path.push(index);
+    Ok(DerivationPath::from(
+        path.into_iter()
+            .map(ChildNumber::from_hardened_idx)
+            .collect::<Result<Vec<_>, _>>()?,
+    ))
+}
+
+/// Derives 64 bytes of the child entropy with a given `index` for the
+/// `application` from the account private key. The decryption key and
+/// derived private key are wiped out right after the derivation.
+pub fn entropy(
+    account: &KeysAccount,
+    application: Bip85Application,
+    index: u32,
+    decryption_key: &mut SecretKey,
+) -> Result<[u8; 64], Error> {
+    let path = path(application, index)?;
+    let xpriv = account.xprivkey(decryption_key)?.derive_priv(&path)?;
+    let mut engine = hmac::HmacEngine::<sha512::Hash>::new(ENTROPY_HMAC_KEY);
+    engine.input(&xpriv.secret_key()[..]);
+    Ok(hmac::Hmac::<sha512::Hash>::from_engine(engine).into_inner())
+}
+
+/// Derives child secret with a given `index` for the `application` and
+/// encodes it in the application format: mnemonic words, WIF, extended
+/// private key for the account network or hex string
+pub fn derive(
+    account: &KeysAccount,
+    application: Bip85Application,
+    index: u32,
+    decryption_key: &mut SecretKey,
+) -> Result<String, Error> {
+    if !application.is_valid() {
+        return Err(Error::Bip85Application(application));
+    }
+    let network = account.network();
+    let mut entropy = entropy(account, application, index, decryption_key)?;
+    let secret = match application {
+        Bip85Application::Mnemonic {
+            language: code,
+            words,
+        } => {
+            let language =
+                language(code).ok_or(Error::Bip85Application(application))?;
+            let len = words as usize * 4 / 3;
+            Mnemonic::from_entropy_in(language, &entropy[..len])
+                .map_err(|_| Error::Bip85Application(application))?
+                .to_string()
+        }
+        Bip85Application::Wif => PrivateKey {
+            compressed: true,
+            network,
+            key: SecretKey::from_slice(&entropy[..32])?,
+        }
+        .to_wif(),
+        Bip85Application::Xprv => ExtendedPrivKey {
+            network,
+            depth: 0,
+            parent_fingerprint: Fingerprint::default(),
+            child_number: ChildNumber::from(0),
+            private_key: PrivateKey {
+                compressed: true,
+                network,
+                key: SecretKey::from_slice(&entropy[32..])?,
+            },
+            chain_code: ChainCode::from(&entropy[..32]),
+        }
+        .to_string(),
+        Bip85Application::Hex { bytes } => entropy[..bytes as usize].to_hex(),
+    };
+    entropy[..].zeroize();
+    Ok(secret)
+}
@@ -0,0 +1,132 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Vault driver of browser wallets, which keeps strict-encoded vault data in
+//! the storage managed by JavaScript code, like IndexedDB. Browser storage
+//! APIs are asynchronous, so the vault snapshot is loaded by the caller
+//! before the vault is opened, and each update is handed over to the store
+//! callback returning a promise. The promise is not awaited by the vault:
+//! its rejection is reported as a failure of the next vault update.
+
+use ::core::any::Any;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use js_sys::{Function, Promise, Uint8Array};
+use lnpbp::strict_encoding::{StrictDecode, StrictEncode};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+use super::{driver, Driver, Keyring};
+use crate::error::BootstrapError;
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Vault data previously passed to the `store_cb`; empty for a new vault
+    pub snapshot: Vec<u8>,
+
+    /// JavaScript function called with `Uint8Array` of the vault data on
+    /// each vault update; may return a promise
+    pub store_cb: Function,
+}
+
+// Configuration is provided by the application code and is never read from
+// or written into configuration files
+impl PartialEq for Config {
+    fn eq(&self, other: &Self) -> bool {
+        self.snapshot == other.snapshot
+            && JsValue::from(&self.store_cb) == JsValue::from(&other.store_cb)
+    }
+}
+
+impl Eq for Config {}
+
+#[derive(Debug)]
+pub struct BrowserDriver {
+    snapshot: Option<Vec<u8>>,
+    store_cb: Function,
+    /// Error of the last store promise rejected after the vault update has
+    /// returned
+    failure: Rc<RefCell<Option<String>>>,
+}
+
+// `wasm32-unknown-unknown` target is single-threaded, so JavaScript values
+// and the reference-counted failure slot are never accessed from multiple
+// threads
+unsafe impl Send for BrowserDriver {}
+unsafe impl Sync for BrowserDriver {}
+
+impl Driver for BrowserDriver {
+    fn init(config: &dyn Any) -> Result<Self, BootstrapError> {
+        let config = config.downcast_ref::<Config>().expect(
+            "`BrowserDriver` must be configured with `browser::Config` object",
+        );
+        info!("Initializing browser driver for the vault");
+        Ok(Self {
+            snapshot: Some(config.snapshot.clone()),
+            store_cb: config.store_cb.clone(),
+            failure: Rc::new(RefCell::new(None)),
+        })
+    }
+
+    fn load(&mut self) -> Result<Vec<Keyring>, driver::Error> {
+        debug!("Loading vault from the browser storage snapshot");
+        let snapshot = self.snapshot.take().unwrap_or_default();
+        if snapshot.is_empty() {
+            warn!("Vault snapshot is empty, creating new vault");
+            return Ok(vec![]);
+        }
+        let accounts = Vec::<Keyring>::strict_decode(&snapshot[..])
+            .map_err(|err| driver::Error::Corrupted(err.to_string()))?;
+        trace!("Vault loaded: {:?}", accounts);
+        Ok(accounts)
+    }
+
+    fn store(&mut self, accounts: &Vec<Keyring>) -> Result<(), driver::Error> {
+        if let Some(err) = self.failure.borrow_mut().take() {
+            return Err(driver::Error::Storage(format!(
+                "previous vault update was not stored: {}",
+                err
+            )));
+        }
+        debug!("Storing vault data to the browser storage");
+        let mut data = vec![];
+        accounts.strict_encode(&mut data)?;
+        let result = self
+            .store_cb
+            .call1(&JsValue::NULL, &Uint8Array::from(&data[..]))
+            .map_err(|err| driver::Error::Storage(js_error(err)))?;
+        if let Ok(promise) = result.dyn_into::<Promise>() {
+            let failure = self.failure.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Err(err) = JsFuture::from(promise).await {
+                    let err = js_error(err);
+                    error!("Unable to store vault data: {}", err);
+                    *failure.borrow_mut() = Some(err);
+                }
+            });
+        }
+        trace!("Vault data stored");
+        Ok(())
+    }
+}
+
+/// Describes JavaScript exception or promise rejection reason
+fn js_error(err: JsValue) -> String {
+    err.as_string()
+        .or_else(|| {
+            err.dyn_ref::<js_sys::Error>()
+                .map(|err| String::from(err.message()))
+        })
+        .unwrap_or_else(|| format!("{:?}", err))
+}
@@ -0,0 +1,206 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Extraction of extended public keys from output descriptors for import of
+//! watch-only accounts, and export of accounts as output descriptors
+
+use std::str::FromStr;
+
+use bitcoin::util::bip32::{ExtendedPubKey, Fingerprint, KeySource};
+use miniscript::descriptor::{Descriptor, DescriptorPublicKey, DescriptorType};
+use miniscript::{ForEach, ForEachKey};
+use slip132::KeyApplication;
+
+use super::keymgm::{Error, KeysAccount};
+
+const INPUT_CHARSET: &str = "0123456789()[],'/*abcdefgh@:$%{}\
+                             IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~\
+                             ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const CHECKSUM_GENERATORS: [u64; 5] = [
+    0xf5dee51989,
+    0xa9fdca3312,
+    0x1bab10e32d,
+    0x3706b1677a,
+    0x644d626ffd,
+];
+
+/// Extended public key extracted from an output descriptor
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DescriptorKey {
+    /// Extended public key
+    pub xpubkey: ExtendedPubKey,
+
+    /// Key origin information, if provided by the descriptor
+    pub origin: Option<KeySource>,
+
+    /// Key application matching the descriptor type, if any
+    pub application: Option<KeyApplication>,
+
+    /// Source descriptor string
+    pub descriptor: String,
+}
+
+impl DescriptorKey {
+    /// Returns fingerprint of the master key from which the key originates;
+    /// if the descriptor does not provide origin information, the key itself
+    /// is considered to be a master key
+    pub fn master_fingerprint(&self) -> Fingerprint {
+        self.origin
+            .as_ref()
+            .map(|(fingerprint, _)| *fingerprint)
+            .unwrap_or_else(|| self.xpubkey.fingerprint())
+    }
+
+    /// Returns number of derivation steps from the master key
+    pub fn depth(&self) -> usize {
+        self.origin
+            .as_ref()
+            .map(|(_, path)| path.as_ref().len())
+            .unwrap_or_default()
+    }
+}
+
+/// Parses output descriptor and returns all extended public keys used in it.
+/// Descriptors containing single public keys are not supported, since such
+/// keys can't represent an account.
+pub fn parse(descriptor: &str) -> Result<Vec<DescriptorKey>, Error> {
+    let parsed = Descriptor::<DescriptorPublicKey>::from_str(descriptor)
+        .map_err(|err| Error::Descriptor(err.to_string()))?;
+    let application = application(parsed.desc_type());
+
+    let mut keys = vec![];
+    let mut single_key = false;
+    parsed.for_each_key(|key| {
+        match key {
+            ForEach::Key(DescriptorPublicKey::XPub(xkey)) => {
+                keys.push(DescriptorKey {
+                    xpubkey: xkey.xkey,
+                    origin: xkey.origin.clone(),
+                    application,
+                    descriptor: descriptor.to_owned(),
+                })
+            }
+            _ => single_key = true,
+        }
+        true
+    });
+    if single_key {
+        return Err(Error::Descriptor(format!(
+            "descriptor `{}` contains keys which are not extended public keys",
+            descriptor
+        )));
+    }
+    Ok(keys)
+}
+
+fn application(desc_type: DescriptorType) -> Option<KeyApplication> {
+    match desc_type {
+        DescriptorType::Pkh => Some(KeyApplication::Hashed),
+        DescriptorType::Wpkh => Some(KeyApplication::SegWit),
+        DescriptorType::ShWpkh => Some(KeyApplication::Nested),
+        DescriptorType::Wsh | DescriptorType::WshSortedMulti => {
+            Some(KeyApplication::SegWitMultisig)
+        }
+        DescriptorType::ShWsh | DescriptorType::ShWshSortedMulti => {
+            Some(KeyApplication::NestedMultisig)
+        }
+        _ => None,
+    }
+}
+
+/// Composes output descriptors for the receiving (`/0/*`) and change (`/1/*`)
+/// address ranges of the keys `account` originating from `origin`. Accounts
+/// without key application are exported with all supported single-key
+/// descriptor types. Returned descriptors include checksums, so they can be
+/// imported into Bitcoin Core and BDK wallets as is.
+pub fn export(
+    account: &KeysAccount,
+    origin: &KeySource,
+) -> Result<Vec<String>, Error> {
+    let templates: &[&str] = match account.application() {
+        Some(KeyApplication::Hashed) => &["pkh({})"],
+        Some(KeyApplication::SegWit) => &["wpkh({})", "tr({})"],
+        Some(KeyApplication::Nested) => &["sh(wpkh({}))"],
+        None => &["wpkh({})", "sh(wpkh({}))", "tr({})"],
+        Some(application) => {
+            return Err(Error::Descriptor(format!(
+                "accounts with {:?} application require cosigner keys and \
+                 can't be exported as a single-key descriptor",
+                application
+            )))
+        }
+    };
+    let (fingerprint, path) = origin;
+    let key = format!(
+        "[{}{}]{}",
+        fingerprint,
+        path.to_string().trim_start_matches('m'),
+        account.xpubkey()
+    );
+    let mut descriptors = vec![];
+    for template in templates {
+        for branch in 0..=1 {
+            let descriptor =
+                template.replace("{}", &format!("{}/{}/*", key, branch));
+            let checksum = checksum(&descriptor)
+                .expect("descriptor is composed from valid characters");
+            descriptors.push(format!("{}#{}", descriptor, checksum));
+        }
+    }
+    Ok(descriptors)
+}
+
+/// Computes descriptor checksum as defined in BIP-380. Returns
+/// [`Option::None`] if the descriptor contains characters not allowed in
+/// descriptors.
+pub fn checksum(descriptor: &str) -> Option<String> {
+    fn poly_mod(mut c: u64, val: u64) -> u64 {
+        let c0 = c >> 35;
+        c = ((c & 0x7ffffffff) << 5) ^ val;
+        for (bit, generator) in CHECKSUM_GENERATORS.iter().enumerate() {
+            if c0 & (1 << bit) != 0 {
+                c ^= generator;
+            }
+        }
+        c
+    }
+
+    let mut c = 1u64;
+    let mut class = 0u64;
+    let mut class_count = 0;
+    for ch in descriptor.chars() {
+        let pos = INPUT_CHARSET.find(ch)? as u64;
+        c = poly_mod(c, pos & 31);
+        class = class * 3 + (pos >> 5);
+        class_count += 1;
+        if class_count == 3 {
+            c = poly_mod(c, class);
+            class = 0;
+            class_count = 0;
+        }
+    }
+    if class_count > 0 {
+        c = poly_mod(c, class);
+    }
+    (0..8).for_each(|_| c = poly_mod(c, 0));
+    c ^= 1;
+
+    Some(
+        (0..8)
+            .map(|j| {
+                CHECKSUM_CHARSET[((c >> (5 * (7 - j))) & 31) as usize] as char
+            })
+            .collect(),
+    )
+}
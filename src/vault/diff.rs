@@ -0,0 +1,166 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Comparison of vault snapshots (like backup copies of the vault file),
+//! reporting added, removed and modified keyrings and accounts. Only account
+//! metadata are compared; encrypted keys are never decrypted.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use bitcoin::XpubIdentifier;
+
+use super::Keyring;
+use crate::rpc::types::AccountInfo;
+
+/// Account metadata as stored in a vault snapshot
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Entry {
+    /// Account information as reported by the daemon
+    pub info: AccountInfo,
+
+    /// Whether the account is archived (soft-deleted)
+    pub archived: bool,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.info)?;
+        if self.archived {
+            f.write_str(", archived")?;
+        }
+        Ok(())
+    }
+}
+
+/// Difference in a single keyring or account between two vault snapshots
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Change {
+    /// Account is present only in the second snapshot
+    Added(Entry),
+
+    /// Account is present only in the first snapshot
+    Removed(Entry),
+
+    /// Account metadata differ; the list contains names of changed fields
+    Modified {
+        before: Entry,
+        after: Entry,
+        fields: Vec<&'static str>,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added(entry) => write!(f, "+ {}", entry),
+            Change::Removed(entry) => write!(f, "- {}", entry),
+            Change::Modified {
+                before,
+                after,
+                fields,
+            } => write!(
+                f,
+                "~ {} ({} changed)\n    before: {}\n    after:  {}",
+                after.info.id,
+                fields.join(", "),
+                before,
+                after
+            ),
+        }
+    }
+}
+
+/// Returns metadata of all keyrings and their sub-accounts in the snapshot,
+/// indexed by the account id
+pub fn entries(keyrings: &[Keyring]) -> BTreeMap<XpubIdentifier, Entry> {
+    let mut entries = BTreeMap::new();
+    for keyring in keyrings {
+        let info = AccountInfo::from(keyring);
+        entries.insert(
+            info.id,
+            Entry {
+                info,
+                archived: keyring.is_archived(),
+            },
+        );
+        for (path, account) in keyring.sub_accounts() {
+            let mut info = AccountInfo::from(account);
+            info.key_source = Some((keyring.fingerprint(), path.clone()));
+            entries.insert(
+                info.id,
+                Entry {
+                    info,
+                    archived: *account.archived(),
+                },
+            );
+        }
+    }
+    entries
+}
+
+/// Compares two vault snapshots. Changes are ordered by account id.
+pub fn diff(before: &[Keyring], after: &[Keyring]) -> Vec<Change> {
+    let before = entries(before);
+    let mut after = entries(after);
+    let mut changes = vec![];
+    for (id, old) in before {
+        match after.remove(&id) {
+            None => changes.push(Change::Removed(old)),
+            Some(new) if new != old => {
+                let fields = changed_fields(&old, &new);
+                changes.push(Change::Modified {
+                    before: old,
+                    after: new,
+                    fields,
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    changes.extend(after.into_iter().map(|(_, new)| Change::Added(new)));
+    changes
+}
+
+fn changed_fields(before: &Entry, after: &Entry) -> Vec<&'static str> {
+    let (old, new) = (&before.info, &after.info);
+    let mut fields = vec![];
+    if old.name != new.name {
+        fields.push("name");
+    }
+    if old.details != new.details {
+        fields.push("details");
+    }
+    if old.assets != new.assets {
+        fields.push("assets");
+    }
+    if old.application != new.application {
+        fields.push("application");
+    }
+    if old.key_source != new.key_source {
+        fields.push("key source");
+    }
+    if old.lifecycle != new.lifecycle {
+        fields.push("lifecycle");
+    }
+    if old.watch_only != new.watch_only {
+        fields.push("watch-only");
+    }
+    if old.branches != new.branches {
+        fields.push("branches");
+    }
+    if before.archived != after.archived {
+        fields.push("archived");
+    }
+    fields
+}
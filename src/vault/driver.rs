@@ -15,7 +15,17 @@
 
 use ::core::any::Any;
 
-use super::{delegated, file_driver, Keyring};
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+use super::browser;
+#[cfg(feature = "node")]
+use super::file_driver;
+#[cfg(feature = "os-keychain")]
+use super::os_keystore;
+#[cfg(feature = "remote-vault")]
+use super::remote;
+#[cfg(feature = "sqlite")]
+use super::sqlite_driver;
+use super::{delegated, Keyring};
 use crate::error::BootstrapError;
 
 pub trait Driver: Send + Sync {
@@ -24,6 +34,23 @@ pub trait Driver: Send + Sync {
         Self: Sized;
     fn load(&mut self) -> Result<Vec<Keyring>, Error>;
     fn store(&mut self, accounts: &Vec<Keyring>) -> Result<(), Error>;
+
+    /// Overwrites stored vault data in place with `accounts`, in which the
+    /// encrypted keys being purged were replaced with random bytes. Must
+    /// cover all storage replicas controlled by the driver and make sure
+    /// that the data reach the storage media. Returns the list of the
+    /// overwritten replicas for the shredding certificate.
+    fn shred(&mut self, accounts: &Vec<Keyring>) -> Result<Vec<String>, Error> {
+        self.store(accounts)?;
+        Ok(vec![])
+    }
+
+    /// Makes sure that the stored vault data reach the storage media before
+    /// the daemon exits. Drivers syncing the data on each store do not need
+    /// to do anything.
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Debug, Display, Serialize, Deserialize)]
@@ -31,21 +58,66 @@ pub trait Driver: Send + Sync {
 #[display(Debug)]
 #[non_exhaustive]
 pub enum Config {
+    #[cfg(feature = "node")]
     File(file_driver::Config),
     Delegated(delegated::Config),
+    #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+    #[serde(skip)]
+    Browser(browser::Config),
+    #[cfg(feature = "sqlite")]
+    Sqlite(sqlite_driver::Config),
+    #[cfg(feature = "os-keychain")]
+    OsKeychain(os_keystore::Config),
+    #[cfg(feature = "remote-vault")]
+    Remote(remote::Config),
     /* Terezor,
      * Ledger, */
 }
 
+impl Config {
+    /// Name of the driver type, as used in the configuration file
+    pub fn name(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "node")]
+            Config::File(_) => "File",
+            Config::Delegated(_) => "Delegated",
+            #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+            Config::Browser(_) => "Browser",
+            #[cfg(feature = "sqlite")]
+            Config::Sqlite(_) => "Sqlite",
+            #[cfg(feature = "os-keychain")]
+            Config::OsKeychain(_) => "OsKeychain",
+            #[cfg(feature = "remote-vault")]
+            Config::Remote(_) => "Remote",
+        }
+    }
+}
+
+/// Error cases of the vault storage drivers. The type does not implement
+/// [`std::error::Error`], so any error type can be converted into
+/// [`Error::Storage`].
 #[derive(Clone, PartialEq, Eq, Debug, Display)]
-#[display(Debug)]
-pub struct Error(String);
+#[display(doc_comments)]
+pub enum Error {
+    /// {0}
+    Storage(String),
+
+    /// vault data are corrupted: {0}
+    Corrupted(String),
+
+    /// vault data were modified outside of the daemon: {0}
+    Tampered(String),
+
+    /// {0} vault format is not supported by this build; supported formats
+    /// are {1}
+    UnsupportedFormat(String, String),
+}
 
 impl<T> From<T> for Error
 where
     T: ::std::error::Error,
 {
     fn from(err: T) -> Self {
-        Self(format!("{:?}", err))
+        Self::Storage(err.to_string())
     }
 }
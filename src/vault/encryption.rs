@@ -0,0 +1,62 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Vault encryption modes
+
+/// Default time after which the vault unlocked with a passphrase is locked
+/// again, in seconds
+pub const DEFAULT_UNLOCK_TIMEOUT: u64 = 300;
+
+/// Mode of vault encryption, defining the source of the ElGamal key used to
+/// encrypt private keys stored in the vault
+#[derive(Clone, PartialEq, Eq, Debug, Display, Serialize, Deserialize)]
+#[serde(crate = "serde_crate", tag = "mode", rename_all = "snake_case")]
+#[display(Debug)]
+#[non_exhaustive]
+pub enum Encryption {
+    /// Vault is encrypted with the daemon node key. Sessions unlocked with
+    /// the node key are valid for [`DEFAULT_UNLOCK_TIMEOUT`]
+    NodeKey,
+
+    /// Vault is encrypted with a key derived from the user passphrase
+    /// according to the daemon passphrase policy. The passphrase must be
+    /// provided with `unlock` request before any operation requiring private
+    /// keys; the request returns session token which must be used with these
+    /// operations
+    Passphrase {
+        /// Number of seconds after the unlock during which the derived key
+        /// is kept in memory
+        #[serde(default = "default_unlock_timeout")]
+        unlock_timeout: u64,
+    },
+}
+
+impl Default for Encryption {
+    fn default() -> Self {
+        Encryption::NodeKey
+    }
+}
+
+impl Encryption {
+    /// Returns number of seconds during which unlocked session remains valid
+    pub fn unlock_timeout(&self) -> u64 {
+        match self {
+            Encryption::Passphrase { unlock_timeout, .. } => *unlock_timeout,
+            Encryption::NodeKey => DEFAULT_UNLOCK_TIMEOUT,
+        }
+    }
+}
+
+fn default_unlock_timeout() -> u64 {
+    DEFAULT_UNLOCK_TIMEOUT
+}
@@ -0,0 +1,160 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Example vault populated with fake data, which uses every field of the
+//! vault object model: keyrings with and without private keys, key origins,
+//! sub-accounts with assets, aliases, labels, branch layouts, signing
+//! policies, non-default lifecycle states and archived accounts. Written
+//! into a file, it serves as a reference for the tools reading vault files,
+//! which always matches the schema of the current build.
+//!
+//! NB: All keys of the example vault are derived from the publicly known
+//! [`EXAMPLE_SEED`]; never use them for anything except tests.
+
+use std::str::FromStr;
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use bitcoin::util::bip32::{DerivationPath, ExtendedPrivKey, ExtendedPubKey};
+use bitcoin::{Network, Script, WPubkeyHash};
+use lnpbp::chain::AssetId;
+use slip132::KeyApplication;
+
+use super::keymgm::Error;
+use super::{Keyring, KeysAccount};
+use crate::lifecycle::Lifecycle;
+use crate::rpc::types::{Branches, RateLimit, SigningPolicy};
+use crate::SECP256K1;
+
+/// Seed from which all keys of the example vault are derived
+pub const EXAMPLE_SEED: &'static [u8] = b"keyring example vault seed";
+
+/// Returns the key decrypting private keys of the example vault accounts
+pub fn decryption_key() -> SecretKey {
+    SecretKey::from_slice(&sha256::Hash::hash(EXAMPLE_SEED)[..])
+        .expect("negligible probability of the hash being out of the curve")
+}
+
+/// Creates keyrings of the example vault. Keys are deterministic, while
+/// encrypted private key data differ each time, since ElGamal encryption is
+/// randomized.
+pub fn keyrings() -> Result<Vec<Keyring>, Error> {
+    let encryption_key =
+        PublicKey::from_secret_key(&SECP256K1, &decryption_key());
+    let master = ExtendedPrivKey::new_master(Network::Testnet, EXAMPLE_SEED)?;
+    let origin = DerivationPath::from_str("m/84'/1'/0'")?;
+
+    let mut keyring = Keyring::from_xpriv(
+        "Example",
+        "Keyring with the fake keys",
+        Some(KeyApplication::SegWit),
+        master.derive_priv(&SECP256K1, &origin)?,
+        Some((master.fingerprint(&SECP256K1), origin)),
+        encryption_key,
+    )?;
+    let master_id = keyring.identifier();
+    let account = keyring
+        .account_by_id_mut(master_id)
+        .expect("master account is always present");
+    account.add_alias("Example wallet");
+    account.set_label("env", Some("example"))?;
+
+    let asset = AssetId::hash(b"example asset");
+    let savings = keyring
+        .create_account(
+            "m/0'",
+            "Savings",
+            Some("Account with all optional fields set"),
+            set![asset],
+            &mut decryption_key(),
+        )?
+        .identifier();
+    let account = keyring
+        .account_by_id_mut(savings)
+        .expect("account was just created");
+    account.set_application(KeyApplication::SegWit);
+    account.add_alias("Cold savings");
+    account.set_label("cold", Some(""))?;
+    account.set_label("owner", Some("Alice"))?;
+    account.set_branches(Branches {
+        external: 0,
+        internal: 1,
+        gap_limit: Some(50),
+    })?;
+    account.set_policy(SigningPolicy {
+        max_amount: Some(1_000_000),
+        destinations: vec![Script::new_v0_wpkh(&WPubkeyHash::hash(
+            b"example destination",
+        ))],
+        sighash_types: vec![0x01, 0x83],
+        rate_limit: Some(RateLimit {
+            count: 10,
+            period: 86400,
+        }),
+        allow_ecdh: false,
+    })?;
+
+    let retiring = keyring
+        .create_account(
+            "m/1'",
+            "Retiring",
+            None::<String>,
+            set![],
+            &mut decryption_key(),
+        )?
+        .identifier();
+    keyring
+        .account_by_id_mut(retiring)
+        .expect("account was just created")
+        .transit(Lifecycle::Retiring)?;
+
+    let archived = keyring
+        .create_account(
+            "m/2'",
+            "Archived",
+            None::<String>,
+            set![],
+            &mut decryption_key(),
+        )?
+        .identifier();
+    keyring.delete_account(archived, false)?;
+
+    let watched = ExtendedPubKey::from_private(
+        &SECP256K1,
+        &master.derive_priv(
+            &SECP256K1,
+            &DerivationPath::from_str("m/49'/1'/0'")?,
+        )?,
+    );
+    let mut watch_only = Keyring::watch_only(
+        KeysAccount::watch_only(
+            "Watch-only",
+            "Keyring without private keys",
+            watched,
+            Some(KeyApplication::Nested),
+        ),
+        None,
+    );
+    let path = DerivationPath::from_str("m/0")?;
+    watch_only.add_watch_only(
+        path.clone(),
+        KeysAccount::watch_only(
+            "Receiving",
+            "",
+            watched.derive_pub(&SECP256K1, &path)?,
+            Some(KeyApplication::Nested),
+        ),
+    )?;
+
+    Ok(vec![keyring, watch_only])
+}
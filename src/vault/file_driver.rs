@@ -12,31 +12,196 @@
 // If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
 
 //! File storage drivers for private key vault
+//!
+//! Vault file starts with a header containing the file format version,
+//! SHA256 checksum of the vault data following the header and an optional
+//! signature of the checksum made with the daemon node key:
+//!
+//! | Field     | Size  | Content                                  |
+//! |-----------|-------|------------------------------------------|
+//! | Magic     | 4     | `KRVF`                                   |
+//! | Version   | 1     | [`FILE_VERSION`]                         |
+//! | Checksum  | 32    | SHA256 of the vault data                 |
+//! | Sig. len  | 1     | 0 or 64                                  |
+//! | Signature | 0, 64 | compact ECDSA signature of the checksum  |
+//!
+//! Files written before the header was introduced are read without
+//! integrity checks, unless signing is required by the configuration.
+//!
+//! Strict-encoded vault data in the files without header and with header
+//! version 1 keep keyrings in the original layout, without the extension
+//! records (see [`EXTENSION_VERSION`]); such files are decoded with
+//! [`LegacyKeyring`] and are written in the current layout on the next vault
+//! update.
+//!
+//! Vault data in text formats are parsed in two steps: the file is split
+//! into keyring entries, which are then deserialized in parallel, so a
+//! malformed entry is reported with its number and the path to the invalid
+//! field.
+//!
+//! [`EXTENSION_VERSION`]: super::keymgm::EXTENSION_VERSION
 
 use ::core::any::Any;
+use ::core::fmt::{self, Debug, Display, Formatter};
+use ::core::hash::Hasher;
+use ::core::sync::atomic::{AtomicUsize, Ordering};
 use ::std::fs;
 use ::std::io;
 use ::std::io::{Read, Seek, Write};
-use ::std::path::Path;
+use ::std::path::{Path, PathBuf};
 
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1;
+use fs2::FileExt;
 use lnpbp::strict_encoding::{StrictDecode, StrictEncode};
 use microservices::FileFormat;
+use rayon::prelude::*;
+use serde::Deserializer;
 
+use super::keymgm::LegacyKeyring;
 use super::{driver, Driver, Keyring};
 use crate::error::BootstrapError;
 
 #[derive(Debug, Display)]
 #[display(Debug)]
 pub struct FileDriver {
-    fd: fs::File,
     config: Config,
+    /// Read-only drivers do not lock the vault
+    _lock: Option<VaultLock>,
 }
 
+/// Exclusive lock on the vault, held by the driver for its lifetime. The
+/// lock is taken on a separate `<vault>.lock` file, which is not replaced on
+/// vault writes and records the id of the process holding the lock. The OS
+/// releases the lock when the process exits, even if it crashes.
+#[derive(Debug)]
+struct VaultLock {
+    fd: fs::File,
+    path: PathBuf,
+}
+
+impl VaultLock {
+    fn acquire(location: &str) -> Result<Self, BootstrapError> {
+        let path = PathBuf::from(format!("{}.lock", location));
+        let mut fd = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(&path)?;
+        if let Err(err) = fd.try_lock_exclusive() {
+            if err.raw_os_error() != fs2::lock_contended_error().raw_os_error()
+            {
+                return Err(err.into());
+            }
+            // Windows does not allow reading the locked file, so the holder
+            // is reported as unknown there
+            let mut holder = String::new();
+            let _ = fd.read_to_string(&mut holder);
+            let holder = match holder.trim().parse::<u32>() {
+                Ok(pid) => format!("process id {}", pid),
+                Err(_) => "unknown process".to_string(),
+            };
+            error!("Vault {} is locked by {}", location, holder);
+            return Err(BootstrapError::VaultLocked(
+                location.to_string(),
+                holder,
+            ));
+        }
+        fd.set_len(0)?;
+        write!(fd, "{}", std::process::id())?;
+        fd.sync_all()?;
+        trace!("Vault lock {} acquired", path.display());
+        Ok(Self { fd, path })
+    }
+}
+
+impl Drop for VaultLock {
+    fn drop(&mut self) {
+        if self.fd.unlock().is_ok() {
+            trace!("Vault lock {} released", self.path.display());
+        }
+    }
+}
+
+/// Default number of rotated vault file backups
+pub const DEFAULT_BACKUPS: u8 = 3;
+
+/// Version of the vault file header
+pub const FILE_VERSION: u8 = 2;
+
+/// Last version of the vault file header used with the original keyring
+/// layout
+const LEGACY_FILE_VERSION: u8 = 1;
+
+const FILE_MAGIC: [u8; 4] = *b"KRVF";
+
+/// Length of the header without the signature
+const HEADER_LEN: usize = 4 + 1 + 32 + 1;
+
+const SIGNATURE_LEN: usize = 64;
+
 #[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 #[serde(crate = "serde_crate")]
 pub struct Config {
     pub location: String,
     pub format: FileFormat,
+
+    /// Number of rotated backup copies of the vault file, kept as
+    /// `<location>.1` (the most recent one) to `<location>.<backups>`
+    #[serde(default = "default_backups")]
+    pub backups: u8,
+
+    /// Sign the vault file with the daemon node key and refuse loading
+    /// files without a valid signature
+    #[serde(default)]
+    pub signed: bool,
+
+    /// Key signing the vault file; provided by the daemon from its node key
+    #[serde(skip)]
+    pub node_key: Option<NodeKey>,
+
+    /// Open the vault without locking it and refuse all writes; set by the
+    /// daemon running in read-only mode
+    #[serde(skip)]
+    pub read_only: bool,
+}
+
+/// Daemon node key used to sign the vault file. Debug output contains only
+/// the node id, so the key does not leak into the logs with the driver
+/// configuration.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct NodeKey(pub secp256k1::SecretKey);
+
+impl NodeKey {
+    pub fn node_id(&self) -> secp256k1::PublicKey {
+        secp256k1::PublicKey::from_secret_key(&crate::SECP256K1, &self.0)
+    }
+}
+
+impl ::core::hash::Hash for NodeKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write(&self.0[..])
+    }
+}
+
+impl Debug for NodeKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "NodeKey({})", self.node_id())
+    }
+}
+
+fn default_backups() -> u8 {
+    DEFAULT_BACKUPS
+}
+
+impl Config {
+    fn backup_path(&self, no: u8) -> PathBuf {
+        PathBuf::from(format!("{}.{}", self.location, no))
+    }
+
+    fn temp_path(&self) -> PathBuf {
+        PathBuf::from(format!("{}.tmp", self.location))
+    }
 }
 
 impl Driver for FileDriver {
@@ -48,78 +213,556 @@ impl Driver for FileDriver {
             "Initializing file driver for vault in {:?}",
             &config.location
         );
-        let exists = Path::new(&config.location).exists();
-        let fd = fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(!exists)
-            .open(&config.location)?;
+        if !supported_formats().contains(&config.format) {
+            return Err(unsupported_format(&config.format).into());
+        }
+        if config.read_only {
+            return Ok(Self {
+                config: config.clone(),
+                _lock: None,
+            });
+        }
+        let lock = VaultLock::acquire(&config.location)?;
+        let temp_path = config.temp_path();
+        if temp_path.exists() {
+            warn!(
+                "Removing {} left by interrupted vault write",
+                temp_path.display()
+            );
+            fs::remove_file(&temp_path)?;
+        }
         let mut me = Self {
-            fd,
             config: config.clone(),
+            _lock: Some(lock),
         };
-        if !exists {
+        if !Path::new(&config.location).exists()
+            && !config.backup_path(1).exists()
+        {
             warn!("Vault file does not exist: initializing empty vault");
             me.store(&vec![])?;
         }
         Ok(me)
     }
 
+    /// If the vault file is missing or damaged, the vault is recovered from
+    /// the most recent readable backup; the damaged file is kept with
+    /// `.damaged` extension.
     fn load(&mut self) -> Result<Vec<Keyring>, driver::Error> {
         debug!("Loading vault from {}", self.config.location);
-        self.fd.seek(io::SeekFrom::Start(0))?;
         trace!(
             "Parsing vault data (expected format {})",
             self.config.format
         );
-        let accounts = match self.config.format {
-            FileFormat::StrictEncode => {
-                Vec::<Keyring>::strict_decode(&mut self.fd)?
+        let err = match self.read_file(Path::new(&self.config.location)) {
+            Ok(accounts) => {
+                trace!("Vault loaded: {:?}", accounts);
+                return Ok(accounts);
             }
-            #[cfg(feature = "serde_yaml")]
-            FileFormat::Yaml => serde_yaml::from_reader(&mut self.fd)?,
-            #[cfg(feature = "toml")]
-            FileFormat::Toml => {
-                let mut data: Vec<u8> = vec![];
-                self.fd.read_to_end(&mut data)?;
-                toml::from_slice(&data)?
+            Err(err @ driver::Error::Tampered(_)) => {
+                error!(
+                    "Vault file {} was modified outside of the daemon",
+                    self.config.location
+                );
+                return Err(err);
             }
-            #[cfg(feature = "serde_json")]
-            FileFormat::Json => serde_json::from_reader(&mut self.fd)?,
-            _ => unimplemented!(),
+            Err(err) => err,
         };
-        trace!("Vault loaded: {:?}", accounts);
-        Ok(accounts)
+        error!("Vault file {} is damaged: {}", self.config.location, err);
+        for no in 1..=self.config.backups {
+            let path = self.config.backup_path(no);
+            if !path.exists() {
+                continue;
+            }
+            match self.read_file(&path) {
+                Ok(accounts) if self.config.read_only => {
+                    warn!("Using vault backup {}", path.display());
+                    return Ok(accounts);
+                }
+                Ok(accounts) => {
+                    warn!("Recovering vault from backup {}", path.display());
+                    if Path::new(&self.config.location).exists() {
+                        fs::rename(
+                            &self.config.location,
+                            format!("{}.damaged", self.config.location),
+                        )?;
+                    }
+                    self.replace(&accounts)?;
+                    return Ok(accounts);
+                }
+                Err(err) => warn!(
+                    "Vault backup {} can't be used: {}",
+                    path.display(),
+                    err
+                ),
+            }
+        }
+        Err(err)
     }
 
+    /// The data are written to a temporary file, which atomically replaces
+    /// the vault file after it reaches the storage media, so a crash leaves
+    /// either the old or the new version of the vault. The previous version
+    /// is kept as the most recent backup.
     fn store(&mut self, accounts: &Vec<Keyring>) -> Result<(), driver::Error> {
         debug!(
             "Storing vault data to the file {} in {} format",
             self.config.location, self.config.format
         );
         trace!("Current vault data: {:?}", accounts);
-        self.fd.seek(io::SeekFrom::Start(0))?;
-        self.fd.set_len(0)?;
-        match self.config.format {
+        self.check_writable()?;
+        self.rotate()?;
+        self.replace(accounts)?;
+        trace!("Vault data stored");
+        Ok(())
+    }
+
+    /// Shredded keys have the same size as the original ones, so the vault
+    /// data are written over the existing file content without truncating it
+    /// first; otherwise the file system may place new data into different
+    /// blocks, leaving the old ones intact. Backups contain older versions
+    /// of the vault, so they are filled with zeros before the shredded data
+    /// are written into them.
+    fn shred(
+        &mut self,
+        accounts: &Vec<Keyring>,
+    ) -> Result<Vec<String>, driver::Error> {
+        debug!("Overwriting vault file {} in place", self.config.location);
+        self.check_writable()?;
+        let data = self.encode(accounts)?;
+        let mut replicas = vec![];
+        let backups = (1..=self.config.backups)
+            .map(|no| self.config.backup_path(no))
+            .filter(|path| path.exists());
+        for (path, zeroize) in
+            ::std::iter::once((PathBuf::from(&self.config.location), false))
+                .chain(backups.map(|path| (path, true)))
+        {
+            let mut fd = fs::OpenOptions::new().write(true).open(&path)?;
+            if zeroize {
+                let len = fd.metadata()?.len();
+                io::copy(&mut io::repeat(0).take(len), &mut fd)?;
+                fd.sync_all()?;
+                fd.seek(io::SeekFrom::Start(0))?;
+            }
+            fd.write_all(&data)?;
+            fd.set_len(data.len() as u64)?;
+            fd.sync_all()?;
+            replicas.push(path.display().to_string());
+        }
+        Ok(replicas)
+    }
+}
+
+impl FileDriver {
+    /// Reads vault snapshot (like a vault backup copy) without opening it
+    /// for writing. Snapshot format is detected automatically. The snapshot
+    /// checksum is verified, but the signature is not, since the key of the
+    /// daemon which has written the snapshot is unknown.
+    pub fn read_snapshot(
+        path: impl AsRef<Path>,
+    ) -> Result<Vec<Keyring>, driver::Error> {
+        let path = path.as_ref();
+        let data = fs::read(path)?;
+        let (data, legacy) = match Header::parse(&data, path)? {
+            Some((header, payload)) => {
+                header.verify_checksum(payload, path)?;
+                (payload, header.is_legacy())
+            }
+            None => (&data[..], true),
+        };
+        let mut last_err = None;
+        for format in supported_formats() {
+            match Self::read(&mut io::Cursor::new(data), &format, legacy) {
+                Ok(accounts) => {
+                    trace!("Vault snapshot is read in {} format", format);
+                    return Ok(accounts);
+                }
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err.expect("at least one format is always tried"))
+    }
+
+    /// Writes vault snapshot with the integrity header in a given `format`,
+    /// as the vault file is written by the daemon. The snapshot is not
+    /// signed.
+    pub fn write_snapshot(
+        path: impl AsRef<Path>,
+        accounts: &Vec<Keyring>,
+        format: &FileFormat,
+    ) -> Result<(), driver::Error> {
+        let mut payload = vec![];
+        Self::write(&mut payload, accounts, format)?;
+        let mut data = Header {
+            version: FILE_VERSION,
+            checksum: sha256::Hash::hash(&payload),
+            signature: None,
+        }
+        .serialize();
+        data.extend(payload);
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// Reads vault data in a given `format`; `legacy` strict-encoded data
+    /// are decoded with the original keyring layout
+    fn read(
+        reader: &mut impl Read,
+        format: &FileFormat,
+        legacy: bool,
+    ) -> Result<Vec<Keyring>, driver::Error> {
+        Ok(match format {
+            FileFormat::StrictEncode if legacy => {
+                Vec::<LegacyKeyring>::strict_decode(reader)?
+                    .into_iter()
+                    .map(Keyring::from)
+                    .collect()
+            }
+            FileFormat::StrictEncode => Vec::<Keyring>::strict_decode(reader)?,
+            #[cfg(feature = "serde_yaml")]
+            FileFormat::Yaml => parse_entries(serde_yaml::from_reader::<
+                _,
+                Vec<serde_yaml::Value>,
+            >(reader)?)?,
+            #[cfg(feature = "toml")]
+            FileFormat::Toml => {
+                let mut data: Vec<u8> = vec![];
+                reader.read_to_end(&mut data)?;
+                parse_entries(toml::from_slice::<Vec<toml::Value>>(&data)?)?
+            }
+            #[cfg(feature = "serde_json")]
+            FileFormat::Json => parse_entries(serde_json::from_reader::<
+                _,
+                Vec<serde_json::Value>,
+            >(reader)?)?,
+            _ => return Err(unsupported_format(format)),
+        })
+    }
+
+    fn read_file(&self, path: &Path) -> Result<Vec<Keyring>, driver::Error> {
+        let data = fs::read(path)?;
+        let (payload, legacy) = match Header::parse(&data, path)? {
+            Some((header, payload)) => {
+                header.verify_checksum(payload, path)?;
+                if let Some(node_key) = self.config.node_key {
+                    header.verify_signature(
+                        node_key,
+                        self.config.signed,
+                        path,
+                    )?;
+                }
+                (payload, header.is_legacy())
+            }
+            None if self.config.signed => {
+                return Err(driver::Error::Tampered(format!(
+                    "{} has no signed header",
+                    path.display()
+                )))
+            }
+            None => {
+                warn!(
+                    "Vault file {} has no integrity header; it will be added \
+                     on the next vault update",
+                    path.display()
+                );
+                (&data[..], true)
+            }
+        };
+        Self::read(&mut io::Cursor::new(payload), &self.config.format, legacy)
+    }
+
+    fn check_writable(&self) -> Result<(), driver::Error> {
+        if self.config.read_only {
+            return Err(driver::Error::Storage(format!(
+                "vault file {} is opened read-only",
+                self.config.location
+            )));
+        }
+        Ok(())
+    }
+
+    /// Serializes vault data prefixed with the integrity header
+    fn encode(
+        &self,
+        accounts: &Vec<Keyring>,
+    ) -> Result<Vec<u8>, driver::Error> {
+        let mut payload = vec![];
+        Self::write(&mut payload, accounts, &self.config.format)?;
+        let checksum = sha256::Hash::hash(&payload);
+        let signature = match (self.config.signed, self.config.node_key) {
+            (true, Some(node_key)) => {
+                let message = secp256k1::Message::from_slice(&checksum[..])
+                    .expect("SHA256 hash is always a valid message");
+                Some(crate::SECP256K1.sign(&message, &node_key.0))
+            }
+            (true, None) => Err(driver::Error::Storage(s!(
+                "vault signing is required, but the signing key is not \
+                 provided"
+            )))?,
+            (false, _) => None,
+        };
+        let mut data = Header {
+            version: FILE_VERSION,
+            checksum,
+            signature,
+        }
+        .serialize();
+        data.extend(payload);
+        Ok(data)
+    }
+
+    fn write(
+        writer: &mut impl Write,
+        accounts: &Vec<Keyring>,
+        format: &FileFormat,
+    ) -> Result<(), driver::Error> {
+        match format {
             FileFormat::StrictEncode => {
-                accounts.strict_encode(&mut self.fd)?;
+                accounts.strict_encode(writer)?;
             }
             #[cfg(feature = "serde_yaml")]
             FileFormat::Yaml => {
-                serde_yaml::to_writer(&mut self.fd, accounts)?;
+                serde_yaml::to_writer(writer, accounts)?;
             }
             #[cfg(feature = "toml")]
             FileFormat::Toml => {
                 let data = toml::to_vec(accounts)?;
-                self.fd.write_all(&data)?;
+                writer.write_all(&data)?;
             }
             #[cfg(feature = "serde_json")]
             FileFormat::Json => {
-                serde_json::to_writer(&mut self.fd, accounts)?;
+                serde_json::to_writer(writer, accounts)?;
             }
-            _ => unimplemented!(),
+            _ => return Err(unsupported_format(format)),
         };
-        trace!("Vault data stored");
         Ok(())
     }
+
+    /// Shifts backups by one, dropping the oldest of them, and copies the
+    /// current vault file into the most recent backup
+    fn rotate(&self) -> Result<(), driver::Error> {
+        if self.config.backups == 0
+            || !Path::new(&self.config.location).exists()
+        {
+            return Ok(());
+        }
+        for no in (1..self.config.backups).rev() {
+            let path = self.config.backup_path(no);
+            if path.exists() {
+                fs::rename(&path, self.config.backup_path(no + 1))?;
+            }
+        }
+        fs::copy(&self.config.location, self.config.backup_path(1))?;
+        Ok(())
+    }
+
+    /// Atomically replaces the vault file with the new data
+    fn replace(&self, accounts: &Vec<Keyring>) -> Result<(), driver::Error> {
+        let data = self.encode(accounts)?;
+        let temp_path = self.config.temp_path();
+        let mut fd = fs::File::create(&temp_path)?;
+        fd.write_all(&data)?;
+        fd.sync_all()?;
+        fs::rename(&temp_path, &self.config.location)?;
+        sync_dir(Path::new(&self.config.location))?;
+        Ok(())
+    }
+}
+
+/// Returns vault file formats which serialization is compiled into this
+/// build
+pub fn supported_formats() -> Vec<FileFormat> {
+    let mut formats = vec![FileFormat::StrictEncode];
+    #[cfg(feature = "serde_yaml")]
+    formats.push(FileFormat::Yaml);
+    #[cfg(feature = "serde_json")]
+    formats.push(FileFormat::Json);
+    #[cfg(feature = "toml")]
+    formats.push(FileFormat::Toml);
+    formats
+}
+
+fn unsupported_format(format: &FileFormat) -> driver::Error {
+    let supported = supported_formats()
+        .iter()
+        .map(FileFormat::to_string)
+        .collect::<Vec<_>>();
+    driver::Error::UnsupportedFormat(format.to_string(), supported.join(", "))
+}
+
+/// Number of the deserialized keyrings after which the vault loading
+/// progress is logged
+const PROGRESS_STEP: usize = 100;
+
+/// Deserializes keyrings from the vault `entries` in parallel. Keyring
+/// numbers in the errors start from 1, in the order of the entries in the
+/// vault file.
+fn parse_entries<V>(entries: Vec<V>) -> Result<Vec<Keyring>, driver::Error>
+where
+    V: Deserializer<'static> + Send,
+    V::Error: Display,
+{
+    let total = entries.len();
+    debug!("Deserializing {} keyrings of the vault", total);
+    let parsed = AtomicUsize::new(0);
+    let keyrings = entries
+        .into_par_iter()
+        .enumerate()
+        .map(|(no, entry)| {
+            let keyring =
+                serde_path_to_error::deserialize(entry).map_err(|err| {
+                    driver::Error::Corrupted(format!(
+                        "keyring #{} is malformed at `{}`: {}",
+                        no + 1,
+                        err.path(),
+                        err.inner()
+                    ))
+                })?;
+            let done = parsed.fetch_add(1, Ordering::Relaxed) + 1;
+            if done % PROGRESS_STEP == 0 {
+                info!("{} of {} vault keyrings are loaded", done, total);
+            }
+            Ok(keyring)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    info!("{} vault keyrings are loaded", total);
+    Ok(keyrings)
+}
+
+/// Integrity header of the vault file
+struct Header {
+    version: u8,
+    checksum: sha256::Hash,
+    signature: Option<secp256k1::Signature>,
+}
+
+impl Header {
+    /// Splits file data into the header and the vault data. Returns
+    /// [`Option::None`] for the files without header.
+    fn parse<'a>(
+        data: &'a [u8],
+        path: &Path,
+    ) -> Result<Option<(Header, &'a [u8])>, driver::Error> {
+        if !data.starts_with(&FILE_MAGIC) {
+            return Ok(None);
+        }
+        let corrupted = |details: &str| {
+            driver::Error::Corrupted(format!("{} {}", path.display(), details))
+        };
+        if data.len() < HEADER_LEN {
+            return Err(corrupted("has truncated header"));
+        }
+        let version = data[4];
+        if version == 0 || version > FILE_VERSION {
+            return Err(corrupted(&format!(
+                "has unsupported version {}",
+                version
+            )));
+        }
+        let checksum = sha256::Hash::from_slice(&data[5..37])
+            .expect("slice length is always 32 bytes");
+        let (signature, payload) = match data[37] as usize {
+            0 => (None, &data[HEADER_LEN..]),
+            SIGNATURE_LEN if data.len() >= HEADER_LEN + SIGNATURE_LEN => {
+                let signature = secp256k1::Signature::from_compact(
+                    &data[HEADER_LEN..HEADER_LEN + SIGNATURE_LEN],
+                )
+                .map_err(|_| corrupted("has invalid signature encoding"))?;
+                (Some(signature), &data[HEADER_LEN + SIGNATURE_LEN..])
+            }
+            _ => return Err(corrupted("has invalid signature length")),
+        };
+        Ok(Some((
+            Header {
+                version,
+                checksum,
+                signature,
+            },
+            payload,
+        )))
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(HEADER_LEN + SIGNATURE_LEN);
+        data.extend(&FILE_MAGIC);
+        data.push(self.version);
+        data.extend(&self.checksum[..]);
+        match self.signature {
+            Some(signature) => {
+                data.push(SIGNATURE_LEN as u8);
+                data.extend(&signature.serialize_compact()[..]);
+            }
+            None => data.push(0),
+        }
+        data
+    }
+
+    /// Detects whether the vault data use the original keyring layout
+    fn is_legacy(&self) -> bool {
+        self.version <= LEGACY_FILE_VERSION
+    }
+
+    fn verify_checksum(
+        &self,
+        payload: &[u8],
+        path: &Path,
+    ) -> Result<(), driver::Error> {
+        if sha256::Hash::hash(payload) != self.checksum {
+            return Err(driver::Error::Corrupted(format!(
+                "checksum of {} does not match its data",
+                path.display()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Checks that the header is signed with `node_key`. Unsigned headers
+    /// are accepted only if the signature is not `required`.
+    fn verify_signature(
+        &self,
+        node_key: NodeKey,
+        required: bool,
+        path: &Path,
+    ) -> Result<(), driver::Error> {
+        let signature = match self.signature {
+            Some(signature) => signature,
+            None if required => {
+                return Err(driver::Error::Tampered(format!(
+                    "{} is not signed",
+                    path.display()
+                )))
+            }
+            None => return Ok(()),
+        };
+        let node_id = node_key.node_id();
+        let message = secp256k1::Message::from_slice(&self.checksum[..])
+            .expect("SHA256 hash is always a valid message");
+        crate::SECP256K1
+            .verify(&message, &signature, &node_id)
+            .map_err(|_| {
+                driver::Error::Tampered(format!(
+                    "{} is not signed by the node key {}",
+                    path.display(),
+                    node_id
+                ))
+            })
+    }
+}
+
+/// Makes sure that the directory entry of a renamed file reaches the storage
+/// media
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> io::Result<()> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    fs::File::open(dir)?.sync_all()
+}
+
+/// Windows does not support opening directories as files; renames are
+/// written through by the file system
+#[cfg(not(unix))]
+fn sync_dir(_: &Path) -> io::Result<()> {
+    Ok(())
 }
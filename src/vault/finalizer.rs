@@ -0,0 +1,212 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! PSBT finalizer and extractor (BIP-174 roles). Finalizer constructs final
+//! script sigs and witnesses for the inputs which have enough signatures,
+//! removing data which are not needed anymore. Supported are single-key
+//! inputs (P2PKH, P2WPKH, P2SH-P2WPKH and P2TR key path spends) and
+//! `OP_CHECKMULTISIG` scripts wrapped into P2SH, P2WSH or P2SH-P2WSH.
+
+use std::collections::BTreeMap;
+use std::iter;
+
+use bitcoin::blockdata::opcodes::{self, Class};
+use bitcoin::blockdata::script::{Builder, Instruction};
+use bitcoin::hashes::Hash;
+use bitcoin::util::psbt::{Input, PartiallySignedTransaction};
+use bitcoin::{PublicKey, Script, Transaction, WPubkeyHash};
+
+use super::keymgm::Error;
+use super::taproot;
+
+/// Finalizes all PSBT inputs which have enough signatures for that. Inputs
+/// which are already final are left untouched. Returns number of inputs
+/// which remain non-final.
+pub fn finalize(psbt: &mut PartiallySignedTransaction) -> usize {
+    let mut pending = 0usize;
+    for index in 0..psbt.inputs.len() {
+        if is_final(&psbt.inputs[index]) {
+            continue;
+        }
+        let spent = match taproot::spent_output(psbt, index) {
+            Some(txout) => txout.script_pubkey,
+            None => {
+                pending += 1;
+                continue;
+            }
+        };
+        let input = &mut psbt.inputs[index];
+        match satisfy(input, &spent) {
+            Some((script_sig, witness)) => {
+                trace!("PSBT input #{} is finalized", index);
+                input.final_script_sig =
+                    Some(script_sig).filter(|script| !script.is_empty());
+                input.final_script_witness =
+                    Some(witness).filter(|witness| !witness.is_empty());
+                clear(input);
+            }
+            None => pending += 1,
+        }
+    }
+    pending
+}
+
+/// Extracts network-serializable transaction from a PSBT with all inputs
+/// being final
+pub fn extract(psbt: PartiallySignedTransaction) -> Result<Transaction, Error> {
+    let pending = psbt.inputs.iter().filter(|input| !is_final(input)).count();
+    if pending > 0 {
+        return Err(Error::PsbtNotFinalized(pending));
+    }
+    Ok(psbt.extract_tx())
+}
+
+/// Detects whether the PSBT input has final script sig or witness
+pub fn is_final(input: &Input) -> bool {
+    input.final_script_sig.is_some() || input.final_script_witness.is_some()
+}
+
+fn satisfy(input: &Input, spent: &Script) -> Option<(Script, Vec<Vec<u8>>)> {
+    if taproot::is_p2tr(spent) {
+        let sig = input
+            .unknown
+            .iter()
+            .find(|(key, _)| key.type_value == taproot::PSBT_IN_TAP_KEY_SIG)
+            .map(|(_, sig)| sig.clone())?;
+        return Some((Script::new(), vec![sig]));
+    }
+    if spent.is_p2pkh() {
+        let (pubkey, sig) = input.partial_sigs.iter().find(|(pubkey, _)| {
+            Script::new_p2pkh(&pubkey.pubkey_hash()) == *spent
+        })?;
+        let script_sig = Builder::new()
+            .push_slice(sig)
+            .push_key(pubkey)
+            .into_script();
+        return Some((script_sig, vec![]));
+    }
+    if spent.is_v0_p2wpkh() {
+        return wpkh_witness(input, spent)
+            .map(|witness| (Script::new(), witness));
+    }
+    if spent.is_v0_p2wsh() {
+        let witness_script = input.witness_script.as_ref()?;
+        if witness_script.to_v0_p2wsh() != *spent {
+            return None;
+        }
+        let mut witness = multisig_sigs(&input.partial_sigs, witness_script)?;
+        witness.push(witness_script.to_bytes());
+        return Some((Script::new(), witness));
+    }
+    if spent.is_p2sh() {
+        let redeem_script = input.redeem_script.as_ref()?;
+        if redeem_script.to_p2sh() != *spent {
+            return None;
+        }
+        let script_sig = Builder::new()
+            .push_slice(redeem_script.as_bytes())
+            .into_script();
+        if redeem_script.is_v0_p2wpkh() {
+            let witness = wpkh_witness(input, redeem_script)?;
+            return Some((script_sig, witness));
+        }
+        if redeem_script.is_v0_p2wsh() {
+            let witness_script = input.witness_script.as_ref()?;
+            if witness_script.to_v0_p2wsh() != *redeem_script {
+                return None;
+            }
+            let mut witness =
+                multisig_sigs(&input.partial_sigs, witness_script)?;
+            witness.push(witness_script.to_bytes());
+            return Some((script_sig, witness));
+        }
+        let builder = multisig_sigs(&input.partial_sigs, redeem_script)?
+            .into_iter()
+            .fold(Builder::new(), |builder, item| builder.push_slice(&item));
+        let script_sig =
+            builder.push_slice(redeem_script.as_bytes()).into_script();
+        return Some((script_sig, vec![]));
+    }
+    None
+}
+
+fn wpkh_witness(input: &Input, script: &Script) -> Option<Vec<Vec<u8>>> {
+    let (pubkey, sig) = input.partial_sigs.iter().find(|(pubkey, _)| {
+        pubkey.compressed
+            && Script::new_v0_wpkh(&WPubkeyHash::hash(&pubkey.to_bytes()))
+                == *script
+    })?;
+    Some(vec![sig.clone(), pubkey.to_bytes()])
+}
+
+/// Parses `m <pubkey>... n OP_CHECKMULTISIG` script and returns stack items
+/// satisfying it: dummy empty item followed by `m` signatures in the order
+/// of the script keys
+fn multisig_sigs(
+    partial_sigs: &BTreeMap<PublicKey, Vec<u8>>,
+    script: &Script,
+) -> Option<Vec<Vec<u8>>> {
+    let mut instructions = script.instructions();
+    let threshold = match instructions.next()?.ok()? {
+        Instruction::Op(op) => pushnum(op)?,
+        _ => return None,
+    };
+    let mut pubkeys = vec![];
+    let total = loop {
+        match instructions.next()?.ok()? {
+            Instruction::PushBytes(data) => {
+                pubkeys.push(PublicKey::from_slice(data).ok()?)
+            }
+            Instruction::Op(op) => break pushnum(op)?,
+        }
+    };
+    match instructions.next()?.ok()? {
+        Instruction::Op(op) if op == opcodes::all::OP_CHECKMULTISIG => {}
+        _ => return None,
+    }
+    if instructions.next().is_some()
+        || total != pubkeys.len()
+        || threshold > total
+    {
+        return None;
+    }
+    let sigs = pubkeys
+        .iter()
+        .filter_map(|pubkey| partial_sigs.get(pubkey))
+        .take(threshold)
+        .cloned()
+        .collect::<Vec<_>>();
+    if sigs.len() < threshold {
+        return None;
+    }
+    Some(iter::once(vec![]).chain(sigs).collect())
+}
+
+fn pushnum(op: opcodes::All) -> Option<usize> {
+    match op.classify() {
+        Class::PushNum(num) if num > 0 => Some(num as usize),
+        _ => None,
+    }
+}
+
+/// Removes all input data which are not required after finalization
+fn clear(input: &mut Input) {
+    input.partial_sigs.clear();
+    input.sighash_type = None;
+    input.redeem_script = None;
+    input.witness_script = None;
+    input.bip32_derivation.clear();
+    input
+        .unknown
+        .retain(|key, _| key.type_value != taproot::PSBT_IN_TAP_KEY_SIG);
+}
@@ -0,0 +1,72 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Identity keys for Nostr/DID-style protocols: x-only public keys with
+//! BIP-340 Schnorr signatures over event digests. Identity keys are derived
+//! from the account private key under a dedicated hardened path, following
+//! NIP-06 (`m/44'/1237'/<index>'/0/0`) when the account is a keyring master
+//! account, so the identity keys do not overlap with any of Bitcoin keys.
+
+use bitcoin::hashes::sha256;
+use bitcoin::secp256k1::{schnorrsig, SecretKey};
+use bitcoin::util::bip32::{ChildNumber, DerivationPath};
+
+use super::keymgm::Error;
+use super::{taproot, KeysAccount};
+
+/// BIP-44 purpose used for the identity keys
+pub const IDENTITY_PURPOSE: u32 = 44;
+
+/// SLIP-44 coin type registered for Nostr and used for the identity keys
+pub const IDENTITY_COIN_TYPE: u32 = 1237;
+
+/// Returns derivation path of the identity key with a given `index`
+pub fn path(index: u32) -> Result<DerivationPath, Error> {
+    Ok(DerivationPath::from(vec![
+        ChildNumber::from_hardened_idx(IDENTITY_PURPOSE)?,
+        ChildNumber::from_hardened_idx(IDENTITY_COIN_TYPE)?,
+        ChildNumber::from_hardened_idx(index)?,
+        ChildNumber::from_normal_idx(0)?,
+        ChildNumber::from_normal_idx(0)?,
+    ]))
+}
+
+/// Derives identity key pair with a given `index` from the account private
+/// key. The decryption key and derived private keys are wiped out right
+/// after the derivation.
+pub fn keypair(
+    account: &KeysAccount,
+    index: u32,
+    decryption_key: &mut SecretKey,
+) -> Result<schnorrsig::KeyPair, Error> {
+    let path = path(index)?;
+    let xpriv = account.xprivkey(decryption_key)?.derive_priv(&path)?;
+    Ok(schnorrsig::KeyPair::from_seckey_slice(
+        &crate::SECP256K1,
+        &xpriv.secret_key()[..],
+    )?)
+}
+
+/// Returns x-only public key of the identity key pair
+pub fn pubkey(keypair: &schnorrsig::KeyPair) -> schnorrsig::PublicKey {
+    schnorrsig::PublicKey::from_keypair(&crate::SECP256K1, keypair)
+}
+
+/// Signs identity event `digest`, like Nostr event id, with BIP-340
+/// signature
+pub fn sign(
+    digest: sha256::Hash,
+    keypair: &schnorrsig::KeyPair,
+) -> Result<schnorrsig::Signature, Error> {
+    taproot::sign(digest, keypair)
+}
@@ -0,0 +1,221 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Portable vault dump used to migrate vaults between machines and storage
+//! drivers. The dump does not depend on the storage driver and contains all
+//! keyrings with their accounts and metadata; private keys remain encrypted
+//! with the vault key, so the daemon importing the dump must use the same
+//! vault encryption key (node key or passphrase). Unless the dump is
+//! encrypted, the same vault always produces the same dump.
+//!
+//! | Field      | Length | Value                                   |
+//! |------------|--------|-----------------------------------------|
+//! | Magic      | 4      | [`MAGIC`]                               |
+//! | Version    | 1      | [`DUMP_VERSION`]                        |
+//! | Encryption | 1      | `0x00` - plain, `0x01` - passphrase     |
+//! | Payload    | ...    | Strict-encoded keyrings                 |
+//!
+//! Payload of the passphrase-encrypted dump starts with the key-derivation
+//! function parameters: algorithm byte (`0x01` - Argon2id, `0x02` - scrypt)
+//! followed by the parameters in little-endian order (`m_cost`, `t_cost`,
+//! `lanes` as 4-byte integers for Argon2id; 1-byte `log_n`, 4-byte `r` and
+//! `p` for scrypt). Next comes 33-byte salt public key, and the rest of the
+//! payload is strict-encoded keyrings wrapped with [`crypto::wrap`] for the
+//! key derived from the passphrase and the salt.
+
+use std::convert::TryInto;
+
+use bitcoin::secp256k1::rand::thread_rng;
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use lnpbp::strict_encoding::{strict_deserialize, strict_serialize};
+
+use super::Keyring;
+use crate::crypto;
+use crate::passphrase::{self, Kdf};
+
+/// Magic bytes starting vault dump
+pub const MAGIC: [u8; 4] = *b"KRVD";
+
+/// Version of the vault dump format produced by [`encode`]
+pub const DUMP_VERSION: u8 = 1;
+
+const PLAIN: u8 = 0x00;
+const PASSPHRASE: u8 = 0x01;
+
+const ARGON2ID: u8 = 0x01;
+const SCRYPT: u8 = 0x02;
+
+const HEADER_LEN: usize = MAGIC.len() + 2;
+const SALT_LEN: usize = 33;
+
+/// Error cases of vault dump encoding and decoding
+#[derive(Clone, PartialEq, Eq, Debug, Display, From, Error)]
+#[display(doc_comments)]
+pub enum Error {
+    /// Data are not a vault dump
+    Magic,
+
+    /// Vault dump version {0} is not supported
+    Version(u8),
+
+    /// Vault dump encryption type {0:#04x} is not supported
+    Encryption(u8),
+
+    /// Key-derivation function {0:#04x} of the vault dump is not supported
+    Kdf(u8),
+
+    /// Vault dump is truncated
+    Truncated,
+
+    /// Vault dump is encrypted; please provide the passphrase
+    PassphraseRequired,
+
+    /// Vault dump can't be decrypted: {0}
+    #[from]
+    Crypto(crypto::Error),
+
+    /// {0}
+    #[from]
+    Passphrase(passphrase::Error),
+
+    /// Vault dump contains malformed keyring data: {0}
+    Encoding(String),
+}
+
+impl From<lnpbp::strict_encoding::Error> for Error {
+    fn from(err: lnpbp::strict_encoding::Error) -> Self {
+        Error::Encoding(err.to_string())
+    }
+}
+
+/// Produces vault dump of the `keyrings`. If `encryption` is given, the dump
+/// is encrypted with the key derived from the passphrase using the provided
+/// key-derivation function.
+pub fn encode(
+    keyrings: &Vec<Keyring>,
+    encryption: Option<(&str, Kdf)>,
+) -> Result<Vec<u8>, Error> {
+    let mut data = Vec::from(&MAGIC[..]);
+    data.push(DUMP_VERSION);
+    let mut payload = strict_serialize(keyrings)?;
+    match encryption {
+        None => {
+            data.push(PLAIN);
+            data.extend_from_slice(&payload);
+        }
+        Some((passphrase, kdf)) => {
+            data.push(PASSPHRASE);
+            data.extend(encode_kdf(kdf));
+            let salt = PublicKey::from_secret_key(
+                &crate::SECP256K1,
+                &SecretKey::new(&mut thread_rng()),
+            );
+            data.extend_from_slice(&salt.serialize());
+            let key = kdf.derive_key(passphrase, salt)?;
+            let encryption_key =
+                PublicKey::from_secret_key(&crate::SECP256K1, &key);
+            data.extend(crypto::wrap(&payload, encryption_key)?);
+        }
+    }
+    payload.iter_mut().for_each(|byte| *byte = 0);
+    Ok(data)
+}
+
+/// Reads keyrings from the vault `dump`, decrypting it with the
+/// `passphrase` if the dump is encrypted
+pub fn decode(
+    dump: &[u8],
+    passphrase: Option<&str>,
+) -> Result<Vec<Keyring>, Error> {
+    if dump.len() < HEADER_LEN {
+        return Err(Error::Truncated);
+    }
+    if dump[..MAGIC.len()] != MAGIC {
+        return Err(Error::Magic);
+    }
+    if dump[MAGIC.len()] != DUMP_VERSION {
+        return Err(Error::Version(dump[MAGIC.len()]));
+    }
+    let payload = &dump[HEADER_LEN..];
+    match dump[MAGIC.len() + 1] {
+        PLAIN => Ok(strict_deserialize(payload)?),
+        PASSPHRASE => {
+            let passphrase = passphrase.ok_or(Error::PassphraseRequired)?;
+            let (kdf, rest) = decode_kdf(payload)?;
+            if rest.len() < SALT_LEN {
+                return Err(Error::Truncated);
+            }
+            let salt = PublicKey::from_slice(&rest[..SALT_LEN])
+                .map_err(|_| crypto::Error::InvalidUnblinding)?;
+            let key = kdf.derive_key(passphrase, salt)?;
+            let mut data = crypto::unwrap(&rest[SALT_LEN..], &key)?;
+            let keyrings = strict_deserialize(&data);
+            data.iter_mut().for_each(|byte| *byte = 0);
+            Ok(keyrings?)
+        }
+        other => Err(Error::Encryption(other)),
+    }
+}
+
+fn encode_kdf(kdf: Kdf) -> Vec<u8> {
+    let mut data = vec![];
+    match kdf {
+        Kdf::Argon2id {
+            m_cost,
+            t_cost,
+            lanes,
+        } => {
+            data.push(ARGON2ID);
+            data.extend_from_slice(&m_cost.to_le_bytes());
+            data.extend_from_slice(&t_cost.to_le_bytes());
+            data.extend_from_slice(&lanes.to_le_bytes());
+        }
+        Kdf::Scrypt { log_n, r, p } => {
+            data.push(SCRYPT);
+            data.push(log_n);
+            data.extend_from_slice(&r.to_le_bytes());
+            data.extend_from_slice(&p.to_le_bytes());
+        }
+    }
+    data
+}
+
+fn decode_kdf(data: &[u8]) -> Result<(Kdf, &[u8]), Error> {
+    let u32_at = |pos: usize| -> Result<u32, Error> {
+        data.get(pos..pos + 4)
+            .map(|bytes| {
+                u32::from_le_bytes(bytes.try_into().expect("fixed length"))
+            })
+            .ok_or(Error::Truncated)
+    };
+    match data.first() {
+        None => Err(Error::Truncated),
+        Some(&ARGON2ID) => Ok((
+            Kdf::Argon2id {
+                m_cost: u32_at(1)?,
+                t_cost: u32_at(5)?,
+                lanes: u32_at(9)?,
+            },
+            &data[13..],
+        )),
+        Some(&SCRYPT) => Ok((
+            Kdf::Scrypt {
+                log_n: *data.get(1).ok_or(Error::Truncated)?,
+                r: u32_at(2)?,
+                p: u32_at(6)?,
+            },
+            &data[10..],
+        )),
+        Some(other) => Err(Error::Kdf(*other)),
+    }
+}
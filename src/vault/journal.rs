@@ -0,0 +1,301 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Hash-chained journal of the vault updates. After each update of the vault
+//! a record listing the accounts added, modified and removed by the update
+//! is appended to the journal file as a line of JSON. Each record contains
+//! digest of the previous record line, so a record can't be changed,
+//! inserted or removed without breaking the chain of the records following
+//! it. Accounts are recorded with the digests of their metadata, so
+//! [`verify`] replays the journal and checks that the vault contents are
+//! consistent with the recorded history.
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::XpubIdentifier;
+use chrono::Utc;
+use lnpbp::strict_encoding::strict_serialize;
+
+use super::{diff, Keyring};
+
+/// Errors of the vault journal
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum Error {
+    /// Journal record #{0} is malformed: {1}
+    Malformed(u64, String),
+
+    /// Journal record #{0} is out of sequence
+    Sequence(u64),
+
+    /// Journal record #{0} does not refer to the digest of the previous
+    /// record; the journal is modified starting from this record
+    Chain(u64),
+
+    /// Journal record #{0} removes account {1}, which is not present in the
+    /// vault at that point of the history
+    Replay(u64, XpubIdentifier),
+
+    /// Vault account {1} diverges from the journal starting from record #{0}
+    Divergence(u64, XpubIdentifier),
+
+    /// Vault account {0} is not recorded in the journal
+    Unrecorded(XpubIdentifier),
+
+    /// Journal storage error: {0}
+    Storage(String),
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Storage(err.to_string())
+    }
+}
+
+/// Journal record of a single vault update
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(crate = "serde_crate")]
+pub struct Record {
+    /// Number of the record in the journal, starting from zero
+    pub seq: u64,
+
+    /// Time of the update, in seconds since UNIX epoch
+    pub timestamp: u64,
+
+    /// Digest of the previous record line; zero hash for the first record
+    pub prev: sha256::Hash,
+
+    /// Accounts added or modified by the update with the digests of their
+    /// metadata
+    #[serde(default)]
+    pub updated: BTreeMap<XpubIdentifier, sha256::Hash>,
+
+    /// Accounts removed from the vault by the update
+    #[serde(default)]
+    pub removed: Vec<XpubIdentifier>,
+}
+
+/// Vault accounts as recorded by the replayed journal records
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Replay {
+    /// Number of the replayed records
+    pub records: u64,
+
+    /// Digest of the last record line
+    pub head: sha256::Hash,
+
+    /// Digests of the metadata of the recorded accounts
+    pub accounts: BTreeMap<XpubIdentifier, sha256::Hash>,
+
+    /// Number of the last record updating or removing each of the accounts
+    pub changed: BTreeMap<XpubIdentifier, u64>,
+}
+
+impl Default for Replay {
+    fn default() -> Self {
+        Self {
+            records: 0,
+            head: sha256::Hash::from_inner([0u8; 32]),
+            accounts: BTreeMap::new(),
+            changed: BTreeMap::new(),
+        }
+    }
+}
+
+impl Replay {
+    /// Applies the next record `line`, checking its sequence number and the
+    /// digest of the previous record
+    fn apply(&mut self, line: &str) -> Result<(), Error> {
+        let seq = self.records;
+        let record: Record = serde_json::from_str(line)
+            .map_err(|err| Error::Malformed(seq, err.to_string()))?;
+        if record.seq != seq {
+            return Err(Error::Sequence(seq));
+        }
+        if record.prev != self.head {
+            return Err(Error::Chain(seq));
+        }
+        for id in record.removed {
+            if self.accounts.remove(&id).is_none() {
+                return Err(Error::Replay(seq, id));
+            }
+            self.changed.insert(id, seq);
+        }
+        for (id, digest) in record.updated {
+            self.accounts.insert(id, digest);
+            self.changed.insert(id, seq);
+        }
+        self.records += 1;
+        self.head = sha256::Hash::hash(line.as_bytes());
+        Ok(())
+    }
+
+    /// Compares the replayed accounts with the vault `keyrings`. If some of
+    /// the accounts differ, reports the earliest of the records which were
+    /// the last to change these accounts.
+    pub fn check(&self, keyrings: &[Keyring]) -> Result<(), Error> {
+        let mut contents = digests(keyrings);
+        let mut divergence: Option<(u64, XpubIdentifier)> = None;
+        let mut diverge = |seq: u64, id: XpubIdentifier| match divergence {
+            Some((first, _)) if first <= seq => {}
+            _ => divergence = Some((seq, id)),
+        };
+        for (id, digest) in &self.accounts {
+            if contents.remove(id) != Some(*digest) {
+                diverge(self.changed[id], *id);
+            }
+        }
+        let mut unrecorded = None;
+        for id in contents.keys() {
+            match self.changed.get(id) {
+                Some(seq) => diverge(*seq, *id),
+                None => {
+                    unrecorded.get_or_insert(*id);
+                }
+            }
+        }
+        if let Some((seq, id)) = divergence {
+            return Err(Error::Divergence(seq, id));
+        }
+        match unrecorded {
+            Some(id) => Err(Error::Unrecorded(id)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Journal file the vault updates are recorded to
+#[derive(Clone, Debug)]
+pub struct Journal {
+    path: PathBuf,
+    replay: Replay,
+}
+
+impl Journal {
+    /// Opens journal at `path`, replaying its records. The file is created
+    /// with the first record.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let replay = replay(&path)?;
+        info!(
+            "Vault updates are recorded to the journal {} ({} records)",
+            path.display(),
+            replay.records
+        );
+        Ok(Self { path, replay })
+    }
+
+    /// Number of the records in the journal
+    pub fn records(&self) -> u64 {
+        self.replay.records
+    }
+
+    /// Compares the vault `keyrings` with the journal; see [`Replay::check`]
+    pub fn check(&self, keyrings: &[Keyring]) -> Result<(), Error> {
+        self.replay.check(keyrings)
+    }
+
+    /// Appends record of the vault `keyrings` changes made since the last
+    /// record, syncing it to the disk. Nothing is recorded if metadata of
+    /// the accounts are not changed. Returns number of the new record.
+    pub fn record(
+        &mut self,
+        keyrings: &[Keyring],
+    ) -> Result<Option<u64>, Error> {
+        let contents = digests(keyrings);
+        let removed = self
+            .replay
+            .accounts
+            .keys()
+            .filter(|id| !contents.contains_key(*id))
+            .copied()
+            .collect::<Vec<_>>();
+        let updated = contents
+            .into_iter()
+            .filter(|(id, digest)| self.replay.accounts.get(id) != Some(digest))
+            .collect::<BTreeMap<_, _>>();
+        if updated.is_empty() && removed.is_empty() {
+            return Ok(None);
+        }
+        let seq = self.replay.records;
+        let record = Record {
+            seq,
+            timestamp: Utc::now().timestamp() as u64,
+            prev: self.replay.head,
+            updated,
+            removed,
+        };
+        debug!("Recording vault update #{} to the journal", seq);
+        let line = serde_json::to_string(&record)
+            .map_err(|err| Error::Storage(err.to_string()))?;
+        let mut fd = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        fd.write_all(format!("{}\n", line).as_bytes())?;
+        fd.sync_data()?;
+        self.replay.apply(&line)?;
+        Ok(Some(seq))
+    }
+}
+
+/// Digests of the metadata of all the vault accounts, indexed by the
+/// account id. Encrypted keys are not included, so the digests do not change
+/// when the keys are re-encrypted.
+pub fn digests(keyrings: &[Keyring]) -> BTreeMap<XpubIdentifier, sha256::Hash> {
+    diff::entries(keyrings)
+        .into_iter()
+        .map(|(id, entry)| {
+            let mut engine = sha256::Hash::engine();
+            engine.input(
+                &strict_serialize(&entry.info)
+                    .expect("account metadata are always encodable"),
+            );
+            engine.input(&[entry.archived as u8]);
+            (id, sha256::Hash::from_engine(engine))
+        })
+        .collect()
+}
+
+/// Replays the journal at `path`, checking the chain of its records. Missing
+/// journal has no records.
+pub fn replay(path: impl AsRef<Path>) -> Result<Replay, Error> {
+    let fd = match fs::File::open(path) {
+        Ok(fd) => fd,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return Ok(Replay::default())
+        }
+        Err(err) => return Err(err.into()),
+    };
+    let mut replay = Replay::default();
+    for line in BufReader::new(fd).lines() {
+        replay.apply(&line?)?;
+    }
+    Ok(replay)
+}
+
+/// Replays the journal at `path` and checks that the vault `keyrings` are
+/// consistent with it, reporting the first divergent record. Returns number
+/// of the journal records.
+pub fn verify(
+    path: impl AsRef<Path>,
+    keyrings: &[Keyring],
+) -> Result<u64, Error> {
+    let replay = replay(path)?;
+    replay.check(keyrings)?;
+    Ok(replay.records)
+}
@@ -25,23 +25,79 @@
 //! from the master account.
 
 use serde::{Deserialize, Deserializer, Serializer};
-use std::cmp::Ordering;
 use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::convert::TryFrom;
+use std::io;
 
 use bitcoin;
 use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::hashes::{sha256, Hash};
 use bitcoin::secp256k1;
+use bitcoin::secp256k1::recovery::RecoverableSignature;
 use bitcoin::secp256k1::Signature;
 use bitcoin::util::bip32::{
-    self, DerivationPath, ExtendedPrivKey, ExtendedPubKey, Fingerprint,
-    IntoDerivationPath, KeySource,
+    self, ChildNumber, DerivationPath, ExtendedPrivKey, ExtendedPubKey,
+    Fingerprint, IntoDerivationPath, KeySource,
 };
 use bitcoin::XpubIdentifier;
 use lnpbp::chain::{AssetId, Chain};
 use lnpbp::elgamal;
+use lnpbp::strict_encoding::{self, StrictDecode, StrictEncode};
 use secp256k1::rand::{thread_rng, RngCore};
 use slip132::KeyApplication;
+use zeroize::{Zeroize, Zeroizing};
+
+use super::secret::{wipe_key, SecretBuffer, SecretXpriv};
+use super::shred::Shredded;
+use crate::lifecycle::{Lifecycle, Operation};
+pub use crate::rpc::types::UpdateMode;
+use crate::rpc::types::{
+    Bip85Application, Branches, MultisigGroup, PaymentCode, SigningPolicy,
+};
+use crate::signed_message;
+
+/// Approximate costs of the operations performed during sub-account
+/// derivation, relative to a hardened derivation step (HMAC-SHA512 and a
+/// scalar addition). Normal step additionally computes public key of the
+/// parent; decryption of an account key performs ElGamal decryption and
+/// checks the decrypted key against the account public key. The costs can
+/// be re-measured with `cargo bench --features node --bench derivation`.
+const HARDENED_STEP_COST: u32 = 1;
+const NORMAL_STEP_COST: u32 = 12;
+const DECRYPTION_COST: u32 = 40;
+
+/// Estimates cost of deriving private key with a relative `derivation`
+fn derivation_cost(derivation: &[ChildNumber]) -> u32 {
+    derivation
+        .iter()
+        .map(|step| match step {
+            ChildNumber::Hardened { .. } => HARDENED_STEP_COST,
+            ChildNumber::Normal { .. } => NORMAL_STEP_COST,
+        })
+        .sum()
+}
+
+/// Checks whether `path` is a proper prefix of the `derivation` path
+fn is_parent(path: &DerivationPath, derivation: &DerivationPath) -> bool {
+    let (path, derivation) = (path.as_ref(), derivation.as_ref());
+    path.len() < derivation.len() && path == &derivation[..path.len()]
+}
+
+/// Returns chain which keys of the bitcoin `network` are used on
+pub fn network_chain(network: bitcoin::Network) -> Chain {
+    match network {
+        bitcoin::Network::Bitcoin => Chain::Mainnet,
+        bitcoin::Network::Testnet => Chain::Testnet3,
+        bitcoin::Network::Signet => Chain::Signet,
+        bitcoin::Network::Regtest => Chain::Regtest(
+            bitcoin::blockdata::constants::genesis_block(network).block_hash(),
+        ),
+    }
+}
+
+/// Maximal number of keys which can be derived with a single range
+/// derivation request
+pub const MAX_DERIVATION_RANGE: u32 = 10_000;
 
 /// Error cases related to keyring & keys account management and usage
 #[derive(Clone, PartialEq, Eq, Debug, Display, From, Error)]
@@ -63,9 +119,9 @@ pub enum Error {
     GroupOverflow,
 
     /// The account keys can't be derived with hardened path; a private key
-    /// is required. The error is returned by [KeysAccount::derive] function
-    /// if a hardened derivation path is used, but no decryption key for the
-    /// secret key is provided.
+    /// is required. The error is returned by [KeysAccount::derive_neutered]
+    /// function if a hardened derivation path is used, since no decryption
+    /// key for the secret key is provided.
     HardenedDerivation,
 
     /// This error implies that secret key storage was corrupted and that
@@ -107,6 +163,80 @@ pub enum Error {
     #[from(bip32::Error)]
     ExtendedKeyFormat(bip32::Error),
 
+    /// Operation {1} is not allowed for the key in {0} lifecycle state
+    LifecycleRestriction(Lifecycle, Operation),
+
+    /// Key lifecycle state can't be changed from {0} to {1}
+    LifecycleTransition(Lifecycle, Lifecycle),
+
+    /// Account is watch-only and does not hold a private key
+    WatchOnly,
+
+    /// Extended public key {0} is already known to the vault
+    KnownKey(XpubIdentifier),
+
+    /// Output descriptor can't be imported: {0}
+    Descriptor(String),
+
+    /// Invalid label key `{0}`: keys must be non-empty and can't contain `=`
+    /// or whitespace
+    LabelKey(String),
+
+    /// Invalid contact name `{0}`: names of the counterparty payment codes
+    /// must be non-empty
+    ContactName(String),
+
+    /// No payment code is saved for the contact `{0}`
+    UnknownContact(String),
+
+    /// Human-readable part `{0}` does not belong to a BOLT-11 invoice
+    InvoiceHrp(String),
+
+    /// BOLT-11 invoice data contain values which are not 5-bit
+    InvoiceData,
+
+    /// Lightning message of type {0} is not a gossip message signed with the
+    /// node key
+    GossipType(u16),
+
+    /// Lightning gossip message is malformed or does not belong to the node
+    /// and channel it is signed for
+    GossipMessage,
+
+    /// PSBT input #{0} belongs to a network other than {1}, which is the
+    /// chain of the keyring signing the input
+    ChainMismatch(usize, Chain),
+
+    /// Range of {0} keys exceeds the limit of keys derived per request
+    DerivationRange(u32),
+
+    /// Invalid branch layout ({0}): branches must be distinct normal
+    /// derivation indexes and gap limit must not be zero
+    BranchLayout(Branches),
+
+    /// Invalid signing policy ({0}): sighash types must be standard and rate
+    /// limit period must not be zero
+    SigningPolicy(SigningPolicy),
+
+    /// Unsupported BIP-85 application {0}
+    Bip85Application(Bip85Application),
+
+    /// PSBT input #{0} does not provide information about the output it
+    /// spends, which is required to compute signature hash
+    PsbtInputData(usize),
+
+    /// PSBT can't be extracted into a transaction since {0} of its inputs
+    /// have not enough signatures to be finalized
+    PsbtNotFinalized(usize),
+
+    /// Account spent by PSBT input #{0} has no single-key SegWit application,
+    /// so the input can't be composed without the previous transaction
+    PsbtInputApplication(usize),
+
+    /// PSBT outputs spend {1} satoshis, exceeding {0} satoshis provided by
+    /// the inputs
+    InsufficientFunds(u64, u64),
+
     /// Error happens when operations related to [`ExtendedPubKey`] or
     /// [`ExtendedPrivKey`] resolving tasks has failed. Key resolving is done
     /// using resolvers implementing [`VersionResolver`], and fail if there
@@ -139,55 +269,27 @@ impl From<secp256k1::Error> for Error {
     }
 }
 
-/// Mode for an update operation
-#[derive(Copy, Clone, PartialEq, Eq, Debug, Display)]
-#[display(Debug)]
-pub enum UpdateMode {
-    /// Add new qualifiers to existing ones
-    Add,
-
-    /// Add new qualifiers **replacing** existing ones
-    Replace,
-
-    /// Removes qualifiers from the provided list; if some of the qualifiers
-    /// are not found just ignore them and process the rest
-    RemoveIgnore,
-
-    /// Removes qualifiers from the provided list; if any of the qualifiers
-    /// is not found then the function fails returning error, not updating any
-    /// of the qualifiers
-    RemoveOrFail,
-}
-
-impl Default for UpdateMode {
-    fn default() -> Self {
-        Self::Add
-    }
-}
-
 /// Keyring is a root account governed by the single extended private/public key
 /// pair. This pair can be a master key - or represent some derivation from
 /// another master; however in this case this master should not a be part of the
 /// same vault.
 #[derive(
-    Getters,
-    Clone,
-    PartialEq,
-    Eq,
-    Display,
-    Debug,
-    Serialize,
-    Deserialize,
-    StrictEncode,
-    StrictDecode,
+    Getters, Clone, PartialEq, Eq, Display, Debug, Serialize, Deserialize,
 )]
-#[strict_encoding_crate(lnpbp::strict_encoding)]
 #[serde(crate = "serde_crate")]
 #[display(Debug)]
 pub struct Keyring {
     master_account: KeysAccount,
     key_source: Option<KeySource>,
     sub_accounts: BTreeMap<DerivationPath, KeysAccount>,
+
+    /// Chain the keyring is created for; absent for the keyrings stored
+    /// before the chain was persisted, which chain is detected from the
+    /// master key network (see [`Keyring::chain`]). Strict-encoded vaults
+    /// keep it in the keyring extension record (see [`EXTENSION_VERSION`]),
+    /// so it is absent for the keyrings written in the legacy layout.
+    #[serde(default, rename = "chain")]
+    stored_chain: Option<Chain>,
 }
 
 impl Keyring {
@@ -239,6 +341,36 @@ impl Keyring {
             master_account,
             key_source,
             sub_accounts: Default::default(),
+            stored_chain: Some(chain.clone()),
+        })
+    }
+
+    /// Creates keyring from an existing extended private key imported from
+    /// some other wallet. The key becomes keyring master account, while its
+    /// `key_source`, if known, records the origin of the key. The keyring
+    /// chain is detected from the network of the key.
+    pub fn from_xpriv(
+        name: impl ToString,
+        details: impl ToString,
+        application: Option<KeyApplication>,
+        xprivkey: ExtendedPrivKey,
+        key_source: Option<KeySource>,
+        encryption_key: secp256k1::PublicKey,
+    ) -> Result<Self, Error> {
+        let stored_chain = Some(network_chain(xprivkey.network));
+        let master_account = KeysAccount::from_xpriv(
+            name,
+            details,
+            set![],
+            application,
+            SecretXpriv::from(xprivkey),
+            encryption_key,
+        )?;
+        Ok(Self {
+            master_account,
+            key_source,
+            sub_accounts: Default::default(),
+            stored_chain,
         })
     }
 
@@ -267,6 +399,14 @@ impl Keyring {
         &self.master_account.xpubkey
     }
 
+    /// Returns chain the keyring is created for. Chain of the keyrings
+    /// stored without it is detected from the master key network.
+    pub fn chain(&self) -> Chain {
+        self.stored_chain
+            .clone()
+            .unwrap_or_else(|| network_chain(self.master_xpubkey().network))
+    }
+
     /// Returns [`KeysAccount`] for a given `key_id`, or [`Option::None`] if
     /// account does not exist under the current keyring
     pub fn account_by_id(
@@ -283,6 +423,33 @@ impl Keyring {
         }
     }
 
+    /// Returns the deepest account which derivation path is a prefix of
+    /// `derivation` (including the account derived with `derivation`
+    /// itself), or the master account if there is no such sub-account
+    pub fn account_by_derivation(
+        &self,
+        derivation: &DerivationPath,
+    ) -> &KeysAccount {
+        self.sub_accounts
+            .iter()
+            .filter(|(path, _)| derivation.as_ref().starts_with(path.as_ref()))
+            .max_by_key(|(path, _)| path.as_ref().len())
+            .map(|(_, account)| account)
+            .unwrap_or(&self.master_account)
+    }
+
+    /// Checks that the key derived with `derivation` may sign: lifecycle
+    /// states of both the master account and the account returned by
+    /// [`Keyring::account_by_derivation`] must allow signing
+    pub fn check_signing(
+        &self,
+        derivation: &DerivationPath,
+    ) -> Result<(), Error> {
+        self.master_account.check_lifecycle(Operation::Sign)?;
+        self.account_by_derivation(derivation)
+            .check_lifecycle(Operation::Sign)
+    }
+
     /// Creates new sub-account and does all required derivation for a given
     /// derivation path [`DerivationPath`] and a list of assets identified by
     /// respective [`AssetId`] (may be empty). Returns derivation error if the
@@ -333,6 +500,48 @@ impl Keyring {
         assets: HashSet<AssetId>,
         decryption_key: &mut secp256k1::SecretKey,
     ) -> Result<&KeysAccount, Error> {
+        let (derivation, account) = self.derive_account(
+            derivation,
+            name,
+            details,
+            assets,
+            decryption_key,
+        )?;
+        self.add_account(derivation, account)
+    }
+
+    /// Derives new sub-account in the same way as [`Keyring::create_account`]
+    /// does, but does not add it to the keyring. Returns derivation path of
+    /// the account together with the account itself, which may be later
+    /// added with [`Keyring::add_account`].
+    pub fn derive_account(
+        &self,
+        derivation: impl IntoDerivationPath,
+        name: impl ToString,
+        details: Option<impl ToString>,
+        assets: HashSet<AssetId>,
+        decryption_key: &mut secp256k1::SecretKey,
+    ) -> Result<(DerivationPath, KeysAccount), Error> {
+        let mut cache = DerivationCache::with(decryption_key);
+        self.derive_account_cached(
+            derivation, name, details, assets, &mut cache,
+        )
+    }
+
+    /// Derives new sub-account in the same way as [`Keyring::derive_account`]
+    /// does, but takes decryption key from the `cache` and keeps the keys
+    /// decrypted or derived in the process there. Subsequent derivations
+    /// with the same cache start from the cached keys, if it is cheaper
+    /// than decrypting an account closer to the derivation target, and
+    /// siblings of the derived account require a single derivation step.
+    pub fn derive_account_cached(
+        &self,
+        derivation: impl IntoDerivationPath,
+        name: impl ToString,
+        details: Option<impl ToString>,
+        assets: HashSet<AssetId>,
+        cache: &mut DerivationCache,
+    ) -> Result<(DerivationPath, KeysAccount), Error> {
         let derivation = derivation.into_derivation_path()?;
 
         // Check if the derivation path is already used and return error
@@ -340,38 +549,140 @@ impl Keyring {
             return Err(Error::DerivationAlreadyUsed);
         }
 
-        // Find a proper extended key to derive from: it must be the one
-        // which is maximally close to the derivation target by its path
-        let derivation_ref = derivation.as_ref();
-        let mut sorted = self
+        let root = self.identifier();
+        let parent = self.derivation_parent(&derivation, cache)?;
+        if !cache.xprivs.contains_key(&(root, parent.clone())) {
+            let mut decryption_key = cache.decryption_key;
+            let xprivkey =
+                self.all_accounts()[&parent].unlock(&mut decryption_key)?;
+            cache.decryptions += 1;
+            cache.xprivs.insert((root, parent.clone()), xprivkey);
+        }
+
+        // Caching the key one level above the target
+        let steps = derivation.as_ref();
+        let len = steps.len();
+        let base = DerivationPath::from(&steps[..len - 1]);
+        if !cache.xprivs.contains_key(&(root, base.clone())) {
+            let xprivkey = cache.xprivs[&(root, parent.clone())]
+                .derive_priv(&&steps[parent.as_ref().len()..len - 1])?;
+            cache.xprivs.insert((root, base.clone()), xprivkey);
+        }
+
+        // New account inherits application of the closest account
+        let origin = self
             .all_accounts()
             .into_iter()
-            .map(|(path, acc)| {
-                let path_ref = path.as_ref();
-                if path_ref.len() < derivation_ref.len()
-                    && path_ref == &derivation_ref[..path_ref.len()]
-                {
-                    Some((&derivation[path_ref.len()..], acc))
-                } else {
-                    None
-                }
+            .filter(|(path, _)| is_parent(path, &derivation))
+            .max_by_key(|(path, _)| path.as_ref().len())
+            .map(|(_, account)| account)
+            .unwrap_or(&self.master_account);
+        let account = origin.derive_from(
+            &cache.xprivs[&(root, base)],
+            &steps[len - 1..],
+            name,
+            details,
+            assets,
+            cache.encryption_key,
+        )?;
+        Ok((derivation, account))
+    }
+
+    /// Selects the key to derive private key with `derivation` from: either
+    /// a key from the `cache` or one of the keyring accounts, which path is
+    /// a prefix of the `derivation`. The key with the lowest cost of the
+    /// decryption and remaining derivation steps is selected; watch-only
+    /// accounts can't be used.
+    fn derivation_parent(
+        &self,
+        derivation: &DerivationPath,
+        cache: &DerivationCache,
+    ) -> Result<DerivationPath, Error> {
+        let root = self.identifier();
+        let cached = cache
+            .xprivs
+            .keys()
+            .filter(|(id, path)| *id == root && is_parent(path, derivation))
+            .map(|(_, path)| (path.clone(), 0));
+        let accounts = self
+            .all_accounts()
+            .into_iter()
+            .filter(|(path, account)| {
+                !account.is_watch_only() && is_parent(path, derivation)
             })
-            .collect::<Vec<_>>();
-        sorted.sort_by(|item1, item2| {
-            if let (Some((path1, ..)), Some((path2, ..))) = (item1, item2) {
-                path1.len().cmp(&path2.len())
-            } else {
-                Ordering::Equal
-            }
-        });
-        let from = sorted.first().expect(
-            "We always have at least one element equal to the master key path",
-        ).expect("...and we know that this element is a parent item");
-
-        // Do a derivation starting from the found key account
-        let account =
-            from.1
-                .derive(from.0, name, details, assets, decryption_key)?;
+            .map(|(path, _)| (path, DECRYPTION_COST));
+        cached
+            .chain(accounts)
+            .map(|(path, cost)| {
+                let steps = &derivation.as_ref()[path.as_ref().len()..];
+                (cost + derivation_cost(steps), path)
+            })
+            .min_by(|(cost1, path1), (cost2, path2)| {
+                cost1
+                    .cmp(cost2)
+                    .then(path2.as_ref().len().cmp(&path1.as_ref().len()))
+            })
+            .map(|(_, path)| path)
+            .ok_or(Error::WatchOnly)
+    }
+
+    /// Creates watch-only sub-account with a given `derivation` path, which
+    /// extended public key is derived from the closest keyring account
+    /// without decryption of any private keys; see
+    /// [`Keyring::derive_account_neutered`]
+    pub fn create_account_neutered(
+        &mut self,
+        derivation: impl IntoDerivationPath,
+        name: impl ToString,
+        details: Option<impl ToString>,
+        assets: HashSet<AssetId>,
+    ) -> Result<&KeysAccount, Error> {
+        let (derivation, account) =
+            self.derive_account_neutered(derivation, name, details, assets)?;
+        self.add_account(derivation, account)
+    }
+
+    /// Derives new watch-only sub-account from the extended public key of
+    /// the closest account, which path is a prefix of the `derivation`. All
+    /// remaining derivation steps must be normal, otherwise the derivation
+    /// fails with [`Error::HardenedDerivation`]. Unlike
+    /// [`Keyring::derive_account`], works for watch-only keyrings as well.
+    pub fn derive_account_neutered(
+        &self,
+        derivation: impl IntoDerivationPath,
+        name: impl ToString,
+        details: Option<impl ToString>,
+        assets: HashSet<AssetId>,
+    ) -> Result<(DerivationPath, KeysAccount), Error> {
+        let derivation = derivation.into_derivation_path()?;
+        if self.derivation_paths().contains(&derivation) {
+            return Err(Error::DerivationAlreadyUsed);
+        }
+        let (path, parent) = self
+            .all_accounts()
+            .into_iter()
+            .filter(|(path, _)| is_parent(path, &derivation))
+            .max_by_key(|(path, _)| path.as_ref().len())
+            .unwrap_or((DerivationPath::master(), &self.master_account));
+        let account = parent.derive_neutered(
+            &derivation.as_ref()[path.as_ref().len()..],
+            name,
+            details,
+            assets,
+        )?;
+        Ok((derivation, account))
+    }
+
+    /// Adds previously derived sub-account under a given derivation path,
+    /// which must not be used by other keys of the keyring
+    pub fn add_account(
+        &mut self,
+        derivation: DerivationPath,
+        account: KeysAccount,
+    ) -> Result<&KeysAccount, Error> {
+        if self.derivation_paths().contains(&derivation) {
+            return Err(Error::DerivationAlreadyUsed);
+        }
         self.sub_accounts.insert(derivation.clone(), account);
         Ok(self.sub_accounts.get(&derivation).unwrap())
     }
@@ -599,6 +910,170 @@ impl Keyring {
         account.update(name, details, assets, update_mode)
     }
 
+    /// Returns mutable [`KeysAccount`] for a given `key_id`, or
+    /// [`Option::None`] if account does not exist under the current keyring
+    pub fn account_by_id_mut(
+        &mut self,
+        key_id: XpubIdentifier,
+    ) -> Option<&mut KeysAccount> {
+        if self.identifier() == key_id {
+            Some(&mut self.master_account)
+        } else {
+            self.sub_accounts
+                .values_mut()
+                .find(|account| account.identifier() == key_id)
+        }
+    }
+
+    /// Returns origin of the account with a given `key_id`: fingerprint of
+    /// the original master key and full derivation path from it, taking into
+    /// account the origin of the keyring master key, if known. Returns
+    /// [`Option::None`] if account does not exist under the current keyring
+    pub fn account_origin(&self, key_id: XpubIdentifier) -> Option<KeySource> {
+        let (fingerprint, base) = self
+            .key_source
+            .clone()
+            .unwrap_or_else(|| (self.fingerprint(), DerivationPath::master()));
+        if self.identifier() == key_id {
+            Some((fingerprint, base))
+        } else {
+            self.sub_accounts
+                .iter()
+                .find(|(_, account)| account.identifier() == key_id)
+                .map(|(path, _)| (fingerprint, base.extend(path)))
+        }
+    }
+
+    /// Creates watch-only keyring from the master watch-only account
+    /// (see [`KeysAccount::watch_only`]) and an optional information on the
+    /// origin of its extended public key. The keyring chain is detected from
+    /// the network of the key.
+    pub fn watch_only(
+        master_account: KeysAccount,
+        key_source: Option<KeySource>,
+    ) -> Self {
+        let stored_chain = Some(network_chain(master_account.network()));
+        Self {
+            master_account,
+            key_source,
+            sub_accounts: Default::default(),
+            stored_chain,
+        }
+    }
+
+    /// Reconstructs keyring from its parts, as they are kept by storage
+    /// drivers which do not store keyrings as a whole
+    pub(crate) fn with_accounts(
+        master_account: KeysAccount,
+        key_source: Option<KeySource>,
+        sub_accounts: BTreeMap<DerivationPath, KeysAccount>,
+        chain: Option<Chain>,
+    ) -> Self {
+        Self {
+            master_account,
+            key_source,
+            sub_accounts,
+            stored_chain: chain,
+        }
+    }
+
+    /// Adds watch-only sub-account under a given derivation path, which must
+    /// not be used by other sub-accounts of the keyring. The caller is
+    /// responsible for checking that the account extended public key is
+    /// derivable from the master key with the path.
+    pub fn add_watch_only(
+        &mut self,
+        derivation: DerivationPath,
+        account: KeysAccount,
+    ) -> Result<(), Error> {
+        if self.derivation_paths().contains(&derivation) {
+            return Err(Error::DerivationAlreadyUsed);
+        }
+        self.sub_accounts.insert(derivation, account);
+        Ok(())
+    }
+
+    /// Detects whether a given derivation path is already used by some of
+    /// the keyring keys
+    pub fn is_derivation_used(&self, derivation: &DerivationPath) -> bool {
+        self.derivation_paths().contains(derivation)
+    }
+
+    /// Returns whether the keyring was archived (soft-deleted)
+    pub fn is_archived(&self) -> bool {
+        self.master_account.archived
+    }
+
+    /// Archives (soft-deletes) the keyring; the data are kept in the vault
+    /// storage, but the keyring is not available for any operations
+    pub fn archive(&mut self) {
+        self.master_account.archived = true;
+    }
+
+    /// Shreds encrypted private keys of the master account and all
+    /// sub-accounts, including archived ones; see [`KeysAccount::shred`].
+    /// The keyring can't be used for signing after this operation and must
+    /// be removed from the vault.
+    pub fn shred(&mut self) -> Vec<Shredded> {
+        let mut shredded: Vec<_> =
+            self.master_account.shred().into_iter().collect();
+        shredded.extend(
+            self.sub_accounts
+                .values_mut()
+                .filter_map(KeysAccount::shred),
+        );
+        shredded
+    }
+
+    /// Shreds encrypted private key of the sub-account with a given `key_id`
+    /// before it gets purged; see [`KeysAccount::shred`].
+    ///
+    /// Returns [`Error::MasterAccount`] if the `key_id` corresponds to the
+    /// master account and [`Error::NotFound`] if there is no sub-account with
+    /// the provided `key_id`
+    pub fn shred_account(
+        &mut self,
+        key_id: XpubIdentifier,
+    ) -> Result<Option<Shredded>, Error> {
+        if self.identifier() == key_id {
+            return Err(Error::MasterAccount);
+        }
+        self.sub_accounts
+            .values_mut()
+            .find(|account| account.identifier() == key_id)
+            .map(KeysAccount::shred)
+            .ok_or(Error::NotFound)
+    }
+
+    /// Deletes sub-account with a given `key_id`. If `purge` is set, the
+    /// account is removed from the keyring; otherwise it is archived
+    /// (soft-deleted) and its data are kept in the vault storage.
+    ///
+    /// Returns [`Error::MasterAccount`] if the `key_id` corresponds to the
+    /// master account and [`Error::NotFound`] if there is no sub-account with
+    /// the provided `key_id`
+    pub fn delete_account(
+        &mut self,
+        key_id: XpubIdentifier,
+        purge: bool,
+    ) -> Result<(), Error> {
+        if self.identifier() == key_id {
+            return Err(Error::MasterAccount);
+        }
+        let derivation = self
+            .sub_accounts
+            .iter()
+            .find(|(_, account)| account.identifier() == key_id)
+            .map(|(path, _)| path.clone())
+            .ok_or(Error::NotFound)?;
+        if purge {
+            self.sub_accounts.remove(&derivation);
+        } else if let Some(account) = self.sub_accounts.get_mut(&derivation) {
+            account.archived = true;
+        }
+        Ok(())
+    }
+
     /// Returns all accounts, i.e. master key account plus all subaccounts
     /// joined into a single structure
     fn all_accounts(&self) -> BTreeMap<DerivationPath, &KeysAccount> {
@@ -618,24 +1093,179 @@ impl Keyring {
     }
 }
 
+/// Extended private keys decrypted or derived during a batch of sub-account
+/// derivations, so that each account key is decrypted only once per batch.
+/// Keys are kept under the keyring identifier and derivation path and are
+/// wiped together with the decryption key when the cache is dropped.
+pub struct DerivationCache {
+    decryption_key: secp256k1::SecretKey,
+    encryption_key: secp256k1::PublicKey,
+    xprivs: BTreeMap<(XpubIdentifier, DerivationPath), SecretXpriv>,
+    decryptions: usize,
+}
+
+impl DerivationCache {
+    /// Creates empty cache holding a copy of `decryption_key`; the value of
+    /// the original key is instantly reset
+    pub fn with(decryption_key: &mut secp256k1::SecretKey) -> Self {
+        let cache = Self {
+            decryption_key: *decryption_key,
+            encryption_key: secp256k1::PublicKey::from_secret_key(
+                &crate::SECP256K1,
+                decryption_key,
+            ),
+            xprivs: Default::default(),
+            decryptions: 0,
+        };
+        wipe_key(decryption_key);
+        cache
+    }
+
+    /// Number of account keys decrypted with the cache
+    pub fn decryptions(&self) -> usize {
+        self.decryptions
+    }
+}
+
+impl Drop for DerivationCache {
+    fn drop(&mut self) {
+        wipe_key(&mut self.decryption_key);
+    }
+}
+
+/// Signing keys used during PSBT signing. Besides the signing key itself,
+/// the cache keeps the key at the end of the hardened part of its derivation
+/// path, so signing with other keys of the same account requires neither
+/// decryption of the keyring master key nor hardened derivation steps. When
+/// the cache is full, the least recently used key is evicted; keys are wiped
+/// when evicted, cleared or dropped together with the cache.
+pub struct SigningCache {
+    capacity: usize,
+    tick: u64,
+    xprivs: BTreeMap<(XpubIdentifier, DerivationPath), (u64, SecretXpriv)>,
+    decryptions: usize,
+}
+
+impl SigningCache {
+    /// Creates empty cache keeping up to `capacity` keys
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            tick: 0,
+            xprivs: Default::default(),
+            decryptions: 0,
+        }
+    }
+
+    /// Creates empty cache which is not limited in size. Should be used for a
+    /// single signing operation only and dropped right after it.
+    pub fn unbounded() -> Self {
+        Self::with_capacity(usize::MAX)
+    }
+
+    /// Maximal number of keys kept by the cache
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of keys currently kept by the cache
+    pub fn len(&self) -> usize {
+        self.xprivs.len()
+    }
+
+    /// Returns whether the cache keeps no keys
+    pub fn is_empty(&self) -> bool {
+        self.xprivs.is_empty()
+    }
+
+    /// Number of account keys decrypted with the cache
+    pub fn decryptions(&self) -> usize {
+        self.decryptions
+    }
+
+    /// Returns private key derived with `derivation` from the `account` key,
+    /// decrypting the account key with a copy of the `decryption_key` if no
+    /// key along the hardened part of the path is cached
+    pub fn signing_key(
+        &mut self,
+        account: &KeysAccount,
+        derivation: &DerivationPath,
+        decryption_key: &secp256k1::SecretKey,
+    ) -> Result<&SecretXpriv, Error> {
+        let id = account.identifier();
+        let target = (id, derivation.clone());
+        if !self.xprivs.contains_key(&target) {
+            let steps = derivation.as_ref();
+            let hardened = steps
+                .iter()
+                .rposition(ChildNumber::is_hardened)
+                .map(|pos| pos + 1)
+                .unwrap_or(0);
+            let base = (id, DerivationPath::from(&steps[..hardened]));
+            if !self.xprivs.contains_key(&base) {
+                let mut seckey = *decryption_key;
+                let xprivkey =
+                    account.xprivkey(&mut seckey)?.derive_priv(&base.1)?;
+                self.decryptions += 1;
+                self.insert(base.clone(), xprivkey);
+            }
+            if hardened < steps.len() {
+                let xprivkey =
+                    self.touch(&base).derive_priv(&&steps[hardened..])?;
+                self.insert(target.clone(), xprivkey);
+            }
+        }
+        Ok(self.touch(&target))
+    }
+
+    /// Wipes all cached keys, returning their number
+    pub fn clear(&mut self) -> usize {
+        let len = self.xprivs.len();
+        self.xprivs.clear();
+        len
+    }
+
+    fn touch(
+        &mut self,
+        key: &(XpubIdentifier, DerivationPath),
+    ) -> &SecretXpriv {
+        self.tick += 1;
+        let entry = self
+            .xprivs
+            .get_mut(key)
+            .expect("signing cache key is checked before use");
+        entry.0 = self.tick;
+        &entry.1
+    }
+
+    fn insert(
+        &mut self,
+        key: (XpubIdentifier, DerivationPath),
+        xprivkey: SecretXpriv,
+    ) {
+        if self.xprivs.len() >= self.capacity {
+            let lru = self
+                .xprivs
+                .iter()
+                .min_by_key(|(_, (used, _))| *used)
+                .map(|(key, _)| key.clone());
+            if let Some(lru) = lru {
+                self.xprivs.remove(&lru);
+            }
+        }
+        self.tick += 1;
+        self.xprivs.insert(key, (self.tick, xprivkey));
+    }
+}
+
 /// Key account is a structure holding information necessary to create a
 /// transaction signature. It represents an abstraction of signature domain:
 /// a specific set of use or application cases for a given area; like signatures
 /// involved in a corporate account, private account, relations with particular
 /// customer or a service provider etc.
 #[derive(
-    Getters,
-    Clone,
-    PartialEq,
-    Eq,
-    Debug,
-    Display,
-    Serialize,
-    Deserialize,
-    StrictEncode,
-    StrictDecode,
+    Getters, Clone, PartialEq, Eq, Debug, Display, Serialize, Deserialize,
 )]
-#[strict_encoding_crate(lnpbp::strict_encoding)]
 #[serde(crate = "serde_crate")]
 #[display("{name} ({xpubkey})")]
 pub struct KeysAccount {
@@ -647,6 +1277,38 @@ pub struct KeysAccount {
 
     assets: HashSet<AssetId>,
 
+    #[serde(default)]
+    application: Option<KeyApplication>,
+
+    #[serde(default)]
+    archived: bool,
+
+    #[serde(default)]
+    lifecycle: Lifecycle,
+
+    #[serde(default)]
+    branches: Branches,
+
+    #[serde(default)]
+    policy: SigningPolicy,
+
+    /// Other names under which the account key was imported
+    #[serde(default)]
+    aliases: Vec<String>,
+
+    /// Key/value metadata of the account
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+
+    /// Multisig groups in which the account is the first of the vault
+    /// cosigners
+    #[serde(default)]
+    multisig: Vec<MultisigGroup>,
+
+    /// BIP-47 payment codes of the counterparties under their names
+    #[serde(default)]
+    payment_codes: BTreeMap<String, PaymentCode>,
+
     #[serde(serialize_with = "to_hex", deserialize_with = "from_hex")]
     encrypted: Vec<u8>,
 
@@ -664,61 +1326,50 @@ impl KeysAccount {
         details: impl ToString,
         assets: HashSet<AssetId>,
         chain: &Chain,
-        _application: KeyApplication,
+        application: KeyApplication,
         encryption_key: secp256k1::PublicKey,
     ) -> Result<Self, Error> {
         debug!("Generating seed");
-        let mut random = [0u8; 32];
-        thread_rng().fill_bytes(&mut random);
-        let mut seed = random;
-        // Clearing random value right after the copy takes place
-        thread_rng().fill_bytes(&mut random);
+        let mut seed = Zeroizing::new([0u8; 32]);
+        thread_rng().fill_bytes(&mut *seed);
 
         trace!("Creating master extended private key from the seed");
         let xprivkey = ExtendedPrivKey::new_master(
             bitcoin::Network::try_from(chain)
                 .unwrap_or(bitcoin::Network::Bitcoin),
-            &seed,
-        );
-        // Wiping out seed
-        thread_rng().fill_bytes(&mut seed);
-        let mut xprivkey = xprivkey?;
+            &*seed,
+        )?;
 
+        Self::from_xpriv(
+            name,
+            details,
+            assets,
+            Some(application),
+            SecretXpriv::from(xprivkey),
+            encryption_key,
+        )
+    }
+
+    /// Creates keys account from an existing extended private key, which is
+    /// encrypted with `encryption_key` and wiped out from memory. The
+    /// account extended public key keeps depth, parent fingerprint and child
+    /// number of the private key.
+    pub(self) fn from_xpriv(
+        name: impl ToString,
+        details: impl ToString,
+        assets: HashSet<AssetId>,
+        application: Option<KeyApplication>,
+        xprivkey: SecretXpriv,
+        encryption_key: secp256k1::PublicKey,
+    ) -> Result<Self, Error> {
         trace!("Creating master extended public key from the xpriv");
         let xpubkey =
             ExtendedPubKey::from_private(&crate::SECP256K1, &xprivkey);
         // TODO: Uncomment after key resolves will get into rust-bitcoin
         //        .ok_or(Error::ResolverFailure)?;
 
-        trace!("Creating blinding and unblinding keys for Elgamal encryption");
-        thread_rng().fill_bytes(&mut random);
-        let mut blinding =
-            secp256k1::SecretKey::from_slice(&random).or_else(|err| {
-                // Clearing private key before unwrapping
-                let sk = &mut xprivkey.private_key.key;
-                *sk = secp256k1::key::ONE_KEY;
-                Err(err)
-            })?;
-        // Wiping out blinding source
-        thread_rng().fill_bytes(&mut random);
-
-        // Creating unblinding key
-        let unblinding =
-            secp256k1::PublicKey::from_secret_key(&crate::SECP256K1, &blinding);
-
-        trace!("Encrypting private key");
-        let mut encoded = xprivkey.encode();
-        let encrypted =
-            elgamal::encrypt(&encoded, encryption_key, &mut blinding);
-        // Clearing key encoding data
-        encoded.copy_from_slice(&[0u8; 78]);
-        let encrypted = encrypted?;
-        // Instantly wiping out xpriv:
-        thread_rng().fill_bytes(&mut random);
-        let _ = xprivkey.private_key.key.add_assign(&random).map_err(|_| {
-            *(&mut xprivkey.private_key.key) = secp256k1::key::ONE_KEY
-        });
-        trace!("Seed and keys are successfully generated and memory data were cleared");
+        let (encrypted, unblinding) = encrypt(&xprivkey, encryption_key)?;
+        trace!("Private key is encrypted and memory data were cleared");
 
         trace!(
             "Encoded length of the private key is 78, encrypted - {} bytes",
@@ -730,6 +1381,15 @@ impl KeysAccount {
             name: name.to_string(),
             details: details.to_string(),
             assets,
+            application,
+            archived: false,
+            lifecycle: Lifecycle::Active,
+            branches: Branches::default(),
+            policy: SigningPolicy::default(),
+            aliases: vec![],
+            labels: BTreeMap::new(),
+            multisig: vec![],
+            payment_codes: BTreeMap::new(),
             encrypted,
             unblinding,
         })
@@ -750,57 +1410,160 @@ impl KeysAccount {
     ) -> Result<KeysAccount, Error> {
         let derivation = derivation.into_derivation_path()?;
 
-        let mut random = [0u8; 32];
-
         // Deriving encryption key from the decryption key
         let encryption_key = secp256k1::PublicKey::from_secret_key(
             &crate::SECP256K1,
             decryption_key,
         );
 
-        let mut master_xpriv = self.xprivkey(&mut decryption_key)?;
-        let master_xpub =
-            ExtendedPubKey::from_private(&crate::SECP256K1, &master_xpriv);
+        let master_xpriv = self.unlock(&mut decryption_key)?;
+        self.derive_from(
+            &master_xpriv,
+            derivation.as_ref(),
+            name,
+            details,
+            assets,
+            encryption_key,
+        )
+    }
+
+    /// Derives a new watch-only subaccount with a given relative
+    /// `derivation` path from the account extended public key, so the
+    /// account private key is not decrypted. The subaccount inherits the
+    /// account application. Fails with [`Error::HardenedDerivation`] if the
+    /// path contains hardened segments.
+    pub fn derive_neutered(
+        &self,
+        derivation: &[ChildNumber],
+        name: impl ToString,
+        details: Option<impl ToString>,
+        assets: HashSet<AssetId>,
+    ) -> Result<KeysAccount, Error> {
+        if derivation.iter().any(|step| step.is_hardened()) {
+            return Err(Error::HardenedDerivation);
+        }
+        let xpubkey =
+            self.xpubkey.derive_pub(&crate::SECP256K1, &derivation)?;
+        let mut account = KeysAccount::watch_only(
+            name,
+            details.map(|s| s.to_string()).unwrap_or_default(),
+            xpubkey,
+            self.application,
+        );
+        account.assets = assets;
+        Ok(account)
+    }
+
+    /// Decrypts extended private key of the account and checks it against
+    /// the account extended public key
+    fn unlock(
+        &self,
+        decryption_key: &mut secp256k1::SecretKey,
+    ) -> Result<SecretXpriv, Error> {
+        let xprivkey = self.xprivkey(decryption_key)?;
+        let xpubkey =
+            ExtendedPubKey::from_private(&crate::SECP256K1, &xprivkey);
         // TODO: Uncomment after key resolves will get into rust-bitcoin
         //  .ok_or(Error::ResolverFailure)?;
-        if master_xpub != self.xpubkey {
-            // Instantly wiping out xpriv:
-            master_xpriv.private_key.key.add_assign(&random)?;
+        if xpubkey != self.xpubkey {
             return Err(Error::SecretKeyCorrupted);
         }
+        Ok(xprivkey)
+    }
 
-        // Deriving new secret key
-        let xprivkey =
-            master_xpriv.derive_priv(&crate::SECP256K1, &derivation)?;
+    /// Creates a subaccount from the key derived with `derivation` from the
+    /// unlocked `parent` key, which is either the account key or a key
+    /// derived from it. The subaccount key is encrypted with
+    /// `encryption_key`.
+    fn derive_from(
+        &self,
+        parent: &SecretXpriv,
+        derivation: &[ChildNumber],
+        name: impl ToString,
+        details: Option<impl ToString>,
+        assets: HashSet<AssetId>,
+        encryption_key: secp256k1::PublicKey,
+    ) -> Result<KeysAccount, Error> {
+        // Deriving new secret key; the private key is wiped when dropped
+        let xprivkey = parent.derive_priv(&derivation)?;
         let xpubkey =
             ExtendedPubKey::from_private(&crate::SECP256K1, &xprivkey);
         // TODO: Uncomment after key resolves will get into rust-bitcoin
         //  .ok_or(Error::ResolverFailure)?;
 
-        // Creating blinding and unblinding keys; doing the encryption
-        thread_rng().fill_bytes(&mut random);
-        let mut blinding = secp256k1::SecretKey::from_slice(&random)?;
-        let unblinding =
-            secp256k1::PublicKey::from_secret_key(&crate::SECP256K1, &blinding);
-        let encrypted = elgamal::encrypt(
-            &xprivkey.encode(),
-            encryption_key,
-            &mut blinding,
-        )?;
-        // Instantly wiping out xpriv and blinding data
-        thread_rng().fill_bytes(&mut random);
-        master_xpriv.private_key.key.add_assign(&random)?;
+        let (encrypted, unblinding) = encrypt(&xprivkey, encryption_key)?;
 
         Ok(Self {
             xpubkey,
             name: name.to_string(),
             details: details.map(|s| s.to_string()).unwrap_or_default(),
             assets,
+            application: self.application,
+            archived: false,
+            lifecycle: Lifecycle::Active,
+            branches: Branches::default(),
+            policy: SigningPolicy::default(),
+            aliases: vec![],
+            labels: BTreeMap::new(),
+            multisig: vec![],
+            payment_codes: BTreeMap::new(),
             encrypted,
             unblinding,
         })
     }
 
+    /// Creates watch-only account for an extended public key imported from
+    /// some external source. The account has no encrypted private key data,
+    /// so all operations requiring private key fail with
+    /// [`Error::WatchOnly`].
+    pub fn watch_only(
+        name: impl ToString,
+        details: impl ToString,
+        xpubkey: ExtendedPubKey,
+        application: Option<KeyApplication>,
+    ) -> Self {
+        Self {
+            xpubkey,
+            name: name.to_string(),
+            details: details.to_string(),
+            assets: Default::default(),
+            application,
+            archived: false,
+            lifecycle: Lifecycle::Active,
+            branches: Branches::default(),
+            policy: SigningPolicy::default(),
+            aliases: vec![],
+            labels: BTreeMap::new(),
+            multisig: vec![],
+            payment_codes: BTreeMap::new(),
+            encrypted: vec![],
+            // Not used for watch-only accounts since there is no encrypted
+            // data
+            unblinding: xpubkey.public_key.key,
+        }
+    }
+
+    /// Overwrites encrypted private key data with random bytes of the same
+    /// length, so the stored data do not change their size. Returns
+    /// [`Option::None`] for watch-only accounts, which have nothing to shred.
+    pub fn shred(&mut self) -> Option<Shredded> {
+        if self.is_watch_only() {
+            return None;
+        }
+        let digest = sha256::Hash::hash(&self.encrypted);
+        thread_rng().fill_bytes(&mut self.encrypted);
+        Some(Shredded {
+            key_id: self.identifier(),
+            digest,
+        })
+    }
+
+    /// Returns whether the account is watch-only, i.e. does not have an
+    /// encrypted private key
+    pub fn is_watch_only(&self) -> bool {
+        self.encrypted.is_empty()
+    }
+
     /// Returns extended public key identifier from the master account
     pub fn identifier(&self) -> XpubIdentifier {
         self.xpubkey.identifier()
@@ -811,13 +1574,154 @@ impl KeysAccount {
         self.xpubkey.fingerprint()
     }
 
+    /// Returns network of the account extended keys
+    pub fn network(&self) -> bitcoin::Network {
+        self.xpubkey.network
+    }
+
+    /// Checks whether the current account lifecycle state allows a given
+    /// `operation`, returning [`Error::LifecycleRestriction`] otherwise
+    pub fn check_lifecycle(&self, operation: Operation) -> Result<(), Error> {
+        if !self.lifecycle.allows(operation) {
+            return Err(Error::LifecycleRestriction(self.lifecycle, operation));
+        }
+        Ok(())
+    }
+
+    /// Changes lifecycle state of the account, returning
+    /// [`Error::LifecycleTransition`] if the transition is not allowed
+    pub fn transit(&mut self, next: Lifecycle) -> Result<(), Error> {
+        if !self.lifecycle.can_transit(next) {
+            return Err(Error::LifecycleTransition(self.lifecycle, next));
+        }
+        debug!(
+            "Changing lifecycle state of {} from {} to {}",
+            self.identifier(),
+            self.lifecycle,
+            next
+        );
+        self.lifecycle = next;
+        Ok(())
+    }
+
+    /// Sets key application used for the account addresses. Intended for
+    /// newly derived accounts, which inherit application from the master
+    /// account.
+    pub fn set_application(&mut self, application: KeyApplication) {
+        self.application = Some(application);
+    }
+
+    /// Registers another name of the account; returns `false` if the name
+    /// is already known
+    pub fn add_alias(&mut self, alias: impl ToString) -> bool {
+        let alias = alias.to_string();
+        if alias == self.name || self.aliases.contains(&alias) {
+            return false;
+        }
+        self.aliases.push(alias);
+        true
+    }
+
+    /// Sets label with a given `key` to the `value`, or removes the label if
+    /// the `value` is `None`; returns `false` if the labels are unchanged
+    pub fn set_label(
+        &mut self,
+        key: impl ToString,
+        value: Option<impl ToString>,
+    ) -> Result<bool, Error> {
+        let key = key.to_string();
+        if key.is_empty()
+            || key.contains(|c: char| c == '=' || c.is_whitespace())
+        {
+            return Err(Error::LabelKey(key));
+        }
+        Ok(match value {
+            Some(value) => {
+                let value = value.to_string();
+                self.labels.insert(key, value.clone()) != Some(value)
+            }
+            None => self.labels.remove(&key).is_some(),
+        })
+    }
+
+    /// Registers multisig group in which the account is a cosigner; returns
+    /// `false` if the group is already registered with the account
+    pub fn add_multisig(&mut self, group: MultisigGroup) -> bool {
+        if self.multisig.iter().any(|known| known.id() == group.id()) {
+            return false;
+        }
+        self.multisig.push(group);
+        true
+    }
+
+    /// Saves payment code of a BIP-47 counterparty under a given `name`,
+    /// replacing previously saved code; returns `false` if the code is
+    /// already saved under this name
+    pub fn save_payment_code(
+        &mut self,
+        name: impl ToString,
+        code: PaymentCode,
+    ) -> Result<bool, Error> {
+        let name = name.to_string();
+        if name.trim().is_empty() {
+            return Err(Error::ContactName(name));
+        }
+        Ok(self.payment_codes.insert(name, code) != Some(code))
+    }
+
+    /// Changes layout of the account derivation branches
+    pub fn set_branches(&mut self, branches: Branches) -> Result<(), Error> {
+        if !branches.is_valid() {
+            return Err(Error::BranchLayout(branches));
+        }
+        debug!(
+            "Changing branch layout of {} to {}",
+            self.identifier(),
+            branches
+        );
+        self.branches = branches;
+        Ok(())
+    }
+
+    /// Changes signing policy of the account
+    pub fn set_policy(&mut self, policy: SigningPolicy) -> Result<(), Error> {
+        if !policy.is_valid() {
+            return Err(Error::SigningPolicy(policy));
+        }
+        debug!(
+            "Changing signing policy of {} to {}",
+            self.identifier(),
+            policy
+        );
+        self.policy = policy;
+        Ok(())
+    }
+
+    /// Checks that the provided `decryption_key` is able to decrypt the
+    /// account private key, clearing the decryption key and decrypted data
+    /// after. Returns [`Error::SecretKeyCorrupted`] if the decrypted key does
+    /// not match the account public key. Watch-only accounts have no
+    /// encrypted data, so any key is accepted for them.
+    pub fn verify_decryption_key(
+        &self,
+        decryption_key: &mut secp256k1::SecretKey,
+    ) -> Result<(), Error> {
+        if self.is_watch_only() {
+            return Ok(());
+        }
+        self.unlock(decryption_key).map(|_| ())
+    }
+
     /// Returns extended private key by decrypting it's data using
-    /// `decryption_key`, clearing it's content after
+    /// `decryption_key`, clearing it's content after. The returned key is
+    /// wiped from the memory when dropped.
     pub fn xprivkey(
         &self,
         decryption_key: &mut secp256k1::SecretKey,
-    ) -> Result<ExtendedPrivKey, Error> {
-        let mut random = [0u8; 32];
+    ) -> Result<SecretXpriv, Error> {
+        if self.is_watch_only() {
+            return Err(Error::WatchOnly);
+        }
 
         debug!("Unlocking extended private key");
         trace!("Decrypting private key & clearing decryption key");
@@ -825,26 +1729,23 @@ impl KeysAccount {
             elgamal::decrypt(&self.encrypted, decryption_key, self.unblinding);
 
         trace!("Instantly wiping our decryption key");
-        thread_rng().fill_bytes(&mut random);
-        let _ = decryption_key
-            .add_assign(&random)
-            .map_err(|_| *decryption_key = secp256k1::key::ONE_KEY);
+        wipe_key(decryption_key);
 
-        // Now it's safe to unwrap
-        let mut secret_data = secret_data?;
+        // Decrypted data are zeroed when dropped
+        let secret_data = SecretBuffer::from(secret_data?);
         trace!(
             "Decrypted {} bytes our of {} bytes",
             secret_data.len(),
             self.encrypted.len()
         );
+        if secret_data.len() < 78 {
+            return Err(Error::SecretKeyCorrupted);
+        }
 
         trace!("Decoding extended private key");
-        let xprivkey = ExtendedPrivKey::decode(&secret_data[..78]);
-
-        trace!("Wiping out secret data");
-        thread_rng().fill_bytes(&mut secret_data);
-
-        Ok(xprivkey?)
+        Ok(SecretXpriv::from(ExtendedPrivKey::decode(
+            &secret_data[..78],
+        )?))
     }
 
     /// Updates information inside keys account. For information on the
@@ -862,12 +1763,6 @@ impl KeysAccount {
 
         let mut count = 0;
 
-        if let Some(name) = name {
-            self.name = name.to_string();
-        }
-        if let Some(details) = details {
-            self.details = details.to_string();
-        }
         match (assets, update_mode) {
             (Some(assets), UpdateMode::Add) => {
                 count = assets.len();
@@ -889,10 +1784,10 @@ impl KeysAccount {
                     .difference(&self.assets)
                     .cloned()
                     .collect::<HashSet<AssetId>>();
-                if diff.is_empty() {
+                if !diff.is_empty() {
                     return Err(Error::AssetIds(diff));
                 }
-                count = self.assets.len() - assets.len();
+                count = assets.len();
                 self.assets =
                     self.assets.difference(&assets).cloned().collect();
             }
@@ -901,6 +1796,15 @@ impl KeysAccount {
             }
         }
 
+        // Name and details are updated only after the assets, so a failed
+        // asset removal leaves the account intact
+        if let Some(name) = name {
+            self.name = name.to_string();
+        }
+        if let Some(details) = details {
+            self.details = details.to_string();
+        }
+
         Ok(count)
     }
 
@@ -915,22 +1819,56 @@ impl KeysAccount {
         H: bitcoin::hashes::Hash,
     {
         trace!("Decrypting private key");
-        let mut xprivkey = self.xprivkey(&mut decryption_key)?;
+        let xprivkey = self.xprivkey(&mut decryption_key)?;
 
         trace!("Signing {}", digest);
         let signature = crate::SECP256K1.sign(
             &secp256k1::Message::from_slice(&digest[..])?,
-            &xprivkey.private_key.key,
+            xprivkey.secret_key(),
         );
 
-        trace!("Wiping private key from memory");
-        let mut random = [0u8; 32];
-        thread_rng().fill_bytes(&mut random);
-        xprivkey.private_key.key.add_assign(&random)?;
-
         debug!("Signature for message {} created", digest);
         Ok(signature)
     }
+
+    /// Produces recoverable signature for a text `message` in the Bitcoin
+    /// Signed Message format; see [`crate::signed_message`]
+    pub fn sign_message(
+        &self,
+        message: &str,
+        mut decryption_key: &mut secp256k1::SecretKey,
+    ) -> Result<RecoverableSignature, Error> {
+        trace!("Decrypting private key");
+        let xprivkey = self.xprivkey(&mut decryption_key)?;
+
+        let signature = signed_message::sign(message, xprivkey.secret_key());
+
+        debug!("Signature for text message created");
+        Ok(signature)
+    }
+}
+
+/// Encrypts extended private key with `encryption_key` using a newly
+/// generated blinding key. Returns encrypted data together with the
+/// unblinding key required for the decryption.
+fn encrypt(
+    xprivkey: &SecretXpriv,
+    encryption_key: secp256k1::PublicKey,
+) -> Result<(Vec<u8>, secp256k1::PublicKey), Error> {
+    trace!("Creating blinding and unblinding keys for Elgamal encryption");
+    let mut random = Zeroizing::new([0u8; 32]);
+    thread_rng().fill_bytes(&mut *random);
+    let mut blinding = secp256k1::SecretKey::from_slice(&*random)?;
+    let unblinding =
+        secp256k1::PublicKey::from_secret_key(&crate::SECP256K1, &blinding);
+
+    trace!("Encrypting private key");
+    let mut encoded = xprivkey.encode();
+    let encrypted =
+        elgamal::encrypt(&encoded[..], encryption_key, &mut blinding);
+    encoded[..].zeroize();
+    wipe_key(&mut blinding);
+    Ok((encrypted?, unblinding))
 }
 
 /// Serializes `buffer` to a lowercase hex string.
@@ -952,3 +1890,171 @@ where
         Vec::from_hex(&string).map_err(|err| Error::custom(err.to_string()))
     })
 }
+
+/// Version of the keyring and account extension records. Strict encoding of
+/// [`Keyring`] and [`KeysAccount`] starts with the fields of the original
+/// vault layout, followed by the extension record: the version byte and the
+/// fields added since then. Fields added later are appended to the record
+/// under a new version, so the data written by the previous versions are
+/// still decoded. Vault data written before the records were introduced are
+/// decoded with [`LegacyKeyring`].
+pub const EXTENSION_VERSION: u8 = 1;
+
+/// Reads version of the extension record, rejecting versions written by
+/// newer releases
+fn decode_extension_version(
+    d: impl io::Read,
+) -> Result<u8, strict_encoding::Error> {
+    match u8::strict_decode(d)? {
+        version @ 1..=EXTENSION_VERSION => Ok(version),
+        version => Err(strict_encoding::Error::DataIntegrityError(format!(
+            "vault extension record version {} is not supported; the vault \
+             was written by a newer release",
+            version
+        ))),
+    }
+}
+
+impl StrictEncode for Keyring {
+    fn strict_encode<E: io::Write>(
+        &self,
+        mut e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        Ok(self.master_account.strict_encode(&mut e)?
+            + self.key_source.strict_encode(&mut e)?
+            + self.sub_accounts.strict_encode(&mut e)?
+            + EXTENSION_VERSION.strict_encode(&mut e)?
+            + self.stored_chain.strict_encode(&mut e)?)
+    }
+}
+
+impl StrictDecode for Keyring {
+    fn strict_decode<D: io::Read>(
+        mut d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        let master_account = KeysAccount::strict_decode(&mut d)?;
+        let key_source = StrictDecode::strict_decode(&mut d)?;
+        let sub_accounts = StrictDecode::strict_decode(&mut d)?;
+        decode_extension_version(&mut d)?;
+        Ok(Keyring {
+            master_account,
+            key_source,
+            sub_accounts,
+            stored_chain: StrictDecode::strict_decode(&mut d)?,
+        })
+    }
+}
+
+impl StrictEncode for KeysAccount {
+    fn strict_encode<E: io::Write>(
+        &self,
+        mut e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        Ok(self.xpubkey.strict_encode(&mut e)?
+            + self.name.strict_encode(&mut e)?
+            + self.details.strict_encode(&mut e)?
+            + self.assets.strict_encode(&mut e)?
+            + self.encrypted.strict_encode(&mut e)?
+            + self.unblinding.strict_encode(&mut e)?
+            + EXTENSION_VERSION.strict_encode(&mut e)?
+            + self.application.strict_encode(&mut e)?
+            + self.archived.strict_encode(&mut e)?
+            + self.lifecycle.strict_encode(&mut e)?
+            + self.branches.strict_encode(&mut e)?
+            + self.policy.strict_encode(&mut e)?
+            + self.aliases.strict_encode(&mut e)?
+            + self.labels.strict_encode(&mut e)?
+            + self.multisig.strict_encode(&mut e)?
+            + self.payment_codes.strict_encode(&mut e)?)
+    }
+}
+
+impl StrictDecode for KeysAccount {
+    fn strict_decode<D: io::Read>(
+        mut d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        let mut account = KeysAccount::strict_decode_legacy(&mut d)?;
+        decode_extension_version(&mut d)?;
+        account.application = StrictDecode::strict_decode(&mut d)?;
+        account.archived = StrictDecode::strict_decode(&mut d)?;
+        account.lifecycle = StrictDecode::strict_decode(&mut d)?;
+        account.branches = StrictDecode::strict_decode(&mut d)?;
+        account.policy = StrictDecode::strict_decode(&mut d)?;
+        account.aliases = StrictDecode::strict_decode(&mut d)?;
+        account.labels = StrictDecode::strict_decode(&mut d)?;
+        account.multisig = StrictDecode::strict_decode(&mut d)?;
+        account.payment_codes = StrictDecode::strict_decode(&mut d)?;
+        Ok(account)
+    }
+}
+
+impl KeysAccount {
+    /// Decodes fields of the original vault layout, setting the fields of
+    /// the extension record to their defaults
+    fn strict_decode_legacy(
+        mut d: impl io::Read,
+    ) -> Result<Self, strict_encoding::Error> {
+        Ok(KeysAccount {
+            xpubkey: StrictDecode::strict_decode(&mut d)?,
+            name: StrictDecode::strict_decode(&mut d)?,
+            details: StrictDecode::strict_decode(&mut d)?,
+            assets: StrictDecode::strict_decode(&mut d)?,
+            encrypted: StrictDecode::strict_decode(&mut d)?,
+            unblinding: StrictDecode::strict_decode(&mut d)?,
+            application: None,
+            archived: false,
+            lifecycle: Lifecycle::default(),
+            branches: Branches::default(),
+            policy: SigningPolicy::default(),
+            aliases: vec![],
+            labels: BTreeMap::new(),
+            multisig: vec![],
+            payment_codes: BTreeMap::new(),
+        })
+    }
+}
+
+/// Keyring decoded from the strict-encoded vault data written before the
+/// extension records were introduced: vault files without header or with
+/// header version 1. Chain of such keyrings is detected from the master key
+/// network and the other fields added since then get their defaults.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LegacyKeyring(pub Keyring);
+
+/// Account of [`LegacyKeyring`]
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct LegacyAccount(KeysAccount);
+
+impl StrictDecode for LegacyAccount {
+    fn strict_decode<D: io::Read>(
+        d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        KeysAccount::strict_decode_legacy(d).map(LegacyAccount)
+    }
+}
+
+impl StrictDecode for LegacyKeyring {
+    fn strict_decode<D: io::Read>(
+        mut d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        let master_account = KeysAccount::strict_decode_legacy(&mut d)?;
+        let key_source = StrictDecode::strict_decode(&mut d)?;
+        let sub_accounts =
+            BTreeMap::<DerivationPath, LegacyAccount>::strict_decode(&mut d)?
+                .into_iter()
+                .map(|(path, LegacyAccount(account))| (path, account))
+                .collect();
+        Ok(LegacyKeyring(Keyring {
+            master_account,
+            key_source,
+            sub_accounts,
+            stored_chain: None,
+        }))
+    }
+}
+
+impl From<LegacyKeyring> for Keyring {
+    fn from(legacy: LegacyKeyring) -> Self {
+        legacy.0
+    }
+}
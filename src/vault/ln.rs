@@ -0,0 +1,272 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Lightning node keys derived from a keyring dedicated to the node. Channel
+//! basepoints use `<basepoint>/<channel index>` path, matching the keys
+//! with which [`crate::lnp`] signer requests commitment signatures, while
+//! the node identity key is derived with the path following the last of the
+//! basepoints. All public keys are derived from the extended public key, so
+//! they are available for watch-only keyrings as well; the node private key
+//! never leaves the vault. It is used for the BOLT-8 ECDH and for signing
+//! invoices and gossip messages, which digests are computed here from the
+//! messages themselves, so the node key can't be used to sign arbitrary
+//! data.
+
+use bitcoin::hashes::{sha256, sha256d, Hash, HashEngine};
+use bitcoin::secp256k1::{Message, PublicKey, SecretKey, Signature};
+use bitcoin::util::bip32::{self, ChildNumber, DerivationPath, ExtendedPubKey};
+
+use super::keymgm::Error;
+use super::secret::SecretXpriv;
+use super::KeysAccount;
+use crate::rpc::types::{
+    AnnouncementSignatures, Basepoint, LnKeySet, SharedSecret,
+};
+use crate::SECP256K1;
+
+/// BOLT-7 `channel_announcement` message type
+pub const CHANNEL_ANNOUNCEMENT: u16 = 256;
+
+/// BOLT-7 `node_announcement` message type
+pub const NODE_ANNOUNCEMENT: u16 = 257;
+
+/// BOLT-7 `channel_update` message type
+pub const CHANNEL_UPDATE: u16 = 258;
+
+/// Length of the compact signature in the gossip messages
+const SIGNATURE_LEN: usize = 64;
+
+/// Length of the compressed public key in the gossip messages
+const PUBKEY_LEN: usize = 33;
+
+/// First segment of the node identity key derivation path
+pub const NODE_KEY_BRANCH: u32 = 5;
+
+/// Returns derivation path of the node identity key
+pub fn node_key_path() -> Result<DerivationPath, bip32::Error> {
+    Ok(DerivationPath::from(vec![
+        ChildNumber::from_normal_idx(NODE_KEY_BRANCH)?,
+        ChildNumber::from_normal_idx(0)?,
+    ]))
+}
+
+fn node_key(master: &SecretXpriv) -> Result<SecretXpriv, Error> {
+    Ok(master.derive_priv(&node_key_path()?)?)
+}
+
+fn derive(
+    xpubkey: &ExtendedPubKey,
+    path: &DerivationPath,
+) -> Result<PublicKey, bip32::Error> {
+    Ok(xpubkey.derive_pub(&SECP256K1, path)?.public_key.key)
+}
+
+/// Derives node identity key and the basepoints of the `channel` from the
+/// keyring master extended public key
+pub fn key_set(
+    xpubkey: &ExtendedPubKey,
+    channel: u32,
+) -> Result<LnKeySet, bip32::Error> {
+    let basepoint =
+        |basepoint: Basepoint| derive(xpubkey, &basepoint.path(channel)?);
+    Ok(LnKeySet {
+        key_id: xpubkey.identifier(),
+        channel,
+        node_id: derive(xpubkey, &node_key_path()?)?,
+        funding_pubkey: basepoint(Basepoint::Funding)?,
+        revocation_basepoint: basepoint(Basepoint::Revocation)?,
+        payment_basepoint: basepoint(Basepoint::Payment)?,
+        delayed_payment_basepoint: basepoint(Basepoint::DelayedPayment)?,
+        htlc_basepoint: basepoint(Basepoint::Htlc)?,
+    })
+}
+
+/// Performs BOLT-8 ECDH of the remote `pubkey` with the node identity key of
+/// the `account`, returning SHA256 of the compressed shared point. The
+/// decryption key is wiped out right after the node key derivation.
+pub fn ecdh(
+    account: &KeysAccount,
+    pubkey: &PublicKey,
+    decryption_key: &mut SecretKey,
+) -> Result<SharedSecret, Error> {
+    let node_key = node_key(&account.xprivkey(decryption_key)?)?;
+    let mut point = *pubkey;
+    point.mul_assign(&SECP256K1, &node_key.secret_key()[..])?;
+    Ok(sha256::Hash::hash(&point.serialize()))
+}
+
+/// Computes BOLT-11 signature hash: SHA256 of the invoice human-readable
+/// part followed by the 5-bit `data` values (excluding the signature) packed
+/// into bytes, with the last byte padded with zero bits
+pub fn invoice_digest(hrp: &str, data: &[u8]) -> Result<sha256::Hash, Error> {
+    if !hrp.starts_with("ln") || hrp.to_lowercase() != hrp {
+        return Err(Error::InvoiceHrp(hrp.to_owned()));
+    }
+    let mut engine = sha256::Hash::engine();
+    engine.input(hrp.as_bytes());
+    let mut acc = 0u16;
+    let mut bits = 0;
+    for value in data {
+        if *value >= 32 {
+            return Err(Error::InvoiceData);
+        }
+        acc = (acc << 5) | *value as u16;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            engine.input(&[(acc >> bits) as u8]);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    if bits > 0 {
+        engine.input(&[(acc << (8 - bits)) as u8]);
+    }
+    Ok(sha256::Hash::from_engine(engine))
+}
+
+/// Signs BOLT-11 invoice with the node key, returning compact signature
+/// followed by the recovery id, as they are put into the invoice
+pub fn sign_invoice(
+    account: &KeysAccount,
+    hrp: &str,
+    data: &[u8],
+    decryption_key: &mut SecretKey,
+) -> Result<Vec<u8>, Error> {
+    let digest = invoice_digest(hrp, data)?;
+    let node_key = node_key(&account.xprivkey(decryption_key)?)?;
+    let signature = SECP256K1.sign_recoverable(
+        &Message::from_slice(&digest[..])?,
+        node_key.secret_key(),
+    );
+    let (recovery_id, compact) = signature.serialize_compact();
+    let mut data = compact.to_vec();
+    data.push(recovery_id.to_i32() as u8);
+    Ok(data)
+}
+
+/// Splits gossip message into its type and the part following the
+/// signatures, which is signed
+fn unsigned_part(
+    message: &[u8],
+    signatures: usize,
+) -> Result<(u16, &[u8]), Error> {
+    if message.len() < 2 + signatures * SIGNATURE_LEN {
+        return Err(Error::GossipMessage);
+    }
+    let msg_type = u16::from_be_bytes([message[0], message[1]]);
+    Ok((msg_type, &message[2 + signatures * SIGNATURE_LEN..]))
+}
+
+/// Reads public key at `offset` of the message part
+fn read_pubkey(data: &[u8], offset: usize) -> Result<PublicKey, Error> {
+    data.get(offset..offset + PUBKEY_LEN)
+        .and_then(|slice| PublicKey::from_slice(slice).ok())
+        .ok_or(Error::GossipMessage)
+}
+
+/// Reads the length of the feature bits at `offset` of the message part,
+/// returning the offset of the data following the features
+fn skip_features(data: &[u8], offset: usize) -> Result<usize, Error> {
+    let len = data
+        .get(offset..offset + 2)
+        .map(|len| u16::from_be_bytes([len[0], len[1]]) as usize)
+        .ok_or(Error::GossipMessage)?;
+    Ok(offset + 2 + len)
+}
+
+/// Computes signature hash of the BOLT-7 `channel_announcement` wire
+/// `message` (including its type and the signature placeholders), checking
+/// that one of the channel sides uses `node_id` with `bitcoin_key`
+pub fn announcement_digest(
+    message: &[u8],
+    node_id: &PublicKey,
+    bitcoin_key: &PublicKey,
+) -> Result<sha256d::Hash, Error> {
+    let (msg_type, unsigned) = unsigned_part(message, 4)?;
+    if msg_type != CHANNEL_ANNOUNCEMENT {
+        return Err(Error::GossipType(msg_type));
+    }
+    // Node ids follow the features, chain hash and short channel id, and
+    // are followed by the bitcoin keys
+    let offset = skip_features(unsigned, 0)? + 32 + 8;
+    let own_side = (0..2).any(|side| {
+        read_pubkey(unsigned, offset + side * PUBKEY_LEN).ok() == Some(*node_id)
+            && read_pubkey(unsigned, offset + (side + 2) * PUBKEY_LEN).ok()
+                == Some(*bitcoin_key)
+    });
+    if !own_side {
+        return Err(Error::GossipMessage);
+    }
+    Ok(sha256d::Hash::hash(unsigned))
+}
+
+/// Computes signature hash of the BOLT-7 `node_announcement` or
+/// `channel_update` wire `message` (including its type and the signature
+/// placeholder); node announcements must announce the `node_id`
+pub fn gossip_digest(
+    message: &[u8],
+    node_id: &PublicKey,
+) -> Result<sha256d::Hash, Error> {
+    let (msg_type, unsigned) = unsigned_part(message, 1)?;
+    match msg_type {
+        NODE_ANNOUNCEMENT => {
+            // Node id follows the features and timestamp
+            let offset = skip_features(unsigned, 0)? + 4;
+            if read_pubkey(unsigned, offset)? != *node_id {
+                return Err(Error::GossipMessage);
+            }
+        }
+        CHANNEL_UPDATE => {}
+        _ => return Err(Error::GossipType(msg_type)),
+    }
+    Ok(sha256d::Hash::hash(unsigned))
+}
+
+/// Signs BOLT-7 `channel_announcement` wire `message` of the `channel` with
+/// the node key and the channel funding key
+pub fn sign_announcement(
+    account: &KeysAccount,
+    channel: u32,
+    message: &[u8],
+    decryption_key: &mut SecretKey,
+) -> Result<AnnouncementSignatures, Error> {
+    let master = account.xprivkey(decryption_key)?;
+    let node_key = node_key(&master)?;
+    let funding_key = master.derive_priv(&Basepoint::Funding.path(channel)?)?;
+    let digest = announcement_digest(
+        message,
+        &PublicKey::from_secret_key(&SECP256K1, node_key.secret_key()),
+        &PublicKey::from_secret_key(&SECP256K1, funding_key.secret_key()),
+    )?;
+    let digest = Message::from_slice(&digest[..])?;
+    Ok(AnnouncementSignatures {
+        node_signature: SECP256K1.sign(&digest, node_key.secret_key()),
+        bitcoin_signature: SECP256K1.sign(&digest, funding_key.secret_key()),
+    })
+}
+
+/// Signs BOLT-7 `node_announcement` or `channel_update` wire `message` with
+/// the node key
+pub fn sign_gossip(
+    account: &KeysAccount,
+    message: &[u8],
+    decryption_key: &mut SecretKey,
+) -> Result<Signature, Error> {
+    let node_key = node_key(&account.xprivkey(decryption_key)?)?;
+    let digest = gossip_digest(
+        message,
+        &PublicKey::from_secret_key(&SECP256K1, node_key.secret_key()),
+    )?;
+    Ok(SECP256K1
+        .sign(&Message::from_slice(&digest[..])?, node_key.secret_key()))
+}
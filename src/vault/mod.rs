@@ -13,14 +13,60 @@
 
 //! Storage drivers for private key vault
 
+pub mod backup;
+pub mod bip47;
+pub mod bip85;
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub mod browser;
 pub mod delegated;
+pub mod descriptor;
+pub mod diff;
 pub mod driver;
+pub mod encryption;
+pub mod example;
+#[cfg(feature = "node")]
 pub mod file_driver;
+pub mod finalizer;
+pub mod identity;
+#[cfg(feature = "node")]
+pub mod interchange;
+pub mod journal;
 pub mod keymgm;
+pub mod ln;
+pub mod multisig;
+pub mod musig;
+#[cfg(feature = "os-keychain")]
+pub mod os_keystore;
+pub mod policy;
+#[cfg(feature = "remote-vault")]
+pub mod remote;
+pub mod rgb;
+pub mod secret;
+pub mod session;
+pub mod shred;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_driver;
+pub mod taproot;
 mod vault;
 
+pub use backup::Backups;
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub use browser::BrowserDriver;
 pub use delegated::DelegatedDriver;
 pub use driver::Driver;
+pub use encryption::Encryption;
+#[cfg(feature = "node")]
 pub use file_driver::FileDriver;
-pub use keymgm::{Keyring, KeysAccount};
+pub use journal::Journal;
+pub use keymgm::{DerivationCache, Keyring, KeysAccount, SigningCache};
+#[cfg(feature = "os-keychain")]
+pub use os_keystore::OsKeystoreDriver;
+#[cfg(feature = "remote-vault")]
+pub use remote::RemoteDriver;
+pub use secret::{SecretBuffer, SecretXpriv};
+pub use session::{Sandboxed, Sessions};
+#[cfg(feature = "sqlite")]
+pub use sqlite_driver::SqliteDriver;
 pub use vault::Vault;
+
+pub(crate) use vault::input_sighash;
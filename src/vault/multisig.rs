@@ -0,0 +1,169 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Multisig account groups: N-of-M groups of vault accounts and external
+//! cosigner keys spending with `wsh(multi(...))` scripts. A group is kept
+//! by the first of its vault accounts and is exported as a pair of output
+//! descriptors for the receiving and change addresses.
+//!
+//! Before signing a PSBT the vault checks inputs which key derivations
+//! belong to a cosigner of some group: such inputs must spend the script of
+//! one of these groups, derived with the same path for all cosigners. This
+//! prevents signing for scripts in which some of the cosigners were
+//! replaced.
+
+use std::collections::HashSet;
+
+use bitcoin::blockdata::opcodes::all::OP_CHECKMULTISIG;
+use bitcoin::blockdata::script::Builder;
+use bitcoin::util::bip32::{self, DerivationPath, ExtendedPubKey};
+use bitcoin::util::psbt::Input;
+use bitcoin::Script;
+
+use super::descriptor;
+use crate::rpc::types::{
+    CosignerKey, MultisigGroup, MultisigId, MAX_COSIGNERS,
+};
+use crate::SECP256K1;
+
+/// Errors of the multisig group registration and signing
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum Error {
+    /// Multisig group must have from 1 to {0} cosigners, while {1} are given
+    CosignerCount(usize, usize),
+
+    /// Multisig group threshold {0} exceeds number of cosigners {1}
+    Threshold(u8, usize),
+
+    /// Cosigner key {0} is used more than once
+    DuplicateCosigner(ExtendedPubKey),
+
+    /// Multisig group must include at least one account of the vault
+    NoVaultAccount,
+
+    /// Multisig group {0} is already registered
+    KnownGroup(MultisigId),
+
+    /// Multisig group {0} is not known
+    UnknownGroup(MultisigId),
+
+    /// Input #{0} uses key of a multisig group cosigner, but does not spend
+    /// the script of any of the registered groups
+    ScriptMismatch(usize),
+
+    /// Unable to derive cosigner key: {0}
+    #[from]
+    Derivation(bip32::Error),
+}
+
+/// Checks that the group has a valid threshold and unique cosigner keys
+pub fn validate(group: &MultisigGroup) -> Result<(), Error> {
+    let count = group.cosigners.len();
+    if count == 0 || count > MAX_COSIGNERS {
+        return Err(Error::CosignerCount(MAX_COSIGNERS, count));
+    }
+    if group.threshold == 0 || group.threshold as usize > count {
+        return Err(Error::Threshold(group.threshold, count));
+    }
+    let mut known = HashSet::with_capacity(count);
+    for cosigner in &group.cosigners {
+        if !known.insert(cosigner.xpubkey.identifier()) {
+            return Err(Error::DuplicateCosigner(cosigner.xpubkey));
+        }
+    }
+    Ok(())
+}
+
+/// Composes witness script of the group for the keys derived from each of
+/// the cosigner keys with the relative `derivation` path
+pub fn script(
+    group: &MultisigGroup,
+    derivation: &DerivationPath,
+) -> Result<Script, Error> {
+    let mut builder = Builder::new().push_int(group.threshold as i64);
+    for cosigner in &group.cosigners {
+        let xpubkey = cosigner.xpubkey.derive_pub(&SECP256K1, derivation)?;
+        builder = builder.push_key(&xpubkey.public_key);
+    }
+    Ok(builder
+        .push_int(group.cosigners.len() as i64)
+        .push_opcode(OP_CHECKMULTISIG)
+        .into_script())
+}
+
+/// Composes output descriptors for the receiving (`/0/*`) and change (`/1/*`)
+/// addresses of the group. Returned descriptors include checksums.
+pub fn export(group: &MultisigGroup) -> Vec<String> {
+    (0..=1)
+        .map(|branch| {
+            let keys = group
+                .cosigners
+                .iter()
+                .map(|cosigner| format!("{}/{}/*", cosigner, branch))
+                .collect::<Vec<_>>();
+            let descriptor =
+                format!("wsh(multi({},{}))", group.threshold, keys.join(","));
+            let checksum = descriptor::checksum(&descriptor)
+                .expect("descriptor is composed from valid characters");
+            format!("{}#{}", descriptor, checksum)
+        })
+        .collect()
+}
+
+/// Returns paths of the `input` key derivations relative to the `cosigner`
+/// key, for all input keys originating from it
+fn derivations<'a>(
+    cosigner: &'a CosignerKey,
+    input: &'a Input,
+) -> impl Iterator<Item = DerivationPath> + 'a {
+    let (fingerprint, origin) = &cosigner.origin;
+    input
+        .bip32_derivation
+        .values()
+        .filter(move |(fp, path)| {
+            fp == fingerprint && path.as_ref().starts_with(origin.as_ref())
+        })
+        .map(move |(_, path)| {
+            DerivationPath::from(&path.as_ref()[origin.as_ref().len()..])
+        })
+}
+
+/// Detects whether the `input` uses key derived from any of the group
+/// cosigners for which `filter` returns `true`
+pub fn involves(
+    group: &MultisigGroup,
+    input: &Input,
+    filter: impl Fn(&CosignerKey) -> bool,
+) -> bool {
+    group
+        .cosigners
+        .iter()
+        .filter(|cosigner| filter(cosigner))
+        .any(|cosigner| derivations(cosigner, input).next().is_some())
+}
+
+/// Detects whether the `input` spends the group script derived with the
+/// path of one of the input keys originating from the group cosigners
+pub fn matches(group: &MultisigGroup, input: &Input) -> bool {
+    let witness_script = match input.witness_script {
+        Some(ref script) => script,
+        None => return false,
+    };
+    group.cosigners.iter().any(|cosigner| {
+        derivations(cosigner, input).any(|derivation| {
+            script(group, &derivation)
+                .map_or(false, |script| &script == witness_script)
+        })
+    })
+}
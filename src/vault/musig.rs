@@ -0,0 +1,344 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! MuSig2 (BIP-327) multi-signatures: key aggregation, nonce generation and
+//! aggregation, partial signing and aggregation of the partial signatures
+//! into a BIP-340 signature valid for the aggregated key.
+//!
+//! Scalars derived from hashes are used without reduction modulo the curve
+//! order; hash values exceeding the order, which have negligible
+//! probability, fail with [`Error::Secp`] instead.
+
+use bitcoin::hashes::sha256;
+use bitcoin::secp256k1::rand::thread_rng;
+use bitcoin::secp256k1::{self, schnorrsig, PublicKey, SecretKey};
+
+use super::secret::wipe_key;
+use super::taproot::tagged_hash;
+use crate::SECP256K1;
+
+/// Length of the serialized public nonce: two compressed points
+pub const PUBNONCE_LEN: usize = 66;
+
+/// Length of the serialized partial signature
+pub const PARTIAL_SIG_LEN: usize = 32;
+
+/// Errors of the MuSig2 signing protocol
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum Error {
+    /// MuSig2 signing requires at least one participant key
+    NoParticipants,
+
+    /// Participant key {0} is given more than once
+    DuplicateParticipant(PublicKey),
+
+    /// Key {0} does not participate in the MuSig2 session
+    UnknownParticipant(PublicKey),
+
+    /// Public nonce must be {0} bytes of two compressed curve points
+    InvalidNonce(usize),
+
+    /// Partial signature must be a {0}-byte scalar below the curve order
+    InvalidPartialSignature(usize),
+
+    /// Aggregated signature is not valid for the aggregated key; some of
+    /// the partial signatures are invalid or were produced for another
+    /// session
+    InvalidSignature,
+
+    /// Elliptic curve operation failed: {0}
+    #[from]
+    Secp(secp256k1::Error),
+}
+
+/// Aggregated key of the MuSig2 participants together with the values
+/// required for signing with it (`KeyAgg Context` of BIP-327)
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct KeyAggContext {
+    keys: Vec<PublicKey>,
+    aggregated: PublicKey,
+    /// Whether accumulated sign factor `gacc` is negative
+    negated: bool,
+    /// Accumulated tweak `tacc`; absent if the key is not tweaked
+    tweak: Option<SecretKey>,
+}
+
+impl KeyAggContext {
+    /// Aggregates participant `keys` given in the order agreed between the
+    /// participants
+    pub fn with(keys: &[PublicKey]) -> Result<Self, Error> {
+        let first = *keys.first().ok_or(Error::NoParticipants)?;
+        for (index, key) in keys.iter().enumerate() {
+            if keys[..index].contains(key) {
+                return Err(Error::DuplicateParticipant(*key));
+            }
+        }
+        let mut context = KeyAggContext {
+            keys: keys.to_vec(),
+            aggregated: first,
+            negated: false,
+            tweak: None,
+        };
+        let mut points = Vec::with_capacity(keys.len());
+        for key in keys {
+            let mut point = *key;
+            point.mul_assign(&SECP256K1, &context.coefficient(key)?[..])?;
+            points.push(point);
+        }
+        let mut aggregated = points[0];
+        for point in &points[1..] {
+            aggregated = aggregated.combine(point)?;
+        }
+        context.aggregated = aggregated;
+        Ok(context)
+    }
+
+    /// Participant keys in the order of aggregation
+    pub fn keys(&self) -> &[PublicKey] {
+        &self.keys
+    }
+
+    /// Returns x-only aggregated key, which is used to verify BIP-340
+    /// signatures produced by the participants
+    pub fn aggregated_key(&self) -> schnorrsig::PublicKey {
+        schnorrsig::PublicKey::from_slice(&xonly(&self.aggregated))
+            .expect("serialized point is a valid x-only key")
+    }
+
+    /// Applies BIP-341 tweak for a key-path spend of the output without
+    /// script tree, so the aggregated key becomes taproot output key
+    pub fn tweak_taproot(&mut self) -> Result<(), Error> {
+        let tweak = tagged_hash("TapTweak", &[&xonly(&self.aggregated)]);
+        let mut tweak = SecretKey::from_slice(&tweak[..])?;
+        let negate = !has_even_y(&self.aggregated);
+        if negate {
+            self.aggregated.negate_assign(&SECP256K1);
+            self.negated = !self.negated;
+        }
+        if let Some(previous) = self.tweak {
+            tweak.add_assign(&negate_if(previous, negate)[..])?;
+        }
+        self.aggregated.add_exp_assign(&SECP256K1, &tweak[..])?;
+        self.tweak = Some(tweak);
+        Ok(())
+    }
+
+    /// Computes key aggregation coefficient of the participant `key`. The
+    /// second distinct key in the list has coefficient 1.
+    fn coefficient(&self, key: &PublicKey) -> Result<SecretKey, Error> {
+        if !self.keys.contains(key) {
+            return Err(Error::UnknownParticipant(*key));
+        }
+        if self.keys.iter().find(|k| *k != &self.keys[0]) == Some(key) {
+            return Ok(secp256k1::key::ONE_KEY);
+        }
+        let serialized = self
+            .keys
+            .iter()
+            .flat_map(|key| key.serialize().to_vec())
+            .collect::<Vec<_>>();
+        let list = tagged_hash("KeyAgg list", &[&serialized]);
+        let coefficient =
+            tagged_hash("KeyAgg coefficient", &[&list[..], &key.serialize()]);
+        Ok(SecretKey::from_slice(&coefficient[..])?)
+    }
+}
+
+/// Public nonce of a MuSig2 participant, or aggregate of the public nonces
+/// of all participants
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct PubNonce(PublicKey, PublicKey);
+
+impl PubNonce {
+    /// Parses nonce serialized as two compressed points
+    pub fn from_slice(data: &[u8]) -> Result<Self, Error> {
+        if data.len() != PUBNONCE_LEN {
+            return Err(Error::InvalidNonce(PUBNONCE_LEN));
+        }
+        let parse = |data: &[u8]| {
+            PublicKey::from_slice(data)
+                .map_err(|_| Error::InvalidNonce(PUBNONCE_LEN))
+        };
+        Ok(PubNonce(parse(&data[..33])?, parse(&data[33..])?))
+    }
+
+    /// Serializes nonce as two compressed points
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data = self.0.serialize().to_vec();
+        data.extend(&self.1.serialize());
+        data
+    }
+}
+
+/// Secret nonce of a MuSig2 participant. It must be used for a single
+/// partial signature only, so [`partial_sign`] consumes it; the nonce is
+/// wiped from memory when dropped.
+pub struct SecNonce(SecretKey, SecretKey);
+
+impl Drop for SecNonce {
+    fn drop(&mut self) {
+        wipe_key(&mut self.0);
+        wipe_key(&mut self.1);
+    }
+}
+
+/// Generates random secret nonce and the matching public nonce
+pub fn nonce_gen() -> (SecNonce, PubNonce) {
+    let mut rng = thread_rng();
+    let secnonce = SecNonce(SecretKey::new(&mut rng), SecretKey::new(&mut rng));
+    let pubnonce = PubNonce(
+        PublicKey::from_secret_key(&SECP256K1, &secnonce.0),
+        PublicKey::from_secret_key(&SECP256K1, &secnonce.1),
+    );
+    (secnonce, pubnonce)
+}
+
+/// Aggregates public nonces of all participants. Nonces summing up to the
+/// point at infinity fail with [`Error::Secp`].
+pub fn nonce_agg(nonces: &[PubNonce]) -> Result<PubNonce, Error> {
+    let (first, rest) = nonces.split_first().ok_or(Error::NoParticipants)?;
+    rest.iter()
+        .try_fold(*first, |acc, nonce| -> Result<PubNonce, Error> {
+            Ok(PubNonce(acc.0.combine(&nonce.0)?, acc.1.combine(&nonce.1)?))
+        })
+}
+
+/// Computes partial signature of the `message` with the participant
+/// `seckey` and the secret nonce, which public part was aggregated into
+/// the `aggnonce`
+pub fn partial_sign(
+    secnonce: SecNonce,
+    seckey: &SecretKey,
+    context: &KeyAggContext,
+    aggnonce: &PubNonce,
+    message: sha256::Hash,
+) -> Result<[u8; PARTIAL_SIG_LEN], Error> {
+    let pubkey = PublicKey::from_secret_key(&SECP256K1, seckey);
+    let coefficient = context.coefficient(&pubkey)?;
+    let (nonce_coef, nonce) = nonce_values(context, aggnonce, message)?;
+    let challenge = challenge(context, &nonce, message)?;
+
+    let mut k1 = negate_if(secnonce.0, !has_even_y(&nonce));
+    let mut k2 = negate_if(secnonce.1, !has_even_y(&nonce));
+    drop(secnonce);
+    // Signing key is multiplied by `g * gacc`, where `g` is the sign
+    // factor of the final aggregated key
+    let negate = context.negated != !has_even_y(&context.aggregated);
+    let mut key = negate_if(*seckey, negate);
+
+    let result = (|| -> Result<[u8; PARTIAL_SIG_LEN], Error> {
+        key.mul_assign(&coefficient[..])?;
+        key.mul_assign(&challenge[..])?;
+        k2.mul_assign(&nonce_coef[..])?;
+        let mut sig = k1;
+        sig.add_assign(&k2[..])?;
+        sig.add_assign(&key[..])?;
+        let mut partial = [0u8; PARTIAL_SIG_LEN];
+        partial.copy_from_slice(&sig[..]);
+        wipe_key(&mut sig);
+        Ok(partial)
+    })();
+    wipe_key(&mut k1);
+    wipe_key(&mut k2);
+    wipe_key(&mut key);
+    result
+}
+
+/// Aggregates partial signatures of all participants into BIP-340
+/// signature, which is verified against the aggregated key
+pub fn aggregate(
+    context: &KeyAggContext,
+    aggnonce: &PubNonce,
+    message: sha256::Hash,
+    partial_sigs: &[[u8; PARTIAL_SIG_LEN]],
+) -> Result<schnorrsig::Signature, Error> {
+    let (_, nonce) = nonce_values(context, aggnonce, message)?;
+    let parse = |sig: &[u8]| {
+        SecretKey::from_slice(sig)
+            .map_err(|_| Error::InvalidPartialSignature(PARTIAL_SIG_LEN))
+    };
+    let (first, rest) =
+        partial_sigs.split_first().ok_or(Error::NoParticipants)?;
+    let mut sig = parse(first)?;
+    for partial in rest {
+        sig.add_assign(&parse(partial)?[..])?;
+    }
+    if let Some(tweak) = context.tweak {
+        let mut tweak = negate_if(tweak, !has_even_y(&context.aggregated));
+        tweak.mul_assign(&challenge(context, &nonce, message)?[..])?;
+        sig.add_assign(&tweak[..])?;
+    }
+
+    let mut data = xonly(&nonce).to_vec();
+    data.extend(&sig[..]);
+    let signature = schnorrsig::Signature::from_slice(&data)?;
+    SECP256K1
+        .schnorrsig_verify(
+            &signature,
+            &secp256k1::Message::from_slice(&message[..])?,
+            &context.aggregated_key(),
+        )
+        .map_err(|_| Error::InvalidSignature)?;
+    Ok(signature)
+}
+
+/// Computes nonce coefficient `b` and the final nonce `R` of the session
+fn nonce_values(
+    context: &KeyAggContext,
+    aggnonce: &PubNonce,
+    message: sha256::Hash,
+) -> Result<(SecretKey, PublicKey), Error> {
+    let coef = tagged_hash(
+        "MuSig/noncecoef",
+        &[
+            &aggnonce.serialize(),
+            &xonly(&context.aggregated),
+            &message[..],
+        ],
+    );
+    let coef = SecretKey::from_slice(&coef[..])?;
+    let mut second = aggnonce.1;
+    second.mul_assign(&SECP256K1, &coef[..])?;
+    Ok((coef, aggnonce.0.combine(&second)?))
+}
+
+/// Computes BIP-340 challenge `e` for the final nonce and aggregated key
+fn challenge(
+    context: &KeyAggContext,
+    nonce: &PublicKey,
+    message: sha256::Hash,
+) -> Result<SecretKey, Error> {
+    let challenge = tagged_hash(
+        "BIP0340/challenge",
+        &[&xonly(nonce), &xonly(&context.aggregated), &message[..]],
+    );
+    Ok(SecretKey::from_slice(&challenge[..])?)
+}
+
+fn xonly(point: &PublicKey) -> [u8; 32] {
+    let mut data = [0u8; 32];
+    data.copy_from_slice(&point.serialize()[1..]);
+    data
+}
+
+fn has_even_y(point: &PublicKey) -> bool {
+    point.serialize()[0] == 0x02
+}
+
+fn negate_if(mut scalar: SecretKey, negate: bool) -> SecretKey {
+    if negate {
+        scalar.negate_assign();
+    }
+    scalar
+}
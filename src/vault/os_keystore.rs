@@ -0,0 +1,196 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Vault storage driver keeping keyrings in the operating system secret
+//! storage: macOS Keychain, Windows Credential Manager (protected with DPAPI)
+//! or Secret Service on Linux. Each keyring is stored as a separate entry
+//! under the configured service name, and an index entry lists the keyrings
+//! in the vault order. If the OS storage is not available (like on headless
+//! Linux systems without Secret Service daemon), the driver may fall back to
+//! a file vault.
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "windows"
+)))]
+compile_error!(
+    "`os-keychain` feature is supported only on Linux, macOS and Windows"
+);
+
+use ::core::any::Any;
+
+use lnpbp::strict_encoding::{strict_deserialize, strict_serialize};
+use os_keyring::KeyringError;
+
+use super::{driver, file_driver, Driver, FileDriver, Keyring};
+use crate::error::BootstrapError;
+
+/// Default service name for the vault entries
+pub const DEFAULT_SERVICE: &str = "keyringd";
+
+/// Name of the entry listing identifiers of the stored keyrings
+const INDEX_ENTRY: &str = "index";
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(crate = "serde_crate")]
+pub struct Config {
+    /// Service name under which the vault entries are stored
+    #[serde(default = "default_service")]
+    pub service: String,
+
+    /// File vault used when the OS secret storage is not available
+    #[serde(default)]
+    pub fallback: Option<file_driver::Config>,
+}
+
+fn default_service() -> String {
+    DEFAULT_SERVICE.to_string()
+}
+
+#[derive(Debug, Display)]
+#[display("OsKeystoreDriver({config:?})")]
+pub struct OsKeystoreDriver {
+    config: Config,
+    fallback: Option<FileDriver>,
+}
+
+impl Driver for OsKeystoreDriver {
+    fn init(config: &dyn Any) -> Result<Self, BootstrapError> {
+        let config = config.downcast_ref::<Config>().expect(
+            "`OsKeystoreDriver` must be configured with `os_keystore::Config` \
+             object",
+        );
+        info!(
+            "Initializing OS keystore driver for vault under service {:?}",
+            config.service
+        );
+        let mut me = Self {
+            config: config.clone(),
+            fallback: None,
+        };
+        match me.read_entry(INDEX_ENTRY) {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                warn!("Vault index is not found: initializing empty vault");
+                me.store(&vec![])?;
+            }
+            Err(err) => match config.fallback {
+                Some(ref fdc) => {
+                    warn!(
+                        "OS keystore is not available ({}); falling back to \
+                         vault file {}",
+                        err, fdc.location
+                    );
+                    me.fallback = Some(FileDriver::init(fdc)?);
+                }
+                None => Err(driver::Error::from(err))?,
+            },
+        }
+        Ok(me)
+    }
+
+    fn load(&mut self) -> Result<Vec<Keyring>, driver::Error> {
+        if let Some(ref mut fallback) = self.fallback {
+            return fallback.load();
+        }
+        debug!("Loading vault from OS keystore");
+        let keyrings = self
+            .index()?
+            .into_iter()
+            .map(|id| {
+                let data = self
+                    .read_entry(&keyring_entry(&id))?
+                    .ok_or_else(|| driver::Error::from(MissingEntry(id)))?;
+                Ok(strict_deserialize(&base64::decode(data)?)?)
+            })
+            .collect::<Result<Vec<Keyring>, driver::Error>>()?;
+        trace!("Vault loaded: {:?}", keyrings);
+        Ok(keyrings)
+    }
+
+    /// Keyring entries are written before the index, so if the operation is
+    /// interrupted the index still refers to the complete set of keyrings;
+    /// entries of the removed keyrings are deleted afterwards.
+    fn store(&mut self, accounts: &Vec<Keyring>) -> Result<(), driver::Error> {
+        if let Some(ref mut fallback) = self.fallback {
+            return fallback.store(accounts);
+        }
+        debug!("Storing vault data to OS keystore");
+        trace!("Current vault data: {:?}", accounts);
+        let previous = self.index().unwrap_or_default();
+        let mut index = vec![];
+        for keyring in accounts {
+            let id = keyring.identifier().to_string();
+            let data = base64::encode(&strict_serialize(keyring)?);
+            self.entry(&keyring_entry(&id)).set_password(&data)?;
+            index.push(id);
+        }
+        self.entry(INDEX_ENTRY).set_password(&index.join("\n"))?;
+        for id in previous.iter().filter(|id| !index.contains(id)) {
+            match self.entry(&keyring_entry(id)).delete_password() {
+                Ok(()) | Err(KeyringError::NoPasswordFound) => {}
+                Err(err) => Err(err)?,
+            }
+        }
+        trace!("Vault data stored");
+        Ok(())
+    }
+
+    fn shred(
+        &mut self,
+        accounts: &Vec<Keyring>,
+    ) -> Result<Vec<String>, driver::Error> {
+        match self.fallback {
+            Some(ref mut fallback) => fallback.shred(accounts),
+            // OS secret storages provide no means to control how the
+            // replaced data are disposed of
+            None => {
+                self.store(accounts)?;
+                Ok(vec![])
+            }
+        }
+    }
+}
+
+impl OsKeystoreDriver {
+    fn entry<'a>(&'a self, name: &'a str) -> os_keyring::Keyring<'a> {
+        os_keyring::Keyring::new(&self.config.service, name)
+    }
+
+    fn read_entry(&self, name: &str) -> Result<Option<String>, KeyringError> {
+        match self.entry(name).get_password() {
+            Ok(data) => Ok(Some(data)),
+            Err(KeyringError::NoPasswordFound) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn index(&self) -> Result<Vec<String>, driver::Error> {
+        Ok(self
+            .read_entry(INDEX_ENTRY)?
+            .unwrap_or_default()
+            .lines()
+            .filter(|id| !id.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+fn keyring_entry(id: &str) -> String {
+    format!("keyring/{}", id)
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display("OS keystore has no entry for keyring {0} listed in the vault index")]
+struct MissingEntry(String);
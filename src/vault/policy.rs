@@ -0,0 +1,234 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Signing policies of the keys accounts. Before signing a PSBT the vault
+//! evaluates policies of all accounts which keys are used by the PSBT
+//! inputs: the keyring master account policy applies to all inputs signed
+//! with the keyring keys, while a derived account policy applies to the
+//! inputs which key derivation paths start with the account path.
+//!
+//! Rate limits are tracked in memory with [`SigningHistory`], so the history
+//! is reset when the daemon restarts.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+#[cfg(target_arch = "wasm32")]
+use instant::Instant;
+
+use bitcoin::util::bip32::Fingerprint;
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::{SigHashType, XpubIdentifier};
+
+use super::taproot;
+use crate::rpc::types::SigningPolicy;
+
+/// Signing policy rule violated by a PSBT
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Display)]
+#[display(doc_comments)]
+pub enum Rule {
+    /// transaction sends {1} sat, exceeding the limit of {0} sat
+    MaxAmount(u64, u64),
+
+    /// output #{0} pays to an address which is not whitelisted
+    Destination(usize),
+
+    /// input #{0} is signed with sighash type {1:#04x}, which is not allowed
+    SighashType(usize, u8),
+
+    /// more than {0} PSBTs are signed within {1} seconds
+    RateLimit(u32, u64),
+
+    /// ECDH with the account node key is not allowed
+    Ecdh,
+}
+
+/// PSBT violates signing policy of the account
+#[derive(Clone, PartialEq, Eq, Debug, Error)]
+pub struct PolicyViolation {
+    /// Account which policy is violated
+    pub account: XpubIdentifier,
+
+    /// List of the violated rules
+    pub rules: Vec<Rule>,
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Signing policy of account {} is violated: ",
+            self.account
+        )?;
+        let rules = self.rules.iter().map(Rule::to_string).collect::<Vec<_>>();
+        f.write_str(&rules.join("; "))
+    }
+}
+
+/// Times at which PSBTs were signed by each of the accounts with a rate
+/// limit
+#[derive(Clone, Debug, Default)]
+pub struct SigningHistory {
+    signed: HashMap<XpubIdentifier, VecDeque<Instant>>,
+}
+
+impl SigningHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns number of PSBTs signed by the `account` within the last
+    /// `period`
+    pub fn count(&self, account: XpubIdentifier, period: Duration) -> usize {
+        self.signed
+            .get(&account)
+            .map(|times| {
+                times.iter().filter(|time| time.elapsed() < period).count()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Records PSBT signed by the `account`, forgetting records older than
+    /// the account rate limit `period`
+    pub fn record(&mut self, account: XpubIdentifier, period: Duration) {
+        let times = self.signed.entry(account).or_default();
+        while times.front().map(|time| time.elapsed() >= period) == Some(true) {
+            times.pop_front();
+        }
+        times.push_back(Instant::now());
+    }
+}
+
+/// Sighash types allowed by the policies which do not list sighash types:
+/// BIP-341 `SIGHASH_DEFAULT` and `SIGHASH_ALL`
+pub const DEFAULT_SIGHASH_TYPES: [u8; 2] = [taproot::SIGHASH_DEFAULT, 0x01];
+
+/// Sighash type with which the vault signs PSBT input #`index`: BIP-341
+/// inputs use the type given in the PSBT, while other inputs are always
+/// signed with `SIGHASH_ALL`. Types other than [`DEFAULT_SIGHASH_TYPES`]
+/// have to be allowed by the signing account policy; see
+/// [`check_sighash_type`].
+pub fn sighash_type(psbt: &PartiallySignedTransaction, index: usize) -> u8 {
+    if taproot::is_taproot_input(psbt, index) {
+        psbt.inputs[index]
+            .sighash_type
+            .map(|sighash_type| sighash_type.as_u32() as u8)
+            .unwrap_or(taproot::SIGHASH_DEFAULT)
+    } else {
+        SigHashType::All.as_u32() as u8
+    }
+}
+
+/// Checks whether `policy` allows signing with `sighash_type`. Policies
+/// which do not list sighash types allow only [`DEFAULT_SIGHASH_TYPES`].
+pub fn allows_sighash_type(policy: &SigningPolicy, sighash_type: u8) -> bool {
+    if policy.sighash_types.is_empty() {
+        DEFAULT_SIGHASH_TYPES.contains(&sighash_type)
+    } else {
+        policy.sighash_types.contains(&sighash_type)
+    }
+}
+
+/// Checks that `policy` of the `account` allows signing PSBT input #`index`
+/// with `sighash_type`
+pub fn check_sighash_type(
+    account: XpubIdentifier,
+    policy: &SigningPolicy,
+    index: usize,
+    sighash_type: u8,
+) -> Result<(), PolicyViolation> {
+    if allows_sighash_type(policy, sighash_type) {
+        Ok(())
+    } else {
+        Err(PolicyViolation {
+            account,
+            rules: vec![Rule::SighashType(index, sighash_type)],
+        })
+    }
+}
+
+/// Evaluates `policy` of the `account` for the PSBT `inputs` signed by the
+/// account. Outputs which BIP-32 derivation refers to the keyring master key
+/// `fingerprint` return funds to the keyring and are not counted towards the
+/// amount limit and destination whitelist.
+pub fn evaluate(
+    account: XpubIdentifier,
+    policy: &SigningPolicy,
+    psbt: &PartiallySignedTransaction,
+    inputs: &[usize],
+    fingerprint: Fingerprint,
+    history: &SigningHistory,
+) -> Result<(), PolicyViolation> {
+    let mut rules = vec![];
+    let tx = &psbt.global.unsigned_tx;
+
+    let outgoing =
+        psbt.outputs
+            .iter()
+            .zip(&tx.output)
+            .enumerate()
+            .filter(|(_, (output, _))| {
+                !output.bip32_derivation.values().any(
+                    |(output_fingerprint, _)| {
+                        *output_fingerprint == fingerprint
+                    },
+                )
+            })
+            .map(|(index, (_, txout))| (index, txout))
+            .collect::<Vec<_>>();
+
+    if let Some(max_amount) = policy.max_amount {
+        let amount = outgoing
+            .iter()
+            .fold(0u64, |sum, (_, txout)| sum.saturating_add(txout.value));
+        if amount > max_amount {
+            rules.push(Rule::MaxAmount(max_amount, amount));
+        }
+    }
+
+    if !policy.destinations.is_empty() {
+        rules.extend(
+            outgoing
+                .iter()
+                .filter(|(_, txout)| {
+                    !policy.destinations.contains(&txout.script_pubkey)
+                })
+                .map(|(index, _)| Rule::Destination(*index)),
+        );
+    }
+
+    rules.extend(inputs.iter().filter_map(|index| {
+        let sighash_type = sighash_type(psbt, *index);
+        if allows_sighash_type(policy, sighash_type) {
+            None
+        } else {
+            Some(Rule::SighashType(*index, sighash_type))
+        }
+    }));
+
+    if let Some(rate_limit) = policy.rate_limit {
+        let period = Duration::from_secs(rate_limit.period);
+        if history.count(account, period) >= rate_limit.count as usize {
+            rules.push(Rule::RateLimit(rate_limit.count, rate_limit.period));
+        }
+    }
+
+    if rules.is_empty() {
+        Ok(())
+    } else {
+        Err(PolicyViolation { account, rules })
+    }
+}
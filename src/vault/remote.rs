@@ -0,0 +1,149 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Vault storage driver keeping keyrings in another keyringd instance (the
+//! backend), accessed over the daemon RPC. This allows running a front
+//! daemon serving clients while the vault data are held on a separate
+//! hardened machine. The backend must have vault federation enabled and
+//! should require request authorization with the secret shared with the
+//! front daemon.
+
+use ::core::any::Any;
+use ::serde_with::hex::Hex;
+use ::serde_with::DisplayFromStr;
+use ::std::sync::Mutex;
+
+use bitcoin::secp256k1;
+use internet2::zmqsocket::ZmqSocketAddr;
+use lnpbp::strict_encoding::{strict_deserialize, strict_serialize};
+
+use super::{driver, Driver, Keyring};
+use crate::cli::{self, Client};
+use crate::error::BootstrapError;
+use crate::rpc::{self, message, Reply, Request};
+
+#[serde_as]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(crate = "serde_crate")]
+pub struct Config {
+    /// RPC endpoint of the backend daemon
+    #[serde_as(as = "DisplayFromStr")]
+    pub endpoint: ZmqSocketAddr,
+
+    /// Name of the client under which the auth secret is configured in the
+    /// backend daemon
+    #[serde(default)]
+    pub auth_client: Option<String>,
+
+    /// Secret shared with the backend daemon used to authorize requests
+    #[serde_as(as = "Option<Hex>")]
+    #[serde(default)]
+    pub auth_secret: Option<Vec<u8>>,
+
+    /// Node id of the backend daemon used to establish encrypted channel;
+    /// required if the requests are encrypted
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub daemon_id: Option<secp256k1::PublicKey>,
+
+    /// Whether requests must be sent over encrypted channel. If not given,
+    /// encryption is used for all endpoints except local ones
+    #[serde(default)]
+    pub transport_encryption: Option<bool>,
+
+    /// Whether vault data are sealed to the backend daemon node id, which
+    /// must be given with `daemon_id`
+    #[serde(default)]
+    pub payload_encryption: bool,
+}
+
+#[derive(Display)]
+#[display("RemoteDriver({endpoint})")]
+pub struct RemoteDriver {
+    endpoint: ZmqSocketAddr,
+    // RPC client socket is not `Sync`, while drivers are shared between
+    // threads
+    client: Mutex<Client>,
+}
+
+impl Driver for RemoteDriver {
+    fn init(config: &dyn Any) -> Result<Self, BootstrapError> {
+        let config = config.downcast_ref::<Config>().expect(
+            "`RemoteDriver` must be configured with `remote::Config` object",
+        );
+        info!(
+            "Initializing remote driver for vault at {}",
+            config.endpoint
+        );
+        // The front daemon is authenticated by the auth secret, so the
+        // channel uses random node key generated by the default config
+        let client = Client::with(cli::Config {
+            endpoint: config.endpoint.clone(),
+            auth_client: config.auth_client.clone(),
+            auth_secret: config.auth_secret.clone(),
+            daemon_id: config.daemon_id,
+            transport_encryption: config.transport_encryption,
+            payload_encryption: config.payload_encryption,
+            ..cli::Config::default()
+        })?;
+        Ok(Self {
+            endpoint: config.endpoint.clone(),
+            client: Mutex::new(client),
+        })
+    }
+
+    fn load(&mut self) -> Result<Vec<Keyring>, driver::Error> {
+        debug!("Loading vault from the backend daemon {}", self.endpoint);
+        let data =
+            match self.request(Request::LoadVault(message::LoadVault {
+                auth_code: rpc::types::AuthCode::default(),
+            }))? {
+                Reply::Vault(data) => data,
+                reply => Err(unexpected(reply))?,
+            };
+        let keyrings = strict_deserialize(&data)?;
+        trace!("Vault loaded: {:?}", keyrings);
+        Ok(keyrings)
+    }
+
+    fn store(&mut self, accounts: &Vec<Keyring>) -> Result<(), driver::Error> {
+        debug!("Storing vault data to the backend daemon {}", self.endpoint);
+        trace!("Current vault data: {:?}", accounts);
+        match self.request(Request::StoreVault(message::StoreVault {
+            data: strict_serialize(accounts)?,
+            auth_code: rpc::types::AuthCode::default(),
+        }))? {
+            Reply::Success => {}
+            reply => Err(unexpected(reply))?,
+        }
+        trace!("Vault data stored");
+        Ok(())
+    }
+}
+
+impl RemoteDriver {
+    fn request(&mut self, request: Request) -> Result<Reply, driver::Error> {
+        Ok(self
+            .client
+            .get_mut()
+            .expect("poisoned mutex")
+            .request(request)?)
+    }
+}
+
+fn unexpected(reply: Reply) -> rpc::Error {
+    match reply {
+        Reply::Failure(failure) => rpc::Error::ServerFailure(failure),
+        _ => rpc::Error::UnexpectedServerResponse,
+    }
+}
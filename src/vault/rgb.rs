@@ -0,0 +1,68 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! RGB asset metadata of PSBTs. Inputs spending outpoints with assigned RGB
+//! assets carry BIP-174 proprietary keys with `RGB` prefix, which key data
+//! is the identifier of the transferred asset and the value is the asset
+//! state transition. The vault uses them to check that such inputs are
+//! signed only by the accounts bound to the assets.
+
+use std::collections::HashSet;
+
+use bitcoin::hashes::Hash;
+use bitcoin::util::psbt::{raw, Input};
+use lnpbp::chain::AssetId;
+
+/// Type of the BIP-174 proprietary key
+pub const PSBT_PROPRIETARY: u8 = 0xFC;
+
+/// Prefix of the RGB proprietary keys
+pub const PSBT_RGB_PREFIX: &[u8] = b"RGB";
+
+/// Subtype of the RGB proprietary input key holding asset state transition
+pub const PSBT_IN_RGB_TRANSITION: u8 = 0x01;
+
+/// Returns proprietary key of the `asset` transition in a PSBT input
+pub fn transition_key(asset: AssetId) -> raw::Key {
+    let mut key = Vec::with_capacity(2 + PSBT_RGB_PREFIX.len() + 32);
+    key.push(PSBT_RGB_PREFIX.len() as u8);
+    key.extend(PSBT_RGB_PREFIX);
+    key.push(PSBT_IN_RGB_TRANSITION);
+    key.extend(&asset[..]);
+    raw::Key {
+        type_value: PSBT_PROPRIETARY,
+        key,
+    }
+}
+
+/// Adds state `transition` of the `asset` to the PSBT input
+pub fn add_transition(input: &mut Input, asset: AssetId, transition: Vec<u8>) {
+    input.unknown.insert(transition_key(asset), transition);
+}
+
+/// Lists assets with state transitions in the PSBT input
+pub fn input_assets(input: &Input) -> HashSet<AssetId> {
+    let prefix_len = 2 + PSBT_RGB_PREFIX.len();
+    input
+        .unknown
+        .keys()
+        .filter(|key| {
+            key.type_value == PSBT_PROPRIETARY
+                && key.key.len() == prefix_len + 32
+                && key.key[0] as usize == PSBT_RGB_PREFIX.len()
+                && &key.key[1..prefix_len - 1] == PSBT_RGB_PREFIX
+                && key.key[prefix_len - 1] == PSBT_IN_RGB_TRANSITION
+        })
+        .filter_map(|key| AssetId::from_slice(&key.key[prefix_len..]).ok())
+        .collect()
+}
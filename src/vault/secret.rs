@@ -0,0 +1,126 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Containers for the secret data which are wiped from the memory when
+//! dropped, so secrets do not survive early returns on errors. Wiping is
+//! done with volatile writes followed by a compiler fence, which can't be
+//! optimized out, following the `zeroize` crate semantics.
+
+use std::fmt::{self, Debug, Formatter};
+use std::ops::{Deref, DerefMut};
+use std::ptr;
+use std::sync::atomic::{compiler_fence, Ordering};
+
+use bitcoin::secp256k1::{self, SecretKey};
+use bitcoin::util::bip32::{self, ChainCode, ChildNumber, ExtendedPrivKey};
+use zeroize::Zeroize;
+
+/// Overwrites secret `key` in place. All-zero secret keys are invalid and
+/// may not be passed to secp256k1 functions, so the key is replaced with
+/// the well-known key of value 1 instead.
+pub fn wipe_key(key: &mut SecretKey) {
+    // Safety: the pointer is obtained from a valid mutable reference to a
+    // `Copy` type, so no destructor is skipped
+    unsafe { ptr::write_volatile(key, secp256k1::key::ONE_KEY) };
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// Byte buffer with secret data, zeroed when dropped
+#[derive(Default)]
+pub struct SecretBuffer(Vec<u8>);
+
+impl From<Vec<u8>> for SecretBuffer {
+    fn from(data: Vec<u8>) -> Self {
+        SecretBuffer(data)
+    }
+}
+
+impl Deref for SecretBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for SecretBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Debug for SecretBuffer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretBuffer(<{} bytes>)", self.0.len())
+    }
+}
+
+impl Drop for SecretBuffer {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// Extended private key, which private key and chain code are wiped when
+/// dropped. Copies obtained by dereferencing the wrapper are not wiped, so
+/// they must be used only for the keys leaving the vault.
+pub struct SecretXpriv(ExtendedPrivKey);
+
+impl From<ExtendedPrivKey> for SecretXpriv {
+    fn from(xpriv: ExtendedPrivKey) -> Self {
+        SecretXpriv(xpriv)
+    }
+}
+
+impl SecretXpriv {
+    /// Derives child extended private key, which is wiped when dropped
+    pub fn derive_priv<P>(&self, path: &P) -> Result<SecretXpriv, bip32::Error>
+    where
+        P: AsRef<[ChildNumber]>,
+    {
+        self.0.derive_priv(&crate::SECP256K1, path).map(SecretXpriv)
+    }
+
+    /// Private key of the extended key
+    pub fn secret_key(&self) -> &SecretKey {
+        &self.0.private_key.key
+    }
+}
+
+impl Deref for SecretXpriv {
+    type Target = ExtendedPrivKey;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Debug for SecretXpriv {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretXpriv(..)")
+    }
+}
+
+impl Drop for SecretXpriv {
+    fn drop(&mut self) {
+        wipe_key(&mut self.0.private_key.key);
+        // Safety: `ChainCode` is a `Copy` array wrapper
+        unsafe {
+            ptr::write_volatile(
+                &mut self.0.chain_code,
+                ChainCode::from(&[0u8; 32][..]),
+            )
+        };
+        compiler_fence(Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,254 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Unlocked vault sessions. Each session keeps vault decryption key in memory
+//! for a limited time and is referenced by clients with a random session
+//! token, so the decryption key does not need to be sent with each request.
+//! Sessions also hold sandbox of sub-accounts derived for testing purposes,
+//! which are either committed to the vault or discarded when the session is
+//! locked or expired, and, if enabled, a cache of the signing keys used by
+//! the session, which is wiped when the session is locked or expired.
+
+use std::collections::HashMap;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+#[cfg(target_arch = "wasm32")]
+use instant::Instant;
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::rand::{thread_rng, RngCore};
+use bitcoin::secp256k1::SecretKey;
+use bitcoin::util::bip32::DerivationPath;
+use bitcoin::XpubIdentifier;
+
+use super::secret::wipe_key;
+use super::{KeysAccount, SigningCache};
+use crate::rpc::types::SessionToken;
+
+/// Error cases related to unlocked session management
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum Error {
+    /// Session {0} is not known; it may have been expired or locked
+    UnknownSession(SessionToken),
+
+    /// Sandboxed derivations are possible only within unlocked session
+    SandboxRequiresSession,
+}
+
+/// Sub-account derived into the session sandbox, which is not stored in the
+/// vault until the sandbox is committed
+#[derive(Clone, Debug)]
+pub struct Sandboxed {
+    /// Master key identifier of the keyring the account is derived from
+    pub keyring: XpubIdentifier,
+
+    /// Derivation path of the account relative to the keyring master key
+    pub derivation: DerivationPath,
+
+    /// Derived account
+    pub account: KeysAccount,
+}
+
+struct Session {
+    decryption_key: SecretKey,
+    created: Instant,
+    sandbox: Vec<Sandboxed>,
+    signing_cache: Option<SigningCache>,
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        wipe_key(&mut self.decryption_key);
+    }
+}
+
+/// Set of the currently unlocked sessions
+pub struct Sessions {
+    timeout: Duration,
+    signing_cache: usize,
+    sessions: HashMap<SessionToken, Session>,
+}
+
+impl Sessions {
+    /// Constructs empty set of sessions, each of which will be valid for the
+    /// `timeout` period after the unlock
+    pub fn with(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            signing_cache: 0,
+            sessions: Default::default(),
+        }
+    }
+
+    /// Enables cache of up to `capacity` signing keys in each of the sessions
+    /// unlocked afterwards; zero `capacity` disables the cache
+    pub fn enable_signing_cache(&mut self, capacity: usize) {
+        self.signing_cache = capacity;
+    }
+
+    /// Changes validity period of the sessions, including the ones which are
+    /// already unlocked
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Returns session validity period
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Creates new unlocked session holding `decryption_key` and returns its
+    /// token
+    pub fn unlock(&mut self, decryption_key: SecretKey) -> SessionToken {
+        let mut random = [0u8; 32];
+        thread_rng().fill_bytes(&mut random);
+        let token = sha256::Hash::from_inner(random);
+        self.sessions.insert(
+            token,
+            Session {
+                decryption_key,
+                created: Instant::now(),
+                sandbox: vec![],
+                signing_cache: match self.signing_cache {
+                    0 => None,
+                    capacity => Some(SigningCache::with_capacity(capacity)),
+                },
+            },
+        );
+        debug!("Vault session {} is unlocked", token);
+        token
+    }
+
+    /// Locks session with a given `token`, wiping decryption key and cached
+    /// signing keys from the memory
+    pub fn lock(&mut self, token: SessionToken) -> Result<(), Error> {
+        let mut session = self
+            .sessions
+            .remove(&token)
+            .ok_or(Error::UnknownSession(token))?;
+        if let Some(ref mut cache) = session.signing_cache {
+            let wiped = cache.clear();
+            debug!(
+                "{} cached signing keys of session {} are wiped",
+                wiped, token
+            );
+        }
+        if !session.sandbox.is_empty() {
+            info!(
+                "{} sandboxed accounts of session {} are discarded",
+                session.sandbox.len(),
+                token
+            );
+        }
+        debug!("Vault session {} is locked", token);
+        Ok(())
+    }
+
+    /// Locks all sessions which are unlocked for longer than the timeout
+    pub fn expire(&mut self) {
+        let timeout = self.timeout;
+        let before = self.sessions.len();
+        self.sessions
+            .retain(|_, session| session.created.elapsed() < timeout);
+        let expired = before - self.sessions.len();
+        if expired > 0 {
+            debug!("{} vault sessions are expired and locked", expired);
+        }
+    }
+
+    /// Returns whether there are no unlocked sessions
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// Locks all sessions, wiping their decryption keys and cached signing
+    /// keys from the memory
+    pub fn lock_all(&mut self) {
+        if !self.sessions.is_empty() {
+            debug!("Locking {} vault sessions", self.sessions.len());
+        }
+        self.sessions.clear();
+    }
+
+    /// Returns a copy of decryption key for the session with a given `token`.
+    /// The caller is responsible for wiping the returned key after use.
+    pub fn decryption_key(
+        &mut self,
+        token: SessionToken,
+    ) -> Result<SecretKey, Error> {
+        self.expire();
+        self.sessions
+            .get(&token)
+            .map(|session| session.decryption_key)
+            .ok_or(Error::UnknownSession(token))
+    }
+
+    /// Returns sandbox of the session with a given `token` for adding new
+    /// sandboxed accounts
+    pub fn sandbox_mut(
+        &mut self,
+        token: SessionToken,
+    ) -> Result<&mut Vec<Sandboxed>, Error> {
+        self.expire();
+        self.sessions
+            .get_mut(&token)
+            .map(|session| &mut session.sandbox)
+            .ok_or(Error::UnknownSession(token))
+    }
+
+    /// Removes all accounts from the sandbox of the session with a given
+    /// `token` and returns them
+    pub fn take_sandbox(
+        &mut self,
+        token: SessionToken,
+    ) -> Result<Vec<Sandboxed>, Error> {
+        self.sandbox_mut(token).map(std::mem::take)
+    }
+
+    /// Takes signing cache out of the session with a given `token` for the
+    /// duration of a signing operation; returns `None` if the cache is not
+    /// enabled or is already taken by another signing operation
+    pub fn take_signing_cache(
+        &mut self,
+        token: SessionToken,
+    ) -> Result<Option<SigningCache>, Error> {
+        self.expire();
+        self.sessions
+            .get_mut(&token)
+            .map(|session| session.signing_cache.take())
+            .ok_or(Error::UnknownSession(token))
+    }
+
+    /// Returns signing cache taken with [`Sessions::take_signing_cache`] to
+    /// the session. If the session was locked or expired in the meantime,
+    /// the cache is wiped instead.
+    pub fn restore_signing_cache(
+        &mut self,
+        token: SessionToken,
+        cache: SigningCache,
+    ) {
+        self.expire();
+        match self.sessions.get_mut(&token) {
+            Some(session) => session.signing_cache = Some(cache),
+            None => debug!(
+                "Session {} is locked during signing; {} cached signing keys \
+                 are wiped",
+                token,
+                cache.len()
+            ),
+        }
+    }
+}
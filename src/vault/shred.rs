@@ -0,0 +1,92 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Cryptographic shredding of purged keys. Encrypted private key data of the
+//! purged accounts are overwritten with random bytes in memory and in all
+//! storage replicas controlled by the vault driver before the accounts are
+//! removed, and a shredding certificate is written to the audit log.
+
+use std::fmt::{self, Display, Formatter};
+
+use bitcoin::hashes::sha256;
+use bitcoin::XpubIdentifier;
+use chrono::{DateTime, Utc};
+
+/// Log target used for the audit records
+pub const AUDIT_TARGET: &str = "audit";
+
+/// Account whose encrypted private key was shredded
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Display)]
+#[display("{key_id}:{digest}")]
+pub struct Shredded {
+    /// Extended public key identifier of the account
+    pub key_id: XpubIdentifier,
+
+    /// SHA256 digest of the encrypted private key data before shredding,
+    /// allowing to match the certificate against existing backups
+    pub digest: sha256::Hash,
+}
+
+/// Certificate proving that the encrypted data of purged keys were destroyed
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Certificate {
+    /// Identifier of the purged keyring or account
+    pub key_id: XpubIdentifier,
+
+    /// Time of the shredding
+    pub timestamp: DateTime<Utc>,
+
+    /// Shredded accounts, including sub-accounts of a purged keyring
+    pub shredded: Vec<Shredded>,
+
+    /// Storage replicas where the data were overwritten
+    pub replicas: Vec<String>,
+}
+
+impl Certificate {
+    pub fn with(
+        key_id: XpubIdentifier,
+        shredded: Vec<Shredded>,
+        replicas: Vec<String>,
+    ) -> Self {
+        Self {
+            key_id,
+            timestamp: Utc::now(),
+            shredded,
+            replicas,
+        }
+    }
+
+    /// Writes certificate to the audit log
+    pub fn record(&self) {
+        info!(target: AUDIT_TARGET, "{}", self);
+    }
+}
+
+impl Display for Certificate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "shredded {} at {}; accounts: [",
+            self.key_id,
+            self.timestamp.to_rfc3339()
+        )?;
+        for (index, shredded) in self.shredded.iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            Display::fmt(shredded, f)?;
+        }
+        write!(f, "]; replicas: [{}]", self.replicas.join(", "))
+    }
+}
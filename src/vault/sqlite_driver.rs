@@ -0,0 +1,325 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! SQLite storage driver for private key vault. Keyrings and their accounts
+//! are kept in separate tables, and each update of the vault is performed as
+//! a single database transaction, so the vault is never left partially
+//! written. Accounts are stored in strict encoding; the account names and
+//! extended public keys are duplicated into separate columns so the database
+//! can be inspected with generic SQLite tools.
+
+use ::core::any::Any;
+use ::std::collections::BTreeMap;
+use ::std::path::Path;
+use ::std::str::FromStr;
+use ::std::sync::Mutex;
+
+use bitcoin::hashes::hex::FromHex;
+use bitcoin::util::bip32::{DerivationPath, Fingerprint};
+use lnpbp::chain::Chain;
+use lnpbp::strict_encoding::{strict_deserialize, strict_serialize};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+
+use super::{driver, Driver, FileDriver, Keyring, KeysAccount};
+use crate::error::BootstrapError;
+
+/// Version of the database schema, stored in the `metadata` table
+pub const SCHEMA_VERSION: u32 = 2;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS metadata (
+        key TEXT PRIMARY KEY NOT NULL,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS keyrings (
+        id TEXT PRIMARY KEY NOT NULL,
+        position INTEGER NOT NULL,
+        origin_fingerprint TEXT,
+        origin_path TEXT,
+        chain TEXT
+    );
+    -- Master account of each keyring is stored with `m` derivation path
+    CREATE TABLE IF NOT EXISTS accounts (
+        keyring_id TEXT NOT NULL REFERENCES keyrings(id) ON DELETE CASCADE,
+        derivation TEXT NOT NULL,
+        name TEXT NOT NULL,
+        xpubkey TEXT NOT NULL,
+        data BLOB NOT NULL,
+        PRIMARY KEY (keyring_id, derivation)
+    );
+";
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(crate = "serde_crate")]
+pub struct Config {
+    /// Path to the database file
+    pub path: String,
+
+    /// Path to the file vault (stored in strict encoding), which keyrings
+    /// are imported from when the database is created
+    #[serde(default)]
+    pub migrate_from: Option<String>,
+
+    /// Open the database read-only; set by the daemon running in read-only
+    /// mode
+    #[serde(skip)]
+    pub read_only: bool,
+}
+
+#[derive(Debug, Display)]
+#[display("SqliteDriver({config:?})")]
+pub struct SqliteDriver {
+    // `rusqlite::Connection` is not `Sync`, while drivers are shared
+    // between threads
+    connection: Mutex<Connection>,
+    config: Config,
+}
+
+impl Driver for SqliteDriver {
+    fn init(config: &dyn Any) -> Result<Self, BootstrapError> {
+        let config = config.downcast_ref::<Config>().expect(
+            "`SqliteDriver` must be configured with `sqlite_driver::Config` \
+             object",
+        );
+        info!("Initializing SQLite driver for vault in {:?}", &config.path);
+        if config.read_only {
+            let connection = Connection::open_with_flags(
+                &config.path,
+                OpenFlags::SQLITE_OPEN_READ_ONLY,
+            )
+            .map_err(driver::Error::from)?;
+            let mut me = Self {
+                connection: Mutex::new(connection),
+                config: config.clone(),
+            };
+            me.check_schema()?;
+            return Ok(me);
+        }
+        let exists = Path::new(&config.path).exists();
+        let connection =
+            Connection::open(&config.path).map_err(driver::Error::from)?;
+        // Deleted records are overwritten with zeros, so replaced vault data
+        // do not remain in the database file
+        connection
+            .execute_batch(
+                "PRAGMA foreign_keys = ON; PRAGMA secure_delete = ON;",
+            )
+            .map_err(driver::Error::from)?;
+        connection
+            .execute_batch(SCHEMA)
+            .map_err(driver::Error::from)?;
+        let mut me = Self {
+            connection: Mutex::new(connection),
+            config: config.clone(),
+        };
+        me.check_schema()?;
+        if !exists {
+            match config.migrate_from {
+                Some(ref location) => {
+                    info!("Migrating vault from file {}", location);
+                    let keyrings = FileDriver::read_snapshot(location)?;
+                    me.store(&keyrings)?;
+                    info!("{} keyrings migrated", keyrings.len());
+                }
+                None => warn!(
+                    "Vault database does not exist: initializing empty vault"
+                ),
+            }
+        }
+        Ok(me)
+    }
+
+    fn load(&mut self) -> Result<Vec<Keyring>, driver::Error> {
+        debug!("Loading vault from database {}", self.config.path);
+        let connection = self.connection.get_mut().expect("poisoned mutex");
+
+        let mut stmt = connection.prepare(
+            "SELECT id, origin_fingerprint, origin_path, chain FROM keyrings
+             ORDER BY position",
+        )?;
+        let rows = stmt.query_map(params![], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })?;
+        let mut keyrings = vec![];
+        for row in rows {
+            let (id, fingerprint, path, chain) = row?;
+            let key_source = match (fingerprint, path) {
+                (Some(fingerprint), Some(path)) => Some((
+                    Fingerprint::from_hex(&fingerprint)?,
+                    DerivationPath::from_str(&path)?,
+                )),
+                _ => None,
+            };
+            let chain = chain
+                .map(|chain| {
+                    Chain::from_str(&chain).map_err(|_| {
+                        driver::Error::Corrupted(format!(
+                            "keyring {} has unknown chain {}",
+                            id, chain
+                        ))
+                    })
+                })
+                .transpose()?;
+
+            let mut stmt = connection.prepare(
+                "SELECT derivation, data FROM accounts WHERE keyring_id = ?",
+            )?;
+            let accounts = stmt.query_map(params![id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?))
+            })?;
+            let mut master_account = None;
+            let mut sub_accounts = BTreeMap::new();
+            for account in accounts {
+                let (derivation, data) = account?;
+                let account: KeysAccount = strict_deserialize(&data)?;
+                let derivation = DerivationPath::from_str(&derivation)?;
+                if derivation == DerivationPath::master() {
+                    master_account = Some(account);
+                } else {
+                    sub_accounts.insert(derivation, account);
+                }
+            }
+            let master_account = master_account.ok_or_else(|| {
+                driver::Error::Corrupted(format!(
+                    "keyring {} has no master account",
+                    id
+                ))
+            })?;
+            keyrings.push(Keyring::with_accounts(
+                master_account,
+                key_source,
+                sub_accounts,
+                chain,
+            ));
+        }
+        trace!("Vault loaded: {:?}", keyrings);
+        Ok(keyrings)
+    }
+
+    fn store(&mut self, accounts: &Vec<Keyring>) -> Result<(), driver::Error> {
+        debug!("Storing vault data to the database {}", self.config.path);
+        trace!("Current vault data: {:?}", accounts);
+        let connection = self.connection.get_mut().expect("poisoned mutex");
+        let tx = connection.transaction()?;
+        tx.execute("DELETE FROM accounts", params![])?;
+        tx.execute("DELETE FROM keyrings", params![])?;
+        for (position, keyring) in accounts.iter().enumerate() {
+            let id = keyring.identifier().to_string();
+            let (fingerprint, path) = match keyring.key_source() {
+                Some((fingerprint, path)) => {
+                    (Some(fingerprint.to_string()), Some(path.to_string()))
+                }
+                None => (None, None),
+            };
+            tx.execute(
+                "INSERT INTO keyrings
+                 (id, position, origin_fingerprint, origin_path, chain)
+                 VALUES (?, ?, ?, ?, ?)",
+                params![
+                    id,
+                    position as i64,
+                    fingerprint,
+                    path,
+                    keyring.chain().to_string()
+                ],
+            )?;
+            let master = (DerivationPath::master(), keyring.master_account());
+            for (derivation, account) in ::std::iter::once(master).chain(
+                keyring
+                    .sub_accounts()
+                    .iter()
+                    .map(|(path, account)| (path.clone(), account)),
+            ) {
+                tx.execute(
+                    "INSERT INTO accounts
+                     (keyring_id, derivation, name, xpubkey, data)
+                     VALUES (?, ?, ?, ?, ?)",
+                    params![
+                        id,
+                        derivation.to_string(),
+                        account.name(),
+                        account.xpubkey().to_string(),
+                        strict_serialize(account)?
+                    ],
+                )?;
+            }
+        }
+        tx.commit()?;
+        trace!("Vault data stored");
+        Ok(())
+    }
+
+    /// Records are replaced within a transaction with `secure_delete` mode
+    /// on, so the old records are zeroed; the database is then vacuumed to
+    /// rewrite the file without the free pages.
+    fn shred(
+        &mut self,
+        accounts: &Vec<Keyring>,
+    ) -> Result<Vec<String>, driver::Error> {
+        debug!("Overwriting vault database {}", self.config.path);
+        self.store(accounts)?;
+        self.connection
+            .get_mut()
+            .expect("poisoned mutex")
+            .execute_batch("VACUUM")?;
+        Ok(vec![self.config.path.clone()])
+    }
+}
+
+impl SqliteDriver {
+    fn check_schema(&mut self) -> Result<(), driver::Error> {
+        let connection = self.connection.get_mut().expect("poisoned mutex");
+        let version = connection
+            .query_row(
+                "SELECT value FROM metadata WHERE key = 'schema_version'",
+                params![],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+        match version {
+            None if self.config.read_only => Err(SchemaVersion(s!("none")))?,
+            None => {
+                connection.execute(
+                    "INSERT INTO metadata (key, value)
+                     VALUES ('schema_version', ?)",
+                    params![SCHEMA_VERSION.to_string()],
+                )?;
+            }
+            Some(version) if version == SCHEMA_VERSION.to_string() => {}
+            // Version 1 databases do not keep keyring chains, which are
+            // detected from the master keys until the vault is stored again
+            Some(version) if version == "1" && !self.config.read_only => {
+                info!("Upgrading vault database schema to {}", SCHEMA_VERSION);
+                connection.execute_batch(
+                    "ALTER TABLE keyrings ADD COLUMN chain TEXT;",
+                )?;
+                connection.execute(
+                    "UPDATE metadata SET value = ?
+                     WHERE key = 'schema_version'",
+                    params![SCHEMA_VERSION.to_string()],
+                )?;
+            }
+            Some(version) => Err(SchemaVersion(version))?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display("vault database has unsupported schema version {0}")]
+struct SchemaVersion(String);
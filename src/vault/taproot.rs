@@ -0,0 +1,226 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Taproot (BIP340/341) primitives required for signing key-path spends of
+//! P2TR outputs: tagged hashes, x-only keys, key tweaking and signature hash
+//! computation.
+
+use bitcoin::consensus::encode::Encodable;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::{self, schnorrsig};
+use bitcoin::util::psbt::{self, raw, PartiallySignedTransaction};
+use bitcoin::{Script, TxOut};
+use secp256k1::rand::{thread_rng, RngCore};
+
+use super::keymgm::Error;
+
+/// PSBT input key type for a taproot key spend signature, defined in BIP-371
+pub const PSBT_IN_TAP_KEY_SIG: u8 = 0x13;
+
+/// Sighash type meaning "sign all"; the signature for this type is serialized
+/// without the trailing sighash byte
+pub const SIGHASH_DEFAULT: u8 = 0x00;
+
+const SIGHASH_NONE: u8 = 0x02;
+const SIGHASH_SINGLE: u8 = 0x03;
+const SIGHASH_ANYONECANPAY: u8 = 0x80;
+
+/// Computes BIP-340 tagged hash of the concatenation of the provided data
+/// slices
+pub fn tagged_hash(tag: &str, data: &[&[u8]]) -> sha256::Hash {
+    let tag_hash = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(&tag_hash[..]);
+    engine.input(&tag_hash[..]);
+    data.iter().for_each(|slice| engine.input(slice));
+    sha256::Hash::from_engine(engine)
+}
+
+/// Detects whether a given script pubkey is a segwit v1 (P2TR) output
+pub fn is_p2tr(script: &Script) -> bool {
+    let bytes = script.as_bytes();
+    bytes.len() == 34 && bytes[0] == 0x51 && bytes[1] == 0x20
+}
+
+/// Returns output key from a P2TR script pubkey
+pub fn output_key(script: &Script) -> Option<schnorrsig::PublicKey> {
+    if !is_p2tr(script) {
+        return None;
+    }
+    schnorrsig::PublicKey::from_slice(&script.as_bytes()[2..]).ok()
+}
+
+/// Returns the output spent by the PSBT input with a given index, taking it
+/// either from the witness UTXO or from the full previous transaction
+pub fn spent_output(
+    psbt: &PartiallySignedTransaction,
+    index: usize,
+) -> Option<TxOut> {
+    let input = psbt.inputs.get(index)?;
+    if let Some(ref txout) = input.witness_utxo {
+        return Some(txout.clone());
+    }
+    let vout = psbt
+        .global
+        .unsigned_tx
+        .input
+        .get(index)?
+        .previous_output
+        .vout;
+    input
+        .non_witness_utxo
+        .as_ref()?
+        .output
+        .get(vout as usize)
+        .cloned()
+}
+
+/// Detects whether a PSBT input spends P2TR output
+pub fn is_taproot_input(
+    psbt: &PartiallySignedTransaction,
+    index: usize,
+) -> bool {
+    spent_output(psbt, index)
+        .map(|txout| is_p2tr(&txout.script_pubkey))
+        .unwrap_or_default()
+}
+
+/// Creates key pair for a key-path spend from an internal secret key,
+/// applying BIP-341 tweak for an output without script tree
+pub fn tweaked_keypair(
+    seckey: &secp256k1::SecretKey,
+) -> Result<schnorrsig::KeyPair, Error> {
+    let mut keypair =
+        schnorrsig::KeyPair::from_seckey_slice(&crate::SECP256K1, &seckey[..])?;
+    let internal_key =
+        schnorrsig::PublicKey::from_keypair(&crate::SECP256K1, &keypair);
+    let tweak = tagged_hash("TapTweak", &[&internal_key.serialize()]);
+    keypair.tweak_add_assign(&crate::SECP256K1, &tweak[..])?;
+    Ok(keypair)
+}
+
+/// Computes BIP-341 signature hash for a key-path spend of the PSBT input
+/// with a given `index` (no annex, no script path).
+///
+/// All PSBT inputs must provide information on the outputs they spend,
+/// otherwise [`Error::PsbtInputData`] is returned.
+pub fn sighash(
+    psbt: &PartiallySignedTransaction,
+    index: usize,
+    sighash_type: u8,
+) -> Result<sha256::Hash, Error> {
+    const ERR: &'static str = "in-memory encoders do not error";
+
+    let tx = &psbt.global.unsigned_tx;
+    let prevouts = (0..tx.input.len())
+        .map(|i| spent_output(psbt, i).ok_or(Error::PsbtInputData(i)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let base_type = sighash_type & 0x03;
+    let anyone_can_pay = sighash_type & SIGHASH_ANYONECANPAY != 0;
+
+    let mut msg = vec![0x00u8, sighash_type];
+    tx.version.consensus_encode(&mut msg).expect(ERR);
+    tx.lock_time.consensus_encode(&mut msg).expect(ERR);
+
+    if !anyone_can_pay {
+        let mut prev_engine = sha256::Hash::engine();
+        let mut amount_engine = sha256::Hash::engine();
+        let mut script_engine = sha256::Hash::engine();
+        let mut seq_engine = sha256::Hash::engine();
+        for (txin, prevout) in tx.input.iter().zip(&prevouts) {
+            txin.previous_output
+                .consensus_encode(&mut prev_engine)
+                .expect(ERR);
+            prevout
+                .value
+                .consensus_encode(&mut amount_engine)
+                .expect(ERR);
+            prevout
+                .script_pubkey
+                .consensus_encode(&mut script_engine)
+                .expect(ERR);
+            txin.sequence.consensus_encode(&mut seq_engine).expect(ERR);
+        }
+        msg.extend(&sha256::Hash::from_engine(prev_engine)[..]);
+        msg.extend(&sha256::Hash::from_engine(amount_engine)[..]);
+        msg.extend(&sha256::Hash::from_engine(script_engine)[..]);
+        msg.extend(&sha256::Hash::from_engine(seq_engine)[..]);
+    }
+
+    if base_type != SIGHASH_NONE && base_type != SIGHASH_SINGLE {
+        let mut engine = sha256::Hash::engine();
+        for txout in &tx.output {
+            txout.consensus_encode(&mut engine).expect(ERR);
+        }
+        msg.extend(&sha256::Hash::from_engine(engine)[..]);
+    }
+
+    // spend_type: no annex, key path spend
+    msg.push(0x00);
+
+    if anyone_can_pay {
+        let txin = &tx.input[index];
+        txin.previous_output.consensus_encode(&mut msg).expect(ERR);
+        prevouts[index].value.consensus_encode(&mut msg).expect(ERR);
+        prevouts[index]
+            .script_pubkey
+            .consensus_encode(&mut msg)
+            .expect(ERR);
+        txin.sequence.consensus_encode(&mut msg).expect(ERR);
+    } else {
+        (index as u32).consensus_encode(&mut msg).expect(ERR);
+    }
+
+    if base_type == SIGHASH_SINGLE {
+        let txout = tx.output.get(index).ok_or(Error::PsbtInputData(index))?;
+        let mut engine = sha256::Hash::engine();
+        txout.consensus_encode(&mut engine).expect(ERR);
+        msg.extend(&sha256::Hash::from_engine(engine)[..]);
+    }
+
+    Ok(tagged_hash("TapSighash", &[&msg]))
+}
+
+/// Creates BIP-340 signature over the `digest` with a given key pair, using
+/// random auxiliary data
+pub fn sign(
+    digest: sha256::Hash,
+    keypair: &schnorrsig::KeyPair,
+) -> Result<schnorrsig::Signature, Error> {
+    let mut aux = [0u8; 32];
+    thread_rng().fill_bytes(&mut aux);
+    Ok(crate::SECP256K1.schnorrsig_sign_with_aux_rand(
+        &secp256k1::Message::from_slice(&digest[..])?,
+        keypair,
+        &aux,
+    ))
+}
+
+/// Adds taproot key spend signature to the PSBT input in BIP-371 format
+pub fn add_key_sig(
+    input: &mut psbt::Input,
+    signature: schnorrsig::Signature,
+    sighash_type: u8,
+) {
+    let mut sig = signature[..].to_vec();
+    if sighash_type != SIGHASH_DEFAULT {
+        sig.push(sighash_type);
+    }
+    input.unknown.insert(
+        raw::Key {
+            type_value: PSBT_IN_TAP_KEY_SIG,
+            key: vec![],
+        },
+        sig,
+    );
+}
@@ -11,49 +11,262 @@
 // along with this software.
 // If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
 
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
+use std::convert::TryFrom;
+use std::iter;
+use std::time::Duration;
 
-use bitcoin::hash_types::XpubIdentifier;
+use bitcoin::hash_types::{SigHash, XpubIdentifier};
 use bitcoin::hashes::{sha256, Hash};
-use bitcoin::secp256k1::{PublicKey, SecretKey, Signature};
-use bitcoin::util::bip32::{DerivationPath, ExtendedPrivKey, ExtendedPubKey};
-use bitcoin::util::psbt::PartiallySignedTransaction;
-use bitcoin::SigHashType;
+use bitcoin::secp256k1::{schnorrsig, PublicKey, SecretKey, Signature};
+use bitcoin::util::bip143::SigHashCache;
+use bitcoin::util::bip32::{
+    ChildNumber, DerivationPath, ExtendedPrivKey, ExtendedPubKey, Fingerprint,
+    KeySource,
+};
+use bitcoin::util::psbt::{Input, PartiallySignedTransaction};
+use bitcoin::{Script, SigHashType, Transaction, TxIn, TxOut};
 use lnpbp::chain::{AssetId, Chain};
+use lnpbp::strict_encoding::{strict_deserialize, strict_serialize};
 use slip132::KeyApplication;
 
+#[cfg(feature = "node")]
+use super::interchange;
+use super::keymgm::{Error, SigningCache, UpdateMode, MAX_DERIVATION_RANGE};
+use super::policy::{self, PolicyViolation, Rule, SigningHistory};
+use super::secret::wipe_key;
+use super::shred::Certificate;
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+use super::BrowserDriver;
+#[cfg(feature = "node")]
+use super::FileDriver;
+#[cfg(feature = "os-keychain")]
+use super::OsKeystoreDriver;
+#[cfg(feature = "remote-vault")]
+use super::RemoteDriver;
+#[cfg(feature = "sqlite")]
+use super::SqliteDriver;
 use super::{
-    driver, keymgm::Error, DelegatedDriver, Driver, FileDriver, Keyring,
-    KeysAccount,
+    bip47, bip85, descriptor, driver, identity, journal, ln, multisig, musig,
+    rgb, taproot, Backups, DelegatedDriver, DerivationCache, Driver, Journal,
+    Keyring, KeysAccount, Sandboxed,
 };
+use crate::chain::{self, ChainSource};
 use crate::error::{BootstrapError, RuntimeError};
-use crate::rpc::types::AccountInfo;
+use crate::lifecycle::{Lifecycle, Operation};
+#[cfg(feature = "node")]
+use crate::passphrase::Kdf;
+use crate::rpc::types::{
+    AccountBalance, AccountInfo, AnnouncementSignatures, Bip85Application,
+    Branches, CollisionPolicy, CosignerKey, DerivationTemplate, DerivedKey,
+    IdentityKey, IdentitySignature, InputAnalysis, LnKeySet, MultisigGroup,
+    MultisigId, PaymentCode, PaymentCodeInfo, PaymentDirection, PsbtAnalysis,
+    PsbtInput, PsbtOutput, SharedSecret, SigningPolicy,
+};
+use crate::signed_message::{self, SignatureType};
+
+/// Computes signature hash of a non-taproot PSBT input spending `spent`
+/// output: BIP-143 hash for native and P2SH-wrapped SegWit v0 outputs, and
+/// legacy hash otherwise. The script code of P2WPKH outputs is constructed
+/// from the signing `pubkey`; P2WSH outputs require the witness script.
+pub(crate) fn input_sighash(
+    tx: &Transaction,
+    cache: &mut SigHashCache<&Transaction>,
+    index: usize,
+    input: &Input,
+    spent: &TxOut,
+    pubkey: &bitcoin::PublicKey,
+) -> Result<SigHash, Error> {
+    let script = input.redeem_script.as_ref().unwrap_or(&spent.script_pubkey);
+    if script.is_v0_p2wpkh() {
+        let script_code = Script::new_p2pkh(&pubkey.pubkey_hash());
+        Ok(cache.signature_hash(
+            index,
+            &script_code,
+            spent.value,
+            SigHashType::All,
+        ))
+    } else if script.is_v0_p2wsh() {
+        let script_code = input
+            .witness_script
+            .as_ref()
+            .ok_or(Error::PsbtInputData(index))?;
+        Ok(cache.signature_hash(
+            index,
+            script_code,
+            spent.value,
+            SigHashType::All,
+        ))
+    } else {
+        Ok(tx.signature_hash(index, script, SigHashType::All.as_u32()))
+    }
+}
+
+/// Derivation purposes (BIP-43) of the standard schemes which next path
+/// segment is a SLIP-44 coin type
+const COIN_TYPE_PURPOSES: [u32; 5] = [44, 48, 49, 84, 86];
+
+/// Detects whether a key with `derivation` is used on bitcoin mainnet
+/// (`Some(true)`) or on a test network (`Some(false)`) from the coin type of
+/// the derivation. Returns [`Option::None`] if the derivation does not follow
+/// BIP-44 scheme or has coin type of other chain.
+fn derivation_mainnet(derivation: &DerivationPath) -> Option<bool> {
+    let hardened = |step: Option<&ChildNumber>| match step {
+        Some(ChildNumber::Hardened { index }) => Some(*index),
+        _ => None,
+    };
+    let steps = derivation.as_ref();
+    let purpose = hardened(steps.get(0))?;
+    let coin_type = hardened(steps.get(1))?;
+    if !COIN_TYPE_PURPOSES.contains(&purpose) {
+        return None;
+    }
+    match coin_type {
+        0 => Some(true),
+        1 => Some(false),
+        _ => None,
+    }
+}
 
 pub struct Vault {
     driver: Box<dyn Driver>,
     keyrings: Vec<Keyring>,
+    backups: Option<Backups>,
+    journal: Option<Journal>,
+    history: SigningHistory,
 }
 
 impl Vault {
     pub fn with(config: &driver::Config) -> Result<Self, BootstrapError> {
         let mut driver = match config {
+            #[cfg(feature = "node")]
             driver::Config::File(fdc) => {
                 Box::new(FileDriver::init(fdc)?) as Box<dyn Driver>
             }
             driver::Config::Delegated(dc) => {
                 Box::new(DelegatedDriver::init(dc)?) as Box<dyn Driver>
             }
+            #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+            driver::Config::Browser(bc) => {
+                Box::new(BrowserDriver::init(bc)?) as Box<dyn Driver>
+            }
+            #[cfg(feature = "sqlite")]
+            driver::Config::Sqlite(sdc) => {
+                Box::new(SqliteDriver::init(sdc)?) as Box<dyn Driver>
+            }
+            #[cfg(feature = "os-keychain")]
+            driver::Config::OsKeychain(kc) => {
+                Box::new(OsKeystoreDriver::init(kc)?) as Box<dyn Driver>
+            }
+            #[cfg(feature = "remote-vault")]
+            driver::Config::Remote(rc) => {
+                Box::new(RemoteDriver::init(rc)?) as Box<dyn Driver>
+            }
         };
         let keyrings = driver.load()?;
         Ok(Self {
             driver,
             //keyrings: vec![],
             keyrings,
+            backups: None,
+            journal: None,
+            history: SigningHistory::new(),
         })
     }
 
+    /// Enables writing encrypted backup snapshot after each vault update
+    pub fn enable_backups(&mut self, backups: Backups) {
+        self.backups = Some(backups);
+    }
+
+    /// Enables recording of the vault updates to the hash-chained journal.
+    /// Accounts changed since the last journal record, i.e. by the updates
+    /// made outside of the daemon, are recorded right away.
+    pub fn enable_journal(
+        &mut self,
+        mut journal: Journal,
+    ) -> Result<(), journal::Error> {
+        if journal.records() > 0 {
+            if let Err(err) = journal.check(&self.keyrings) {
+                warn!("Vault is changed outside of the daemon: {}", err);
+            }
+        }
+        journal.record(&self.keyrings)?;
+        self.journal = Some(journal);
+        Ok(())
+    }
+
+    /// Stores vault data with the driver, backs them up and records the
+    /// update to the journal. Failure to write the backup or the journal
+    /// record does not fail the operation, since the vault itself is already
+    /// updated.
+    fn store(&mut self) -> Result<(), driver::Error> {
+        self.driver.store(&self.keyrings)?;
+        if let Some(ref backups) = self.backups {
+            match backups.write(&self.keyrings) {
+                Ok(path) => trace!("Vault is backed up to {}", path.display()),
+                Err(err) => error!("Unable to back up the vault: {}", err),
+            }
+        }
+        if let Some(ref mut journal) = self.journal {
+            match journal.record(&self.keyrings) {
+                Ok(Some(seq)) => {
+                    trace!("Vault update is journaled as #{}", seq)
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    error!("Unable to journal the vault update: {}", err)
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Flushes vault data with the driver on the daemon shutdown
+    pub fn flush(&mut self) -> Result<(), driver::Error> {
+        self.driver.flush()
+    }
+
+    /// Shreds vault data with the driver and removes the backup snapshots,
+    /// which are re-created on the next [`Vault::store`]
+    fn shred(&mut self) -> Result<Vec<String>, driver::Error> {
+        let mut replicas = self.driver.shred(&self.keyrings)?;
+        if let Some(ref backups) = self.backups {
+            replicas.extend(backups.shred()?);
+        }
+        Ok(replicas)
+    }
+
+    /// Returns keyring with a given id, unless it is archived
     pub fn keyring_by_id(&self, key_id: XpubIdentifier) -> Option<&Keyring> {
-        self.keyrings.iter().find(|kr| kr.identifier() == key_id)
+        self.keyrings
+            .iter()
+            .filter(|kr| !kr.is_archived())
+            .find(|kr| kr.identifier() == key_id)
+    }
+
+    /// Returns keyring with a given master key fingerprint, unless it is
+    /// archived
+    pub fn keyring_by_fingerprint(
+        &self,
+        fingerprint: Fingerprint,
+    ) -> Option<&Keyring> {
+        self.keyrings
+            .iter()
+            .filter(|kr| !kr.is_archived())
+            .find(|kr| kr.fingerprint() == fingerprint)
+    }
+
+    /// Returns keyring which contains account `key_id` (including the
+    /// keyring master account), unless the keyring is archived
+    pub fn keyring_by_account(
+        &self,
+        key_id: XpubIdentifier,
+    ) -> Option<&Keyring> {
+        self.keyrings
+            .iter()
+            .filter(|kr| !kr.is_archived())
+            .find(|kr| kr.account_by_id(key_id).is_some())
     }
 
     pub fn keyring_by_id_mut(
@@ -62,6 +275,7 @@ impl Vault {
     ) -> Option<&mut Keyring> {
         self.keyrings
             .iter_mut()
+            .filter(|kr| !kr.is_archived())
             .find(|kr| kr.identifier() == key_id)
     }
 
@@ -69,23 +283,221 @@ impl Vault {
         &self,
         key_id: XpubIdentifier,
     ) -> Option<&KeysAccount> {
-        self.keyrings.iter().find_map(|kr| kr.account_by_id(key_id))
+        self.keyrings
+            .iter()
+            .filter(|kr| !kr.is_archived())
+            .find_map(|kr| kr.account_by_id(key_id))
+            .filter(|account| !account.archived())
+    }
+
+    /// Evaluates signing policies of the accounts which keys are used by the
+    /// PSBT inputs. Returns identifiers and rate limit periods of the
+    /// accounts which signatures have to be recorded in the signing history.
+    fn check_policies(
+        &self,
+        psbt: &PartiallySignedTransaction,
+    ) -> Result<Vec<(XpubIdentifier, Duration)>, PolicyViolation> {
+        let mut rate_limited = vec![];
+        for keyring in self
+            .keyrings
+            .iter()
+            .filter(|kr| !kr.is_archived())
+            .filter(|kr| !kr.master_account().is_watch_only())
+        {
+            let fingerprint = keyring.fingerprint();
+            let master = (DerivationPath::master(), keyring.master_account());
+            let accounts = iter::once(master).chain(
+                keyring
+                    .sub_accounts()
+                    .iter()
+                    .map(|(path, account)| (path.clone(), account)),
+            );
+            for (path, account) in accounts {
+                if account.policy().is_unrestricted() {
+                    continue;
+                }
+                let inputs = psbt
+                    .inputs
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, inp)| {
+                        inp.bip32_derivation.values().any(|(fp, derivation)| {
+                            *fp == fingerprint
+                                && derivation
+                                    .as_ref()
+                                    .starts_with(path.as_ref())
+                        })
+                    })
+                    .map(|(index, _)| index)
+                    .collect::<Vec<_>>();
+                if inputs.is_empty() {
+                    continue;
+                }
+                policy::evaluate(
+                    account.identifier(),
+                    account.policy(),
+                    psbt,
+                    &inputs,
+                    fingerprint,
+                    &self.history,
+                )?;
+                if let Some(rate_limit) = account.policy().rate_limit {
+                    rate_limited.push((
+                        account.identifier(),
+                        Duration::from_secs(rate_limit.period),
+                    ));
+                }
+            }
+        }
+        Ok(rate_limited)
+    }
+
+    /// Checks that the PSBT inputs using keys of the vault accounts which are
+    /// cosigners of multisig groups spend scripts of these groups
+    fn check_multisig(
+        &self,
+        psbt: &PartiallySignedTransaction,
+    ) -> Result<(), multisig::Error> {
+        let groups = self.multisig_groups();
+        if groups.is_empty() {
+            return Ok(());
+        }
+        let is_local = |cosigner: &CosignerKey| {
+            self.account_by_id(cosigner.xpubkey.identifier()).is_some()
+        };
+        for (index, input) in psbt.inputs.iter().enumerate() {
+            if taproot::is_taproot_input(psbt, index) {
+                continue;
+            }
+            let mut involved = groups
+                .iter()
+                .filter(|group| multisig::involves(group, input, &is_local))
+                .peekable();
+            if involved.peek().is_some()
+                && !involved.any(|group| multisig::matches(group, input))
+            {
+                return Err(multisig::Error::ScriptMismatch(index));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the keyrings which keys are used by the PSBT inputs are
+    /// created for the network of the PSBT. PSBT does not specify its
+    /// network, so it is detected from the extended public keys in the PSBT
+    /// global map and from the coin types of the input key derivations.
+    /// Keyrings of the chains which are not bitcoin networks are not checked.
+    fn check_chains(
+        &self,
+        psbt: &PartiallySignedTransaction,
+    ) -> Result<(), Error> {
+        let global = psbt
+            .global
+            .xpub
+            .keys()
+            .map(|xpub| xpub.network == bitcoin::Network::Bitcoin)
+            .collect::<HashSet<_>>();
+        for (index, input) in psbt.inputs.iter().enumerate() {
+            for (fingerprint, derivation) in input.bip32_derivation.values() {
+                let keyring = match self.keyrings.iter().find(|keyring| {
+                    keyring.fingerprint() == *fingerprint
+                        && !keyring.is_archived()
+                        && !keyring.master_account().is_watch_only()
+                }) {
+                    Some(keyring) => keyring,
+                    None => continue,
+                };
+                let chain = keyring.chain();
+                let mainnet = match bitcoin::Network::try_from(&chain) {
+                    Ok(network) => network == bitcoin::Network::Bitcoin,
+                    Err(_) => continue,
+                };
+                if global.iter().any(|global| *global != mainnet)
+                    || derivation_mainnet(derivation)
+                        .map_or(false, |used| used != mainnet)
+                {
+                    return Err(Error::ChainMismatch(index, chain));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that PSBT inputs with RGB asset transitions are signed only by
+    /// the accounts bound to the transferred assets. The account signing an
+    /// input is the deepest keyring account which derivation path is a
+    /// prefix of the input key derivation.
+    fn check_assets(
+        &self,
+        psbt: &PartiallySignedTransaction,
+    ) -> Result<(), Error> {
+        let mut mismatches = HashSet::new();
+        for input in &psbt.inputs {
+            let assets = rgb::input_assets(input);
+            if assets.is_empty() {
+                continue;
+            }
+            for (fingerprint, derivation) in input.bip32_derivation.values() {
+                let keyring = match self.keyrings.iter().find(|keyring| {
+                    keyring.fingerprint() == *fingerprint
+                        && !keyring.is_archived()
+                        && !keyring.master_account().is_watch_only()
+                }) {
+                    Some(keyring) => keyring,
+                    None => continue,
+                };
+                let account = keyring.account_by_derivation(derivation);
+                mismatches.extend(assets.difference(account.assets()));
+            }
+        }
+        if !mismatches.is_empty() {
+            return Err(Error::AssetIds(mismatches));
+        }
+        Ok(())
+    }
+
+    /// Detects whether the vault holds private keys, with which
+    /// [`Vault::verify_decryption_key`] verifies the decryption key
+    pub fn has_private_keys(&self) -> bool {
+        self.keyrings
+            .iter()
+            .any(|kr| !kr.is_archived() && !kr.master_account().is_watch_only())
+    }
+
+    /// Checks that the `decryption_key` is able to decrypt vault data. Since
+    /// all keyrings are encrypted with the same key, it is sufficient to
+    /// check the first of them; an empty vault accepts any key.
+    pub fn verify_decryption_key(
+        &self,
+        decryption_key: &mut SecretKey,
+    ) -> Result<(), RuntimeError> {
+        match self.keyrings.iter().find(|kr| {
+            !kr.is_archived() && !kr.master_account().is_watch_only()
+        }) {
+            Some(keyring) => Ok(keyring
+                .master_account()
+                .verify_decryption_key(decryption_key)?),
+            None => Ok(()),
+        }
     }
 }
 
 // API implementation
 impl Vault {
     pub fn list(&self) -> Result<Vec<AccountInfo>, RuntimeError> {
+        let keyrings = self.keyrings.iter().filter(|kr| !kr.is_archived());
         let mut list: Vec<_> =
-            self.keyrings.iter().map(AccountInfo::from).collect();
-        list.extend(self.keyrings.iter().flat_map(|keyring| {
+            keyrings.clone().map(AccountInfo::from).collect();
+        list.extend(keyrings.flat_map(|keyring| {
             keyring
                 .sub_accounts()
                 .iter()
+                .filter(|(_, account)| !account.archived())
                 .map(|(path, account)| {
                     let mut info = AccountInfo::from(account);
                     info.key_source =
                         Some((keyring.fingerprint(), path.clone()));
+                    info.chain = Some(keyring.chain());
                     info
                 })
                 .collect::<Vec<_>>()
@@ -93,6 +505,258 @@ impl Vault {
         Ok(list)
     }
 
+    /// Returns number of keyrings and keys accounts in the vault, not
+    /// counting archived ones
+    pub fn count(&self) -> (usize, usize) {
+        let keyrings = self.keyrings.iter().filter(|kr| !kr.is_archived());
+        let accounts = keyrings
+            .clone()
+            .map(|keyring| {
+                1 + keyring
+                    .sub_accounts()
+                    .values()
+                    .filter(|account| !account.archived())
+                    .count()
+            })
+            .sum();
+        (keyrings.count(), accounts)
+    }
+
+    pub fn list_with_balances(
+        &self,
+        source: &dyn ChainSource,
+        gap_limit: u32,
+    ) -> Result<Vec<AccountBalance>, RuntimeError> {
+        self.list()?
+            .into_iter()
+            .map(|info| {
+                let account =
+                    self.account_by_id(info.id).ok_or(Error::NotFound)?;
+                let stats = match account.application().map(|application| {
+                    chain::scan_account(
+                        source,
+                        account.xpubkey(),
+                        application,
+                        account.branches(),
+                        gap_limit,
+                    )
+                }) {
+                    Some(Ok(stats)) => stats,
+                    Some(Err(chain::Error::MultisigApplication(_))) | None => {
+                        warn!(
+                            "Account {} can't be scanned for balance since \
+                             it has no single-key application defined",
+                            info.id
+                        );
+                        chain::ScriptStats::default()
+                    }
+                    Some(Err(err)) => Err(err)?,
+                };
+                Ok(AccountBalance {
+                    info,
+                    tx_count: stats.tx_count,
+                    received: stats.received,
+                    balance: stats.balance,
+                })
+            })
+            .collect()
+    }
+
+    /// Discovers sub-accounts of the keyring `root` which were used on-chain,
+    /// following BIP-44 account discovery procedure for each of the
+    /// [`chain::DISCOVERY_PURPOSES`]: accounts `m/purpose'/coin'/account'`
+    /// are scanned one by one with the address `gap_limit` until the first
+    /// account with no transactions. Used accounts are added to the keyring
+    /// with the application matching the purpose; accounts already present
+    /// in the keyring are not scanned. Returns information on the added
+    /// accounts.
+    pub fn discover(
+        &mut self,
+        root: XpubIdentifier,
+        source: &dyn ChainSource,
+        gap_limit: u32,
+        decryption_key: &mut SecretKey,
+    ) -> Result<Vec<AccountInfo>, RuntimeError> {
+        let keyring = self.keyring_by_id(root).ok_or(Error::NotFound)?;
+        keyring
+            .master_account()
+            .check_lifecycle(Operation::Derive)?;
+        let coin_type = match keyring.master_xpubkey().network {
+            bitcoin::Network::Bitcoin => 0,
+            _ => 1,
+        };
+
+        // Master key and the coin type keys are decrypted and derived once
+        // for all scanned accounts
+        let mut cache = DerivationCache::with(decryption_key);
+        let mut discovered = vec![];
+        for (purpose, application) in &chain::DISCOVERY_PURPOSES {
+            for index in 0..(1u32 << 31) {
+                let derivation = DerivationPath::from(vec![
+                    ChildNumber::Hardened { index: *purpose },
+                    ChildNumber::Hardened { index: coin_type },
+                    ChildNumber::Hardened { index },
+                ]);
+                if keyring.is_derivation_used(&derivation) {
+                    trace!("Account {} is already known", derivation);
+                    continue;
+                }
+                let name = format!("Account {}", derivation);
+                let (derivation, mut account) = keyring.derive_account_cached(
+                    derivation,
+                    name,
+                    None::<String>,
+                    Default::default(),
+                    &mut cache,
+                )?;
+                account.set_application(*application);
+                let stats = chain::scan_account(
+                    source,
+                    account.xpubkey(),
+                    *application,
+                    account.branches(),
+                    gap_limit,
+                )?;
+                if stats.tx_count == 0 {
+                    debug!("Account {} was never used", derivation);
+                    break;
+                }
+                info!(
+                    "Discovered account {} with {} transactions",
+                    derivation, stats.tx_count
+                );
+                discovered.push((derivation, account));
+            }
+        }
+        drop(cache);
+
+        let keyring = self
+            .keyring_by_id_mut(root)
+            .expect("keyring presence is checked above");
+        let mut added = vec![];
+        for (derivation, account) in discovered {
+            let account = keyring.add_account(derivation, account)?;
+            added.push(AccountInfo::from(account));
+        }
+        if !added.is_empty() {
+            self.store()?;
+        }
+        Ok(added)
+    }
+
+    /// Writes backup snapshot of the current vault state, returning path to
+    /// the snapshot
+    pub fn backup(&self) -> Result<String, RuntimeError> {
+        let backups =
+            self.backups.as_ref().ok_or(RuntimeError::BackupsDisabled)?;
+        let path = backups.write(&self.keyrings)?;
+        info!("Vault is backed up to {}", path.display());
+        Ok(path.display().to_string())
+    }
+
+    /// Replaces all vault data with the content of the backup snapshot,
+    /// decrypting it with `decryption_key`
+    pub fn restore(
+        &mut self,
+        snapshot: &[u8],
+        decryption_key: &SecretKey,
+    ) -> Result<Vec<AccountInfo>, RuntimeError> {
+        let keyrings = Backups::read(snapshot, decryption_key)?;
+        warn!(
+            "Restoring vault from backup with {} keyrings; {} keyrings are \
+             replaced",
+            keyrings.len(),
+            self.keyrings.len()
+        );
+        self.replace(keyrings)?;
+        self.list()
+    }
+
+    /// Returns strict-encoded vault data for a front daemon using remote
+    /// vault driver
+    pub fn export_data(&self) -> Result<Vec<u8>, RuntimeError> {
+        Ok(strict_serialize(&self.keyrings).map_err(driver::Error::from)?)
+    }
+
+    /// Replaces vault data with the strict-encoded data stored by a front
+    /// daemon using remote vault driver
+    pub fn import_data(&mut self, data: &[u8]) -> Result<(), RuntimeError> {
+        let keyrings = strict_deserialize(data).map_err(driver::Error::from)?;
+        self.replace(keyrings)?;
+        Ok(())
+    }
+
+    /// Returns portable dump of all vault keyrings, optionally encrypted with
+    /// the passphrase; see [`interchange`] for the format
+    #[cfg(feature = "node")]
+    pub fn export_dump(
+        &self,
+        encryption: Option<(&str, Kdf)>,
+    ) -> Result<Vec<u8>, RuntimeError> {
+        Ok(interchange::encode(&self.keyrings, encryption)?)
+    }
+
+    /// Imports keyrings from the portable vault `dump`. With `replace` set
+    /// all vault keyrings are replaced with the dump content; otherwise
+    /// keyrings already present in the vault are skipped. Private keys of
+    /// the dump must be encrypted with the key matching `decryption_key`,
+    /// which is wiped after the check.
+    #[cfg(feature = "node")]
+    pub fn import_dump(
+        &mut self,
+        dump: &[u8],
+        passphrase: Option<&str>,
+        replace: bool,
+        decryption_key: &mut SecretKey,
+    ) -> Result<Vec<AccountInfo>, RuntimeError> {
+        let keyrings = interchange::decode(dump, passphrase)?;
+        let result = keyrings
+            .iter()
+            .filter(|kr| !kr.master_account().is_watch_only())
+            .try_for_each(|keyring| {
+                let mut check_key = *decryption_key;
+                keyring
+                    .master_account()
+                    .verify_decryption_key(&mut check_key)
+            });
+        wipe_key(decryption_key);
+        result?;
+
+        if replace {
+            warn!(
+                "Importing vault dump with {} keyrings; {} keyrings are \
+                 replaced",
+                keyrings.len(),
+                self.keyrings.len()
+            );
+            self.replace(keyrings)?;
+        } else {
+            let known: HashSet<_> =
+                self.keyrings.iter().map(Keyring::identifier).collect();
+            let (skipped, added): (Vec<_>, Vec<_>) = keyrings
+                .into_iter()
+                .partition(|kr| known.contains(&kr.identifier()));
+            info!(
+                "Importing {} keyrings from vault dump, skipping {} keyrings \
+                 already present in the vault",
+                added.len(),
+                skipped.len()
+            );
+            self.keyrings.extend(added);
+            self.store()?;
+        }
+        self.list()
+    }
+
+    fn replace(&mut self, keyrings: Vec<Keyring>) -> Result<(), driver::Error> {
+        self.keyrings = keyrings;
+        self.store()
+    }
+
+    /// Creates new keyring from a random seed, returning information about
+    /// its master account. Fails with [`Error::KnownKey`] in the
+    /// (improbable) case the generated master key is already present in the
+    /// vault.
     pub fn seed(
         &mut self,
         name: impl ToString,
@@ -100,7 +764,7 @@ impl Vault {
         chain: &Chain,
         application: KeyApplication,
         encryption_key: PublicKey,
-    ) -> Result<(), RuntimeError> {
+    ) -> Result<AccountInfo, RuntimeError> {
         let description =
             description.map(|s| s.to_string()).unwrap_or_default();
         let keyring = Keyring::with(
@@ -111,15 +775,280 @@ impl Vault {
             None,
             encryption_key,
         )?;
+        let id = keyring.identifier();
+        if self.account_by_id(id).is_some() {
+            return Err(Error::KnownKey(id).into());
+        }
+        let info = AccountInfo::from(&keyring);
         self.keyrings.push(keyring);
         trace!(
             "New keyring created from a seed; total number of keyring is {}",
             self.keyrings.len()
         );
-        self.driver.store(&self.keyrings)?;
-        Ok(())
+        self.store()?;
+        Ok(info)
+    }
+
+    /// Handles import of the key `id` which is already present in the vault
+    /// according to the `collision` policy, returning information on the
+    /// existing account. Private key of the existing watch-only account is
+    /// never added, only the account metadata are updated.
+    fn resolve_collision(
+        &mut self,
+        id: XpubIdentifier,
+        collision: CollisionPolicy,
+        name: impl ToString,
+        details: Option<impl ToString>,
+        application: Option<KeyApplication>,
+    ) -> Result<AccountInfo, RuntimeError> {
+        let account = self
+            .keyrings
+            .iter_mut()
+            .filter(|kr| !kr.is_archived())
+            .find_map(|kr| kr.account_by_id_mut(id))
+            .filter(|account| !account.archived())
+            .ok_or(Error::NotFound)?;
+        match collision {
+            CollisionPolicy::Reject => return Err(Error::KnownKey(id).into()),
+            CollisionPolicy::Skip => {
+                debug!("Key {} is already known to the vault", id)
+            }
+            CollisionPolicy::Merge => {
+                account.update(Some(name), details, None, UpdateMode::Add)?;
+                if let (None, Some(application)) =
+                    (account.application(), application)
+                {
+                    account.set_application(application);
+                }
+                info!("Imported metadata are merged into account {}", id);
+            }
+            CollisionPolicy::Alias => {
+                if account.add_alias(name) {
+                    info!("New alias is added to account {}", id);
+                }
+            }
+        }
+        Ok(self
+            .list()?
+            .into_iter()
+            .find(|info| info.id == id)
+            .expect("account presence is checked above"))
     }
 
+    /// Creates new keyring from an existing extended private key, encrypting
+    /// it with `encryption_key`. If the key is already present in the vault,
+    /// including watch-only accounts, the import is handled according to the
+    /// `collision` policy.
+    pub fn import_xpriv(
+        &mut self,
+        xprivkey: ExtendedPrivKey,
+        key_source: Option<KeySource>,
+        application: Option<KeyApplication>,
+        name: impl ToString,
+        details: Option<impl ToString>,
+        collision: CollisionPolicy,
+        encryption_key: PublicKey,
+    ) -> Result<AccountInfo, RuntimeError> {
+        let id = ExtendedPubKey::from_private(&crate::SECP256K1, &xprivkey)
+            .identifier();
+        if self.account_by_id(id).is_some() {
+            let info = self.resolve_collision(
+                id,
+                collision,
+                name,
+                details,
+                application,
+            )?;
+            self.store()?;
+            return Ok(info);
+        }
+        let keyring = Keyring::from_xpriv(
+            name,
+            details.map(|s| s.to_string()).unwrap_or_default(),
+            application,
+            xprivkey,
+            key_source,
+            encryption_key,
+        )?;
+        let info = AccountInfo::from(&keyring);
+        self.keyrings.push(keyring);
+        info!("Imported keyring {} from extended private key", id);
+        self.store()?;
+        Ok(info)
+    }
+
+    /// Imports extended public keys from a list of output descriptors as
+    /// watch-only accounts. Keys originating from the same master key
+    /// fingerprint are grouped into a single keyring, which is keyed by the
+    /// fingerprint of its `key_source`: the key with the shortest origin
+    /// path becomes keyring master account, and keys which origin paths
+    /// extend it become its sub-accounts. Sub-accounts derived with normal
+    /// derivation steps only must be derivable from the master account.
+    /// Keys already present in the vault are handled according
+    /// to the `collision` policy; with [`CollisionPolicy::Reject`] nothing is
+    /// imported if any of the keys is known.
+    ///
+    /// Returns information on all newly created accounts and on the existing
+    /// accounts updated by the import.
+    pub fn import_descriptors(
+        &mut self,
+        descriptors: &[String],
+        collision: CollisionPolicy,
+    ) -> Result<Vec<AccountInfo>, RuntimeError> {
+        let mut keys = vec![];
+        for descriptor in descriptors {
+            keys.extend(descriptor::parse(descriptor)?);
+        }
+        keys.sort_by_key(descriptor::DescriptorKey::depth);
+        if collision == CollisionPolicy::Reject {
+            if let Some(id) = keys
+                .iter()
+                .map(|key| key.xpubkey.identifier())
+                .find(|id| self.account_by_id(*id).is_some())
+            {
+                return Err(Error::KnownKey(id).into());
+            }
+        }
+
+        let mut imported = vec![];
+        for key in keys {
+            let id = key.xpubkey.identifier();
+            let name = match key.origin {
+                Some((fingerprint, ref path)) => {
+                    format!("[{}]{}", fingerprint, path)
+                }
+                None => key.xpubkey.fingerprint().to_string(),
+            };
+            let details = key.descriptor.clone();
+            if self.account_by_id(id).is_some() {
+                let info = self.resolve_collision(
+                    id,
+                    collision,
+                    name,
+                    Some(details),
+                    key.application,
+                )?;
+                if collision != CollisionPolicy::Skip {
+                    imported.push(info);
+                }
+                continue;
+            }
+            imported.push(self.add_watch_only(key, name, details)?);
+        }
+
+        self.store()?;
+        Ok(imported)
+    }
+
+    /// Imports extended public key as a watch-only account. If the key
+    /// originates from the master key fingerprint of some existing
+    /// watch-only keyring under its origin path, the account is added as its
+    /// sub-account; otherwise a new watch-only keyring is created.
+    /// Import of the key already present in the vault is handled according
+    /// to the `collision` policy.
+    pub fn import_xpub(
+        &mut self,
+        xpubkey: ExtendedPubKey,
+        key_source: Option<KeySource>,
+        application: Option<KeyApplication>,
+        name: impl ToString,
+        details: Option<impl ToString>,
+        collision: CollisionPolicy,
+    ) -> Result<AccountInfo, RuntimeError> {
+        let id = xpubkey.identifier();
+        if self.account_by_id(id).is_some() {
+            let info = self.resolve_collision(
+                id,
+                collision,
+                name,
+                details,
+                application,
+            )?;
+            self.store()?;
+            return Ok(info);
+        }
+        let key = descriptor::DescriptorKey {
+            xpubkey,
+            origin: key_source,
+            application,
+            descriptor: String::new(),
+        };
+        let details = details.map(|s| s.to_string()).unwrap_or_default();
+        let info = self.add_watch_only(key, name, details)?;
+        self.store()?;
+        Ok(info)
+    }
+
+    fn add_watch_only(
+        &mut self,
+        key: descriptor::DescriptorKey,
+        name: impl ToString,
+        details: impl ToString,
+    ) -> Result<AccountInfo, RuntimeError> {
+        let account = KeysAccount::watch_only(
+            name,
+            details,
+            key.xpubkey,
+            key.application,
+        );
+        let mut info = AccountInfo::from(&account);
+
+        let origin_path = key
+            .origin
+            .as_ref()
+            .map(|(_, path)| path.as_ref().to_vec())
+            .unwrap_or_default();
+        let parent = self
+            .keyrings
+            .iter_mut()
+            .filter(|kr| {
+                !kr.is_archived() && kr.master_account().is_watch_only()
+            })
+            .find_map(|kr| {
+                let (fingerprint, path) = kr
+                    .key_source()
+                    .clone()
+                    .unwrap_or((kr.fingerprint(), DerivationPath::master()));
+                if fingerprint != key.master_fingerprint()
+                    || !origin_path.starts_with(path.as_ref())
+                {
+                    return None;
+                }
+                let relative = &origin_path[path.as_ref().len()..];
+                // Keys derived with hardened steps can't be checked against
+                // the keyring master key, so their origin is trusted
+                if relative.iter().any(ChildNumber::is_hardened) {
+                    return Some((kr, DerivationPath::from(relative)));
+                }
+                let relative = DerivationPath::from(relative);
+                match kr
+                    .master_xpubkey()
+                    .derive_pub(&crate::SECP256K1, &relative)
+                {
+                    Ok(xpub) if xpub == key.xpubkey => Some((kr, relative)),
+                    _ => None,
+                }
+            });
+
+        match parent {
+            Some((keyring, relative)) => {
+                info.key_source =
+                    Some((keyring.fingerprint(), relative.clone()));
+                keyring.add_watch_only(relative, account)?;
+            }
+            None => {
+                info.key_source = key.origin.clone();
+                self.keyrings.push(Keyring::watch_only(account, key.origin));
+            }
+        }
+        info!("Imported watch-only account {}", info);
+        Ok(info)
+    }
+
+    /// Derives new sub-account of the keyring `root`. Without
+    /// `decryption_key` the sub-account is watch-only and is derived from
+    /// the extended public key of the closest account, so only normal
+    /// derivation steps are allowed below it.
     pub fn derive(
         &mut self,
         root: XpubIdentifier,
@@ -127,74 +1056,623 @@ impl Vault {
         name: impl ToString,
         details: Option<impl ToString>,
         assets: HashSet<AssetId>,
-        decryption_key: &mut SecretKey,
+        decryption_key: Option<&mut SecretKey>,
     ) -> Result<AccountInfo, RuntimeError> {
         let keyring = self.keyring_by_id_mut(root).ok_or(Error::NotFound)?;
-        let account = keyring.create_account(
+        keyring
+            .master_account()
+            .check_lifecycle(Operation::Derive)?;
+        let account = match decryption_key {
+            Some(decryption_key) => keyring.create_account(
+                path,
+                name,
+                details,
+                assets,
+                decryption_key,
+            )?,
+            None => {
+                keyring.create_account_neutered(path, name, details, assets)?
+            }
+        };
+        let info = AccountInfo::from(account);
+        self.store()?;
+        Ok(info)
+    }
+
+    /// Derives sub-account of the keyring `root` in the same way as
+    /// [`Vault::derive`] does, but does not store it in the vault. The
+    /// returned account is kept in the session sandbox and may be later
+    /// committed with [`Vault::commit_sandbox`].
+    pub fn derive_sandboxed(
+        &self,
+        root: XpubIdentifier,
+        path: DerivationPath,
+        name: impl ToString,
+        details: Option<impl ToString>,
+        assets: HashSet<AssetId>,
+        decryption_key: &mut SecretKey,
+    ) -> Result<Sandboxed, RuntimeError> {
+        let keyring = self.keyring_by_id(root).ok_or(Error::NotFound)?;
+        keyring
+            .master_account()
+            .check_lifecycle(Operation::Derive)?;
+        let (derivation, account) = keyring.derive_account(
             path,
             name,
             details,
             assets,
             decryption_key,
         )?;
-        let info = AccountInfo::from(account);
-        self.driver.store(&self.keyrings)?;
-        Ok(info)
+        Ok(Sandboxed {
+            keyring: root,
+            derivation,
+            account,
+        })
+    }
+
+    /// Adds sandboxed accounts to the vault. All accounts are checked before
+    /// the vault is modified, so either all of them are committed or none.
+    pub fn commit_sandbox(
+        &mut self,
+        sandbox: Vec<Sandboxed>,
+    ) -> Result<Vec<AccountInfo>, RuntimeError> {
+        let mut paths = BTreeSet::new();
+        for item in &sandbox {
+            let keyring =
+                self.keyring_by_id(item.keyring).ok_or(Error::NotFound)?;
+            if keyring.is_derivation_used(&item.derivation)
+                || !paths.insert((item.keyring, item.derivation.clone()))
+            {
+                return Err(Error::DerivationAlreadyUsed.into());
+            }
+        }
+
+        let mut committed = vec![];
+        for item in sandbox {
+            let keyring = self
+                .keyring_by_id_mut(item.keyring)
+                .expect("keyring presence is checked above");
+            let account = keyring.add_account(item.derivation, item.account)?;
+            committed.push(AccountInfo::from(account));
+        }
+        self.store()?;
+        info!("{} sandboxed accounts are committed", committed.len());
+        Ok(committed)
+    }
+
+    /// Derives `count` public keys from the account with a given `id`,
+    /// substituting indexes starting from `start` into the derivation
+    /// `template`. If no template is given, the account external or
+    /// `internal` branch is used. For accounts with single-key application
+    /// addresses are also provided. The `progress` callback is called after
+    /// each key is derived.
+    pub fn derive_range(
+        &self,
+        id: XpubIdentifier,
+        template: Option<&DerivationTemplate>,
+        internal: bool,
+        start: u32,
+        count: u32,
+        progress: &mut dyn FnMut(),
+    ) -> Result<Vec<DerivedKey>, RuntimeError> {
+        if count > MAX_DERIVATION_RANGE {
+            Err(Error::DerivationRange(count))?;
+        }
+        let account = self.account_by_id(id).ok_or(Error::NotFound)?;
+        account.check_lifecycle(Operation::ExportXpub)?;
+        let template = template
+            .cloned()
+            .unwrap_or_else(|| account.branches().template(internal));
+        let xpubkey = account.xpubkey();
+        (start..start.saturating_add(count))
+            .map(|index| {
+                let path = template.path(index).map_err(Error::from)?;
+                let pubkey = xpubkey
+                    .derive_pub(&crate::SECP256K1, &path)
+                    .map_err(Error::from)?
+                    .public_key;
+                let address = account
+                    .application()
+                    .and_then(|application| {
+                        chain::script_pubkey(&pubkey, application).ok()
+                    })
+                    .and_then(|script| {
+                        bitcoin::Address::from_script(&script, xpubkey.network)
+                    })
+                    .map(|address| address.to_string());
+                progress();
+                Ok(DerivedKey {
+                    index,
+                    path,
+                    pubkey,
+                    address,
+                })
+            })
+            .collect()
+    }
+
+    /// Deletes keyring with a given master account `id`. The provided
+    /// `decryption_key` must be able to decrypt the keyring master key. If
+    /// `purge` is set, encrypted keys of the keyring are shredded (see
+    /// [`super::shred`]) and the keyring is removed from the vault storage;
+    /// otherwise it is archived (soft-deleted). Already archived keyrings can
+    /// be purged.
+    pub fn delete_keyring(
+        &mut self,
+        id: XpubIdentifier,
+        purge: bool,
+        decryption_key: &mut SecretKey,
+    ) -> Result<(), RuntimeError> {
+        let keyring = self
+            .keyrings
+            .iter_mut()
+            .find(|kr| kr.identifier() == id)
+            .ok_or(Error::NotFound)?;
+        keyring
+            .master_account()
+            .verify_decryption_key(decryption_key)?;
+        if purge {
+            let shredded = keyring.shred();
+            let replicas = self.shred()?;
+            self.keyrings.retain(|kr| kr.identifier() != id);
+            self.store()?;
+            Certificate::with(id, shredded, replicas).record();
+            info!("Keyring {} is purged from the vault", id);
+        } else {
+            keyring.archive();
+            self.store()?;
+            info!("Keyring {} is archived", id);
+        }
+        Ok(())
+    }
+
+    /// Deletes sub-account with a given `id`. The provided `decryption_key`
+    /// must be able to decrypt the master key of the keyring containing the
+    /// account. If `purge` is set, the account encrypted key is shredded
+    /// (see [`super::shred`]) and the account is removed from the vault
+    /// storage; otherwise it is archived (soft-deleted).
+    pub fn delete_account(
+        &mut self,
+        id: XpubIdentifier,
+        purge: bool,
+        decryption_key: &mut SecretKey,
+    ) -> Result<(), RuntimeError> {
+        let keyring = self
+            .keyrings
+            .iter_mut()
+            .filter(|kr| !kr.is_archived())
+            .find(|kr| kr.account_by_id(id).is_some())
+            .ok_or(Error::NotFound)?;
+        keyring
+            .master_account()
+            .verify_decryption_key(decryption_key)?;
+        if purge {
+            let shredded = keyring.shred_account(id)?;
+            let replicas = self.shred()?;
+            self.keyrings
+                .iter_mut()
+                .filter(|kr| !kr.is_archived())
+                .find(|kr| kr.account_by_id(id).is_some())
+                .ok_or(Error::NotFound)?
+                .delete_account(id, purge)?;
+            self.store()?;
+            Certificate::with(id, shredded.into_iter().collect(), replicas)
+                .record();
+        } else {
+            keyring.delete_account(id, purge)?;
+            self.store()?;
+        }
+        info!(
+            "Account {} is {}",
+            id,
+            if purge { "purged" } else { "archived" }
+        );
+        Ok(())
     }
 
     pub fn xpub(
         &self,
         id: XpubIdentifier,
     ) -> Result<ExtendedPubKey, RuntimeError> {
-        Ok(*self.account_by_id(id).ok_or(Error::NotFound)?.xpubkey())
+        let account = self.account_by_id(id).ok_or(Error::NotFound)?;
+        account.check_lifecycle(Operation::ExportXpub)?;
+        Ok(*account.xpubkey())
+    }
+
+    /// Returns output descriptors for the account with a given `id`, see
+    /// [`descriptor::export`]
+    pub fn descriptors(
+        &self,
+        id: XpubIdentifier,
+    ) -> Result<Vec<String>, RuntimeError> {
+        let (origin, account) = self
+            .keyrings
+            .iter()
+            .filter(|kr| !kr.is_archived())
+            .find_map(|kr| {
+                Some((kr.account_origin(id)?, kr.account_by_id(id)?))
+            })
+            .filter(|(_, account)| !account.archived())
+            .ok_or(Error::NotFound)?;
+        account.check_lifecycle(Operation::ExportXpub)?;
+        Ok(descriptor::export(account, &origin)?)
+    }
+
+    /// Derives Lightning node identity key and the basepoints of the
+    /// `channel` from the account `id`; see [`ln`] module for the details
+    pub fn ln_key_set(
+        &self,
+        id: XpubIdentifier,
+        channel: u32,
+    ) -> Result<LnKeySet, RuntimeError> {
+        let account = self.account_by_id(id).ok_or(Error::NotFound)?;
+        account.check_lifecycle(Operation::ExportXpub)?;
+        Ok(ln::key_set(account.xpubkey(), channel).map_err(Error::from)?)
+    }
+
+    /// Performs BOLT-8 ECDH of the remote node `pubkey` with the node key of
+    /// the account with a given `id`. Fails with policy violation unless the
+    /// account signing policy allows ECDH.
+    pub fn ecdh(
+        &self,
+        id: XpubIdentifier,
+        pubkey: PublicKey,
+        decryption_key: &mut SecretKey,
+    ) -> Result<SharedSecret, RuntimeError> {
+        let account = self.signing_account(id)?;
+        if !account.policy().allow_ecdh {
+            Err(PolicyViolation {
+                account: id,
+                rules: vec![Rule::Ecdh],
+            })?;
+        }
+        Ok(ln::ecdh(account, &pubkey, decryption_key)?)
+    }
+
+    /// Signs BOLT-11 invoice with the node key of the account with a given
+    /// `id`; see [`ln::sign_invoice`]
+    pub fn sign_invoice(
+        &self,
+        id: XpubIdentifier,
+        hrp: &str,
+        data: &[u8],
+        decryption_key: &mut SecretKey,
+    ) -> Result<Vec<u8>, RuntimeError> {
+        let account = self.signing_account(id)?;
+        let signature = ln::sign_invoice(account, hrp, data, decryption_key)?;
+        debug!("Lightning invoice {} is signed", hrp);
+        Ok(signature)
+    }
+
+    /// Signs BOLT-7 channel announcement of the `channel` with the node key
+    /// and the channel funding key of the account with a given `id`
+    pub fn sign_channel_announcement(
+        &self,
+        id: XpubIdentifier,
+        channel: u32,
+        announcement: &[u8],
+        decryption_key: &mut SecretKey,
+    ) -> Result<AnnouncementSignatures, RuntimeError> {
+        let account = self.signing_account(id)?;
+        Ok(ln::sign_announcement(
+            account,
+            channel,
+            announcement,
+            decryption_key,
+        )?)
+    }
+
+    /// Signs BOLT-7 node announcement or channel update with the node key of
+    /// the account with a given `id`
+    pub fn sign_gossip(
+        &self,
+        id: XpubIdentifier,
+        message: &[u8],
+        decryption_key: &mut SecretKey,
+    ) -> Result<Signature, RuntimeError> {
+        let account = self.signing_account(id)?;
+        Ok(ln::sign_gossip(account, message, decryption_key)?)
+    }
+
+    /// Registers multisig group of the vault `accounts` and `external`
+    /// cosigner keys, with the vault accounts going first in the group
+    /// scripts. The group is kept by the first of the `accounts`. Origins of
+    /// the vault account keys are given relative to the keyring master key,
+    /// matching the lookup performed by [`Vault::sign_psbt`].
+    pub fn create_multisig(
+        &mut self,
+        name: impl ToString,
+        threshold: u8,
+        accounts: &[XpubIdentifier],
+        external: Vec<CosignerKey>,
+    ) -> Result<MultisigGroup, RuntimeError> {
+        let owner = *accounts.first().ok_or(multisig::Error::NoVaultAccount)?;
+        let mut cosigners = Vec::with_capacity(accounts.len() + external.len());
+        for id in accounts {
+            let (origin, account) = self
+                .keyrings
+                .iter()
+                .filter(|kr| !kr.is_archived())
+                .find_map(|kr| {
+                    if kr.identifier() == *id {
+                        return Some((
+                            DerivationPath::master(),
+                            kr.master_account(),
+                            kr.fingerprint(),
+                        ));
+                    }
+                    kr.sub_accounts()
+                        .iter()
+                        .find(|(_, account)| account.identifier() == *id)
+                        .map(|(path, account)| {
+                            (path.clone(), account, kr.fingerprint())
+                        })
+                })
+                .filter(|(_, account, _)| !account.archived())
+                .map(|(path, account, fingerprint)| {
+                    ((fingerprint, path), account)
+                })
+                .ok_or(Error::NotFound)?;
+            account.check_lifecycle(Operation::ExportXpub)?;
+            cosigners.push(CosignerKey {
+                xpubkey: *account.xpubkey(),
+                origin,
+            });
+        }
+        cosigners.extend(external);
+
+        let group = MultisigGroup {
+            name: name.to_string(),
+            threshold,
+            cosigners,
+        };
+        multisig::validate(&group)?;
+        let id = group.id();
+        if self.multisig_groups().iter().any(|known| known.id() == id) {
+            return Err(multisig::Error::KnownGroup(id).into());
+        }
+        self.keyrings
+            .iter_mut()
+            .filter(|kr| !kr.is_archived())
+            .find_map(|kr| kr.account_by_id_mut(owner))
+            .ok_or(Error::NotFound)?
+            .add_multisig(group.clone());
+        self.store()?;
+        Ok(group)
+    }
+
+    /// Returns multisig groups kept by the vault accounts
+    pub fn multisig_groups(&self) -> Vec<MultisigGroup> {
+        self.keyrings
+            .iter()
+            .filter(|kr| !kr.is_archived())
+            .flat_map(|kr| {
+                iter::once(kr.master_account())
+                    .chain(kr.sub_accounts().values())
+            })
+            .filter(|account| !account.archived())
+            .flat_map(|account| account.multisig().iter().cloned())
+            .collect()
     }
 
+    /// Returns output descriptors of the multisig group with a given `id`,
+    /// see [`multisig::export`]
+    pub fn multisig_descriptors(
+        &self,
+        id: MultisigId,
+    ) -> Result<Vec<String>, RuntimeError> {
+        let group = self
+            .multisig_groups()
+            .into_iter()
+            .find(|group| group.id() == id)
+            .ok_or(multisig::Error::UnknownGroup(id))?;
+        Ok(multisig::export(&group))
+    }
+
+    #[cfg(feature = "export-secrets")]
     pub fn xpriv(
         &self,
         id: XpubIdentifier,
         mut decryption_key: &mut SecretKey,
     ) -> Result<ExtendedPrivKey, RuntimeError> {
-        Ok(self
-            .account_by_id(id)
-            .ok_or(Error::NotFound)?
-            .xprivkey(&mut decryption_key)?)
+        let account = self.account_by_id(id).ok_or(Error::NotFound)?;
+        account.check_lifecycle(Operation::ExportXpriv)?;
+        // The key leaves the vault, so the copy is not wiped
+        Ok(*account.xprivkey(&mut decryption_key)?)
+    }
+
+    /// Changes lifecycle state of the account with a given `id`
+    pub fn set_lifecycle(
+        &mut self,
+        id: XpubIdentifier,
+        state: Lifecycle,
+    ) -> Result<AccountInfo, RuntimeError> {
+        let account = self
+            .keyrings
+            .iter_mut()
+            .filter(|kr| !kr.is_archived())
+            .find_map(|kr| kr.account_by_id_mut(id))
+            .filter(|account| !account.archived())
+            .ok_or(Error::NotFound)?;
+        account.transit(state)?;
+        let info = AccountInfo::from(&*account);
+        self.store()?;
+        Ok(info)
+    }
+
+    /// Changes derivation branch layout of the account with a given `id`
+    pub fn set_branches(
+        &mut self,
+        id: XpubIdentifier,
+        branches: Branches,
+    ) -> Result<AccountInfo, RuntimeError> {
+        let account = self
+            .keyrings
+            .iter_mut()
+            .filter(|kr| !kr.is_archived())
+            .find_map(|kr| kr.account_by_id_mut(id))
+            .filter(|account| !account.archived())
+            .ok_or(Error::NotFound)?;
+        account.set_branches(branches)?;
+        let info = AccountInfo::from(&*account);
+        self.store()?;
+        Ok(info)
+    }
+
+    /// Changes signing policy of the account with a given `id`
+    pub fn set_policy(
+        &mut self,
+        id: XpubIdentifier,
+        policy: SigningPolicy,
+    ) -> Result<AccountInfo, RuntimeError> {
+        let account = self
+            .keyrings
+            .iter_mut()
+            .filter(|kr| !kr.is_archived())
+            .find_map(|kr| kr.account_by_id_mut(id))
+            .filter(|account| !account.archived())
+            .ok_or(Error::NotFound)?;
+        account.set_policy(policy)?;
+        let info = AccountInfo::from(&*account);
+        self.store()?;
+        Ok(info)
     }
 
+    /// Sets label `key` of the account with a given `id` to the `value`, or
+    /// removes the label if the `value` is `None`
+    pub fn set_label(
+        &mut self,
+        id: XpubIdentifier,
+        key: impl ToString,
+        value: Option<impl ToString>,
+    ) -> Result<AccountInfo, RuntimeError> {
+        let account = self
+            .keyrings
+            .iter_mut()
+            .filter(|kr| !kr.is_archived())
+            .find_map(|kr| kr.account_by_id_mut(id))
+            .filter(|account| !account.archived())
+            .ok_or(Error::NotFound)?;
+        let changed = account.set_label(key, value)?;
+        let info = AccountInfo::from(&*account);
+        if changed {
+            self.store()?;
+        }
+        Ok(info)
+    }
+
+    /// Updates name, details and the asset list of the account with a given
+    /// `id`, processing the `assets` according to the `mode` as
+    /// [`Keyring::update_subaccount`] does. Asset list of the keyring master
+    /// account can't be changed, so providing `assets` for it fails with
+    /// [`Error::MasterAccount`].
+    pub fn update_account(
+        &mut self,
+        id: XpubIdentifier,
+        name: Option<impl ToString>,
+        details: Option<impl ToString>,
+        assets: Option<HashSet<AssetId>>,
+        mode: UpdateMode,
+    ) -> Result<AccountInfo, RuntimeError> {
+        let keyring = self
+            .keyrings
+            .iter_mut()
+            .filter(|kr| !kr.is_archived())
+            .find(|kr| kr.account_by_id(id).is_some())
+            .ok_or(Error::NotFound)?;
+        if keyring.identifier() == id && assets.is_some() {
+            return Err(Error::MasterAccount.into());
+        }
+        let account = keyring
+            .account_by_id_mut(id)
+            .filter(|account| !account.archived())
+            .ok_or(Error::NotFound)?;
+        account.update(name, details, assets, mode)?;
+        let info = AccountInfo::from(&*account);
+        self.store()?;
+        Ok(info)
+    }
+
+    /// Signs PSBT inputs which are not P2TR with keys from the vault. Before
+    /// signing, evaluates signing policies of the accounts used by all PSBT
+    /// inputs, including P2TR ones, which are signed with
+    /// [`Vault::sign_psbt_taproot`] afterwards; the PSBT counts towards the
+    /// account rate limits once the policies are satisfied. Unless `force`
+    /// is set, inputs transferring RGB assets must be signed by the accounts
+    /// bound to these assets, otherwise signing fails with
+    /// [`Error::AssetIds`] listing the assets unknown to the accounts. The
+    /// `progress` callback is called after each of the inputs is processed.
     pub fn sign_psbt(
-        &self,
+        &mut self,
+        psbt: PartiallySignedTransaction,
+        force: bool,
+        decryption_key: &mut SecretKey,
+        progress: &mut dyn FnMut(),
+    ) -> Result<PartiallySignedTransaction, RuntimeError> {
+        let mut cache = SigningCache::unbounded();
+        self.sign_psbt_cached(psbt, force, decryption_key, &mut cache, progress)
+    }
+
+    /// Signs PSBT inputs in the same way as [`Vault::sign_psbt`] does, but
+    /// takes signing keys from the `cache` and keeps the keys decrypted or
+    /// derived in the process there. The `decryption_key` is used only for
+    /// the keys missing from the cache and is wiped after signing.
+    pub fn sign_psbt_cached(
+        &mut self,
         mut psbt: PartiallySignedTransaction,
+        force: bool,
         decryption_key: &mut SecretKey,
+        cache: &mut SigningCache,
+        progress: &mut dyn FnMut(),
     ) -> Result<PartiallySignedTransaction, RuntimeError> {
-        // TODO: Rewriting supporting witness and proper signature creation
-        //       (via vault account)
+        // TODO: Signature creation via vault account
         trace!("{:?}", psbt);
+        self.check_chains(&psbt)?;
+        self.check_multisig(&psbt)?;
+        if !force {
+            self.check_assets(&psbt)?;
+        }
+        let rate_limited = self.check_policies(&psbt)?;
+        let taproot_inputs = (0..psbt.inputs.len())
+            .filter(|index| taproot::is_taproot_input(&psbt, *index))
+            .collect::<Vec<_>>();
+        let spent_outputs = (0..psbt.inputs.len())
+            .map(|index| taproot::spent_output(&psbt, index))
+            .collect::<Vec<_>>();
         let tx = &psbt.global.unsigned_tx;
+        let mut sighash_cache = SigHashCache::new(tx);
         for (index, inp) in psbt.inputs.iter_mut().enumerate() {
+            if taproot_inputs.contains(&index) {
+                // Taproot inputs are signed by `Vault::sign_psbt_taproot`
+                continue;
+            }
             for (pubkey, (fingerprint, derivation)) in &inp.bip32_derivation {
-                if let Some(account) = self
-                    .keyrings
-                    .iter()
-                    .find(|keyring| keyring.fingerprint() == *fingerprint)
-                    .map::<&KeysAccount, _>(Keyring::master_account)
+                if let Some(keyring) = self
+                    .keyring_by_fingerprint(*fingerprint)
+                    .filter(|keyring| !keyring.master_account().is_watch_only())
                 {
-                    let xpriv = account
-                        .xprivkey(decryption_key)?
-                        .derive_priv(&crate::SECP256K1, &derivation)
-                        .map_err(|_| RuntimeError::Message)?;
-                    let sig_hash = tx.signature_hash(
+                    keyring.check_signing(derivation)?;
+                    let account = keyring.master_account();
+                    let xpriv = cache.signing_key(
+                        account,
+                        derivation,
+                        decryption_key,
+                    )?;
+                    let spent = spent_outputs[index]
+                        .as_ref()
+                        .ok_or(Error::PsbtInputData(index))?;
+                    let sig_hash = input_sighash(
+                        tx,
+                        &mut sighash_cache,
                         index,
-                        &inp.non_witness_utxo
-                            .as_ref()
-                            .ok_or(RuntimeError::Transport)?
-                            .output
-                            [tx.input[index].previous_output.vout as usize]
-                            .script_pubkey,
-                        SigHashType::All.as_u32(),
-                    );
+                        inp,
+                        spent,
+                        pubkey,
+                    )?;
                     let signature = crate::SECP256K1.sign(
                         &bitcoin::secp256k1::Message::from_slice(&sig_hash[..])
                             .map_err(|_| RuntimeError::Message)?,
-                        &xpriv.private_key.key,
+                        xpriv.secret_key(),
                     );
                     let mut partial_sig = signature.serialize_der().to_vec();
                     partial_sig.push(SigHashType::All.as_u32() as u8);
@@ -202,10 +1680,546 @@ impl Vault {
                     inp.partial_sigs.insert(*pubkey, partial_sig);
                 }
             }
+            progress();
+        }
+
+        trace!("Wiping out decryption key");
+        wipe_key(decryption_key);
+
+        for (account, period) in rate_limited {
+            self.history.record(account, period);
+        }
+        Ok(psbt)
+    }
+
+    /// Composes unsigned PSBT spending outputs controlled by the vault
+    /// accounts. The vault does not know previous transactions, so inputs
+    /// are provided with witness UTXOs only, and only accounts with
+    /// single-key SegWit (native or nested) applications can be spent. BIP-32
+    /// derivations are given relative to the keyring master key, matching the
+    /// lookup performed by [`Vault::sign_psbt`]. All inputs signal
+    /// replaceability.
+    pub fn compose_psbt(
+        &self,
+        inputs: &[PsbtInput],
+        outputs: &[PsbtOutput],
+        lock_time: u32,
+    ) -> Result<PartiallySignedTransaction, RuntimeError> {
+        let total_in = inputs
+            .iter()
+            .fold(0u64, |sum, inp| sum.saturating_add(inp.amount));
+        let total_out = outputs
+            .iter()
+            .fold(0u64, |sum, out| sum.saturating_add(out.amount));
+        if total_out > total_in {
+            Err(Error::InsufficientFunds(total_in, total_out))?;
+        }
+
+        let tx = Transaction {
+            version: 2,
+            lock_time,
+            input: inputs
+                .iter()
+                .map(|inp| TxIn {
+                    previous_output: inp.outpoint,
+                    script_sig: Script::new(),
+                    sequence: 0xFFFF_FFFD,
+                    witness: vec![],
+                })
+                .collect(),
+            output: outputs
+                .iter()
+                .map(|out| TxOut {
+                    value: out.amount,
+                    script_pubkey: out.script_pubkey.clone(),
+                })
+                .collect(),
+        };
+        let mut psbt = PartiallySignedTransaction::from_unsigned_tx(tx)
+            .expect("composed transaction has no signatures");
+
+        for (index, (inp, psbt_input)) in
+            inputs.iter().zip(psbt.inputs.iter_mut()).enumerate()
+        {
+            let (keyring, account, derivation) = self
+                .keyrings
+                .iter()
+                .filter(|kr| !kr.is_archived())
+                .find_map(|kr| {
+                    if kr.identifier() == inp.key_id {
+                        return Some((
+                            kr,
+                            kr.master_account(),
+                            DerivationPath::master(),
+                        ));
+                    }
+                    kr.sub_accounts()
+                        .iter()
+                        .find(|(_, account)| account.identifier() == inp.key_id)
+                        .map(|(path, account)| (kr, account, path.clone()))
+                })
+                .filter(|(_, account, _)| !account.archived())
+                .ok_or(Error::NotFound)?;
+            account.check_lifecycle(Operation::Sign)?;
+
+            let application = account
+                .application()
+                .filter(|application| {
+                    matches!(
+                        application,
+                        KeyApplication::SegWit | KeyApplication::Nested
+                    )
+                })
+                .ok_or(Error::PsbtInputApplication(index))?;
+            let pubkey = account
+                .xpubkey()
+                .derive_pub(&crate::SECP256K1, &inp.terminal)
+                .map_err(Error::from)?
+                .public_key;
+            let script_pubkey = chain::script_pubkey(&pubkey, application)?;
+            if application == KeyApplication::Nested {
+                psbt_input.redeem_script = Some(Script::new_v0_wpkh(
+                    &pubkey
+                        .wpubkey_hash()
+                        .expect("extended public keys are always compressed"),
+                ));
+            }
+            psbt_input.witness_utxo = Some(TxOut {
+                value: inp.amount,
+                script_pubkey,
+            });
+            psbt_input.bip32_derivation.insert(
+                pubkey,
+                (keyring.fingerprint(), derivation.extend(&inp.terminal)),
+            );
+        }
+        Ok(psbt)
+    }
+
+    /// Signs all P2TR inputs of the PSBT which can be spent by a key path
+    /// using keys from the vault, adding BIP-340 signatures in the BIP-371
+    /// format. Inputs are signed with the sighash type given in the PSBT,
+    /// which must be allowed by the policy of the signing account (see
+    /// [`policy::check_sighash_type`]). Inputs which are not P2TR are ignored
+    /// and have to be signed with [`Vault::sign_psbt`]; the `progress`
+    /// callback is called after each of the P2TR inputs is processed.
+    pub fn sign_psbt_taproot(
+        &self,
+        psbt: PartiallySignedTransaction,
+        decryption_key: &mut SecretKey,
+        progress: &mut dyn FnMut(),
+    ) -> Result<PartiallySignedTransaction, RuntimeError> {
+        let mut cache = SigningCache::unbounded();
+        self.sign_psbt_taproot_cached(
+            psbt,
+            decryption_key,
+            &mut cache,
+            progress,
+        )
+    }
+
+    /// Signs P2TR inputs in the same way as [`Vault::sign_psbt_taproot`]
+    /// does, taking signing keys from the `cache`; see
+    /// [`Vault::sign_psbt_cached`].
+    pub fn sign_psbt_taproot_cached(
+        &self,
+        mut psbt: PartiallySignedTransaction,
+        decryption_key: &mut SecretKey,
+        cache: &mut SigningCache,
+        progress: &mut dyn FnMut(),
+    ) -> Result<PartiallySignedTransaction, RuntimeError> {
+        self.check_chains(&psbt)?;
+        let mut signatures = vec![];
+        for (index, inp) in psbt.inputs.iter().enumerate() {
+            let output_key = match taproot::spent_output(&psbt, index)
+                .and_then(|txout| taproot::output_key(&txout.script_pubkey))
+            {
+                Some(output_key) => output_key,
+                None => continue,
+            };
+            let sighash_type = inp
+                .sighash_type
+                .map(|sighash_type| sighash_type.as_u32() as u8)
+                .unwrap_or(taproot::SIGHASH_DEFAULT);
+            for (fingerprint, derivation) in inp.bip32_derivation.values() {
+                let keyring = match self.keyring_by_fingerprint(*fingerprint) {
+                    Some(keyring)
+                        if !keyring.master_account().is_watch_only() =>
+                    {
+                        keyring
+                    }
+                    _ => continue,
+                };
+                keyring.check_signing(derivation)?;
+                let account = keyring.master_account();
+                let xpriv =
+                    cache.signing_key(account, derivation, decryption_key)?;
+                let keypair = taproot::tweaked_keypair(xpriv.secret_key())?;
+
+                if schnorrsig::PublicKey::from_keypair(
+                    &crate::SECP256K1,
+                    &keypair,
+                ) != output_key
+                {
+                    trace!(
+                        "Key {}/{} does not match output key of input #{}",
+                        fingerprint,
+                        derivation,
+                        index
+                    );
+                    continue;
+                }
+                let signer = keyring.account_by_derivation(derivation);
+                policy::check_sighash_type(
+                    signer.identifier(),
+                    signer.policy(),
+                    index,
+                    sighash_type,
+                )?;
+                let sighash = taproot::sighash(&psbt, index, sighash_type)?;
+                let signature = taproot::sign(sighash, &keypair)?;
+                debug!("Taproot key spend input #{} signed", index);
+                signatures.push((index, signature, sighash_type));
+                break;
+            }
+            progress();
+        }
+
+        trace!("Wiping out decryption key");
+        wipe_key(decryption_key);
+
+        for (index, signature, sighash_type) in signatures {
+            taproot::add_key_sig(
+                &mut psbt.inputs[index],
+                signature,
+                sighash_type,
+            );
         }
         Ok(psbt)
     }
 
+    /// Derives identity key with a given `index` from the account `id`; see
+    /// [`identity`] module for the details
+    pub fn identity_key(
+        &self,
+        id: XpubIdentifier,
+        index: u32,
+        decryption_key: &mut SecretKey,
+    ) -> Result<IdentityKey, RuntimeError> {
+        let account = self.account_by_id(id).ok_or(Error::NotFound)?;
+        account.check_lifecycle(Operation::ExportXpub)?;
+        let keypair = identity::keypair(account, index, decryption_key)?;
+        Ok(IdentityKey {
+            key_id: id,
+            index,
+            path: identity::path(index)?,
+            pubkey: identity::pubkey(&keypair).serialize().to_vec(),
+        })
+    }
+
+    /// Derives BIP-85 child secret with a given `index` for the
+    /// `application` from the account `id`; see [`bip85`] module for the
+    /// details
+    pub fn derive_entropy(
+        &self,
+        id: XpubIdentifier,
+        application: Bip85Application,
+        index: u32,
+        decryption_key: &mut SecretKey,
+    ) -> Result<String, RuntimeError> {
+        let account = self.account_by_id(id).ok_or(Error::NotFound)?;
+        account.check_lifecycle(Operation::Derive)?;
+        let secret =
+            bip85::derive(account, application, index, decryption_key)?;
+        debug!("BIP-85 {} secret #{} is derived", application, index);
+        Ok(secret)
+    }
+
+    /// Derives BIP-47 payment code for the `bip47_account` of the account
+    /// `id`. If a `counterparty` payment code is given, or the code saved
+    /// under the `contact` name, keys of `count` payments starting with
+    /// `start` index are derived in both directions. Counterparty code given
+    /// together with the `contact` name is saved with the account.
+    pub fn payment_code(
+        &mut self,
+        id: XpubIdentifier,
+        bip47_account: u32,
+        counterparty: Option<PaymentCode>,
+        contact: Option<String>,
+        start: u32,
+        count: u32,
+        decryption_key: &mut SecretKey,
+    ) -> Result<PaymentCodeInfo, RuntimeError> {
+        if count > MAX_DERIVATION_RANGE || start.checked_add(count).is_none() {
+            Err(Error::DerivationRange(count))?;
+        }
+        let account = self
+            .keyrings
+            .iter_mut()
+            .filter(|kr| !kr.is_archived())
+            .find_map(|kr| kr.account_by_id_mut(id))
+            .filter(|account| !account.archived())
+            .ok_or(Error::NotFound)?;
+        account.check_lifecycle(Operation::Derive)?;
+        let (counterparty, changed) = match (counterparty, contact) {
+            (Some(code), Some(name)) => {
+                (Some(code), account.save_payment_code(name, code)?)
+            }
+            (None, Some(name)) => {
+                let code = account
+                    .payment_codes()
+                    .get(&name)
+                    .copied()
+                    .ok_or(Error::UnknownContact(name))?;
+                (Some(code), false)
+            }
+            (counterparty, None) => (counterparty, false),
+        };
+
+        let network = account.network();
+        let xpriv = bip47::xprivkey(account, bip47_account, decryption_key)?;
+        let code = bip47::payment_code(&xpriv);
+        let mut payments = vec![];
+        if let Some(ref counterparty) = counterparty {
+            for direction in
+                &[PaymentDirection::Send, PaymentDirection::Receive]
+            {
+                for index in start..start + count {
+                    payments.push(bip47::payment_key(
+                        &xpriv,
+                        counterparty,
+                        *direction,
+                        index,
+                        network,
+                    )?);
+                }
+            }
+        }
+        let info = PaymentCodeInfo {
+            key_id: id,
+            code,
+            notification_address: bip47::notification_address(&code, network)?
+                .to_string(),
+            contacts: account.payment_codes().clone(),
+            counterparty,
+            payments,
+        };
+        if changed {
+            self.store()?;
+        }
+        debug!("BIP-47 payment code {} is derived", code);
+        Ok(info)
+    }
+
+    /// Signs identity event `digest` with the identity key with a given
+    /// `index` derived from the account `id`
+    pub fn sign_identity(
+        &self,
+        id: XpubIdentifier,
+        index: u32,
+        digest: sha256::Hash,
+        decryption_key: &mut SecretKey,
+    ) -> Result<IdentitySignature, RuntimeError> {
+        let account = self.signing_account(id)?;
+        let keypair = identity::keypair(account, index, decryption_key)?;
+        let signature = identity::sign(digest, &keypair)?;
+        debug!("Identity event {} is signed", digest);
+        Ok(IdentitySignature {
+            key: IdentityKey {
+                key_id: id,
+                index,
+                path: identity::path(index)?,
+                pubkey: identity::pubkey(&keypair).serialize().to_vec(),
+            },
+            signature: signature[..].to_vec(),
+        })
+    }
+
+    /// Returns public key of the account `id` with which the account
+    /// participates in MuSig2 sessions
+    pub fn musig_key(
+        &self,
+        id: XpubIdentifier,
+    ) -> Result<PublicKey, RuntimeError> {
+        Ok(self.signing_account(id)?.xpubkey().public_key.key)
+    }
+
+    /// Produces MuSig2 partial signature of the `message` with the account
+    /// `id` key, consuming the session `secnonce`
+    pub fn musig_partial_sign(
+        &self,
+        id: XpubIdentifier,
+        secnonce: musig::SecNonce,
+        context: &musig::KeyAggContext,
+        aggnonce: &musig::PubNonce,
+        message: sha256::Hash,
+        decryption_key: &mut SecretKey,
+    ) -> Result<[u8; musig::PARTIAL_SIG_LEN], RuntimeError> {
+        let account = self.signing_account(id)?;
+        let xprivkey = account.xprivkey(decryption_key)?;
+        let partial = musig::partial_sign(
+            secnonce,
+            xprivkey.secret_key(),
+            context,
+            aggnonce,
+            message,
+        )?;
+        debug!("MuSig2 partial signature for {} is created", message);
+        Ok(partial)
+    }
+
+    /// Returns account able to sign with the key `id`. Watch-only accounts
+    /// fail with [`Error::WatchOnly`], so the check can be performed before
+    /// any decryption key is requested.
+    pub fn signing_account(
+        &self,
+        id: XpubIdentifier,
+    ) -> Result<&KeysAccount, RuntimeError> {
+        let account = self.account_by_id(id).ok_or(Error::NotFound)?;
+        if account.is_watch_only() {
+            return Err(Error::WatchOnly.into());
+        }
+        account.check_lifecycle(Operation::Sign)?;
+        Ok(account)
+    }
+
+    /// Checks whether the vault holds private keys for the PSBT. Fails with
+    /// [`Error::WatchOnly`] if all PSBT input keys known to the vault belong
+    /// to watch-only keyrings, and with [`Error::NotFound`] if they belong
+    /// to archived keyrings only.
+    pub fn check_psbt_signers(
+        &self,
+        psbt: &PartiallySignedTransaction,
+    ) -> Result<(), RuntimeError> {
+        let mut watch_only = false;
+        let mut archived = false;
+        for (fingerprint, _) in psbt
+            .inputs
+            .iter()
+            .flat_map(|inp| inp.bip32_derivation.values())
+        {
+            match self.keyring_by_fingerprint(*fingerprint) {
+                Some(keyring) if keyring.master_account().is_watch_only() => {
+                    watch_only = true
+                }
+                Some(_) => return Ok(()),
+                None => {
+                    archived |= self.keyrings.iter().any(|kr| {
+                        kr.is_archived() && kr.fingerprint() == *fingerprint
+                    })
+                }
+            }
+        }
+        if watch_only {
+            return Err(Error::WatchOnly.into());
+        }
+        if archived {
+            return Err(Error::NotFound.into());
+        }
+        Ok(())
+    }
+
+    /// Analyzes PSBT without signing it, reporting the inputs which can be
+    /// signed with the vault keys, amounts spent from and received to the
+    /// vault, and the reasons for which [`Vault::sign_psbt`] would fail
+    /// (unless forced to sign RGB asset transfers)
+    pub fn analyze_psbt(
+        &self,
+        psbt: &PartiallySignedTransaction,
+    ) -> PsbtAnalysis {
+        let can_sign = |keyring: &Keyring, derivation: &DerivationPath| {
+            !keyring.master_account().is_watch_only()
+                && keyring.check_signing(derivation).is_ok()
+        };
+        let tx = &psbt.global.unsigned_tx;
+        let mut spent = 0u64;
+        let mut input_total = Some(0u64);
+        let mut inputs = vec![];
+        for (index, (inp, txin)) in
+            psbt.inputs.iter().zip(&tx.input).enumerate()
+        {
+            let amount =
+                taproot::spent_output(psbt, index).map(|txout| txout.value);
+            input_total = input_total.and_then(|total| Some(total + amount?));
+            let keys = inp
+                .bip32_derivation
+                .values()
+                .filter_map(|(fingerprint, derivation)| {
+                    self.keyring_by_fingerprint(*fingerprint)
+                        .map(|keyring| (keyring, derivation))
+                })
+                .collect::<Vec<_>>();
+            let signer = keys
+                .iter()
+                .find(|(keyring, derivation)| can_sign(keyring, derivation))
+                .or_else(|| keys.first());
+            if signer.is_some() {
+                spent += amount.unwrap_or_default();
+            }
+            inputs.push(InputAnalysis {
+                index: index as u32,
+                outpoint: txin.previous_output,
+                amount,
+                keyring: signer.map(|&(keyring, _)| keyring.identifier()),
+                derivation: signer.map(|&(_, derivation)| derivation.clone()),
+                taproot: taproot::is_taproot_input(psbt, index),
+                signable: signer
+                    .map(|&(keyring, derivation)| can_sign(keyring, derivation))
+                    .unwrap_or_default(),
+                signed: inp.final_script_sig.is_some()
+                    || inp.final_script_witness.is_some()
+                    || !inp.partial_sigs.is_empty()
+                    || inp.unknown.keys().any(|key| {
+                        key.type_value == taproot::PSBT_IN_TAP_KEY_SIG
+                    }),
+            });
+        }
+
+        let received = psbt
+            .outputs
+            .iter()
+            .zip(&tx.output)
+            .filter(|(output, _)| {
+                output.bip32_derivation.values().any(|(fingerprint, _)| {
+                    self.keyring_by_fingerprint(*fingerprint).is_some()
+                })
+            })
+            .map(|(_, txout)| txout.value)
+            .sum();
+        let output_total =
+            tx.output.iter().map(|txout| txout.value).sum::<u64>();
+
+        let mut issues = vec![];
+        if let Err(err) = self.check_psbt_signers(psbt) {
+            issues.push(err.to_string());
+        } else if !inputs.iter().any(|input| input.signable) {
+            issues.push(s!("None of the inputs can be signed by the vault"));
+        }
+        if let Err(err) = self.check_chains(psbt) {
+            issues.push(err.to_string());
+        }
+        if let Err(err) = self.check_multisig(psbt) {
+            issues.push(err.to_string());
+        }
+        if let Err(err) = self.check_assets(psbt) {
+            issues.push(err.to_string());
+        }
+        if let Err(err) = self.check_policies(psbt) {
+            issues.push(err.to_string());
+        }
+
+        PsbtAnalysis {
+            txid: tx.txid(),
+            inputs,
+            input_total,
+            output_total,
+            fee: input_total.and_then(|total| total.checked_sub(output_total)),
+            spent,
+            received,
+            issues,
+        }
+    }
+
     pub fn sign_key(
         &self,
         id: XpubIdentifier,
@@ -215,7 +2229,7 @@ impl Vault {
             "Signing public key with id {} using corresponding private key",
             id
         );
-        let account = self.account_by_id(id).ok_or(Error::NotFound)?;
+        let account = self.signing_account(id)?;
         trace!("Keys account for key id is found: {}", account);
         let pubkey = account.xpubkey().public_key;
         trace!("Public key used for signing: {}", pubkey);
@@ -224,13 +2238,32 @@ impl Vault {
         Ok(account.sign_digest(digest, &mut decryption_key)?)
     }
 
+    /// Signs text `message` in the Bitcoin Signed Message format, returning
+    /// signature serialized with BIP-137 header matching the account
+    /// application
+    pub fn sign_message(
+        &self,
+        id: XpubIdentifier,
+        message: &str,
+        decryption_key: &mut SecretKey,
+    ) -> Result<Vec<u8>, RuntimeError> {
+        let account = self.signing_account(id)?;
+        let signature_type = match account.application() {
+            Some(KeyApplication::SegWit) => SignatureType::P2wpkh,
+            Some(KeyApplication::Nested) => SignatureType::P2shP2wpkh,
+            _ => SignatureType::P2pkh,
+        };
+        let signature = account.sign_message(message, decryption_key)?;
+        Ok(signed_message::serialize(&signature, signature_type).to_vec())
+    }
+
     pub fn sign_data(
         &self,
         id: XpubIdentifier,
         data: &[u8],
         mut decryption_key: &mut SecretKey,
     ) -> Result<Signature, RuntimeError> {
-        let account = self.account_by_id(id).ok_or(Error::NotFound)?;
+        let account = self.signing_account(id)?;
         Ok(account
             .sign_digest(sha256::Hash::hash(&data), &mut decryption_key)?)
     }
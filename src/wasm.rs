@@ -0,0 +1,175 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! JavaScript interface of the vault for browser wallets, built with `wasm`
+//! feature for `wasm32-unknown-unknown` target. The vault is kept in the
+//! browser storage with [`crate::vault::BrowserDriver`] and is encrypted with
+//! the node key provided by the application. Accounts are returned as JSON
+//! strings with the same fields as in `keyring-cli` output; PSBTs are passed
+//! as base64 strings. Failures are thrown as JavaScript string exceptions.
+
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use bitcoin::consensus::{deserialize, serialize};
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use bitcoin::util::bip32::DerivationPath;
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::XpubIdentifier;
+use js_sys::{Function, Uint8Array};
+use lnpbp::Chain;
+use slip132::KeyApplication;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+use crate::vault::secret::wipe_key;
+use crate::vault::{browser, driver};
+use crate::Vault;
+
+fn js_err(err: impl Display) -> JsValue {
+    JsValue::from_str(&err.to_string())
+}
+
+fn parse<T>(s: &str, argument: &str) -> Result<T, JsValue>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    T::from_str(s)
+        .map_err(|err| js_err(format!("invalid {}: {}", argument, err)))
+}
+
+/// Vault opened by the browser wallet
+#[wasm_bindgen]
+pub struct BrowserVault {
+    vault: Vault,
+    node_key: SecretKey,
+}
+
+#[wasm_bindgen]
+impl BrowserVault {
+    /// Opens the vault encrypted with 32-byte `node_key`. The `load`
+    /// function must return `Uint8Array` with the vault data, or a promise
+    /// of it, or `null` for a new vault; the `store` function is called with
+    /// `Uint8Array` of the vault data on each vault update.
+    pub async fn open(
+        node_key: Vec<u8>,
+        load: Function,
+        store: Function,
+    ) -> Result<BrowserVault, JsValue> {
+        let node_key = SecretKey::from_slice(&node_key)
+            .map_err(|err| js_err(format!("invalid node key: {}", err)))?;
+        let mut loaded = load.call0(&JsValue::NULL)?;
+        if loaded.is_instance_of::<js_sys::Promise>() {
+            loaded = JsFuture::from(js_sys::Promise::from(loaded)).await?;
+        }
+        let snapshot = if loaded.is_null() || loaded.is_undefined() {
+            vec![]
+        } else {
+            loaded
+                .dyn_into::<Uint8Array>()
+                .map_err(|_| js_err("vault data must be Uint8Array"))?
+                .to_vec()
+        };
+        let vault = Vault::with(&driver::Config::Browser(browser::Config {
+            snapshot,
+            store_cb: store,
+        }))
+        .map_err(js_err)?;
+        Ok(BrowserVault { vault, node_key })
+    }
+
+    /// Lists the vault accounts as a JSON array
+    pub fn list(&self) -> Result<String, JsValue> {
+        let accounts = self.vault.list().map_err(js_err)?;
+        serde_json::to_string(&accounts).map_err(js_err)
+    }
+
+    /// Creates new keyring from a random seed, returning JSON of its master
+    /// account. The `chain` is given by its name, like `bitcoin` or
+    /// `testnet`, and the `application` is one of `pkh`, `sh`, `wpkh`, `wsh`,
+    /// `wpkh-sh` and `wsh-sh`.
+    pub fn seed(
+        &mut self,
+        chain: &str,
+        application: &str,
+        name: &str,
+        details: Option<String>,
+    ) -> Result<String, JsValue> {
+        let chain: Chain = parse(chain, "chain")?;
+        let application: KeyApplication = parse(application, "application")?;
+        let encryption_key =
+            PublicKey::from_secret_key(&crate::SECP256K1, &self.node_key);
+        let info = self
+            .vault
+            .seed(name, details, &chain, application, encryption_key)
+            .map_err(js_err)?;
+        serde_json::to_string(&info).map_err(js_err)
+    }
+
+    /// Derives sub-account with the `path` from the account `from` given by
+    /// its hex identifier, returning JSON of the new account
+    pub fn derive(
+        &mut self,
+        from: &str,
+        path: &str,
+        name: &str,
+        details: Option<String>,
+    ) -> Result<String, JsValue> {
+        let from: XpubIdentifier = parse(from, "account id")?;
+        let path: DerivationPath = parse(path, "derivation path")?;
+        let mut seckey = self.node_key;
+        let account = self
+            .vault
+            .derive(
+                from,
+                path,
+                name,
+                details,
+                HashSet::new(),
+                Some(&mut seckey),
+            )
+            .map_err(js_err)?;
+        serde_json::to_string(&account).map_err(js_err)
+    }
+
+    /// Signs all inputs of base64-encoded PSBT which can be signed with the
+    /// vault keys, including P2TR key path spends, returning base64 of the
+    /// signed PSBT
+    pub fn sign_psbt(&mut self, psbt: &str) -> Result<String, JsValue> {
+        let data = base64::decode(psbt)
+            .map_err(|err| js_err(format!("invalid psbt: {}", err)))?;
+        let psbt: PartiallySignedTransaction = deserialize(&data)
+            .map_err(|err| js_err(format!("invalid psbt: {}", err)))?;
+        self.vault.check_psbt_signers(&psbt).map_err(js_err)?;
+        let mut seckey = self.node_key;
+        let psbt = self
+            .vault
+            .sign_psbt(psbt, false, &mut seckey, &mut || ())
+            .map_err(js_err)?;
+        let mut seckey = self.node_key;
+        let psbt = self
+            .vault
+            .sign_psbt_taproot(psbt, &mut seckey, &mut || ())
+            .map_err(js_err)?;
+        Ok(base64::encode(&serialize(&psbt)))
+    }
+}
+
+impl Drop for BrowserVault {
+    fn drop(&mut self) {
+        wipe_key(&mut self.node_key);
+    }
+}
@@ -0,0 +1,153 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "node")]
+
+mod common;
+
+use std::str::FromStr;
+
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1;
+use bitcoin::util::bip32::{DerivationPath, Fingerprint};
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::{OutPoint, PublicKey, Script, Transaction, TxIn, TxOut, Txid};
+use keyring::vault::Vault;
+use keyring::SECP256K1;
+
+use common::{decryption_key, xpriv};
+
+const SEED: u8 = 0x3C;
+
+fn vault(name: &str) -> Vault {
+    common::vault(name, SEED, "Testnet keys").0
+}
+
+fn pubkey(derivation: &DerivationPath) -> PublicKey {
+    PublicKey {
+        compressed: true,
+        key: secp256k1::PublicKey::from_secret_key(
+            &SECP256K1,
+            &xpriv(SEED)
+                .derive_priv(&SECP256K1, derivation)
+                .unwrap()
+                .private_key
+                .key,
+        ),
+    }
+}
+
+fn txin(byte: u8) -> TxIn {
+    TxIn {
+        previous_output: OutPoint::new(Txid::from_inner([byte; 32]), 0),
+        script_sig: Script::new(),
+        sequence: 0xFFFF_FFFD,
+        witness: vec![],
+    }
+}
+
+/// PSBT spending 10 000 sat P2WPKH output of the vault keyring and 5 000 sat
+/// output of a foreign key, paying 11 000 sat out and returning 3 000 sat of
+/// change to the keyring
+fn psbt() -> (PartiallySignedTransaction, DerivationPath) {
+    let derivation = DerivationPath::from_str("m/84'/1'/0'/0/0").unwrap();
+    let change = DerivationPath::from_str("m/84'/1'/0'/1/0").unwrap();
+    let script_pubkey =
+        Script::new_v0_wpkh(&pubkey(&derivation).wpubkey_hash().unwrap());
+    let mut psbt = PartiallySignedTransaction::from_unsigned_tx(Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![txin(1), txin(2)],
+        output: vec![
+            TxOut {
+                value: 11_000,
+                script_pubkey: Script::from(vec![0x6a]),
+            },
+            TxOut {
+                value: 3_000,
+                script_pubkey: script_pubkey.clone(),
+            },
+        ],
+    })
+    .unwrap();
+    let fingerprint = xpriv(SEED).fingerprint(&SECP256K1);
+    psbt.inputs[0].witness_utxo = Some(TxOut {
+        value: 10_000,
+        script_pubkey,
+    });
+    psbt.inputs[0]
+        .bip32_derivation
+        .insert(pubkey(&derivation), (fingerprint, derivation.clone()));
+    psbt.inputs[1].witness_utxo = Some(TxOut {
+        value: 5_000,
+        script_pubkey: Script::from(vec![0x51]),
+    });
+    psbt.inputs[1].bip32_derivation.insert(
+        pubkey(&change),
+        (Fingerprint::from(&[0xA5u8; 4][..]), change.clone()),
+    );
+    psbt.outputs[1]
+        .bip32_derivation
+        .insert(pubkey(&change), (fingerprint, change));
+    (psbt, derivation)
+}
+
+#[test]
+fn analyze_psbt() {
+    let mut vault = vault("analysis");
+    let (psbt, derivation) = psbt();
+    let keyring = vault.list().unwrap()[0].key_id;
+
+    let analysis = vault.analyze_psbt(&psbt);
+    assert_eq!(analysis.txid, psbt.global.unsigned_tx.txid());
+    assert_eq!(analysis.input_total, Some(15_000));
+    assert_eq!(analysis.output_total, 14_000);
+    assert_eq!(analysis.fee, Some(1_000));
+    assert_eq!(analysis.spent, 10_000);
+    assert_eq!(analysis.received, 3_000);
+    assert!(analysis.issues.is_empty(), "{:?}", analysis.issues);
+
+    let (signable, foreign) = (&analysis.inputs[0], &analysis.inputs[1]);
+    assert_eq!(signable.keyring, Some(keyring));
+    assert_eq!(signable.derivation, Some(derivation));
+    assert_eq!(signable.amount, Some(10_000));
+    assert!(signable.signable && !signable.signed && !signable.taproot);
+    assert_eq!(foreign.keyring, None);
+    assert!(!foreign.signable && !foreign.signed);
+
+    let signed = vault
+        .sign_psbt(psbt, false, &mut decryption_key(), &mut || {})
+        .unwrap();
+    let analysis = vault.analyze_psbt(&signed);
+    assert!(analysis.inputs[0].signed);
+    assert!(!analysis.inputs[1].signed);
+}
+
+#[test]
+fn analyze_foreign_psbt() {
+    let vault = vault("analysis-foreign");
+    let (mut psbt, _) = psbt();
+    psbt.inputs.iter_mut().for_each(|input| {
+        input.bip32_derivation.clear();
+        input.witness_utxo = None;
+    });
+    psbt.outputs[1].bip32_derivation.clear();
+
+    let analysis = vault.analyze_psbt(&psbt);
+    assert_eq!(analysis.input_total, None);
+    assert_eq!(analysis.fee, None);
+    assert_eq!(analysis.spent, 0);
+    assert_eq!(analysis.received, 0);
+    assert!(analysis.inputs.iter().all(|input| !input.signable));
+    assert_eq!(analysis.issues.len(), 1);
+}
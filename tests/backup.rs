@@ -0,0 +1,65 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "node")]
+
+use std::fs;
+
+use bitcoin::secp256k1;
+use keyring::vault::{backup, Backups, Keyring};
+use lnpbp::Chain;
+use slip132::KeyApplication;
+
+#[test]
+fn backup_rotation() {
+    let dir = std::env::temp_dir()
+        .join(format!("keyring-{}-backups", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let node_key = secp256k1::key::ONE_KEY;
+    let node_id =
+        secp256k1::PublicKey::from_secret_key(&keyring::SECP256K1, &node_key);
+    let backups = Backups::with(
+        &backup::Config {
+            dir: dir.display().to_string(),
+            retention: 2,
+        },
+        node_id,
+    )
+    .unwrap();
+
+    let keyrings = vec![Keyring::with(
+        "Master",
+        "Backed up keyring",
+        &Chain::Testnet3,
+        KeyApplication::SegWit,
+        None,
+        node_id,
+    )
+    .unwrap()];
+    let mut paths = vec![];
+    for _ in 0..3 {
+        paths.push(backups.write(&keyrings).unwrap());
+        std::thread::sleep(std::time::Duration::from_millis(2));
+    }
+    assert_eq!(backups.list().unwrap(), paths[1..].to_vec());
+
+    let snapshot = fs::read(&paths[2]).unwrap();
+    assert_eq!(Backups::read(&snapshot, &node_key).unwrap(), keyrings);
+    assert!(Backups::read(
+        &snapshot,
+        &secp256k1::SecretKey::from_slice(&[2u8; 32]).unwrap()
+    )
+    .is_err());
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,170 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "node")]
+
+mod common;
+
+use std::fs;
+use std::str::FromStr;
+
+use bip39::Mnemonic;
+use bitcoin::util::bip32::ExtendedPrivKey;
+use bitcoin::XpubIdentifier;
+use keyring::rpc::types::{CollisionPolicy, PaymentCode, PaymentDirection};
+use keyring::vault::keymgm::Error;
+use keyring::vault::Vault;
+use keyring::RuntimeError;
+
+use common::{decryption_key, encryption_key, open, path};
+
+// Test vectors from BIP-47
+const ALICE_MNEMONIC: &str = "response seminar brave tip suit recall often \
+                              sound stick majority eyebrow advice";
+const ALICE_CODE: &str = "PM8TJTLJbPRGxSbc8EJi42Wrr6QbNSaSSVJ5Y3E4pbCYiTHUskH\
+                          g13935Ubb7q8tx9GVbh2UuRnBc3WSyJHhUrw8KhprKnn9eDznYGi\
+                          eTzFcwQRya4GA";
+const ALICE_NOTIFICATION: &str = "1JDdmqFLhpzcUwPeinhJbUPw4Co3aWLyzW";
+const BOB_MNEMONIC: &str = "reward upper indicate eight swift arch injury \
+                            crystal super wrestle already dentist";
+const BOB_CODE: &str = "PM8TJS2JxQ5ztXUpBBRnpTbcUXbUHy2T1abfrb3KkAAtMEGNbey4o\
+                        umH7Hc578WgQJhPjBxteQ5GHHToTYHE3A1w6p7tU6KSoFmWBVbFGj\
+                        KPisZDbP97";
+const BOB_NOTIFICATION: &str = "1ChvUUvht2hUQufHBXF8NgLhW8SwE2ecGV";
+/// Addresses of the first payments from Alice to Bob
+const PAYMENTS: [&str; 2] = [
+    "141fi7TY3h936vRUKh1qfUZr8rSBuYbVBK",
+    "12u3Uued2fuko2nY4SoSFGCoGLCBUGPkk6",
+];
+
+fn import(vault: &mut Vault, name: &str, mnemonic: &str) -> XpubIdentifier {
+    let seed = Mnemonic::parse(mnemonic).unwrap().to_seed("");
+    let xpriv =
+        ExtendedPrivKey::new_master(bitcoin::Network::Bitcoin, &seed).unwrap();
+    vault
+        .import_xpriv(
+            xpriv,
+            None,
+            None,
+            name,
+            None::<String>,
+            CollisionPolicy::Reject,
+            encryption_key(),
+        )
+        .unwrap()
+        .id
+}
+
+#[test]
+fn payment_code_encoding() {
+    let code = PaymentCode::from_str(ALICE_CODE).unwrap();
+    assert_eq!(code.to_string(), ALICE_CODE);
+    assert_eq!(PaymentCode::from_slice(&code.serialize()), Ok(code));
+
+    let mut data = code.serialize();
+    data[0] = 0x02;
+    assert!(PaymentCode::from_slice(&data).is_err());
+    assert!(PaymentCode::from_slice(&data[..79]).is_err());
+    let truncated = &ALICE_CODE[..ALICE_CODE.len() - 1];
+    assert!(PaymentCode::from_str(truncated).is_err());
+    assert!(PaymentCode::from_str(
+        "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gS"
+    )
+    .is_err());
+}
+
+#[test]
+fn payment_keys() {
+    let path = path("bip47");
+    let _ = fs::remove_file(&path);
+    let mut vault = open(&path);
+    let alice = import(&mut vault, "Alice", ALICE_MNEMONIC);
+    let bob = import(&mut vault, "Bob", BOB_MNEMONIC);
+    let bob_code = PaymentCode::from_str(BOB_CODE).unwrap();
+
+    let info = vault
+        .payment_code(
+            alice,
+            0,
+            Some(bob_code),
+            Some("Bob".to_string()),
+            0,
+            2,
+            &mut decryption_key(),
+        )
+        .unwrap();
+    assert_eq!(info.code.to_string(), ALICE_CODE);
+    assert_eq!(info.notification_address, ALICE_NOTIFICATION);
+    assert_eq!(info.contacts.get("Bob"), Some(&bob_code));
+    let sent = info
+        .payments
+        .iter()
+        .filter(|key| key.direction == PaymentDirection::Send)
+        .map(|key| key.address.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(sent, PAYMENTS);
+
+    // Bob derives the same keys for the payments received from Alice
+    let info = vault
+        .payment_code(
+            bob,
+            0,
+            Some(PaymentCode::from_str(ALICE_CODE).unwrap()),
+            None,
+            0,
+            2,
+            &mut decryption_key(),
+        )
+        .unwrap();
+    assert_eq!(info.code.to_string(), BOB_CODE);
+    assert_eq!(info.notification_address, BOB_NOTIFICATION);
+    assert!(info.contacts.is_empty());
+    let received = info
+        .payments
+        .iter()
+        .filter(|key| key.direction == PaymentDirection::Receive)
+        .map(|key| key.address.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(received, PAYMENTS);
+
+    // Counterparty code is kept in the vault under the contact name
+    let mut vault = open(&path);
+    let info = vault
+        .payment_code(
+            alice,
+            0,
+            None,
+            Some("Bob".to_string()),
+            1,
+            1,
+            &mut decryption_key(),
+        )
+        .unwrap();
+    assert_eq!(info.counterparty, Some(bob_code));
+    assert_eq!(info.payments.len(), 2);
+    assert_eq!(info.payments[0].address, PAYMENTS[1]);
+    match vault.payment_code(
+        alice,
+        0,
+        None,
+        Some("Carol".to_string()),
+        0,
+        1,
+        &mut decryption_key(),
+    ) {
+        Err(RuntimeError::KeyManagement(Error::UnknownContact(name))) => {
+            assert_eq!(name, "Carol")
+        }
+        other => panic!("unknown contact is resolved: {:?}", other),
+    }
+}
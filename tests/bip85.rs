@@ -0,0 +1,108 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "node")]
+
+mod common;
+
+use std::str::FromStr;
+
+use bitcoin::util::bip32::ExtendedPrivKey;
+use keyring::rpc::types::Bip85Application;
+use keyring::vault::{bip85, keymgm, Keyring};
+
+use common::{decryption_key, encryption_key};
+
+/// Master key of the BIP-85 test vectors
+const MASTER: &str = "xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb";
+
+fn derive(application: &str) -> Result<String, keymgm::Error> {
+    let keyring = Keyring::from_xpriv(
+        "BIP-85",
+        "Test vectors",
+        None,
+        ExtendedPrivKey::from_str(MASTER).unwrap(),
+        None,
+        encryption_key(),
+    )
+    .unwrap();
+    let account = keyring.account_by_id(keyring.identifier()).unwrap();
+    let application = Bip85Application::from_str(application).unwrap();
+    bip85::derive(account, application, 0, &mut decryption_key())
+}
+
+#[test]
+fn bip85_vectors() {
+    assert_eq!(
+        derive("mnemonic:12").unwrap(),
+        "girl mad pet galaxy egg matter matrix prison refuse sense ordinary \
+         nose"
+    );
+    assert_eq!(
+        derive("wif").unwrap(),
+        "Kzyv4uF39d4Jrw2W7UryTHwZr1zQVNk4dAFyqE6BuMrMh1Za7uhp"
+    );
+    assert_eq!(
+        derive("xprv").unwrap(),
+        "xprv9s21ZrQH143K2srSbCSg4m4kLvPMzcWydgmKEnMmoZUurYuBuYG46c6P71UGXMzm\
+         riLzCCBvKQWBUv3vPB3m1SATMhp3uEjXHJ42jFg7myX"
+    );
+    assert_eq!(
+        derive("hex").unwrap(),
+        "492db4698cf3b73a5a24998aa3e9d7fa96275d85724a91e71aa2d645442f8785\
+         55d078fd1f1f67e368976f04137b1f7a0d19232136ca50c44614af72b5582a5c"
+    );
+}
+
+#[test]
+fn bip85_applications() {
+    for (s, application) in &[
+        (
+            "mnemonic",
+            Bip85Application::Mnemonic {
+                language: 0,
+                words: 24,
+            },
+        ),
+        (
+            "bip39:18:8",
+            Bip85Application::Mnemonic {
+                language: 8,
+                words: 18,
+            },
+        ),
+        ("wif", Bip85Application::Wif),
+        ("xprv", Bip85Application::Xprv),
+        ("hex:16", Bip85Application::Hex { bytes: 16 }),
+    ] {
+        let parsed = Bip85Application::from_str(s).unwrap();
+        assert_eq!(parsed, *application);
+        assert_eq!(Bip85Application::from_str(&parsed.to_string()), Ok(parsed));
+    }
+    for s in &[
+        "mnemonic:13",
+        "mnemonic:12:9",
+        "hex:8",
+        "hex:65",
+        "wif:1",
+        "",
+    ] {
+        assert!(Bip85Application::from_str(s).is_err());
+    }
+    assert_eq!(
+        bip85::path(Bip85Application::Hex { bytes: 64 }, 0)
+            .unwrap()
+            .to_string(),
+        "m/83696968'/128169'/64'/0'"
+    );
+}
@@ -0,0 +1,142 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "node")]
+
+mod common;
+
+use std::str::FromStr;
+
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1;
+use bitcoin::util::bip32::{DerivationPath, ExtendedPrivKey, ExtendedPubKey};
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::{OutPoint, PublicKey, Script, Transaction, TxIn, TxOut, Txid};
+use keyring::rpc::types::AccountQuery;
+use keyring::vault::keymgm::Error;
+use keyring::vault::Vault;
+use keyring::{RuntimeError, SECP256K1};
+use lnpbp::Chain;
+
+use common::{decryption_key, xpriv};
+
+const SEED: u8 = 0x3C;
+
+fn vault(name: &str) -> Vault {
+    common::vault(name, SEED, "Testnet keys").0
+}
+/// PSBT spending P2WPKH output of the keyring key with `derivation`
+
+fn psbt(derivation: &str) -> (PartiallySignedTransaction, PublicKey) {
+    let derivation = DerivationPath::from_str(derivation).unwrap();
+    let pubkey = PublicKey {
+        compressed: true,
+        key: secp256k1::PublicKey::from_secret_key(
+            &SECP256K1,
+            &xpriv(SEED)
+                .derive_priv(&SECP256K1, &derivation)
+                .unwrap()
+                .private_key
+                .key,
+        ),
+    };
+    let spent = TxOut {
+        value: 10_000,
+        script_pubkey: Script::new_v0_wpkh(&pubkey.wpubkey_hash().unwrap()),
+    };
+    let mut psbt = PartiallySignedTransaction::from_unsigned_tx(Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(Txid::from_inner([3u8; 32]), 0),
+            script_sig: Script::new(),
+            sequence: 0xFFFF_FFFD,
+            witness: vec![],
+        }],
+        output: vec![TxOut {
+            value: 9_000,
+            script_pubkey: spent.script_pubkey.clone(),
+        }],
+    })
+    .unwrap();
+    let input = &mut psbt.inputs[0];
+    input.witness_utxo = Some(spent);
+    input
+        .bip32_derivation
+        .insert(pubkey, (xpriv(SEED).fingerprint(&SECP256K1), derivation));
+    (psbt, pubkey)
+}
+
+fn assert_mismatch(result: Result<PartiallySignedTransaction, RuntimeError>) {
+    match result {
+        Err(RuntimeError::KeyManagement(Error::ChainMismatch(0, chain))) => {
+            assert_eq!(chain, Chain::Testnet3)
+        }
+        other => panic!("PSBT of other network is signed: {:?}", other),
+    }
+}
+
+#[test]
+fn keyring_chain() {
+    let vault = vault("chain-list");
+    let accounts = vault.list().unwrap();
+    assert_eq!(accounts[0].chain, Some(Chain::Testnet3));
+
+    let query = |chain| AccountQuery {
+        chain: Some(chain),
+        ..Default::default()
+    };
+    assert!(query(Chain::Testnet3).matches(&accounts[0]));
+    assert!(!query(Chain::Mainnet).matches(&accounts[0]));
+}
+
+#[test]
+fn network_enforcement() {
+    let mut vault = vault("chain-sign");
+
+    let (testnet, pubkey) = psbt("m/84'/1'/0'/0/0");
+    let signed = vault
+        .sign_psbt(testnet, false, &mut decryption_key(), &mut || ())
+        .unwrap();
+    assert!(signed.inputs[0].partial_sigs.contains_key(&pubkey));
+
+    // Derivations outside of BIP-44 scheme do not specify network
+    let (custom, pubkey) = psbt("m/0/7");
+    let signed = vault
+        .sign_psbt(custom, false, &mut decryption_key(), &mut || ())
+        .unwrap();
+    assert!(signed.inputs[0].partial_sigs.contains_key(&pubkey));
+
+    let (mainnet, _) = psbt("m/84'/0'/0'/0/0");
+    assert_mismatch(vault.sign_psbt(
+        mainnet,
+        false,
+        &mut decryption_key(),
+        &mut || (),
+    ));
+
+    let (mut global, _) = psbt("m/0/7");
+    let xpriv =
+        ExtendedPrivKey::new_master(bitcoin::Network::Bitcoin, &[0x01u8; 32])
+            .unwrap();
+    global.global.xpub.insert(
+        ExtendedPubKey::from_private(&SECP256K1, &xpriv),
+        (xpriv.fingerprint(&SECP256K1), DerivationPath::master()),
+    );
+    assert_mismatch(vault.sign_psbt(
+        global,
+        false,
+        &mut decryption_key(),
+        &mut || (),
+    ));
+}
@@ -0,0 +1,285 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(all(feature = "server", feature = "cli"))]
+
+mod common;
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::{fs, thread};
+
+use bitcoin::secp256k1;
+use bitcoin::XpubIdentifier;
+use internet2::zmqsocket::ZmqSocketAddr;
+use keyring::cli::{self, Client, SecretSource};
+use keyring::daemon::{self, Runtime};
+use keyring::passphrase::Kdf;
+use keyring::rpc::types::AuthCode;
+use keyring::rpc::{message, Reply, Request};
+use keyring::vault::{driver, file_driver, Encryption};
+use lnpbp::Chain;
+use microservices::node::TryService;
+use microservices::FileFormat;
+use slip132::KeyApplication;
+
+use common::decryption_key;
+
+const PASSPHRASE: &str = "correct horse battery staple";
+
+fn temp_path(name: &str, ext: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "keyring-{}-{}.{}",
+        std::process::id(),
+        name,
+        ext
+    ));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn file_vault(name: &str) -> driver::Config {
+    driver::Config::File(file_driver::Config {
+        location: temp_path(name, "vault").display().to_string(),
+        format: FileFormat::StrictEncode,
+        backups: 0,
+        signed: false,
+        node_key: None,
+        read_only: false,
+    })
+}
+
+/// Starts daemon serving empty vaults over IPC socket, using the common
+/// decryption key as the node key. Besides the default vault, the daemon
+/// serves a vault for each of the `vaults` ids.
+fn daemon(
+    name: &str,
+    encryption: Encryption,
+    vaults: &[&str],
+) -> ZmqSocketAddr {
+    let endpoint =
+        ZmqSocketAddr::Ipc(temp_path(name, "rpc").display().to_string());
+    let mut config = daemon::Config::default();
+    config.node_key = decryption_key();
+    config.endpoint = endpoint.clone();
+    config.vault = file_vault(name);
+    config.vaults = vaults
+        .iter()
+        .map(|id| {
+            let vault = daemon::VaultConfig {
+                chains: vec![],
+                backup: None,
+                journal: None,
+                vault: file_vault(&format!("{}-{}", name, id)),
+            };
+            (id.to_string(), vault)
+        })
+        .collect::<BTreeMap<_, _>>();
+    config.backup = None;
+    config.encryption = encryption;
+    config.passphrase.min_entropy = 0;
+    config.passphrase.kdf = Kdf::Scrypt {
+        log_n: 4,
+        r: 8,
+        p: 1,
+    };
+    thread::spawn(move || {
+        Runtime::init(config)
+            .expect("daemon runtime")
+            .try_run_loop()
+            .expect("daemon run loop")
+    });
+    endpoint
+}
+
+fn client(endpoint: &ZmqSocketAddr, config: cli::Config) -> Client {
+    Client::with(cli::Config {
+        endpoint: endpoint.clone(),
+        ..config
+    })
+    .unwrap()
+}
+
+/// File secret source with the `secret` written to it
+fn secret_file(name: &str, secret: &str) -> Option<SecretSource> {
+    let path = temp_path(name, "secret");
+    fs::write(&path, secret).unwrap();
+    Some(SecretSource::File(path))
+}
+
+fn seed(client: &mut Client) -> XpubIdentifier {
+    match client
+        .request(Request::Seed(message::Seed {
+            name: "Signing".to_owned(),
+            chain: Chain::Testnet3,
+            application: KeyApplication::SegWit,
+            description: None,
+            idempotency_key: None,
+            auth_code: AuthCode::default(),
+        }))
+        .unwrap()
+    {
+        Reply::AccountInfo(info) => info.id,
+        reply => panic!("unexpected reply {}", reply),
+    }
+}
+
+fn sign_data(client: &mut Client, key_id: XpubIdentifier) -> Reply {
+    client
+        .request(Request::SignData(message::SignData {
+            key_id,
+            data: b"message".to_vec(),
+            decryption_key: Client::DECRYPTION_KEY_PLACEHOLDER,
+            session: None,
+            auth_code: AuthCode::default(),
+        }))
+        .unwrap()
+}
+
+#[test]
+fn node_key_vault() {
+    let endpoint = daemon("client-node-key", Encryption::NodeKey, &[]);
+
+    // The key is read from the `--vault-key` source
+    let mut with_source = client(
+        &endpoint,
+        cli::Config {
+            vault_key: secret_file(
+                "client-node-key",
+                &decryption_key().to_string(),
+            ),
+            ..cli::Config::default()
+        },
+    );
+    assert!(!with_source.is_passphrase_encrypted());
+    let key_id = seed(&mut with_source);
+    assert!(matches!(
+        sign_data(&mut with_source, key_id),
+        Reply::Signature(_)
+    ));
+    assert_eq!(with_source.session(), None);
+
+    // Without the source the client node key is used
+    let mut shared = client(
+        &endpoint,
+        cli::Config {
+            node_key: decryption_key(),
+            ..cli::Config::default()
+        },
+    );
+    assert!(matches!(
+        sign_data(&mut shared, key_id),
+        Reply::Signature(_)
+    ));
+
+    let mut other = client(
+        &endpoint,
+        cli::Config {
+            node_key: secp256k1::SecretKey::from_slice(&[0x11; 32]).unwrap(),
+            ..cli::Config::default()
+        },
+    );
+    assert!(matches!(sign_data(&mut other, key_id), Reply::Failure(_)));
+}
+
+#[test]
+fn passphrase_vault() {
+    let endpoint = daemon(
+        "client-passphrase",
+        Encryption::Passphrase { unlock_timeout: 60 },
+        &[],
+    );
+    let passphrase = secret_file("client-passphrase", PASSPHRASE);
+
+    // Passphrase of the empty vault is confirmed by the repeated unlock
+    let mut owner = client(&endpoint, cli::Config::default());
+    assert!(owner.is_passphrase_encrypted());
+    let session = owner.unlock(&passphrase).unwrap();
+    owner.set_session(Some(session.token));
+    let key_id = seed(&mut owner);
+
+    // Client without a session unlocks the vault for the first request
+    // using private keys and keeps the session for the next ones
+    let mut signer = client(
+        &endpoint,
+        cli::Config {
+            vault_passphrase: passphrase,
+            ..cli::Config::default()
+        },
+    );
+    assert_eq!(signer.session(), None);
+    assert!(matches!(
+        sign_data(&mut signer, key_id),
+        Reply::Signature(_)
+    ));
+    let session = signer.session().expect("vault is unlocked");
+    assert!(matches!(
+        sign_data(&mut signer, key_id),
+        Reply::Signature(_)
+    ));
+    assert_eq!(signer.session(), Some(session));
+
+    let mut other = client(&endpoint, cli::Config::default());
+    let wrong = secret_file("client-wrong", "wrong passphrase");
+    assert!(other.unlock(&wrong).is_err());
+}
+
+#[test]
+fn multi_vault_unlock() {
+    let endpoint = daemon(
+        "client-vaults",
+        Encryption::Passphrase { unlock_timeout: 60 },
+        &["cold"],
+    );
+
+    let mut hot = client(&endpoint, cli::Config::default());
+    let session = hot
+        .unlock(&secret_file("client-vaults-hot", PASSPHRASE))
+        .unwrap();
+    hot.set_session(Some(session.token));
+    let hot_id = seed(&mut hot);
+
+    // Unlocking the empty vault with other passphrase doesn't change the
+    // passphrase of the default vault
+    let mut cold = client(
+        &endpoint,
+        cli::Config {
+            vault: Some("cold".to_owned()),
+            ..cli::Config::default()
+        },
+    );
+    let session = cold
+        .unlock(&secret_file("client-vaults-cold", "cold passphrase"))
+        .unwrap();
+    cold.set_session(Some(session.token));
+    let cold_id = seed(&mut cold);
+
+    let hot_id2 = seed(&mut hot);
+    assert!(matches!(sign_data(&mut hot, hot_id), Reply::Signature(_)));
+    assert!(matches!(sign_data(&mut hot, hot_id2), Reply::Signature(_)));
+    assert!(matches!(sign_data(&mut cold, cold_id), Reply::Signature(_)));
+
+    // Session is accepted only by the vault it was issued for: the request
+    // for the key of the cold vault is routed there by its id
+    assert!(matches!(sign_data(&mut hot, cold_id), Reply::Failure(_)));
+
+    let mut other = client(
+        &endpoint,
+        cli::Config {
+            vault: Some("cold".to_owned()),
+            ..cli::Config::default()
+        },
+    );
+    let hot_passphrase = secret_file("client-vaults-other", PASSPHRASE);
+    assert!(other.unlock(&hot_passphrase).is_err());
+}
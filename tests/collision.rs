@@ -0,0 +1,143 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "node")]
+
+mod common;
+
+use std::str::FromStr;
+
+use bitcoin::util::bip32::{DerivationPath, ExtendedPubKey};
+use keyring::rpc::types::CollisionPolicy;
+use keyring::vault::keymgm::Error;
+use keyring::vault::Vault;
+use keyring::{RuntimeError, SECP256K1};
+use slip132::KeyApplication;
+
+use common::{empty_vault, encryption_key, xpriv};
+
+const SEED: u8 = 0x5A;
+
+fn import(
+    vault: &mut Vault,
+    name: &str,
+    details: Option<&str>,
+    collision: CollisionPolicy,
+) -> Result<keyring::rpc::types::AccountInfo, RuntimeError> {
+    vault.import_xpriv(
+        xpriv(SEED),
+        None,
+        Some(KeyApplication::SegWit),
+        name,
+        details,
+        collision,
+        encryption_key(),
+    )
+}
+
+#[test]
+fn collision_policy_roundtrip() {
+    for policy in &[
+        CollisionPolicy::Reject,
+        CollisionPolicy::Skip,
+        CollisionPolicy::Merge,
+        CollisionPolicy::Alias,
+    ] {
+        assert_eq!(CollisionPolicy::from_str(&policy.to_string()), Ok(*policy));
+    }
+    assert_eq!(CollisionPolicy::default(), CollisionPolicy::Reject);
+    assert!(CollisionPolicy::from_str("overwrite").is_err());
+}
+
+#[test]
+fn reject_and_skip() {
+    let mut vault = empty_vault("collision-reject");
+    let id = import(&mut vault, "Original", None, CollisionPolicy::Reject)
+        .unwrap()
+        .id;
+    match import(&mut vault, "Duplicate", None, CollisionPolicy::Reject) {
+        Err(RuntimeError::KeyManagement(Error::KnownKey(known))) => {
+            assert_eq!(known, id)
+        }
+        other => panic!("duplicate key is not rejected: {:?}", other),
+    }
+    let info =
+        import(&mut vault, "Duplicate", None, CollisionPolicy::Skip).unwrap();
+    assert_eq!(info.name, "Original");
+    assert_eq!(vault.count(), (1, 1));
+}
+
+#[test]
+fn merge_metadata() {
+    let mut vault = empty_vault("collision-merge");
+    let xpub = ExtendedPubKey::from_private(&SECP256K1, &xpriv(SEED));
+    vault
+        .import_xpub(
+            xpub,
+            None,
+            None,
+            "Watch-only",
+            None::<String>,
+            CollisionPolicy::Reject,
+        )
+        .unwrap();
+    let info = import(
+        &mut vault,
+        "Merged",
+        Some("From backup"),
+        CollisionPolicy::Merge,
+    )
+    .unwrap();
+    assert_eq!(info.name, "Merged");
+    assert_eq!(info.details.as_deref(), Some("From backup"));
+    assert_eq!(info.application, Some(KeyApplication::SegWit));
+    assert!(info.watch_only);
+    assert_eq!(vault.count(), (1, 1));
+}
+
+#[test]
+fn create_alias() {
+    let mut vault = empty_vault("collision-alias");
+    import(&mut vault, "Original", None, CollisionPolicy::Reject).unwrap();
+    import(&mut vault, "Second", None, CollisionPolicy::Alias).unwrap();
+    let info =
+        import(&mut vault, "Second", None, CollisionPolicy::Alias).unwrap();
+    assert_eq!(info.name, "Original");
+    assert_eq!(info.aliases, vec!["Second".to_string()]);
+    assert!(info.to_string().starts_with("Original (aka Second) ["));
+    assert_eq!(vault.list().unwrap(), vec![info]);
+}
+
+#[test]
+fn group_watch_only() {
+    let mut vault = empty_vault("collision-group");
+    let fingerprint = xpriv(SEED).fingerprint(&SECP256K1);
+    for path in &["m/84'/1'/0'", "m/84'/1'/0'/1'", "m/84'/1'/0'/0"] {
+        let path = DerivationPath::from_str(path).unwrap();
+        let xpub = ExtendedPubKey::from_private(
+            &SECP256K1,
+            &xpriv(SEED).derive_priv(&SECP256K1, &path).unwrap(),
+        );
+        vault
+            .import_xpub(
+                xpub,
+                Some((fingerprint, path)),
+                None,
+                "Watch-only",
+                None::<String>,
+                CollisionPolicy::Reject,
+            )
+            .unwrap();
+    }
+    assert_eq!(vault.count(), (1, 3));
+}
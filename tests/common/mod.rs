@@ -0,0 +1,90 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Fixtures shared by the integration tests. Each test crate uses only some
+//! of them.
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bitcoin::secp256k1;
+use bitcoin::util::bip32::ExtendedPrivKey;
+use bitcoin::XpubIdentifier;
+use keyring::rpc::types::CollisionPolicy;
+use keyring::vault::{driver, file_driver, Vault};
+use keyring::SECP256K1;
+use microservices::FileFormat;
+
+/// Key with which private keys of the test vaults are encrypted
+pub fn decryption_key() -> secp256k1::SecretKey {
+    secp256k1::SecretKey::from_slice(&[0xA5u8; 32]).unwrap()
+}
+
+pub fn encryption_key() -> secp256k1::PublicKey {
+    secp256k1::PublicKey::from_secret_key(&SECP256K1, &decryption_key())
+}
+
+/// Testnet master key generated from the seed filled with `byte`
+pub fn xpriv(byte: u8) -> ExtendedPrivKey {
+    ExtendedPrivKey::new_master(bitcoin::Network::Testnet, &[byte; 32]).unwrap()
+}
+
+/// Temporary path of the vault file, unique for the test process
+pub fn path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "keyring-{}-{}.vault",
+        std::process::id(),
+        name
+    ))
+}
+
+/// Opens file vault at `path`, creating the file if it does not exist
+pub fn open(path: &Path) -> Vault {
+    Vault::with(&driver::Config::File(file_driver::Config {
+        location: path.display().to_string(),
+        format: FileFormat::StrictEncode,
+        backups: 0,
+        signed: false,
+        node_key: None,
+        read_only: false,
+    }))
+    .unwrap()
+}
+
+/// Creates empty file vault, removing the file left by previous test runs
+pub fn empty_vault(name: &str) -> Vault {
+    let path = path(name);
+    let _ = fs::remove_file(&path);
+    open(&path)
+}
+
+/// Creates file vault with a single keyring imported from [`xpriv`] of the
+/// `byte` as account `account`. Returns the vault and the keyring id.
+pub fn vault(name: &str, byte: u8, account: &str) -> (Vault, XpubIdentifier) {
+    let mut vault = empty_vault(name);
+    let id = vault
+        .import_xpriv(
+            xpriv(byte),
+            None,
+            None,
+            account,
+            None::<String>,
+            CollisionPolicy::Reject,
+            encryption_key(),
+        )
+        .unwrap()
+        .id;
+    (vault, id)
+}
@@ -0,0 +1,562 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+//! Interoperability with other wallets: keys derived and signatures made by
+//! the vault are checked against the reference vectors of BIP-32, BIP-39,
+//! BIP-49, BIP-84, BIP-86 and BIP-341.
+
+#![cfg(feature = "node")]
+
+mod common;
+
+use std::fs;
+use std::str::FromStr;
+
+use bip39::Mnemonic;
+use bitcoin::blockdata::script::Builder;
+use bitcoin::consensus::deserialize;
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{schnorrsig, Message, SecretKey, Signature};
+use bitcoin::util::bip143::SigHashCache;
+use bitcoin::util::bip32::{DerivationPath, ExtendedPrivKey, ExtendedPubKey};
+use bitcoin::util::psbt::{raw, PartiallySignedTransaction};
+use bitcoin::{
+    Address, Network, OutPoint, PublicKey, Script, SigHashType, Transaction,
+    TxIn, TxOut, Txid,
+};
+use keyring::rpc::types::{CollisionPolicy, SigningPolicy};
+use keyring::vault::{taproot, Keyring};
+use keyring::{RuntimeError, SECP256K1};
+use slip132::KeyApplication;
+
+use common::{decryption_key, encryption_key, open, path};
+
+/// Mnemonic of the BIP-49, BIP-84 and BIP-86 test vectors
+const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon \
+                        abandon abandon abandon abandon abandon about";
+
+fn master(
+    mnemonic: &str,
+    passphrase: &str,
+    network: Network,
+) -> ExtendedPrivKey {
+    let seed = Mnemonic::parse(mnemonic).unwrap().to_seed(passphrase);
+    ExtendedPrivKey::new_master(network, &seed).unwrap()
+}
+
+/// Derives private key with the given `path` from a keyring created out of
+/// the `master` key
+fn derive(master: ExtendedPrivKey, path: &str) -> ExtendedPrivKey {
+    let keyring = Keyring::from_xpriv(
+        "Conformance",
+        "Test vectors",
+        None,
+        master,
+        None,
+        encryption_key(),
+    )
+    .unwrap();
+    let (_, account) = keyring
+        .derive_account(
+            DerivationPath::from_str(path).unwrap(),
+            path,
+            None::<String>,
+            Default::default(),
+            &mut decryption_key(),
+        )
+        .unwrap();
+    *account.xprivkey(&mut decryption_key()).unwrap()
+}
+
+fn pubkey(xpriv: &ExtendedPrivKey) -> PublicKey {
+    ExtendedPubKey::from_private(&SECP256K1, xpriv).public_key
+}
+
+#[test]
+fn bip32_vector_1() {
+    let seed = Vec::from_hex("000102030405060708090a0b0c0d0e0f").unwrap();
+    let master = ExtendedPrivKey::new_master(Network::Bitcoin, &seed).unwrap();
+    assert_eq!(
+        master.to_string(),
+        "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmP\
+         GJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi"
+    );
+    for (path, xpriv, xpub) in &[
+        (
+            "m/0h",
+            "xprv9uHRZZhk6KAJC1avXpDAp4MDc3sQKNxDiPvvkX8Br5ngLNv1TxvUxt4cV1rGL\
+             5hj6KCesnDYUhd7oWgT11eZG7XnxHrnYeSvkzY7d2bhkJ7",
+            "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwB\
+             ZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw",
+        ),
+        (
+            "m/0h/1",
+            "",
+            "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWb\
+             WMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ",
+        ),
+        (
+            "m/0h/1/2h",
+            "",
+            "xpub6D4BDPcP2GT577Vvch3R8wDkScZWzQzMMUm3PWbmWvVJrZwQY4VUNgqFJPMM3\
+             No2dFDFGTsxxpG5uJh7n7epu4trkrX7x7DogT5Uv6fcLW5",
+        ),
+        (
+            "m/0h/1/2h/2",
+            "",
+            "xpub6FHa3pjLCk84BayeJxFW2SP4XRrFd1JYnxeLeU8EqN3vDfZmbqBqaGJAyiLjT\
+             Awm6ZLRQUMv1ZACTj37sR62cfN7fe5JnJ7dh8zL4fiyLHV",
+        ),
+        (
+            "m/0h/1/2h/2/1000000000",
+            "",
+            "xpub6H1LXWLaKsWFhvm6RVpEL9P4KfRZSW7abD2ttkWP3SSQvnyA8FSVqNTEcYFgJ\
+             S2UaFcxupHiYkro49S8yGasTvXEYBVPamhGW6cFJodrTHy",
+        ),
+    ] {
+        let derived = derive(master, path);
+        if !xpriv.is_empty() {
+            assert_eq!(&derived.to_string(), xpriv);
+        }
+        assert_eq!(
+            &ExtendedPubKey::from_private(&SECP256K1, &derived).to_string(),
+            xpub
+        );
+    }
+}
+
+#[test]
+fn bip39_vectors() {
+    for (entropy, mnemonic, seed, xpriv) in &[
+        (
+            "00000000000000000000000000000000",
+            "abandon abandon abandon abandon abandon abandon abandon abandon \
+             abandon abandon abandon about",
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e5349553\
+             1f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04",
+            "xprv9s21ZrQH143K3h3fDYiay8mocZ3afhfULfb5GX8kCBdno77K4HiA15Tg23wpb\
+             eF1pLfs1c5SPmYHrEpTuuRhxMwvKDwqdKiGJS9XFKzUsAF",
+        ),
+        (
+            "7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+            "legal winner thank year wave sausage worth useful legal winner \
+             thank yellow",
+            "2e8905819b8723fe2c1d161860e5ee1830318dbf49a83bd451cfb8440c28bd6f\
+             a457fe1296106559a3c80937a1c1069be3a3a5bd381ee6260e8d9739fce1f607",
+            "xprv9s21ZrQH143K2gA81bYFHqU68xz1cX2APaSq5tt6MFSLeXnCKV1RVUJt9FWNT\
+             brrryem4ZckN8k4Ls1H6nwdvDTvnV7zEXs2HgPezuVccsq",
+        ),
+        (
+            "80808080808080808080808080808080",
+            "letter advice cage absurd amount doctor acoustic avoid letter \
+             advice cage above",
+            "d71de856f81a8acc65e6fc851a38d4d7ec216fd0796d0a6827a3ad6ed5511a30\
+             fa280f12eb2e47ed2ac03b5c462a0358d18d69fe4f985ec81778c1b370b652a8",
+            "xprv9s21ZrQH143K2shfP28KM3nr5Ap1SXjz8gc2rAqqMEynmjt6o1qboCDpxckqX\
+             avCwdnYds6yBHZGKHv7ef2eTXy461PXUjBFQg6PrwY4Gzq",
+        ),
+        (
+            "ffffffffffffffffffffffffffffffff",
+            "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo wrong",
+            "ac27495480225222079d7be181583751e86f571027b0497b5b5d11218e0a8a13\
+             332572917f0f8e5a589620c6f15b11c61dee327651a14c34e18231052e48c069",
+            "xprv9s21ZrQH143K2V4oox4M8Zmhi2Fjx5XK4Lf7GKRvPSgydU3mjZuKGCTg7UPiB\
+             UD7ydVPvSLtg9hjp7MQTYsW67rZHAXeccqYqrsx8LcXnyd",
+        ),
+    ] {
+        let generated =
+            Mnemonic::from_entropy(&Vec::from_hex(entropy).unwrap()).unwrap();
+        assert_eq!(&generated.to_string(), mnemonic);
+        assert_eq!(&generated.to_seed("TREZOR").to_hex(), seed);
+        assert_eq!(
+            &master(mnemonic, "TREZOR", Network::Bitcoin).to_string(),
+            xpriv
+        );
+    }
+}
+
+#[test]
+fn bip49_bip84_vectors() {
+    let master = master(MNEMONIC, "", Network::Bitcoin);
+    assert_eq!(
+        master.to_string(),
+        "xprv9s21ZrQH143K3GJpoapnV8SFfukcVBSfeCficPSGfubmSFDxo1kuHnLisriDvSnRR\
+         uL2Qrg5ggqHKNVpxR86QEC8w35uxmGoggxtQTPvfUu"
+    );
+    for (path, application, key, address) in &[
+        (
+            "m/84h/0h/0h/0/0",
+            KeyApplication::SegWit,
+            "0330d54fd0dd420a6e5f8d3624f5f3482cae350f79d5f0753bf5beef9c2d91af3c",
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu",
+        ),
+        (
+            "m/84h/0h/0h/0/1",
+            KeyApplication::SegWit,
+            "03e775fd51f0dfb8cd865d9ff1cca2a158cf651fe997fdc9fee9c1d3b5e995ea77",
+            "bc1qnjg0jd8228aq7egyzacy8cys3knf9xvrerkf9g",
+        ),
+        (
+            "m/84h/0h/0h/1/0",
+            KeyApplication::SegWit,
+            "03025324888e429ab8e3dbaf1f7802648b9cd01e9b418485c5fa4c1b9b5700e1a6",
+            "bc1q8c6fshw2dlwun7ekn9qwf37cu2rn755upcp6el",
+        ),
+        (
+            "m/49h/1h/0h/0/0",
+            KeyApplication::Nested,
+            "03a1af804ac108a8a51782198c2d034b28bf90c8803f5a53f76276fa69a4eae77f",
+            "2Mww8dCYPUpKHofjgcXcBCEGmniw9CoaiD2",
+        ),
+    ] {
+        let pubkey = pubkey(&derive(master, path));
+        assert_eq!(&pubkey.to_string(), key);
+        assert_eq!(
+            keyring::chain::script_pubkey(&pubkey, *application).unwrap(),
+            Address::from_str(address).unwrap().script_pubkey()
+        );
+    }
+}
+
+#[test]
+fn bip86_vectors() {
+    // Bech32m addresses are not supported by the bitcoin library yet, so
+    // output keys are compared instead of the addresses
+    let master = master(MNEMONIC, "", Network::Bitcoin);
+    let seckey = derive(master, "m/86h/0h/0h/0/0").private_key.key;
+    let internal_key = schnorrsig::PublicKey::from_keypair(
+        &SECP256K1,
+        &schnorrsig::KeyPair::from_seckey_slice(&SECP256K1, &seckey[..])
+            .unwrap(),
+    );
+    assert_eq!(
+        internal_key.to_string(),
+        "cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115"
+    );
+    let output_key = schnorrsig::PublicKey::from_keypair(
+        &SECP256K1,
+        &taproot::tweaked_keypair(&seckey).unwrap(),
+    );
+    assert_eq!(
+        output_key.to_string(),
+        "a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c"
+    );
+    let script = Builder::new()
+        .push_int(1)
+        .push_slice(&output_key.serialize())
+        .into_script();
+    assert_eq!(taproot::output_key(&script), Some(output_key));
+}
+
+#[test]
+fn bip341_vectors() {
+    // Key path spending vectors from the BIP-341 wallet test vectors
+    let tx: Transaction = deserialize(
+        &Vec::from_hex(
+            "02000000097de20cbff686da83a54981d2b9bab3586f4ca7e48f57f5b55963115f\
+             3b334e9c010000000000000000d7b7cab57b1393ace2d064f4d4a2cb8af6def612\
+             73e127517d44759b6dafdd990000000000fffffffff8e1f583384333689228c5d2\
+             8eac13366be082dc57441760d957275419a418420000000000fffffffff0689180\
+             aa63b30cb162a73c6d2a38b7eeda2a83ece74310fda0843ad604853b0100000000\
+             feffffffaa5202bdf6d8ccd2ee0f0202afbbb7461d9264a25e5bfd3c5a52ee1239\
+             e0ba6c0000000000feffffff956149bdc66faa968eb2be2d2faa29718acbfe3941\
+             215893a2a3446d32acd050000000000000000000e664b9773b88c09c32cb70a2a3\
+             e4da0ced63b7ba3b22f848531bbb1d5d5f4c94010000000000000000e9aa6b8e6c\
+             9de67619e6a3924ae25696bb7b694bb677a632a74ef7eadfd4eabf0000000000ff\
+             ffffffa778eb6a263dc090464cd125c466b5a99667720b1c110468831d058aa1b8\
+             2af10100000000ffffffff0200ca9a3b000000001976a91406afd46bcdfd22ef94\
+             ac122aa11f241244a37ecc88ac807840cb0000000020ac9a87f5594be208f8532d\
+             b38cff670c450ed2fea8fcdefcc9a663f78bab962b0065cd1d",
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    let spent = [
+        (
+            "512053a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343",
+            420_000_000,
+        ),
+        (
+            "5120147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3",
+            462_000_000,
+        ),
+        ("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac", 294_000_000),
+        (
+            "5120e4d810fd50586274face62b8a807eb9719cef49c04177cc6b76a9a4251d5450e",
+            504_000_000,
+        ),
+        (
+            "512091b64d5324723a985170e4dc5a0f84c041804f2cd12660fa5dec09fc21783605",
+            630_000_000,
+        ),
+        ("00147dd65592d0ab2fe0d0257d571abf032cd9db93dc", 378_000_000),
+        (
+            "512075169f4001aa68f15bbed28b218df1d0a62cbbcf1188c6665110c293c907b831",
+            672_000_000,
+        ),
+        (
+            "5120712447206d7a5238acc7ff53fbe94a3b64539ad291c7cdbc490b7577e4b17df5",
+            546_000_000,
+        ),
+        (
+            "512077e30a5522dd9f894c3f8b8bd4c4b2cf82ca7da8a3ea6a239655c39c050ab220",
+            588_000_000,
+        ),
+    ]
+    .iter()
+    .map(|(script, value)| TxOut {
+        value: *value,
+        script_pubkey: Script::from(Vec::from_hex(script).unwrap()),
+    })
+    .collect::<Vec<_>>();
+    let mut psbt = PartiallySignedTransaction::from_unsigned_tx(tx).unwrap();
+    for (input, txout) in psbt.inputs.iter_mut().zip(&spent) {
+        input.witness_utxo = Some(txout.clone());
+    }
+
+    for (index, sighash_type, sighash) in &[
+        (
+            0,
+            0x03,
+            "2514a6272f85cfa0f45eb907fcb0d121b808ed37c6ea160a5a9046ed5526d555",
+        ),
+        (
+            1,
+            0x83,
+            "325a644af47e8a5a2591cda0ab0723978537318f10e6a63d4eed783b96a71a4d",
+        ),
+        (
+            3,
+            0x01,
+            "bf013ea93474aa67815b1b6cc441d23b64fa310911d991e713cd34c7f5d46669",
+        ),
+        (
+            4,
+            0x00,
+            "4f900a0bae3f1446fd48490c2958b5a023228f01661cda3496a11da502a7f7ef",
+        ),
+        (
+            6,
+            0x02,
+            "15f25c298eb5cdc7eb1d638dd2d45c97c4c59dcaec6679cfc16ad84f30876b85",
+        ),
+        (
+            7,
+            0x82,
+            "cd292de50313804dabe4685e83f923d2969577191a3e1d2882220dca88cbeb10",
+        ),
+        (
+            8,
+            0x81,
+            "cccb739eca6c13a8a89e6e5cd317ffe55669bbda23f2fd37b0f18755e008edd2",
+        ),
+    ] {
+        let computed = taproot::sighash(&psbt, *index, *sighash_type).unwrap();
+        assert_eq!(computed[..], Vec::from_hex(sighash).unwrap()[..]);
+    }
+
+    // Tweaked internal key of the first input matches the spent output key
+    let seckey = SecretKey::from_slice(
+        &Vec::from_hex(
+            "6b973d88838f27366ed61c9ad6367663045cb456e28335c109e30717ae0c6baa",
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    let keypair = taproot::tweaked_keypair(&seckey).unwrap();
+    assert_eq!(
+        taproot::output_key(&spent[0].script_pubkey),
+        Some(schnorrsig::PublicKey::from_keypair(&SECP256K1, &keypair))
+    );
+}
+
+#[test]
+fn sign_p2wpkh_psbt() {
+    let path = path("conformance");
+    let _ = fs::remove_file(&path);
+    let mut vault = open(&path);
+    let master = master(MNEMONIC, "", Network::Bitcoin);
+    vault
+        .import_xpriv(
+            master,
+            None,
+            Some(KeyApplication::SegWit),
+            "Conformance",
+            None::<String>,
+            CollisionPolicy::Reject,
+            encryption_key(),
+        )
+        .unwrap();
+
+    let derivation = DerivationPath::from_str("m/84h/0h/0h/0/0").unwrap();
+    let pubkey = pubkey(&derive(master, "m/84h/0h/0h/0/0"));
+    let spent = TxOut {
+        value: 100_000,
+        script_pubkey: Address::from_str(
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu",
+        )
+        .unwrap()
+        .script_pubkey(),
+    };
+    let mut psbt = PartiallySignedTransaction::from_unsigned_tx(Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(Txid::from_inner([1u8; 32]), 0),
+            script_sig: Script::new(),
+            sequence: 0xFFFF_FFFD,
+            witness: vec![],
+        }],
+        output: vec![TxOut {
+            value: 90_000,
+            script_pubkey: spent.script_pubkey.clone(),
+        }],
+    })
+    .unwrap();
+    psbt.inputs[0].witness_utxo = Some(spent.clone());
+    psbt.inputs[0]
+        .bip32_derivation
+        .insert(pubkey, (master.fingerprint(&SECP256K1), derivation));
+
+    let signed = vault
+        .sign_psbt(psbt, false, &mut decryption_key(), &mut || ())
+        .unwrap();
+    let partial_sig = &signed.inputs[0].partial_sigs[&pubkey];
+    let (sighash_type, der) = partial_sig.split_last().unwrap();
+    assert_eq!(*sighash_type, SigHashType::All.as_u32() as u8);
+
+    // Signature must commit to the BIP-143 sighash of the SegWit input
+    let tx = &signed.global.unsigned_tx;
+    let sig_hash = SigHashCache::new(tx).signature_hash(
+        0,
+        &Script::new_p2pkh(&pubkey.pubkey_hash()),
+        spent.value,
+        SigHashType::All,
+    );
+    SECP256K1
+        .verify(
+            &Message::from_slice(&sig_hash[..]).unwrap(),
+            &Signature::from_der(der).unwrap(),
+            &pubkey.key,
+        )
+        .unwrap();
+
+    drop(vault);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn sign_p2tr_psbt() {
+    let path = path("conformance-p2tr");
+    let _ = fs::remove_file(&path);
+    let mut vault = open(&path);
+    let master = master(MNEMONIC, "", Network::Bitcoin);
+    let id = vault
+        .import_xpriv(
+            master,
+            None,
+            None,
+            "Conformance",
+            None::<String>,
+            CollisionPolicy::Reject,
+            encryption_key(),
+        )
+        .unwrap()
+        .id;
+
+    let derivation = DerivationPath::from_str("m/86h/0h/0h/0/0").unwrap();
+    let xpriv = derive(master, "m/86h/0h/0h/0/0");
+    let output_key = schnorrsig::PublicKey::from_keypair(
+        &SECP256K1,
+        &taproot::tweaked_keypair(&xpriv.private_key.key).unwrap(),
+    );
+    let spent = TxOut {
+        value: 100_000,
+        script_pubkey: Builder::new()
+            .push_int(1)
+            .push_slice(&output_key.serialize())
+            .into_script(),
+    };
+    let mut psbt = PartiallySignedTransaction::from_unsigned_tx(Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(Txid::from_inner([2u8; 32]), 0),
+            script_sig: Script::new(),
+            sequence: 0xFFFF_FFFD,
+            witness: vec![],
+        }],
+        output: vec![TxOut {
+            value: 90_000,
+            script_pubkey: spent.script_pubkey.clone(),
+        }],
+    })
+    .unwrap();
+    psbt.inputs[0].witness_utxo = Some(spent);
+    psbt.inputs[0]
+        .bip32_derivation
+        .insert(pubkey(&xpriv), (master.fingerprint(&SECP256K1), derivation));
+
+    // Signature must commit to the BIP-341 sighash and verify against the
+    // tweaked output key
+    let key = raw::Key {
+        type_value: taproot::PSBT_IN_TAP_KEY_SIG,
+        key: vec![],
+    };
+    let verify = |signed: &PartiallySignedTransaction, sighash_type: u8| {
+        let sig = &signed.inputs[0].unknown[&key];
+        let sighash = taproot::sighash(signed, 0, sighash_type).unwrap();
+        SECP256K1
+            .schnorrsig_verify(
+                &schnorrsig::Signature::from_slice(&sig[..64]).unwrap(),
+                &Message::from_slice(&sighash[..]).unwrap(),
+                &output_key,
+            )
+            .unwrap();
+        sig.len()
+    };
+    let signed = vault
+        .sign_psbt_taproot(psbt.clone(), &mut decryption_key(), &mut || ())
+        .unwrap();
+    assert_eq!(verify(&signed, taproot::SIGHASH_DEFAULT), 64);
+
+    // Sighash types other than `SIGHASH_DEFAULT` and `SIGHASH_ALL` must be
+    // allowed by the account policy
+    psbt.inputs[0].sighash_type = Some(SigHashType::SinglePlusAnyoneCanPay);
+    assert!(matches!(
+        vault.sign_psbt_taproot(
+            psbt.clone(),
+            &mut decryption_key(),
+            &mut || ()
+        ),
+        Err(RuntimeError::PolicyViolation(_))
+    ));
+    vault
+        .set_policy(
+            id,
+            SigningPolicy {
+                sighash_types: vec![0x83],
+                ..SigningPolicy::default()
+            },
+        )
+        .unwrap();
+    let signed = vault
+        .sign_psbt_taproot(psbt, &mut decryption_key(), &mut || ())
+        .unwrap();
+    assert_eq!(verify(&signed, 0x83), 65);
+    assert_eq!(signed.inputs[0].unknown[&key][64], 0x83);
+
+    drop(vault);
+    fs::remove_file(path).unwrap();
+}
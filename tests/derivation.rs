@@ -0,0 +1,167 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "node")]
+
+mod common;
+
+use std::str::FromStr;
+
+use bitcoin::secp256k1;
+use bitcoin::util::bip32::{DerivationPath, ExtendedPrivKey, ExtendedPubKey};
+use keyring::vault::keymgm::Error;
+use keyring::vault::{DerivationCache, Keyring, KeysAccount};
+
+use common::{decryption_key, encryption_key};
+
+const MASTER: &str = "xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb";
+
+fn keyring() -> Keyring {
+    Keyring::from_xpriv(
+        "Derivation",
+        "",
+        None,
+        ExtendedPrivKey::from_str(MASTER).unwrap(),
+        None,
+        encryption_key(),
+    )
+    .unwrap()
+}
+
+/// Extended public key derived directly from the master key
+fn xpub(path: &str) -> ExtendedPubKey {
+    let xpriv = ExtendedPrivKey::from_str(MASTER)
+        .unwrap()
+        .derive_priv(
+            &keyring::SECP256K1,
+            &DerivationPath::from_str(path).unwrap(),
+        )
+        .unwrap();
+    ExtendedPubKey::from_private(&keyring::SECP256K1, &xpriv)
+}
+
+#[test]
+fn cached_derivation() {
+    let mut keyring = keyring();
+    let mut decryption_key = decryption_key();
+    let mut cache = DerivationCache::with(&mut decryption_key);
+    assert_eq!(decryption_key, secp256k1::key::ONE_KEY);
+
+    for index in 0..8 {
+        let path = format!("m/84'/0'/0'/0/{}", index);
+        let (derivation, account) = keyring
+            .derive_account_cached(
+                path.as_str(),
+                "",
+                None::<String>,
+                Default::default(),
+                &mut cache,
+            )
+            .unwrap();
+        assert_eq!(*account.xpubkey(), xpub(&path));
+        keyring.add_account(derivation, account).unwrap();
+    }
+    // Master key is decrypted once for the whole batch
+    assert_eq!(cache.decryptions(), 1);
+
+    // Deriving from the cached key is cheaper than decrypting the closer
+    // account m/84'/0'/0'/0/3
+    let (_, account) = keyring
+        .derive_account_cached(
+            "m/84'/0'/0'/0/3/1'",
+            "",
+            None::<String>,
+            Default::default(),
+            &mut cache,
+        )
+        .unwrap();
+    assert_eq!(*account.xpubkey(), xpub("m/84'/0'/0'/0/3/1'"));
+    assert_eq!(cache.decryptions(), 1);
+}
+
+#[test]
+fn watch_only_parent() {
+    let mut keyring = keyring();
+    keyring
+        .add_account(
+            DerivationPath::from_str("m/84'/0'").unwrap(),
+            KeysAccount::watch_only("Watch-only", "", xpub("m/84'/0'"), None),
+        )
+        .unwrap();
+    let (_, account) = keyring
+        .derive_account(
+            "m/84'/0'/1'",
+            "",
+            None::<String>,
+            Default::default(),
+            &mut decryption_key(),
+        )
+        .unwrap();
+    assert_eq!(*account.xpubkey(), xpub("m/84'/0'/1'"));
+}
+
+#[test]
+fn neutered_derivation() {
+    let mut keyring = keyring();
+    keyring
+        .create_account(
+            "m/84'/0'/0'",
+            "Account",
+            None::<String>,
+            Default::default(),
+            &mut decryption_key(),
+        )
+        .unwrap();
+
+    // Normal steps are derived from the closest account extended public key
+    let account = keyring
+        .create_account_neutered(
+            "m/84'/0'/0'/0/5",
+            "Receiving",
+            None::<String>,
+            Default::default(),
+        )
+        .unwrap();
+    assert!(account.is_watch_only());
+    assert_eq!(*account.xpubkey(), xpub("m/84'/0'/0'/0/5"));
+
+    // Watch-only accounts may serve as parents as well
+    let account = keyring
+        .create_account_neutered(
+            "m/84'/0'/0'/0/5/1",
+            "Nested",
+            None::<String>,
+            Default::default(),
+        )
+        .unwrap();
+    assert_eq!(*account.xpubkey(), xpub("m/84'/0'/0'/0/5/1"));
+
+    match keyring.create_account_neutered(
+        "m/84'/0'/1'",
+        "Hardened",
+        None::<String>,
+        Default::default(),
+    ) {
+        Err(Error::HardenedDerivation) => {}
+        other => panic!("hardened path derived from xpub: {:?}", other),
+    }
+    match keyring.create_account_neutered(
+        "m/84'/0'/0'/0/5",
+        "Duplicate",
+        None::<String>,
+        Default::default(),
+    ) {
+        Err(Error::DerivationAlreadyUsed) => {}
+        other => panic!("derivation path reused: {:?}", other),
+    }
+}
@@ -0,0 +1,152 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "server")]
+
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use bitcoin::secp256k1;
+use keyring::lifecycle::Lifecycle;
+use keyring::rpc::types::AccountInfo;
+use keyring::rpc::{self, FailureCode, Reply};
+use keyring::vault::{driver, keymgm, Keyring};
+use keyring::{psbt, RuntimeError};
+use lnpbp::Chain;
+use microservices::rpc::Failure;
+use slip132::KeyApplication;
+
+fn account_info() -> AccountInfo {
+    let keyring = Keyring::with(
+        "Sample",
+        "Display test keyring",
+        &Chain::Testnet3,
+        KeyApplication::SegWit,
+        None,
+        secp256k1::PublicKey::from_str(
+            "03933615cab8f016c8375602884804b56061bcdd8fe362eb7e12c87d61c5275c5f",
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    AccountInfo::from(&keyring)
+}
+
+#[test]
+fn lifecycle_roundtrip() {
+    for state in &[
+        Lifecycle::Pending,
+        Lifecycle::Active,
+        Lifecycle::Retiring,
+        Lifecycle::Revoked,
+    ] {
+        assert_eq!(Lifecycle::from_str(&state.to_string()), Ok(*state));
+        let yaml = serde_yaml::to_string(state).unwrap();
+        assert_eq!(serde_yaml::from_str::<Lifecycle>(&yaml).unwrap(), *state);
+    }
+    assert!(Lifecycle::from_str("unknown").is_err());
+}
+
+#[test]
+fn account_info_display() {
+    let info = account_info();
+    let display = info.to_string();
+    assert!(display.starts_with("Sample ["));
+    assert!(display.contains(&info.id.to_string()));
+    assert!(display.ends_with(", active"));
+    assert!(!display.contains("AccountInfo"));
+}
+
+#[test]
+fn account_info_serde_roundtrip() {
+    let info = account_info();
+    let json = serde_json::to_string(&info).unwrap();
+    assert_eq!(serde_json::from_str::<AccountInfo>(&json).unwrap(), info);
+    let yaml = serde_yaml::to_string(&info).unwrap();
+    assert_eq!(serde_yaml::from_str::<AccountInfo>(&yaml).unwrap(), info);
+}
+
+#[test]
+fn errors_display() {
+    let failure = Failure {
+        code: 1,
+        info: "some failure".to_string(),
+    };
+    let err = rpc::Error::ServerFailure(failure);
+    assert!(err.to_string().contains("some failure"));
+    assert!(!err.to_string().contains("ServerFailure"));
+
+    assert_eq!(
+        RuntimeError::VaultLocked.to_string(),
+        "Vault is locked; please unlock it first"
+    );
+    match Reply::from(RuntimeError::VaultLocked) {
+        Reply::Failure(failure) => {
+            assert_eq!(failure.info, "Vault is locked; please unlock it first")
+        }
+        _ => panic!("runtime error must be converted into failure"),
+    }
+    match Reply::from(RuntimeError::UnsupportedRequest(0x7FFE, 1, 2)) {
+        Reply::Failure(failure) => {
+            assert_eq!(failure.code, rpc::UNSUPPORTED_REQUEST_FAILURE_CODE);
+            assert!(failure.info.contains("32766"));
+            assert!(failure.info.contains("versions 1 to 2"));
+        }
+        _ => panic!("runtime error must be converted into failure"),
+    }
+}
+
+#[test]
+fn failure_codes() {
+    let cases = vec![
+        (RuntimeError::VaultLocked, FailureCode::VaultLocked),
+        (keymgm::Error::NotFound.into(), FailureCode::NotFound),
+        (
+            keymgm::Error::SecretKeyCorrupted.into(),
+            FailureCode::DecryptionKey,
+        ),
+        (keymgm::Error::WatchOnly.into(), FailureCode::WatchOnly),
+        (
+            driver::Error::Tampered("signature".to_string()).into(),
+            FailureCode::Tampered,
+        ),
+        (RuntimeError::Unauthorized, FailureCode::Unauthorized),
+        (RuntimeError::BackupsDisabled, FailureCode::Disabled),
+        (
+            RuntimeError::ImmutableSettings("vault".to_string()),
+            FailureCode::Configuration,
+        ),
+        (
+            psbt::Error::InputMismatch(1, "witness UTXO", 0).into(),
+            FailureCode::PsbtMismatch,
+        ),
+        (
+            RuntimeError::PassphraseConfirmation,
+            FailureCode::PassphraseConfirmation,
+        ),
+    ];
+    for (err, code) in cases {
+        match Reply::from(err) {
+            Reply::Failure(failure) => {
+                assert_eq!(failure.code, u16::from(code));
+                assert_eq!(FailureCode::try_from(failure.code), Ok(code));
+            }
+            _ => panic!("runtime error must be converted into failure"),
+        }
+    }
+
+    // Codes defined before the code enumeration keep their values
+    assert_eq!(rpc::auth::AUTH_FAILURE_CODE, 0x0401);
+    assert_eq!(rpc::WATCH_ONLY_FAILURE_CODE, 0x0403);
+    assert_eq!(FailureCode::try_from(0x7FFF), Err(0x7FFF));
+}
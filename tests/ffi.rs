@@ -0,0 +1,116 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "ffi")]
+
+mod common;
+
+use std::ffi::{CStr, CString};
+use std::{fs, ptr};
+
+use keyring::ffi::*;
+
+use common::decryption_key;
+
+fn config(data_dir: &CString) -> KeyringConfig {
+    let mut node_key = [0u8; 32];
+    node_key.copy_from_slice(&decryption_key()[..]);
+    KeyringConfig {
+        data_dir: data_dir.as_ptr(),
+        node_key,
+        passphrase: false,
+        unlock_timeout: 0,
+        read_only: false,
+        load_cb: None,
+        save_cb: None,
+    }
+}
+
+fn last_error() -> String {
+    let message = keyring_last_error();
+    assert!(!message.is_null());
+    unsafe { CStr::from_ptr(message) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[test]
+fn open_list_sign() {
+    let dir = std::env::temp_dir()
+        .join(format!("keyring-{}-ffi", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let data_dir = CString::new(dir.display().to_string()).unwrap();
+
+    let mut handle = ptr::null_mut();
+    unsafe {
+        assert_eq!(
+            keyring_open(&config(&data_dir), &mut handle),
+            KeyringError::Ok
+        );
+        assert!(!handle.is_null());
+
+        let mut list = KeyringAccountList {
+            accounts: ptr::null_mut(),
+            len: 0,
+        };
+        assert_eq!(keyring_list(handle, &mut list), KeyringError::Ok);
+        assert_eq!(list.len, 0);
+        keyring_free_accounts(&mut list);
+        assert!(list.accounts.is_null());
+
+        let psbt = CString::new("not a psbt").unwrap();
+        let mut signed = ptr::null_mut();
+        assert_eq!(
+            keyring_sign_psbt(handle, psbt.as_ptr(), ptr::null(), &mut signed),
+            KeyringError::InvalidArgument
+        );
+        assert!(signed.is_null());
+        assert!(last_error().contains("psbt"));
+
+        keyring_close(handle);
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn null_arguments() {
+    let mut handle = ptr::null_mut();
+    unsafe {
+        assert_eq!(
+            keyring_open(ptr::null(), &mut handle),
+            KeyringError::InvalidArgument
+        );
+        assert!(handle.is_null());
+        assert!(last_error().contains("config"));
+
+        let mut config = config(&CString::new("").unwrap());
+        config.data_dir = ptr::null();
+        assert_eq!(
+            keyring_open(&config, &mut handle),
+            KeyringError::InvalidArgument
+        );
+        assert!(last_error().contains("data_dir"));
+
+        let mut list = KeyringAccountList {
+            accounts: ptr::null_mut(),
+            len: 0,
+        };
+        assert_eq!(
+            keyring_list(ptr::null(), &mut list),
+            KeyringError::InvalidArgument
+        );
+        keyring_free_accounts(ptr::null_mut());
+        keyring_free_string(ptr::null_mut());
+        keyring_close(ptr::null_mut());
+    }
+}
@@ -0,0 +1,264 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "node")]
+
+use std::fs;
+use std::path::PathBuf;
+
+use bitcoin::secp256k1;
+use keyring::vault::driver::Error;
+use keyring::vault::file_driver::NodeKey;
+use keyring::vault::{
+    example, file_driver, Driver, FileDriver, Keyring, KeysAccount,
+};
+use lnpbp::strict_encoding::StrictEncode;
+use lnpbp::Chain;
+use microservices::FileFormat;
+use slip132::KeyApplication;
+
+fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "keyring-{}-{}",
+        std::process::id(),
+        name
+    ));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn keyrings() -> Vec<Keyring> {
+    let encryption_key = secp256k1::PublicKey::from_secret_key(
+        &keyring::SECP256K1,
+        &secp256k1::key::ONE_KEY,
+    );
+    vec![Keyring::with(
+        "Master",
+        "Signed vault keyring",
+        &Chain::Testnet3,
+        KeyApplication::SegWit,
+        None,
+        encryption_key,
+    )
+    .unwrap()]
+}
+
+/// Encodes account in the layout used by the vaults written before the
+/// account extension records were introduced
+fn legacy_account(account: &KeysAccount, data: &mut Vec<u8>) {
+    account.xpubkey().strict_encode(&mut *data).unwrap();
+    account.name().strict_encode(&mut *data).unwrap();
+    account.details().strict_encode(&mut *data).unwrap();
+    account.assets().strict_encode(&mut *data).unwrap();
+    account.encrypted().strict_encode(&mut *data).unwrap();
+    account.unblinding().strict_encode(&mut *data).unwrap();
+}
+
+fn legacy_data(keyrings: &[Keyring]) -> Vec<u8> {
+    let mut data = vec![];
+    (keyrings.len() as u16).strict_encode(&mut data).unwrap();
+    for keyring in keyrings {
+        legacy_account(keyring.master_account(), &mut data);
+        keyring.key_source().strict_encode(&mut data).unwrap();
+        (keyring.sub_accounts().len() as u16)
+            .strict_encode(&mut data)
+            .unwrap();
+        for (path, account) in keyring.sub_accounts() {
+            path.strict_encode(&mut data).unwrap();
+            legacy_account(account, &mut data);
+        }
+    }
+    data
+}
+
+fn config(path: &PathBuf, signed: bool) -> file_driver::Config {
+    file_driver::Config {
+        location: path.display().to_string(),
+        format: FileFormat::StrictEncode,
+        backups: 0,
+        signed,
+        node_key: Some(NodeKey(secp256k1::key::ONE_KEY)),
+        read_only: false,
+    }
+}
+
+#[test]
+fn corrupted_vault() {
+    let path = temp_path("corrupted.vault");
+    let keyrings = keyrings();
+    let mut driver = FileDriver::init(&config(&path, false)).unwrap();
+    driver.store(&keyrings).unwrap();
+    assert_eq!(driver.load().unwrap(), keyrings);
+
+    let mut data = fs::read(&path).unwrap();
+    let last = data.len() - 1;
+    data[last] ^= 0xFF;
+    fs::write(&path, data).unwrap();
+    assert!(matches!(driver.load(), Err(Error::Corrupted(_))));
+
+    drop(driver);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn tampered_vault() {
+    let path = temp_path("tampered.vault");
+    let keyrings = keyrings();
+    let mut driver = FileDriver::init(&config(&path, true)).unwrap();
+    driver.store(&keyrings).unwrap();
+    assert_eq!(driver.load().unwrap(), keyrings);
+    drop(driver);
+
+    let mut other = config(&path, true);
+    other.node_key = Some(NodeKey(
+        secp256k1::SecretKey::from_slice(&[2u8; 32]).unwrap(),
+    ));
+    let mut driver = FileDriver::init(&other).unwrap();
+    assert!(matches!(driver.load(), Err(Error::Tampered(_))));
+
+    drop(driver);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn legacy_vault() {
+    let path = temp_path("legacy.vault");
+    let keyrings = keyrings();
+    fs::write(&path, legacy_data(&keyrings)).unwrap();
+
+    let mut driver = FileDriver::init(&config(&path, false)).unwrap();
+    let loaded = driver.load().unwrap();
+    assert_eq!(loaded.len(), keyrings.len());
+    let (legacy, keyring) = (&loaded[0], &keyrings[0]);
+    assert_eq!(legacy.identifier(), keyring.identifier());
+    assert_eq!(legacy.key_source(), keyring.key_source());
+    let account = legacy.master_account();
+    assert_eq!(account.encrypted(), keyring.master_account().encrypted());
+    assert_eq!(account.name(), "Master");
+    assert_eq!(*account.application(), None);
+    assert!(!account.archived());
+    assert!(account.labels().is_empty());
+    let mut decryption_key = secp256k1::key::ONE_KEY;
+    account.verify_decryption_key(&mut decryption_key).unwrap();
+
+    // The vault is written in the current layout on the next update
+    driver.store(&loaded).unwrap();
+    assert_eq!(driver.load().unwrap(), loaded);
+    assert_eq!(fs::read(&path).unwrap()[4], file_driver::FILE_VERSION);
+
+    drop(driver);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn legacy_vault_chain() {
+    let path = temp_path("legacy-chain.vault");
+    let encryption_key = secp256k1::PublicKey::from_secret_key(
+        &keyring::SECP256K1,
+        &secp256k1::key::ONE_KEY,
+    );
+    // Signet keys are serialized with the testnet version bytes, so the
+    // chain can't be detected from the master key
+    let keyring = Keyring::with(
+        "Signet",
+        "Legacy signet keyring",
+        &Chain::Signet,
+        KeyApplication::SegWit,
+        None,
+        encryption_key,
+    )
+    .unwrap();
+    assert_eq!(keyring.chain(), Chain::Signet);
+    fs::write(&path, legacy_data(&[keyring])).unwrap();
+
+    let mut driver = FileDriver::init(&config(&path, false)).unwrap();
+    let loaded = driver.load().unwrap();
+    assert_eq!(loaded[0].chain(), Chain::Testnet3);
+
+    // Absent chain is kept when the vault is written in the current layout
+    driver.store(&loaded).unwrap();
+    let stored = driver.load().unwrap();
+    assert_eq!(stored, loaded);
+    assert_eq!(stored[0].chain(), Chain::Testnet3);
+
+    drop(driver);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn unsupported_vault_version() {
+    let path = temp_path("unsupported.vault");
+    let keyrings = keyrings();
+    FileDriver::write_snapshot(&path, &keyrings, &FileFormat::StrictEncode)
+        .unwrap();
+    let mut data = fs::read(&path).unwrap();
+    data[4] = file_driver::FILE_VERSION + 1;
+    fs::write(&path, data).unwrap();
+    assert!(matches!(
+        FileDriver::read_snapshot(&path),
+        Err(Error::Corrupted(_))
+    ));
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn malformed_yaml_keyring() {
+    let path = temp_path("malformed.yaml");
+    let valid = serde_yaml::to_value(&keyrings()[0]).unwrap();
+    let mut malformed = valid.clone();
+    malformed["key_source"] = serde_yaml::Value::from("invalid");
+    fs::write(
+        &path,
+        serde_yaml::to_string(&vec![valid, malformed]).unwrap(),
+    )
+    .unwrap();
+    let mut config = config(&path, false);
+    config.format = FileFormat::Yaml;
+    let mut driver = FileDriver::init(&config).unwrap();
+    match driver.load() {
+        Err(Error::Corrupted(details)) => {
+            assert!(
+                details.starts_with("keyring #2 is malformed at `key_source`")
+            )
+        }
+        result => panic!("unexpected result {:?}", result),
+    }
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn example_vault() {
+    let keyrings = example::keyrings().unwrap();
+    let formats =
+        [FileFormat::StrictEncode, FileFormat::Yaml, FileFormat::Json];
+    for (no, format) in formats.iter().enumerate() {
+        let path = temp_path(&format!("example-{}.vault", no));
+        FileDriver::write_snapshot(&path, &keyrings, format).unwrap();
+        assert_eq!(FileDriver::read_snapshot(&path).unwrap(), keyrings);
+
+        let mut driver = FileDriver::init(&file_driver::Config {
+            format: format.clone(),
+            read_only: true,
+            ..config(&path, false)
+        })
+        .unwrap();
+        assert_eq!(driver.load().unwrap(), keyrings);
+    }
+
+    let master = &keyrings[0];
+    master
+        .account_by_id(master.identifier())
+        .unwrap()
+        .verify_decryption_key(&mut example::decryption_key())
+        .unwrap();
+}
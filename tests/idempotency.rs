@@ -0,0 +1,50 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "server")]
+
+use bitcoin::hashes::{sha256, Hash};
+use keyring::daemon::Idempotency;
+use keyring::rpc::Reply;
+use keyring::RuntimeError;
+
+#[test]
+fn replayed_replies() {
+    let mut idempotency = Idempotency::new();
+    let key = sha256::Hash::hash(b"seed");
+    let client = Some("wallet".to_string());
+
+    assert!(idempotency
+        .begin(client.clone(), key, "seed")
+        .unwrap()
+        .is_none());
+    // Retry while the request is still served
+    assert!(matches!(
+        idempotency.begin(client.clone(), key, "seed"),
+        Err(RuntimeError::IdempotencyConflict)
+    ));
+    idempotency.complete(client.clone(), key, Reply::Success);
+    assert!(matches!(
+        idempotency.begin(client.clone(), key, "seed"),
+        Ok(Some(Reply::Success))
+    ));
+    assert!(matches!(
+        idempotency.begin(client, key, "derive"),
+        Err(RuntimeError::IdempotencyConflict)
+    ));
+
+    // Keys are scoped by the client
+    assert!(idempotency.begin(None, key, "seed").unwrap().is_none());
+    idempotency.abandon(None, key);
+    assert!(idempotency.begin(None, key, "seed").unwrap().is_none());
+}
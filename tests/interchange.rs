@@ -0,0 +1,131 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "node")]
+
+mod common;
+
+use bitcoin::secp256k1;
+use keyring::passphrase::Kdf;
+use keyring::rpc::types::CollisionPolicy;
+use keyring::vault::interchange::{self, Error, DUMP_VERSION, MAGIC};
+use keyring::vault::Vault;
+use keyring::RuntimeError;
+
+use common::{decryption_key, empty_vault, encryption_key, xpriv};
+
+const PASSPHRASE: &str = "correct horse battery staple";
+
+// Weak parameters keeping the tests fast
+const KDF: Kdf = Kdf::Scrypt {
+    log_n: 4,
+    r: 8,
+    p: 1,
+};
+
+fn vault(name: &str, seeds: &[u8]) -> Vault {
+    let mut vault = empty_vault(name);
+    for seed in seeds {
+        vault
+            .import_xpriv(
+                xpriv(*seed),
+                None,
+                None,
+                format!("Keyring {}", seed),
+                None::<String>,
+                CollisionPolicy::Reject,
+                encryption_key(),
+            )
+            .unwrap();
+    }
+    vault
+}
+
+#[test]
+fn plain_dump() {
+    let source = vault("dump-plain-source", &[1, 2]);
+    let dump = source.export_dump(None).unwrap();
+    assert_eq!(dump[..4], MAGIC);
+    assert_eq!(dump[4], DUMP_VERSION);
+    assert_eq!(dump, source.export_dump(None).unwrap());
+    assert_eq!(interchange::decode(&dump, None).unwrap().len(), 2);
+
+    let mut target = vault("dump-plain-target", &[2, 3]);
+    let accounts = target
+        .import_dump(&dump, None, false, &mut decryption_key())
+        .unwrap();
+    assert_eq!(accounts.len(), 3);
+
+    let accounts = target
+        .import_dump(&dump, None, true, &mut decryption_key())
+        .unwrap();
+    assert_eq!(accounts, source.list().unwrap());
+}
+
+#[test]
+fn encrypted_dump() {
+    let source = vault("dump-encrypted-source", &[4]);
+    let dump = source.export_dump(Some((PASSPHRASE, KDF))).unwrap();
+    assert_ne!(dump, source.export_dump(Some((PASSPHRASE, KDF))).unwrap());
+
+    assert_eq!(
+        interchange::decode(&dump, None).unwrap_err(),
+        Error::PassphraseRequired
+    );
+    assert!(interchange::decode(&dump, Some("wrong passphrase")).is_err());
+
+    let mut target = vault("dump-encrypted-target", &[]);
+    let accounts = target
+        .import_dump(&dump, Some(PASSPHRASE), false, &mut decryption_key())
+        .unwrap();
+    assert_eq!(accounts, source.list().unwrap());
+}
+
+#[test]
+fn foreign_dump() {
+    let source = vault("dump-foreign-source", &[5]);
+    let dump = source.export_dump(None).unwrap();
+    let mut target = vault("dump-foreign-target", &[]);
+    let mut other_key =
+        secp256k1::SecretKey::from_slice(&[0x5Au8; 32]).unwrap();
+    assert!(matches!(
+        target.import_dump(&dump, None, false, &mut other_key),
+        Err(RuntimeError::KeyManagement(_))
+    ));
+    assert!(target.list().unwrap().is_empty());
+}
+
+#[test]
+fn malformed_dump() {
+    let mut dump = vault("dump-malformed", &[6]).export_dump(None).unwrap();
+    assert_eq!(
+        interchange::decode(&dump[..5], None).unwrap_err(),
+        Error::Truncated
+    );
+
+    dump[4] = DUMP_VERSION + 1;
+    assert_eq!(
+        interchange::decode(&dump, None).unwrap_err(),
+        Error::Version(DUMP_VERSION + 1)
+    );
+
+    dump[4] = DUMP_VERSION;
+    dump[5] = 0x7F;
+    assert_eq!(
+        interchange::decode(&dump, None).unwrap_err(),
+        Error::Encryption(0x7F)
+    );
+
+    dump[0] = b'X';
+    assert_eq!(interchange::decode(&dump, None).unwrap_err(), Error::Magic);
+}
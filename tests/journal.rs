@@ -0,0 +1,93 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "node")]
+
+mod common;
+
+use std::fs;
+
+use keyring::rpc::types::CollisionPolicy;
+use keyring::vault::journal::{self, Error};
+use keyring::vault::{FileDriver, Journal};
+
+use common::{decryption_key, encryption_key, open, path, vault, xpriv};
+
+#[test]
+fn journal_verification() {
+    let journal_path = path("journal").with_extension("jsonl");
+    let _ = fs::remove_file(&journal_path);
+
+    // Keyring imported before the journal is enabled is recorded right away
+    let (mut vault, purged) = vault("journal", 1, "Purged");
+    vault
+        .enable_journal(Journal::open(&journal_path).unwrap())
+        .unwrap();
+    vault.set_label(purged, "env", Some("prod")).unwrap();
+    vault
+        .delete_keyring(purged, true, &mut decryption_key())
+        .unwrap();
+    let kept = vault
+        .import_xpriv(
+            xpriv(2),
+            None,
+            None,
+            "Kept",
+            None::<String>,
+            CollisionPolicy::Reject,
+            encryption_key(),
+        )
+        .unwrap()
+        .id;
+    drop(vault);
+
+    let keyrings = FileDriver::read_snapshot(path("journal")).unwrap();
+    assert_eq!(journal::verify(&journal_path, &keyrings), Ok(4));
+
+    // Any change of a record breaks the chain at the next record
+    let original = fs::read_to_string(&journal_path).unwrap();
+    let tampered = original.replacen("\"seq\":1,", "\"seq\": 1,", 1);
+    assert_ne!(tampered, original);
+    fs::write(&journal_path, tampered).unwrap();
+    assert_eq!(
+        journal::verify(&journal_path, &keyrings),
+        Err(Error::Chain(2))
+    );
+
+    let truncated = original.lines().skip(1).collect::<Vec<_>>().join("\n");
+    fs::write(&journal_path, truncated).unwrap();
+    assert_eq!(
+        journal::verify(&journal_path, &keyrings),
+        Err(Error::Sequence(0))
+    );
+
+    // Vault changed outside of the daemon diverges from the last record
+    // changing the account
+    fs::write(&journal_path, &original).unwrap();
+    let mut vault = open(&path("journal"));
+    vault.set_label(kept, "env", Some("test")).unwrap();
+    drop(vault);
+    let keyrings = FileDriver::read_snapshot(path("journal")).unwrap();
+    assert_eq!(
+        journal::verify(&journal_path, &keyrings),
+        Err(Error::Divergence(3, kept))
+    );
+
+    // Re-opened journal records the change
+    let mut vault = open(&path("journal"));
+    vault
+        .enable_journal(Journal::open(&journal_path).unwrap())
+        .unwrap();
+    drop(vault);
+    assert_eq!(journal::verify(&journal_path, &keyrings), Ok(5));
+}
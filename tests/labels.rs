@@ -0,0 +1,122 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "node")]
+
+mod common;
+
+use std::fs;
+use std::str::FromStr;
+
+use keyring::rpc::types::{AccountQuery, CollisionPolicy, LabelQuery};
+use keyring::vault::keymgm::Error;
+use keyring::RuntimeError;
+use slip132::KeyApplication;
+
+use common::{encryption_key, open, path, xpriv};
+
+fn query(labels: &[&str]) -> AccountQuery {
+    AccountQuery {
+        labels: labels
+            .iter()
+            .map(|label| LabelQuery::from_str(label).unwrap())
+            .collect(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn label_query_roundtrip() {
+    for label in &["cold", "env=prod", "env=", "note=a=b"] {
+        assert_eq!(
+            LabelQuery::from_str(label).unwrap().to_string(),
+            label.to_string()
+        );
+    }
+    assert_eq!(
+        LabelQuery::from_str("note=a=b").unwrap().value.as_deref(),
+        Some("a=b")
+    );
+    assert!(LabelQuery::from_str("=prod").is_err());
+    assert!(LabelQuery::from_str("").is_err());
+}
+
+#[test]
+fn persist_and_find() {
+    let path = path("labels");
+    let _ = fs::remove_file(&path);
+    let mut vault = open(&path);
+    let info = vault
+        .import_xpriv(
+            xpriv(7),
+            None,
+            Some(KeyApplication::SegWit),
+            "Labelled",
+            None::<String>,
+            CollisionPolicy::Reject,
+            encryption_key(),
+        )
+        .unwrap();
+    vault.set_label(info.id, "env", Some("prod")).unwrap();
+    let info = vault.set_label(info.id, "cold", Some("")).unwrap();
+    assert!(info.to_string().ends_with(", labels cold env=prod"));
+    match vault.set_label(info.id, "bad key", Some("")) {
+        Err(RuntimeError::KeyManagement(Error::LabelKey(key))) => {
+            assert_eq!(key, "bad key")
+        }
+        other => panic!("invalid label key is accepted: {:?}", other),
+    }
+
+    let vault = open(&path);
+    let accounts = vault.list().unwrap();
+    assert_eq!(accounts, vec![info.clone()]);
+    assert!(query(&[]).matches(&info));
+    assert!(query(&["cold", "env=prod"]).matches(&info));
+    assert!(!query(&["env=test"]).matches(&info));
+    assert!(!query(&["hot"]).matches(&info));
+    assert!(AccountQuery {
+        application: Some(KeyApplication::SegWit),
+        fingerprint: Some(info.fingerprint),
+        ..query(&["env"])
+    }
+    .matches(&info));
+    assert!(!AccountQuery {
+        application: Some(KeyApplication::Nested),
+        ..Default::default()
+    }
+    .matches(&info));
+}
+
+#[test]
+fn remove_label() {
+    let path = path("labels-remove");
+    let _ = fs::remove_file(&path);
+    let mut vault = open(&path);
+    let id = vault
+        .import_xpriv(
+            xpriv(8),
+            None,
+            None,
+            "Tagged",
+            None::<String>,
+            CollisionPolicy::Reject,
+            encryption_key(),
+        )
+        .unwrap()
+        .id;
+    vault.set_label(id, "cold", Some("")).unwrap();
+    let info = vault.set_label(id, "cold", None::<String>).unwrap();
+    assert!(info.labels.is_empty());
+    // Removing absent label is not an error
+    vault.set_label(id, "cold", None::<String>).unwrap();
+}
@@ -0,0 +1,270 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "node")]
+
+mod common;
+
+use std::str::FromStr;
+
+use bitcoin::hashes::{sha256, sha256d, Hash};
+use bitcoin::secp256k1::recovery::{RecoverableSignature, RecoveryId};
+use bitcoin::secp256k1::{self, Message, Signature};
+use bitcoin::util::bip32::{DerivationPath, ExtendedPubKey};
+use bitcoin::XpubIdentifier;
+use keyring::rpc::types::{Basepoint, SigningPolicy};
+use keyring::vault::keymgm::Error;
+use keyring::vault::policy::Rule;
+use keyring::vault::{ln, Vault};
+use keyring::{RuntimeError, SECP256K1};
+
+use common::{decryption_key, xpriv};
+
+const SEED: u8 = 0x1A;
+
+fn vault(name: &str) -> (Vault, XpubIdentifier) {
+    common::vault(name, SEED, "Lightning node")
+}
+
+fn xpub() -> ExtendedPubKey {
+    ExtendedPubKey::from_private(&SECP256K1, &xpriv(SEED))
+}
+
+fn pubkey(path: &str) -> secp256k1::PublicKey {
+    let path = DerivationPath::from_str(path).unwrap();
+    xpub().derive_pub(&SECP256K1, &path).unwrap().public_key.key
+}
+
+#[test]
+fn basepoint_path() {
+    assert_eq!(
+        Basepoint::Funding.path(7).unwrap(),
+        DerivationPath::from_str("m/0/7").unwrap()
+    );
+    assert_eq!(
+        Basepoint::Htlc.path(0).unwrap(),
+        DerivationPath::from_str("m/4/0").unwrap()
+    );
+    assert!(Basepoint::Payment.path(1 << 31).is_err());
+}
+
+#[test]
+fn key_set() {
+    let first = ln::key_set(&xpub(), 0).unwrap();
+    assert_eq!(first.key_id, xpub().identifier());
+    assert_eq!(first.node_id, pubkey("m/5/0"));
+    assert_eq!(first.funding_pubkey, pubkey("m/0/0"));
+    assert_eq!(first.revocation_basepoint, pubkey("m/1/0"));
+    assert_eq!(first.payment_basepoint, pubkey("m/2/0"));
+    assert_eq!(first.delayed_payment_basepoint, pubkey("m/3/0"));
+    assert_eq!(first.htlc_basepoint, pubkey("m/4/0"));
+
+    // Node identity is shared by all channels, while basepoints are not
+    let second = ln::key_set(&xpub(), 1).unwrap();
+    assert_eq!(second.node_id, first.node_id);
+    assert_eq!(second.funding_pubkey, pubkey("m/0/1"));
+    assert_ne!(second.htlc_basepoint, first.htlc_basepoint);
+    assert!(ln::key_set(&xpub(), 1 << 31).is_err());
+}
+
+#[test]
+fn vault_key_set() {
+    let (vault, id) = vault("ln");
+    assert_eq!(
+        vault.ln_key_set(id, 3).unwrap(),
+        ln::key_set(&xpub(), 3).unwrap()
+    );
+    assert!(vault
+        .ln_key_set(XpubIdentifier::hash(b"unknown"), 3)
+        .is_err());
+}
+
+#[test]
+fn vault_ecdh() {
+    let (mut vault, id) = vault("ecdh");
+    let remote = secp256k1::SecretKey::from_slice(&[0x5Au8; 32]).unwrap();
+    let remote_pubkey =
+        secp256k1::PublicKey::from_secret_key(&SECP256K1, &remote);
+
+    // ECDH is denied unless allowed by the account policy
+    match vault.ecdh(id, remote_pubkey, &mut decryption_key()) {
+        Err(RuntimeError::PolicyViolation(violation)) => {
+            assert_eq!(violation.rules, vec![Rule::Ecdh])
+        }
+        other => panic!("ECDH is not denied: {:?}", other),
+    }
+    vault
+        .set_policy(
+            id,
+            SigningPolicy {
+                allow_ecdh: true,
+                ..SigningPolicy::default()
+            },
+        )
+        .unwrap();
+    let secret = vault
+        .ecdh(id, remote_pubkey, &mut decryption_key())
+        .unwrap();
+
+    // Remote node gets the same secret from the node identity key
+    let mut point = pubkey("m/5/0");
+    point.mul_assign(&SECP256K1, &remote[..]).unwrap();
+    assert_eq!(secret, sha256::Hash::hash(&point.serialize()));
+}
+
+#[test]
+fn invoice_digest() {
+    // Forty bits are packed into five bytes without padding
+    let mut preimage = b"lnbc".to_vec();
+    preimage.extend(&[0xFFu8; 5]);
+    assert_eq!(
+        ln::invoice_digest("lnbc", &[31; 8]).unwrap(),
+        sha256::Hash::hash(&preimage)
+    );
+    // The last byte is padded with zero bits
+    assert_eq!(
+        ln::invoice_digest("lntb", &[1]).unwrap(),
+        sha256::Hash::hash(b"lntb\x08")
+    );
+    assert_eq!(
+        ln::invoice_digest("bc", &[]),
+        Err(Error::InvoiceHrp("bc".to_string()))
+    );
+    assert_eq!(
+        ln::invoice_digest("LNBC", &[]),
+        Err(Error::InvoiceHrp("LNBC".to_string()))
+    );
+    assert_eq!(ln::invoice_digest("lnbc", &[32]), Err(Error::InvoiceData));
+}
+
+#[test]
+fn vault_sign_invoice() {
+    let (vault, id) = vault("invoice");
+    let data = [0u8, 1, 2, 3, 30, 31, 7];
+    let signature = vault
+        .sign_invoice(id, "lnbc", &data, &mut decryption_key())
+        .unwrap();
+    assert_eq!(signature.len(), 65);
+
+    // Payer recovers the node id from the invoice signature
+    let signature = RecoverableSignature::from_compact(
+        &signature[..64],
+        RecoveryId::from_i32(signature[64] as i32).unwrap(),
+    )
+    .unwrap();
+    let digest = ln::invoice_digest("lnbc", &data).unwrap();
+    assert_eq!(
+        SECP256K1
+            .recover(&Message::from_slice(&digest[..]).unwrap(), &signature)
+            .unwrap(),
+        pubkey("m/5/0")
+    );
+    assert!(vault
+        .sign_invoice(id, "tb", &data, &mut decryption_key())
+        .is_err());
+}
+
+/// Composes `channel_announcement` with blank signatures
+fn announcement(keys: [secp256k1::PublicKey; 4]) -> Vec<u8> {
+    let mut message = ln::CHANNEL_ANNOUNCEMENT.to_be_bytes().to_vec();
+    message.extend(&[0u8; 4 * 64]);
+    // Empty features, chain hash and short channel id
+    message.extend(&[0u8; 2 + 32 + 8]);
+    for key in &keys {
+        message.extend(&key.serialize()[..]);
+    }
+    message
+}
+
+fn verify(message: &[u8], offset: usize, signature: &Signature, key: &str) {
+    let digest = sha256d::Hash::hash(&message[offset..]);
+    SECP256K1
+        .verify(
+            &Message::from_slice(&digest[..]).unwrap(),
+            signature,
+            &pubkey(key),
+        )
+        .unwrap();
+}
+
+#[test]
+fn vault_sign_announcement() {
+    let (vault, id) = vault("announcement");
+    let remote = pubkey("m/100");
+    let message =
+        announcement([remote, pubkey("m/5/0"), remote, pubkey("m/0/2")]);
+    let signatures = vault
+        .sign_channel_announcement(id, 2, &message, &mut decryption_key())
+        .unwrap();
+    verify(&message, 2 + 256, &signatures.node_signature, "m/5/0");
+    verify(&message, 2 + 256, &signatures.bitcoin_signature, "m/0/2");
+
+    // Funding key must belong to the same side as the node id
+    let message =
+        announcement([pubkey("m/5/0"), remote, remote, pubkey("m/0/2")]);
+    assert!(vault
+        .sign_channel_announcement(id, 2, &message, &mut decryption_key())
+        .is_err());
+    // Other message types are not signed as announcements
+    let mut message =
+        announcement([remote, pubkey("m/5/0"), remote, pubkey("m/0/2")]);
+    message[1] = 0x02;
+    assert_eq!(
+        ln::announcement_digest(&message, &pubkey("m/5/0"), &pubkey("m/0/2")),
+        Err(Error::GossipType(ln::CHANNEL_UPDATE))
+    );
+}
+
+#[test]
+fn vault_sign_gossip() {
+    let (vault, id) = vault("gossip");
+    let mut update = ln::CHANNEL_UPDATE.to_be_bytes().to_vec();
+    update.extend(&[0u8; 64]);
+    update.extend(&[0x11u8; 72]);
+    let signature = vault
+        .sign_gossip(id, &update, &mut decryption_key())
+        .unwrap();
+    verify(&update, 2 + 64, &signature, "m/5/0");
+
+    // Node announcement with empty features, followed by the timestamp
+    let node_announcement = |node_id: secp256k1::PublicKey| {
+        let mut message = ln::NODE_ANNOUNCEMENT.to_be_bytes().to_vec();
+        message.extend(&[0u8; 64 + 2 + 4]);
+        message.extend(&node_id.serialize()[..]);
+        message.extend(&[0u8; 3 + 32 + 2]);
+        message
+    };
+    let message = node_announcement(pubkey("m/5/0"));
+    let signature = vault
+        .sign_gossip(id, &message, &mut decryption_key())
+        .unwrap();
+    verify(&message, 2 + 64, &signature, "m/5/0");
+    assert_eq!(
+        ln::gossip_digest(
+            &node_announcement(pubkey("m/100")),
+            &pubkey("m/5/0")
+        ),
+        Err(Error::GossipMessage)
+    );
+
+    // Channel announcements and other messages are rejected
+    let message = announcement([pubkey("m/5/0"); 4]);
+    assert_eq!(
+        ln::gossip_digest(&message, &pubkey("m/5/0")),
+        Err(Error::GossipType(ln::CHANNEL_ANNOUNCEMENT))
+    );
+    assert_eq!(
+        ln::gossip_digest(&[0x00, 0x10], &pubkey("m/5/0")),
+        Err(Error::GossipMessage)
+    );
+}
@@ -0,0 +1,208 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "node")]
+
+mod common;
+
+use std::fs;
+use std::str::FromStr;
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::util::bip32::{DerivationPath, ExtendedPubKey};
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::{OutPoint, Script, Transaction, TxIn, Txid};
+use keyring::rpc::types::{CollisionPolicy, CosignerKey, MultisigGroup};
+use keyring::vault::multisig::{self, Error};
+use keyring::{RuntimeError, SECP256K1};
+
+use common::{decryption_key, encryption_key, open, path, xpriv};
+
+fn xpub(byte: u8) -> ExtendedPubKey {
+    ExtendedPubKey::from_private(&SECP256K1, &xpriv(byte))
+}
+
+fn group(threshold: u8, cosigners: &[u8]) -> MultisigGroup {
+    MultisigGroup {
+        name: "Treasury".to_string(),
+        threshold,
+        cosigners: cosigners
+            .iter()
+            .map(|byte| CosignerKey::master(xpub(*byte)))
+            .collect(),
+    }
+}
+
+/// PSBT with a single input using the key derived with `m/0/<index>` from
+/// each of the `group` cosigners
+fn psbt(group: &MultisigGroup, index: u32) -> PartiallySignedTransaction {
+    let mut psbt = PartiallySignedTransaction::from_unsigned_tx(Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(Txid::from_inner([1u8; 32]), 0),
+            script_sig: Script::new(),
+            sequence: 0xFFFFFFFF,
+            witness: vec![],
+        }],
+        output: vec![],
+    })
+    .unwrap();
+    let path = DerivationPath::from_str(&format!("m/0/{}", index)).unwrap();
+    for cosigner in &group.cosigners {
+        let key = cosigner.xpubkey.derive_pub(&SECP256K1, &path).unwrap();
+        psbt.inputs[0]
+            .bip32_derivation
+            .insert(key.public_key, (cosigner.origin.0, path.clone()));
+    }
+    psbt
+}
+
+#[test]
+fn cosigner_key_roundtrip() {
+    let key = CosignerKey {
+        xpubkey: xpub(1),
+        origin: (
+            xpub(2).fingerprint(),
+            DerivationPath::from_str("m/48'/1'/0'/2'").unwrap(),
+        ),
+    };
+    assert!(key.to_string().starts_with("["));
+    assert!(key.to_string().contains("/48'/1'/0'/2']tpub"));
+    assert_eq!(CosignerKey::from_str(&key.to_string()).unwrap(), key);
+
+    let master = CosignerKey::master(xpub(1));
+    assert_eq!(CosignerKey::from_str(&xpub(1).to_string()).unwrap(), master);
+    assert_eq!(CosignerKey::from_str(&master.to_string()).unwrap(), master);
+    assert!(CosignerKey::from_str(&format!("[{}", xpub(1))).is_err());
+    assert!(CosignerKey::from_str(&format!("[zz]{}", xpub(1))).is_err());
+}
+
+#[test]
+fn validate() {
+    assert_eq!(multisig::validate(&group(2, &[1, 2, 3])), Ok(()));
+    assert_eq!(
+        multisig::validate(&group(1, &[])),
+        Err(Error::CosignerCount(20, 0))
+    );
+    assert_eq!(
+        multisig::validate(&group(0, &[1, 2])),
+        Err(Error::Threshold(0, 2))
+    );
+    assert_eq!(
+        multisig::validate(&group(3, &[1, 2])),
+        Err(Error::Threshold(3, 2))
+    );
+    assert_eq!(
+        multisig::validate(&group(2, &[1, 2, 1])),
+        Err(Error::DuplicateCosigner(xpub(1)))
+    );
+}
+
+#[test]
+fn export() {
+    let group = group(2, &[1, 2, 3]);
+    let descriptors = multisig::export(&group);
+    assert_eq!(descriptors.len(), 2);
+    for (branch, descriptor) in descriptors.iter().enumerate() {
+        assert!(descriptor.starts_with("wsh(multi(2,["));
+        assert!(descriptor.contains(&format!("{}/{}/*", xpub(3), branch)));
+        assert_eq!(descriptor.split('#').nth(1).map(str::len), Some(8));
+    }
+    // Group id commits to the keys, but not to the name
+    let renamed = MultisigGroup {
+        name: "Renamed".to_string(),
+        ..group.clone()
+    };
+    assert_eq!(renamed.id(), group.id());
+    assert_ne!(group.id(), self::group(3, &[1, 2, 3]).id());
+}
+
+#[test]
+fn script_match() {
+    let group = group(2, &[1, 2, 3]);
+    let mut psbt = psbt(&group, 5);
+    let input = &mut psbt.inputs[0];
+    assert!(multisig::involves(&group, input, |_| true));
+    assert!(!multisig::involves(&group, input, |_| false));
+    assert!(!multisig::matches(&group, input));
+
+    let derivation = DerivationPath::from_str("m/0/5").unwrap();
+    input.witness_script = Some(multisig::script(&group, &derivation).unwrap());
+    assert!(multisig::matches(&group, input));
+
+    // Script with the keys of another index or other cosigner set
+    let derivation = DerivationPath::from_str("m/0/6").unwrap();
+    input.witness_script = Some(multisig::script(&group, &derivation).unwrap());
+    assert!(!multisig::matches(&group, input));
+    let other = self::group(2, &[1, 2, 4]);
+    let derivation = DerivationPath::from_str("m/0/5").unwrap();
+    input.witness_script = Some(multisig::script(&other, &derivation).unwrap());
+    assert!(!multisig::matches(&group, input));
+}
+
+#[test]
+fn create_and_sign() {
+    let path = path("multisig");
+    let _ = fs::remove_file(&path);
+    let mut vault = open(&path);
+    let id = vault
+        .import_xpriv(
+            xpriv(1),
+            None,
+            None,
+            "Cosigner",
+            None::<String>,
+            CollisionPolicy::Reject,
+            encryption_key(),
+        )
+        .unwrap()
+        .id;
+    let external = vec![CosignerKey::master(xpub(2))];
+
+    match vault.create_multisig("Empty", 1, &[], external.clone()) {
+        Err(RuntimeError::Multisig(Error::NoVaultAccount)) => {}
+        other => panic!("group without vault accounts: {:?}", other),
+    }
+    let group = vault
+        .create_multisig("Treasury", 2, &[id], external.clone())
+        .unwrap();
+    assert_eq!(group.cosigners[0], CosignerKey::master(xpub(1)));
+    match vault.create_multisig("Copy", 2, &[id], external) {
+        Err(RuntimeError::Multisig(Error::KnownGroup(known))) => {
+            assert_eq!(known, group.id())
+        }
+        other => panic!("duplicate group is accepted: {:?}", other),
+    }
+
+    let mut vault = open(&path);
+    assert_eq!(vault.multisig_groups(), vec![group.clone()]);
+    assert_eq!(
+        vault.multisig_descriptors(group.id()).unwrap(),
+        multisig::export(&group)
+    );
+    assert!(vault
+        .multisig_descriptors(sha256::Hash::hash(b"unknown"))
+        .is_err());
+
+    // Input using the vault account key, but spending the script in which
+    // the external cosigner was replaced
+    let mut psbt = psbt(&group, 0);
+    let derivation = DerivationPath::from_str("m/0/0").unwrap();
+    psbt.inputs[0].witness_script =
+        Some(multisig::script(&self::group(2, &[1, 3]), &derivation).unwrap());
+    match vault.sign_psbt(psbt, false, &mut decryption_key(), &mut || {}) {
+        Err(RuntimeError::Multisig(Error::ScriptMismatch(0))) => {}
+        other => panic!("mismatching script is signed: {:?}", other),
+    }
+}
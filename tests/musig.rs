@@ -0,0 +1,238 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "node")]
+
+mod common;
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::{self, PublicKey, SecretKey};
+use bitcoin::XpubIdentifier;
+use keyring::daemon::musig::{self, MuSigSessions};
+use keyring::rpc::types::MuSigNonce;
+use keyring::vault::musig::{
+    aggregate, nonce_agg, nonce_gen, partial_sign, Error, KeyAggContext,
+    PubNonce,
+};
+use keyring::vault::taproot;
+use keyring::SECP256K1;
+
+use common::decryption_key;
+
+fn keys(count: u8) -> Vec<(SecretKey, PublicKey)> {
+    (1..=count)
+        .map(|byte| {
+            let sk = SecretKey::from_slice(&[byte; 32]).unwrap();
+            (sk, PublicKey::from_secret_key(&SECP256K1, &sk))
+        })
+        .collect()
+}
+
+fn message() -> sha256::Hash {
+    sha256::Hash::hash(b"musig message")
+}
+
+/// Runs complete signing session for all `keys`, returning partial
+/// signatures and the aggregated nonce
+fn sign_all(
+    keys: &[(SecretKey, PublicKey)],
+    context: &KeyAggContext,
+) -> (Vec<[u8; 32]>, PubNonce) {
+    let (secnonces, pubnonces): (Vec<_>, Vec<_>) =
+        keys.iter().map(|_| nonce_gen()).unzip();
+    let aggnonce = nonce_agg(&pubnonces).unwrap();
+    let partial_sigs = secnonces
+        .into_iter()
+        .zip(keys)
+        .map(|(secnonce, (sk, _))| {
+            partial_sign(secnonce, sk, context, &aggnonce, message()).unwrap()
+        })
+        .collect();
+    (partial_sigs, aggnonce)
+}
+
+fn verify(
+    context: &KeyAggContext,
+    signature: &secp256k1::schnorrsig::Signature,
+) {
+    SECP256K1
+        .schnorrsig_verify(
+            signature,
+            &secp256k1::Message::from_slice(&message()[..]).unwrap(),
+            &context.aggregated_key(),
+        )
+        .unwrap();
+}
+
+#[test]
+fn sign_and_aggregate() {
+    for count in 1..=3 {
+        let keys = keys(count);
+        let pubkeys = keys.iter().map(|(_, pk)| *pk).collect::<Vec<_>>();
+        let context = KeyAggContext::with(&pubkeys).unwrap();
+        let (partial_sigs, aggnonce) = sign_all(&keys, &context);
+        let signature =
+            aggregate(&context, &aggnonce, message(), &partial_sigs).unwrap();
+        verify(&context, &signature);
+    }
+}
+
+#[test]
+fn taproot_tweak() {
+    let keys = keys(2);
+    let pubkeys = keys.iter().map(|(_, pk)| *pk).collect::<Vec<_>>();
+    let internal = KeyAggContext::with(&pubkeys).unwrap();
+    let mut context = internal.clone();
+    context.tweak_taproot().unwrap();
+
+    // Output key is P + H(P)G with P lifted to the even y coordinate
+    let internal_key = internal.aggregated_key().serialize();
+    let tweak = taproot::tagged_hash("TapTweak", &[&internal_key]);
+    let mut output_key =
+        PublicKey::from_slice(&[&[0x02u8][..], &internal_key].concat())
+            .unwrap();
+    output_key.add_exp_assign(&SECP256K1, &tweak[..]).unwrap();
+    assert_eq!(
+        context.aggregated_key().serialize()[..],
+        output_key.serialize()[1..]
+    );
+
+    let (partial_sigs, aggnonce) = sign_all(&keys, &context);
+    let signature =
+        aggregate(&context, &aggnonce, message(), &partial_sigs).unwrap();
+    verify(&context, &signature);
+}
+
+#[test]
+fn key_order() {
+    let keys = keys(3);
+    let mut pubkeys = keys.iter().map(|(_, pk)| *pk).collect::<Vec<_>>();
+    let context = KeyAggContext::with(&pubkeys).unwrap();
+    pubkeys.reverse();
+    let reversed = KeyAggContext::with(&pubkeys).unwrap();
+    assert_ne!(context.aggregated_key(), reversed.aggregated_key());
+
+    pubkeys.push(pubkeys[0]);
+    assert_eq!(
+        KeyAggContext::with(&pubkeys),
+        Err(Error::DuplicateParticipant(pubkeys[0]))
+    );
+    assert_eq!(KeyAggContext::with(&[]), Err(Error::NoParticipants));
+}
+
+#[test]
+fn invalid_partial_signature() {
+    let keys = keys(2);
+    let pubkeys = keys.iter().map(|(_, pk)| *pk).collect::<Vec<_>>();
+    let context = KeyAggContext::with(&pubkeys).unwrap();
+    let (mut partial_sigs, aggnonce) = sign_all(&keys, &context);
+    partial_sigs[1][31] ^= 0x01;
+    assert_eq!(
+        aggregate(&context, &aggnonce, message(), &partial_sigs),
+        Err(Error::InvalidSignature)
+    );
+
+    // Non-participant key can't sign
+    let (secnonce, _) = nonce_gen();
+    let outsider = decryption_key();
+    assert_eq!(
+        partial_sign(secnonce, &outsider, &context, &aggnonce, message()),
+        Err(Error::UnknownParticipant(PublicKey::from_secret_key(
+            &SECP256K1, &outsider
+        )))
+    );
+}
+
+#[test]
+fn nonce_serialization() {
+    let (_, nonce) = nonce_gen();
+    let data = nonce.serialize();
+    assert_eq!(data.len(), 66);
+    assert_eq!(PubNonce::from_slice(&data), Ok(nonce));
+    assert!(PubNonce::from_slice(&data[..65]).is_err());
+    assert!(PubNonce::from_slice(&[0u8; 66]).is_err());
+}
+
+#[test]
+fn daemon_sessions() {
+    let outsider = keys(3)[2].1;
+    let keys = keys(2);
+    let pubkeys = keys.iter().map(|(_, pk)| *pk).collect::<Vec<_>>();
+    let key_id = XpubIdentifier::hash(b"account");
+    let mut sessions = MuSigSessions::new();
+
+    assert!(sessions
+        .start(key_id, outsider, &pubkeys, message(), true)
+        .is_err());
+
+    let session = sessions
+        .start(key_id, pubkeys[0], &pubkeys, message(), true)
+        .unwrap();
+    assert!(matches!(
+        sessions.take(session.id),
+        Err(musig::Error::NoncesPending(_))
+    ));
+    assert!(matches!(
+        sessions.exchange(session.id, &[]),
+        Err(musig::Error::MissingNonce(key)) if key == pubkeys[1]
+    ));
+
+    // The other participant
+    let mut context = KeyAggContext::with(&pubkeys).unwrap();
+    context.tweak_taproot().unwrap();
+    assert_eq!(
+        context.aggregated_key().serialize().to_vec(),
+        session.aggregated_key
+    );
+    let (secnonce, pubnonce) = nonce_gen();
+    let aggnonce = sessions
+        .exchange(
+            session.id,
+            &[MuSigNonce {
+                participant: pubkeys[1],
+                nonce: pubnonce.serialize(),
+            }],
+        )
+        .unwrap();
+    assert_eq!(
+        aggnonce,
+        nonce_agg(&[PubNonce::from_slice(&session.nonce).unwrap(), pubnonce])
+            .unwrap()
+            .serialize()
+    );
+    let aggnonce = PubNonce::from_slice(&aggnonce).unwrap();
+    let other =
+        partial_sign(secnonce, &keys[1].0, &context, &aggnonce, message())
+            .unwrap();
+
+    // The daemon side, where the account key is kept in the vault
+    let signing = sessions.take(session.id).unwrap();
+    assert_eq!(signing.key_id, key_id);
+    let own = partial_sign(
+        signing.secnonce,
+        &keys[0].0,
+        &signing.context,
+        &signing.aggnonce,
+        signing.message,
+    )
+    .unwrap();
+    let signature =
+        aggregate(&context, &aggnonce, message(), &[own, other]).unwrap();
+    verify(&context, &signature);
+
+    // Session is closed once the secret nonce is taken
+    assert!(matches!(
+        sessions.take(session.id),
+        Err(musig::Error::UnknownSession(_))
+    ));
+}
@@ -0,0 +1,85 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+use std::str::FromStr;
+
+use bitcoin::util::bip32::DerivationPath;
+use bitcoin::Network;
+use keyring::derivation::{self, Error, Level, PathBuilder, Purpose};
+
+#[test]
+fn build_standard_paths() {
+    let path = PathBuilder::account_path(Purpose::Bip84, Network::Bitcoin, 0)
+        .change(false)
+        .index(5)
+        .build()
+        .unwrap();
+    assert_eq!(path.to_string(), "m/84'/0'/0'/0/5");
+
+    let path = PathBuilder::account_path(Purpose::Bip48, Network::Testnet, 1)
+        .script_type(2)
+        .build()
+        .unwrap();
+    assert_eq!(path.to_string(), "m/48'/1'/1'/2'");
+
+    assert_eq!(
+        PathBuilder::new()
+            .purpose(Purpose::Bip44)
+            .index(1 << 31)
+            .build(),
+        Err(Error::IndexRange(1 << 31))
+    );
+}
+
+#[test]
+fn display_roundtrip() {
+    for s in &["m", "m/84'/0'/0'/1/3", "m/48'/0'/0'/1'", "m/0/1'/2"] {
+        let path = derivation::parse(s).unwrap();
+        assert_eq!(path.to_string(), *s);
+        assert_eq!(derivation::parse(&path.to_string()).unwrap(), path);
+    }
+    assert_eq!(
+        derivation::parse("84h/0h/0h").unwrap(),
+        DerivationPath::from_str("m/84'/0'/0'").unwrap()
+    );
+    assert_eq!(derivation::parse("m/").unwrap(), DerivationPath::master());
+}
+
+#[test]
+fn reject_invalid_layouts() {
+    assert_eq!(
+        derivation::parse("m/84/0'/0'"),
+        Err(Error::NotHardened(Purpose::Bip84, Level::Purpose))
+    );
+    assert_eq!(
+        derivation::parse("m/44'/0/0'"),
+        Err(Error::NotHardened(Purpose::Bip44, Level::CoinType))
+    );
+    assert_eq!(
+        derivation::parse("m/49'/0'/0'/0'"),
+        Err(Error::Hardened(Purpose::Bip49, Level::Change))
+    );
+    assert_eq!(
+        derivation::parse("m/86'/0'/0'/2/0"),
+        Err(Error::Change(Purpose::Bip86, 2))
+    );
+    assert_eq!(
+        derivation::parse("m/84'/0'/0'/0/0/0"),
+        Err(Error::PurposeDepth(Purpose::Bip84, 5))
+    );
+    assert_eq!(
+        derivation::parse("m/48'/0'/0'/3'"),
+        Err(Error::ScriptType(3))
+    );
+    assert!(matches!(derivation::parse("m/84'/x"), Err(Error::Bip32(_))));
+}
@@ -0,0 +1,139 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "node")]
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1;
+use bitcoin::util::bip32::{DerivationPath, Fingerprint};
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::{
+    OutPoint, PublicKey, Script, Transaction, TxIn, TxOut, Txid, XpubIdentifier,
+};
+use keyring::rpc::types::{RateLimit, SigningPolicy};
+use keyring::vault::policy::{evaluate, PolicyViolation, Rule, SigningHistory};
+
+fn fingerprint() -> Fingerprint {
+    Fingerprint::from(&[0xA5u8, 0xA5, 0xA5, 0xA5][..])
+}
+
+fn account() -> XpubIdentifier {
+    XpubIdentifier::hash(b"account")
+}
+
+fn script(byte: u8) -> Script {
+    Script::from(vec![0x6a, 0x01, byte])
+}
+
+/// PSBT spending a single input with two outputs: 30 000 sat payment to
+/// `script(1)` and 60 000 sat change returned to the keyring
+fn psbt() -> PartiallySignedTransaction {
+    let mut psbt = PartiallySignedTransaction::from_unsigned_tx(Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(Txid::from_inner([1u8; 32]), 0),
+            script_sig: Script::new(),
+            sequence: 0xFFFFFFFF,
+            witness: vec![],
+        }],
+        output: vec![
+            TxOut {
+                value: 30_000,
+                script_pubkey: script(1),
+            },
+            TxOut {
+                value: 60_000,
+                script_pubkey: script(2),
+            },
+        ],
+    })
+    .unwrap();
+    let sk = secp256k1::SecretKey::from_slice(&[1u8; 32]).unwrap();
+    let pubkey = PublicKey {
+        compressed: true,
+        key: secp256k1::PublicKey::from_secret_key(&keyring::SECP256K1, &sk),
+    };
+    psbt.outputs[1].bip32_derivation.insert(
+        pubkey,
+        (fingerprint(), DerivationPath::from_str("m/1/0").unwrap()),
+    );
+    psbt
+}
+
+fn check(
+    policy: &SigningPolicy,
+    history: &SigningHistory,
+) -> Result<(), PolicyViolation> {
+    evaluate(account(), policy, &psbt(), &[0], fingerprint(), history)
+}
+
+#[test]
+fn policy_rules() {
+    let history = SigningHistory::new();
+    assert!(check(&SigningPolicy::default(), &history).is_ok());
+
+    // Change output is not counted towards the limit and whitelist
+    let policy = SigningPolicy {
+        max_amount: Some(30_000),
+        destinations: vec![script(1)],
+        sighash_types: vec![0x01],
+        rate_limit: None,
+        allow_ecdh: false,
+    };
+    assert!(check(&policy, &history).is_ok());
+
+    let policy = SigningPolicy {
+        max_amount: Some(29_999),
+        destinations: vec![script(3)],
+        sighash_types: vec![0x81],
+        rate_limit: None,
+        allow_ecdh: false,
+    };
+    assert_eq!(
+        check(&policy, &history).unwrap_err(),
+        PolicyViolation {
+            account: account(),
+            rules: vec![
+                Rule::MaxAmount(29_999, 30_000),
+                Rule::Destination(0),
+                Rule::SighashType(0, 0x01),
+            ]
+        }
+    );
+}
+
+#[test]
+fn policy_rate_limit() {
+    let policy = SigningPolicy {
+        rate_limit: Some(RateLimit {
+            count: 2,
+            period: 3600,
+        }),
+        ..SigningPolicy::default()
+    };
+    let period = Duration::from_secs(3600);
+    let mut history = SigningHistory::new();
+    for _ in 0..2 {
+        assert!(check(&policy, &history).is_ok());
+        history.record(account(), period);
+    }
+    assert_eq!(
+        check(&policy, &history).unwrap_err().rules,
+        vec![Rule::RateLimit(2, 3600)]
+    );
+    assert_eq!(history.count(account(), Duration::from_secs(0)), 0);
+}
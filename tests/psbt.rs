@@ -0,0 +1,96 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(any(feature = "node", feature = "client"))]
+
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1;
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::{OutPoint, PublicKey, Script, Transaction, TxIn, TxOut, Txid};
+use keyring::psbt::{combine, Error};
+
+fn unsigned_psbt(lock_time: u32) -> PartiallySignedTransaction {
+    PartiallySignedTransaction::from_unsigned_tx(Transaction {
+        version: 2,
+        lock_time,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(Txid::from_inner([1u8; 32]), 0),
+            script_sig: Script::new(),
+            sequence: 0xFFFFFFFF,
+            witness: vec![],
+        }],
+        output: vec![TxOut {
+            value: 10_000,
+            script_pubkey: Script::new(),
+        }],
+    })
+    .unwrap()
+}
+
+fn pubkey(secret: u8) -> PublicKey {
+    let sk = secp256k1::SecretKey::from_slice(&[secret; 32]).unwrap();
+    PublicKey {
+        compressed: true,
+        key: secp256k1::PublicKey::from_secret_key(&keyring::SECP256K1, &sk),
+    }
+}
+
+#[test]
+fn combine_signatures() {
+    let mut psbt_a = unsigned_psbt(0);
+    psbt_a.inputs[0].partial_sigs.insert(pubkey(1), vec![0xA1]);
+    let mut psbt_b = unsigned_psbt(0);
+    psbt_b.inputs[0].partial_sigs.insert(pubkey(2), vec![0xB2]);
+    psbt_b.inputs[0].witness_utxo = Some(TxOut {
+        value: 20_000,
+        script_pubkey: Script::new(),
+    });
+
+    let combined = combine(vec![psbt_a, psbt_b]).unwrap();
+    let input = &combined.inputs[0];
+    assert_eq!(input.partial_sigs.len(), 2);
+    assert_eq!(input.partial_sigs[&pubkey(1)], vec![0xA1]);
+    assert_eq!(input.partial_sigs[&pubkey(2)], vec![0xB2]);
+    assert_eq!(input.witness_utxo.as_ref().unwrap().value, 20_000);
+}
+
+#[test]
+fn combine_inconsistent() {
+    assert_eq!(combine(vec![]).unwrap_err(), Error::NoPsbts);
+
+    let psbt = unsigned_psbt(0);
+    let other = unsigned_psbt(1);
+    assert_eq!(
+        combine(vec![psbt.clone(), psbt.clone(), other.clone()]).unwrap_err(),
+        Error::TxMismatch(
+            2,
+            other.global.unsigned_tx.txid(),
+            psbt.global.unsigned_tx.txid()
+        )
+    );
+
+    let mut psbt_a = psbt.clone();
+    let mut psbt_b = psbt;
+    psbt_a.inputs[0].witness_utxo = Some(TxOut {
+        value: 20_000,
+        script_pubkey: Script::new(),
+    });
+    psbt_b.inputs[0].witness_utxo = Some(TxOut {
+        value: 30_000,
+        script_pubkey: Script::new(),
+    });
+    assert_eq!(
+        combine(vec![psbt_a, psbt_b]).unwrap_err(),
+        Error::InputMismatch(1, "witness UTXO", 0)
+    );
+}
@@ -0,0 +1,60 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "server")]
+
+use keyring::daemon::{ClientConfig, Config};
+use keyring::vault;
+use microservices::shell::LogLevel;
+
+#[test]
+fn reloadable_settings() {
+    let config = Config::default();
+    let mut reloaded = config.clone();
+    reloaded.log_level = LogLevel::Trace;
+    reloaded.signing_cache = 16;
+    reloaded.clients.insert(
+        "admin".to_string(),
+        ClientConfig {
+            secret: b"admin secret".to_vec(),
+            redact: Default::default(),
+            admin: true,
+        },
+    );
+    assert!(config.immutable_changes(&reloaded).is_empty());
+}
+
+#[test]
+fn immutable_settings() {
+    let mut config = Config::default();
+    config.encryption = vault::Encryption::Passphrase { unlock_timeout: 60 };
+
+    // Unlock timeout may be changed, while the vault encryption can't
+    let mut reloaded = config.clone();
+    reloaded.encryption = vault::Encryption::Passphrase {
+        unlock_timeout: 600,
+    };
+    assert!(config.immutable_changes(&reloaded).is_empty());
+    reloaded.encryption = vault::Encryption::NodeKey;
+    reloaded.workers += 1;
+    reloaded.read_only = true;
+    assert_eq!(
+        config.immutable_changes(&reloaded),
+        vec!["encryption", "read_only", "workers"]
+    );
+}
+
+#[test]
+fn reload_requires_file() {
+    assert!(Config::default().reload().is_err());
+}
@@ -0,0 +1,140 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "node")]
+
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::XpubIdentifier;
+use keyring::daemon::revocation::{
+    Error, Revocations, SecretChain, MAX_SECRET_INDEX,
+};
+use keyring::rpc::types::CommitmentSecret;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "keyring-{}-{}",
+        std::process::id(),
+        name
+    ));
+    let _ = fs::remove_dir_all(&path);
+    path
+}
+
+/// Chain holding only the seed, from which any secret can be derived
+fn seed_chain(seed: u8) -> SecretChain {
+    let mut chain = SecretChain::new();
+    chain
+        .insert(0, CommitmentSecret::from_inner([seed; 32]))
+        .unwrap();
+    chain
+}
+
+#[test]
+fn bolt3_generation() {
+    // Test vectors from BOLT-3 Appendix D
+    assert_eq!(
+        seed_chain(0x00).get(MAX_SECRET_INDEX).unwrap(),
+        sha256::Hash::from_str(
+            "02a40c85b6f28da08dfdbe0926c53fab2de6d28c10301f8f7c4073d5e42e3148"
+        )
+        .unwrap()
+    );
+    assert_eq!(
+        seed_chain(0xFF).get(MAX_SECRET_INDEX).unwrap(),
+        sha256::Hash::from_str(
+            "7cc854b54e3e0dcdb010d7a3fee464a9687be6e8db3be6854c475621e007a5dc"
+        )
+        .unwrap()
+    );
+    assert_eq!(
+        seed_chain(0xFF).get(0xaaaaaaaaaaa).unwrap(),
+        sha256::Hash::from_str(
+            "56f4008fb007ca9acf0e15b054d5c9fd12ee06cea347914ddbaed70d1c13a528"
+        )
+        .unwrap()
+    );
+}
+
+#[test]
+fn secret_chain() {
+    let seed = seed_chain(0xFF);
+    let mut chain = SecretChain::new();
+    for index in (MAX_SECRET_INDEX - 100..=MAX_SECRET_INDEX).rev() {
+        chain.insert(index, seed.get(index).unwrap()).unwrap();
+    }
+    assert!(chain.secrets().len() <= 49);
+    for index in MAX_SECRET_INDEX - 100..=MAX_SECRET_INDEX {
+        assert_eq!(chain.get(index), seed.get(index));
+    }
+    let next = MAX_SECRET_INDEX - 101;
+    assert_eq!(chain.get(next), Err(Error::UnknownSecret(next)));
+    assert_eq!(
+        chain.insert(next - 1, seed.get(next - 1).unwrap()),
+        Err(Error::Order(next + 1, next - 1))
+    );
+    // Index with many trailing zeros requires checking previous secrets
+    let mut chain = SecretChain::new();
+    chain.insert(0x101, seed.get(0x101).unwrap()).unwrap();
+    assert_eq!(
+        chain.insert(0x100, seed_chain(0x00).get(0x100).unwrap()),
+        Err(Error::Inconsistent(0x100))
+    );
+    chain.insert(0x100, seed.get(0x100).unwrap()).unwrap();
+    assert_eq!(
+        chain.insert(MAX_SECRET_INDEX + 1, seed.get(0).unwrap()),
+        Err(Error::Index(MAX_SECRET_INDEX + 1))
+    );
+}
+
+#[test]
+fn revocation_storage() {
+    let dir = temp_dir("revocations");
+    let revocations = Revocations::with(&dir.to_string_lossy());
+    let account = XpubIdentifier::hash(b"ln node account");
+    let channel = sha256::Hash::hash(b"channel");
+    let seed = seed_chain(0x00);
+
+    let last = MAX_SECRET_INDEX - 200;
+    for index in (last..=MAX_SECRET_INDEX).rev() {
+        revocations
+            .append(account, channel, index, seed.get(index).unwrap())
+            .unwrap();
+    }
+    assert_eq!(
+        revocations.append(account, channel, last, seed.get(last).unwrap()),
+        Err(Error::Order(last, last))
+    );
+    assert_eq!(
+        revocations.get(account, sha256::Hash::hash(b"unknown"), last),
+        Err(Error::UnknownChannel(sha256::Hash::hash(b"unknown")))
+    );
+
+    let secrets = revocations.secrets(account).unwrap()[&channel].secrets();
+    let removed = revocations.compact(account).unwrap();
+    assert_eq!(removed, 201 - secrets.len());
+    assert_eq!(revocations.compact(account).unwrap(), 0);
+
+    // Secrets continue after the compacted records
+    revocations
+        .append(account, channel, last - 1, seed.get(last - 1).unwrap())
+        .unwrap();
+    for index in (last - 1..=MAX_SECRET_INDEX).step_by(7) {
+        assert_eq!(revocations.get(account, channel, index), seed.get(index));
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,145 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "node")]
+
+mod common;
+
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1;
+use bitcoin::util::bip32::DerivationPath;
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::{OutPoint, PublicKey, Script, Transaction, TxIn, TxOut, Txid};
+use keyring::rpc::types::UpdateMode;
+use keyring::vault::keymgm::Error;
+use keyring::vault::rgb;
+use keyring::{RuntimeError, SECP256K1};
+use lnpbp::chain::AssetId;
+
+use common::{decryption_key, vault, xpriv};
+
+const SEED: u8 = 0x2B;
+
+/// PSBT spending P2WPKH output of the account key `m/0/0` and transferring
+/// the `assets`
+fn psbt(assets: &[AssetId]) -> (PartiallySignedTransaction, PublicKey) {
+    let derivation = DerivationPath::from_str("m/0/0").unwrap();
+    let pubkey = PublicKey {
+        compressed: true,
+        key: secp256k1::PublicKey::from_secret_key(
+            &SECP256K1,
+            &xpriv(SEED)
+                .derive_priv(&SECP256K1, &derivation)
+                .unwrap()
+                .private_key
+                .key,
+        ),
+    };
+    let spent = TxOut {
+        value: 10_000,
+        script_pubkey: Script::new_v0_wpkh(&pubkey.wpubkey_hash().unwrap()),
+    };
+    let mut psbt = PartiallySignedTransaction::from_unsigned_tx(Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(Txid::from_inner([2u8; 32]), 1),
+            script_sig: Script::new(),
+            sequence: 0xFFFF_FFFD,
+            witness: vec![],
+        }],
+        output: vec![TxOut {
+            value: 9_000,
+            script_pubkey: spent.script_pubkey.clone(),
+        }],
+    })
+    .unwrap();
+    let input = &mut psbt.inputs[0];
+    input.witness_utxo = Some(spent);
+    input
+        .bip32_derivation
+        .insert(pubkey, (xpriv(SEED).fingerprint(&SECP256K1), derivation));
+    for asset in assets {
+        rgb::add_transition(input, *asset, vec![0x01, 0x02]);
+    }
+    (psbt, pubkey)
+}
+
+#[test]
+fn transition_keys() {
+    let asset = AssetId::hash(b"asset");
+    let (psbt, _) = psbt(&[asset]);
+    let mut input = psbt.inputs[0].clone();
+    assert_eq!(
+        rgb::input_assets(&input),
+        vec![asset].into_iter().collect::<HashSet<_>>()
+    );
+
+    // Proprietary keys of other protocols are ignored
+    let mut key = rgb::transition_key(AssetId::hash(b"other"));
+    key.key[1] = b'L';
+    input.unknown.insert(key, vec![]);
+    assert_eq!(rgb::input_assets(&input).len(), 1);
+}
+
+#[test]
+fn asset_bindings() {
+    let (mut vault, id) = vault("rgb", SEED, "RGB assets");
+    let bound = AssetId::hash(b"bound asset");
+    let unbound = AssetId::hash(b"unbound asset");
+    vault
+        .update_account(
+            id,
+            None::<String>,
+            None::<String>,
+            Some(vec![bound].into_iter().collect()),
+            UpdateMode::Add,
+        )
+        .unwrap();
+
+    // Plain bitcoin inputs are not restricted
+    let (plain, pubkey) = psbt(&[]);
+    let signed = vault
+        .sign_psbt(plain, false, &mut decryption_key(), &mut || ())
+        .unwrap();
+    assert!(signed.inputs[0].partial_sigs.contains_key(&pubkey));
+
+    let (transfer, _) = psbt(&[bound]);
+    let signed = vault
+        .sign_psbt(transfer, false, &mut decryption_key(), &mut || ())
+        .unwrap();
+    assert!(signed.inputs[0].partial_sigs.contains_key(&pubkey));
+
+    let (transfer, _) = psbt(&[bound, unbound]);
+    match vault.sign_psbt(
+        transfer.clone(),
+        false,
+        &mut decryption_key(),
+        &mut || (),
+    ) {
+        Err(RuntimeError::KeyManagement(Error::AssetIds(assets))) => {
+            assert_eq!(
+                assets,
+                vec![unbound].into_iter().collect::<HashSet<_>>()
+            )
+        }
+        other => panic!("unbound asset is signed: {:?}", other),
+    }
+    let signed = vault
+        .sign_psbt(transfer, true, &mut decryption_key(), &mut || ())
+        .unwrap();
+    assert!(signed.inputs[0].partial_sigs.contains_key(&pubkey));
+}
@@ -0,0 +1,1885 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "server")]
+
+use std::collections::{BTreeMap, HashSet};
+use std::str::FromStr;
+
+use bitcoin::consensus::deserialize;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1;
+use bitcoin::util::bip32::{
+    ChainCode, DerivationPath, ExtendedPrivKey, ExtendedPubKey, Fingerprint,
+    KeySource,
+};
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::XpubIdentifier;
+use internet2::{CreateUnmarshaller, TypedEnum, Unmarshall};
+use keyring::daemon::{
+    AccountField, Authenticator, ClientConfig, MAX_CHALLENGES,
+};
+use keyring::lifecycle::Lifecycle;
+use keyring::rpc::auth::{timestamp_challenge, NonceGenerator};
+use keyring::rpc::types::{
+    AccountBalance, AccountInfo, AccountQuery, AnnouncementSignatures,
+    Approval, Attestation, AuthCode, Bip85Application, Branches,
+    CollisionPolicy, CosignerKey, DerivationTemplate, DerivedKey, Feature,
+    Features, Hello, IdentityKey, IdentitySignature, InputAnalysis,
+    JobProgress, KeyPrefix, LabelQuery, LedgerEntry, LnKeySet, MuSigNonce,
+    MuSigSession, MuSigSignature, MultisigGroup, PaymentCode, PaymentCodeInfo,
+    PaymentDirection, PaymentKey, PsbtAnalysis, PsbtInput, PsbtOutput,
+    RateLimit, Session, SigningPolicy, Status, TaggedReply, UpdateMode,
+    VaultInfo,
+};
+use keyring::rpc::{message, routed, tagged, Reply, Request};
+use keyring::vault::Keyring;
+use lnpbp::chain::AssetId;
+use lnpbp::strict_encoding::{strict_deserialize, strict_serialize};
+use lnpbp::Chain;
+use microservices::rpc::Failure;
+use slip132::KeyApplication;
+
+fn encryption_key() -> secp256k1::PublicKey {
+    secp256k1::PublicKey::from_str(
+        "03933615cab8f016c8375602884804b56061bcdd8fe362eb7e12c87d61c5275c5f",
+    )
+    .unwrap()
+}
+
+fn account_info() -> AccountInfo {
+    let keyring = Keyring::with(
+        "Sample",
+        "Round-trip test keyring",
+        &Chain::Testnet3,
+        KeyApplication::SegWit,
+        None,
+        encryption_key(),
+    )
+    .unwrap();
+    AccountInfo::from(&keyring)
+}
+
+fn xpriv() -> ExtendedPrivKey {
+    ExtendedPrivKey::new_master(bitcoin::Network::Testnet, &[0xA5u8; 32])
+        .unwrap()
+}
+
+fn key_id() -> XpubIdentifier {
+    XpubIdentifier::hash(b"key id")
+}
+
+fn session_token() -> sha256::Hash {
+    sha256::Hash::hash(b"session")
+}
+
+fn auth_code() -> AuthCode {
+    AuthCode {
+        client: "client".to_owned(),
+        challenge: sha256::Hash::hash(b"challenge"),
+        timestamp: Some(u64::MAX),
+        hmac: sha256::Hash::hash(b"hmac"),
+    }
+}
+
+/// Secret keys at both ends of the valid range
+fn secret_keys() -> Vec<secp256k1::SecretKey> {
+    vec![
+        secp256k1::key::ONE_KEY,
+        secp256k1::SecretKey::from_slice(&[
+            0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+            0xFF, 0xFF, 0xFF, 0xFF, 0xFE, 0xBA, 0xAE, 0xDC, 0xE6, 0xAF, 0x48,
+            0xA0, 0x3B, 0xBF, 0xD2, 0x5E, 0x8C, 0xD0, 0x36, 0x41, 0x40,
+        ])
+        .unwrap(),
+    ]
+}
+
+/// Empty, the deepest hardened and the largest normal derivation paths
+fn derivation_paths() -> Vec<DerivationPath> {
+    vec![
+        DerivationPath::from_str("m").unwrap(),
+        DerivationPath::from_str("m/2147483647'/0'/2147483647'/1/2147483647")
+            .unwrap(),
+    ]
+}
+
+fn key_sources() -> Vec<Option<KeySource>> {
+    vec![
+        None,
+        Some((
+            Fingerprint::default(),
+            DerivationPath::from_str("m").unwrap(),
+        )),
+        Some((
+            Fingerprint::from(&[0xFFu8; 4][..]),
+            derivation_paths()[1].clone(),
+        )),
+    ]
+}
+
+fn strings() -> Vec<String> {
+    vec![
+        String::new(),
+        "Keyring account".to_string(),
+        "Ключ 🔑 with\nnewline and \u{0} byte".to_string(),
+        "x".repeat(u16::MAX as usize),
+    ]
+}
+
+fn psbt() -> PartiallySignedTransaction {
+    deserialize(include_bytes!("../sample/signed.psbt")).unwrap()
+}
+
+/// Expected wire type of each request. The match is exhaustive, so adding a
+/// new request variant without assigning it a type id here fails the build.
+fn request_type(request: &Request) -> u16 {
+    match request {
+        Request::Challenge(_) => 0x0002,
+        Request::Status => 0x0004,
+        Request::Unlock(_) => 0x0006,
+        Request::Lock(_) => 0x0008,
+        Request::Attest(_) => 0x000A,
+        Request::JobStatus(_) => 0x000C,
+        Request::Sealed(_) => 0x000E,
+        Request::List => 0x0010,
+        Request::ListWithBalances(_) => 0x0012,
+        Request::FindAccounts(_) => 0x0014,
+        Request::ListMultisig => 0x0016,
+        Request::Tagged(_) => 0x0018,
+        Request::Hello(_) => 0x001A,
+        Request::Reconfigure(_) => 0x001C,
+        Request::Routed(_) => 0x001E,
+        Request::Seed(_) => 0x0020,
+        Request::DeleteKeyring(_) => 0x0022,
+        Request::ImportDescriptors(_) => 0x0024,
+        Request::ImportXpub(_) => 0x0026,
+        Request::ImportXpriv(_) => 0x0028,
+        Request::Restore(_) => 0x002A,
+        Request::ListVaults => 0x002C,
+        Request::ExportXpub(_) => 0x0030,
+        Request::ExportXpriv(_) => 0x0032,
+        Request::ExportDescriptor(_) => 0x0034,
+        Request::IdentityKey(_) => 0x0036,
+        Request::Backup(_) => 0x0038,
+        Request::ExportLedger(_) => 0x003A,
+        Request::ApproveExport(_) => 0x003C,
+        Request::DeriveEntropy(_) => 0x003E,
+        Request::Derive(_) => 0x0040,
+        Request::DeleteAccount(_) => 0x0042,
+        Request::SetLifecycle(_) => 0x0044,
+        Request::DeriveRange(_) => 0x0046,
+        Request::SetBranches(_) => 0x0048,
+        Request::CommitSandbox(_) => 0x004A,
+        Request::DiscardSandbox(_) => 0x004C,
+        Request::Discover(_) => 0x004E,
+        Request::SignPsbt(_) => 0x0050,
+        Request::SignKey(_) => 0x0052,
+        Request::SignData(_) => 0x0054,
+        Request::SignIdentity(_) => 0x0056,
+        Request::SignMessage(_) => 0x0058,
+        Request::FinalizePsbt(_) => 0x005A,
+        Request::ComposePsbt(_) => 0x005C,
+        Request::SetPolicy(_) => 0x005E,
+        Request::LoadVault(_) => 0x0060,
+        Request::StoreVault(_) => 0x0062,
+        Request::SetLabel(_) => 0x0064,
+        Request::UpdateAccount(_) => 0x0066,
+        Request::CreateMultisig(_) => 0x0068,
+        Request::ExportMultisig(_) => 0x006A,
+        Request::AppendRevocation(_) => 0x0070,
+        Request::QueryRevocation(_) => 0x0072,
+        Request::CompactRevocations(_) => 0x0074,
+        Request::MuSigStartSession(_) => 0x0080,
+        Request::MuSigNonceExchange(_) => 0x0082,
+        Request::MuSigPartialSign(_) => 0x0084,
+        Request::DerivePaymentCode(_) => 0x0086,
+        Request::DeriveLnKeySet(_) => 0x0088,
+        Request::Ecdh(_) => 0x008A,
+        Request::SignInvoice(_) => 0x008C,
+        Request::SignChannelAnnouncement(_) => 0x008E,
+        Request::SignGossip(_) => 0x0090,
+        Request::ExportVault(_) => 0x0092,
+        Request::ImportVault(_) => 0x0094,
+        Request::AnalyzePsbt(_) => 0x0096,
+        Request::CombinePsbt(_) => 0x0098,
+    }
+}
+
+/// Expected wire type of each reply; see [`request_type`]
+fn reply_type(reply: &Reply) -> u16 {
+    match reply {
+        Reply::Success => 0x0100,
+        Reply::Failure(_) => 0x0102,
+        Reply::Status(_) => 0x0104,
+        Reply::Session(_) => 0x0106,
+        Reply::Challenge(_) => 0x0108,
+        Reply::Approval(_) => 0x010A,
+        Reply::Attestation(_) => 0x010C,
+        Reply::JobProgress(_) => 0x010E,
+        Reply::Sealed(_) => 0x0110,
+        Reply::Tagged(_) => 0x0112,
+        Reply::Hello(_) => 0x0114,
+        Reply::Keylist(_) => 0x0200,
+        Reply::AccountInfo(_) => 0x0202,
+        Reply::BalanceList(_) => 0x0204,
+        Reply::DerivedKeys(_) => 0x0206,
+        Reply::IdentityKey(_) => 0x0208,
+        Reply::Multisig(_) => 0x020A,
+        Reply::MultisigGroups(_) => 0x020C,
+        Reply::PaymentCode(_) => 0x020E,
+        Reply::LnKeySet(_) => 0x0210,
+        Reply::VaultList(_) => 0x0212,
+        Reply::PsbtAnalysis(_) => 0x0214,
+        Reply::XPriv(_) => 0x0300,
+        Reply::XPub(_) => 0x0302,
+        Reply::Descriptors(_) => 0x0304,
+        Reply::Backup(_) => 0x0306,
+        Reply::Ledger(_) => 0x0308,
+        #[cfg(feature = "export-secrets")]
+        Reply::DerivedSecret(_) => 0x030A,
+        Reply::EncodedXPub(_) => 0x030C,
+        Reply::Vault(_) => 0x0400,
+        Reply::VaultDump(_) => 0x0402,
+        Reply::Signature(_) => 0x0500,
+        Reply::Psbt(_) => 0x0502,
+        Reply::IdentitySignature(_) => 0x0504,
+        Reply::MessageSignature(_) => 0x0506,
+        Reply::Transaction(_) => 0x0508,
+        Reply::MuSigSession(_) => 0x050A,
+        Reply::MuSigNonce(_) => 0x050C,
+        Reply::MuSigSignature(_) => 0x050E,
+        Reply::InvoiceSignature(_) => 0x0510,
+        Reply::AnnouncementSignatures(_) => 0x0512,
+        Reply::CommitmentSecret(_) => 0x0600,
+        Reply::SharedSecret(_) => 0x0602,
+    }
+}
+
+fn assert_request_roundtrip(request: Request) {
+    let data = request.serialize();
+    let decoded = Request::create_unmarshaller()
+        .unmarshall(&data)
+        .expect("request must be decodable");
+    assert_eq!(request.get_type(), request_type(&request));
+    assert_eq!(decoded.get_type(), request.get_type());
+    assert_eq!(decoded.serialize(), data);
+}
+
+fn assert_roundtrip(reply: Reply) {
+    let data = reply.serialize();
+    let decoded = Reply::create_unmarshaller()
+        .unmarshall(&data)
+        .expect("reply must be decodable");
+    assert_eq!(reply.get_type(), reply_type(&reply));
+    assert_eq!(decoded.get_type(), reply.get_type());
+    assert_eq!(decoded.serialize(), data);
+}
+
+#[test]
+fn reply_success() {
+    assert_roundtrip(Reply::Success);
+}
+
+#[test]
+fn reply_failure() {
+    assert_roundtrip(Reply::Failure(Failure {
+        code: 0,
+        info: "some failure".to_string(),
+    }));
+    assert_roundtrip(Reply::Failure(Failure {
+        code: u16::MAX,
+        info: String::new(),
+    }));
+}
+
+#[test]
+fn reply_session() {
+    assert_roundtrip(Reply::Session(Session {
+        token: sha256::Hash::hash(b"session"),
+        expires_in: 300,
+    }));
+}
+
+#[test]
+fn reply_challenge() {
+    assert_roundtrip(Reply::Challenge(sha256::Hash::hash(b"challenge")));
+}
+
+#[test]
+fn reply_approval() {
+    for expires_in in &[0u64, 60, u64::MAX] {
+        assert_roundtrip(Reply::Approval(Approval {
+            token: sha256::Hash::hash(b"approval"),
+            expires_in: *expires_in,
+        }));
+    }
+}
+
+#[test]
+fn reply_keylist() {
+    assert_roundtrip(Reply::Keylist(vec![]));
+    assert_roundtrip(Reply::Keylist(vec![account_info(), account_info()]));
+}
+
+#[test]
+fn reply_account_info() {
+    assert_roundtrip(Reply::AccountInfo(account_info()));
+}
+
+#[test]
+#[cfg(feature = "export-secrets")]
+fn reply_xpriv() {
+    assert_roundtrip(Reply::XPriv(xpriv()));
+}
+
+#[test]
+fn reply_xpub() {
+    assert_roundtrip(Reply::XPub(ExtendedPubKey::from_private(
+        &keyring::SECP256K1,
+        &xpriv(),
+    )));
+}
+
+#[test]
+fn reply_signature() {
+    let signature = keyring::SECP256K1.sign(
+        &secp256k1::Message::from_slice(&[0x01u8; 32]).unwrap(),
+        &secp256k1::key::ONE_KEY,
+    );
+    assert_roundtrip(Reply::Signature(signature));
+}
+
+#[test]
+fn reply_psbt() {
+    let psbt: PartiallySignedTransaction =
+        deserialize(include_bytes!("../sample/signed.psbt")).unwrap();
+    assert_roundtrip(Reply::Psbt(psbt));
+}
+
+#[test]
+fn reply_transaction() {
+    assert_roundtrip(Reply::Transaction(psbt().extract_tx()));
+}
+
+#[test]
+fn reply_status() {
+    let mut data = sha256::Hash::hash(b"config").to_vec();
+    data.extend(strict_serialize(&keyring::rpc::PROTOCOL_VERSION).unwrap());
+    data.extend(strict_serialize(&3600u64).unwrap());
+    data.extend(strict_serialize(&"File".to_string()).unwrap());
+    data.extend(strict_serialize(&2u32).unwrap());
+    data.extend(strict_serialize(&5u32).unwrap());
+    data.extend(strict_serialize(&true).unwrap());
+    let status: Status = strict_deserialize(&data).unwrap();
+    assert_eq!(status.accounts, 5);
+    assert!(status.locked);
+    assert_roundtrip(Reply::Status(status));
+}
+
+#[test]
+fn reply_attestation() {
+    let node_id = secp256k1::PublicKey::from_secret_key(
+        &keyring::SECP256K1,
+        &secp256k1::key::ONE_KEY,
+    );
+    let nonce = sha256::Hash::hash(b"nonce");
+    let config_fingerprint = sha256::Hash::hash(b"config");
+    let attestation = Attestation {
+        platform: "sgx".to_string(),
+        config_fingerprint,
+        report_data: Attestation::report_data(
+            &node_id,
+            nonce,
+            config_fingerprint,
+        )
+        .to_vec(),
+        quote: vec![0xA5u8; 4096],
+    };
+    assert!(attestation.is_bound(&node_id, nonce));
+    assert!(!attestation.is_bound(&node_id, sha256::Hash::hash(b"replay")));
+    assert_roundtrip(Reply::Attestation(attestation));
+}
+
+#[test]
+fn reply_sealed() {
+    assert_roundtrip(Reply::Sealed(vec![]));
+    assert_roundtrip(Reply::Sealed(vec![0x5Au8; 1024]));
+}
+
+#[test]
+fn reply_tagged() {
+    assert_roundtrip(Reply::Tagged(TaggedReply {
+        request_id: u64::MAX,
+        payload: vec![],
+    }));
+    let reply = tagged::tag_reply(&Reply::Success, 7);
+    assert_roundtrip(reply.clone());
+    match reply {
+        Reply::Tagged(tagged) => {
+            assert_eq!(tagged.request_id, 7);
+            match tagged::open_reply(&tagged).unwrap() {
+                Reply::Success => {}
+                reply => panic!("tagged reply is changed: {}", reply),
+            }
+        }
+        reply => panic!("reply is not tagged: {}", reply),
+    }
+}
+
+#[test]
+fn reply_hello() {
+    for features in &[
+        Features::default(),
+        Features::with(Feature::ALL.iter().copied()),
+    ] {
+        assert_roundtrip(Reply::Hello(Hello {
+            server_version: keyring::rpc::PROTOCOL_VERSION,
+            min_version: keyring::rpc::MIN_PROTOCOL_VERSION,
+            features: *features,
+        }));
+    }
+}
+
+#[test]
+fn features() {
+    let features = Features::with(vec![Feature::Sealing, Feature::Taproot]);
+    assert!(features.contains(Feature::Taproot));
+    assert!(!features.contains(Feature::Grpc));
+    assert_eq!(features.to_string(), "taproot, sealing");
+    assert_eq!(
+        strict_serialize(&features).unwrap(),
+        vec![0x21, 0, 0, 0, 0, 0, 0, 0]
+    );
+
+    // Features unknown to this version are preserved
+    let unknown: Features =
+        strict_deserialize(&[0x01, 0, 0, 0, 0, 0, 0, 0x80]).unwrap();
+    assert_eq!(unknown.iter().collect::<Vec<_>>(), vec![Feature::Taproot]);
+    assert_eq!(strict_serialize(&unknown).unwrap()[7], 0x80);
+}
+
+#[test]
+fn reply_job_progress() {
+    for (done, total, elapsed) in &[(0, 0, 0), (7, 100, 1500), (1, 1, 20)] {
+        assert_roundtrip(Reply::JobProgress(JobProgress {
+            job: sha256::Hash::hash(b"job"),
+            operation: "sign_psbt".to_string(),
+            done: *done,
+            total: *total,
+            elapsed: *elapsed,
+        }));
+    }
+}
+
+#[test]
+fn reply_balance_list() {
+    assert_roundtrip(Reply::BalanceList(vec![]));
+    let mut data = strict_serialize(&account_info()).unwrap();
+    data.extend(strict_serialize(&u32::MAX).unwrap());
+    data.extend(strict_serialize(&u64::MAX).unwrap());
+    data.extend(strict_serialize(&0u64).unwrap());
+    let balance: AccountBalance = strict_deserialize(&data).unwrap();
+    assert_eq!(balance.tx_count, u32::MAX);
+    assert_eq!(balance.received, u64::MAX);
+    assert_roundtrip(Reply::BalanceList(vec![balance.clone(), balance]));
+}
+
+#[test]
+fn reply_derived_keys() {
+    assert_roundtrip(Reply::DerivedKeys(vec![]));
+    let pubkey = bitcoin::PublicKey::from_private_key(
+        &keyring::SECP256K1,
+        &bitcoin::PrivateKey {
+            compressed: true,
+            network: bitcoin::Network::Testnet,
+            key: secp256k1::key::ONE_KEY,
+        },
+    );
+    let keys = derivation_paths()
+        .into_iter()
+        .zip(&[0u32, u32::MAX])
+        .map(|(path, index)| DerivedKey {
+            index: *index,
+            path,
+            pubkey,
+            address: if *index == 0 {
+                None
+            } else {
+                Some("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx".to_string())
+            },
+        })
+        .collect();
+    assert_roundtrip(Reply::DerivedKeys(keys));
+}
+
+#[test]
+fn reply_identity() {
+    for (index, path) in derivation_paths().into_iter().enumerate() {
+        let key = IdentityKey {
+            key_id: key_id(),
+            index: index as u32 * u32::MAX,
+            path,
+            pubkey: vec![0x5Au8; 32],
+        };
+        assert_roundtrip(Reply::IdentityKey(key.clone()));
+        assert_roundtrip(Reply::IdentitySignature(IdentitySignature {
+            key,
+            signature: vec![0xA5u8; 64],
+        }));
+    }
+}
+
+#[test]
+fn reply_musig() {
+    assert_roundtrip(Reply::MuSigSession(MuSigSession {
+        id: session_token(),
+        key_id: key_id(),
+        participant: encryption_key(),
+        aggregated_key: vec![0x5Au8; 32],
+        nonce: vec![0x02u8; 66],
+    }));
+    assert_roundtrip(Reply::MuSigNonce(vec![0x03u8; 66]));
+    for signature in &[None, Some(vec![0xA5u8; 64])] {
+        assert_roundtrip(Reply::MuSigSignature(MuSigSignature {
+            partial: vec![0x1Fu8; 32],
+            signature: signature.clone(),
+        }));
+    }
+}
+
+#[test]
+fn reply_descriptors() {
+    assert_roundtrip(Reply::Descriptors(vec![]));
+    assert_roundtrip(Reply::Descriptors(strings()));
+}
+
+#[test]
+fn reply_backup() {
+    for path in strings() {
+        assert_roundtrip(Reply::Backup(path));
+    }
+}
+
+#[test]
+#[cfg(feature = "export-secrets")]
+fn reply_derived_secret() {
+    for secret in strings() {
+        assert_roundtrip(Reply::DerivedSecret(secret));
+    }
+}
+
+#[test]
+fn reply_encoded_xpub() {
+    for key in strings() {
+        assert_roundtrip(Reply::EncodedXPub(key));
+    }
+}
+
+#[test]
+fn reply_ledger() {
+    let txid = psbt().global.unsigned_tx.txid();
+    assert_roundtrip(Reply::Ledger(vec![]));
+    assert_roundtrip(Reply::Ledger(
+        strings()
+            .into_iter()
+            .map(Some)
+            .chain(vec![None])
+            .map(|client| LedgerEntry {
+                txid,
+                timestamp: 1_600_000_000,
+                client,
+                spent: 100_000,
+                received: 49_000,
+                fee: Some(1_000),
+            })
+            .collect(),
+    ));
+    assert_roundtrip(Reply::Ledger(vec![LedgerEntry {
+        txid,
+        timestamp: 0,
+        client: None,
+        spent: u64::MAX,
+        received: 0,
+        fee: None,
+    }]));
+}
+
+#[test]
+fn reply_psbt_analysis() {
+    let psbt = psbt();
+    let tx = &psbt.global.unsigned_tx;
+    let inputs = tx
+        .input
+        .iter()
+        .enumerate()
+        .map(|(index, txin)| InputAnalysis {
+            index: index as u32,
+            outpoint: txin.previous_output,
+            amount: Some(100_000),
+            keyring: Some(key_id()),
+            derivation: Some(
+                DerivationPath::from_str("m/84'/1'/0'/0/5").unwrap(),
+            ),
+            taproot: false,
+            signable: true,
+            signed: index > 0,
+        })
+        .collect::<Vec<_>>();
+    assert_roundtrip(Reply::PsbtAnalysis(PsbtAnalysis {
+        txid: tx.txid(),
+        inputs,
+        input_total: Some(100_000),
+        output_total: 99_000,
+        fee: Some(1_000),
+        spent: 100_000,
+        received: 49_000,
+        issues: vec![],
+    }));
+    assert_roundtrip(Reply::PsbtAnalysis(PsbtAnalysis {
+        txid: tx.txid(),
+        inputs: vec![InputAnalysis {
+            index: u32::MAX,
+            outpoint: tx.input[0].previous_output,
+            amount: None,
+            keyring: None,
+            derivation: None,
+            taproot: true,
+            signable: false,
+            signed: false,
+        }],
+        input_total: None,
+        output_total: 0,
+        fee: None,
+        spent: 0,
+        received: 0,
+        issues: strings(),
+    }));
+}
+
+#[test]
+fn reply_vault() {
+    assert_roundtrip(Reply::Vault(vec![]));
+    assert_roundtrip(Reply::Vault(vec![0u8; 1024]));
+    assert_roundtrip(Reply::VaultDump(b"KRVD\x01\x00".to_vec()));
+}
+
+#[test]
+fn reply_vault_list() {
+    assert_roundtrip(Reply::VaultList(vec![]));
+    assert_roundtrip(Reply::VaultList(vec![
+        VaultInfo {
+            id: "default".to_string(),
+            driver: "file".to_string(),
+            chains: vec![],
+            keyrings: 0,
+            accounts: 0,
+        },
+        VaultInfo {
+            id: "mainnet".to_string(),
+            driver: "sqlite".to_string(),
+            chains: vec![Chain::Mainnet, Chain::Testnet3],
+            keyrings: u32::MAX,
+            accounts: u32::MAX,
+        },
+    ]));
+}
+
+#[test]
+fn reply_commitment_secret() {
+    assert_roundtrip(Reply::CommitmentSecret(sha256::Hash::hash(b"secret")));
+}
+
+#[test]
+fn reply_message_signature() {
+    assert_roundtrip(Reply::MessageSignature(vec![]));
+    assert_roundtrip(Reply::MessageSignature(vec![0x1Fu8; 65]));
+}
+
+#[test]
+fn request_no_payload() {
+    assert_request_roundtrip(Request::Challenge("client".to_owned()));
+    assert_request_roundtrip(Request::Status);
+    assert_request_roundtrip(Request::List);
+    assert_request_roundtrip(Request::ListMultisig);
+    assert_request_roundtrip(Request::ListVaults);
+}
+
+#[test]
+fn request_attest() {
+    assert_request_roundtrip(Request::Attest(message::Attest {
+        nonce: sha256::Hash::hash(b"nonce"),
+    }));
+}
+
+#[test]
+fn request_job_status() {
+    assert_request_roundtrip(Request::JobStatus(message::JobStatus {
+        job: sha256::Hash::hash(b"job"),
+    }));
+}
+
+#[test]
+fn request_sealed() {
+    for payload in &[vec![], vec![0xFFu8; u16::MAX as usize]] {
+        assert_request_roundtrip(Request::Sealed(message::Sealed {
+            payload: payload.clone(),
+            auth_code: AuthCode::default(),
+        }));
+    }
+}
+
+#[test]
+fn request_hello() {
+    let request = Request::Hello(message::Hello {
+        client_version: keyring::rpc::PROTOCOL_VERSION,
+        features: Features::with(vec![Feature::Sealing]),
+    });
+    assert!(request.is_read_only());
+    assert!(request.clone().auth_code_mut().is_none());
+    assert_request_roundtrip(request);
+}
+
+#[test]
+fn request_reconfigure() {
+    let mut request = Request::Reconfigure(message::Reconfigure {
+        auth_code: auth_code(),
+    });
+    assert!(request.is_read_only());
+    assert!(!request.has_secrets());
+    assert!(request.auth_code_mut().is_some());
+    assert_request_roundtrip(request);
+}
+
+#[test]
+fn request_tagged() {
+    let request = tagged::tag(&Request::List, 0).unwrap();
+    assert_request_roundtrip(request.clone());
+    match request {
+        Request::Tagged(ref message) => {
+            assert_eq!(message.request_id, 0);
+            assert_eq!(tagged::open(message).unwrap().get_type(), 0x0010);
+        }
+        _ => panic!("request is not tagged"),
+    }
+    assert!(request.is_read_only());
+    assert!(!request.has_secrets());
+    assert!(matches!(
+        tagged::tag(&request, 1),
+        Err(tagged::Error::Nested)
+    ));
+    assert_request_roundtrip(Request::Tagged(message::Tagged {
+        request_id: u64::MAX,
+        payload: vec![0xFFu8; 256],
+        auth_code: auth_code(),
+    }));
+}
+
+#[test]
+fn request_routed() {
+    let request = routed::route(&Request::List, "testnet".to_string()).unwrap();
+    assert_request_roundtrip(request.clone());
+    match request {
+        Request::Routed(ref message) => {
+            assert_eq!(message.vault_id, "testnet");
+            assert_eq!(routed::open(message).unwrap().get_type(), 0x0010);
+        }
+        _ => panic!("request is not routed"),
+    }
+    assert!(request.is_read_only());
+    assert!(!request.has_secrets());
+
+    // Routed requests may be tagged, but not the other way round
+    let tagged = tagged::tag(&request, 1).unwrap();
+    assert!(matches!(
+        routed::route(&tagged, "testnet".to_string()),
+        Err(routed::Error::Nested)
+    ));
+    assert!(matches!(
+        routed::route(&request, "mainnet".to_string()),
+        Err(routed::Error::Nested)
+    ));
+    assert_request_roundtrip(Request::Routed(message::Routed {
+        vault_id: String::new(),
+        payload: vec![0xFFu8; 256],
+        auth_code: auth_code(),
+    }));
+}
+
+#[test]
+fn request_session() {
+    for passphrase in strings() {
+        for decryption_key in secret_keys() {
+            assert_request_roundtrip(Request::Unlock(message::Unlock {
+                passphrase: passphrase.clone(),
+                decryption_key,
+                auth_code: auth_code(),
+            }));
+        }
+    }
+    assert_request_roundtrip(Request::Lock(message::Lock {
+        session: session_token(),
+        auth_code: AuthCode::default(),
+    }));
+}
+
+#[test]
+fn request_list_with_balances() {
+    for gap_limit in &[0, u32::MAX] {
+        assert_request_roundtrip(Request::ListWithBalances(message::Scan {
+            gap_limit: *gap_limit,
+        }));
+    }
+}
+
+#[test]
+fn request_find_accounts() {
+    assert_request_roundtrip(Request::FindAccounts(AccountQuery::default()));
+    assert_request_roundtrip(Request::FindAccounts(AccountQuery {
+        labels: vec![
+            LabelQuery::from_str("team").unwrap(),
+            LabelQuery::from_str("env=prod").unwrap(),
+        ],
+        asset: Some(AssetId::from_inner([0xFFu8; 32])),
+        application: Some(KeyApplication::SegWit),
+        fingerprint: Some(Fingerprint::default()),
+        chain: Some(Chain::Testnet3),
+    }));
+}
+
+#[test]
+fn request_seed() {
+    for name in strings() {
+        for description in &[None, Some(name.clone())] {
+            assert_request_roundtrip(Request::Seed(message::Seed {
+                name: name.clone(),
+                chain: Chain::Mainnet,
+                application: KeyApplication::SegWit,
+                description: description.clone(),
+                idempotency_key: None,
+                auth_code: AuthCode::default(),
+            }));
+        }
+    }
+    let request = Request::Seed(message::Seed {
+        name: String::new(),
+        chain: Chain::Testnet3,
+        application: KeyApplication::Nested,
+        description: None,
+        idempotency_key: Some(sha256::Hash::hash(b"retry")),
+        auth_code: auth_code(),
+    });
+    assert_eq!(
+        request.idempotency_key(),
+        Some(sha256::Hash::hash(b"retry"))
+    );
+    assert_request_roundtrip(request);
+}
+
+#[test]
+fn request_delete() {
+    for decryption_key in secret_keys() {
+        for session in &[None, Some(session_token())] {
+            for purge in &[false, true] {
+                let delete = message::Delete {
+                    key_id: key_id(),
+                    purge: *purge,
+                    decryption_key,
+                    session: *session,
+                    auth_code: AuthCode::default(),
+                };
+                assert_request_roundtrip(Request::DeleteKeyring(
+                    delete.clone(),
+                ));
+                assert_request_roundtrip(Request::DeleteAccount(delete));
+            }
+        }
+    }
+}
+
+#[test]
+fn request_import() {
+    assert_request_roundtrip(Request::ImportDescriptors(
+        message::ImportDescriptors {
+            descriptors: vec![],
+            collision: CollisionPolicy::Skip,
+            auth_code: AuthCode::default(),
+        },
+    ));
+    assert_request_roundtrip(Request::ImportDescriptors(
+        message::ImportDescriptors {
+            descriptors: strings(),
+            collision: CollisionPolicy::Reject,
+            auth_code: auth_code(),
+        },
+    ));
+    let xpubkey = ExtendedPubKey::from_private(&keyring::SECP256K1, &xpriv());
+    for key_source in key_sources() {
+        for application in &[None, Some(KeyApplication::SegWitMultisig)] {
+            for details in &[None, Some(String::new())] {
+                assert_request_roundtrip(Request::ImportXpub(
+                    message::ImportXpub {
+                        xpubkey,
+                        key_source: key_source.clone(),
+                        application: *application,
+                        name: "Watch-only".to_string(),
+                        details: details.clone(),
+                        collision: CollisionPolicy::Alias,
+                        auth_code: AuthCode::default(),
+                    },
+                ));
+                assert_request_roundtrip(Request::ImportXpriv(
+                    message::ImportXpriv {
+                        xprivkey: xpriv(),
+                        key_source: key_source.clone(),
+                        application: *application,
+                        name: String::new(),
+                        details: details.clone(),
+                        collision: CollisionPolicy::Merge,
+                        auth_code: auth_code(),
+                    },
+                ));
+            }
+        }
+    }
+}
+
+#[test]
+fn request_export() {
+    for decryption_key in secret_keys() {
+        for session in &[None, Some(session_token())] {
+            let export = message::Export {
+                key_id: key_id(),
+                slip132: None,
+                decryption_key,
+                session: *session,
+                auth_code: AuthCode::default(),
+            };
+            assert_request_roundtrip(Request::ExportXpub(export.clone()));
+            assert_request_roundtrip(Request::ExportDescriptor(export.clone()));
+            assert_request_roundtrip(Request::ExportXpub(message::Export {
+                slip132: Some(KeyPrefix::Vpub),
+                ..export
+            }));
+            assert_request_roundtrip(Request::ExportXpriv(
+                message::ExportXpriv {
+                    key_id: key_id(),
+                    approval: sha256::Hash::hash(b"approval"),
+                    decryption_key,
+                    session: *session,
+                    auth_code: AuthCode::default(),
+                },
+            ));
+        }
+    }
+    assert_request_roundtrip(Request::ApproveExport(message::ApproveExport {
+        key_id: key_id(),
+        auth_code: auth_code(),
+    }));
+}
+
+#[test]
+fn request_identity() {
+    for index in &[0, u32::MAX] {
+        for session in &[None, Some(session_token())] {
+            assert_request_roundtrip(Request::IdentityKey(
+                message::IdentityKey {
+                    key_id: key_id(),
+                    index: *index,
+                    decryption_key: secp256k1::key::ONE_KEY,
+                    session: *session,
+                    auth_code: AuthCode::default(),
+                },
+            ));
+            assert_request_roundtrip(Request::SignIdentity(
+                message::SignIdentity {
+                    key_id: key_id(),
+                    index: *index,
+                    digest: sha256::Hash::hash(b"event"),
+                    decryption_key: secp256k1::key::ONE_KEY,
+                    session: *session,
+                    auth_code: auth_code(),
+                },
+            ));
+        }
+    }
+}
+
+#[test]
+fn request_derive_entropy() {
+    for application in &[
+        Bip85Application::Mnemonic {
+            language: 8,
+            words: 24,
+        },
+        Bip85Application::Wif,
+        Bip85Application::Xprv,
+        Bip85Application::Hex { bytes: 64 },
+    ] {
+        for index in &[0, u32::MAX] {
+            assert_request_roundtrip(Request::DeriveEntropy(
+                message::DeriveEntropy {
+                    key_id: key_id(),
+                    application: *application,
+                    index: *index,
+                    decryption_key: secp256k1::key::ONE_KEY,
+                    session: Some(session_token()),
+                    auth_code: AuthCode::default(),
+                },
+            ));
+        }
+    }
+}
+
+#[test]
+fn request_derive() {
+    let assets = vec![
+        HashSet::new(),
+        [
+            AssetId::from_inner([0u8; 32]),
+            AssetId::from_inner([0xFFu8; 32]),
+        ]
+        .iter()
+        .cloned()
+        .collect(),
+    ];
+    for path in derivation_paths() {
+        for assets in &assets {
+            for sandbox in &[false, true] {
+                assert_request_roundtrip(Request::Derive(message::Derive {
+                    from: key_id(),
+                    path: path.clone(),
+                    name: "Derived".to_string(),
+                    details: String::new(),
+                    assets: assets.clone(),
+                    sandbox: *sandbox,
+                    idempotency_key: if *sandbox {
+                        None
+                    } else {
+                        Some(sha256::Hash::hash(b"derive"))
+                    },
+                    decryption_key: secp256k1::key::ONE_KEY,
+                    session: if *sandbox {
+                        Some(session_token())
+                    } else {
+                        None
+                    },
+                    auth_code: AuthCode::default(),
+                }));
+            }
+        }
+    }
+}
+
+#[test]
+fn request_sandbox() {
+    let sandbox = message::Sandbox {
+        session: session_token(),
+        auth_code: auth_code(),
+    };
+    assert_request_roundtrip(Request::CommitSandbox(sandbox.clone()));
+    assert_request_roundtrip(Request::DiscardSandbox(sandbox));
+}
+
+#[test]
+fn request_backup() {
+    for job in &[None, Some(sha256::Hash::hash(b"job"))] {
+        assert_request_roundtrip(Request::Backup(message::Backup {
+            job: *job,
+            auth_code: auth_code(),
+        }));
+        for snapshot in &[vec![], vec![0u8; 1024]] {
+            assert_request_roundtrip(Request::Restore(message::Restore {
+                snapshot: snapshot.clone(),
+                job: *job,
+                auth_code: AuthCode::default(),
+            }));
+        }
+    }
+}
+
+#[test]
+fn request_export_ledger() {
+    for since in &[0u64, 1_600_000_000, u64::MAX] {
+        assert_request_roundtrip(Request::ExportLedger(
+            message::ExportLedger {
+                since: *since,
+                auth_code: auth_code(),
+            },
+        ));
+    }
+}
+
+#[test]
+fn request_vault_federation() {
+    assert_request_roundtrip(Request::LoadVault(message::LoadVault {
+        auth_code: auth_code(),
+    }));
+    for data in &[vec![], vec![0xFFu8; 1024]] {
+        assert_request_roundtrip(Request::StoreVault(message::StoreVault {
+            data: data.clone(),
+            auth_code: AuthCode::default(),
+        }));
+    }
+}
+
+#[test]
+fn request_vault_dump() {
+    for passphrase in &[None, Some("correct horse battery staple".to_owned())] {
+        assert_request_roundtrip(Request::ExportVault(message::ExportVault {
+            passphrase: passphrase.clone(),
+            auth_code: auth_code(),
+        }));
+        for replace in &[false, true] {
+            assert_request_roundtrip(Request::ImportVault(
+                message::ImportVault {
+                    dump: vec![0xA5u8; 1024],
+                    passphrase: passphrase.clone(),
+                    replace: *replace,
+                    session: Some(session_token()),
+                    auth_code: AuthCode::default(),
+                },
+            ));
+        }
+    }
+}
+
+#[test]
+fn request_revocation() {
+    let channel = sha256::Hash::hash(b"channel");
+    for index in &[0u64, (1 << 48) - 1, u64::MAX] {
+        assert_request_roundtrip(Request::AppendRevocation(
+            message::AppendRevocation {
+                key_id: key_id(),
+                channel,
+                index: *index,
+                secret: sha256::Hash::hash(b"secret"),
+                auth_code: AuthCode::default(),
+            },
+        ));
+        assert_request_roundtrip(Request::QueryRevocation(
+            message::QueryRevocation {
+                key_id: key_id(),
+                channel,
+                index: *index,
+                auth_code: auth_code(),
+            },
+        ));
+    }
+    assert_request_roundtrip(Request::CompactRevocations(
+        message::CompactRevocations {
+            key_id: key_id(),
+            auth_code: AuthCode::default(),
+        },
+    ));
+}
+
+#[test]
+fn request_discover() {
+    for gap_limit in &[0u32, 20, u32::MAX] {
+        for session in &[None, Some(session_token())] {
+            for job in &[None, Some(sha256::Hash::hash(b"job"))] {
+                assert_request_roundtrip(Request::Discover(
+                    message::Discover {
+                        key_id: key_id(),
+                        gap_limit: *gap_limit,
+                        decryption_key: secp256k1::key::ONE_KEY,
+                        session: *session,
+                        job: *job,
+                        auth_code: auth_code(),
+                    },
+                ));
+            }
+        }
+    }
+}
+
+#[test]
+fn request_set_lifecycle() {
+    for state in &[
+        Lifecycle::Pending,
+        Lifecycle::Active,
+        Lifecycle::Retiring,
+        Lifecycle::Revoked,
+    ] {
+        assert_request_roundtrip(Request::SetLifecycle(
+            message::SetLifecycle {
+                key_id: key_id(),
+                state: *state,
+                auth_code: AuthCode::default(),
+            },
+        ));
+    }
+}
+
+#[test]
+fn request_derive_range() {
+    let templates = vec![
+        None,
+        Some(DerivationTemplate::from_str("*").unwrap()),
+        Some(DerivationTemplate::from_str("0/*").unwrap()),
+        Some(DerivationTemplate::from_str("2147483647/*/2147483647").unwrap()),
+    ];
+    for template in templates {
+        for (start, count) in &[(0, 0), (u32::MAX, u32::MAX)] {
+            assert_request_roundtrip(Request::DeriveRange(
+                message::DeriveRange {
+                    key_id: key_id(),
+                    template: template.clone(),
+                    internal: *start == 0,
+                    start: *start,
+                    count: *count,
+                    job: None,
+                    auth_code: AuthCode::default(),
+                },
+            ));
+        }
+    }
+}
+
+#[test]
+fn request_set_branches() {
+    for branches in &[
+        Branches::default(),
+        Branches {
+            external: u32::MAX,
+            internal: 0,
+            gap_limit: Some(u32::MAX),
+        },
+    ] {
+        assert_request_roundtrip(Request::SetBranches(message::SetBranches {
+            key_id: key_id(),
+            branches: *branches,
+            auth_code: AuthCode::default(),
+        }));
+    }
+}
+
+#[test]
+fn request_set_policy() {
+    let destination = bitcoin::Address::from_str(
+        "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx",
+    )
+    .unwrap()
+    .script_pubkey();
+    for policy in &[
+        SigningPolicy::default(),
+        SigningPolicy {
+            max_amount: Some(u64::MAX),
+            destinations: vec![destination],
+            sighash_types: vec![0x01, 0x83],
+            rate_limit: Some(RateLimit::from_str("10/3600").unwrap()),
+            allow_ecdh: true,
+        },
+    ] {
+        assert_request_roundtrip(Request::SetPolicy(message::SetPolicy {
+            key_id: key_id(),
+            policy: policy.clone(),
+            auth_code: AuthCode::default(),
+        }));
+    }
+    assert_eq!(
+        RateLimit::from_str("10/3600"),
+        Ok(RateLimit {
+            count: 10,
+            period: 3600
+        })
+    );
+    assert!(RateLimit::from_str("10").is_err());
+    assert!(RateLimit::from_str("10/0").is_err());
+}
+
+#[test]
+fn request_set_label() {
+    for value in &[None, Some("".to_string()), Some("prod".to_string())] {
+        assert_request_roundtrip(Request::SetLabel(message::SetLabel {
+            key_id: key_id(),
+            label: "env".to_string(),
+            value: value.clone(),
+            auth_code: AuthCode::default(),
+        }));
+    }
+}
+
+#[test]
+fn request_update_account() {
+    let assets = vec![
+        None,
+        Some(HashSet::new()),
+        Some(
+            [AssetId::from_inner([0xFFu8; 32])]
+                .iter()
+                .cloned()
+                .collect(),
+        ),
+    ];
+    let modes = [
+        UpdateMode::Add,
+        UpdateMode::Replace,
+        UpdateMode::RemoveIgnore,
+        UpdateMode::RemoveOrFail,
+    ];
+    for mode in &modes {
+        assert_eq!(UpdateMode::from_str(&mode.to_string()).unwrap(), *mode);
+        for assets in &assets {
+            assert_request_roundtrip(Request::UpdateAccount(
+                message::UpdateAccount {
+                    key_id: key_id(),
+                    name: Some("Savings".to_string()),
+                    details: None,
+                    assets: assets.clone(),
+                    mode: *mode,
+                    auth_code: AuthCode::default(),
+                },
+            ));
+        }
+    }
+}
+
+fn multisig_group() -> MultisigGroup {
+    let xpub = ExtendedPubKey::from_private(&keyring::SECP256K1, &xpriv());
+    MultisigGroup {
+        name: "Treasury".to_string(),
+        threshold: 2,
+        cosigners: vec![
+            CosignerKey::master(xpub),
+            CosignerKey {
+                xpubkey: xpub,
+                origin: (
+                    Fingerprint::from(&[0xA5u8; 4][..]),
+                    DerivationPath::from_str("m/48'/1'/0'/2'").unwrap(),
+                ),
+            },
+        ],
+    }
+}
+
+#[test]
+fn request_multisig() {
+    let group = multisig_group();
+    for accounts in &[vec![], vec![key_id()]] {
+        assert_request_roundtrip(Request::CreateMultisig(
+            message::CreateMultisig {
+                name: group.name.clone(),
+                threshold: group.threshold,
+                accounts: accounts.clone(),
+                cosigners: group.cosigners.clone(),
+                auth_code: AuthCode::default(),
+            },
+        ));
+    }
+    assert_request_roundtrip(Request::ExportMultisig(
+        message::ExportMultisig {
+            group: group.id(),
+            auth_code: AuthCode::default(),
+        },
+    ));
+}
+
+#[test]
+fn reply_multisig() {
+    assert_roundtrip(Reply::Multisig(multisig_group()));
+    assert_roundtrip(Reply::MultisigGroups(vec![]));
+    assert_roundtrip(Reply::MultisigGroups(vec![multisig_group()]));
+}
+
+#[test]
+fn request_musig() {
+    for taproot in &[false, true] {
+        assert_request_roundtrip(Request::MuSigStartSession(
+            message::MuSigStartSession {
+                key_id: key_id(),
+                participants: vec![encryption_key(), encryption_key()],
+                message: sha256::Hash::hash(b"message"),
+                taproot: *taproot,
+                auth_code: AuthCode::default(),
+            },
+        ));
+    }
+    let nonce = MuSigNonce {
+        participant: encryption_key(),
+        nonce: vec![0x02u8; 66],
+    };
+    assert_eq!(MuSigNonce::from_str(&nonce.to_string()), Ok(nonce.clone()));
+    assert!(MuSigNonce::from_str("02aa").is_err());
+    assert_request_roundtrip(Request::MuSigNonceExchange(
+        message::MuSigNonceExchange {
+            id: session_token(),
+            nonces: vec![nonce],
+            auth_code: AuthCode::default(),
+        },
+    ));
+    for decryption_key in secret_keys() {
+        for partial_sigs in &[vec![], vec![vec![0x1Fu8; 32]]] {
+            assert_request_roundtrip(Request::MuSigPartialSign(
+                message::MuSigPartialSign {
+                    id: session_token(),
+                    partial_sigs: partial_sigs.clone(),
+                    decryption_key,
+                    session: Some(session_token()),
+                    auth_code: AuthCode::default(),
+                },
+            ));
+        }
+    }
+}
+
+#[test]
+fn request_sign() {
+    for decryption_key in secret_keys() {
+        for session in &[None, Some(session_token())] {
+            assert_request_roundtrip(Request::SignPsbt(message::SignPsbt {
+                psbt: psbt(),
+                force: session.is_some(),
+                decryption_key,
+                session: *session,
+                job: Some(sha256::Hash::hash(b"job")),
+                auth_code: AuthCode::default(),
+            }));
+            assert_request_roundtrip(Request::SignKey(message::SignKey {
+                key_id: key_id(),
+                decryption_key,
+                session: *session,
+                auth_code: AuthCode::default(),
+            }));
+            for data in &[vec![], vec![0xFFu8; u16::MAX as usize]] {
+                assert_request_roundtrip(Request::SignData(
+                    message::SignData {
+                        key_id: key_id(),
+                        data: data.clone(),
+                        decryption_key,
+                        session: *session,
+                        auth_code: auth_code(),
+                    },
+                ));
+            }
+            for text in strings() {
+                assert_request_roundtrip(Request::SignMessage(
+                    message::SignMessage {
+                        key_id: key_id(),
+                        message: text,
+                        decryption_key,
+                        session: *session,
+                        auth_code: AuthCode::default(),
+                    },
+                ));
+            }
+        }
+    }
+}
+
+#[test]
+fn request_analyze_psbt() {
+    assert_request_roundtrip(Request::AnalyzePsbt(message::AnalyzePsbt {
+        psbt: psbt(),
+        auth_code: AuthCode::default(),
+    }));
+}
+
+#[test]
+fn request_combine_psbt() {
+    assert_request_roundtrip(Request::CombinePsbt(message::CombinePsbt {
+        psbts: vec![psbt(), psbt()],
+    }));
+}
+
+#[test]
+fn request_finalize_psbt() {
+    for extract in &[false, true] {
+        assert_request_roundtrip(Request::FinalizePsbt(
+            message::FinalizePsbt {
+                psbt: psbt(),
+                extract: *extract,
+            },
+        ));
+    }
+}
+
+#[test]
+fn request_compose_psbt() {
+    let txid =
+        "e8b43025641eea4fd21190f01bd870ef90f1a8b199d8fc3376c5b62c0b1a179d";
+    let inputs = vec![
+        PsbtInput::from_str(&format!("{}:0:100000:{}:0/5", txid, key_id()))
+            .unwrap(),
+        PsbtInput::from_str(&format!(
+            "{}:4294967295:{}:{}:m",
+            txid,
+            u64::MAX,
+            key_id()
+        ))
+        .unwrap(),
+    ];
+    assert_eq!(
+        inputs[0].terminal,
+        DerivationPath::from_str("m/0/5").unwrap()
+    );
+    assert_eq!(inputs[1].terminal, DerivationPath::master());
+    let outputs = vec![
+        PsbtOutput::from_str("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx:0")
+            .unwrap(),
+        PsbtOutput::from_str("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2:99000")
+            .unwrap(),
+    ];
+    assert!(PsbtInput::from_str(&format!("{}:0:100000", txid)).is_err());
+    assert!(PsbtOutput::from_str("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2").is_err());
+    for (inputs, outputs) in &[
+        (vec![], vec![]),
+        (inputs.clone(), vec![]),
+        (inputs, outputs),
+    ] {
+        for lock_time in &[0u32, 500_000_000, u32::MAX] {
+            assert_request_roundtrip(Request::ComposePsbt(
+                message::ComposePsbt {
+                    inputs: inputs.clone(),
+                    outputs: outputs.clone(),
+                    lock_time: *lock_time,
+                    auth_code: auth_code(),
+                },
+            ));
+        }
+    }
+}
+
+#[test]
+fn request_auth_code_is_preserved() {
+    let mut request = Request::Lock(message::Lock {
+        session: session_token(),
+        auth_code: AuthCode::default(),
+    });
+    *request.auth_code_mut().unwrap() = auth_code();
+    let decoded = Request::create_unmarshaller()
+        .unmarshall(&request.serialize())
+        .unwrap();
+    let mut decoded = (&*decoded).clone();
+    assert_eq!(decoded.auth_code_mut().cloned(), Some(auth_code()));
+}
+
+#[test]
+fn auth_code_commits_to_client_and_challenge() {
+    let secret = b"client secret";
+    let mut request = Request::Lock(message::Lock {
+        session: session_token(),
+        auth_code: AuthCode::default(),
+    });
+    let challenge = sha256::Hash::hash(b"challenge");
+    request.authorize(secret, AuthCode::with_challenge("alice", challenge));
+    let auth_code = request.auth_code_mut().cloned().unwrap();
+    assert_eq!(auth_code.client, "alice");
+    assert_eq!(auth_code.challenge, challenge);
+    assert_eq!(auth_code.hmac, request.compute_auth_hmac(secret));
+
+    let mut renamed = request.clone();
+    renamed.auth_code_mut().unwrap().client = "bob".to_owned();
+    assert_ne!(renamed.compute_auth_hmac(secret), auth_code.hmac);
+    let mut rechallenged = request.clone();
+    rechallenged.auth_code_mut().unwrap().challenge =
+        sha256::Hash::hash(b"other challenge");
+    assert_ne!(rechallenged.compute_auth_hmac(secret), auth_code.hmac);
+    assert_ne!(request.compute_auth_hmac(b"other secret"), auth_code.hmac);
+}
+
+#[test]
+fn timestamp_nonces_are_monotonic() {
+    let secret = b"client secret";
+    let mut nonces = NonceGenerator::new();
+    let mut request = Request::Lock(message::Lock {
+        session: session_token(),
+        auth_code: AuthCode::default(),
+    });
+    let first = nonces.authorize(&mut request, "alice", secret);
+    let auth_code = request.auth_code_mut().cloned().unwrap();
+    assert_eq!(auth_code.timestamp, Some(first));
+    assert_eq!(auth_code.challenge, timestamp_challenge(first));
+    assert_eq!(auth_code.hmac, request.compute_auth_hmac(secret));
+    let second = nonces.next();
+    assert!(second > first);
+    assert_ne!(timestamp_challenge(first), timestamp_challenge(second));
+}
+
+#[test]
+fn client_redaction() {
+    let mut info = account_info();
+    info.details = Some("Treasury".to_string());
+    info.aliases = vec!["Cold storage".to_string()];
+    info.labels.insert("env".to_string(), "prod".to_string());
+    let client = ClientConfig {
+        secret: b"client secret".to_vec(),
+        redact: vec![
+            AccountField::Name,
+            AccountField::Details,
+            AccountField::Labels,
+        ]
+        .into_iter()
+        .collect(),
+        admin: false,
+    };
+    let redacted = match client.redact(Reply::Keylist(vec![info.clone()])) {
+        Reply::Keylist(list) => list[0].clone(),
+        reply => panic!("unexpected reply {}", reply),
+    };
+    assert_eq!(redacted.name, "");
+    assert!(redacted.aliases.is_empty());
+    assert_eq!(redacted.details, None);
+    assert!(redacted.labels.is_empty());
+    assert_eq!(redacted.id, info.id);
+    assert_eq!(redacted.lifecycle, info.lifecycle);
+
+    let client = ClientConfig {
+        redact: Default::default(),
+        ..client
+    };
+    match client.redact(Reply::AccountInfo(info.clone())) {
+        Reply::AccountInfo(unchanged) => assert_eq!(unchanged, info),
+        reply => panic!("unexpected reply {}", reply),
+    }
+}
+
+#[test]
+fn unauthenticated_redaction() {
+    let mut info = account_info();
+    info.details = Some("Treasury".to_string());
+    info.labels.insert("env".to_string(), "prod".to_string());
+    let client = ClientConfig {
+        secret: b"client secret".to_vec(),
+        redact: Default::default(),
+        admin: false,
+    };
+
+    let authenticator = Authenticator::with(Default::default());
+    match authenticator.redact(Reply::AccountInfo(info.clone()), None) {
+        Reply::AccountInfo(unchanged) => assert_eq!(unchanged, info),
+        reply => panic!("unexpected reply {}", reply),
+    }
+
+    let authenticator = Authenticator::with(
+        vec![("alice".to_owned(), client)].into_iter().collect(),
+    );
+    match authenticator.redact(Reply::AccountInfo(info.clone()), Some("alice"))
+    {
+        Reply::AccountInfo(unchanged) => assert_eq!(unchanged, info),
+        reply => panic!("unexpected reply {}", reply),
+    }
+    for client in &[None, Some("mallory")] {
+        let reply = Reply::Keylist(vec![info.clone()]);
+        let redacted = match authenticator.redact(reply, *client) {
+            Reply::Keylist(list) => list[0].clone(),
+            reply => panic!("unexpected reply {}", reply),
+        };
+        assert_eq!(redacted.name, "");
+        assert_eq!(redacted.details, None);
+        assert!(redacted.labels.is_empty());
+        assert_eq!(redacted.branches, Branches::default());
+        assert_eq!(redacted.policy, SigningPolicy::default());
+        assert_eq!(redacted.id, info.id);
+    }
+}
+
+#[test]
+fn authenticator_challenges() {
+    let secret = b"client secret".to_vec();
+    let client = ClientConfig {
+        secret: secret.clone(),
+        redact: Default::default(),
+        admin: false,
+    };
+    let mut authenticator = Authenticator::with(
+        vec![("alice".to_owned(), client)].into_iter().collect(),
+    );
+    assert!(authenticator.challenge("mallory").is_err());
+
+    let lock = Request::Lock(message::Lock {
+        session: session_token(),
+        auth_code: AuthCode::default(),
+    });
+    let challenge = authenticator.challenge("alice").unwrap();
+    let mut request = lock.clone();
+    request.authorize(&secret, AuthCode::with_challenge("mallory", challenge));
+    assert!(authenticator.authorize(&request).is_err());
+    request.authorize(
+        b"other secret",
+        AuthCode::with_challenge("alice", challenge),
+    );
+    assert!(authenticator.authorize(&request).is_err());
+    request.authorize(&secret, AuthCode::with_challenge("alice", challenge));
+    assert_eq!(
+        authenticator.authorize(&request).unwrap(),
+        Some("alice".to_owned())
+    );
+    // Challenges are single-use
+    assert!(authenticator.authorize(&request).is_err());
+
+    for _ in 0..MAX_CHALLENGES {
+        authenticator.challenge("alice").unwrap();
+    }
+    assert!(authenticator.challenge("alice").is_err());
+
+    let mut nonces = NonceGenerator::new();
+    let mut request = lock;
+    nonces.authorize(&mut request, "alice", &secret);
+    assert_eq!(
+        authenticator.authorize(&request).unwrap(),
+        Some("alice".to_owned())
+    );
+    // Timestamps can't be replayed
+    assert!(authenticator.authorize(&request).is_err());
+}
+
+fn payment_code() -> PaymentCode {
+    PaymentCode {
+        pubkey: encryption_key(),
+        chain_code: ChainCode::from(&[0x5Au8; 32][..]),
+    }
+}
+
+#[test]
+fn request_payment_code() {
+    for decryption_key in secret_keys() {
+        for (counterparty, contact) in &[
+            (None, None),
+            (Some(payment_code()), None),
+            (None, Some("Bob".to_string())),
+            (Some(payment_code()), Some("Bob".to_string())),
+        ] {
+            assert_request_roundtrip(Request::DerivePaymentCode(
+                message::DerivePaymentCode {
+                    key_id: key_id(),
+                    account: 0,
+                    counterparty: *counterparty,
+                    contact: contact.clone(),
+                    start: 0,
+                    count: 5,
+                    decryption_key,
+                    session: Some(session_token()),
+                    auth_code: AuthCode::default(),
+                },
+            ));
+        }
+    }
+}
+
+#[test]
+fn reply_payment_code() {
+    let code = payment_code();
+    assert_eq!(PaymentCode::from_str(&code.to_string()), Ok(code));
+    assert!(code.to_string().starts_with("PM8T"));
+    let mut contacts = BTreeMap::new();
+    contacts.insert("Bob".to_string(), code);
+    assert_roundtrip(Reply::PaymentCode(PaymentCodeInfo {
+        key_id: key_id(),
+        code,
+        notification_address: "1JDdmqFLhpzcUwPeinhJbUPw4Co3aWLyzW".to_string(),
+        contacts,
+        counterparty: Some(code),
+        payments: vec![PaymentKey {
+            direction: PaymentDirection::Receive,
+            index: 0,
+            pubkey: bitcoin::PublicKey {
+                compressed: true,
+                key: encryption_key(),
+            },
+            address: "141fi7TY3h936vRUKh1qfUZr8rSBuYbVBK".to_string(),
+        }],
+    }));
+}
+
+#[test]
+fn request_ln_key_set() {
+    for channel in &[0, u32::MAX] {
+        assert_request_roundtrip(Request::DeriveLnKeySet(
+            message::DeriveLnKeySet {
+                key_id: key_id(),
+                channel: *channel,
+                auth_code: AuthCode::default(),
+            },
+        ));
+    }
+}
+
+#[test]
+fn reply_ln_key_set() {
+    assert_roundtrip(Reply::LnKeySet(LnKeySet {
+        key_id: key_id(),
+        channel: 1,
+        node_id: encryption_key(),
+        funding_pubkey: encryption_key(),
+        revocation_basepoint: encryption_key(),
+        payment_basepoint: encryption_key(),
+        delayed_payment_basepoint: encryption_key(),
+        htlc_basepoint: encryption_key(),
+    }));
+}
+
+#[test]
+fn request_ecdh() {
+    for decryption_key in secret_keys() {
+        for session in &[None, Some(session_token())] {
+            assert_request_roundtrip(Request::Ecdh(message::Ecdh {
+                key_id: key_id(),
+                pubkey: encryption_key(),
+                decryption_key,
+                session: *session,
+                auth_code: AuthCode::default(),
+            }));
+        }
+    }
+}
+
+#[test]
+fn reply_shared_secret() {
+    assert_roundtrip(Reply::SharedSecret(sha256::Hash::hash(b"ecdh")));
+}
+
+#[test]
+fn request_sign_lightning() {
+    for decryption_key in secret_keys() {
+        assert_request_roundtrip(Request::SignInvoice(message::SignInvoice {
+            key_id: key_id(),
+            hrp: "lnbc2500u".to_string(),
+            data: vec![0, 31, 16, 7],
+            decryption_key,
+            session: Some(session_token()),
+            auth_code: AuthCode::default(),
+        }));
+        assert_request_roundtrip(Request::SignChannelAnnouncement(
+            message::SignChannelAnnouncement {
+                key_id: key_id(),
+                channel: 3,
+                announcement: vec![0x01, 0x00],
+                decryption_key,
+                session: None,
+                auth_code: AuthCode::default(),
+            },
+        ));
+        assert_request_roundtrip(Request::SignGossip(message::SignGossip {
+            key_id: key_id(),
+            message: vec![0x01, 0x02],
+            decryption_key,
+            session: None,
+            auth_code: AuthCode::default(),
+        }));
+    }
+}
+
+#[test]
+fn reply_lightning_signatures() {
+    assert_roundtrip(Reply::InvoiceSignature(vec![0u8; 65]));
+    let signature = keyring::SECP256K1.sign(
+        &secp256k1::Message::from_slice(&[0x01u8; 32]).unwrap(),
+        &secp256k1::SecretKey::from_slice(&[0x02u8; 32]).unwrap(),
+    );
+    assert_roundtrip(Reply::AnnouncementSignatures(AnnouncementSignatures {
+        node_signature: signature,
+        bitcoin_signature: signature,
+    }));
+}
@@ -0,0 +1,106 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(any(feature = "node", feature = "client"))]
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use internet2::TypedEnum;
+use keyring::crypto;
+use keyring::rpc::sealed::{self, Error};
+use keyring::rpc::types::AuthCode;
+use keyring::rpc::{message, Reply, Request};
+use keyring::SECP256K1;
+
+fn key(byte: u8) -> (SecretKey, PublicKey) {
+    let sk = SecretKey::from_slice(&[byte; 32]).unwrap();
+    (sk, PublicKey::from_secret_key(&SECP256K1, &sk))
+}
+
+fn unlock() -> Request {
+    Request::Unlock(message::Unlock {
+        passphrase: "correct horse battery staple".to_string(),
+        decryption_key: key(0x11).0,
+        auth_code: AuthCode::default(),
+    })
+}
+
+#[test]
+fn seal_request() {
+    let (node_key, daemon_id) = key(0xA5);
+    let (_, reply_key) = key(0x5A);
+    let request = unlock();
+    let envelope = match sealed::seal(&request, daemon_id, reply_key).unwrap() {
+        Request::Sealed(envelope) => envelope,
+        request => panic!("request {} is not sealed", request),
+    };
+    assert!(!envelope
+        .payload
+        .windows(28)
+        .any(|window| window == &b"correct horse battery staple"[..]));
+
+    let (opened, opened_key) = sealed::open(&envelope, &node_key).unwrap();
+    assert_eq!(opened.serialize(), request.serialize());
+    assert_eq!(opened_key, reply_key);
+    assert!(sealed::open(&envelope, &key(0x42).0).is_err());
+}
+
+#[test]
+fn reject_nested() {
+    let (node_key, daemon_id) = key(0xA5);
+    let request = sealed::seal(&unlock(), daemon_id, daemon_id).unwrap();
+    assert!(matches!(
+        sealed::seal(&request, daemon_id, daemon_id),
+        Err(Error::Nested)
+    ));
+
+    // Nested request sealed manually
+    let mut data = daemon_id.serialize().to_vec();
+    data.extend(request.serialize());
+    let nested = message::Sealed {
+        payload: crypto::wrap(&data, daemon_id).unwrap(),
+        auth_code: AuthCode::default(),
+    };
+    assert!(matches!(
+        sealed::open(&nested, &node_key),
+        Err(Error::Nested)
+    ));
+}
+
+#[test]
+fn seal_reply() {
+    let (client_key, reply_key) = key(0x5A);
+    let reply = Reply::CommitmentSecret(sha256::Hash::hash(b"secret"));
+    let sealed = sealed::seal_reply(&reply, reply_key).unwrap();
+    let data = match sealed {
+        Reply::Sealed(ref data) => data,
+        ref reply => panic!("reply {} is not sealed", reply),
+    };
+    let opened = sealed::open_reply(data, &client_key).unwrap();
+    assert_eq!(opened.serialize(), reply.serialize());
+    assert!(sealed::open_reply(data, &key(0x42).0).is_err());
+}
+
+#[test]
+fn requests_with_secrets() {
+    assert!(unlock().has_secrets());
+    assert!(!Request::List.has_secrets());
+    assert!(!Request::JobStatus(message::JobStatus {
+        job: sha256::Hash::hash(b"job"),
+    })
+    .has_secrets());
+    let (_, daemon_id) = key(0xA5);
+    assert!(!sealed::seal(&unlock(), daemon_id, daemon_id)
+        .unwrap()
+        .has_secrets());
+}
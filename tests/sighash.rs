@@ -0,0 +1,252 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "node")]
+
+mod common;
+
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use bitcoin::hash_types::SigHash;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{self, Message, Signature};
+use bitcoin::util::bip143::SigHashCache;
+use bitcoin::util::bip32::DerivationPath;
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::{
+    OutPoint, PublicKey, Script, SigHashType, Transaction, TxIn, TxOut, Txid,
+};
+use keyring::lifecycle::{Lifecycle, Operation};
+use keyring::vault::{keymgm, Vault};
+use keyring::{RuntimeError, SECP256K1};
+
+use common::{decryption_key, xpriv};
+
+const SEED: u8 = 0x3C;
+const VALUE: u64 = 10_000;
+
+fn vault(name: &str) -> Vault {
+    common::vault(name, SEED, "Testnet keys").0
+}
+
+fn derivation() -> DerivationPath {
+    DerivationPath::from_str("m/84'/1'/0'/0/0").unwrap()
+}
+
+fn pubkey() -> PublicKey {
+    PublicKey {
+        compressed: true,
+        key: secp256k1::PublicKey::from_secret_key(
+            &SECP256K1,
+            &xpriv(SEED)
+                .derive_priv(&SECP256K1, &derivation())
+                .unwrap()
+                .private_key
+                .key,
+        ),
+    }
+}
+
+/// PSBT spending `previous_output` locked to the vault key
+fn psbt(previous_output: OutPoint) -> PartiallySignedTransaction {
+    let mut psbt = PartiallySignedTransaction::from_unsigned_tx(Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output,
+            script_sig: Script::new(),
+            sequence: 0xFFFF_FFFD,
+            witness: vec![],
+        }],
+        output: vec![TxOut {
+            value: VALUE - 1_000,
+            script_pubkey: Script::from(vec![0x6a]),
+        }],
+    })
+    .unwrap();
+    psbt.inputs[0].bip32_derivation.insert(
+        pubkey(),
+        (xpriv(SEED).fingerprint(&SECP256K1), derivation()),
+    );
+    psbt
+}
+
+/// PSBT spending SegWit output with a given `script_pubkey`
+fn segwit_psbt(script_pubkey: Script) -> PartiallySignedTransaction {
+    let mut psbt = psbt(OutPoint::new(Txid::from_inner([7u8; 32]), 0));
+    psbt.inputs[0].witness_utxo = Some(TxOut {
+        value: VALUE,
+        script_pubkey,
+    });
+    psbt
+}
+
+fn sign(
+    vault: &mut Vault,
+    psbt: PartiallySignedTransaction,
+) -> Result<PartiallySignedTransaction, RuntimeError> {
+    vault.sign_psbt(psbt, false, &mut decryption_key(), &mut || ())
+}
+
+/// BIP-143 signature hash of the first input with a given `script_code`
+fn bip143_sighash(
+    psbt: &PartiallySignedTransaction,
+    script_code: &Script,
+) -> SigHash {
+    SigHashCache::new(&psbt.global.unsigned_tx).signature_hash(
+        0,
+        script_code,
+        VALUE,
+        SigHashType::All,
+    )
+}
+
+/// Checks that the first input is signed by the vault key over `sig_hash`
+fn signs(psbt: &PartiallySignedTransaction, sig_hash: SigHash) -> bool {
+    let partial_sig = &psbt.inputs[0].partial_sigs[&pubkey()];
+    let (sighash_type, der) = partial_sig.split_last().unwrap();
+    assert_eq!(*sighash_type, SigHashType::All.as_u32() as u8);
+    SECP256K1
+        .verify(
+            &Message::from_slice(&sig_hash[..]).unwrap(),
+            &Signature::from_der(der).unwrap(),
+            &pubkey().key,
+        )
+        .is_ok()
+}
+
+#[test]
+fn p2wpkh() {
+    let mut vault = vault("sighash-p2wpkh");
+    let script_pubkey = Script::new_v0_wpkh(&pubkey().wpubkey_hash().unwrap());
+    let signed = sign(&mut vault, segwit_psbt(script_pubkey.clone())).unwrap();
+
+    let script_code = Script::new_p2pkh(&pubkey().pubkey_hash());
+    assert!(signs(&signed, bip143_sighash(&signed, &script_code)));
+    let legacy = signed.global.unsigned_tx.signature_hash(
+        0,
+        &script_pubkey,
+        SigHashType::All.as_u32(),
+    );
+    assert!(!signs(&signed, legacy));
+}
+
+#[test]
+fn p2sh_p2wpkh() {
+    let mut vault = vault("sighash-p2sh-p2wpkh");
+    let redeem_script = Script::new_v0_wpkh(&pubkey().wpubkey_hash().unwrap());
+    let mut psbt = segwit_psbt(Script::new_p2sh(&redeem_script.script_hash()));
+    psbt.inputs[0].redeem_script = Some(redeem_script);
+    let signed = sign(&mut vault, psbt).unwrap();
+
+    let script_code = Script::new_p2pkh(&pubkey().pubkey_hash());
+    assert!(signs(&signed, bip143_sighash(&signed, &script_code)));
+}
+
+#[test]
+fn p2wsh() {
+    let mut vault = vault("sighash-p2wsh");
+    let witness_script = Script::new_p2pk(&pubkey());
+    let psbt = segwit_psbt(Script::new_v0_wsh(&witness_script.wscript_hash()));
+
+    match sign(&mut vault, psbt.clone()) {
+        Err(RuntimeError::KeyManagement(keymgm::Error::PsbtInputData(0))) => {}
+        other => panic!("P2WSH input without witness script: {:?}", other),
+    }
+
+    let mut psbt = psbt;
+    psbt.inputs[0].witness_script = Some(witness_script.clone());
+    let signed = sign(&mut vault, psbt).unwrap();
+    assert!(signs(&signed, bip143_sighash(&signed, &witness_script)));
+}
+
+#[test]
+fn p2pkh() {
+    let mut vault = vault("sighash-p2pkh");
+    let script_pubkey = Script::new_p2pkh(&pubkey().pubkey_hash());
+    let prev_tx = Transaction {
+        version: 1,
+        lock_time: 0,
+        input: vec![],
+        output: vec![TxOut {
+            value: VALUE,
+            script_pubkey: script_pubkey.clone(),
+        }],
+    };
+    let mut psbt = psbt(OutPoint::new(prev_tx.txid(), 0));
+    psbt.inputs[0].non_witness_utxo = Some(prev_tx);
+    let signed = sign(&mut vault, psbt).unwrap();
+
+    let legacy = signed.global.unsigned_tx.signature_hash(
+        0,
+        &script_pubkey,
+        SigHashType::All.as_u32(),
+    );
+    assert!(signs(&signed, legacy));
+}
+
+#[test]
+fn archived_keyring() {
+    let mut vault = vault("sighash-archived");
+    let id = vault
+        .keyring_by_fingerprint(xpriv(SEED).fingerprint(&SECP256K1))
+        .unwrap()
+        .identifier();
+    vault
+        .delete_keyring(id, false, &mut decryption_key())
+        .unwrap();
+    assert!(vault
+        .keyring_by_fingerprint(xpriv(SEED).fingerprint(&SECP256K1))
+        .is_none());
+
+    let script_pubkey = Script::new_v0_wpkh(&pubkey().wpubkey_hash().unwrap());
+    let psbt = segwit_psbt(script_pubkey);
+    match vault.check_psbt_signers(&psbt) {
+        Err(RuntimeError::KeyManagement(keymgm::Error::NotFound)) => {}
+        other => panic!("PSBT signed by archived keyring: {:?}", other),
+    }
+    let signed = sign(&mut vault, psbt).unwrap();
+    assert!(signed.inputs[0].partial_sigs.is_empty());
+}
+
+#[test]
+fn revoked_account() {
+    let mut vault = vault("sighash-revoked");
+    let root = vault
+        .keyring_by_fingerprint(xpriv(SEED).fingerprint(&SECP256K1))
+        .unwrap()
+        .identifier();
+    let account = vault
+        .derive(
+            root,
+            DerivationPath::from_str("m/84'/1'/0'").unwrap(),
+            "Revoked account",
+            None::<String>,
+            HashSet::new(),
+            Some(&mut decryption_key()),
+        )
+        .unwrap();
+    vault.set_lifecycle(account.id, Lifecycle::Revoked).unwrap();
+
+    let script_pubkey = Script::new_v0_wpkh(&pubkey().wpubkey_hash().unwrap());
+    match sign(&mut vault, segwit_psbt(script_pubkey)) {
+        Err(RuntimeError::KeyManagement(
+            keymgm::Error::LifecycleRestriction(
+                Lifecycle::Revoked,
+                Operation::Sign,
+            ),
+        )) => {}
+        other => panic!("PSBT signed by revoked account: {:?}", other),
+    }
+}
@@ -0,0 +1,102 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "node")]
+
+mod common;
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use bitcoin::util::bip32::DerivationPath;
+use keyring::vault::session::Error;
+use keyring::vault::{Keyring, Sessions, SigningCache};
+use keyring::SECP256K1;
+
+use common::{decryption_key, encryption_key, xpriv};
+
+const SEED: u8 = 0x5A;
+
+fn keyring() -> Keyring {
+    Keyring::from_xpriv(
+        "Signing",
+        "",
+        None,
+        xpriv(SEED),
+        None,
+        encryption_key(),
+    )
+    .unwrap()
+}
+
+fn path(index: u32) -> DerivationPath {
+    DerivationPath::from_str(&format!("m/84'/1'/0'/0/{}", index)).unwrap()
+}
+
+#[test]
+fn reuse_hardened_key() {
+    let keyring = keyring();
+    let account = keyring.account_by_id(keyring.identifier()).unwrap();
+    let mut cache = SigningCache::with_capacity(8);
+    for index in 0..3 {
+        let key = *cache
+            .signing_key(account, &path(index), &decryption_key())
+            .unwrap()
+            .secret_key();
+        let expected =
+            xpriv(SEED).derive_priv(&SECP256K1, &path(index)).unwrap();
+        assert_eq!(key, expected.private_key.key);
+    }
+    assert_eq!(cache.decryptions(), 1);
+    assert_eq!(cache.len(), 4);
+    assert_eq!(cache.clear(), 4);
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn evict_least_recently_used() {
+    let keyring = keyring();
+    let account = keyring.account_by_id(keyring.identifier()).unwrap();
+    let mut cache = SigningCache::with_capacity(2);
+    cache
+        .signing_key(account, &path(0), &decryption_key())
+        .unwrap();
+    cache
+        .signing_key(account, &path(1), &decryption_key())
+        .unwrap();
+    cache
+        .signing_key(account, &path(0), &decryption_key())
+        .unwrap();
+    // Hardened key is used by each of the signing keys, so only the signing
+    // keys are evicted
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.decryptions(), 1);
+}
+
+#[test]
+fn wipe_on_lock() {
+    let mut sessions = Sessions::with(Duration::from_secs(60));
+    sessions.enable_signing_cache(4);
+    let token = sessions.unlock(decryption_key());
+    let cache = sessions.take_signing_cache(token).unwrap().unwrap();
+    assert!(sessions.take_signing_cache(token).unwrap().is_none());
+    sessions.restore_signing_cache(token, cache);
+    assert!(sessions.take_signing_cache(token).unwrap().is_some());
+
+    sessions.lock(token).unwrap();
+    assert_eq!(
+        sessions.take_signing_cache(token).unwrap_err(),
+        Error::UnknownSession(token)
+    );
+    sessions.restore_signing_cache(token, SigningCache::with_capacity(4));
+}
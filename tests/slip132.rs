@@ -0,0 +1,86 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "_rpc")]
+
+use std::str::FromStr;
+
+use bitcoin::util::base58;
+use bitcoin::util::bip32::{ExtendedPrivKey, ExtendedPubKey};
+use keyring::rpc::types::{KeyPrefix, KeyPrefixError};
+use keyring::SECP256K1;
+
+const PREFIXES: [&str; 10] = [
+    "xpub", "ypub", "zpub", "Ypub", "Zpub", "tpub", "upub", "vpub", "Upub",
+    "Vpub",
+];
+
+fn xpub(network: bitcoin::Network) -> ExtendedPubKey {
+    let xpriv = ExtendedPrivKey::new_master(network, &[0x42u8; 32]).unwrap();
+    ExtendedPubKey::from_private(&SECP256K1, &xpriv)
+}
+
+#[test]
+fn prefix_parsing() {
+    for prefix in PREFIXES.iter() {
+        assert_eq!(KeyPrefix::from_str(prefix).unwrap().to_string(), *prefix);
+    }
+    assert_eq!(
+        KeyPrefix::from_str("Zpub").unwrap(),
+        KeyPrefix::ZpubMultisig
+    );
+    assert_eq!(
+        KeyPrefix::from_str("ZPUB").unwrap_err(),
+        KeyPrefixError::Unknown("ZPUB".to_owned())
+    );
+}
+
+#[test]
+fn prefix_conversion() {
+    for prefix in PREFIXES.iter() {
+        let prefix = KeyPrefix::from_str(prefix).unwrap();
+        let network = if prefix.is_testnet() {
+            bitcoin::Network::Testnet
+        } else {
+            bitcoin::Network::Bitcoin
+        };
+        let key = xpub(network);
+        let encoded = prefix.encode(&key).unwrap();
+        assert!(encoded.starts_with(&prefix.to_string()));
+
+        let data = base58::from_check(&encoded).unwrap();
+        let original = base58::from_check(&key.to_string()).unwrap();
+        assert_eq!(data[..4], prefix.version());
+        assert_eq!(data[4..], original[4..]);
+    }
+    let key = xpub(bitcoin::Network::Testnet);
+    assert_eq!(KeyPrefix::Tpub.encode(&key).unwrap(), key.to_string());
+}
+
+#[test]
+fn prefix_network_mismatch() {
+    assert_eq!(
+        KeyPrefix::Zpub.encode(&xpub(bitcoin::Network::Testnet)),
+        Err(KeyPrefixError::Network(
+            KeyPrefix::Zpub,
+            bitcoin::Network::Testnet
+        ))
+    );
+    assert_eq!(
+        KeyPrefix::Vpub.encode(&xpub(bitcoin::Network::Bitcoin)),
+        Err(KeyPrefixError::Network(
+            KeyPrefix::Vpub,
+            bitcoin::Network::Bitcoin
+        ))
+    );
+}
@@ -0,0 +1,130 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "sqlite")]
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use bitcoin::secp256k1;
+use bitcoin::util::bip32::{DerivationPath, Fingerprint};
+use keyring::vault::{
+    file_driver, sqlite_driver, Driver, FileDriver, Keyring, SqliteDriver,
+};
+use lnpbp::Chain;
+use microservices::FileFormat;
+use slip132::KeyApplication;
+
+fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "keyring-{}-{}",
+        std::process::id(),
+        name
+    ));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn keyrings() -> Vec<Keyring> {
+    let mut decryption_key = secp256k1::key::ONE_KEY;
+    let encryption_key = secp256k1::PublicKey::from_secret_key(
+        &keyring::SECP256K1,
+        &decryption_key,
+    );
+    let mut master = Keyring::with(
+        "Master",
+        "Keyring with sub-accounts",
+        &Chain::Testnet3,
+        KeyApplication::SegWit,
+        None,
+        encryption_key,
+    )
+    .unwrap();
+    let (derivation, account) = master
+        .derive_account(
+            DerivationPath::from_str("m/84'/1'/0'").unwrap(),
+            "Savings",
+            Some("Derived account"),
+            HashSet::new(),
+            &mut decryption_key,
+        )
+        .unwrap();
+    master.add_account(derivation, account).unwrap();
+    let derived = Keyring::with(
+        "Derived",
+        "",
+        &Chain::Testnet3,
+        KeyApplication::Nested,
+        Some((
+            Fingerprint::from(&[0xA0u8, 0xB1, 0xC2, 0xD3][..]),
+            DerivationPath::from_str("m/49'/1'/0'").unwrap(),
+        )),
+        encryption_key,
+    )
+    .unwrap();
+    vec![master, derived]
+}
+
+#[test]
+fn store_and_load() {
+    let path = temp_path("store.sqlite");
+    let config = sqlite_driver::Config {
+        path: path.display().to_string(),
+        migrate_from: None,
+        read_only: false,
+    };
+    let mut driver = SqliteDriver::init(&config).unwrap();
+    assert!(driver.load().unwrap().is_empty());
+
+    let keyrings = keyrings();
+    driver.store(&keyrings).unwrap();
+    assert_eq!(driver.load().unwrap(), keyrings);
+
+    driver.store(&keyrings[1..].to_vec()).unwrap();
+    drop(driver);
+    let mut driver = SqliteDriver::init(&config).unwrap();
+    assert_eq!(driver.load().unwrap(), keyrings[1..].to_vec());
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn migrate_from_file() {
+    let file_path = temp_path("migrate.vault");
+    let path = temp_path("migrate.sqlite");
+    let keyrings = keyrings();
+    FileDriver::init(&file_driver::Config {
+        location: file_path.display().to_string(),
+        format: FileFormat::StrictEncode,
+        backups: 0,
+        signed: false,
+        node_key: None,
+        read_only: false,
+    })
+    .unwrap()
+    .store(&keyrings)
+    .unwrap();
+
+    let mut driver = SqliteDriver::init(&sqlite_driver::Config {
+        path: path.display().to_string(),
+        migrate_from: Some(file_path.display().to_string()),
+        read_only: false,
+    })
+    .unwrap();
+    assert_eq!(driver.load().unwrap(), keyrings);
+
+    fs::remove_file(file_path).unwrap();
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,155 @@
+// Keyring: private/public key managing service
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the AGPL License
+// along with this software.
+// If not, see <https://www.gnu.org/licenses/agpl-3.0-standalone.html>.
+
+#![cfg(feature = "node")]
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use bitcoin::util::bip32::DerivationPath;
+use bitcoin::XpubIdentifier;
+use keyring::rpc::types::{CollisionPolicy, UpdateMode};
+use keyring::vault::keymgm::Error;
+use keyring::RuntimeError;
+use lnpbp::chain::AssetId;
+
+use common::{decryption_key, encryption_key, open, path, xpriv};
+
+fn asset(byte: u8) -> AssetId {
+    AssetId::from_inner([byte; 32])
+}
+
+/// Creates vault with a keyring and a subaccount holding two assets,
+/// returning ids of the keyring and of the subaccount
+fn setup(path: &PathBuf) -> (XpubIdentifier, XpubIdentifier) {
+    let _ = fs::remove_file(path);
+    let mut vault = open(path);
+    let master = vault
+        .import_xpriv(
+            xpriv(9),
+            None,
+            None,
+            "Mastr",
+            None::<String>,
+            CollisionPolicy::Reject,
+            encryption_key(),
+        )
+        .unwrap()
+        .id;
+    let account = vault
+        .derive(
+            master,
+            DerivationPath::from_str("m/0'").unwrap(),
+            "Savngs",
+            None::<String>,
+            [asset(1), asset(2)].iter().cloned().collect(),
+            Some(&mut decryption_key()),
+        )
+        .unwrap()
+        .id;
+    (master, account)
+}
+
+#[test]
+fn rename() {
+    let path = path("update-rename");
+    let (master, account) = setup(&path);
+    let mut vault = open(&path);
+    let info = vault
+        .update_account(
+            master,
+            Some("Master"),
+            Some("Fixed typo"),
+            None,
+            UpdateMode::default(),
+        )
+        .unwrap();
+    assert_eq!(info.name, "Master");
+    vault
+        .update_account(
+            account,
+            Some("Savings"),
+            None::<String>,
+            None,
+            UpdateMode::default(),
+        )
+        .unwrap();
+
+    let vault = open(&path);
+    let accounts = vault.list().unwrap();
+    let names = accounts
+        .iter()
+        .map(|info| (info.id, info.name.as_str(), info.details.clone()))
+        .collect::<Vec<_>>();
+    assert!(names.contains(&(
+        master,
+        "Master",
+        Some("Fixed typo".to_string())
+    )));
+    assert!(names.contains(&(account, "Savings", None)));
+}
+
+#[test]
+fn assets() {
+    let path = path("update-assets");
+    let (master, account) = setup(&path);
+    let mut vault = open(&path);
+    let mut update = |assets: &[AssetId], mode| {
+        vault.update_account(
+            account,
+            None::<String>,
+            None::<String>,
+            Some(assets.iter().cloned().collect()),
+            mode,
+        )
+    };
+
+    let info = update(&[asset(3)], UpdateMode::Add).unwrap();
+    assert_eq!(info.assets.len(), 3);
+    let info = update(&[asset(3), asset(4)], UpdateMode::RemoveIgnore).unwrap();
+    assert_eq!(info.assets, [asset(1), asset(2)].iter().cloned().collect());
+    match update(&[asset(1), asset(4)], UpdateMode::RemoveOrFail) {
+        Err(RuntimeError::KeyManagement(Error::AssetIds(missing))) => {
+            assert_eq!(missing, [asset(4)].iter().cloned().collect())
+        }
+        other => panic!("missing asset is not reported: {:?}", other),
+    }
+    let info = update(&[asset(1)], UpdateMode::RemoveOrFail).unwrap();
+    assert_eq!(info.assets, [asset(2)].iter().cloned().collect());
+    let info = update(&[], UpdateMode::Replace).unwrap();
+    assert!(info.assets.is_empty());
+
+    match vault.update_account(
+        master,
+        None::<String>,
+        None::<String>,
+        Some([asset(1)].iter().cloned().collect()),
+        UpdateMode::Add,
+    ) {
+        Err(RuntimeError::KeyManagement(Error::MasterAccount)) => {}
+        other => panic!("master account assets are updated: {:?}", other),
+    }
+    match vault.update_account(
+        account,
+        None::<String>,
+        None::<String>,
+        None,
+        UpdateMode::Add,
+    ) {
+        Err(RuntimeError::KeyManagement(Error::NoOp)) => {}
+        other => panic!("empty update is accepted: {:?}", other),
+    }
+}